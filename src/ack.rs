@@ -0,0 +1,1414 @@
+#![forbid(unsafe_code)]
+//! # A work queue with acknowledgements and redelivery
+//!
+//! Values sent to the queue are handed out to one of the (cloneable)
+//! [`Receiver`]s as a [`Delivery`], which has to be acknowledged
+//! ([`Delivery::ack`]) once the value has been processed. A value is
+//! delivered again (to any receiver) if its delivery is
+//!
+//! - rejected with [`Delivery::nack`] or dropped without acknowledging it,
+//!   e.g. because the processing failed or panicked, or
+//! - not acknowledged within the timeout of the queue, e.g. because the
+//!   consumer hangs.
+//!
+//! So every value is processed at least once, but may be processed more than
+//! once if a consumer exceeds the timeout. [`Delivery::attempt`] tells how
+//! often a value has been delivered. Queues created by
+//! [`channel_with_dead_letters`] give up on values that keep failing: after a
+//! maximum number of deliveries, they're moved to the [`DeadLetters`] instead
+//! of being delivered again, so poison messages don't wedge the workers.
+//!
+//! The queue is unbounded and backed by a `std::sync::Mutex`. It doesn't
+//! contain any unsafe code and works with any async runtime. As the queue
+//! keeps every value until it's acknowledged, receivers get a clone of it, so
+//! `T` has to be `Clone` (wrap it in an `Arc` if it's expensive to clone).
+//!
+//! Once a delivery times out, the waiting receivers are woken up by a timer
+//! thread of the crate (or by the timeout of [`Receiver::recv_blocking`]), so
+//! the value is delivered again even if no other value is sent.
+//!
+//! ## Optional features
+//!
+//! - `futures`: Implements `futures_core::Stream` for the [`Receiver`].
+//!
+//! # Examples
+//! ```rust
+//! use std::time::Duration;
+//!
+//! let (tx, rx) = laika::ack::channel(Duration::from_secs(30));
+//!
+//! tx.send("job").unwrap();
+//!
+//! // Processing fails, so the value is redelivered
+//! let delivery = rx.try_recv().unwrap();
+//! assert_eq!((*delivery, delivery.attempt()), ("job", 1));
+//! delivery.nack();
+//!
+//! let delivery = rx.try_recv().unwrap();
+//! assert_eq!((*delivery, delivery.attempt()), ("job", 2));
+//! assert!(delivery.ack());
+//!
+//! drop(tx);
+//! assert!(rx.recv_blocking().is_none());
+//! ```
+
+mod error;
+
+pub use error::{SendError, TryRecvError};
+
+use crate::timer::{self, alarm::Alarm};
+use alloc::{
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    sync::{Arc, Weak},
+    task::Wake,
+    vec::Vec,
+};
+use core::{
+    fmt,
+    future::Future,
+    mem,
+    ops::{Deref, DerefMut},
+    pin::Pin,
+    task::{Context, Poll, Waker},
+    time::Duration,
+};
+use std::{
+    sync::{Condvar, Mutex, MutexGuard, PoisonError},
+    time::Instant,
+};
+
+/// Queued value
+#[derive(Debug)]
+struct Entry<T> {
+    /// The value itself
+    value: T,
+    /// Number of times the value has been delivered
+    attempts: u32,
+}
+
+/// Value that has been delivered but not acknowledged yet
+#[derive(Debug)]
+struct InFlight<T> {
+    /// The delivered value
+    entry: Entry<T>,
+    /// Time at which the value is redelivered if it hasn't been acknowledged,
+    /// [`None`] if the timeout is too long to be represented
+    deadline: Option<Instant>,
+}
+
+/// Queue and bookkeeping of a channel, guarded by [`Shared::state`]
+#[derive(Debug)]
+struct State<T> {
+    /// Values waiting to be delivered, oldest first
+    ready: VecDeque<Entry<T>>,
+    /// Delivered values that haven't been acknowledged yet, keyed by delivery
+    /// id
+    in_flight: BTreeMap<u64, InFlight<T>>,
+    /// Deadlines of the delivered values along with their delivery id,
+    /// earliest first, so timed out deliveries are found without going
+    /// through all of them
+    deadlines: BTreeSet<(Instant, u64)>,
+    /// Values that have been delivered `max_deliveries` times without being
+    /// acknowledged, oldest first
+    dead: VecDeque<T>,
+    /// Number of deliveries after which a value is moved to the dead letters
+    /// instead of being requeued
+    max_deliveries: u32,
+    /// Whether the [`DeadLetters`] still exist
+    dead_letters: bool,
+    /// Number of existing [`Sender`]s
+    senders: usize,
+    /// Number of existing [`Receiver`]s
+    receivers: usize,
+    /// Deadline the alarm has been scheduled for, so it's scheduled once per
+    /// deadline rather than once per delivery
+    armed: Option<Instant>,
+    /// Next id of a receiver, waiting future or delivery
+    next_id: u64,
+}
+
+/// Where a value goes whose delivery failed
+enum Requeued<T> {
+    /// Back into the queue, to be delivered again
+    Ready,
+    /// Into the dead letters, as it has been delivered too often
+    Dead,
+    /// Nowhere, as there are no receivers (or dead letters) for it
+    Discarded(T),
+}
+
+impl<T> State<T> {
+    /// Returns a new receiver, future or delivery id.
+    fn id(&mut self) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    /// Requeues a value whose delivery failed, or moves it to the dead
+    /// letters once it has been delivered `max_deliveries` times.
+    fn requeue(&mut self, entry: Entry<T>) -> Requeued<T> {
+        if self.receivers == 0 {
+            return Requeued::Discarded(entry.value);
+        }
+
+        if entry.attempts < self.max_deliveries {
+            self.ready.push_back(entry);
+            return Requeued::Ready;
+        }
+
+        if !self.dead_letters {
+            return Requeued::Discarded(entry.value);
+        }
+
+        self.dead.push_back(entry.value);
+        Requeued::Dead
+    }
+
+    /// Removes the delivered value with delivery `id` (and its deadline).
+    fn settle(&mut self, id: u64) -> Option<InFlight<T>> {
+        let in_flight = self.in_flight.remove(&id)?;
+
+        if let Some(deadline) = in_flight.deadline {
+            self.deadlines.remove(&(deadline, id));
+        }
+
+        Some(in_flight)
+    }
+
+    /// Requeues the values whose deliveries have timed out, in the order of
+    /// their deadlines. Returns whether values have been moved to the dead
+    /// letters and the values that have been discarded.
+    fn expire(&mut self, now: Instant) -> (bool, Vec<T>) {
+        let mut dead = false;
+        let mut discarded = Vec::new();
+
+        while let Some(&(deadline, id)) = self.deadlines.first() {
+            if deadline > now {
+                break;
+            }
+
+            let Some(in_flight) = self.settle(id) else {
+                unreachable!("Deadlines are removed along with their delivery");
+            };
+
+            match self.requeue(in_flight.entry) {
+                Requeued::Ready => {}
+                Requeued::Dead => dead = true,
+                Requeued::Discarded(value) => discarded.push(value),
+            }
+        }
+
+        (dead, discarded)
+    }
+
+    /// Returns the earliest deadline of a delivered value.
+    fn next_deadline(&self) -> Option<Instant> {
+        self.deadlines.first().map(|(deadline, _)| *deadline)
+    }
+
+    /// Returns whether no value is left and all senders have been dropped.
+    fn is_finished(&self) -> bool {
+        self.ready.is_empty() && self.in_flight.is_empty() && self.senders == 0
+    }
+
+    /// Takes the oldest dead letter.
+    fn pop_dead(&mut self) -> Result<T, TryRecvError> {
+        match self.dead.pop_front() {
+            Some(value) => Ok(value),
+            None if self.is_exhausted() => Err(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+
+    /// Returns whether no value is left and none can be sent anymore, so no
+    /// value can become a dead letter.
+    fn is_exhausted(&self) -> bool {
+        self.ready.is_empty()
+            && self.in_flight.is_empty()
+            && (self.senders == 0 || self.receivers == 0)
+    }
+}
+
+impl<T> State<T>
+where
+    T: Clone,
+{
+    /// Delivers the oldest ready value. Returns the delivery id, a clone of
+    /// the value and the attempt.
+    fn pop(&mut self, timeout: Duration) -> Result<(u64, T, u32), TryRecvError> {
+        let Some(mut entry) = self.ready.pop_front() else {
+            if self.is_finished() {
+                return Err(TryRecvError::Disconnected);
+            }
+
+            return Err(TryRecvError::Empty);
+        };
+
+        entry.attempts = entry.attempts.saturating_add(1);
+
+        let id = self.id();
+        let value = entry.value.clone();
+        let attempt = entry.attempts;
+
+        let deadline = Instant::now().checked_add(timeout);
+
+        if let Some(deadline) = deadline {
+            self.deadlines.insert((deadline, id));
+        }
+
+        self.in_flight.insert(id, InFlight { entry, deadline });
+
+        Ok((id, value, attempt))
+    }
+}
+
+/// Wakers of receivers (and the dead letters) waiting for a value, keyed by
+/// receiver or future id
+type Wakers = Mutex<BTreeMap<u64, Waker>>;
+
+/// State shared by all senders, receivers and deliveries of a queue
+#[derive(Debug)]
+struct Shared<T> {
+    /// Queue and bookkeeping
+    state: Mutex<State<T>>,
+    /// Wakers of the waiting receivers. Kept apart from the state, so the
+    /// alarm can wake them up without knowing the type of the values. Only
+    /// locked while the state is locked, except by the alarm.
+    wakers: Arc<Wakers>,
+    /// Notified when a value becomes ready or the queue is closed, so that
+    /// threads blocking in [`Receiver::recv_blocking`] wake up.
+    condvar: Condvar,
+    /// Notified when a value becomes a dead letter or no value can become one
+    /// anymore, so that a thread blocking in [`DeadLetters::recv_blocking`]
+    /// wakes up.
+    dead: Condvar,
+    /// Time after which an unacknowledged delivery is redelivered
+    timeout: Duration,
+    /// Alarm ringing when the earliest delivery times out
+    alarm: Arc<Alarm>,
+    /// Waker the alarm rings, waking up the waiting receivers so they
+    /// redeliver the timed out values
+    expire: Waker,
+}
+
+/// Waker ringing on [`Shared::alarm`]. Weak, so the timer doesn't keep the
+/// wakers of a dropped queue alive.
+struct Expire(Weak<Wakers>);
+
+/// Id of the receiver created together with a queue
+const RECEIVER_ID: u64 = 0;
+
+/// Id of the [`DeadLetters`] of a queue
+const DEAD_LETTERS_ID: u64 = 1;
+
+impl Wake for Expire {
+    fn wake(self: Arc<Self>) {
+        let Some(wakers) = self.0.upgrade() else {
+            return;
+        };
+
+        let woken = mem::take(&mut *crate::lock(&wakers));
+
+        for waker in woken.into_values() {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> Shared<T> {
+    /// Creates the state of a queue with one sender and one receiver.
+    fn new(timeout: Duration, max_deliveries: u32, dead_letters: bool) -> Arc<Self> {
+        let wakers = Arc::new(Mutex::new(BTreeMap::new()));
+        let expire = Waker::from(Arc::new(Expire(Arc::downgrade(&wakers))));
+
+        Arc::new(Shared {
+            state: Mutex::new(State {
+                ready: VecDeque::new(),
+                in_flight: BTreeMap::new(),
+                deadlines: BTreeSet::new(),
+                dead: VecDeque::new(),
+                max_deliveries,
+                dead_letters,
+                senders: 1,
+                receivers: 1,
+                armed: None,
+                next_id: DEAD_LETTERS_ID + 1,
+            }),
+            wakers,
+            condvar: Condvar::new(),
+            dead: Condvar::new(),
+            timeout,
+            alarm: Arc::new(Alarm::default()),
+            expire,
+        })
+    }
+
+    /// Locks the state.
+    fn lock(&self) -> MutexGuard<'_, State<T>> {
+        crate::lock(&self.state)
+    }
+
+    /// Locks the wakers, see [`Shared::lock`].
+    fn wakers(&self) -> MutexGuard<'_, BTreeMap<u64, Waker>> {
+        crate::lock(&self.wakers)
+    }
+
+    /// Lets the alarm ring when the earliest delivery in locked `state` times
+    /// out, unless it's going to ring before anyway.
+    fn arm(&self, state: &mut State<T>) {
+        let Some(deadline) = state.next_deadline() else {
+            return;
+        };
+
+        if state
+            .armed
+            .is_some_and(|armed| armed <= deadline && armed > Instant::now())
+        {
+            return;
+        }
+
+        state.armed = Some(deadline);
+        self.alarm.register(&self.expire);
+        timer::schedule(deadline, &self.alarm);
+    }
+
+    /// Registers the waker of `cx` under `id` while `state` is locked and
+    /// lets the alarm ring for the earliest delivery. If that one timed out
+    /// already, the alarm may have rung before the waker was registered, so
+    /// it's woken up right away instead.
+    fn register(&self, state: &mut State<T>, id: u64, cx: &Context<'_>) {
+        self.arm(state);
+
+        let mut wakers = self.wakers();
+
+        if !wakers
+            .get(&id)
+            .is_some_and(|waker| waker.will_wake(cx.waker()))
+        {
+            wakers.insert(id, cx.waker().clone());
+        }
+
+        drop(wakers);
+
+        if state
+            .next_deadline()
+            .is_some_and(|deadline| deadline <= Instant::now())
+        {
+            cx.waker().wake_by_ref();
+        }
+    }
+
+    /// Wakes up one waiting receiver after unlocking `state`, as a single
+    /// value became ready.
+    ///
+    /// A woken up receiver that's dropped before receiving passes the wake-up
+    /// on to the next one, see [`Shared::withdraw`].
+    fn wake_receiver(&self, state: MutexGuard<'_, State<T>>) {
+        let mut wakers = self.wakers();
+        let id = wakers.keys().copied().find(|&id| id != DEAD_LETTERS_ID);
+        let waker = id.and_then(|id| wakers.remove(&id));
+        drop(wakers);
+        drop(state);
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+
+        self.condvar.notify_one();
+    }
+
+    /// Wakes up the waiting [`DeadLetters`] after unlocking `state`, as a
+    /// value became a dead letter.
+    fn wake_dead_letters(&self, state: MutexGuard<'_, State<T>>) {
+        let waker = self.wakers().remove(&DEAD_LETTERS_ID);
+        drop(state);
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+
+        self.dead.notify_one();
+    }
+
+    /// Wakes up everyone waiting after unlocking `state`, as the queue has
+    /// been closed or exhausted, or deliveries have expired.
+    fn wake_all(&self, state: MutexGuard<'_, State<T>>) {
+        let wakers = mem::take(&mut *self.wakers());
+        drop(state);
+
+        for waker in wakers.into_values() {
+            waker.wake();
+        }
+
+        self.condvar.notify_all();
+        self.dead.notify_all();
+    }
+
+    /// Removes the waker of a receiver that stops waiting under `id`. If it
+    /// has been taken already, the receiver has been woken up for a value it
+    /// won't receive, so the next waiting receiver is woken up instead.
+    fn withdraw(&self, id: u64) {
+        let state = self.lock();
+
+        if self.wakers().remove(&id).is_none() && !state.ready.is_empty() {
+            self.wake_receiver(state);
+        }
+    }
+
+    /// Locks the state after requeuing the values of timed out deliveries. If
+    /// values have been moved to the dead letters, everyone waiting is woken
+    /// up (and the state is locked again).
+    fn lock_expired(&self) -> MutexGuard<'_, State<T>> {
+        let mut state = self.lock();
+        let (dead, discarded) = state.expire(Instant::now());
+
+        if !dead && discarded.is_empty() {
+            return state;
+        }
+
+        if dead {
+            self.wake_all(state);
+        } else {
+            drop(state);
+        }
+
+        // Values are dropped after unlocking, in case their `Drop` uses the
+        // queue.
+        drop(discarded);
+        self.lock()
+    }
+
+    /// Blocks the current thread until `condvar` is notified or the next
+    /// delivery times out.
+    fn wait<'a>(
+        &self,
+        condvar: &Condvar,
+        state: MutexGuard<'a, State<T>>,
+    ) -> MutexGuard<'a, State<T>> {
+        match state.next_deadline() {
+            Some(deadline) => {
+                let timeout = deadline.saturating_duration_since(Instant::now());
+
+                condvar
+                    .wait_timeout(state, timeout)
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .0
+            }
+            None => condvar.wait(state).unwrap_or_else(PoisonError::into_inner),
+        }
+    }
+}
+
+impl<T> Shared<T>
+where
+    T: Clone,
+{
+    /// Delivers the oldest ready value from locked `state`, letting the alarm
+    /// ring once the delivery times out.
+    fn deliver(&self, state: &mut State<T>) -> Result<(u64, T, u32), TryRecvError> {
+        let delivered = state.pop(self.timeout)?;
+        self.arm(state);
+
+        Ok(delivered)
+    }
+}
+
+/// Sending side of an acknowledged queue
+///
+/// Can be cloned to send from multiple producers. The queue is closed once
+/// all senders have been dropped.
+#[derive(Debug)]
+pub struct Sender<T> {
+    /// State shared with all other senders and receivers
+    shared: Arc<Shared<T>>,
+}
+
+/// Receiving side of an acknowledged queue
+///
+/// Can be cloned to process values with multiple workers. Every value is
+/// delivered to one receiver at a time.
+#[derive(Debug)]
+pub struct Receiver<T> {
+    /// State shared with all senders and other receivers
+    shared: Arc<Shared<T>>,
+    /// Id of this receiver, under which [`Receiver::poll_recv`] registers its
+    /// waker
+    id: u64,
+}
+
+/// Future returned by [`Receiver::recv`]
+///
+/// Resolves to the next ready value, or to [`None`] once all values have been
+/// acknowledged and all senders have been dropped.
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Recv<'a, T> {
+    /// Receiver the value is received with
+    receiver: &'a Receiver<T>,
+    /// Id of the waker once the future waits for a value
+    id: Option<u64>,
+}
+
+/// Value delivered by an acknowledged queue, dereferencing to the value
+///
+/// Has to be acknowledged with [`Delivery::ack`] once the value has been
+/// processed. Rejecting it with [`Delivery::nack`] or dropping it requeues
+/// the value, so it's delivered again.
+pub struct Delivery<T> {
+    /// State of the queue the value has been delivered by
+    shared: Arc<Shared<T>>,
+    /// Id of the delivery, under which the value is kept until it's
+    /// acknowledged
+    id: u64,
+    /// Clone of the delivered value
+    value: T,
+    /// Number of times the value has been delivered, including this delivery
+    attempt: u32,
+    /// Whether the delivery has been acknowledged or rejected already
+    settled: bool,
+}
+
+/// Receiver of the values that have been delivered too often
+///
+/// Created by [`channel_with_dead_letters`]. A value is moved here instead of
+/// being delivered again once its delivery failed `max_deliveries` times, so
+/// a value that can't be processed (a poison message) doesn't keep the
+/// workers busy forever. The dead letters can be inspected, logged or sent
+/// again later.
+///
+/// If the dead letters are dropped, such values are dropped as well.
+#[derive(Debug)]
+pub struct DeadLetters<T> {
+    /// State shared with the senders and receivers
+    shared: Arc<Shared<T>>,
+    /// Id under which the waker is registered while waiting
+    id: u64,
+}
+
+impl<T> Sender<T> {
+    /// Queues a value. This function is **non-blocking**.
+    ///
+    /// Returns the value in a [`SendError`] if all [`Receiver`]s have been
+    /// dropped.
+    pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+        let mut state = self.shared.lock();
+
+        if state.receivers == 0 {
+            return Err(SendError(value));
+        }
+
+        state.ready.push_back(Entry { value, attempts: 0 });
+        self.shared.wake_receiver(state);
+
+        Ok(())
+    }
+
+    /// Returns whether all [`Receiver`]s have been dropped, so sending would
+    /// fail.
+    pub fn is_closed(&self) -> bool {
+        self.shared.lock().receivers == 0
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.shared.lock().senders += 1;
+
+        Sender {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+/// Closes the queue when the last [`Sender`] is dropped, waking up all
+/// waiting receivers.
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.senders -= 1;
+
+        if state.senders == 0 {
+            self.shared.wake_all(state);
+        }
+    }
+}
+
+impl<T> Receiver<T>
+where
+    T: Clone,
+{
+    /// Delivers the next ready value, if there is one. This function is
+    /// **non-blocking**.
+    ///
+    /// Returns [`TryRecvError::Empty`] if no value is ready or
+    /// [`TryRecvError::Disconnected`] if all values have been acknowledged and
+    /// all senders have been dropped.
+    pub fn try_recv(&self) -> Result<Delivery<T>, TryRecvError> {
+        let (id, value, attempt) = self.shared.deliver(&mut self.shared.lock_expired())?;
+
+        Ok(self.delivery(id, value, attempt))
+    }
+
+    /// Polls for the next ready value, registering the waker of `cx` if there
+    /// is none. Resolves to [`None`] once all values have been acknowledged
+    /// and all senders have been dropped.
+    ///
+    /// Only the waker of the last call is kept, so tasks sharing a receiver
+    /// use [`Receiver::recv`] instead.
+    pub fn poll_recv(&self, cx: &mut Context<'_>) -> Poll<Option<Delivery<T>>> {
+        self.poll_deliver(&mut Some(self.id), cx)
+    }
+
+    /// Polls for the next ready value, registering the waker of `cx` under
+    /// `id` if there is none. A new id is assigned if `id` is [`None`].
+    fn poll_deliver(&self, id: &mut Option<u64>, cx: &Context<'_>) -> Poll<Option<Delivery<T>>> {
+        let mut state = self.shared.lock_expired();
+
+        let delivered = match self.shared.deliver(&mut state) {
+            Ok(delivered) => Some(delivered),
+            Err(TryRecvError::Disconnected) => None,
+            Err(TryRecvError::Empty) => {
+                let id = *id.get_or_insert_with(|| state.id());
+                self.shared.register(&mut state, id, cx);
+
+                return Poll::Pending;
+            }
+        };
+
+        // A waker registered by an earlier poll is stale now, it mustn't take
+        // the wake-up of another receiver.
+        if let Some(id) = id {
+            self.shared.wakers().remove(id);
+        }
+
+        drop(state);
+        Poll::Ready(delivered.map(|(id, value, attempt)| self.delivery(id, value, attempt)))
+    }
+
+    /// Delivers the next ready value, waiting asynchronously until there is
+    /// one. Resolves to [`None`] once all values have been acknowledged and
+    /// all senders have been dropped.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # tokio::runtime::Builder::new_current_thread()
+    /// #     .build()
+    /// #     .unwrap()
+    /// #     .block_on(async {
+    /// use std::time::Duration;
+    ///
+    /// let (tx, rx) = laika::ack::channel(Duration::from_secs(30));
+    ///
+    /// for job in 0..10 {
+    ///     tx.send(job).unwrap();
+    /// }
+    ///
+    /// drop(tx);
+    ///
+    /// let workers = (0..4)
+    ///     .map(|_| {
+    ///         let rx = rx.clone();
+    ///
+    ///         tokio::spawn(async move {
+    ///             while let Some(delivery) = rx.recv().await {
+    ///                 // Odd jobs fail on their first attempt
+    ///                 if *delivery % 2 == 1 && delivery.attempt() == 1 {
+    ///                     delivery.nack();
+    ///                 } else {
+    ///                     delivery.ack();
+    ///                 }
+    ///             }
+    ///         })
+    ///     })
+    ///     .collect::<Vec<_>>();
+    ///
+    /// for worker in workers {
+    ///     worker.await.unwrap();
+    /// }
+    /// # });
+    /// ```
+    pub fn recv(&self) -> Recv<'_, T> {
+        Recv {
+            receiver: self,
+            id: None,
+        }
+    }
+
+    /// Delivers the next ready value, blocking the current thread until there
+    /// is one (or a delivery timed out). See [`Receiver::recv`].
+    pub fn recv_blocking(&self) -> Option<Delivery<T>> {
+        loop {
+            let mut state = self.shared.lock_expired();
+
+            match self.shared.deliver(&mut state) {
+                Ok((id, value, attempt)) => {
+                    drop(state);
+                    return Some(self.delivery(id, value, attempt));
+                }
+                Err(TryRecvError::Disconnected) => return None,
+                Err(TryRecvError::Empty) => drop(self.shared.wait(&self.shared.condvar, state)),
+            }
+        }
+    }
+
+    /// Wraps a delivered value into a [`Delivery`] that requeues it unless
+    /// it's acknowledged.
+    fn delivery(&self, id: u64, value: T, attempt: u32) -> Delivery<T> {
+        Delivery {
+            shared: Arc::clone(&self.shared),
+            id,
+            value,
+            attempt,
+            settled: false,
+        }
+    }
+}
+
+impl<T> Receiver<T> {
+    /// Returns the number of values waiting to be delivered.
+    pub fn len(&self) -> usize {
+        self.shared.lock().ready.len()
+    }
+
+    /// Returns whether no values are waiting to be delivered.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of delivered values that haven't been acknowledged
+    /// yet.
+    pub fn in_flight(&self) -> usize {
+        self.shared.lock().in_flight.len()
+    }
+}
+
+impl<T> Clone for Receiver<T> {
+    fn clone(&self) -> Self {
+        let mut state = self.shared.lock();
+        state.receivers += 1;
+        let id = state.id();
+
+        Receiver {
+            shared: Arc::clone(&self.shared),
+            id,
+        }
+    }
+}
+
+/// Closes the queue for senders when the last [`Receiver`] is dropped and
+/// drops the values waiting to be delivered. Otherwise, a wake-up this
+/// receiver got is passed on.
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.receivers -= 1;
+
+        if state.receivers > 0 {
+            drop(state);
+            self.shared.withdraw(self.id);
+        } else {
+            // Values are dropped after unlocking, in case their `Drop` uses
+            // the queue. The dead letters are woken up, as no more values can
+            // become dead letters once the in-flight ones are settled.
+            let ready = mem::take(&mut state.ready);
+            self.shared.wake_all(state);
+            drop(ready);
+        }
+    }
+}
+
+impl<T> Delivery<T> {
+    /// Returns how often the value has been delivered, including this
+    /// delivery (so `1` for the first one).
+    pub fn attempt(&self) -> u32 {
+        self.attempt
+    }
+
+    /// Acknowledges that the value has been processed, removing it from the
+    /// queue.
+    ///
+    /// Returns `false` if the delivery has timed out already, so the value
+    /// has been (or will be) delivered again.
+    pub fn ack(mut self) -> bool {
+        self.settle(true)
+    }
+
+    /// Rejects the value, so it's delivered again (or moved to the
+    /// [`DeadLetters`] if it has been delivered too often). Same as dropping
+    /// the delivery.
+    pub fn nack(mut self) {
+        self.settle(false);
+    }
+
+    /// Removes the value from the delivered ones, requeuing it unless `ack`.
+    /// Returns whether the delivery hadn't timed out yet.
+    fn settle(&mut self, ack: bool) -> bool {
+        self.settled = true;
+
+        let mut state = self.shared.lock();
+
+        let Some(in_flight) = state.settle(self.id) else {
+            return false;
+        };
+
+        let value = if ack {
+            in_flight.entry.value
+        } else {
+            match state.requeue(in_flight.entry) {
+                Requeued::Ready => {
+                    self.shared.wake_receiver(state);
+                    return true;
+                }
+                Requeued::Dead => {
+                    self.shared.wake_dead_letters(state);
+                    return true;
+                }
+                Requeued::Discarded(value) => value,
+            }
+        };
+
+        // Wake up everyone waiting if this was the last value of a closed
+        // queue
+        if state.is_exhausted() {
+            self.shared.wake_all(state);
+        } else {
+            drop(state);
+        }
+
+        // The value is dropped after unlocking, in case its `Drop` uses the
+        // queue.
+        drop(value);
+        true
+    }
+}
+
+impl<T> DeadLetters<T> {
+    /// Takes the oldest dead letter, if there is one. This function is
+    /// **non-blocking**.
+    ///
+    /// Returns [`TryRecvError::Empty`] if there is none or
+    /// [`TryRecvError::Disconnected`] if there is none and no value can become
+    /// one anymore, as all values have been settled and all senders (or all
+    /// receivers) have been dropped.
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        self.shared.lock_expired().pop_dead()
+    }
+
+    /// Polls for the oldest dead letter, registering the waker of `cx` if
+    /// there is none. Resolves to [`None`] once no value can become a dead
+    /// letter anymore.
+    pub fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        let mut state = self.shared.lock_expired();
+
+        match state.pop_dead() {
+            Ok(value) => Poll::Ready(Some(value)),
+            Err(TryRecvError::Disconnected) => Poll::Ready(None),
+            Err(TryRecvError::Empty) => {
+                self.shared.register(&mut state, self.id, cx);
+                Poll::Pending
+            }
+        }
+    }
+
+    /// Takes the oldest dead letter, waiting asynchronously until there is
+    /// one. Resolves to [`None`] once no value can become a dead letter
+    /// anymore.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # tokio::runtime::Builder::new_current_thread()
+    /// #     .build()
+    /// #     .unwrap()
+    /// #     .block_on(async {
+    /// use std::time::Duration;
+    ///
+    /// let (tx, rx, mut dead_letters) =
+    ///     laika::ack::channel_with_dead_letters(Duration::from_secs(30), 3);
+    ///
+    /// for value in [1, -1, 2] {
+    ///     tx.send(value).unwrap();
+    /// }
+    ///
+    /// drop(tx);
+    ///
+    /// tokio::spawn(async move {
+    ///     while let Some(delivery) = rx.recv().await {
+    ///         // Negative values can't be processed
+    ///         if *delivery >= 0 {
+    ///             delivery.ack();
+    ///         }
+    ///     }
+    /// });
+    ///
+    /// assert_eq!(dead_letters.recv().await, Some(-1));
+    /// assert_eq!(dead_letters.recv().await, None);
+    /// # });
+    /// ```
+    pub async fn recv(&mut self) -> Option<T> {
+        core::future::poll_fn(|cx| self.poll_recv(cx)).await
+    }
+
+    /// Takes the oldest dead letter, blocking the current thread until there
+    /// is one. See [`DeadLetters::recv`].
+    pub fn recv_blocking(&mut self) -> Option<T> {
+        loop {
+            let mut state = self.shared.lock_expired();
+
+            match state.pop_dead() {
+                Ok(value) => return Some(value),
+                Err(TryRecvError::Disconnected) => return None,
+                Err(TryRecvError::Empty) => drop(self.shared.wait(&self.shared.dead, state)),
+            }
+        }
+    }
+
+    /// Returns the number of dead letters that haven't been taken yet.
+    pub fn len(&self) -> usize {
+        self.shared.lock().dead.len()
+    }
+
+    /// Returns whether there are no dead letters.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Drops the dead letters, as well as the ones of the future.
+impl<T> Drop for DeadLetters<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.dead_letters = false;
+        self.shared.wakers().remove(&self.id);
+
+        // Values are dropped after unlocking, in case their `Drop` uses the
+        // queue.
+        let dead = mem::take(&mut state.dead);
+        drop(state);
+        drop(dead);
+    }
+}
+
+impl<T> Deref for Delivery<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T> DerefMut for Delivery<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+impl<T> fmt::Debug for Delivery<T>
+where
+    T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Delivery")
+            .field("value", &self.value)
+            .field("attempt", &self.attempt)
+            .finish()
+    }
+}
+
+/// Requeues the value unless the delivery has been acknowledged.
+impl<T> Drop for Delivery<T> {
+    fn drop(&mut self) {
+        if !self.settled {
+            self.settle(false);
+        }
+    }
+}
+
+impl<T> Future for Recv<'_, T>
+where
+    T: Clone,
+{
+    type Output = Option<Delivery<T>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let poll = this.receiver.poll_deliver(&mut this.id, cx);
+
+        if poll.is_ready() {
+            this.id = None;
+        }
+
+        poll
+    }
+}
+
+/// Withdraws the waker of a pending future, so the queue doesn't keep it. If
+/// the future has been woken up already, the next waiting receiver is woken
+/// up instead.
+impl<T> Drop for Recv<'_, T> {
+    fn drop(&mut self) {
+        if let Some(id) = self.id {
+            self.receiver.shared.withdraw(id);
+        }
+    }
+}
+
+/// Implement [`futures_core::Stream`] for [`Receiver`], yielding deliveries
+/// until all values have been acknowledged and all senders have been dropped.
+#[cfg(feature = "futures")]
+impl<T> futures_core::Stream for Receiver<T>
+where
+    T: Clone,
+{
+    type Item = Delivery<T>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Delivery<T>>> {
+        self.poll_recv(cx)
+    }
+}
+
+/// Implement [`futures_core::Stream`] for [`DeadLetters`], yielding dead
+/// letters until no value can become one anymore.
+#[cfg(feature = "futures")]
+impl<T> futures_core::Stream for DeadLetters<T> {
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        self.get_mut().poll_recv(cx)
+    }
+}
+
+/// Creates an acknowledged queue, redelivering values that haven't been
+/// acknowledged within `timeout` after being delivered.
+///
+/// Values are delivered again and again until they're acknowledged, see
+/// [`channel_with_dead_letters`] to give up on them at some point.
+pub fn channel<T>(timeout: Duration) -> (Sender<T>, Receiver<T>) {
+    let shared = Shared::new(timeout, u32::MAX, false);
+
+    let receiver = Receiver {
+        shared: Arc::clone(&shared),
+        id: RECEIVER_ID,
+    };
+
+    (Sender { shared }, receiver)
+}
+
+/// Creates an acknowledged queue like [`channel`], but moves values to the
+/// [`DeadLetters`] once they've been delivered `max_deliveries` times without
+/// being acknowledged.
+///
+/// # Panics
+/// Panics if `max_deliveries` is zero.
+///
+/// # Examples
+/// ```rust
+/// use std::time::Duration;
+///
+/// let (tx, rx, mut dead_letters) = laika::ack::channel_with_dead_letters(Duration::from_secs(30), 2);
+///
+/// tx.send("poison").unwrap();
+///
+/// rx.try_recv().unwrap().nack();
+/// rx.try_recv().unwrap().nack();
+///
+/// // The value isn't delivered a third time
+/// assert!(rx.try_recv().is_err());
+/// assert_eq!(dead_letters.try_recv(), Ok("poison"));
+/// ```
+pub fn channel_with_dead_letters<T>(
+    timeout: Duration,
+    max_deliveries: u32,
+) -> (Sender<T>, Receiver<T>, DeadLetters<T>) {
+    assert!(
+        max_deliveries > 0,
+        "values have to be delivered at least once"
+    );
+
+    let shared = Shared::new(timeout, max_deliveries, true);
+
+    let receiver = Receiver {
+        shared: Arc::clone(&shared),
+        id: RECEIVER_ID,
+    };
+
+    let dead_letters = DeadLetters {
+        shared: Arc::clone(&shared),
+        id: DEAD_LETTERS_ID,
+    };
+
+    (Sender { shared }, receiver, dead_letters)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::pin::pin;
+    use std::{thread, vec::Vec};
+
+    #[test]
+    fn test_ack_nack() {
+        let (tx, rx) = channel(Duration::from_secs(60));
+        let rx1 = rx.clone();
+
+        tx.send(1).unwrap();
+        tx.send(2).unwrap();
+
+        let first = rx.try_recv().unwrap();
+        let second = rx1.try_recv().unwrap();
+        assert_eq!((rx.len(), rx.in_flight()), (0, 2));
+        assert_eq!(rx.try_recv().unwrap_err(), TryRecvError::Empty);
+
+        // Rejected and dropped deliveries are requeued
+        first.nack();
+        drop(second);
+        assert_eq!((rx.len(), rx.in_flight()), (2, 0));
+
+        let first = rx1.try_recv().unwrap();
+        assert_eq!((*first, first.attempt()), (1, 2));
+        assert!(first.ack());
+
+        // The queue is closed once all values have been acknowledged
+        drop(tx);
+        let second = rx.try_recv().unwrap();
+        assert_eq!(rx1.try_recv().unwrap_err(), TryRecvError::Empty);
+        assert!(second.ack());
+        assert_eq!(rx1.try_recv().unwrap_err(), TryRecvError::Disconnected);
+    }
+
+    #[test]
+    fn test_timeout() {
+        let (tx, rx) = channel(Duration::from_millis(10));
+
+        tx.send(1).unwrap();
+
+        let stale = rx.try_recv().unwrap();
+        assert_eq!(rx.try_recv().unwrap_err(), TryRecvError::Empty);
+
+        thread::sleep(Duration::from_millis(20));
+
+        // The timed out value is delivered again
+        let delivery = rx.try_recv().unwrap();
+        assert_eq!((*delivery, delivery.attempt()), (1, 2));
+        assert!(!stale.ack());
+        assert!(delivery.ack());
+        assert!(rx.is_empty());
+    }
+
+    #[test]
+    fn test_timeout_order() {
+        let (tx, rx) = channel(Duration::from_millis(10));
+
+        for value in 1..=3 {
+            tx.send(value).unwrap();
+        }
+
+        let deliveries: Vec<_> = (0..3).map(|_| rx.try_recv().unwrap()).collect();
+        let [first, second, third] = <[_; 3]>::try_from(deliveries).unwrap();
+        assert!(second.ack());
+        assert_eq!(rx.shared.lock().deadlines.len(), 2);
+
+        thread::sleep(Duration::from_millis(20));
+
+        // Only the unacknowledged values time out, in the order of delivery
+        let redelivered = [rx.try_recv().unwrap(), rx.try_recv().unwrap()];
+        assert_eq!(redelivered.each_ref().map(|delivery| **delivery), [1, 3]);
+        assert_eq!(rx.shared.lock().deadlines.len(), 2);
+        assert!(!first.ack());
+        assert!(!third.ack());
+
+        for delivery in redelivered {
+            assert!(delivery.ack());
+        }
+
+        assert!(rx.shared.lock().deadlines.is_empty());
+    }
+
+    #[test]
+    fn test_recv_blocking() {
+        let (tx, rx) = channel(Duration::from_millis(50));
+
+        // The first delivery of every value is lost, so the receiver has to
+        // wake up for the timeout
+        let lost = thread::spawn({
+            let rx = rx.clone();
+            move || {
+                (0..3)
+                    .map(|_| rx.recv_blocking().unwrap())
+                    .collect::<Vec<_>>()
+            }
+        });
+
+        for value in 0..3 {
+            tx.send(value).unwrap();
+        }
+
+        let lost = lost.join().unwrap();
+        drop(tx);
+
+        let mut values = Vec::new();
+
+        while let Some(delivery) = rx.recv_blocking() {
+            assert_eq!(delivery.attempt(), 2);
+            values.push(*delivery);
+            delivery.ack();
+        }
+
+        assert_eq!(values, [0, 1, 2]);
+        assert!(lost.into_iter().all(|delivery| !delivery.ack()));
+    }
+
+    #[test]
+    fn test_dead_letters() {
+        let (tx, rx, mut dead_letters) = channel_with_dead_letters(Duration::from_millis(10), 2);
+
+        tx.send(1).unwrap();
+        tx.send(2).unwrap();
+
+        // The first value is rejected twice, the second one times out
+        rx.try_recv().unwrap().nack();
+        let stale = rx.try_recv().unwrap();
+        assert_eq!(dead_letters.try_recv(), Err(TryRecvError::Empty));
+        rx.try_recv().unwrap().nack();
+        assert_eq!(dead_letters.try_recv(), Ok(1));
+
+        thread::sleep(Duration::from_millis(20));
+
+        let second = rx.try_recv().unwrap();
+        assert_eq!((*second, second.attempt()), (2, 2));
+        assert_eq!((dead_letters.len(), rx.in_flight()), (0, 1));
+
+        drop(tx);
+        drop(second);
+        assert!(!stale.ack());
+
+        assert_eq!(dead_letters.recv_blocking(), Some(2));
+        assert_eq!(dead_letters.recv_blocking(), None);
+        assert!(rx.recv_blocking().is_none());
+    }
+
+    #[test]
+    fn test_discarded_dead_letters() {
+        let (tx, rx, dead_letters) = channel_with_dead_letters(Duration::from_secs(60), 1);
+        drop(dead_letters);
+
+        tx.send(1).unwrap();
+        tx.send(2).unwrap();
+
+        // Without dead letters, values are dropped after the last delivery
+        rx.try_recv().unwrap().nack();
+        assert_eq!(*rx.try_recv().unwrap(), 2);
+        assert_eq!((rx.len(), rx.in_flight()), (0, 0));
+    }
+
+    #[test]
+    fn test_shared_receiver() {
+        let (tx, rx) = channel(Duration::from_secs(60));
+        let mut cx = Context::from_waker(Waker::noop());
+
+        {
+            let mut recv = pin!(rx.recv());
+            let mut recv1 = pin!(rx.recv());
+
+            assert!(recv.as_mut().poll(&mut cx).is_pending());
+            assert!(recv1.as_mut().poll(&mut cx).is_pending());
+
+            // Each future waits with a waker of its own
+            assert_eq!(rx.shared.wakers().len(), 2);
+
+            tx.send(1).unwrap();
+
+            let Poll::Ready(Some(delivery)) = recv1.as_mut().poll(&mut cx) else {
+                panic!("the value hasn't been delivered");
+            };
+
+            assert!(recv.as_mut().poll(&mut cx).is_pending());
+            assert!(delivery.ack());
+        }
+
+        assert!(rx.shared.wakers().is_empty());
+    }
+
+    #[test]
+    fn test_pass_on() {
+        let (tx, rx, mut dead_letters) = channel_with_dead_letters(Duration::from_secs(60), 1);
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut recv1 = pin!(rx.recv());
+
+        {
+            let mut recv = pin!(rx.recv());
+
+            assert!(recv.as_mut().poll(&mut cx).is_pending());
+            assert!(recv1.as_mut().poll(&mut cx).is_pending());
+            assert!(dead_letters.poll_recv(&mut cx).is_pending());
+
+            // A single value wakes up a single receiver
+            tx.send(1).unwrap();
+            assert_eq!(rx.shared.wakers().len(), 2);
+        }
+
+        // The woken up future has been dropped, so the next one is woken up
+        assert_eq!(rx.shared.wakers().len(), 1);
+
+        let Poll::Ready(Some(delivery)) = recv1.as_mut().poll(&mut cx) else {
+            panic!("the value hasn't been delivered");
+        };
+
+        // A dead letter only wakes up the dead letters
+        assert!(rx.poll_recv(&mut cx).is_pending());
+        delivery.nack();
+
+        assert!(rx.shared.wakers().contains_key(&RECEIVER_ID));
+        assert!(!rx.shared.wakers().contains_key(&DEAD_LETTERS_ID));
+        assert_eq!(dead_letters.try_recv(), Ok(1));
+    }
+
+    #[tokio::test]
+    async fn test_redeliver() {
+        let (tx, rx) = channel(Duration::from_millis(20));
+
+        tx.send(1).unwrap();
+        drop(tx);
+
+        // Nothing else is sent, so the timer has to wake up the receiver for
+        // the hanging delivery
+        let hanging = rx.try_recv().unwrap();
+        let delivery = rx.recv().await.unwrap();
+
+        assert_eq!((*delivery, delivery.attempt()), (1, 2));
+        assert!(delivery.ack());
+        assert!(!hanging.ack());
+        assert!(rx.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_recv() {
+        let (tx, rx) = channel::<()>(Duration::from_secs(60));
+
+        let worker = tokio::spawn({
+            let rx = rx.clone();
+
+            async move {
+                let mut attempts = Vec::new();
+
+                while let Some(delivery) = rx.recv().await {
+                    attempts.push(delivery.attempt());
+
+                    if delivery.attempt() < 3 {
+                        delivery.nack();
+                    } else {
+                        delivery.ack();
+                    }
+                }
+
+                attempts
+            }
+        });
+
+        tx.send(()).unwrap();
+        drop(tx);
+
+        assert_eq!(worker.await.unwrap(), [1, 2, 3]);
+        assert_eq!(rx.in_flight(), 0);
+    }
+}
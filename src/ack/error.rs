@@ -0,0 +1,64 @@
+//! Errors of sending on and receiving from acknowledged queues.
+
+#[cfg(doc)]
+use super::{Receiver, Sender};
+use core::{error::Error, fmt};
+
+/// Error returned by [`Sender::send`] if all [`Receiver`]s have been dropped.
+/// Contains the value, so it can be reclaimed.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct SendError<T>(pub T);
+
+impl<T> SendError<T> {
+    /// Returns the value that couldn't be sent.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+debug_without_value!(SendError);
+
+impl<T> fmt::Display for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "sending on a queue without receivers")
+    }
+}
+
+impl<T> Error for SendError<T> {}
+
+/// Error returned by [`Receiver::try_recv`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TryRecvError {
+    /// No value is ready. A [`Sender`] still exists or values are still being
+    /// processed, which may be redelivered.
+    Empty,
+    /// No value is ready or being processed and all [`Sender`]s have been
+    /// dropped.
+    Disconnected,
+}
+
+impl fmt::Display for TryRecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TryRecvError::Empty => write!(f, "no value is ready"),
+            TryRecvError::Disconnected => write!(f, "queue closed"),
+        }
+    }
+}
+
+impl Error for TryRecvError {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::string::ToString;
+
+    #[test]
+    fn test_display() {
+        assert_eq!(
+            SendError(1).to_string(),
+            "sending on a queue without receivers"
+        );
+        assert_eq!(TryRecvError::Disconnected.to_string(), "queue closed");
+    }
+}
@@ -0,0 +1,734 @@
+#![forbid(unsafe_code)]
+//! # A multi-value broadcast channel with a bounded buffer
+//!
+//! Every value sent by a [`Sender`] is received by every [`Receiver`]. Unlike
+//! [`shotgun`](crate::shotgun), the channel carries any number of values: the
+//! last `capacity` of them are kept in a ring buffer. Sending never blocks,
+//! instead the oldest value is overwritten once the buffer is full. Receivers
+//! that fell behind then get [`RecvError::Lagged`] with the number of values
+//! they missed and continue with the oldest value that's still buffered.
+//! Channels created by [`channel_with_overflow`] can drop or reject new values
+//! instead, see [`Overflow`]. As sending never waits, there's no
+//! [`Overflow::Block`] policy for broadcast channels.
+//!
+//! Both senders and receivers can be cloned (MPMC). A cloned receiver
+//! continues where the original one is, while [`Sender::subscribe`] creates
+//! one that only receives values sent afterwards. Once all senders have been
+//! dropped, receivers still get the buffered values and
+//! [`RecvError::Closed`] afterwards.
+//!
+//! The channel is backed by a `std::sync::Mutex` and doesn't contain any
+//! unsafe code. The async functions work with any runtime.
+//!
+//! # Examples
+//! ```rust
+//! use laika::broadcast::{self, RecvError};
+//!
+//! let (tx, mut rx) = broadcast::channel(2);
+//! let mut rx1 = tx.subscribe();
+//!
+//! tx.send(1).unwrap();
+//! tx.send(2).unwrap();
+//! assert_eq!(rx.recv_blocking(), Ok(1));
+//!
+//! // The buffer only holds 2 values, so 1 is overwritten for `rx1`
+//! tx.send(3).unwrap();
+//! assert_eq!(rx1.recv_blocking(), Err(RecvError::Lagged(1)));
+//! assert_eq!(rx1.recv_blocking(), Ok(2));
+//!
+//! drop(tx);
+//! assert_eq!(rx.recv_blocking(), Ok(2));
+//! assert_eq!(rx.recv_blocking(), Ok(3));
+//! assert_eq!(rx.recv_blocking(), Err(RecvError::Closed));
+//! ```
+
+mod error;
+
+pub use crate::Overflow;
+pub use error::{RecvError, SendError, TryRecvError};
+
+use alloc::{
+    collections::{btree_map::Entry, VecDeque},
+    sync::Arc,
+};
+use core::{
+    future, mem,
+    task::{Context, Poll, Waker},
+};
+use std::{
+    collections::BTreeMap,
+    sync::{Condvar, Mutex, MutexGuard, PoisonError},
+};
+
+/// Buffer and bookkeeping of a channel, guarded by [`Shared::state`]
+#[derive(Debug)]
+struct State<T> {
+    /// Last sent values, oldest first
+    buffer: VecDeque<T>,
+    /// Maximum number of buffered values
+    capacity: usize,
+    /// What sending on a full buffer does
+    overflow: Overflow,
+    /// Sequence number of the oldest buffered value
+    head: u64,
+    /// Number of existing [`Sender`]s
+    senders: usize,
+    /// Number of existing [`Receiver`]s
+    receivers: usize,
+    /// Number of [`Receiver`]s per sequence number of the value they receive
+    /// next, to tell how far the slowest one is behind
+    positions: BTreeMap<u64, usize>,
+    /// Wakers of pending [`Receiver`]s, keyed by [`Receiver::id`]
+    wakers: BTreeMap<u64, Waker>,
+    /// Id of the next [`Receiver`]
+    next_id: u64,
+}
+
+impl<T> State<T> {
+    /// Returns the sequence number the next sent value will get.
+    fn tail(&self) -> u64 {
+        self.head + self.buffer.len() as u64
+    }
+
+    /// Returns a new receiver id.
+    fn id(&mut self) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    /// Adds a receiver that receives the value with sequence number `next`
+    /// next.
+    fn track(&mut self, next: u64) {
+        *self.positions.entry(next).or_default() += 1;
+    }
+
+    /// Removes a receiver that receives the value with sequence number `next`
+    /// next.
+    fn untrack(&mut self, next: u64) {
+        if let Entry::Occupied(mut entry) = self.positions.entry(next) {
+            *entry.get_mut() -= 1;
+
+            if *entry.get() == 0 {
+                entry.remove();
+            }
+        }
+    }
+
+    /// Moves a receiver from sequence number `next` to `to`.
+    fn advance(&mut self, next: &mut u64, to: u64) {
+        self.untrack(*next);
+        self.track(to);
+        *next = to;
+    }
+
+    /// Returns whether the slowest receiver hasn't received any of the
+    /// buffered values, after dropping the values every receiver has received
+    /// already.
+    fn is_full(&mut self) -> bool {
+        let slowest = self
+            .positions
+            .first_key_value()
+            .map_or(self.tail(), |(next, _)| *next);
+
+        while self.head < slowest && self.buffer.pop_front().is_some() {
+            self.head += 1;
+        }
+
+        self.buffer.len() == self.capacity
+    }
+
+    /// Takes the value with sequence number `next` and advances it.
+    fn take(&mut self, next: &mut u64) -> Result<T, TryRecvError>
+    where
+        T: Clone,
+    {
+        if *next < self.head {
+            let missed = self.head - *next;
+            self.advance(next, self.head);
+
+            return Err(TryRecvError::Lagged(missed));
+        }
+
+        match self.buffer.get((*next - self.head) as usize).cloned() {
+            Some(value) => {
+                self.advance(next, *next + 1);
+                Ok(value)
+            }
+            None if self.senders == 0 => Err(TryRecvError::Closed),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+}
+
+/// State shared by all [`Sender`]s and [`Receiver`]s of a channel
+#[derive(Debug)]
+struct Shared<T> {
+    /// Buffer and bookkeeping
+    state: Mutex<State<T>>,
+    /// Notified when a value is sent or the channel is closed, so that threads
+    /// blocking in [`Receiver::recv_blocking`] wake up.
+    condvar: Condvar,
+}
+
+impl<T> Shared<T> {
+    /// Locks the state.
+    fn lock(&self) -> MutexGuard<'_, State<T>> {
+        crate::lock(&self.state)
+    }
+
+    /// Wakes up all pending receivers. Takes the wakers while `state` is
+    /// locked but wakes them after unlocking it.
+    fn wake_all(&self, mut state: MutexGuard<'_, State<T>>) {
+        let wakers = mem::take(&mut state.wakers);
+        drop(state);
+
+        for waker in wakers.into_values() {
+            waker.wake();
+        }
+
+        self.condvar.notify_all();
+    }
+}
+
+/// Sending side of a broadcast channel
+///
+/// Can be cloned to send from multiple producers. The channel is closed once
+/// all senders have been dropped.
+#[derive(Debug)]
+pub struct Sender<T> {
+    /// State shared with all receivers
+    shared: Arc<Shared<T>>,
+}
+
+/// Receiving side of a broadcast channel
+///
+/// Receives every value sent after it has been created, see [`Sender::send`].
+/// A cloned receiver continues where the original one is.
+#[derive(Debug)]
+pub struct Receiver<T> {
+    /// State shared with all senders
+    shared: Arc<Shared<T>>,
+    /// Sequence number of the value that's received next
+    next: u64,
+    /// Key of the waker this receiver registered while being polled
+    id: u64,
+}
+
+impl<T> Sender<T> {
+    /// Sends a value to all receivers and returns how many receivers there
+    /// are. Never blocks: if the buffer is full, the oldest value is
+    /// overwritten and receivers that haven't received it yet get
+    /// [`RecvError::Lagged`]. Channels with another [`Overflow`] policy drop
+    /// the value instead (returning `0` receivers) or fail with
+    /// [`SendError::Full`] while the slowest receiver is `capacity` values
+    /// behind.
+    ///
+    /// Returns the value in [`SendError::Disconnected`] if all [`Receiver`]s
+    /// have been dropped. Receivers created later don't see it either way.
+    ///
+    /// # Examples
+    /// ```rust
+    /// let (tx, rx) = laika::broadcast::channel(8);
+    /// let _rx1 = rx.clone();
+    ///
+    /// assert_eq!(tx.send(1), Ok(2));
+    ///
+    /// drop(rx);
+    /// drop(_rx1);
+    ///
+    /// assert_eq!(tx.send(2).unwrap_err().into_inner(), 2);
+    /// ```
+    pub fn send(&self, value: T) -> Result<usize, SendError<T>> {
+        let mut state = self.shared.lock();
+
+        if state.receivers == 0 {
+            return Err(SendError::Disconnected(value));
+        }
+
+        let overflow = state.overflow;
+
+        match overflow {
+            Overflow::DropOldest if state.buffer.len() == state.capacity => {
+                state.buffer.pop_front();
+                state.head += 1;
+            }
+            Overflow::DropOldest => {}
+            Overflow::DropNewest if state.is_full() => {
+                // The value is dropped after unlocking, in case its `Drop`
+                // uses the channel.
+                drop(state);
+                drop(value);
+
+                return Ok(0);
+            }
+            Overflow::Error if state.is_full() => return Err(SendError::Full(value)),
+            Overflow::DropNewest | Overflow::Error => {}
+            Overflow::Block => unreachable!("Rejected when creating the channel"),
+        }
+
+        state.buffer.push_back(value);
+
+        let receivers = state.receivers;
+        self.shared.wake_all(state);
+
+        Ok(receivers)
+    }
+
+    /// Creates a new [`Receiver`] that receives all values sent from now on.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use laika::broadcast::TryRecvError;
+    ///
+    /// let (tx, _rx) = laika::broadcast::channel(8);
+    /// tx.send(1).unwrap();
+    ///
+    /// let mut rx1 = tx.subscribe();
+    /// assert_eq!(rx1.try_recv(), Err(TryRecvError::Empty));
+    ///
+    /// tx.send(2).unwrap();
+    /// assert_eq!(rx1.try_recv(), Ok(2));
+    /// ```
+    pub fn subscribe(&self) -> Receiver<T> {
+        let mut state = self.shared.lock();
+        state.receivers += 1;
+
+        let next = state.tail();
+        state.track(next);
+
+        Receiver {
+            shared: Arc::clone(&self.shared),
+            next,
+            id: state.id(),
+        }
+    }
+
+    /// Returns the number of [`Receiver`]s that currently exist.
+    pub fn receiver_count(&self) -> usize {
+        self.shared.lock().receivers
+    }
+
+    /// Returns what sending on a full buffer does.
+    pub fn overflow(&self) -> Overflow {
+        self.shared.lock().overflow
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.shared.lock().senders += 1;
+
+        Sender {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+/// Closes the channel when the last [`Sender`] is dropped, waking up all
+/// pending receivers.
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.senders -= 1;
+
+        if state.senders == 0 {
+            self.shared.wake_all(state);
+        }
+    }
+}
+
+impl<T> Receiver<T>
+where
+    T: Clone,
+{
+    /// Receives the next value if there is one. This function is
+    /// **non-blocking**.
+    ///
+    /// Returns [`TryRecvError::Empty`] if no new value has been sent,
+    /// [`TryRecvError::Lagged`] if values have been overwritten before this
+    /// receiver got them or [`TryRecvError::Closed`] if all [`Sender`]s have
+    /// been dropped and every value has been received.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use laika::broadcast::TryRecvError;
+    ///
+    /// let (tx, mut rx) = laika::broadcast::channel(1);
+    /// assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+    ///
+    /// tx.send(1).unwrap();
+    /// tx.send(2).unwrap();
+    ///
+    /// assert_eq!(rx.try_recv(), Err(TryRecvError::Lagged(1)));
+    /// assert_eq!(rx.try_recv(), Ok(2));
+    /// ```
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        self.shared.lock().take(&mut self.next)
+    }
+
+    /// Polls for the next value, registering the waker of `cx` if there is
+    /// none yet.
+    pub fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Result<T, RecvError>> {
+        let mut state = self.shared.lock();
+
+        match state.take(&mut self.next) {
+            Ok(value) => Poll::Ready(Ok(value)),
+            Err(TryRecvError::Closed) => Poll::Ready(Err(RecvError::Closed)),
+            Err(TryRecvError::Lagged(missed)) => Poll::Ready(Err(RecvError::Lagged(missed))),
+            Err(TryRecvError::Empty) => {
+                let registered = state.wakers.get(&self.id);
+
+                if !registered.is_some_and(|waker| waker.will_wake(cx.waker())) {
+                    state.wakers.insert(self.id, cx.waker().clone());
+                }
+
+                Poll::Pending
+            }
+        }
+    }
+
+    /// Receives the next value, waiting asynchronously until one is sent.
+    ///
+    /// Resolves to [`RecvError::Lagged`] if values have been overwritten
+    /// before this receiver got them, after which receiving continues with the
+    /// oldest buffered value, or [`RecvError::Closed`] if all [`Sender`]s have
+    /// been dropped and every value has been received.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use laika::broadcast::RecvError;
+    ///
+    /// # tokio::runtime::Builder::new_current_thread()
+    /// #     .build()
+    /// #     .unwrap()
+    /// #     .block_on(async {
+    /// let (tx, mut rx) = laika::broadcast::channel(8);
+    ///
+    /// let task = tokio::spawn(async move {
+    ///     let mut sum = 0;
+    ///
+    ///     while let Ok(value) = rx.recv().await {
+    ///         sum += value;
+    ///     }
+    ///
+    ///     sum
+    /// });
+    ///
+    /// for value in 1..=3 {
+    ///     tx.send(value).unwrap();
+    /// }
+    ///
+    /// drop(tx);
+    /// assert_eq!(task.await.unwrap(), 6);
+    /// # });
+    /// ```
+    pub async fn recv(&mut self) -> Result<T, RecvError> {
+        future::poll_fn(|cx| self.poll_recv(cx)).await
+    }
+
+    /// Receives the next value, blocking the current thread until one is
+    /// sent. See [`Receiver::recv`].
+    pub fn recv_blocking(&mut self) -> Result<T, RecvError> {
+        let mut state = self.shared.lock();
+
+        loop {
+            match state.take(&mut self.next) {
+                Ok(value) => return Ok(value),
+                Err(TryRecvError::Closed) => return Err(RecvError::Closed),
+                Err(TryRecvError::Lagged(missed)) => return Err(RecvError::Lagged(missed)),
+                Err(TryRecvError::Empty) => {
+                    state = self
+                        .shared
+                        .condvar
+                        .wait(state)
+                        .unwrap_or_else(PoisonError::into_inner);
+                }
+            }
+        }
+    }
+}
+
+impl<T> Receiver<T> {
+    /// Returns the number of values that have been sent but not received by
+    /// this receiver yet (including overwritten ones).
+    pub fn len(&self) -> usize {
+        (self.shared.lock().tail() - self.next) as usize
+    }
+
+    /// Returns whether this receiver has received every value sent so far.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Cloned receivers continue where the original one is, i.e. receive the same
+/// values.
+impl<T> Clone for Receiver<T> {
+    fn clone(&self) -> Self {
+        let mut state = self.shared.lock();
+        state.receivers += 1;
+        state.track(self.next);
+
+        Receiver {
+            shared: Arc::clone(&self.shared),
+            next: self.next,
+            id: state.id(),
+        }
+    }
+}
+
+/// Removes the registered waker (if any) when a [`Receiver`] is dropped.
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.receivers -= 1;
+        state.untrack(self.next);
+        state.wakers.remove(&self.id);
+    }
+}
+
+/// Creates a broadcast channel that buffers the last `capacity` values.
+///
+/// # Panics
+/// Panics if `capacity` is zero.
+///
+/// # Examples
+/// ```rust
+/// let (tx, mut rx) = laika::broadcast::channel(16);
+///
+/// tx.send("hello").unwrap();
+/// assert_eq!(rx.try_recv(), Ok("hello"));
+/// ```
+pub fn channel<T>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    channel_with_overflow(capacity, Overflow::DropOldest)
+}
+
+/// Creates a broadcast channel like [`channel`], but handles sending while the
+/// slowest receiver is `capacity` values behind by `overflow`.
+///
+/// # Panics
+/// Panics if `capacity` is zero or `overflow` is [`Overflow::Block`], as
+/// sending on a broadcast channel never waits.
+///
+/// # Examples
+/// ```rust
+/// use laika::broadcast::{self, Overflow, SendError};
+///
+/// let (tx, mut rx) = broadcast::channel_with_overflow(1, Overflow::Error);
+///
+/// tx.send(1).unwrap();
+/// assert_eq!(tx.send(2), Err(SendError::Full(2)));
+///
+/// assert_eq!(rx.try_recv(), Ok(1));
+/// assert_eq!(tx.send(2), Ok(1));
+/// ```
+pub fn channel_with_overflow<T>(capacity: usize, overflow: Overflow) -> (Sender<T>, Receiver<T>) {
+    assert!(capacity > 0, "a broadcast channel needs a capacity");
+    assert!(
+        overflow != Overflow::Block,
+        "a broadcast channel can't block on sending"
+    );
+
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            buffer: VecDeque::with_capacity(capacity),
+            capacity,
+            overflow,
+            head: 0,
+            senders: 1,
+            receivers: 1,
+            positions: BTreeMap::from([(0, 1)]),
+            wakers: BTreeMap::new(),
+            next_id: 1,
+        }),
+        condvar: Condvar::new(),
+    });
+
+    let receiver = Receiver {
+        shared: Arc::clone(&shared),
+        next: 0,
+        id: 0,
+    };
+
+    (Sender { shared }, receiver)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::{future::Future, pin::pin};
+    use std::{sync::Barrier, thread, vec::Vec};
+
+    #[test]
+    fn test_lagged() {
+        let (tx, mut rx) = channel(3);
+        let mut rx1 = rx.clone();
+
+        for value in 0..5 {
+            assert_eq!(tx.send(value), Ok(2));
+        }
+
+        assert_eq!(rx.len(), 5);
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Lagged(2)));
+        assert_eq!(rx.len(), 3);
+        assert_eq!(rx.try_recv(), Ok(2));
+        assert_eq!(rx.try_recv(), Ok(3));
+        assert_eq!(rx.try_recv(), Ok(4));
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+        assert!(rx.is_empty());
+
+        // Clones keep their own position
+        assert_eq!(rx1.recv_blocking(), Err(RecvError::Lagged(2)));
+        assert_eq!(rx1.recv_blocking(), Ok(2));
+    }
+
+    #[test]
+    fn test_overflow() {
+        let (tx, mut rx) = channel_with_overflow(2, Overflow::Error);
+        let mut rx1 = rx.clone();
+
+        assert_eq!(tx.overflow(), Overflow::Error);
+        assert_eq!(tx.send(1), Ok(2));
+        assert_eq!(tx.send(2), Ok(2));
+
+        // Both receivers are 2 values behind
+        assert_eq!(rx.try_recv(), Ok(1));
+        assert_eq!(tx.send(3), Err(SendError::Full(3)));
+
+        // Values every receiver has received make room
+        assert_eq!(rx1.try_recv(), Ok(1));
+        assert_eq!(tx.send(3), Ok(2));
+
+        // The slowest receiver no longer holds values back once dropped
+        drop(rx1);
+        assert_eq!(tx.send(4), Err(SendError::Full(4)));
+        assert_eq!(rx.try_recv(), Ok(2));
+        assert_eq!(tx.send(4), Ok(1));
+
+        let (tx, mut rx) = channel_with_overflow(1, Overflow::DropNewest);
+
+        assert_eq!(tx.send(1), Ok(1));
+        assert_eq!(tx.send(2), Ok(0));
+        assert_eq!(rx.try_recv(), Ok(1));
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_block_overflow() {
+        channel_with_overflow::<u8>(1, Overflow::Block);
+    }
+
+    #[test]
+    fn test_closed() {
+        let (tx, mut rx) = channel(4);
+        let tx1 = tx.clone();
+
+        tx.send(1).unwrap();
+        drop(tx);
+        tx1.send(2).unwrap();
+        drop(tx1);
+
+        assert_eq!(rx.try_recv(), Ok(1));
+        assert_eq!(rx.recv_blocking(), Ok(2));
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Closed));
+        assert_eq!(rx.recv_blocking(), Err(RecvError::Closed));
+
+        let (tx, rx) = channel(4);
+        drop(rx);
+
+        assert_eq!(tx.receiver_count(), 0);
+        assert_eq!(tx.send(1), Err(SendError::Disconnected(1)));
+
+        let mut rx = tx.subscribe();
+        assert_eq!(tx.send(2), Ok(1));
+        assert_eq!(rx.try_recv(), Ok(2));
+    }
+
+    #[test]
+    fn test_poll_recv() {
+        let (tx, mut rx) = channel(2);
+        let mut cx = Context::from_waker(Waker::noop());
+
+        {
+            let mut recv = pin!(rx.recv());
+            assert_eq!(recv.as_mut().poll(&mut cx), Poll::Pending);
+            assert_eq!(recv.as_mut().poll(&mut cx), Poll::Pending);
+            assert_eq!(tx.shared.lock().wakers.len(), 1);
+
+            tx.send(1).unwrap();
+            assert!(tx.shared.lock().wakers.is_empty());
+            assert_eq!(recv.as_mut().poll(&mut cx), Poll::Ready(Ok(1)));
+        }
+
+        assert_eq!(rx.poll_recv(&mut cx), Poll::Pending);
+        drop(rx);
+        assert!(tx.shared.lock().wakers.is_empty());
+    }
+
+    #[test]
+    fn test_recv_blocking() {
+        let (tx, mut rx) = channel(8);
+        let barrier = Arc::new(Barrier::new(2));
+        let received = Arc::clone(&barrier);
+
+        // Each value is sent once the receiver has taken the previous one, so
+        // it waits on an empty channel every time.
+        let thread = thread::spawn(move || {
+            let mut values = Vec::new();
+            received.wait();
+
+            while let Ok(value) = rx.recv_blocking() {
+                values.push(value);
+                received.wait();
+            }
+
+            values
+        });
+
+        barrier.wait();
+
+        for value in 0..4 {
+            tx.send(value).unwrap();
+            barrier.wait();
+        }
+
+        drop(tx);
+        assert_eq!(thread.join().unwrap(), [0, 1, 2, 3]);
+    }
+
+    #[tokio::test]
+    async fn test_recv() {
+        let (tx, rx) = channel(8);
+
+        let tasks = (0..4)
+            .map(|_| {
+                let mut rx = rx.clone();
+
+                tokio::spawn(async move {
+                    let mut values = Vec::new();
+
+                    while let Ok(value) = rx.recv().await {
+                        values.push(value);
+                    }
+
+                    values
+                })
+            })
+            .collect::<Vec<_>>();
+
+        drop(rx);
+        tokio::task::yield_now().await;
+
+        for value in 0..3 {
+            assert_eq!(tx.send(value), Ok(4));
+        }
+
+        drop(tx);
+
+        for task in tasks {
+            assert_eq!(task.await.unwrap(), [0, 1, 2]);
+        }
+    }
+}
@@ -0,0 +1,117 @@
+//! Errors of sending on and receiving from broadcast channels.
+
+#[cfg(doc)]
+use super::{Overflow, Receiver, Sender};
+use core::{error::Error, fmt};
+
+/// Error returned by [`Sender::send`]. Contains the value, so it can be
+/// reclaimed.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SendError<T> {
+    /// The slowest receiver is `capacity` values behind and the
+    /// [`Overflow::Error`] policy rejects new values until it catches up.
+    Full(T),
+    /// All [`Receiver`]s have been dropped.
+    Disconnected(T),
+}
+
+impl<T> SendError<T> {
+    /// Returns the value that couldn't be sent.
+    pub fn into_inner(self) -> T {
+        match self {
+            SendError::Full(value) | SendError::Disconnected(value) => value,
+        }
+    }
+}
+
+debug_without_value!(SendError { Full, Disconnected });
+
+impl<T> fmt::Display for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SendError::Full(_) => write!(f, "sending on a full channel"),
+            SendError::Disconnected(_) => write!(f, "sending on a channel without receivers"),
+        }
+    }
+}
+
+impl<T> Error for SendError<T> {}
+
+/// Error returned by [`Receiver::recv`] and [`Receiver::recv_blocking`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RecvError {
+    /// All [`Sender`]s have been dropped and every buffered value has been
+    /// received, so no value will ever be received.
+    Closed,
+    /// The receiver fell behind and the contained number of values has been
+    /// overwritten before it received them. The next receive returns the
+    /// oldest value that's still buffered.
+    Lagged(u64),
+}
+
+impl fmt::Display for RecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecvError::Closed => write!(f, "channel closed"),
+            RecvError::Lagged(count) => write!(f, "receiver lagged behind by {count} values"),
+        }
+    }
+}
+
+impl Error for RecvError {}
+
+/// Error returned by [`Receiver::try_recv`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TryRecvError {
+    /// No new value has been sent, but a [`Sender`] still exists.
+    Empty,
+    /// All [`Sender`]s have been dropped and every buffered value has been
+    /// received, so no value will ever be received.
+    Closed,
+    /// The receiver fell behind, see [`RecvError::Lagged`].
+    Lagged(u64),
+}
+
+impl fmt::Display for TryRecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TryRecvError::Empty => write!(f, "no new value has been sent"),
+            TryRecvError::Closed => RecvError::Closed.fmt(f),
+            TryRecvError::Lagged(count) => RecvError::Lagged(*count).fmt(f),
+        }
+    }
+}
+
+impl Error for TryRecvError {}
+
+impl From<RecvError> for TryRecvError {
+    fn from(error: RecvError) -> Self {
+        match error {
+            RecvError::Closed => TryRecvError::Closed,
+            RecvError::Lagged(count) => TryRecvError::Lagged(count),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::string::ToString;
+
+    #[test]
+    fn test_display() {
+        assert_eq!(
+            SendError::Disconnected(1).to_string(),
+            "sending on a channel without receivers"
+        );
+        assert_eq!(SendError::Full(1).to_string(), "sending on a full channel");
+        assert_eq!(
+            TryRecvError::from(RecvError::Lagged(2)).to_string(),
+            "receiver lagged behind by 2 values"
+        );
+        assert_eq!(
+            TryRecvError::from(RecvError::Closed).to_string(),
+            RecvError::Closed.to_string()
+        );
+    }
+}
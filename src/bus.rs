@@ -0,0 +1,501 @@
+#![forbid(unsafe_code)]
+//! # In-process message bus routing by type
+//!
+//! A [`Bus`] connects components that don't know about each other: any of
+//! them can [`publish`](Bus::publish) a message of any type, and it's
+//! delivered to the components that [`subscribe`](Bus::subscribe)d to that
+//! type. Messages are routed by their [`TypeId`], so plugins can define their
+//! own message types instead of extending a global enum of events.
+//!
+//! Every [`Subscription`] has its own bounded [`mpsc`](crate::mpsc) queue.
+//! [`Bus::publish`] waits until each subscriber has room for the message
+//! (backpressure), while [`Bus::try_publish`] skips subscribers that are
+//! full. Buses created by [`Bus::with_overflow`] shed load by an [`Overflow`]
+//! policy instead.
+//!
+//! Subscribers can come and go at any time: a subscription gets the messages
+//! published while it exists and unsubscribes when it's dropped. The bus can
+//! be cloned to publish from multiple places. Once all clones have been
+//! dropped, subscriptions get the remaining queued messages and [`None`]
+//! afterwards.
+//!
+//! The type has to match exactly: `Arc<T>` and `T` are different types, as
+//! are the integer types, so `publish(1)` only reaches subscribers of `i32`.
+//! Messages have to be `Clone`, as every subscriber gets its own. To share
+//! messages that are expensive to clone, publish them in an `Arc` (and
+//! subscribe to `Arc<T>`).
+//!
+//! The bus doesn't contain any unsafe code and works with any async runtime.
+//!
+//! ## Optional features
+//!
+//! - `futures`: Implements `futures_core::Stream` for [`Subscription`].
+//!
+//! # Examples
+//! ```rust
+//! use laika::bus::Bus;
+//!
+//! #[derive(Clone, Debug, PartialEq)]
+//! struct UserCreated(u32);
+//!
+//! #[derive(Clone, Debug, PartialEq)]
+//! struct CacheFlushed;
+//!
+//! let bus = Bus::new(16);
+//!
+//! let mut audit = bus.subscribe::<UserCreated>();
+//! let mut mailer = bus.subscribe::<UserCreated>();
+//! let mut metrics = bus.subscribe::<CacheFlushed>();
+//!
+//! assert_eq!(bus.publish_blocking(UserCreated(7)), 2);
+//! assert_eq!(bus.publish_blocking(CacheFlushed), 1);
+//! // Nobody subscribed to strings
+//! assert_eq!(bus.publish_blocking("ignored"), 0);
+//!
+//! assert_eq!(audit.try_recv(), Ok(UserCreated(7)));
+//! assert_eq!(mailer.try_recv(), Ok(UserCreated(7)));
+//! assert_eq!(metrics.try_recv(), Ok(CacheFlushed));
+//! ```
+
+use crate::mpsc::{self, Overflow, Receiver, Sender, TryRecvError};
+use alloc::{
+    boxed::Box,
+    collections::BTreeMap,
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+#[cfg(feature = "futures")]
+use core::pin::Pin;
+use core::{
+    any::{Any, TypeId},
+    fmt,
+    task::{Context, Poll},
+};
+use std::sync::{Mutex, MutexGuard};
+
+/// Subscribers of a bus, guarded by [`Shared::state`]
+struct State {
+    /// Senders of the subscriptions (a `Sender<T>` for messages of type `T`),
+    /// keyed by the type and the subscription id
+    subscribers: BTreeMap<TypeId, BTreeMap<u64, Box<dyn Any + Send>>>,
+    /// Id of the next subscription
+    next_id: u64,
+}
+
+/// State shared by all clones of a [`Bus`]
+struct Shared {
+    /// Subscribers
+    state: Mutex<State>,
+    /// Capacity of the queues of the subscriptions
+    capacity: usize,
+    /// What publishing to a full queue does
+    overflow: Overflow,
+}
+
+impl Shared {
+    /// Locks the state.
+    fn lock(&self) -> MutexGuard<'_, State> {
+        crate::lock(&self.state)
+    }
+
+    /// Removes the subscription with `id` to messages of type `type_id`.
+    fn unsubscribe(&self, type_id: TypeId, id: u64) {
+        let mut state = self.lock();
+
+        let Some(subscribers) = state.subscribers.get_mut(&type_id) else {
+            return;
+        };
+
+        let sender = subscribers.remove(&id);
+
+        if subscribers.is_empty() {
+            state.subscribers.remove(&type_id);
+        }
+
+        // The sender is dropped after unlocking, in case it's the last one and
+        // the `Drop` of queued messages uses the bus.
+        drop(state);
+        drop(sender);
+    }
+}
+
+/// In-process bus delivering messages to the subscribers of their type
+///
+/// See the [module documentation](self) for details.
+pub struct Bus {
+    /// State shared with all clones
+    shared: Arc<Shared>,
+}
+
+/// Subscription to the messages of type `T` published on a [`Bus`]
+///
+/// Dropping it unsubscribes.
+pub struct Subscription<T> {
+    /// Queue of delivered messages
+    receiver: Receiver<T>,
+    /// Bus to unsubscribe from
+    bus: Weak<Shared>,
+    /// Type of the messages, under which the subscription is registered
+    type_id: TypeId,
+    /// Id of this subscription
+    id: u64,
+}
+
+impl Bus {
+    /// Creates a bus without subscribers, queueing at most `capacity`
+    /// messages per subscription.
+    ///
+    /// # Panics
+    /// Panics if `capacity` is zero.
+    pub fn new(capacity: usize) -> Self {
+        Bus::with_overflow(capacity, Overflow::Block)
+    }
+
+    /// Creates a bus without subscribers, queueing at most `capacity`
+    /// messages per subscription and handling publishing to a full one by
+    /// `overflow`.
+    ///
+    /// # Panics
+    /// Panics if `capacity` is zero.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use laika::{bus::Bus, mpsc::Overflow};
+    ///
+    /// let bus = Bus::with_overflow(1, Overflow::DropOldest);
+    /// let mut ticks = bus.subscribe::<u64>();
+    ///
+    /// // Never waits, a slow subscriber only keeps the latest tick
+    /// bus.publish_blocking(1_u64);
+    /// bus.publish_blocking(2_u64);
+    ///
+    /// assert_eq!(ticks.try_recv(), Ok(2));
+    /// ```
+    pub fn with_overflow(capacity: usize, overflow: Overflow) -> Self {
+        assert!(capacity > 0, "a bus needs a capacity");
+
+        Bus {
+            shared: Arc::new(Shared {
+                state: Mutex::new(State {
+                    subscribers: BTreeMap::new(),
+                    next_id: 0,
+                }),
+                capacity,
+                overflow,
+            }),
+        }
+    }
+
+    /// Subscribes to the messages of type `T`.
+    pub fn subscribe<T>(&self) -> Subscription<T>
+    where
+        T: Send + 'static,
+    {
+        let (sender, receiver) =
+            mpsc::bounded_with_overflow(self.shared.capacity, self.shared.overflow);
+
+        let mut state = self.shared.lock();
+        let id = state.next_id;
+        state.next_id += 1;
+        state
+            .subscribers
+            .entry(TypeId::of::<T>())
+            .or_default()
+            .insert(id, Box::new(sender));
+
+        Subscription {
+            receiver,
+            bus: Arc::downgrade(&self.shared),
+            type_id: TypeId::of::<T>(),
+            id,
+        }
+    }
+
+    /// Returns the senders of the current subscriptions to messages of type
+    /// `T`.
+    fn senders<T>(&self) -> Vec<Sender<T>>
+    where
+        T: 'static,
+    {
+        self.shared
+            .lock()
+            .subscribers
+            .get(&TypeId::of::<T>())
+            .into_iter()
+            .flat_map(BTreeMap::values)
+            .filter_map(|sender| sender.downcast_ref::<Sender<T>>())
+            .cloned()
+            .collect()
+    }
+
+    /// Delivers `message` to all subscribers of its type, waiting
+    /// asynchronously until each of them has room for it. Subscribers that
+    /// are full don't wait if the bus has an [`Overflow`] policy other than
+    /// [`Block`](Overflow::Block), but handle the message by their policy.
+    ///
+    /// Returns the number of subscribers it has been delivered to.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # tokio::runtime::Builder::new_current_thread()
+    /// #     .build()
+    /// #     .unwrap()
+    /// #     .block_on(async {
+    /// use laika::bus::Bus;
+    ///
+    /// #[derive(Clone)]
+    /// struct Shutdown;
+    ///
+    /// let bus = Bus::new(1);
+    /// let mut shutdown = bus.subscribe::<Shutdown>();
+    ///
+    /// let plugin = tokio::spawn(async move {
+    ///     shutdown.recv().await;
+    ///     "stopped"
+    /// });
+    ///
+    /// assert_eq!(bus.publish(Shutdown).await, 1);
+    /// assert_eq!(plugin.await.unwrap(), "stopped");
+    /// # });
+    /// ```
+    pub async fn publish<T>(&self, message: T) -> usize
+    where
+        T: Clone + Send + 'static,
+    {
+        let mut delivered = 0;
+
+        for sender in self.senders::<T>() {
+            if sender.send(message.clone()).await.is_ok() {
+                delivered += 1;
+            }
+        }
+
+        delivered
+    }
+
+    /// Delivers `message` to all subscribers of its type, blocking the current
+    /// thread until each of them has room for it. See [`Bus::publish`].
+    pub fn publish_blocking<T>(&self, message: T) -> usize
+    where
+        T: Clone + Send + 'static,
+    {
+        self.senders::<T>()
+            .into_iter()
+            .filter(|sender| sender.send_blocking(message.clone()).is_ok())
+            .count()
+    }
+
+    /// Delivers `message` to all subscribers of its type that have room for
+    /// it. This function is **non-blocking**, subscribers that are full skip
+    /// the message (or handle it by the [`Overflow`] policy of the bus).
+    ///
+    /// Returns the number of subscribers it has been delivered to.
+    pub fn try_publish<T>(&self, message: T) -> usize
+    where
+        T: Clone + Send + 'static,
+    {
+        self.senders::<T>()
+            .into_iter()
+            .filter(|sender| sender.try_send(message.clone()).is_ok())
+            .count()
+    }
+
+    /// Returns the number of current subscribers to messages of type `T`.
+    pub fn subscriber_count<T>(&self) -> usize
+    where
+        T: 'static,
+    {
+        self.shared
+            .lock()
+            .subscribers
+            .get(&TypeId::of::<T>())
+            .map_or(0, BTreeMap::len)
+    }
+}
+
+impl Clone for Bus {
+    fn clone(&self) -> Self {
+        Bus {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+impl fmt::Debug for Bus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.shared.lock();
+
+        f.debug_struct("Bus")
+            .field("types", &state.subscribers.len())
+            .field(
+                "subscribers",
+                &state.subscribers.values().map(BTreeMap::len).sum::<usize>(),
+            )
+            .field("capacity", &self.shared.capacity)
+            .field("overflow", &self.shared.overflow)
+            .finish()
+    }
+}
+
+impl<T> Subscription<T> {
+    /// Receives the oldest delivered message if there is one. This function
+    /// is **non-blocking**.
+    ///
+    /// Returns [`TryRecvError::Empty`] if no message is queued or
+    /// [`TryRecvError::Disconnected`] if none is and the [`Bus`] has been
+    /// dropped.
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        self.receiver.try_recv()
+    }
+
+    /// Polls for the oldest delivered message, registering the waker of `cx`
+    /// if there is none yet. Resolves to [`None`] once no message is queued
+    /// and the [`Bus`] has been dropped.
+    pub fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        self.receiver.poll_recv(cx)
+    }
+
+    /// Receives the oldest delivered message, waiting asynchronously until
+    /// there is one. Resolves to [`None`] once no message is queued and the
+    /// [`Bus`] has been dropped.
+    pub async fn recv(&mut self) -> Option<T> {
+        self.receiver.recv().await
+    }
+
+    /// Receives the oldest delivered message, blocking the current thread
+    /// until there is one. See [`Subscription::recv`].
+    pub fn recv_blocking(&mut self) -> Option<T> {
+        self.receiver.recv_blocking()
+    }
+
+    /// Unsubscribes, dropping the queued messages. Same as dropping the
+    /// subscription.
+    pub fn unsubscribe(self) {}
+}
+
+/// Unsubscribes from the [`Bus`], so no more messages are delivered.
+impl<T> Drop for Subscription<T> {
+    fn drop(&mut self) {
+        if let Some(bus) = self.bus.upgrade() {
+            bus.unsubscribe(self.type_id, self.id);
+        }
+    }
+}
+
+impl<T> fmt::Debug for Subscription<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Subscription")
+            .field("id", &self.id)
+            .field("len", &self.receiver.len())
+            .finish()
+    }
+}
+
+/// Implement [`futures_core::Stream`] for [`Subscription`], yielding the
+/// delivered messages until the [`Bus`] has been dropped.
+#[cfg(feature = "futures")]
+impl<T> futures_core::Stream for Subscription<T> {
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        self.get_mut().poll_recv(cx)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::{string::String, thread, time::Duration};
+
+    #[test]
+    fn test_routing() {
+        let bus = Bus::new(4);
+
+        let mut numbers = bus.subscribe::<u32>();
+        let mut names = bus.subscribe::<String>();
+        let mut other_numbers = bus.subscribe::<u64>();
+
+        assert_eq!(bus.publish_blocking(1_u32), 1);
+        assert_eq!(bus.publish_blocking(String::from("laika")), 1);
+        assert_eq!(bus.publish_blocking('c'), 0);
+
+        assert_eq!(numbers.try_recv(), Ok(1));
+        assert_eq!(names.try_recv().as_deref(), Ok("laika"));
+        // Same shape, different type
+        assert_eq!(other_numbers.try_recv(), Err(TryRecvError::Empty));
+    }
+
+    #[test]
+    fn test_unsubscribe() {
+        let bus = Bus::new(1);
+        let publisher = bus.clone();
+
+        let mut first = bus.subscribe::<u8>();
+        let second = bus.subscribe::<u8>();
+        assert_eq!(bus.subscriber_count::<u8>(), 2);
+
+        second.unsubscribe();
+        assert_eq!(bus.subscriber_count::<u8>(), 1);
+        assert_eq!(publisher.try_publish(1_u8), 1);
+
+        // The queue is full, so it's skipped
+        assert_eq!(publisher.try_publish(2_u8), 0);
+
+        drop(bus);
+        drop(publisher);
+
+        // Queued messages are still received after the bus is gone
+        assert_eq!(first.try_recv(), Ok(1));
+        assert_eq!(first.try_recv(), Err(TryRecvError::Disconnected));
+    }
+
+    #[test]
+    fn test_backpressure() {
+        let bus = Bus::new(1);
+        let mut slow = bus.subscribe::<u32>();
+
+        let publisher = thread::spawn({
+            let bus = bus.clone();
+            move || {
+                (0..3_u32)
+                    .map(|value| bus.publish_blocking(value))
+                    .sum::<usize>()
+            }
+        });
+
+        thread::sleep(Duration::from_millis(20));
+        assert!(!publisher.is_finished());
+
+        drop(bus);
+
+        for value in 0..3 {
+            assert_eq!(slow.recv_blocking(), Some(value));
+        }
+
+        assert_eq!(publisher.join().unwrap(), 3);
+        assert_eq!(slow.recv_blocking(), None);
+    }
+
+    #[tokio::test]
+    async fn test_recv() {
+        let bus = Bus::new(2);
+        let mut events = bus.subscribe::<&'static str>();
+
+        let task = tokio::spawn(async move {
+            let mut count = 0;
+
+            while events.recv().await.is_some() {
+                count += 1;
+            }
+
+            count
+        });
+
+        for event in ["started", "loaded", "stopped"] {
+            assert_eq!(bus.publish(event).await, 1);
+        }
+
+        drop(bus);
+        assert_eq!(task.await.unwrap(), 3);
+    }
+}
@@ -0,0 +1,333 @@
+#![forbid(unsafe_code)]
+//! # Receiving values in chunks
+//!
+//! [`Chunks`] wraps a receiver and yields its values in chunks (`Vec<T>`) for
+//! downstream APIs that work on batches, like bulk inserts. A chunk is
+//! emitted once it holds `size` values or, with
+//! [`with_timeout`](Chunks::with_timeout), once the timeout elapsed since its
+//! first value arrived, so a slow trickle of values isn't held back
+//! indefinitely. The values buffered when the receiver has been closed are
+//! emitted as a last, smaller chunk.
+//!
+//! Any [`Source`] can be wrapped, i.e. the receivers of the channel modules
+//! (see [`merge`](crate::merge) for the list) or a [`Merge`] of them. Chunks
+//! are received with [`Chunks::next_chunk`] or blocking with
+//! [`Chunks::next_chunk_blocking`].
+//!
+//! Timeouts of chunks received asynchronously are tracked by a single
+//! background thread, so they work with any async runtime. The adapter doesn't
+//! contain any unsafe code.
+//!
+//! ## Optional features
+//!
+//! - `futures`: Implements `futures_core::Stream` for [`Chunks`].
+//!
+//! # Examples
+#![cfg_attr(feature = "mpsc", doc = "```rust")]
+#![cfg_attr(not(feature = "mpsc"), doc = "```ignore")]
+//! use laika::{chunks::Chunks, mpsc};
+//!
+//! let (tx, rx) = mpsc::unbounded();
+//!
+//! for value in 0..5 {
+//!     tx.send(value).unwrap();
+//! }
+//!
+//! drop(tx);
+//!
+//! let mut chunks = Chunks::new(rx, 2);
+//!
+//! assert_eq!(chunks.next_chunk_blocking(), Some(vec![0, 1]));
+//! assert_eq!(chunks.next_chunk_blocking(), Some(vec![2, 3]));
+//! assert_eq!(chunks.next_chunk_blocking(), Some(vec![4]));
+//! assert_eq!(chunks.next_chunk_blocking(), None);
+//! ```
+//!
+//! [`Merge`]: crate::merge::Merge
+
+use crate::{
+    merge::Source,
+    park,
+    timer::{self, alarm::Alarm},
+};
+use alloc::{boxed::Box, sync::Arc, vec::Vec};
+#[cfg(feature = "futures")]
+use core::pin::Pin;
+use core::{
+    fmt, mem,
+    task::{Context, Poll},
+};
+use std::time::{Duration, Instant};
+
+/// Adapter yielding the values of a receiver in chunks
+///
+/// See the [module documentation](self) for details.
+pub struct Chunks<T> {
+    /// Wrapped receiver, [`None`] once it has been closed
+    source: Option<Box<dyn Source<T> + Send>>,
+    /// Number of values of a full chunk
+    size: usize,
+    /// Time after which a partial chunk is emitted
+    timeout: Option<Duration>,
+    /// Values of the current chunk
+    chunk: Vec<T>,
+    /// When the current chunk is emitted, if it has a value and a timeout
+    deadline: Option<Instant>,
+    /// Alarm of the current chunk, if it has been scheduled
+    alarm: Option<Arc<Alarm>>,
+}
+
+impl<T> Unpin for Chunks<T> {}
+
+impl<T> Chunks<T> {
+    /// Wraps `source`, yielding chunks of `size` values.
+    ///
+    /// # Panics
+    /// Panics if `size` is zero.
+    pub fn new<S>(source: S, size: usize) -> Self
+    where
+        S: Source<T> + Send + 'static,
+    {
+        assert!(size > 0, "chunks need a size");
+
+        Chunks {
+            source: Some(Box::new(source)),
+            size,
+            timeout: None,
+            chunk: Vec::with_capacity(size),
+            deadline: None,
+            alarm: None,
+        }
+    }
+
+    /// Emits partial chunks once `timeout` elapsed since their first value
+    /// arrived.
+    ///
+    /// # Examples
+    #[cfg_attr(feature = "mpsc", doc = "```rust")]
+    #[cfg_attr(not(feature = "mpsc"), doc = "```ignore")]
+    /// use laika::{chunks::Chunks, mpsc};
+    /// use std::time::Duration;
+    ///
+    /// let (tx, rx) = mpsc::unbounded();
+    /// let mut chunks = Chunks::new(rx, 100).with_timeout(Duration::from_millis(10));
+    ///
+    /// tx.send("lonely").unwrap();
+    ///
+    /// // Doesn't wait for 99 more values
+    /// assert_eq!(chunks.next_chunk_blocking(), Some(vec!["lonely"]));
+    /// ```
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Returns the number of values buffered for the next chunk.
+    pub fn buffered(&self) -> usize {
+        self.chunk.len()
+    }
+
+    /// Takes the current chunk, starting an empty one.
+    fn take(&mut self) -> Vec<T> {
+        self.deadline = None;
+        self.alarm = None;
+
+        mem::replace(&mut self.chunk, Vec::with_capacity(self.size))
+    }
+
+    /// Polls the receiver until the chunk is full, it would block or it has
+    /// been closed. Afterwards, emits the chunk if it's due.
+    fn poll_chunk(&mut self, cx: &mut Context<'_>) -> Poll<Option<Vec<T>>> {
+        loop {
+            if self.chunk.len() == self.size {
+                return Poll::Ready(Some(self.take()));
+            }
+
+            let Some(source) = &mut self.source else {
+                break;
+            };
+
+            match source.poll_next(cx) {
+                Poll::Ready(Some(value)) => {
+                    if self.chunk.is_empty() {
+                        self.deadline = self
+                            .timeout
+                            .and_then(|timeout| Instant::now().checked_add(timeout));
+                    }
+
+                    self.chunk.push(value);
+                }
+                Poll::Ready(None) => self.source = None,
+                Poll::Pending => break,
+            }
+        }
+
+        if self.source.is_none() {
+            return match self.chunk.is_empty() {
+                true => Poll::Ready(None),
+                false => Poll::Ready(Some(self.take())),
+            };
+        }
+
+        if self
+            .deadline
+            .is_some_and(|deadline| deadline <= Instant::now())
+        {
+            return Poll::Ready(Some(self.take()));
+        }
+
+        Poll::Pending
+    }
+
+    /// Polls for the next chunk, registering the waker of `cx` with the
+    /// receiver (and the timer, if the chunk has a timeout) if it isn't due
+    /// yet. Resolves to [`None`] once the receiver has been closed and every
+    /// value has been emitted.
+    pub fn poll_next_chunk(&mut self, cx: &mut Context<'_>) -> Poll<Option<Vec<T>>> {
+        let poll = self.poll_chunk(cx);
+
+        if let (Poll::Pending, Some(deadline)) = (&poll, self.deadline) {
+            let alarm = self.alarm.get_or_insert_with(|| {
+                let alarm = Arc::new(Alarm::default());
+                timer::schedule(deadline, &alarm);
+
+                alarm
+            });
+
+            alarm.register(cx.waker());
+        }
+
+        poll
+    }
+
+    /// Receives the next chunk, waiting asynchronously until it's full or its
+    /// timeout elapsed. Resolves to [`None`] once the receiver has been closed
+    /// and every value has been emitted.
+    ///
+    /// # Examples
+    #[cfg_attr(feature = "mpsc", doc = "```rust")]
+    #[cfg_attr(not(feature = "mpsc"), doc = "```ignore")]
+    /// # tokio::runtime::Builder::new_current_thread()
+    /// #     .build()
+    /// #     .unwrap()
+    /// #     .block_on(async {
+    /// use laika::{chunks::Chunks, mpsc};
+    ///
+    /// let (tx, rx) = mpsc::bounded(16);
+    ///
+    /// tokio::spawn(async move {
+    ///     for value in 0..10 {
+    ///         tx.send(value).await.unwrap();
+    ///     }
+    /// });
+    ///
+    /// let mut chunks = Chunks::new(rx, 4);
+    /// let mut sizes = Vec::new();
+    ///
+    /// while let Some(chunk) = chunks.next_chunk().await {
+    ///     sizes.push(chunk.len());
+    /// }
+    ///
+    /// assert_eq!(sizes, [4, 4, 2]);
+    /// # });
+    /// ```
+    pub async fn next_chunk(&mut self) -> Option<Vec<T>> {
+        core::future::poll_fn(|cx| self.poll_next_chunk(cx)).await
+    }
+
+    /// Receives the next chunk, blocking the current thread until it's full
+    /// or its timeout elapsed. See [`Chunks::next_chunk`].
+    pub fn next_chunk_blocking(&mut self) -> Option<Vec<T>> {
+        park::block_on(|cx| self.poll_next_chunk(cx))
+    }
+}
+
+impl<T> fmt::Debug for Chunks<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Chunks")
+            .field("closed", &self.source.is_none())
+            .field("size", &self.size)
+            .field("timeout", &self.timeout)
+            .field("buffered", &self.chunk.len())
+            .finish()
+    }
+}
+
+/// Implement [`futures_core::Stream`] for [`Chunks`], yielding chunks until the
+/// receiver has been closed.
+#[cfg(feature = "futures")]
+impl<T> futures_core::Stream for Chunks<T> {
+    type Item = Vec<T>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Vec<T>>> {
+        self.get_mut().poll_next_chunk(cx)
+    }
+}
+
+#[cfg(all(test, feature = "mpsc"))]
+mod test {
+    use super::*;
+    use crate::mpsc;
+    use core::task::Waker;
+    use std::{thread, vec};
+
+    #[test]
+    fn test_size() {
+        let (tx, rx) = mpsc::unbounded();
+        let mut chunks = Chunks::new(rx, 3);
+        let mut cx = Context::from_waker(Waker::noop());
+
+        for value in 0..4 {
+            tx.send(value).unwrap();
+        }
+
+        assert_eq!(
+            chunks.poll_next_chunk(&mut cx),
+            Poll::Ready(Some(vec![0, 1, 2]))
+        );
+
+        // Partial chunks without a timeout wait for more values
+        assert_eq!(chunks.poll_next_chunk(&mut cx), Poll::Pending);
+        assert_eq!(chunks.buffered(), 1);
+
+        drop(tx);
+        assert_eq!(chunks.poll_next_chunk(&mut cx), Poll::Ready(Some(vec![3])));
+        assert_eq!(chunks.poll_next_chunk(&mut cx), Poll::Ready(None));
+    }
+
+    #[test]
+    fn test_timeout_blocking() {
+        let (tx, rx) = mpsc::unbounded();
+        let mut chunks = Chunks::new(rx, 10).with_timeout(Duration::from_millis(20));
+
+        let producer = thread::spawn(move || {
+            tx.send(1).unwrap();
+            tx.send(2).unwrap();
+            thread::sleep(Duration::from_millis(200));
+            tx.send(3).unwrap();
+        });
+
+        let start = Instant::now();
+
+        assert_eq!(chunks.next_chunk_blocking(), Some(vec![1, 2]));
+        assert!(start.elapsed() < Duration::from_millis(200));
+
+        assert_eq!(chunks.next_chunk_blocking(), Some(vec![3]));
+        assert_eq!(chunks.next_chunk_blocking(), None);
+        producer.join().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_timeout() {
+        let (tx, rx) = mpsc::unbounded();
+        let mut chunks = Chunks::new(rx, 10).with_timeout(Duration::from_millis(20));
+
+        tx.send(1).unwrap();
+
+        // Woken up by the timer thread, as no more values arrive
+        assert_eq!(chunks.next_chunk().await, Some(vec![1]));
+
+        drop(tx);
+        assert_eq!(chunks.next_chunk().await, None);
+    }
+}
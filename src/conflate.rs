@@ -0,0 +1,403 @@
+#![forbid(unsafe_code)]
+//! # A single-slot channel where newer values overwrite older ones
+//!
+//! A conflating channel holds at most one pending value. Sending replaces a
+//! value the [`Receiver`] hasn't taken yet, so it always gets the latest
+//! state and never works through stale ones. Sending never waits, a slow
+//! receiver just skips intermediate values.
+//!
+//! Unlike [`watch`](crate::watch) (where receivers borrow a value that stays
+//! in the channel), receiving moves the value out of the slot, so every value
+//! is received at most once and `T` doesn't need to be `Clone`. This fits
+//! telemetry or UI refresh pipelines, where only the freshest value is worth
+//! processing.
+//!
+//! The [`Sender`] can be cloned. Once all senders have been dropped, the
+//! receiver gets a last pending value and `None` afterwards. The channel is
+//! backed by a `std::sync::Mutex`, doesn't contain any unsafe code and works
+//! with any async runtime.
+//!
+//! ## Optional features
+//!
+//! - `futures`: Implements `futures_core::Stream` for the [`Receiver`].
+//!
+//! # Examples
+//! ```rust
+//! let (tx, mut rx) = laika::conflate::channel();
+//!
+//! tx.send(1).unwrap();
+//! tx.send(2).unwrap();
+//! tx.send(3).unwrap();
+//!
+//! // Only the latest value is kept
+//! assert_eq!(rx.recv_blocking(), Some(3));
+//!
+//! drop(tx);
+//! assert_eq!(rx.recv_blocking(), None);
+//! ```
+
+mod error;
+
+pub use error::{SendError, TryRecvError};
+
+use alloc::sync::Arc;
+#[cfg(feature = "futures")]
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+use std::sync::{Condvar, Mutex, MutexGuard, PoisonError};
+
+/// Slot and bookkeeping of a channel, guarded by [`Shared::state`]
+#[derive(Debug)]
+struct State<T> {
+    /// Latest value that hasn't been received yet
+    value: Option<T>,
+    /// Number of existing [`Sender`]s
+    senders: usize,
+    /// Whether the receiver still exists
+    receiver: bool,
+    /// Waker of the receiver while it's waiting for a value
+    waker: Option<Waker>,
+}
+
+impl<T> State<T> {
+    /// Takes the pending value if there is one.
+    fn take(&mut self) -> Result<T, TryRecvError> {
+        match self.value.take() {
+            Some(value) => Ok(value),
+            None if self.senders == 0 => Err(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+}
+
+/// State shared by all senders and the receiver of a channel
+#[derive(Debug)]
+struct Shared<T> {
+    /// Slot and bookkeeping
+    state: Mutex<State<T>>,
+    /// Notified when a value is sent or all senders are dropped, so that a
+    /// thread blocking in [`Receiver::recv_blocking`] wakes up.
+    condvar: Condvar,
+}
+
+impl<T> Shared<T> {
+    /// Locks the state.
+    fn lock(&self) -> MutexGuard<'_, State<T>> {
+        crate::lock(&self.state)
+    }
+
+    /// Wakes up the receiver. Takes its waker while `state` is locked but
+    /// wakes it after unlocking it.
+    fn wake(&self, mut state: MutexGuard<'_, State<T>>) {
+        let waker = state.waker.take();
+        drop(state);
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+
+        self.condvar.notify_one();
+    }
+}
+
+/// Sending side of a conflating channel
+///
+/// Can be cloned to send from multiple producers. The channel is closed once
+/// all senders have been dropped.
+#[derive(Debug)]
+pub struct Sender<T> {
+    /// State shared with all other senders and the receiver
+    shared: Arc<Shared<T>>,
+}
+
+/// Receiving side of a conflating channel
+///
+/// Always receives the latest sent value, skipping the ones that have been
+/// overwritten in the meantime.
+#[derive(Debug)]
+pub struct Receiver<T> {
+    /// State shared with the senders
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Sender<T> {
+    /// Puts a value into the slot, overwriting a value the receiver hasn't
+    /// taken yet. This function is **non-blocking**.
+    ///
+    /// Returns the overwritten value, if there was one, or a [`SendError`]
+    /// containing the value if the [`Receiver`] has been dropped.
+    ///
+    /// # Examples
+    /// ```rust
+    /// let (tx, mut rx) = laika::conflate::channel();
+    ///
+    /// assert_eq!(tx.send(1), Ok(None));
+    /// assert_eq!(tx.send(2), Ok(Some(1)));
+    ///
+    /// assert_eq!(rx.try_recv(), Ok(2));
+    /// assert_eq!(tx.send(3), Ok(None));
+    /// ```
+    pub fn send(&self, value: T) -> Result<Option<T>, SendError<T>> {
+        let mut state = self.shared.lock();
+
+        if !state.receiver {
+            return Err(SendError(value));
+        }
+
+        let overwritten = state.value.replace(value);
+        self.shared.wake(state);
+
+        Ok(overwritten)
+    }
+
+    /// Returns whether the [`Receiver`] has been dropped, so sending would
+    /// fail.
+    pub fn is_closed(&self) -> bool {
+        !self.shared.lock().receiver
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.shared.lock().senders += 1;
+
+        Sender {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+/// Closes the channel when the last [`Sender`] is dropped, waking up the
+/// receiver.
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.senders -= 1;
+
+        if state.senders == 0 {
+            self.shared.wake(state);
+        }
+    }
+}
+
+impl<T> Receiver<T> {
+    /// Takes the latest value if one is pending. This function is
+    /// **non-blocking**.
+    ///
+    /// Returns [`TryRecvError::Empty`] if no value is pending or
+    /// [`TryRecvError::Disconnected`] if none is and all senders have been
+    /// dropped.
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        self.shared.lock().take()
+    }
+
+    /// Polls for the latest value, registering the waker of `cx` if none is
+    /// pending. Resolves to [`None`] once no value is pending and all senders
+    /// have been dropped.
+    pub fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        let mut state = self.shared.lock();
+
+        match state.take() {
+            Ok(value) => Poll::Ready(Some(value)),
+            Err(TryRecvError::Disconnected) => Poll::Ready(None),
+            Err(TryRecvError::Empty) => {
+                if !state
+                    .waker
+                    .as_ref()
+                    .is_some_and(|waker| waker.will_wake(cx.waker()))
+                {
+                    state.waker = Some(cx.waker().clone());
+                }
+
+                Poll::Pending
+            }
+        }
+    }
+
+    /// Takes the latest value, waiting asynchronously until one is sent.
+    /// Resolves to [`None`] once no value is pending and all senders have been
+    /// dropped.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # tokio::runtime::Builder::new_current_thread()
+    /// #     .build()
+    /// #     .unwrap()
+    /// #     .block_on(async {
+    /// let (tx, mut rx) = laika::conflate::channel();
+    ///
+    /// tokio::spawn(async move {
+    ///     for progress in 0..=100 {
+    ///         tx.send(progress).unwrap();
+    ///     }
+    /// });
+    ///
+    /// let mut last = 0;
+    ///
+    /// while let Some(progress) = rx.recv().await {
+    ///     // Values may be skipped, but never arrive out of order
+    ///     assert!(progress >= last);
+    ///     last = progress;
+    /// }
+    ///
+    /// assert_eq!(last, 100);
+    /// # });
+    /// ```
+    pub async fn recv(&mut self) -> Option<T> {
+        core::future::poll_fn(|cx| self.poll_recv(cx)).await
+    }
+
+    /// Takes the latest value, blocking the current thread until one is sent.
+    /// See [`Receiver::recv`].
+    pub fn recv_blocking(&mut self) -> Option<T> {
+        let mut state = self.shared.lock();
+
+        loop {
+            match state.take() {
+                Ok(value) => return Some(value),
+                Err(TryRecvError::Disconnected) => return None,
+                Err(TryRecvError::Empty) => {
+                    state = self
+                        .shared
+                        .condvar
+                        .wait(state)
+                        .unwrap_or_else(PoisonError::into_inner);
+                }
+            }
+        }
+    }
+
+    /// Returns whether a value is pending.
+    pub fn has_pending(&self) -> bool {
+        self.shared.lock().value.is_some()
+    }
+}
+
+/// Closes the channel for the senders and drops a pending value.
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.receiver = false;
+        state.waker = None;
+
+        // The value is dropped after unlocking, in case its `Drop` uses the
+        // channel.
+        let value = state.value.take();
+        drop(state);
+        drop(value);
+    }
+}
+
+/// Implement [`futures_core::Stream`] for [`Receiver`], yielding the latest
+/// values until all senders have been dropped.
+#[cfg(feature = "futures")]
+impl<T> futures_core::Stream for Receiver<T> {
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        self.get_mut().poll_recv(cx)
+    }
+}
+
+/// Creates a conflating channel with an empty slot.
+pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            value: None,
+            senders: 1,
+            receiver: true,
+            waker: None,
+        }),
+        condvar: Condvar::new(),
+    });
+
+    let receiver = Receiver {
+        shared: Arc::clone(&shared),
+    };
+
+    (Sender { shared }, receiver)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::{thread, time::Duration, vec::Vec};
+
+    #[test]
+    fn test_overwrite() {
+        let (tx, mut rx) = channel();
+        let mut cx = Context::from_waker(Waker::noop());
+
+        assert_eq!(rx.poll_recv(&mut cx), Poll::Pending);
+        assert!(tx.shared.lock().waker.is_some());
+
+        assert_eq!(tx.send(1), Ok(None));
+        assert!(tx.shared.lock().waker.is_none());
+        assert_eq!(tx.send(2), Ok(Some(1)));
+        assert!(rx.has_pending());
+
+        assert_eq!(rx.poll_recv(&mut cx), Poll::Ready(Some(2)));
+        assert!(!rx.has_pending());
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+    }
+
+    #[test]
+    fn test_close() {
+        let (tx, mut rx) = channel();
+        let tx1 = tx.clone();
+
+        tx.send(1).unwrap();
+        drop(tx);
+        drop(tx1);
+
+        // A pending value is still received after the senders are gone
+        assert_eq!(rx.try_recv(), Ok(1));
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Disconnected));
+        assert_eq!(rx.recv_blocking(), None);
+
+        let (tx, rx) = channel();
+        drop(rx);
+
+        assert!(tx.is_closed());
+        assert_eq!(tx.send(1), Err(SendError(1)));
+    }
+
+    #[test]
+    fn test_recv_blocking() {
+        let (tx, mut rx) = channel();
+
+        let producer = thread::spawn(move || {
+            for value in 0..1000 {
+                tx.send(value).unwrap();
+            }
+        });
+
+        thread::sleep(Duration::from_millis(10));
+
+        let mut values = Vec::new();
+
+        while let Some(value) = rx.recv_blocking() {
+            values.push(value);
+        }
+
+        producer.join().unwrap();
+
+        assert!(values.is_sorted());
+        assert_eq!(values.last(), Some(&999));
+    }
+
+    #[tokio::test]
+    async fn test_recv() {
+        let (tx, mut rx) = channel();
+
+        let producer = tokio::spawn(async move {
+            tx.send(1).unwrap();
+            tx.send(2).unwrap();
+        });
+
+        producer.await.unwrap();
+
+        assert_eq!(rx.recv().await, Some(2));
+        assert_eq!(rx.recv().await, None);
+    }
+}
@@ -0,0 +1,45 @@
+#![forbid(unsafe_code)]
+//! # Counters for concurrent code
+//!
+//! Counters that are shared by many threads or tasks:
+//!
+//! - [`Sharded`]: Spreads increments over multiple cache lines, so counting on
+//!   a hot path doesn't make all threads contend for a single atomic. Reading
+//!   the total sums up the shards.
+//! - [`Waitable`]: Lets tasks wait until the value matches a condition, e.g.
+//!   to start a phase once 100 items have been processed. Changing the value
+//!   wakes up the matching waiters.
+//!
+//! The counters don't contain any unsafe code and work with any async
+//! runtime.
+//!
+//! # Examples
+//! ```rust
+//! use laika::counter::Sharded;
+//! use std::{sync::Arc, thread};
+//!
+//! let requests = Arc::new(Sharded::new());
+//!
+//! let workers: Vec<_> = (0..4)
+//!     .map(|_| {
+//!         let requests = Arc::clone(&requests);
+//!         thread::spawn(move || {
+//!             for _ in 0..1000 {
+//!                 requests.increment();
+//!             }
+//!         })
+//!     })
+//!     .collect();
+//!
+//! for worker in workers {
+//!     worker.join().unwrap();
+//! }
+//!
+//! assert_eq!(requests.sum(), 4000);
+//! ```
+
+mod sharded;
+mod waitable;
+
+pub use sharded::Sharded;
+pub use waitable::{WaitUntil, Waitable};
@@ -0,0 +1,204 @@
+//! Counter striped over multiple cache lines.
+
+use core::{
+    fmt,
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+};
+use std::{boxed::Box, thread, vec::Vec};
+
+/// Id of the next thread using a sharded counter, to spread threads over the
+/// shards
+static NEXT_THREAD: AtomicUsize = AtomicUsize::new(0);
+
+std::thread_local! {
+    /// Id of the current thread, picking its shard
+    static THREAD: usize = NEXT_THREAD.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Part of a [`Sharded`] counter, aligned to its own cache line (or pair of
+/// them, as some CPUs prefetch adjacent lines), so shards don't contend
+#[repr(align(128))]
+#[derive(Default)]
+struct Shard(AtomicU64);
+
+/// Counter spread over multiple shards
+///
+/// Every thread adds to a shard of its own (as far as there are enough), so
+/// threads counting at the same time don't contend for a single atomic.
+/// [`Sharded::sum`] sums up all shards: it's eventually consistent, i.e. it
+/// includes all additions that finished before, but additions happening at
+/// the same time may or may not be included.
+///
+/// Arithmetic wraps around on overflow. Subtracting is supported as well, so
+/// the counter can track e.g. the number of open connections, as long as the
+/// sum doesn't become negative.
+///
+/// See the [module documentation](super) for details.
+pub struct Sharded {
+    /// Shards, a power of two
+    shards: Box<[Shard]>,
+}
+
+impl Sharded {
+    /// Creates a counter with one shard per CPU core (rounded up to a power of
+    /// two), starting at 0.
+    pub fn new() -> Self {
+        let cores = thread::available_parallelism().map_or(1, |cores| cores.get());
+
+        Sharded::with_shards(cores)
+    }
+
+    /// Creates a counter with `shards` shards (rounded up to a power of two),
+    /// starting at 0. More shards make contention less likely, but summing
+    /// up slower.
+    ///
+    /// # Panics
+    /// Panics if `shards` is 0.
+    pub fn with_shards(shards: usize) -> Self {
+        assert!(shards > 0, "a sharded counter needs at least one shard");
+
+        let shards: Vec<_> = (0..shards.next_power_of_two())
+            .map(|_| Shard::default())
+            .collect();
+
+        Sharded {
+            shards: shards.into_boxed_slice(),
+        }
+    }
+
+    /// Returns the shard of the current thread.
+    fn shard(&self) -> &AtomicU64 {
+        let thread = THREAD.with(|thread| *thread);
+
+        &self.shards[thread & (self.shards.len() - 1)].0
+    }
+
+    /// Adds `n` to the counter.
+    pub fn add(&self, n: u64) {
+        self.shard().fetch_add(n, Ordering::Relaxed);
+    }
+
+    /// Subtracts `n` from the counter.
+    pub fn sub(&self, n: u64) {
+        self.shard().fetch_sub(n, Ordering::Relaxed);
+    }
+
+    /// Adds 1 to the counter.
+    pub fn increment(&self) {
+        self.add(1);
+    }
+
+    /// Subtracts 1 from the counter.
+    pub fn decrement(&self) {
+        self.sub(1);
+    }
+
+    /// Returns the sum of all shards. Additions and subtractions happening at
+    /// the same time may or may not be included.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use laika::counter::Sharded;
+    ///
+    /// let connections = Sharded::with_shards(4);
+    ///
+    /// connections.add(3);
+    /// connections.decrement();
+    ///
+    /// assert_eq!(connections.sum(), 2);
+    /// ```
+    pub fn sum(&self) -> u64 {
+        self.shards.iter().fold(0, |sum, shard| {
+            sum.wrapping_add(shard.0.load(Ordering::Relaxed))
+        })
+    }
+
+    /// Resets all shards to 0 and returns their sum, e.g. to report the
+    /// number of events per interval. Every addition is either included in
+    /// the sum or kept for the next one, none is lost.
+    pub fn take(&self) -> u64 {
+        self.shards.iter().fold(0, |sum, shard| {
+            sum.wrapping_add(shard.0.swap(0, Ordering::Relaxed))
+        })
+    }
+
+    /// Returns the number of shards.
+    pub fn shards(&self) -> usize {
+        self.shards.len()
+    }
+}
+
+impl Default for Sharded {
+    fn default() -> Self {
+        Sharded::new()
+    }
+}
+
+impl fmt::Debug for Sharded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sharded")
+            .field("sum", &self.sum())
+            .field("shards", &self.shards.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::sync::Arc;
+
+    #[test]
+    fn test_shards() {
+        assert_eq!(Sharded::with_shards(1).shards(), 1);
+        assert_eq!(Sharded::with_shards(3).shards(), 4);
+        assert!(Sharded::new().shards().is_power_of_two());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_no_shards() {
+        Sharded::with_shards(0);
+    }
+
+    #[test]
+    fn test_wrapping() {
+        let counter = Sharded::with_shards(2);
+
+        // Subtracted on another shard than added
+        counter.add(5);
+        thread::scope(|scope| {
+            scope.spawn(|| counter.sub(2));
+        });
+
+        assert_eq!(counter.sum(), 3);
+        assert_eq!(counter.take(), 3);
+        assert_eq!(counter.sum(), 0);
+    }
+
+    #[test]
+    fn test_concurrent() {
+        let counter = Arc::new(Sharded::with_shards(8));
+
+        let workers: Vec<_> = (0..8)
+            .map(|_| {
+                let counter = Arc::clone(&counter);
+                thread::spawn(move || {
+                    for _ in 0..10_000 {
+                        counter.increment();
+                    }
+                })
+            })
+            .collect();
+
+        let mut taken = 0;
+
+        for worker in workers {
+            taken += counter.take();
+            worker.join().unwrap();
+        }
+
+        // Nothing lost while taking at the same time
+        assert_eq!(taken + counter.sum(), 80_000);
+    }
+}
@@ -0,0 +1,524 @@
+//! Counter that tasks can wait on until it reaches a threshold.
+
+use alloc::{boxed::Box, collections::BTreeMap, vec::Vec};
+use core::{
+    fmt,
+    future::Future,
+    pin::Pin,
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+    task::{Context, Poll, Waker},
+};
+use std::{
+    panic::{self, AssertUnwindSafe},
+    sync::{Condvar, Mutex, MutexGuard, PoisonError},
+    thread,
+};
+
+/// Condition a waiter waits for
+type Predicate = Box<dyn Fn(u64) -> bool + Send>;
+
+/// Task or thread waiting for a [`Waitable`] counter
+struct Waiter {
+    /// Condition it waits for
+    predicate: Predicate,
+    /// Waker of the task, [`None`] for threads waiting on the condition
+    /// variable
+    waker: Option<Waker>,
+    /// Value that matched the condition once it did, or the panic of the
+    /// condition to raise on the waiter's own thread
+    outcome: Option<thread::Result<u64>>,
+}
+
+/// Waiters of a [`Waitable`] counter, guarded by [`Waitable::state`]
+struct State {
+    /// Waiters keyed by id
+    waiters: BTreeMap<u64, Waiter>,
+    /// Id of the next waiter
+    next_id: u64,
+}
+
+/// Atomic counter that tasks can wait on until its value matches a condition
+///
+/// Changing the value is a single atomic operation as long as nobody is
+/// waiting. Otherwise, the conditions of the waiters are checked against the
+/// new value and the matching waiters are woken up. A waiter completes with
+/// the value that matched, even if the counter changed again before it ran.
+/// If the value is changed by multiple threads at the same time, a value that
+/// is replaced right away may not be checked, so prefer conditions like
+/// `n >= 100` over `n == 100`.
+///
+/// Conditions are checked while the waiters are locked, so they should be
+/// cheap and must not use the counter themselves. If a condition panics while
+/// checking a changed value, the panic is raised by its waiter instead of by
+/// the thread that changed the counter. Arithmetic wraps around on overflow.
+///
+/// See the [module documentation](super) for details.
+pub struct Waitable {
+    /// Value of the counter
+    value: AtomicU64,
+    /// Number of waiters, so changing the value only locks the waiters if
+    /// there are any
+    waiting: AtomicUsize,
+    /// Waiters and their conditions
+    state: Mutex<State>,
+    /// Wakes up threads blocking in [`Waitable::wait_until_blocking`]
+    condvar: Condvar,
+}
+
+/// Future returned by [`Waitable::wait_until`]
+///
+/// Resolves to the value that matched the condition.
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct WaitUntil<'a, F> {
+    /// Counter to wait on
+    counter: &'a Waitable,
+    /// Condition, until it's handed to the counter
+    predicate: Option<F>,
+    /// Id while waiting
+    id: Option<u64>,
+}
+
+impl Waitable {
+    /// Creates a counter starting at `value`. As it's a const function, the
+    /// counter can be placed in a `static`.
+    pub const fn new(value: u64) -> Self {
+        Waitable {
+            value: AtomicU64::new(value),
+            waiting: AtomicUsize::new(0),
+            state: Mutex::new(State {
+                waiters: BTreeMap::new(),
+                next_id: 0,
+            }),
+            condvar: Condvar::new(),
+        }
+    }
+
+    /// Locks the state. Conditions are the only user code run while it's
+    /// locked, and they run either before a waiter is added or with their
+    /// panics caught, so the state stays consistent.
+    fn lock(&self) -> MutexGuard<'_, State> {
+        crate::lock(&self.state)
+    }
+
+    /// Returns the current value.
+    pub fn get(&self) -> u64 {
+        self.value.load(Ordering::SeqCst)
+    }
+
+    /// Sets the value, waking up the waiters it matches.
+    pub fn set(&self, value: u64) {
+        self.value.store(value, Ordering::SeqCst);
+        self.changed();
+    }
+
+    /// Adds `n` to the counter, waking up the waiters the new value matches.
+    /// Returns the new value.
+    pub fn add(&self, n: u64) -> u64 {
+        let value = self.value.fetch_add(n, Ordering::SeqCst).wrapping_add(n);
+        self.changed();
+
+        value
+    }
+
+    /// Subtracts `n` from the counter, waking up the waiters the new value
+    /// matches. Returns the new value.
+    pub fn sub(&self, n: u64) -> u64 {
+        let value = self.value.fetch_sub(n, Ordering::SeqCst).wrapping_sub(n);
+        self.changed();
+
+        value
+    }
+
+    /// Adds 1 to the counter. See [`Waitable::add`].
+    pub fn increment(&self) -> u64 {
+        self.add(1)
+    }
+
+    /// Subtracts 1 from the counter. See [`Waitable::sub`].
+    pub fn decrement(&self) -> u64 {
+        self.sub(1)
+    }
+
+    /// Checks the conditions of the waiters after the value changed.
+    fn changed(&self) {
+        // Pairs with registering a waiter: either the waiter sees the new
+        // value, or this sees the waiter
+        if self.waiting.load(Ordering::SeqCst) == 0 {
+            return;
+        }
+
+        let mut state = self.lock();
+        let value = self.value.load(Ordering::SeqCst);
+        let mut wakers = Vec::new();
+        let mut blocking = false;
+
+        for waiter in state.waiters.values_mut() {
+            if waiter.outcome.is_some() {
+                continue;
+            }
+
+            // A panicking condition must neither take down the thread that
+            // changed the value nor keep the other waiters from being woken
+            let outcome = match panic::catch_unwind(AssertUnwindSafe(|| (waiter.predicate)(value)))
+            {
+                Ok(false) => continue,
+                Ok(true) => Ok(value),
+                Err(payload) => Err(payload),
+            };
+
+            waiter.outcome = Some(outcome);
+
+            match &waiter.waker {
+                Some(waker) => wakers.push(waker.clone()),
+                None => blocking = true,
+            }
+        }
+
+        drop(state);
+
+        for waker in wakers {
+            waker.wake();
+        }
+
+        if blocking {
+            self.condvar.notify_all();
+        }
+    }
+
+    /// Registers a waiter for `predicate` unless the current value matches it
+    /// already. Returns the id of the waiter or the matching value.
+    fn register<F>(&self, state: &mut State, predicate: F, waker: Option<Waker>) -> Result<u64, u64>
+    where
+        F: Fn(u64) -> bool + Send + 'static,
+    {
+        // Pairs with `changed`, see there
+        self.waiting.fetch_add(1, Ordering::SeqCst);
+        let value = self.value.load(Ordering::SeqCst);
+
+        match panic::catch_unwind(AssertUnwindSafe(|| predicate(value))) {
+            Ok(false) => {}
+            Ok(true) => {
+                self.waiting.fetch_sub(1, Ordering::SeqCst);
+                return Err(value);
+            }
+            Err(payload) => {
+                self.waiting.fetch_sub(1, Ordering::SeqCst);
+                panic::resume_unwind(payload);
+            }
+        }
+
+        let id = state.next_id;
+        state.next_id += 1;
+        state.waiters.insert(
+            id,
+            Waiter {
+                predicate: Box::new(predicate),
+                waker,
+                outcome: None,
+            },
+        );
+
+        Ok(id)
+    }
+
+    /// Removes the waiter with `id`, returning the outcome of its condition
+    /// (if any).
+    fn unregister(&self, state: &mut State, id: u64) -> Option<thread::Result<u64>> {
+        let waiter = state.waiters.remove(&id)?;
+        self.waiting.fetch_sub(1, Ordering::SeqCst);
+
+        waiter.outcome
+    }
+
+    /// Waits asynchronously until the value matches `predicate` and returns
+    /// the matching value. Completes right away if the current value matches.
+    ///
+    /// # Panics
+    /// Polling the future panics if `predicate` panics.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # tokio::runtime::Builder::new_current_thread()
+    /// #     .build()
+    /// #     .unwrap()
+    /// #     .block_on(async {
+    /// use laika::counter::Waitable;
+    /// use std::sync::Arc;
+    ///
+    /// let processed = Arc::new(Waitable::new(0));
+    ///
+    /// let phase_2 = tokio::spawn({
+    ///     let processed = Arc::clone(&processed);
+    ///     async move { processed.wait_until(|n| n >= 100).await }
+    /// });
+    ///
+    /// for _item in 0..150 {
+    ///     processed.increment();
+    ///     tokio::task::yield_now().await;
+    /// }
+    ///
+    /// // Started as soon as 100 items were processed
+    /// assert_eq!(phase_2.await.unwrap(), 100);
+    /// # });
+    /// ```
+    pub fn wait_until<F>(&self, predicate: F) -> WaitUntil<'_, F>
+    where
+        F: Fn(u64) -> bool + Send + 'static,
+    {
+        WaitUntil {
+            counter: self,
+            predicate: Some(predicate),
+            id: None,
+        }
+    }
+
+    /// Blocks the current thread until the value matches `predicate` and
+    /// returns the matching value. See [`Waitable::wait_until`].
+    ///
+    /// # Panics
+    /// Panics if `predicate` panics.
+    pub fn wait_until_blocking<F>(&self, predicate: F) -> u64
+    where
+        F: Fn(u64) -> bool + Send + 'static,
+    {
+        let mut state = self.lock();
+
+        let id = match self.register(&mut state, predicate, None) {
+            Ok(id) => id,
+            Err(value) => return value,
+        };
+
+        loop {
+            if state.waiters.get(&id).is_some_and(|w| w.outcome.is_some()) {
+                if let Some(outcome) = self.unregister(&mut state, id) {
+                    drop(state);
+                    return outcome.unwrap_or_else(|payload| panic::resume_unwind(payload));
+                }
+            }
+
+            state = self
+                .condvar
+                .wait(state)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+    }
+
+    /// Returns the number of tasks and threads waiting right now.
+    pub fn waiting(&self) -> usize {
+        self.waiting.load(Ordering::SeqCst)
+    }
+}
+
+impl Default for Waitable {
+    fn default() -> Self {
+        Waitable::new(0)
+    }
+}
+
+impl fmt::Debug for Waitable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Waitable")
+            .field("value", &self.get())
+            .field("waiting", &self.waiting())
+            .finish()
+    }
+}
+
+impl<F> Future for WaitUntil<'_, F>
+where
+    F: Fn(u64) -> bool + Send + 'static,
+{
+    type Output = u64;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let counter = this.counter;
+        let mut state = counter.lock();
+
+        let Some(id) = this.id else {
+            let predicate = this
+                .predicate
+                .take()
+                .expect("`WaitUntil` polled after completion");
+
+            return match counter.register(&mut state, predicate, Some(cx.waker().clone())) {
+                Ok(id) => {
+                    this.id = Some(id);
+                    Poll::Pending
+                }
+                Err(value) => Poll::Ready(value),
+            };
+        };
+
+        let Some(waiter) = state.waiters.get_mut(&id) else {
+            panic!("`WaitUntil` polled after completion");
+        };
+
+        if waiter.outcome.is_none() {
+            if !waiter
+                .waker
+                .as_ref()
+                .is_some_and(|w| w.will_wake(cx.waker()))
+            {
+                waiter.waker = Some(cx.waker().clone());
+            }
+
+            return Poll::Pending;
+        }
+
+        this.id = None;
+
+        let Some(outcome) = counter.unregister(&mut state, id) else {
+            unreachable!("the waiter matched");
+        };
+
+        drop(state);
+
+        match outcome {
+            Ok(value) => Poll::Ready(value),
+            Err(payload) => panic::resume_unwind(payload),
+        }
+    }
+}
+
+// The predicate is only moved, never pinned
+impl<F> Unpin for WaitUntil<'_, F> {}
+
+impl<F> Drop for WaitUntil<'_, F> {
+    fn drop(&mut self) {
+        if let Some(id) = self.id {
+            let mut state = self.counter.lock();
+            self.counter.unregister(&mut state, id);
+        }
+    }
+}
+
+impl<F> fmt::Debug for WaitUntil<'_, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WaitUntil")
+            .field("counter", &self.counter)
+            .field("waiting", &self.id.is_some())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::sync::Arc;
+    use core::pin::pin;
+    use std::{thread, time::Duration};
+
+    #[test]
+    fn test_wait_until() {
+        let counter = Waitable::new(0);
+        let mut cx = Context::from_waker(Waker::noop());
+
+        let mut ten = pin!(counter.wait_until(|n| n >= 10));
+        let mut five = pin!(counter.wait_until(|n| n >= 5));
+        assert!(ten.as_mut().poll(&mut cx).is_pending());
+        assert!(five.as_mut().poll(&mut cx).is_pending());
+        assert_eq!(counter.waiting(), 2);
+
+        assert_eq!(counter.add(7), 7);
+        assert!(ten.as_mut().poll(&mut cx).is_pending());
+        assert_eq!(counter.waiting(), 2);
+
+        // Completes with the matching value, even though it changed since
+        counter.set(0);
+        assert_eq!(five.as_mut().poll(&mut cx), Poll::Ready(7));
+        assert_eq!(counter.waiting(), 1);
+
+        counter.add(10);
+        assert_eq!(ten.as_mut().poll(&mut cx), Poll::Ready(10));
+        assert_eq!(counter.waiting(), 0);
+    }
+
+    #[test]
+    fn test_ready() {
+        let counter = Waitable::new(3);
+        let mut cx = Context::from_waker(Waker::noop());
+
+        assert_eq!(
+            pin!(counter.wait_until(|n| n == 3)).poll(&mut cx),
+            Poll::Ready(3)
+        );
+        assert_eq!(counter.waiting(), 0);
+
+        {
+            let mut cancelled = pin!(counter.wait_until(|n| n == 0));
+            assert!(cancelled.as_mut().poll(&mut cx).is_pending());
+        }
+
+        assert_eq!(counter.waiting(), 0);
+        assert_eq!(counter.decrement(), 2);
+        assert_eq!(counter.sub(3), u64::MAX);
+    }
+
+    #[test]
+    fn test_wait_until_blocking() {
+        let counter = Arc::new(Waitable::default());
+
+        let waiter = thread::spawn({
+            let counter = Arc::clone(&counter);
+            move || counter.wait_until_blocking(|n| n % 2 == 1)
+        });
+
+        while counter.waiting() == 0 {
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        counter.add(2);
+        counter.add(3);
+
+        assert_eq!(waiter.join().unwrap(), 5);
+    }
+
+    #[test]
+    fn test_panicking_predicate() {
+        let counter = Waitable::new(0);
+        let mut cx = Context::from_waker(Waker::noop());
+
+        let mut panicking = pin!(counter.wait_until(|n| n >= 5 && panic!("condition")));
+        let mut five = pin!(counter.wait_until(|n| n >= 5));
+        assert!(panicking.as_mut().poll(&mut cx).is_pending());
+        assert!(five.as_mut().poll(&mut cx).is_pending());
+
+        // Changing the value neither panics nor skips the other waiter
+        assert_eq!(counter.add(5), 5);
+        assert_eq!(five.as_mut().poll(&mut cx), Poll::Ready(5));
+
+        let result = panic::catch_unwind(AssertUnwindSafe(|| panicking.as_mut().poll(&mut cx)));
+        assert!(result.is_err());
+        assert_eq!(counter.waiting(), 0);
+
+        // Panicking while registering doesn't leave the waiter counted
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            counter.wait_until_blocking(|_| panic!("condition"))
+        }));
+        assert!(result.is_err());
+        assert_eq!(counter.waiting(), 0);
+        assert_eq!(counter.increment(), 6);
+    }
+
+    #[tokio::test]
+    async fn test_incrementers() {
+        static PROCESSED: Waitable = Waitable::new(0);
+
+        let workers: Vec<_> = (0..4)
+            .map(|_| {
+                thread::spawn(|| {
+                    for _ in 0..250 {
+                        PROCESSED.increment();
+                    }
+                })
+            })
+            .collect();
+
+        assert!(PROCESSED.wait_until(|n| n >= 1000).await >= 1000);
+
+        for worker in workers {
+            worker.join().unwrap();
+        }
+    }
+}
@@ -0,0 +1,313 @@
+#![forbid(unsafe_code)]
+//! # Debouncing the values of a receiver
+//!
+//! [`Debounce`] wraps a receiver and holds back its values until no new value
+//! arrived for a quiet period. Only the final value of each burst is yielded,
+//! the earlier ones are discarded. This suits file watchers that report many
+//! events for a single save, or user input where only the state after typing
+//! stopped matters. The value held when the receiver has been closed is
+//! yielded right away.
+//!
+//! Any [`Source`] can be wrapped, i.e. the receivers of the channel modules
+//! (see [`merge`](crate::merge) for the list). A debounce is a [`Source`]
+//! itself. Values are received with [`Debounce::next`] or blocking with
+//! [`Debounce::next_blocking`].
+//!
+//! Quiet periods of debounces received asynchronously are tracked by a single
+//! background thread, so they work with any async runtime. The debounce
+//! doesn't contain any unsafe code.
+//!
+//! ## Optional features
+//!
+//! - `futures`: Implements `futures_core::Stream` for [`Debounce`].
+//!
+//! # Examples
+#![cfg_attr(feature = "mpsc", doc = "```rust")]
+#![cfg_attr(not(feature = "mpsc"), doc = "```ignore")]
+//! use laika::{debounce::Debounce, mpsc};
+//! use std::time::Duration;
+//!
+//! let (tx, rx) = mpsc::unbounded();
+//! let mut debounce = Debounce::new(rx, Duration::from_millis(20));
+//!
+//! for query in ["r", "ru", "rus", "rust"] {
+//!     tx.send(query).unwrap();
+//! }
+//!
+//! // Yielded once the input has been quiet for 20 ms
+//! assert_eq!(debounce.next_blocking(), Some("rust"));
+//! assert_eq!(debounce.discarded(), 3);
+//! ```
+
+use crate::{
+    merge::Source,
+    park,
+    timer::{self, alarm::Alarm},
+};
+use alloc::{boxed::Box, sync::Arc};
+#[cfg(feature = "futures")]
+use core::pin::Pin;
+use core::{
+    fmt,
+    task::{Context, Poll},
+};
+use std::time::{Duration, Instant};
+
+/// Adapter yielding the final value of each burst of a receiver
+///
+/// See the [module documentation](self) for details.
+pub struct Debounce<T> {
+    /// Wrapped receiver, [`None`] once it has been closed
+    source: Option<Box<dyn Source<T> + Send>>,
+    /// Time without new values after which the latest one is yielded
+    quiet: Duration,
+    /// Latest value of the current burst and when it's yielded, [`None`] if
+    /// the quiet period is too long to ever end
+    latest: Option<(T, Option<Instant>)>,
+    /// Number of values discarded in favor of a later one
+    discarded: u64,
+    /// Alarm and the time it rings, if it has been scheduled. Rings at the
+    /// latest when the held value is due, as later values only postpone it.
+    alarm: Option<(Instant, Arc<Alarm>)>,
+}
+
+impl<T> Unpin for Debounce<T> {}
+
+impl<T> Debounce<T> {
+    /// Wraps `source`, yielding its latest value once no new one arrived for
+    /// `quiet`, measured from when the debounce received it.
+    pub fn new<S>(source: S, quiet: Duration) -> Self
+    where
+        S: Source<T> + Send + 'static,
+    {
+        Debounce {
+            source: Some(Box::new(source)),
+            quiet,
+            latest: None,
+            discarded: 0,
+            alarm: None,
+        }
+    }
+
+    /// Returns the number of values that have been discarded since the
+    /// debounce has been created, as a later one arrived within the quiet
+    /// period.
+    pub fn discarded(&self) -> u64 {
+        self.discarded
+    }
+
+    /// Receives all values that are ready, keeping the latest one. Afterwards,
+    /// yields it if it's due (or the receiver has been closed).
+    fn poll_quiet(&mut self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        while let Some(source) = &mut self.source {
+            match source.poll_next(cx) {
+                Poll::Ready(Some(value)) => {
+                    let due = Instant::now().checked_add(self.quiet);
+
+                    if self.latest.replace((value, due)).is_some() {
+                        self.discarded += 1;
+                    }
+                }
+                Poll::Ready(None) => self.source = None,
+                Poll::Pending => break,
+            }
+        }
+
+        match self.latest.take() {
+            Some((value, _)) if self.source.is_none() => Poll::Ready(Some(value)),
+            Some((value, Some(due))) if due <= Instant::now() => Poll::Ready(Some(value)),
+            None if self.source.is_none() => Poll::Ready(None),
+            latest => {
+                self.latest = latest;
+                Poll::Pending
+            }
+        }
+    }
+
+    /// Polls for the next value, registering the waker of `cx` with the
+    /// receiver (and the timer, if a value is held back) if there is none yet.
+    /// Resolves to [`None`] once the receiver has been closed and the latest
+    /// value has been yielded.
+    pub fn poll_next(&mut self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        let poll = self.poll_quiet(cx);
+
+        if let (Poll::Pending, Some((_, Some(due)))) = (&poll, &self.latest) {
+            let due = *due;
+
+            // An alarm that rang (or is about to) won't wake up the task again
+            if self
+                .alarm
+                .as_ref()
+                .is_none_or(|(at, _)| *at <= Instant::now())
+            {
+                let alarm = Arc::new(Alarm::default());
+                timer::schedule(due, &alarm);
+
+                self.alarm = Some((due, alarm));
+            }
+
+            if let Some((_, alarm)) = &self.alarm {
+                alarm.register(cx.waker());
+            }
+        }
+
+        poll
+    }
+
+    /// Receives the next value, waiting asynchronously until a burst ended.
+    /// Resolves to [`None`] once the receiver has been closed and the latest
+    /// value has been yielded.
+    ///
+    /// # Examples
+    #[cfg_attr(feature = "mpsc", doc = "```rust")]
+    #[cfg_attr(not(feature = "mpsc"), doc = "```ignore")]
+    /// # tokio::runtime::Builder::new_current_thread()
+    /// #     .build()
+    /// #     .unwrap()
+    /// #     .block_on(async {
+    /// use laika::{debounce::Debounce, mpsc};
+    /// use std::time::Duration;
+    ///
+    /// let (tx, rx) = mpsc::unbounded();
+    /// let mut changes = Debounce::new(rx, Duration::from_millis(10));
+    ///
+    /// tx.send("config.toml modified").unwrap();
+    /// tx.send("config.toml modified").unwrap();
+    ///
+    /// assert_eq!(changes.next().await, Some("config.toml modified"));
+    ///
+    /// drop(tx);
+    /// assert_eq!(changes.next().await, None);
+    /// # });
+    /// ```
+    pub async fn next(&mut self) -> Option<T> {
+        core::future::poll_fn(|cx| self.poll_next(cx)).await
+    }
+
+    /// Receives the next value, blocking the current thread until a burst
+    /// ended. See [`Debounce::next`].
+    pub fn next_blocking(&mut self) -> Option<T> {
+        park::block_on(|cx| self.poll_next(cx))
+    }
+}
+
+impl<T> Source<T> for Debounce<T> {
+    fn poll_next(&mut self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        Debounce::poll_next(self, cx)
+    }
+}
+
+impl<T> fmt::Debug for Debounce<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Debounce")
+            .field("closed", &self.source.is_none())
+            .field("quiet", &self.quiet)
+            .field("pending", &self.latest.is_some())
+            .field("discarded", &self.discarded)
+            .finish()
+    }
+}
+
+/// Implement [`futures_core::Stream`] for [`Debounce`], yielding the final
+/// value of each burst until the receiver has been closed.
+#[cfg(feature = "futures")]
+impl<T> futures_core::Stream for Debounce<T> {
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        self.get_mut().poll_next(cx)
+    }
+}
+
+#[cfg(all(test, feature = "mpsc"))]
+mod test {
+    use super::*;
+    use crate::mpsc;
+    use core::task::Waker;
+    use std::{thread, vec::Vec};
+
+    #[test]
+    fn test_quiet() {
+        let (tx, rx) = mpsc::unbounded();
+        let mut debounce = Debounce::new(rx, Duration::from_millis(50));
+        let mut cx = Context::from_waker(Waker::noop());
+
+        tx.send(1).unwrap();
+        assert_eq!(debounce.poll_next(&mut cx), Poll::Pending);
+
+        // A new value restarts the quiet period
+        thread::sleep(Duration::from_millis(30));
+        tx.send(2).unwrap();
+        assert_eq!(debounce.poll_next(&mut cx), Poll::Pending);
+        thread::sleep(Duration::from_millis(30));
+        assert_eq!(debounce.poll_next(&mut cx), Poll::Pending);
+
+        thread::sleep(Duration::from_millis(30));
+        assert_eq!(debounce.poll_next(&mut cx), Poll::Ready(Some(2)));
+        assert_eq!(debounce.poll_next(&mut cx), Poll::Pending);
+
+        // The held value is yielded right away once the receiver is closed
+        tx.send(3).unwrap();
+        drop(tx);
+        assert_eq!(debounce.poll_next(&mut cx), Poll::Ready(Some(3)));
+        assert_eq!(debounce.poll_next(&mut cx), Poll::Ready(None));
+        assert_eq!(debounce.discarded(), 1);
+    }
+
+    #[test]
+    fn test_endless_quiet() {
+        let (tx, rx) = mpsc::unbounded();
+        let mut debounce = Debounce::new(rx, Duration::MAX);
+        let mut cx = Context::from_waker(Waker::noop());
+
+        // The value is never due, but still yielded once the receiver closes
+        tx.send(1).unwrap();
+        assert_eq!(debounce.poll_next(&mut cx), Poll::Pending);
+        assert!(debounce.alarm.is_none());
+
+        drop(tx);
+        assert_eq!(debounce.poll_next(&mut cx), Poll::Ready(Some(1)));
+    }
+
+    #[test]
+    fn test_next_blocking() {
+        let (tx, rx) = mpsc::unbounded();
+        let mut debounce = Debounce::new(rx, Duration::from_millis(40));
+
+        let producer = thread::spawn(move || {
+            for burst in 0..3 {
+                for event in 0..5 {
+                    tx.send((burst, event)).unwrap();
+                    thread::sleep(Duration::from_millis(2));
+                }
+
+                thread::sleep(Duration::from_millis(100));
+            }
+        });
+
+        let mut values = Vec::new();
+
+        while let Some(value) = debounce.next_blocking() {
+            values.push(value);
+        }
+
+        producer.join().unwrap();
+
+        // One value per burst, its last one
+        assert_eq!(values, [(0, 4), (1, 4), (2, 4)]);
+    }
+
+    #[tokio::test]
+    async fn test_next() {
+        let (tx, rx) = mpsc::unbounded();
+        let mut debounce = Debounce::new(rx, Duration::from_millis(20));
+
+        let start = Instant::now();
+        tx.send('a').unwrap();
+        tx.send('b').unwrap();
+
+        // Woken up by the timer thread after the quiet period
+        assert_eq!(debounce.next().await, Some('b'));
+        assert!(start.elapsed() >= Duration::from_millis(20));
+    }
+}
@@ -0,0 +1,638 @@
+#![forbid(unsafe_code)]
+//! # Async events
+//!
+//! An [`Event`] lets tasks wait until something happened, without depending
+//! on the synchronization types of an async runtime. It's created in one of
+//! two [modes](Reset):
+//!
+//! - [Manual reset](Event::manual): Once [set](Event::set), the event stays
+//!   latched and every waiter (current and future ones) proceeds right away
+//!   until it's [cleared](Event::clear) again. Useful for conditions like
+//!   "initialized" or "shutting down".
+//! - [Auto reset](Event::auto): Every [set](Event::set) lets exactly one
+//!   waiter proceed. If nobody is waiting, the event stays set until the next
+//!   waiter consumes it, so a notification is never lost. Useful for handing
+//!   work to one of several workers.
+//!
+//! Independent of the mode, [`Event::notify_waiters`] lets all tasks that are
+//! waiting right now proceed without setting the event, like
+//! `tokio::sync::Notify::notify_waiters`.
+//!
+//! Waiters are woken up in the order they started waiting. Events can be
+//! awaited ([`Event::wait`]) or block the current thread
+//! ([`Event::wait_blocking`]). They don't contain any unsafe code and work
+//! with any async runtime.
+//!
+//! # Examples
+//! ```rust
+//! # tokio::runtime::Builder::new_current_thread()
+//! #     .build()
+//! #     .unwrap()
+//! #     .block_on(async {
+//! use laika::event::Event;
+//! use std::sync::Arc;
+//!
+//! let ready = Arc::new(Event::manual());
+//!
+//! let worker = tokio::spawn({
+//!     let ready = Arc::clone(&ready);
+//!     async move {
+//!         ready.wait().await;
+//!         "started"
+//!     }
+//! });
+//!
+//! // Stays set, so later waiters don't wait at all
+//! ready.set();
+//! ready.wait().await;
+//!
+//! assert_eq!(worker.await.unwrap(), "started");
+//! # });
+//! ```
+
+use alloc::collections::BTreeMap;
+use core::{
+    fmt,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+use std::{
+    sync::{Condvar, Mutex, MutexGuard, PoisonError},
+    vec::Vec,
+};
+
+/// What setting an [`Event`] does
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Reset {
+    /// The event stays set, letting every waiter proceed, until it's cleared.
+    Manual,
+    /// Every set lets exactly one waiter proceed. If nobody is waiting, the
+    /// event stays set for the next waiter.
+    Auto,
+}
+
+/// Task or thread waiting for an [`Event`]
+struct Waiter {
+    /// Waker of the task, [`None`] for threads waiting on the condition
+    /// variable
+    waker: Option<Waker>,
+    /// Whether it has been notified and may proceed
+    notified: bool,
+    /// Whether it has been picked by setting an auto resetting event, so the
+    /// notification has to be passed on if it's dropped before observing it
+    picked: bool,
+}
+
+/// State of an [`Event`], guarded by [`Event::state`]
+struct State {
+    /// Whether the event is set
+    set: bool,
+    /// Waiters in the order they started waiting, keyed by id
+    waiters: BTreeMap<u64, Waiter>,
+    /// Id of the next waiter
+    next_id: u64,
+}
+
+/// Async event, either [manual](Reset::Manual) or [auto](Reset::Auto)
+/// resetting
+///
+/// See the [module documentation](self) for details.
+pub struct Event {
+    /// What setting the event does
+    reset: Reset,
+    /// Whether the event is set and its waiters
+    state: Mutex<State>,
+    /// Wakes up threads blocking in [`Event::wait_blocking`]
+    condvar: Condvar,
+}
+
+/// Future returned by [`Event::wait`]
+///
+/// Resolves once the event is set or [`Event::notify_waiters`] is called.
+/// If an auto resetting event picked this waiter, but it's dropped before
+/// observing it, the notification is passed on to the next waiter.
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Wait<'a> {
+    /// Event to wait for
+    event: &'a Event,
+    /// Id while waiting
+    id: Option<u64>,
+}
+
+impl State {
+    /// Adds a waiter and returns its id.
+    fn enqueue(&mut self, waker: Option<Waker>) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.waiters.insert(
+            id,
+            Waiter {
+                waker,
+                notified: false,
+                picked: false,
+            },
+        );
+
+        id
+    }
+
+    /// Takes the event for a waiter if it's set, resetting it if auto
+    /// resetting.
+    fn take(&mut self, reset: Reset) -> bool {
+        if !self.set {
+            return false;
+        }
+
+        if reset == Reset::Auto {
+            self.set = false;
+        }
+
+        true
+    }
+
+    /// Notifies the first waiter that hasn't been notified yet. Returns
+    /// whether there was one, appending its waker to `wakers`.
+    fn notify_one(&mut self, wakers: &mut Vec<Waker>) -> bool {
+        let Some(waiter) = self.waiters.values_mut().find(|waiter| !waiter.notified) else {
+            return false;
+        };
+
+        waiter.notified = true;
+        waiter.picked = true;
+        wakers.extend(waiter.waker.clone());
+
+        true
+    }
+
+    /// Notifies all waiters, appending their wakers to `wakers`.
+    fn notify_all(&mut self, wakers: &mut Vec<Waker>) {
+        for waiter in self.waiters.values_mut() {
+            if !waiter.notified {
+                waiter.notified = true;
+                wakers.extend(waiter.waker.clone());
+            }
+        }
+    }
+
+    /// Removes the waiter with `id`, returning whether it had been picked by
+    /// setting an auto resetting event.
+    fn remove(&mut self, id: u64) -> bool {
+        self.waiters.remove(&id).is_some_and(|waiter| waiter.picked)
+    }
+}
+
+impl Event {
+    /// Creates an event with the given [`Reset`] mode that isn't set. As it's
+    /// a const function, the event can be placed in a `static`.
+    pub const fn new(reset: Reset) -> Self {
+        Event {
+            reset,
+            state: Mutex::new(State {
+                set: false,
+                waiters: BTreeMap::new(),
+                next_id: 0,
+            }),
+            condvar: Condvar::new(),
+        }
+    }
+
+    /// Creates a manual resetting event, see [`Reset::Manual`].
+    pub const fn manual() -> Self {
+        Event::new(Reset::Manual)
+    }
+
+    /// Creates an auto resetting event, see [`Reset::Auto`].
+    pub const fn auto() -> Self {
+        Event::new(Reset::Auto)
+    }
+
+    /// Locks the state.
+    fn lock(&self) -> MutexGuard<'_, State> {
+        crate::lock(&self.state)
+    }
+
+    /// Wakes up `wakers` and the blocking threads after unlocking `state`.
+    fn wake(&self, state: MutexGuard<'_, State>, wakers: Vec<Waker>) {
+        drop(state);
+
+        for waker in wakers {
+            waker.wake();
+        }
+
+        self.condvar.notify_all();
+    }
+
+    /// Returns the [`Reset`] mode of the event.
+    pub fn reset_mode(&self) -> Reset {
+        self.reset
+    }
+
+    /// Sets the event.
+    ///
+    /// A manual resetting event lets all waiters proceed until it's
+    /// [cleared](Event::clear). An auto resetting event lets the first waiter
+    /// proceed, or stays set until the next one arrives if nobody is waiting.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use laika::event::Event;
+    ///
+    /// let jobs = Event::auto();
+    ///
+    /// jobs.set();
+    /// jobs.set();
+    ///
+    /// // Setting an already set event has no effect
+    /// assert!(jobs.try_wait());
+    /// assert!(!jobs.try_wait());
+    /// ```
+    pub fn set(&self) {
+        let mut state = self.lock();
+        let mut wakers = Vec::new();
+
+        match self.reset {
+            Reset::Manual => {
+                state.set = true;
+                state.notify_all(&mut wakers);
+            }
+            Reset::Auto => {
+                if !state.notify_one(&mut wakers) {
+                    state.set = true;
+                }
+            }
+        }
+
+        self.wake(state, wakers);
+    }
+
+    /// Clears the event, so waiters wait again until the next
+    /// [set](Event::set).
+    pub fn clear(&self) {
+        self.lock().set = false;
+    }
+
+    /// Returns whether the event is set right now.
+    pub fn is_set(&self) -> bool {
+        self.lock().set
+    }
+
+    /// Lets all tasks and threads that are waiting right now proceed, without
+    /// setting the event. Waiters arriving later wait for the next
+    /// notification. Returns the number of waiters that have been notified.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # tokio::runtime::Builder::new_current_thread()
+    /// #     .build()
+    /// #     .unwrap()
+    /// #     .block_on(async {
+    /// use laika::event::Event;
+    /// use std::sync::Arc;
+    ///
+    /// let tick = Arc::new(Event::manual());
+    ///
+    /// let waiters: Vec<_> = (0..3)
+    ///     .map(|_| {
+    ///         let tick = Arc::clone(&tick);
+    ///         tokio::spawn(async move { tick.wait().await })
+    ///     })
+    ///     .collect();
+    ///
+    /// // Let the waiters start waiting
+    /// tokio::task::yield_now().await;
+    ///
+    /// assert_eq!(tick.notify_waiters(), 3);
+    /// assert!(!tick.is_set());
+    ///
+    /// for waiter in waiters {
+    ///     waiter.await.unwrap();
+    /// }
+    /// # });
+    /// ```
+    pub fn notify_waiters(&self) -> usize {
+        let mut state = self.lock();
+        let mut wakers = Vec::new();
+        let waiting = state
+            .waiters
+            .values()
+            .filter(|waiter| !waiter.notified)
+            .count();
+
+        state.notify_all(&mut wakers);
+        self.wake(state, wakers);
+
+        waiting
+    }
+
+    /// Takes the event if it's set, resetting it if auto resetting. This
+    /// function is **non-blocking**.
+    pub fn try_wait(&self) -> bool {
+        self.lock().take(self.reset)
+    }
+
+    /// Waits asynchronously until the event is set (taking it, if auto
+    /// resetting) or [`Event::notify_waiters`] is called.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # tokio::runtime::Builder::new_current_thread()
+    /// #     .build()
+    /// #     .unwrap()
+    /// #     .block_on(async {
+    /// use laika::event::Event;
+    /// use std::sync::{
+    ///     Arc,
+    ///     atomic::{AtomicUsize, Ordering},
+    /// };
+    ///
+    /// let job = Arc::new(Event::auto());
+    /// let done = Arc::new(AtomicUsize::new(0));
+    ///
+    /// for _ in 0..2 {
+    ///     let job = Arc::clone(&job);
+    ///     let done = Arc::clone(&done);
+    ///
+    ///     tokio::spawn(async move {
+    ///         job.wait().await;
+    ///         done.fetch_add(1, Ordering::Relaxed);
+    ///     });
+    /// }
+    ///
+    /// tokio::task::yield_now().await;
+    ///
+    /// // Only one of the workers gets the job
+    /// job.set();
+    /// tokio::task::yield_now().await;
+    ///
+    /// assert_eq!(done.load(Ordering::Relaxed), 1);
+    /// # });
+    /// ```
+    pub fn wait(&self) -> Wait<'_> {
+        Wait {
+            event: self,
+            id: None,
+        }
+    }
+
+    /// Blocks the current thread until the event is set or
+    /// [`Event::notify_waiters`] is called. See [`Event::wait`].
+    pub fn wait_blocking(&self) {
+        let mut state = self.lock();
+
+        if state.take(self.reset) {
+            return;
+        }
+
+        let id = state.enqueue(None);
+
+        while !state.waiters.get(&id).is_some_and(|waiter| waiter.notified) {
+            state = self
+                .condvar
+                .wait(state)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+
+        state.remove(id);
+    }
+
+    /// Passes the notification of a waiter that has been dropped before
+    /// observing it on to the next waiter.
+    fn forward(&self, mut state: MutexGuard<'_, State>) {
+        let mut wakers = Vec::new();
+
+        if !state.notify_one(&mut wakers) {
+            state.set = true;
+        }
+
+        self.wake(state, wakers);
+    }
+}
+
+impl fmt::Debug for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.lock();
+
+        f.debug_struct("Event")
+            .field("reset", &self.reset)
+            .field("set", &state.set)
+            .field("waiters", &state.waiters.len())
+            .finish()
+    }
+}
+
+impl Future for Wait<'_> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let mut state = this.event.lock();
+
+        let Some(id) = this.id else {
+            if state.take(this.event.reset) {
+                return Poll::Ready(());
+            }
+
+            this.id = Some(state.enqueue(Some(cx.waker().clone())));
+
+            return Poll::Pending;
+        };
+
+        let Some(waiter) = state.waiters.get_mut(&id) else {
+            // Removed by a previous poll
+            return Poll::Ready(());
+        };
+
+        if waiter.notified {
+            state.remove(id);
+            this.id = None;
+
+            return Poll::Ready(());
+        }
+
+        if !waiter
+            .waker
+            .as_ref()
+            .is_some_and(|w| w.will_wake(cx.waker()))
+        {
+            waiter.waker = Some(cx.waker().clone());
+        }
+
+        Poll::Pending
+    }
+}
+
+/// Stops waiting. If an auto resetting event picked this waiter in the
+/// meantime, its notification is passed on.
+impl Drop for Wait<'_> {
+    fn drop(&mut self) {
+        let Some(id) = self.id else {
+            return;
+        };
+
+        let mut state = self.event.lock();
+
+        if state.remove(id) {
+            self.event.forward(state);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::sync::Arc;
+    use core::pin::pin;
+    use std::{thread, time::Duration};
+
+    #[test]
+    fn test_manual() {
+        let event = Event::manual();
+        let mut cx = Context::from_waker(Waker::noop());
+
+        let mut first = pin!(event.wait());
+        let mut second = pin!(event.wait());
+        assert!(first.as_mut().poll(&mut cx).is_pending());
+        assert!(second.as_mut().poll(&mut cx).is_pending());
+
+        event.set();
+        assert!(first.as_mut().poll(&mut cx).is_ready());
+        assert!(second.as_mut().poll(&mut cx).is_ready());
+
+        // Latched until cleared
+        assert!(event.try_wait());
+        assert!(event.try_wait());
+
+        event.clear();
+        assert!(!event.try_wait());
+        assert!(pin!(event.wait()).poll(&mut cx).is_pending());
+    }
+
+    #[test]
+    fn test_auto() {
+        let event = Event::auto();
+        let mut cx = Context::from_waker(Waker::noop());
+
+        let mut first = pin!(event.wait());
+        let mut second = pin!(event.wait());
+        assert!(first.as_mut().poll(&mut cx).is_pending());
+        assert!(second.as_mut().poll(&mut cx).is_pending());
+
+        // One waiter per set, in order
+        event.set();
+        assert!(second.as_mut().poll(&mut cx).is_pending());
+        assert!(first.as_mut().poll(&mut cx).is_ready());
+        assert!(!event.is_set());
+
+        event.set();
+        assert!(second.as_mut().poll(&mut cx).is_ready());
+
+        // Nobody waiting: kept for the next waiter
+        event.set();
+        assert!(event.is_set());
+        assert!(pin!(event.wait()).poll(&mut cx).is_ready());
+        assert!(!event.is_set());
+    }
+
+    #[test]
+    fn test_forward() {
+        let event = Event::auto();
+        let mut cx = Context::from_waker(Waker::noop());
+
+        let mut second = pin!(event.wait());
+
+        {
+            let mut first = pin!(event.wait());
+            assert!(first.as_mut().poll(&mut cx).is_pending());
+            assert!(second.as_mut().poll(&mut cx).is_pending());
+
+            event.set();
+        }
+
+        // The notification of the dropped waiter isn't lost
+        assert!(second.as_mut().poll(&mut cx).is_ready());
+
+        {
+            let mut only = pin!(event.wait());
+            assert!(only.as_mut().poll(&mut cx).is_pending());
+
+            event.set();
+        }
+
+        assert!(event.is_set());
+    }
+
+    #[test]
+    fn test_notify_waiters() {
+        let event = Event::auto();
+        let mut cx = Context::from_waker(Waker::noop());
+
+        assert_eq!(event.notify_waiters(), 0);
+
+        let mut waiting = pin!(event.wait());
+        assert!(waiting.as_mut().poll(&mut cx).is_pending());
+
+        assert_eq!(event.notify_waiters(), 1);
+        assert!(!event.is_set());
+
+        {
+            let mut dropped = pin!(event.wait());
+            assert!(dropped.as_mut().poll(&mut cx).is_pending());
+            event.notify_waiters();
+        }
+
+        // Not passed on like a set
+        assert!(!event.is_set());
+
+        // Waiters arriving later aren't notified
+        let mut late = pin!(event.wait());
+        assert!(late.as_mut().poll(&mut cx).is_pending());
+        assert!(waiting.as_mut().poll(&mut cx).is_ready());
+        assert!(late.as_mut().poll(&mut cx).is_pending());
+    }
+
+    #[test]
+    fn test_wait_blocking() {
+        let event = Arc::new(Event::auto());
+
+        let workers: Vec<_> = (0..3)
+            .map(|_| {
+                let event = Arc::clone(&event);
+                thread::spawn(move || event.wait_blocking())
+            })
+            .collect();
+
+        thread::sleep(Duration::from_millis(20));
+
+        for _ in 0..3 {
+            event.set();
+
+            // Don't set it again before a worker took it
+            while event.is_set() {
+                thread::sleep(Duration::from_millis(1));
+            }
+        }
+
+        for worker in workers {
+            worker.join().unwrap();
+        }
+
+        assert!(!event.is_set());
+    }
+
+    #[tokio::test]
+    async fn test_wait() {
+        let event = Arc::new(Event::manual());
+
+        let waiter = tokio::spawn({
+            let event = Arc::clone(&event);
+            async move { event.wait().await }
+        });
+
+        tokio::task::yield_now().await;
+        event.set();
+
+        waiter.await.unwrap();
+    }
+}
@@ -0,0 +1,537 @@
+#![forbid(unsafe_code)]
+//! # A slot where two parties swap values
+//!
+//! An exchanger connects exactly two [`Exchanger`]s. Each of them offers a
+//! value with [`Exchanger::exchange`] (or [`Exchanger::exchange_blocking`])
+//! and gets the value of the other one in return: the first party to arrive
+//! waits, the second one takes the waiting value and leaves its own in one
+//! step, so neither value is ever owned by both of them.
+//!
+//! Cancelling an exchange doesn't lose a value either: if an [`Exchange`]
+//! future is dropped (e.g. by `select!` or a timeout) after the other party
+//! took its value, the value left in return is kept until it's taken with
+//! [`Exchanger::take_received`].
+//!
+//! This suits double-buffering, where a producer fills one buffer while the
+//! consumer drains the other and both swap once they're done, and any other
+//! ping-pong transfer of ownership between two tasks or threads.
+//!
+//! The exchanger is backed by a `std::sync::Mutex`, doesn't contain any unsafe
+//! code and works with any async runtime.
+//!
+//! # Examples
+//! ```rust
+//! use std::thread;
+//!
+//! let (mut producer, mut consumer) = laika::exchange::channel();
+//!
+//! let thread = thread::spawn(move || {
+//!     let mut buffer = Vec::new();
+//!
+//!     for round in 0..3 {
+//!         buffer.push(round);
+//!         // Hands the filled buffer over and continues with an empty one
+//!         buffer = producer.exchange_blocking(buffer).unwrap();
+//!     }
+//! });
+//!
+//! let mut buffer = Vec::new();
+//!
+//! for round in 0..3 {
+//!     buffer = consumer.exchange_blocking(buffer).unwrap();
+//!     assert_eq!(buffer, [round]);
+//!     buffer.clear();
+//! }
+//!
+//! thread.join().unwrap();
+//! ```
+
+mod error;
+
+pub use error::{ExchangeError, TryExchangeError};
+
+use alloc::{collections::VecDeque, sync::Arc};
+use core::{
+    future::Future,
+    mem,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+use std::sync::{Condvar, Mutex, MutexGuard, PoisonError};
+
+/// Value slot of one party
+#[derive(Debug)]
+enum Slot<T> {
+    /// The party isn't exchanging
+    Empty,
+    /// The party is waiting for the other one with its value
+    Offered(T),
+    /// The other party took the offered value and left its own
+    Received(T),
+}
+
+/// Slots and bookkeeping of an exchanger, guarded by [`Shared::state`]
+#[derive(Debug)]
+struct State<T> {
+    /// Slots of both parties, indexed by [`Exchanger::side`]
+    slots: [Slot<T>; 2],
+    /// Wakers of waiting parties, indexed by [`Exchanger::side`]
+    wakers: [Option<Waker>; 2],
+    /// Values received by cancelled exchanges that haven't been taken yet,
+    /// oldest first, indexed by [`Exchanger::side`]
+    unclaimed: [VecDeque<T>; 2],
+    /// Number of existing [`Exchanger`]s
+    parties: usize,
+}
+
+impl<T> State<T> {
+    /// Takes the value the other party of `side` offered and leaves `value`
+    /// in its slot, or returns `value` if it isn't offering one.
+    fn swap(&mut self, side: usize, value: T) -> Result<T, T> {
+        let slot = &mut self.slots[1 - side];
+
+        match mem::replace(slot, Slot::Empty) {
+            Slot::Offered(offered) => {
+                *slot = Slot::Received(value);
+                Ok(offered)
+            }
+            other => {
+                *slot = other;
+                Err(value)
+            }
+        }
+    }
+
+    /// Returns whether the other party has been dropped.
+    fn is_closed(&self) -> bool {
+        self.parties < 2
+    }
+}
+
+/// State shared by both parties
+#[derive(Debug)]
+struct Shared<T> {
+    /// Slots and bookkeeping
+    state: Mutex<State<T>>,
+    /// Notified when a party took an offer or has been dropped, so that
+    /// threads blocking in [`Exchanger::exchange_blocking`] wake up.
+    condvar: Condvar,
+}
+
+impl<T> Shared<T> {
+    /// Locks the state.
+    fn lock(&self) -> MutexGuard<'_, State<T>> {
+        crate::lock(&self.state)
+    }
+
+    /// Wakes up the other party of `side`. Takes its waker while `state` is
+    /// locked but wakes it after unlocking it.
+    fn wake_other(&self, mut state: MutexGuard<'_, State<T>>, side: usize) {
+        let waker = state.wakers[1 - side].take();
+        drop(state);
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+
+        self.condvar.notify_all();
+    }
+}
+
+/// One of the two parties of an exchanger
+///
+/// See the [module documentation](self) for details. Dropping it makes
+/// exchanging fail for the other party.
+#[derive(Debug)]
+pub struct Exchanger<T> {
+    /// State shared with the other party
+    shared: Arc<Shared<T>>,
+    /// Index of the slot and waker of this party
+    side: usize,
+}
+
+/// Future returned by [`Exchanger::exchange`]
+///
+/// Resolves to the value of the other party. Dropping it before the other
+/// party arrived withdraws the value. If the other party took it already, the
+/// value received in return is kept for [`Exchanger::take_received`].
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Exchange<'a, T> {
+    /// Party exchanging the value
+    exchanger: &'a mut Exchanger<T>,
+    /// Value until it has been offered or swapped
+    value: Option<T>,
+}
+
+impl<T> Unpin for Exchange<'_, T> {}
+
+impl<T> Future for Exchange<'_, T> {
+    type Output = Result<T, ExchangeError<T>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let side = this.exchanger.side;
+        let shared = &this.exchanger.shared;
+        let mut state = shared.lock();
+
+        match mem::replace(&mut state.slots[side], Slot::Empty) {
+            Slot::Received(value) => return Poll::Ready(Ok(value)),
+            Slot::Offered(value) if state.is_closed() => {
+                return Poll::Ready(Err(ExchangeError(value)))
+            }
+            Slot::Offered(value) => state.slots[side] = Slot::Offered(value),
+            Slot::Empty => {
+                let Some(value) = this.value.take() else {
+                    panic!("`Exchange` polled after completion");
+                };
+
+                if state.is_closed() {
+                    return Poll::Ready(Err(ExchangeError(value)));
+                }
+
+                match state.swap(side, value) {
+                    Ok(received) => {
+                        shared.wake_other(state, side);
+                        return Poll::Ready(Ok(received));
+                    }
+                    Err(value) => state.slots[side] = Slot::Offered(value),
+                }
+            }
+        }
+
+        let waker = &mut state.wakers[side];
+
+        if !waker.as_ref().is_some_and(|w| w.will_wake(cx.waker())) {
+            *waker = Some(cx.waker().clone());
+        }
+
+        Poll::Pending
+    }
+}
+
+/// Withdraws the value if the other party hasn't taken it yet, or keeps the
+/// value received in return otherwise.
+impl<T> Drop for Exchange<'_, T> {
+    fn drop(&mut self) {
+        if self.value.is_some() {
+            return;
+        }
+
+        let side = self.exchanger.side;
+        let mut state = self.exchanger.shared.lock();
+
+        let slot = mem::replace(&mut state.slots[side], Slot::Empty);
+        state.wakers[side] = None;
+
+        if let Slot::Received(value) = slot {
+            state.unclaimed[side].push_back(value);
+            return;
+        }
+
+        // The value is dropped after unlocking, in case its `Drop` uses the
+        // exchanger.
+        drop(state);
+        drop(slot);
+    }
+}
+
+impl<T> Exchanger<T> {
+    /// Swaps `value` with the other party if it's waiting to exchange. This
+    /// function is **non-blocking**.
+    ///
+    /// Returns the value in [`TryExchangeError::Empty`] if the other party
+    /// isn't waiting or in [`TryExchangeError::Disconnected`] if it has been
+    /// dropped.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use laika::exchange::TryExchangeError;
+    ///
+    /// let (mut left, right) = laika::exchange::channel();
+    /// assert_eq!(left.try_exchange(1), Err(TryExchangeError::Empty(1)));
+    ///
+    /// drop(right);
+    /// assert_eq!(left.try_exchange(1), Err(TryExchangeError::Disconnected(1)));
+    /// ```
+    pub fn try_exchange(&mut self, value: T) -> Result<T, TryExchangeError<T>> {
+        let mut state = self.shared.lock();
+
+        if state.is_closed() {
+            return Err(TryExchangeError::Disconnected(value));
+        }
+
+        match state.swap(self.side, value) {
+            Ok(received) => {
+                self.shared.wake_other(state, self.side);
+                Ok(received)
+            }
+            Err(value) => Err(TryExchangeError::Empty(value)),
+        }
+    }
+
+    /// Swaps `value` with the other party, waiting asynchronously until it
+    /// exchanges as well.
+    ///
+    /// Resolves to an [`ExchangeError`] containing the value if the other
+    /// party has been dropped before it took the value.
+    ///
+    /// # Cancel safety
+    /// Dropping the future before the other party arrived withdraws `value`.
+    /// Once the other party took `value`, the value it left in return is kept
+    /// and can be taken with [`Exchanger::take_received`].
+    ///
+    /// # Examples
+    /// ```rust
+    /// # tokio::runtime::Builder::new_current_thread()
+    /// #     .build()
+    /// #     .unwrap()
+    /// #     .block_on(async {
+    /// let (mut ping, mut pong) = laika::exchange::channel();
+    ///
+    /// let task = tokio::spawn(async move { pong.exchange("pong").await });
+    ///
+    /// assert_eq!(ping.exchange("ping").await.unwrap(), "pong");
+    /// assert_eq!(task.await.unwrap().unwrap(), "ping");
+    /// # });
+    /// ```
+    pub fn exchange(&mut self, value: T) -> Exchange<'_, T> {
+        Exchange {
+            exchanger: self,
+            value: Some(value),
+        }
+    }
+
+    /// Swaps `value` with the other party, blocking the current thread until
+    /// it exchanges as well. See [`Exchanger::exchange`].
+    pub fn exchange_blocking(&mut self, value: T) -> Result<T, ExchangeError<T>> {
+        let mut state = self.shared.lock();
+
+        if state.is_closed() {
+            return Err(ExchangeError(value));
+        }
+
+        match state.swap(self.side, value) {
+            Ok(received) => {
+                self.shared.wake_other(state, self.side);
+                return Ok(received);
+            }
+            Err(value) => state.slots[self.side] = Slot::Offered(value),
+        }
+
+        loop {
+            state = self
+                .shared
+                .condvar
+                .wait(state)
+                .unwrap_or_else(PoisonError::into_inner);
+
+            match mem::replace(&mut state.slots[self.side], Slot::Empty) {
+                Slot::Received(value) => return Ok(value),
+                Slot::Offered(value) if state.is_closed() => return Err(ExchangeError(value)),
+                slot => state.slots[self.side] = slot,
+            }
+        }
+    }
+
+    /// Takes the oldest value received by an [`Exchange`] that has been
+    /// dropped after the other party took its value, if there is one.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use std::{
+    ///     future::Future,
+    ///     pin::pin,
+    ///     task::{Context, Waker},
+    /// };
+    ///
+    /// let (mut left, mut right) = laika::exchange::channel();
+    /// let mut cx = Context::from_waker(Waker::noop());
+    ///
+    /// {
+    ///     let mut exchange = pin!(left.exchange(1));
+    ///     assert!(exchange.as_mut().poll(&mut cx).is_pending());
+    ///     assert_eq!(right.try_exchange(2), Ok(1));
+    /// }
+    ///
+    /// // The exchange has been cancelled, but the value hasn't been lost
+    /// assert_eq!(left.take_received(), Some(2));
+    /// assert_eq!(left.take_received(), None);
+    /// ```
+    pub fn take_received(&mut self) -> Option<T> {
+        self.shared.lock().unclaimed[self.side].pop_front()
+    }
+
+    /// Returns whether the other party has been dropped, so exchanging would
+    /// fail.
+    pub fn is_closed(&self) -> bool {
+        self.shared.lock().is_closed()
+    }
+}
+
+/// Closes the exchanger, waking up the other party if it's waiting so it gets
+/// its value back. Values that haven't been taken with
+/// [`Exchanger::take_received`] are dropped.
+impl<T> Drop for Exchanger<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.parties -= 1;
+
+        // Values are dropped after unlocking, in case their `Drop` uses the
+        // exchanger.
+        let unclaimed = mem::take(&mut state.unclaimed[self.side]);
+        self.shared.wake_other(state, self.side);
+        drop(unclaimed);
+    }
+}
+
+/// Creates an exchanger, returning its two parties.
+///
+/// # Examples
+/// ```rust
+/// let (mut left, mut right) = laika::exchange::channel();
+///
+/// let thread = std::thread::spawn(move || right.exchange_blocking('r'));
+///
+/// assert_eq!(left.exchange_blocking('l'), Ok('r'));
+/// assert_eq!(thread.join().unwrap(), Ok('l'));
+/// ```
+pub fn channel<T>() -> (Exchanger<T>, Exchanger<T>) {
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            slots: [Slot::Empty, Slot::Empty],
+            wakers: [None, None],
+            unclaimed: [VecDeque::new(), VecDeque::new()],
+            parties: 2,
+        }),
+        condvar: Condvar::new(),
+    });
+
+    let left = Exchanger {
+        shared: Arc::clone(&shared),
+        side: 0,
+    };
+
+    (left, Exchanger { shared, side: 1 })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::{pin::pin, task::Waker};
+    use std::{thread, time::Duration, vec::Vec};
+
+    #[test]
+    fn test_try_exchange() {
+        let (mut left, mut right) = channel();
+        let mut cx = Context::from_waker(Waker::noop());
+
+        let mut exchange = pin!(left.exchange(1));
+        assert_eq!(exchange.as_mut().poll(&mut cx), Poll::Pending);
+
+        // Takes the offered value and leaves its own
+        assert_eq!(right.try_exchange(2), Ok(1));
+        assert_eq!(right.try_exchange(3), Err(TryExchangeError::Empty(3)));
+        assert_eq!(exchange.poll(&mut cx), Poll::Ready(Ok(2)));
+    }
+
+    #[test]
+    fn test_withdraw() {
+        let (mut left, mut right) = channel();
+        let mut cx = Context::from_waker(Waker::noop());
+
+        {
+            let mut exchange = pin!(left.exchange(1));
+            assert_eq!(exchange.as_mut().poll(&mut cx), Poll::Pending);
+        }
+
+        // The dropped future withdrew its value
+        assert_eq!(right.try_exchange(2), Err(TryExchangeError::Empty(2)));
+        assert!(!right.is_closed());
+    }
+
+    #[test]
+    fn test_cancel_after_swap() {
+        let (mut left, mut right) = channel();
+        let mut cx = Context::from_waker(Waker::noop());
+
+        for value in [1, 3] {
+            let mut exchange = pin!(left.exchange(value));
+            assert_eq!(exchange.as_mut().poll(&mut cx), Poll::Pending);
+            assert_eq!(right.try_exchange(value + 1), Ok(value));
+        }
+
+        // The values received by the dropped futures are kept, without
+        // getting in the way of further exchanges
+        assert_eq!(right.try_exchange(5), Err(TryExchangeError::Empty(5)));
+
+        {
+            let mut exchange = pin!(left.exchange(6));
+            assert_eq!(exchange.as_mut().poll(&mut cx), Poll::Pending);
+            assert_eq!(right.try_exchange(7), Ok(6));
+            assert_eq!(exchange.poll(&mut cx), Poll::Ready(Ok(7)));
+        }
+
+        assert_eq!(left.take_received(), Some(2));
+        assert_eq!(left.take_received(), Some(4));
+        assert_eq!(left.take_received(), None);
+        assert_eq!(right.take_received(), None);
+    }
+
+    #[test]
+    fn test_disconnected() {
+        let (mut left, right) = channel::<u8>();
+
+        let thread = thread::spawn(move || left.exchange_blocking(1));
+
+        thread::sleep(Duration::from_millis(50));
+        drop(right);
+
+        // The waiting party gets its value back
+        assert_eq!(thread.join().unwrap(), Err(ExchangeError(1)));
+    }
+
+    #[test]
+    fn test_exchange_blocking() {
+        let (mut left, mut right) = channel();
+
+        let thread = thread::spawn(move || {
+            (0..100)
+                .map(|round| right.exchange_blocking(-round).unwrap())
+                .collect::<Vec<_>>()
+        });
+
+        for round in 0..100 {
+            assert_eq!(left.exchange_blocking(round), Ok(-round));
+        }
+
+        assert_eq!(thread.join().unwrap(), (0..100).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn test_exchange() {
+        let (mut left, mut right) = channel();
+
+        let task = tokio::spawn(async move {
+            let mut buffer = Vec::new();
+
+            for round in 0..3 {
+                buffer.push(round);
+                buffer = right.exchange(buffer).await.unwrap();
+            }
+
+            right.exchange(buffer).await
+        });
+
+        let mut buffer = Vec::with_capacity(1);
+
+        for round in 0..3 {
+            buffer = left.exchange(buffer).await.unwrap();
+            assert_eq!(buffer, [round]);
+            buffer.clear();
+        }
+
+        drop(left);
+        assert!(task.await.unwrap().is_err());
+    }
+}
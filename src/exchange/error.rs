@@ -0,0 +1,82 @@
+//! Errors of exchanging values.
+
+#[cfg(doc)]
+use super::Exchanger;
+use core::{error::Error, fmt};
+
+/// Error returned by [`Exchanger::exchange`] and
+/// [`Exchanger::exchange_blocking`] if the other party has been dropped before
+/// it exchanged. Contains the value, so it can be reclaimed.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct ExchangeError<T>(pub T);
+
+impl<T> ExchangeError<T> {
+    /// Returns the value that couldn't be exchanged.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+debug_without_value!(ExchangeError);
+
+impl<T> fmt::Display for ExchangeError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "exchanging without the other party")
+    }
+}
+
+impl<T> Error for ExchangeError<T> {}
+
+/// Error returned by [`Exchanger::try_exchange`]. Contains the value, so it
+/// can be reclaimed.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum TryExchangeError<T> {
+    /// The other party isn't waiting to exchange.
+    Empty(T),
+    /// The other party has been dropped, so exchanging will never succeed.
+    Disconnected(T),
+}
+
+impl<T> TryExchangeError<T> {
+    /// Returns the value that couldn't be exchanged.
+    pub fn into_inner(self) -> T {
+        match self {
+            TryExchangeError::Empty(value) | TryExchangeError::Disconnected(value) => value,
+        }
+    }
+}
+
+debug_without_value!(TryExchangeError {
+    Empty,
+    Disconnected
+});
+
+impl<T> fmt::Display for TryExchangeError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TryExchangeError::Empty(_) => write!(f, "the other party isn't waiting"),
+            TryExchangeError::Disconnected(_) => write!(f, "exchanging without the other party"),
+        }
+    }
+}
+
+impl<T> Error for TryExchangeError<T> {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::string::ToString;
+
+    #[test]
+    fn test_display() {
+        assert_eq!(
+            ExchangeError(1).to_string(),
+            "exchanging without the other party"
+        );
+        assert_eq!(
+            TryExchangeError::Empty(1).to_string(),
+            "the other party isn't waiting"
+        );
+        assert_eq!(TryExchangeError::Disconnected(1).into_inner(), 1);
+    }
+}
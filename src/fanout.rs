@@ -0,0 +1,454 @@
+#![forbid(unsafe_code)]
+//! # Fan-out of messages to filtered subscribers
+//!
+//! A [`Fanout`] delivers every published message to all of its subscribers.
+//! Each subscriber can register with a filter ([`Fanout::subscribe_filter`])
+//! or a closure that filters and transforms the messages at once
+//! ([`Fanout::subscribe_map`]), so it only gets the messages it's interested
+//! in, in the shape it needs them.
+//!
+//! Subscribers can come and go at any time: a [`Subscription`] gets the
+//! messages published while it exists and unsubscribes when it's dropped.
+//! Every subscription has its own unbounded queue, so publishing never waits
+//! for slow subscribers.
+//!
+//! The hub can be cloned to publish from multiple places. Once all clones
+//! have been dropped, subscriptions get the remaining queued messages and
+//! [`None`] afterwards. Filter and map closures are called while publishing,
+//! so they should be cheap and must not use the [`Fanout`] themselves.
+//!
+//! ## Optional features
+//!
+//! - `futures`: Implements `futures_core::Stream` for [`Subscription`].
+//!
+//! # Examples
+//! ```rust
+//! use laika::fanout::Fanout;
+//!
+//! let fanout = Fanout::new();
+//!
+//! let mut all = fanout.subscribe();
+//! let mut errors = fanout.subscribe_filter(|line: &&str| line.starts_with("error"));
+//! let mut lengths = fanout.subscribe_map(|line: &&str| Some(line.len()));
+//!
+//! assert_eq!(fanout.publish("info: started"), 2);
+//! assert_eq!(fanout.publish("error: disk full"), 3);
+//!
+//! assert_eq!(all.try_recv(), Ok("info: started"));
+//! assert_eq!(errors.try_recv(), Ok("error: disk full"));
+//! assert_eq!(lengths.try_recv(), Ok(13));
+//! ```
+
+use crate::mpsc::{self, TryRecvError, UnboundedReceiver};
+use alloc::{
+    boxed::Box,
+    collections::BTreeMap,
+    sync::{Arc, Weak},
+};
+#[cfg(feature = "futures")]
+use core::pin::Pin;
+use core::{
+    fmt,
+    task::{Context, Poll},
+};
+use std::sync::{Mutex, MutexGuard};
+
+/// Outcome of delivering a message to a subscriber
+enum Delivery {
+    /// The message has been queued for the subscriber
+    Delivered,
+    /// The filter of the subscriber rejected the message
+    Skipped,
+    /// The subscriber is gone
+    Closed,
+}
+
+/// Filters, transforms and queues messages for a subscriber
+type Deliver<T> = Box<dyn Fn(&T) -> Delivery + Send>;
+
+/// Subscribers of a hub, guarded by [`Shared::state`]
+struct State<T> {
+    /// Subscribers, keyed by subscription id
+    subscribers: BTreeMap<u64, Deliver<T>>,
+    /// Id of the next subscription
+    next_id: u64,
+}
+
+/// State shared by all clones of a [`Fanout`]
+struct Shared<T> {
+    /// Subscribers
+    state: Mutex<State<T>>,
+}
+
+impl<T> Shared<T> {
+    /// Locks the state. The filters of the subscribers run while it's locked,
+    /// but a panicking one only aborts the publish, keeping every subscriber.
+    fn lock(&self) -> MutexGuard<'_, State<T>> {
+        crate::lock(&self.state)
+    }
+}
+
+/// Removes subscribers from a hub, regardless of its message type
+trait Unsubscribe: Send + Sync {
+    /// Removes the subscriber with `id`.
+    fn unsubscribe(&self, id: u64);
+}
+
+impl<T> Unsubscribe for Shared<T> {
+    fn unsubscribe(&self, id: u64) {
+        let deliver = self.lock().subscribers.remove(&id);
+
+        // The closure is dropped after unlocking, in case it captured
+        // something whose `Drop` uses the hub.
+        drop(deliver);
+    }
+}
+
+/// Hub delivering published messages to its subscribers
+///
+/// Can be cloned to publish from multiple places. Subscriptions are closed
+/// once all clones have been dropped.
+pub struct Fanout<T> {
+    /// State shared with all clones
+    shared: Arc<Shared<T>>,
+}
+
+/// Subscription to a [`Fanout`], receiving the (filtered and transformed)
+/// messages published while it exists
+///
+/// Dropping it unsubscribes.
+pub struct Subscription<U> {
+    /// Queue of delivered messages
+    receiver: UnboundedReceiver<U>,
+    /// Hub to unsubscribe from
+    hub: Weak<dyn Unsubscribe>,
+    /// Id of this subscription
+    id: u64,
+}
+
+impl<T> Fanout<T> {
+    /// Creates a hub without subscribers.
+    pub fn new() -> Self {
+        Fanout {
+            shared: Arc::new(Shared {
+                state: Mutex::new(State {
+                    subscribers: BTreeMap::new(),
+                    next_id: 0,
+                }),
+            }),
+        }
+    }
+
+    /// Delivers `value` to all subscribers whose filter accepts it. Never
+    /// waits. Returns the number of subscribers it has been delivered to.
+    pub fn publish(&self, value: T) -> usize {
+        let mut state = self.shared.lock();
+        let mut delivered = 0;
+
+        state
+            .subscribers
+            .retain(|_, deliver| match deliver(&value) {
+                Delivery::Delivered => {
+                    delivered += 1;
+                    true
+                }
+                Delivery::Skipped => true,
+                Delivery::Closed => false,
+            });
+
+        delivered
+    }
+
+    /// Subscribes to messages, transforming them with `map`. Messages for
+    /// which `map` returns [`None`] are skipped.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use laika::fanout::Fanout;
+    ///
+    /// let fanout = Fanout::new();
+    /// let mut evens = fanout.subscribe_map(|value: &u8| value.is_multiple_of(2).then(|| value * 10));
+    ///
+    /// for value in 0..4 {
+    ///     fanout.publish(value);
+    /// }
+    ///
+    /// assert_eq!(evens.try_recv(), Ok(0));
+    /// assert_eq!(evens.try_recv(), Ok(20));
+    /// ```
+    pub fn subscribe_map<U, F>(&self, map: F) -> Subscription<U>
+    where
+        T: 'static,
+        U: Send + 'static,
+        F: Fn(&T) -> Option<U> + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::unbounded();
+
+        let deliver = move |value: &T| match map(value) {
+            Some(mapped) => match sender.send(mapped) {
+                Ok(()) => Delivery::Delivered,
+                Err(_) => Delivery::Closed,
+            },
+            None => Delivery::Skipped,
+        };
+
+        let mut state = self.shared.lock();
+        let id = state.next_id;
+        state.next_id += 1;
+        state.subscribers.insert(id, Box::new(deliver));
+
+        Subscription {
+            receiver,
+            hub: Arc::downgrade(&self.shared) as Weak<dyn Unsubscribe>,
+            id,
+        }
+    }
+
+    /// Subscribes to the messages accepted by `filter`.
+    pub fn subscribe_filter<F>(&self, filter: F) -> Subscription<T>
+    where
+        T: Clone + Send + 'static,
+        F: Fn(&T) -> bool + Send + 'static,
+    {
+        self.subscribe_map(move |value| filter(value).then(|| value.clone()))
+    }
+
+    /// Subscribes to all messages.
+    pub fn subscribe(&self) -> Subscription<T>
+    where
+        T: Clone + Send + 'static,
+    {
+        self.subscribe_map(|value| Some(value.clone()))
+    }
+
+    /// Returns the number of current subscribers.
+    pub fn subscriber_count(&self) -> usize {
+        self.shared.lock().subscribers.len()
+    }
+}
+
+impl<T> Default for Fanout<T> {
+    fn default() -> Self {
+        Fanout::new()
+    }
+}
+
+impl<T> Clone for Fanout<T> {
+    fn clone(&self) -> Self {
+        Fanout {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+impl<T> fmt::Debug for Fanout<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Fanout")
+            .field("subscribers", &self.subscriber_count())
+            .finish()
+    }
+}
+
+impl<U> Subscription<U> {
+    /// Receives the oldest delivered message if there is one. This function
+    /// is **non-blocking**.
+    ///
+    /// Returns [`TryRecvError::Empty`] if no message is queued or
+    /// [`TryRecvError::Disconnected`] if none is and the [`Fanout`] has been
+    /// dropped.
+    pub fn try_recv(&mut self) -> Result<U, TryRecvError> {
+        self.receiver.try_recv()
+    }
+
+    /// Polls for the oldest delivered message, registering the waker of `cx`
+    /// if there is none yet. Resolves to [`None`] once no message is queued
+    /// and the [`Fanout`] has been dropped.
+    pub fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<U>> {
+        self.receiver.poll_recv(cx)
+    }
+
+    /// Receives the oldest delivered message, waiting asynchronously until
+    /// there is one. Resolves to [`None`] once no message is queued and the
+    /// [`Fanout`] has been dropped.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # tokio::runtime::Builder::new_current_thread()
+    /// #     .build()
+    /// #     .unwrap()
+    /// #     .block_on(async {
+    /// use laika::fanout::Fanout;
+    ///
+    /// let fanout = Fanout::new();
+    /// let mut big = fanout.subscribe_filter(|value: &u32| *value > 100);
+    ///
+    /// let task = tokio::spawn(async move {
+    ///     let mut count = 0;
+    ///
+    ///     while big.recv().await.is_some() {
+    ///         count += 1;
+    ///     }
+    ///
+    ///     count
+    /// });
+    ///
+    /// for value in [1, 200, 3, 400] {
+    ///     fanout.publish(value);
+    /// }
+    ///
+    /// drop(fanout);
+    /// assert_eq!(task.await.unwrap(), 2);
+    /// # });
+    /// ```
+    pub async fn recv(&mut self) -> Option<U> {
+        self.receiver.recv().await
+    }
+
+    /// Receives the oldest delivered message, blocking the current thread
+    /// until there is one. See [`Subscription::recv`].
+    pub fn recv_blocking(&mut self) -> Option<U> {
+        self.receiver.recv_blocking()
+    }
+
+    /// Unsubscribes, dropping the queued messages. Same as dropping the
+    /// subscription.
+    pub fn unsubscribe(self) {}
+}
+
+/// Unsubscribes from the [`Fanout`], so no more messages are delivered.
+impl<U> Drop for Subscription<U> {
+    fn drop(&mut self) {
+        if let Some(hub) = self.hub.upgrade() {
+            hub.unsubscribe(self.id);
+        }
+    }
+}
+
+impl<U> fmt::Debug for Subscription<U> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Subscription")
+            .field("id", &self.id)
+            .field("len", &self.receiver.len())
+            .finish()
+    }
+}
+
+/// Implement [`futures_core::Stream`] for [`Subscription`], yielding the
+/// delivered messages until the [`Fanout`] has been dropped.
+#[cfg(feature = "futures")]
+impl<U> futures_core::Stream for Subscription<U> {
+    type Item = U;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<U>> {
+        self.get_mut().poll_recv(cx)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::{string::String, thread, vec::Vec};
+
+    #[test]
+    fn test_filters() {
+        let fanout = Fanout::new();
+
+        let mut all = fanout.subscribe();
+        let mut odd = fanout.subscribe_filter(|value: &u8| value % 2 == 1);
+        let mut names = fanout.subscribe_map(|value: &u8| match value {
+            0 => Some(String::from("zero")),
+            _ => None,
+        });
+
+        assert_eq!(fanout.publish(0), 2);
+        assert_eq!(fanout.publish(1), 2);
+
+        assert_eq!(all.try_recv(), Ok(0));
+        assert_eq!(all.try_recv(), Ok(1));
+        assert_eq!(odd.try_recv(), Ok(1));
+        assert_eq!(odd.try_recv(), Err(TryRecvError::Empty));
+        assert_eq!(names.try_recv().as_deref(), Ok("zero"));
+    }
+
+    #[test]
+    fn test_unsubscribe() {
+        let fanout = Fanout::new();
+        let fanout1 = fanout.clone();
+
+        let mut first = fanout.subscribe();
+        let second = fanout.subscribe();
+        assert_eq!(fanout.subscriber_count(), 2);
+
+        second.unsubscribe();
+        assert_eq!(fanout.subscriber_count(), 1);
+        assert_eq!(fanout1.publish(1), 1);
+
+        // Subscribers only get messages published after subscribing
+        let mut third = fanout1.subscribe();
+        assert_eq!(fanout.publish(2), 2);
+        assert_eq!(third.try_recv(), Ok(2));
+
+        drop(fanout);
+        drop(fanout1);
+
+        // Queued messages are still received after the hub is gone
+        assert_eq!(first.try_recv(), Ok(1));
+        assert_eq!(first.try_recv(), Ok(2));
+        assert_eq!(first.try_recv(), Err(TryRecvError::Disconnected));
+        assert_eq!(third.recv_blocking(), None);
+    }
+
+    #[test]
+    fn test_recv_blocking() {
+        let fanout = Fanout::new();
+
+        let subscribers = (1..=3)
+            .map(|divisor| {
+                let mut subscription =
+                    fanout.subscribe_filter(move |value: &u32| value.is_multiple_of(divisor));
+
+                thread::spawn(move || {
+                    let mut values = Vec::new();
+
+                    while let Some(value) = subscription.recv_blocking() {
+                        values.push(value);
+                    }
+
+                    values
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for value in 0..10 {
+            fanout.publish(value);
+        }
+
+        drop(fanout);
+
+        let values = subscribers
+            .into_iter()
+            .map(|subscriber| subscriber.join().unwrap().len())
+            .collect::<Vec<_>>();
+
+        assert_eq!(values, [10, 5, 4]);
+    }
+
+    #[tokio::test]
+    async fn test_recv() {
+        let fanout = Fanout::new();
+        let mut subscription = fanout.subscribe_map(|value: &u8| Some(*value as u32 * 2));
+
+        let publisher = tokio::spawn(async move {
+            for value in 1..=3 {
+                fanout.publish(value);
+            }
+        });
+
+        publisher.await.unwrap();
+
+        assert_eq!(subscription.recv().await, Some(2));
+        assert_eq!(subscription.recv().await, Some(4));
+        assert_eq!(subscription.recv().await, Some(6));
+        assert_eq!(subscription.recv().await, None);
+    }
+}
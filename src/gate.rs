@@ -0,0 +1,426 @@
+#![forbid(unsafe_code)]
+//! # Pausing and resuming workers
+//!
+//! A gate is either open or closed. Workers call [`Gate::passed`] at their
+//! checkpoints (e.g. between two jobs): while the gate is open, it completes
+//! right away, while it's closed, the workers wait there. The [`Controller`]
+//! closes the gate to pause a whole fleet of workers at their next checkpoint
+//! and opens it again to resume all of them at once.
+//!
+//! Every worker waiting at the gate passes once it's opened, even if it's
+//! closed again before the worker got to run. Once the controller is dropped,
+//! the gate is opened for good, so workers don't wait for a controller that
+//! is gone.
+//!
+//! Workers can wait asynchronously ([`Gate::passed`]) or block the current
+//! thread ([`Gate::passed_blocking`]). The gate doesn't contain any unsafe
+//! code and works with any async runtime.
+//!
+//! # Examples
+//! ```rust
+//! # tokio::runtime::Builder::new_current_thread()
+//! #     .build()
+//! #     .unwrap()
+//! #     .block_on(async {
+//! use laika::gate;
+//! use std::sync::{
+//!     Arc,
+//!     atomic::{AtomicUsize, Ordering},
+//! };
+//!
+//! let (controller, gate) = gate::new(true);
+//! let processed = Arc::new(AtomicUsize::new(0));
+//!
+//! let worker = tokio::spawn({
+//!     let processed = Arc::clone(&processed);
+//!
+//!     async move {
+//!         for _job in 0..3 {
+//!             gate.passed().await;
+//!             processed.fetch_add(1, Ordering::Relaxed);
+//!             tokio::task::yield_now().await;
+//!         }
+//!     }
+//! });
+//!
+//! // Pause the worker at its next checkpoint
+//! controller.close();
+//! tokio::task::yield_now().await;
+//! assert_eq!(controller.waiting(), 1);
+//!
+//! controller.open();
+//! worker.await.unwrap();
+//! assert_eq!(processed.load(Ordering::Relaxed), 3);
+//! # });
+//! ```
+
+use alloc::{collections::BTreeMap, sync::Arc};
+use core::{
+    fmt,
+    future::Future,
+    mem,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+use std::sync::{Condvar, Mutex, MutexGuard, PoisonError};
+
+/// State of a gate, guarded by [`Shared::state`]
+struct State {
+    /// Whether the gate is open
+    open: bool,
+    /// Number of times the gate has been opened, so waiters know they may
+    /// pass even if it's closed again before they run
+    openings: u64,
+    /// Wakers of the tasks waiting at the gate, keyed by id
+    wakers: BTreeMap<u64, Waker>,
+    /// Number of threads blocking in [`Gate::passed_blocking`]
+    blocking: usize,
+    /// Id of the next waker
+    next_id: u64,
+}
+
+/// State shared by the [`Controller`] and the [`Gate`]s
+struct Shared {
+    /// Whether the gate is open and its waiters
+    state: Mutex<State>,
+    /// Wakes up threads blocking in [`Gate::passed_blocking`]
+    condvar: Condvar,
+}
+
+/// Side of a gate opening and closing it
+///
+/// Opens the gate for good when dropped. See the
+/// [module documentation](self) for details.
+pub struct Controller {
+    /// State shared with the gates
+    shared: Arc<Shared>,
+}
+
+/// Side of a gate the workers wait at
+///
+/// Can be cloned for every worker. See the [module documentation](self) for
+/// details.
+#[derive(Clone)]
+pub struct Gate {
+    /// State shared with the controller
+    shared: Arc<Shared>,
+}
+
+/// Future returned by [`Gate::passed`]
+///
+/// Resolves once the gate is open.
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Passed<'a> {
+    /// Gate to pass
+    gate: &'a Gate,
+    /// Openings when it started waiting and the id of its waker
+    waiting: Option<(u64, u64)>,
+}
+
+impl Shared {
+    /// Locks the state.
+    fn lock(&self) -> MutexGuard<'_, State> {
+        crate::lock(&self.state)
+    }
+
+    /// Opens the gate, waking up everybody waiting at it.
+    fn open(&self) {
+        let mut state = self.lock();
+
+        if state.open {
+            return;
+        }
+
+        state.open = true;
+        state.openings += 1;
+
+        let wakers = mem::take(&mut state.wakers);
+
+        drop(state);
+
+        for waker in wakers.into_values() {
+            waker.wake();
+        }
+
+        self.condvar.notify_all();
+    }
+}
+
+impl Controller {
+    /// Opens the gate, resuming all workers waiting at it. Does nothing if
+    /// it's open already.
+    pub fn open(&self) {
+        self.shared.open();
+    }
+
+    /// Closes the gate, so workers wait at their next checkpoint. Workers
+    /// that are between two checkpoints aren't interrupted.
+    pub fn close(&self) {
+        self.shared.lock().open = false;
+    }
+
+    /// Returns whether the gate is open.
+    pub fn is_open(&self) -> bool {
+        self.shared.lock().open
+    }
+
+    /// Returns the number of workers waiting at the gate right now, e.g. to
+    /// await all of them being paused.
+    pub fn waiting(&self) -> usize {
+        let state = self.shared.lock();
+
+        state.wakers.len() + state.blocking
+    }
+
+    /// Returns a new gate for a worker.
+    pub fn gate(&self) -> Gate {
+        Gate {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+/// Opens the gate for good, so no worker waits for a controller that is gone.
+impl Drop for Controller {
+    fn drop(&mut self) {
+        self.shared.open();
+    }
+}
+
+impl fmt::Debug for Controller {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Controller")
+            .field("open", &self.is_open())
+            .field("waiting", &self.waiting())
+            .finish()
+    }
+}
+
+impl Gate {
+    /// Passes the gate: completes right away if it's open, otherwise waits
+    /// asynchronously until the controller opens it.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # tokio::runtime::Builder::new_current_thread()
+    /// #     .build()
+    /// #     .unwrap()
+    /// #     .block_on(async {
+    /// use laika::gate;
+    ///
+    /// let (controller, gate) = gate::new(false);
+    /// let worker = tokio::spawn(async move { gate.passed().await });
+    ///
+    /// tokio::task::yield_now().await;
+    /// assert!(!worker.is_finished());
+    ///
+    /// controller.open();
+    /// worker.await.unwrap();
+    /// # });
+    /// ```
+    pub fn passed(&self) -> Passed<'_> {
+        Passed {
+            gate: self,
+            waiting: None,
+        }
+    }
+
+    /// Passes the gate, blocking the current thread while it's closed. See
+    /// [`Gate::passed`].
+    pub fn passed_blocking(&self) {
+        let mut state = self.shared.lock();
+
+        if state.open {
+            return;
+        }
+
+        let openings = state.openings;
+        state.blocking += 1;
+
+        while state.openings == openings {
+            state = self
+                .shared
+                .condvar
+                .wait(state)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+
+        state.blocking -= 1;
+    }
+
+    /// Returns whether the gate is open, so passing it completes right away.
+    pub fn is_open(&self) -> bool {
+        self.shared.lock().open
+    }
+}
+
+impl fmt::Debug for Gate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Gate")
+            .field("open", &self.is_open())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Future for Passed<'_> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let mut state = this.gate.shared.lock();
+
+        let Some((openings, id)) = this.waiting else {
+            if state.open {
+                return Poll::Ready(());
+            }
+
+            let id = state.next_id;
+            state.next_id += 1;
+            state.wakers.insert(id, cx.waker().clone());
+            this.waiting = Some((state.openings, id));
+
+            return Poll::Pending;
+        };
+
+        if state.openings != openings {
+            this.waiting = None;
+            return Poll::Ready(());
+        }
+
+        if let Some(waker) = state.wakers.get_mut(&id) {
+            if !waker.will_wake(cx.waker()) {
+                *waker = cx.waker().clone();
+            }
+        }
+
+        Poll::Pending
+    }
+}
+
+impl Drop for Passed<'_> {
+    fn drop(&mut self) {
+        if let Some((_, id)) = self.waiting {
+            self.gate.shared.lock().wakers.remove(&id);
+        }
+    }
+}
+
+impl fmt::Debug for Passed<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Passed")
+            .field("waiting", &self.waiting.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Creates a gate that is initially `open` or closed. Returns its controller
+/// and a gate for the workers, which can be cloned (or created by
+/// [`Controller::gate`]) for more workers.
+///
+/// See the [module documentation](self) for details.
+pub fn new(open: bool) -> (Controller, Gate) {
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            open,
+            openings: 0,
+            wakers: BTreeMap::new(),
+            blocking: 0,
+            next_id: 0,
+        }),
+        condvar: Condvar::new(),
+    });
+
+    let gate = Gate {
+        shared: Arc::clone(&shared),
+    };
+
+    (Controller { shared }, gate)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::pin::pin;
+    use std::{thread, time::Duration, vec::Vec};
+
+    #[test]
+    fn test_passed() {
+        let (controller, gate) = new(true);
+        let mut cx = Context::from_waker(Waker::noop());
+
+        assert!(pin!(gate.passed()).poll(&mut cx).is_ready());
+
+        controller.close();
+        assert!(!gate.is_open());
+
+        let mut first = pin!(gate.passed());
+        let mut second = pin!(gate.passed());
+        assert!(first.as_mut().poll(&mut cx).is_pending());
+        assert!(second.as_mut().poll(&mut cx).is_pending());
+        assert_eq!(controller.waiting(), 2);
+
+        // Closed again before the waiters ran, they pass anyway
+        controller.open();
+        controller.close();
+        assert!(first.as_mut().poll(&mut cx).is_ready());
+        assert!(second.as_mut().poll(&mut cx).is_ready());
+        assert_eq!(controller.waiting(), 0);
+
+        // Not waiting yet, so the gate is closed for this one
+        assert!(pin!(gate.passed()).poll(&mut cx).is_pending());
+        assert_eq!(controller.waiting(), 0);
+    }
+
+    #[test]
+    fn test_drop_controller() {
+        let (controller, gate) = new(false);
+        let mut cx = Context::from_waker(Waker::noop());
+
+        let mut waiting = pin!(gate.passed());
+        assert!(waiting.as_mut().poll(&mut cx).is_pending());
+
+        drop(controller);
+        assert!(waiting.as_mut().poll(&mut cx).is_ready());
+        assert!(gate.is_open());
+    }
+
+    #[test]
+    fn test_passed_blocking() {
+        let (controller, gate) = new(false);
+
+        let workers: Vec<_> = (0..3)
+            .map(|_| {
+                let gate = controller.gate();
+                thread::spawn(move || gate.passed_blocking())
+            })
+            .collect();
+
+        while controller.waiting() < 3 {
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        controller.open();
+
+        for worker in workers {
+            worker.join().unwrap();
+        }
+
+        gate.passed_blocking();
+    }
+
+    #[tokio::test]
+    async fn test_pause() {
+        let (controller, gate) = new(false);
+
+        let worker = tokio::spawn(async move {
+            gate.passed().await;
+            1
+        });
+
+        tokio::task::yield_now().await;
+        assert_eq!(controller.waiting(), 1);
+
+        controller.open();
+        assert_eq!(worker.await.unwrap(), 1);
+    }
+}
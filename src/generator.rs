@@ -0,0 +1,394 @@
+#![forbid(unsafe_code)]
+//! # Turning callbacks into receivers
+//!
+//! Many APIs push values by invoking a callback (`register_callback(|item|
+//! ...)`) instead of being polled. A [`Generator`] bridges them to a receiver
+//! that can be awaited, blocked on or used as a stream: [`Generator::new`]
+//! passes an [`Emitter`] to a registration function, which hands it to the
+//! callback. Every value the callback emits is queued for the generator.
+//!
+//! The queue is a bounded [`mpsc`](crate::mpsc) channel, so a callback that
+//! emits faster than the values are received is slowed down by blocking in
+//! [`Emitter::emit`] (backpressure). Callbacks that must not block pick an
+//! [`Overflow`] policy to shed load instead with [`Generator::with_overflow`],
+//! or use [`Emitter::try_emit`].
+//!
+//! The registration function returns an unregistration function, which is
+//! called once the generator is dropped. The queue is closed before, so
+//! callbacks blocking in [`Emitter::emit`] return right away. The generator
+//! completes once all emitters have been dropped and the queued values have
+//! been received.
+//!
+//! The generator doesn't contain any unsafe code and works with any async
+//! runtime.
+//!
+//! ## Optional features
+//!
+//! - `futures`: Implements `futures_core::Stream` for [`Generator`].
+//!
+//! # Examples
+//! ```rust
+//! use laika::generator::Generator;
+//! use std::sync::{Arc, Mutex};
+//!
+//! // A push-style API, invoking all registered callbacks for every event
+//! type Callback = Box<dyn Fn(&str) + Send>;
+//! let callbacks: Arc<Mutex<Vec<Callback>>> = Arc::default();
+//!
+//! let registry = Arc::clone(&callbacks);
+//! let mut events = Generator::new(8, move |emitter| {
+//!     registry.lock().unwrap().push(Box::new(move |event| {
+//!         let _ = emitter.emit(event.to_owned());
+//!     }));
+//!
+//!     // Unregisters the callback once the generator is dropped
+//!     move || registry.lock().unwrap().clear()
+//! });
+//!
+//! for callback in callbacks.lock().unwrap().iter() {
+//!     callback("connected");
+//! }
+//!
+//! assert_eq!(events.recv_blocking().as_deref(), Some("connected"));
+//!
+//! drop(events);
+//! assert!(callbacks.lock().unwrap().is_empty());
+//! ```
+
+use crate::mpsc::{self, Overflow, Receiver, SendError, Sender, TryRecvError, TrySendError};
+use alloc::boxed::Box;
+#[cfg(feature = "futures")]
+use core::pin::Pin;
+use core::{
+    fmt,
+    task::{Context, Poll},
+};
+
+/// Boxed unregistration function of a [`Generator`]
+type Unregister = Box<dyn FnOnce() + Send>;
+
+/// Handle for a callback to emit values to a [`Generator`]
+///
+/// Can be cloned to register multiple callbacks. The generator completes once
+/// all emitters have been dropped.
+pub struct Emitter<T> {
+    /// Sender of the queue of the generator
+    sender: Sender<T>,
+}
+
+impl<T> Emitter<T> {
+    /// Queues a value for the generator, blocking the current thread until
+    /// there's room for it. If the queue is full, generators with an
+    /// [`Overflow`] policy other than [`Block`](Overflow::Block) don't block
+    /// but handle the value by their policy.
+    ///
+    /// Returns [`SendError::Disconnected`] containing the value if the
+    /// generator has been dropped (also while blocking), or
+    /// [`SendError::Full`] if the queue is full and the policy is
+    /// [`Error`](Overflow::Error).
+    ///
+    /// As it blocks, don't call it from callbacks invoked on the threads of an
+    /// async runtime. Use [`Emitter::try_emit`] or [`Emitter::emit_async`]
+    /// there.
+    pub fn emit(&self, value: T) -> Result<(), SendError<T>> {
+        self.sender.send_blocking(value)
+    }
+
+    /// Queues a value for the generator if there's room for it. This function
+    /// is **non-blocking**. See [`Emitter::emit`].
+    pub fn try_emit(&self, value: T) -> Result<(), TrySendError<T>> {
+        self.sender.try_send(value)
+    }
+
+    /// Queues a value for the generator, waiting asynchronously until there's
+    /// room for it. See [`Emitter::emit`].
+    pub async fn emit_async(&self, value: T) -> Result<(), SendError<T>> {
+        self.sender.send(value).await
+    }
+
+    /// Returns whether the generator has been dropped, so emitting would fail
+    /// and the callback may stop producing values.
+    pub fn is_closed(&self) -> bool {
+        self.sender.is_closed()
+    }
+}
+
+impl<T> Clone for Emitter<T> {
+    fn clone(&self) -> Self {
+        Emitter {
+            sender: self.sender.clone(),
+        }
+    }
+}
+
+impl<T> fmt::Debug for Emitter<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Emitter")
+            .field("closed", &self.is_closed())
+            .finish()
+    }
+}
+
+/// Receiver of the values emitted by registered callbacks
+///
+/// See the [module documentation](self) for details.
+pub struct Generator<T> {
+    /// Receiver of the queue, only [`None`] while being dropped
+    receiver: Option<Receiver<T>>,
+    /// Unregisters the callbacks, only [`None`] while being dropped
+    unregister: Option<Unregister>,
+}
+
+impl<T> Unpin for Generator<T> {}
+
+impl<T> Generator<T> {
+    /// Creates a generator queueing at most `capacity` values. `register` is
+    /// called right away with the [`Emitter`] for the callback and returns
+    /// the function unregistering it, which is called once the generator is
+    /// dropped.
+    ///
+    /// Emitting on a full queue blocks until there's room.
+    ///
+    /// # Panics
+    /// Panics if `capacity` is zero.
+    pub fn new<F, U>(capacity: usize, register: F) -> Self
+    where
+        F: FnOnce(Emitter<T>) -> U,
+        U: FnOnce() + Send + 'static,
+    {
+        Generator::with_overflow(capacity, Overflow::Block, register)
+    }
+
+    /// Creates a generator queueing at most `capacity` values and handling
+    /// emitting on a full queue by `overflow`. See [`Generator::new`].
+    ///
+    /// # Panics
+    /// Panics if `capacity` is zero.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use laika::{generator::Generator, mpsc::Overflow};
+    ///
+    /// let mut emitter = None;
+    /// let mut readings = Generator::with_overflow(2, Overflow::DropOldest, |e| {
+    ///     emitter = Some(e);
+    ///     || {}
+    /// });
+    ///
+    /// let emitter = emitter.unwrap();
+    ///
+    /// // The callback never blocks, only the latest readings are kept
+    /// for reading in 0..5 {
+    ///     emitter.emit(reading).unwrap();
+    /// }
+    ///
+    /// drop(emitter);
+    ///
+    /// assert_eq!(readings.recv_blocking(), Some(3));
+    /// assert_eq!(readings.recv_blocking(), Some(4));
+    /// assert_eq!(readings.recv_blocking(), None);
+    /// ```
+    pub fn with_overflow<F, U>(capacity: usize, overflow: Overflow, register: F) -> Self
+    where
+        F: FnOnce(Emitter<T>) -> U,
+        U: FnOnce() + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::bounded_with_overflow(capacity, overflow);
+        let unregister = register(Emitter { sender });
+
+        Generator {
+            receiver: Some(receiver),
+            unregister: Some(Box::new(unregister)),
+        }
+    }
+
+    /// Returns the receiver of the queue.
+    fn receiver(&mut self) -> &mut Receiver<T> {
+        self.receiver
+            .as_mut()
+            .expect("the receiver is only taken on drop")
+    }
+
+    /// Receives the oldest emitted value if there is one. This function is
+    /// **non-blocking**.
+    ///
+    /// Returns [`TryRecvError::Empty`] if there is none or
+    /// [`TryRecvError::Disconnected`] if there is none and all emitters have
+    /// been dropped.
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        self.receiver().try_recv()
+    }
+
+    /// Polls for the oldest emitted value, registering the waker of `cx` if
+    /// there is none yet. Resolves to [`None`] once all emitters have been
+    /// dropped and the queue is empty.
+    pub fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        self.receiver().poll_recv(cx)
+    }
+
+    /// Receives the oldest emitted value, waiting asynchronously until there
+    /// is one. Resolves to [`None`] once all emitters have been dropped and
+    /// the queue is empty.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # tokio::runtime::Builder::new_current_thread()
+    /// #     .build()
+    /// #     .unwrap()
+    /// #     .block_on(async {
+    /// use laika::generator::Generator;
+    /// use std::thread;
+    ///
+    /// // A callback invoked on a thread of a (simulated) native library
+    /// let mut ticks = Generator::new(4, |emitter| {
+    ///     thread::spawn(move || {
+    ///         for tick in 0..3 {
+    ///             emitter.emit(tick).unwrap();
+    ///         }
+    ///     });
+    ///
+    ///     || {}
+    /// });
+    ///
+    /// let mut received = Vec::new();
+    ///
+    /// while let Some(tick) = ticks.recv().await {
+    ///     received.push(tick);
+    /// }
+    ///
+    /// assert_eq!(received, [0, 1, 2]);
+    /// # });
+    /// ```
+    pub async fn recv(&mut self) -> Option<T> {
+        core::future::poll_fn(|cx| self.poll_recv(cx)).await
+    }
+
+    /// Receives the oldest emitted value, blocking the current thread until
+    /// there is one. See [`Generator::recv`].
+    pub fn recv_blocking(&mut self) -> Option<T> {
+        self.receiver().recv_blocking()
+    }
+}
+
+/// Closes the queue, so emitting fails (also for blocked callbacks), and
+/// calls the unregistration function afterwards.
+impl<T> Drop for Generator<T> {
+    fn drop(&mut self) {
+        drop(self.receiver.take());
+
+        if let Some(unregister) = self.unregister.take() {
+            unregister();
+        }
+    }
+}
+
+impl<T> fmt::Debug for Generator<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Generator")
+            .field("len", &self.receiver.as_ref().map_or(0, Receiver::len))
+            .finish()
+    }
+}
+
+/// Implement [`futures_core::Stream`] for [`Generator`], yielding the emitted
+/// values until all emitters have been dropped.
+#[cfg(feature = "futures")]
+impl<T> futures_core::Stream for Generator<T> {
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        self.get_mut().poll_recv(cx)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::sync::Arc;
+    use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::{sync::Mutex, thread, time::Duration, vec::Vec};
+
+    #[test]
+    fn test_backpressure() {
+        let emitted = Arc::new(AtomicUsize::new(0));
+
+        let mut generator = Generator::new(1, |emitter| {
+            let emitted = Arc::clone(&emitted);
+
+            thread::spawn(move || {
+                for value in 0..3 {
+                    emitter.emit(value).unwrap();
+                    emitted.fetch_add(1, Ordering::AcqRel);
+                }
+            });
+
+            || {}
+        });
+
+        // The callback blocks on the full queue
+        thread::sleep(Duration::from_millis(20));
+        assert_eq!(emitted.load(Ordering::Acquire), 1);
+
+        assert_eq!(generator.try_recv(), Ok(0));
+        assert_eq!(generator.recv_blocking(), Some(1));
+        assert_eq!(generator.recv_blocking(), Some(2));
+        assert_eq!(generator.recv_blocking(), None);
+        assert_eq!(generator.try_recv(), Err(TryRecvError::Disconnected));
+    }
+
+    #[test]
+    fn test_unregister() {
+        let unregistered = Arc::new(AtomicBool::new(false));
+        let emitter = Arc::new(Mutex::new(None));
+
+        let generator = Generator::new(1, {
+            let unregistered = Arc::clone(&unregistered);
+            let emitter = Arc::clone(&emitter);
+
+            move |e| {
+                *emitter.lock().unwrap() = Some(e);
+                move || unregistered.store(true, Ordering::Release)
+            }
+        });
+
+        let emitter = emitter.lock().unwrap().take().unwrap();
+        emitter.emit('a').unwrap();
+
+        // Blocks on the full queue until the generator is dropped
+        let callback = thread::spawn(move || {
+            let result = emitter.emit('b');
+            (result, emitter.is_closed())
+        });
+
+        thread::sleep(Duration::from_millis(20));
+        assert!(!unregistered.load(Ordering::Acquire));
+
+        drop(generator);
+        assert!(unregistered.load(Ordering::Acquire));
+        assert_eq!(
+            callback.join().unwrap(),
+            (Err(SendError::Disconnected('b')), true)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_recv() {
+        let mut generator = Generator::new(2, |emitter| {
+            tokio::spawn(async move {
+                for value in 0..5 {
+                    emitter.emit_async(value).await.unwrap();
+                }
+            });
+
+            || {}
+        });
+
+        let mut values = Vec::new();
+
+        while let Some(value) = generator.recv().await {
+            values.push(value);
+        }
+
+        assert_eq!(values, [0, 1, 2, 3, 4]);
+    }
+}
@@ -0,0 +1,739 @@
+#![forbid(unsafe_code)]
+//! # A work queue that keeps the order per key
+//!
+//! Values are sent together with a key, e.g. the id of the entity they belong
+//! to. Any of the (cloneable) [`Receiver`]s can take a value, but values with
+//! the same key are handed out one at a time and in the order they've been
+//! sent: while an [`Item`] of a key is being processed, the next value of that
+//! key waits until the item is dropped (or marked [`Item::done`]). Values of
+//! different keys are processed concurrently by multiple workers.
+//!
+//! This is the standard pattern for sequential processing per entity, like
+//! applying the events of an account in order while still spreading all
+//! accounts across workers.
+//!
+//! The channel is unbounded and backed by a `std::sync::Mutex`. It doesn't
+//! contain any unsafe code and works with any async runtime. Keys have to
+//! implement [`Ord`] and [`Clone`].
+//!
+//! # Examples
+//! ```rust
+//! use laika::keyed::TryRecvError;
+//!
+//! let (tx, rx) = laika::keyed::channel();
+//!
+//! tx.send("alice", 1).unwrap();
+//! tx.send("alice", 2).unwrap();
+//! tx.send("bob", 1).unwrap();
+//!
+//! let alice = rx.try_recv().unwrap();
+//! assert_eq!((*alice.key(), *alice), ("alice", 1));
+//!
+//! // The next value of "alice" waits until the first one is done
+//! let bob = rx.try_recv().unwrap();
+//! assert_eq!((*bob.key(), *bob), ("bob", 1));
+//! assert_eq!(rx.try_recv().unwrap_err(), TryRecvError::Empty);
+//!
+//! alice.done();
+//! assert_eq!(*rx.try_recv().unwrap(), 2);
+//! ```
+
+mod error;
+
+pub use error::{SendError, TryRecvError};
+
+use alloc::{
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    sync::Arc,
+};
+use core::{
+    fmt,
+    future::Future,
+    mem,
+    ops::{Deref, DerefMut},
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+use std::sync::{Condvar, Mutex, MutexGuard, PoisonError};
+
+/// Queues and bookkeeping of a channel, guarded by [`Shared::state`]
+#[derive(Debug)]
+struct State<K, T> {
+    /// Queued values per key, oldest first. Keys without queued values are
+    /// removed.
+    queues: BTreeMap<K, VecDeque<T>>,
+    /// Keys with queued values that aren't being processed, in the order
+    /// they became ready
+    ready: VecDeque<K>,
+    /// Keys with an [`Item`] being processed
+    busy: BTreeSet<K>,
+    /// Total number of queued values
+    len: usize,
+    /// Number of existing [`Sender`]s
+    senders: usize,
+    /// Number of existing [`Receiver`]s
+    receivers: usize,
+    /// Wakers of receivers waiting for a value, keyed by receiver or future
+    /// id
+    wakers: BTreeMap<u64, Waker>,
+    /// Id of the next receiver or `recv` future
+    next_id: u64,
+}
+
+impl<K, T> State<K, T> {
+    /// Returns a new receiver id.
+    fn id(&mut self) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+}
+
+impl<K, T> State<K, T>
+where
+    K: Ord + Clone,
+{
+    /// Takes the oldest value of the key that has been ready the longest and
+    /// marks the key as busy.
+    fn pop(&mut self) -> Result<(K, T), TryRecvError> {
+        let Some(key) = self.ready.pop_front() else {
+            if self.len == 0 && self.senders == 0 {
+                return Err(TryRecvError::Disconnected);
+            }
+
+            return Err(TryRecvError::Empty);
+        };
+
+        let Some(queue) = self.queues.get_mut(&key) else {
+            unreachable!("Ready keys have queued values");
+        };
+
+        let Some(value) = queue.pop_front() else {
+            unreachable!("Queues of ready keys aren't empty");
+        };
+
+        if queue.is_empty() {
+            self.queues.remove(&key);
+        }
+
+        self.len -= 1;
+        self.busy.insert(key.clone());
+
+        Ok((key, value))
+    }
+}
+
+/// State shared by all senders and receivers of a channel
+#[derive(Debug)]
+struct Shared<K, T> {
+    /// Queues and bookkeeping
+    state: Mutex<State<K, T>>,
+    /// Notified when a value becomes ready or the channel is closed, so that
+    /// threads blocking in [`Receiver::recv_blocking`] wake up.
+    condvar: Condvar,
+}
+
+impl<K, T> Shared<K, T> {
+    /// Locks the state.
+    fn lock(&self) -> MutexGuard<'_, State<K, T>> {
+        crate::lock(&self.state)
+    }
+
+    /// Wakes up all waiting receivers after unlocking `state`. All of them
+    /// are woken up rather than one, as a woken up `recv` future might have
+    /// been dropped already.
+    fn wake_all(&self, mut state: MutexGuard<'_, State<K, T>>) {
+        let wakers = mem::take(&mut state.wakers);
+        drop(state);
+
+        for waker in wakers.into_values() {
+            waker.wake();
+        }
+
+        self.condvar.notify_all();
+    }
+}
+
+/// Sending side of a keyed channel
+///
+/// Can be cloned to send from multiple producers. The channel is closed once
+/// all senders have been dropped.
+#[derive(Debug)]
+pub struct Sender<K, T> {
+    /// State shared with all other senders and receivers
+    shared: Arc<Shared<K, T>>,
+}
+
+/// Receiving side of a keyed channel
+///
+/// Can be cloned to process values with multiple workers. Values of the same
+/// key are never handed out to two receivers at the same time.
+#[derive(Debug)]
+pub struct Receiver<K, T> {
+    /// State shared with all senders and other receivers
+    shared: Arc<Shared<K, T>>,
+    /// Id of this receiver, under which [`Receiver::poll_recv`] registers its
+    /// waker
+    id: u64,
+}
+
+/// Future returned by [`Receiver::recv`]
+///
+/// Resolves to the next value whose key isn't being processed, or to [`None`]
+/// once the queue is empty and all senders have been dropped.
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Recv<'a, K, T> {
+    /// Receiver the value is received with
+    receiver: &'a Receiver<K, T>,
+    /// Id of the waker once the future waits for a value
+    id: Option<u64>,
+}
+
+/// Value taken from a keyed channel, dereferencing to the value
+///
+/// While it exists, no other value of its key is handed out. Dropping it (or
+/// calling [`Item::done`]) releases the key, so the next value of the key can
+/// be received.
+pub struct Item<K, T>
+where
+    K: Ord + Clone,
+{
+    /// State of the channel the item has been taken from
+    shared: Arc<Shared<K, T>>,
+    /// Key of the value
+    key: K,
+    /// Received value
+    value: T,
+}
+
+impl<K, T> Sender<K, T>
+where
+    K: Ord + Clone,
+{
+    /// Queues a value for `key`. This function is **non-blocking**.
+    ///
+    /// Returns the key and the value in a [`SendError`] if all [`Receiver`]s
+    /// have been dropped.
+    pub fn send(&self, key: K, value: T) -> Result<(), SendError<(K, T)>> {
+        let mut state = self.shared.lock();
+
+        if state.receivers == 0 {
+            return Err(SendError((key, value)));
+        }
+
+        state.len += 1;
+
+        if let Some(queue) = state.queues.get_mut(&key) {
+            // The key is ready or busy already
+            queue.push_back(value);
+            return Ok(());
+        }
+
+        if !state.busy.contains(&key) {
+            state.ready.push_back(key.clone());
+        }
+
+        state.queues.insert(key, VecDeque::from([value]));
+        self.shared.wake_all(state);
+
+        Ok(())
+    }
+
+    /// Returns whether all [`Receiver`]s have been dropped, so sending would
+    /// fail.
+    pub fn is_closed(&self) -> bool {
+        self.shared.lock().receivers == 0
+    }
+}
+
+impl<K, T> Clone for Sender<K, T> {
+    fn clone(&self) -> Self {
+        self.shared.lock().senders += 1;
+
+        Sender {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+/// Closes the channel when the last [`Sender`] is dropped, waking up all
+/// waiting receivers.
+impl<K, T> Drop for Sender<K, T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.senders -= 1;
+
+        if state.senders == 0 {
+            self.shared.wake_all(state);
+        }
+    }
+}
+
+impl<K, T> Receiver<K, T>
+where
+    K: Ord + Clone,
+{
+    /// Takes the next value whose key isn't being processed, if there is one.
+    /// This function is **non-blocking**.
+    ///
+    /// Returns [`TryRecvError::Empty`] if no value is ready or
+    /// [`TryRecvError::Disconnected`] if the queue is empty and all senders
+    /// have been dropped.
+    pub fn try_recv(&self) -> Result<Item<K, T>, TryRecvError> {
+        let (key, value) = self.shared.lock().pop()?;
+
+        Ok(self.item(key, value))
+    }
+
+    /// Polls for the next value whose key isn't being processed, registering
+    /// the waker of `cx` if there is none. Resolves to [`None`] once the queue
+    /// is empty and all senders have been dropped.
+    ///
+    /// Only the waker of the last call is kept, so tasks sharing a receiver
+    /// use [`Receiver::recv`] instead.
+    pub fn poll_recv(&self, cx: &mut Context<'_>) -> Poll<Option<Item<K, T>>> {
+        self.poll_take(&mut Some(self.id), cx)
+    }
+
+    /// Polls for the next value whose key isn't being processed, registering
+    /// the waker of `cx` under `id` if there is none. A new id is assigned if
+    /// `id` is [`None`].
+    fn poll_take(&self, id: &mut Option<u64>, cx: &Context<'_>) -> Poll<Option<Item<K, T>>> {
+        let mut state = self.shared.lock();
+
+        match state.pop() {
+            Ok((key, value)) => {
+                drop(state);
+                Poll::Ready(Some(self.item(key, value)))
+            }
+            Err(TryRecvError::Disconnected) => Poll::Ready(None),
+            Err(TryRecvError::Empty) => {
+                let id = *id.get_or_insert_with(|| state.id());
+
+                if !state
+                    .wakers
+                    .get(&id)
+                    .is_some_and(|waker| waker.will_wake(cx.waker()))
+                {
+                    state.wakers.insert(id, cx.waker().clone());
+                }
+
+                Poll::Pending
+            }
+        }
+    }
+
+    /// Takes the next value whose key isn't being processed, waiting
+    /// asynchronously until there is one. Resolves to [`None`] once the queue
+    /// is empty and all senders have been dropped.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # tokio::runtime::Builder::new_current_thread()
+    /// #     .build()
+    /// #     .unwrap()
+    /// #     .block_on(async {
+    /// let (tx, rx) = laika::keyed::channel();
+    ///
+    /// for (account, event) in [(1, "opened"), (2, "opened"), (1, "closed")] {
+    ///     tx.send(account, event).unwrap();
+    /// }
+    ///
+    /// drop(tx);
+    ///
+    /// let workers = (0..4)
+    ///     .map(|_| {
+    ///         let rx = rx.clone();
+    ///
+    ///         tokio::spawn(async move {
+    ///             while let Some(item) = rx.recv().await {
+    ///                 // Items of the same account are processed in order
+    ///                 println!("{}: {}", item.key(), *item);
+    ///             }
+    ///         })
+    ///     })
+    ///     .collect::<Vec<_>>();
+    ///
+    /// for worker in workers {
+    ///     worker.await.unwrap();
+    /// }
+    /// # });
+    /// ```
+    pub fn recv(&self) -> Recv<'_, K, T> {
+        Recv {
+            receiver: self,
+            id: None,
+        }
+    }
+
+    /// Takes the next value whose key isn't being processed, blocking the
+    /// current thread until there is one. See [`Receiver::recv`].
+    pub fn recv_blocking(&self) -> Option<Item<K, T>> {
+        let mut state = self.shared.lock();
+
+        loop {
+            match state.pop() {
+                Ok((key, value)) => {
+                    drop(state);
+                    return Some(self.item(key, value));
+                }
+                Err(TryRecvError::Disconnected) => return None,
+                Err(TryRecvError::Empty) => {
+                    state = self
+                        .shared
+                        .condvar
+                        .wait(state)
+                        .unwrap_or_else(PoisonError::into_inner);
+                }
+            }
+        }
+    }
+
+    /// Wraps a taken value into an [`Item`] that releases its key on drop.
+    fn item(&self, key: K, value: T) -> Item<K, T> {
+        Item {
+            shared: Arc::clone(&self.shared),
+            key,
+            value,
+        }
+    }
+}
+
+impl<K, T> Receiver<K, T> {
+    /// Returns the number of queued values, including the ones waiting for
+    /// their key to be released.
+    pub fn len(&self) -> usize {
+        self.shared.lock().len
+    }
+
+    /// Returns whether no values are queued.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<K, T> Clone for Receiver<K, T> {
+    fn clone(&self) -> Self {
+        let mut state = self.shared.lock();
+        state.receivers += 1;
+        let id = state.id();
+
+        Receiver {
+            shared: Arc::clone(&self.shared),
+            id,
+        }
+    }
+}
+
+/// Closes the channel for senders when the last [`Receiver`] is dropped and
+/// drops the queued values.
+impl<K, T> Drop for Receiver<K, T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.receivers -= 1;
+        state.wakers.remove(&self.id);
+
+        if state.receivers == 0 {
+            // Values are dropped after unlocking, in case their `Drop` uses
+            // the channel.
+            let queues = mem::take(&mut state.queues);
+            state.ready.clear();
+            state.len = 0;
+            drop(state);
+            drop(queues);
+        }
+    }
+}
+
+impl<K, T> Item<K, T>
+where
+    K: Ord + Clone,
+{
+    /// Returns the key of the value.
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Marks the value as processed, releasing its key. Same as dropping the
+    /// item.
+    pub fn done(self) {}
+}
+
+impl<K, T> Deref for Item<K, T>
+where
+    K: Ord + Clone,
+{
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<K, T> DerefMut for Item<K, T>
+where
+    K: Ord + Clone,
+{
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+impl<K, T> fmt::Debug for Item<K, T>
+where
+    K: Ord + Clone + fmt::Debug,
+    T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Item")
+            .field("key", &self.key)
+            .field("value", &self.value)
+            .finish()
+    }
+}
+
+/// Releases the key, so that the next value of it can be received.
+impl<K, T> Drop for Item<K, T>
+where
+    K: Ord + Clone,
+{
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.busy.remove(&self.key);
+
+        if state.queues.contains_key(&self.key) {
+            state.ready.push_back(self.key.clone());
+        } else if state.len > 0 || state.senders > 0 {
+            return;
+        }
+
+        // Either a value became ready or the last busy key of a closed channel
+        // has been released
+        self.shared.wake_all(state);
+    }
+}
+
+impl<K, T> Future for Recv<'_, K, T>
+where
+    K: Ord + Clone,
+{
+    type Output = Option<Item<K, T>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        this.receiver.poll_take(&mut this.id, cx)
+    }
+}
+
+/// Withdraws the waker, so the channel doesn't keep it.
+impl<K, T> Drop for Recv<'_, K, T> {
+    fn drop(&mut self) {
+        if let Some(id) = self.id {
+            self.receiver.shared.lock().wakers.remove(&id);
+        }
+    }
+}
+
+/// Implement [`futures_core::Stream`] for [`Receiver`], yielding items until
+/// the queue is empty and all senders have been dropped.
+#[cfg(feature = "futures")]
+impl<K, T> futures_core::Stream for Receiver<K, T>
+where
+    K: Ord + Clone,
+{
+    type Item = Item<K, T>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Item<K, T>>> {
+        self.poll_recv(cx)
+    }
+}
+
+/// Creates a keyed channel.
+pub fn channel<K, T>() -> (Sender<K, T>, Receiver<K, T>) {
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            queues: BTreeMap::new(),
+            ready: VecDeque::new(),
+            busy: BTreeSet::new(),
+            len: 0,
+            senders: 1,
+            receivers: 1,
+            wakers: BTreeMap::new(),
+            next_id: 1,
+        }),
+        condvar: Condvar::new(),
+    });
+
+    let receiver = Receiver {
+        shared: Arc::clone(&shared),
+        id: 0,
+    };
+
+    (Sender { shared }, receiver)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::pin::pin;
+    use std::{thread, time::Duration, vec::Vec};
+
+    #[test]
+    fn test_shared_receiver() {
+        let (tx, rx) = channel();
+        let mut cx = Context::from_waker(Waker::noop());
+
+        {
+            let mut recv = pin!(rx.recv());
+            let mut recv1 = pin!(rx.recv());
+
+            assert!(recv.as_mut().poll(&mut cx).is_pending());
+            assert!(recv1.as_mut().poll(&mut cx).is_pending());
+
+            // Each future waits with a waker of its own
+            assert_eq!(tx.shared.lock().wakers.len(), 2);
+
+            tx.send(1, 'a').unwrap();
+
+            let Poll::Ready(Some(item)) = recv1.as_mut().poll(&mut cx) else {
+                panic!("the value hasn't been handed out");
+            };
+
+            assert_eq!(*item, 'a');
+            assert!(recv.as_mut().poll(&mut cx).is_pending());
+        }
+
+        assert!(tx.shared.lock().wakers.is_empty());
+    }
+
+    #[test]
+    fn test_per_key_order() {
+        let (tx, rx) = channel();
+        let mut cx = Context::from_waker(Waker::noop());
+
+        for (key, value) in [(1, 'a'), (1, 'b'), (2, 'c'), (1, 'd'), (3, 'e')] {
+            tx.send(key, value).unwrap();
+        }
+
+        assert_eq!(rx.len(), 5);
+
+        let a = rx.try_recv().unwrap();
+        let c = rx.try_recv().unwrap();
+        let e = rx.try_recv().unwrap();
+        assert_eq!([*a, *c, *e], ['a', 'c', 'e']);
+
+        // Key 1 is busy, the others have no queued values
+        assert!(rx.poll_recv(&mut cx).is_pending());
+        assert_eq!(tx.shared.lock().wakers.len(), 1);
+
+        // Values sent for a busy key wait as well
+        tx.send(2, 'f').unwrap();
+        assert_eq!(rx.try_recv().unwrap_err(), TryRecvError::Empty);
+
+        drop(c);
+        assert!(tx.shared.lock().wakers.is_empty());
+        assert_eq!(*rx.try_recv().unwrap(), 'f');
+
+        a.done();
+        let b = rx.try_recv().unwrap();
+        assert_eq!((*b.key(), *b), (1, 'b'));
+
+        drop(b);
+        assert_eq!(*rx.try_recv().unwrap(), 'd');
+        assert!(rx.is_empty());
+    }
+
+    #[test]
+    fn test_close() {
+        let (tx, rx) = channel();
+
+        tx.send(1, 1).unwrap();
+        tx.send(1, 2).unwrap();
+        drop(tx);
+
+        // Queued values are still received after the senders are gone
+        let item = rx.try_recv().unwrap();
+        assert_eq!(rx.try_recv().unwrap_err(), TryRecvError::Empty);
+        drop(item);
+
+        assert_eq!(rx.recv_blocking().as_deref(), Some(&2));
+        assert_eq!(rx.try_recv().unwrap_err(), TryRecvError::Disconnected);
+
+        let (tx, rx) = channel();
+        drop(rx);
+
+        assert!(tx.is_closed());
+        assert_eq!(tx.send(1, 1), Err(SendError((1, 1))));
+    }
+
+    #[test]
+    fn test_recv_blocking() {
+        let (tx, rx) = channel::<u8, u8>();
+        let log = Arc::new(Mutex::new(Vec::new()));
+
+        let workers = (0..4)
+            .map(|_| {
+                let rx = rx.clone();
+                let log = Arc::clone(&log);
+
+                thread::spawn(move || {
+                    while let Some(item) = rx.recv_blocking() {
+                        thread::sleep(Duration::from_millis(1));
+                        log.lock().unwrap().push((*item.key(), *item));
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        drop(rx);
+
+        for value in 0..8 {
+            for key in 0..3 {
+                tx.send(key, value).unwrap();
+            }
+        }
+
+        drop(tx);
+
+        for worker in workers {
+            worker.join().unwrap();
+        }
+
+        let log = log.lock().unwrap();
+        assert_eq!(log.len(), 24);
+
+        for key in 0..3 {
+            let values = log
+                .iter()
+                .filter(|(k, _)| *k == key)
+                .map(|(_, value)| *value)
+                .collect::<Vec<_>>();
+
+            assert_eq!(values, (0..8).collect::<Vec<_>>());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_recv() {
+        let (tx, rx) = channel();
+
+        let worker = tokio::spawn(async move {
+            let mut values = Vec::new();
+
+            while let Some(item) = rx.recv().await {
+                values.push(*item);
+            }
+
+            values
+        });
+
+        for value in 0..4 {
+            tx.send(value % 2, value).unwrap();
+        }
+
+        drop(tx);
+        assert_eq!(worker.await.unwrap(), [0, 1, 2, 3]);
+    }
+}
@@ -0,0 +1,577 @@
+#![forbid(unsafe_code)]
+//! # Leases: exclusive ownership that expires
+//!
+//! A [`Lease`] is held by at most one [`Token`] at a time, like a lock. Unlike
+//! a lock, it's only held for its time to live (TTL): if the holder doesn't
+//! [renew](Token::renew) it in time, e.g. because its task hangs or crashed
+//! without unwinding, the lease expires and the next waiter acquires it. This
+//! models crash-tolerant ownership of a shared resource within a process,
+//! like the "leader" polling a queue or the task refreshing a cache.
+//!
+//! Waiters acquire the lease asynchronously ([`Lease::acquire`]) or block the
+//! current thread ([`Lease::acquire_blocking`]). Once a token has been
+//! dropped or its lease expired, one of them acquires it. Acquiring isn't
+//! fair, waiters don't get the lease in the order they started waiting.
+//!
+//! Renewing a token of an expired lease fails with [`ExpiredError`], so the
+//! former holder knows it has to stop using the resource. Expiry is tracked by
+//! a background thread shared by all leases, so the lease doesn't contain any
+//! unsafe code and works with any async runtime.
+//!
+//! # Examples
+//! ```rust
+//! # tokio::runtime::Builder::new_current_thread()
+//! #     .build()
+//! #     .unwrap()
+//! #     .block_on(async {
+//! use laika::lease::{ExpiredError, Lease};
+//! use std::time::Duration;
+//!
+//! let leader = Lease::new(Duration::from_millis(50));
+//!
+//! let token = leader.acquire().await;
+//! token.renew().unwrap();
+//!
+//! // The holder hangs, so the lease expires and the next one gets it
+//! let next = leader.acquire().await;
+//!
+//! assert_eq!(token.renew(), Err(ExpiredError));
+//! assert!(next.renew().is_ok());
+//! # });
+//! ```
+
+mod error;
+
+pub use error::ExpiredError;
+
+use crate::timer::{self, alarm::Alarm};
+use alloc::{
+    collections::BTreeMap,
+    sync::{Arc, Weak},
+    task::Wake,
+};
+use core::{
+    fmt,
+    future::Future,
+    mem,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+    time::Duration,
+};
+use std::{
+    sync::{Condvar, Mutex, MutexGuard, PoisonError},
+    time::Instant,
+};
+
+/// Current holder of a lease
+struct Holder {
+    /// Generation of the token holding the lease
+    generation: u64,
+    /// When the lease expires, unless it's renewed. [`None`] if the time to
+    /// live is too long to ever end.
+    deadline: Option<Instant>,
+}
+
+/// State of a lease, guarded by [`Shared::state`]
+struct State {
+    /// Current holder, if the lease has been acquired (it may have expired
+    /// since)
+    holder: Option<Holder>,
+    /// Generation of the next token
+    generation: u64,
+    /// Deadline the alarm is scheduled for, if it hasn't rung yet. Renewing
+    /// doesn't schedule it again, instead it's scheduled for the new deadline
+    /// once it rang.
+    armed: Option<Instant>,
+    /// Wakers of the tasks waiting for the lease, keyed by id
+    wakers: BTreeMap<u64, Waker>,
+    /// Id of the next waker
+    next_id: u64,
+}
+
+/// State shared by a [`Lease`] and its [`Token`]s
+struct Shared {
+    /// How long a lease is held without renewing it
+    ttl: Duration,
+    /// Holder and waiters
+    state: Mutex<State>,
+    /// Wakes up threads blocking in [`Lease::acquire_blocking`]
+    condvar: Condvar,
+    /// Alarm ringing when the lease is due to expire
+    alarm: Arc<Alarm>,
+    /// Waker the alarm rings, waking up the waiters once the lease expired
+    expire: Waker,
+}
+
+/// Waker ringing on [`Shared::alarm`]. Weak, so the timer doesn't keep a
+/// lease alive that has been dropped.
+struct Expire(Weak<Shared>);
+
+/// Resource that is held by at most one [`Token`] at a time, for a time to
+/// live
+///
+/// Can be cloned to be shared by multiple tasks. See the
+/// [module documentation](self) for details.
+#[derive(Clone)]
+pub struct Lease {
+    /// State shared with the tokens
+    shared: Arc<Shared>,
+}
+
+/// Proof of holding a [`Lease`], releasing it when dropped
+///
+/// The lease expires after its time to live unless the token is
+/// [renewed](Token::renew).
+#[must_use = "the lease is released right away if the token isn't used"]
+pub struct Token {
+    /// State shared with the lease
+    shared: Arc<Shared>,
+    /// Generation of this token, to recognize that the lease expired and has
+    /// been acquired by another token
+    generation: u64,
+}
+
+/// Future returned by [`Lease::acquire`]
+///
+/// Resolves to a [`Token`] once the lease has been acquired.
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Acquire<'a> {
+    /// Lease to acquire
+    lease: &'a Lease,
+    /// Id of the registered waker
+    id: Option<u64>,
+}
+
+impl State {
+    /// Returns whether the lease can be acquired at `now`.
+    fn is_free(&self, now: Instant) -> bool {
+        self.holder
+            .as_ref()
+            .is_none_or(|holder| holder.deadline.is_some_and(|deadline| deadline <= now))
+    }
+}
+
+impl Shared {
+    /// Locks the state.
+    fn lock(&self) -> MutexGuard<'_, State> {
+        crate::lock(&self.state)
+    }
+
+    /// Lets the alarm ring at `deadline`, to check for expiry, unless it's
+    /// going to ring before anyway.
+    fn arm(&self, state: &mut State, deadline: Instant) {
+        if state.armed.is_some_and(|armed| armed <= deadline) {
+            return;
+        }
+
+        state.armed = Some(deadline);
+        self.alarm.register(&self.expire);
+        timer::schedule(deadline, &self.alarm);
+    }
+
+    /// Acquires the lease if it's free, returning the token.
+    fn take(self: &Arc<Self>, state: &mut State) -> Option<Token> {
+        let now = Instant::now();
+
+        if !state.is_free(now) {
+            return None;
+        }
+
+        let generation = state.generation;
+        let deadline = now.checked_add(self.ttl);
+        state.generation += 1;
+        state.holder = Some(Holder {
+            generation,
+            deadline,
+        });
+
+        if let Some(deadline) = deadline {
+            self.arm(state, deadline);
+        }
+
+        Some(Token {
+            shared: Arc::clone(self),
+            generation,
+        })
+    }
+
+    /// Wakes up all waiters after unlocking `state`, as the lease is free.
+    fn wake_all(&self, mut state: MutexGuard<'_, State>) {
+        let wakers = mem::take(&mut state.wakers);
+
+        drop(state);
+
+        for waker in wakers.into_values() {
+            waker.wake();
+        }
+
+        self.condvar.notify_all();
+    }
+}
+
+impl Wake for Expire {
+    fn wake(self: Arc<Self>) {
+        let Some(shared) = self.0.upgrade() else {
+            return;
+        };
+
+        let mut state = shared.lock();
+        state.armed = None;
+
+        let now = Instant::now();
+
+        match &state.holder {
+            Some(holder) if holder.deadline.is_none_or(|deadline| deadline > now) => {
+                // Renewed in the meantime
+                if let Some(deadline) = holder.deadline {
+                    shared.arm(&mut state, deadline);
+                }
+            }
+            _ if state.wakers.is_empty() => {}
+            _ => shared.wake_all(state),
+        }
+    }
+}
+
+impl Lease {
+    /// Creates a lease that is free, held for `ttl` by every token unless
+    /// it's renewed.
+    pub fn new(ttl: Duration) -> Self {
+        let shared = Arc::new_cyclic(|shared| Shared {
+            ttl,
+            state: Mutex::new(State {
+                holder: None,
+                generation: 0,
+                armed: None,
+                wakers: BTreeMap::new(),
+                next_id: 0,
+            }),
+            condvar: Condvar::new(),
+            alarm: Arc::new(Alarm::default()),
+            expire: Waker::from(Arc::new(Expire(Weak::clone(shared)))),
+        });
+
+        Lease { shared }
+    }
+
+    /// Returns the time to live of the lease.
+    pub fn ttl(&self) -> Duration {
+        self.shared.ttl
+    }
+
+    /// Returns whether the lease is held (and hasn't expired) right now.
+    pub fn is_held(&self) -> bool {
+        !self.shared.lock().is_free(Instant::now())
+    }
+
+    /// Acquires the lease if it's free or expired. This function is
+    /// **non-blocking**.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use laika::lease::Lease;
+    /// use std::time::Duration;
+    ///
+    /// let lease = Lease::new(Duration::from_secs(10));
+    ///
+    /// let token = lease.try_acquire().unwrap();
+    /// assert!(lease.try_acquire().is_none());
+    ///
+    /// token.release();
+    /// assert!(lease.try_acquire().is_some());
+    /// ```
+    pub fn try_acquire(&self) -> Option<Token> {
+        self.shared.take(&mut self.shared.lock())
+    }
+
+    /// Acquires the lease, waiting asynchronously until it's released or
+    /// expired.
+    pub fn acquire(&self) -> Acquire<'_> {
+        Acquire {
+            lease: self,
+            id: None,
+        }
+    }
+
+    /// Acquires the lease, blocking the current thread until it's released or
+    /// expired. See [`Lease::acquire`].
+    pub fn acquire_blocking(&self) -> Token {
+        let mut state = self.shared.lock();
+
+        loop {
+            if let Some(token) = self.shared.take(&mut state) {
+                return token;
+            }
+
+            let deadline = state.holder.as_ref().and_then(|holder| holder.deadline);
+
+            state = match deadline {
+                Some(deadline) => {
+                    let timeout = deadline.saturating_duration_since(Instant::now());
+
+                    self.shared
+                        .condvar
+                        .wait_timeout(state, timeout)
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .0
+                }
+                // The lease never expires, only releasing it wakes this up
+                None => self
+                    .shared
+                    .condvar
+                    .wait(state)
+                    .unwrap_or_else(PoisonError::into_inner),
+            };
+        }
+    }
+}
+
+impl fmt::Debug for Lease {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Lease")
+            .field("ttl", &self.shared.ttl)
+            .field("held", &self.is_held())
+            .finish()
+    }
+}
+
+impl Token {
+    /// Renews the lease, so it's held for another time to live from now on.
+    ///
+    /// Returns [`ExpiredError`] if the lease expired already: it may be held
+    /// by another token now, so the resource mustn't be used anymore.
+    pub fn renew(&self) -> Result<(), ExpiredError> {
+        let mut state = self.shared.lock();
+        let now = Instant::now();
+
+        match &mut state.holder {
+            Some(holder)
+                if holder.generation == self.generation
+                    && holder.deadline.is_none_or(|deadline| deadline > now) =>
+            {
+                // The alarm is scheduled for an earlier deadline already and
+                // is scheduled for this one once it rang.
+                holder.deadline = now.checked_add(self.shared.ttl);
+
+                Ok(())
+            }
+            _ => Err(ExpiredError),
+        }
+    }
+
+    /// Returns whether the lease expired, so this token doesn't hold it
+    /// anymore.
+    pub fn is_expired(&self) -> bool {
+        self.expires_in().is_none()
+    }
+
+    /// Returns the time until the lease expires unless it's renewed
+    /// ([`Duration::MAX`] if it never does), or [`None`] if it expired
+    /// already.
+    pub fn expires_in(&self) -> Option<Duration> {
+        let state = self.shared.lock();
+
+        match &state.holder {
+            Some(holder) if holder.generation == self.generation => match holder.deadline {
+                Some(deadline) => deadline
+                    .checked_duration_since(Instant::now())
+                    .filter(|remaining| !remaining.is_zero()),
+                None => Some(Duration::MAX),
+            },
+            _ => None,
+        }
+    }
+
+    /// Releases the lease, so the next waiter acquires it. Same as dropping
+    /// the token.
+    pub fn release(self) {}
+}
+
+/// Releases the lease (unless it expired), waking up the waiters.
+impl Drop for Token {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+
+        if state
+            .holder
+            .as_ref()
+            .is_some_and(|holder| holder.generation == self.generation)
+        {
+            state.holder = None;
+            self.shared.wake_all(state);
+        }
+    }
+}
+
+impl fmt::Debug for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Token")
+            .field("expires_in", &self.expires_in())
+            .finish()
+    }
+}
+
+impl Future for Acquire<'_> {
+    type Output = Token;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let shared = &this.lease.shared;
+        let mut state = shared.lock();
+
+        if let Some(token) = shared.take(&mut state) {
+            if let Some(id) = this.id.take() {
+                state.wakers.remove(&id);
+            }
+
+            return Poll::Ready(token);
+        }
+
+        match this.id.and_then(|id| state.wakers.get_mut(&id)) {
+            Some(waker) => {
+                if !waker.will_wake(cx.waker()) {
+                    *waker = cx.waker().clone();
+                }
+            }
+            None => {
+                let id = state.next_id;
+                state.next_id += 1;
+                state.wakers.insert(id, cx.waker().clone());
+                this.id = Some(id);
+            }
+        }
+
+        Poll::Pending
+    }
+}
+
+impl Drop for Acquire<'_> {
+    fn drop(&mut self) {
+        if let Some(id) = self.id {
+            self.lease.shared.lock().wakers.remove(&id);
+        }
+    }
+}
+
+impl fmt::Debug for Acquire<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Acquire")
+            .field("lease", self.lease)
+            .field("waiting", &self.id.is_some())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::pin::pin;
+    use std::thread;
+
+    #[test]
+    fn test_release() {
+        let lease = Lease::new(Duration::from_secs(60));
+        let mut cx = Context::from_waker(Waker::noop());
+
+        let token = lease.try_acquire().unwrap();
+        assert!(lease.is_held());
+        assert!(!token.is_expired());
+
+        let mut waiting = pin!(lease.acquire());
+        assert!(waiting.as_mut().poll(&mut cx).is_pending());
+
+        drop(token);
+
+        let Poll::Ready(token) = waiting.as_mut().poll(&mut cx) else {
+            panic!("the lease has been released");
+        };
+
+        assert!(token.expires_in().unwrap() <= Duration::from_secs(60));
+    }
+
+    #[test]
+    fn test_expire() {
+        let lease = Lease::new(Duration::from_millis(20));
+
+        let token = lease.try_acquire().unwrap();
+        thread::sleep(Duration::from_millis(30));
+
+        assert!(token.is_expired());
+        assert!(!lease.is_held());
+        assert_eq!(token.renew(), Err(ExpiredError));
+
+        // Dropping the expired token doesn't release the next holder's lease
+        let next = lease.try_acquire().unwrap();
+        drop(token);
+        assert!(lease.is_held());
+        assert!(next.renew().is_ok());
+    }
+
+    #[test]
+    fn test_renew() {
+        let lease = Lease::new(Duration::from_millis(40));
+        let token = lease.try_acquire().unwrap();
+
+        let armed = lease.shared.lock().armed;
+
+        // Renewing doesn't schedule the alarm again
+        for _ in 0..100 {
+            token.renew().unwrap();
+        }
+
+        assert!(armed.is_some());
+        assert_eq!(lease.shared.lock().armed, armed);
+
+        for _ in 0..4 {
+            thread::sleep(Duration::from_millis(20));
+            token.renew().unwrap();
+        }
+
+        assert!(lease.is_held());
+    }
+
+    #[test]
+    fn test_endless_ttl() {
+        let lease = Lease::new(Duration::MAX);
+        let token = lease.try_acquire().unwrap();
+
+        // The lease never expires, so the alarm isn't needed
+        assert!(lease.shared.lock().armed.is_none());
+        assert_eq!(token.expires_in(), Some(Duration::MAX));
+        assert!(token.renew().is_ok());
+
+        assert!(lease.try_acquire().is_none());
+        drop(token);
+        assert!(lease.try_acquire().is_some());
+    }
+
+    #[test]
+    fn test_acquire_blocking() {
+        let lease = Lease::new(Duration::from_millis(20));
+        let token = lease.try_acquire().unwrap();
+
+        let waiter = thread::spawn({
+            let lease = lease.clone();
+            move || lease.acquire_blocking().is_expired()
+        });
+
+        // Expires while the waiter blocks
+        assert!(!waiter.join().unwrap());
+        assert!(token.is_expired());
+    }
+
+    #[tokio::test]
+    async fn test_acquire_expired() {
+        let lease = Lease::new(Duration::from_millis(20));
+        let token = lease.acquire().await;
+
+        // Woken up by the timer once the lease expired
+        let next = tokio::spawn({
+            let lease = lease.clone();
+            async move { lease.acquire().await.is_expired() }
+        });
+
+        assert!(!next.await.unwrap());
+        assert!(token.is_expired());
+    }
+}
@@ -0,0 +1,29 @@
+//! Errors of leases.
+
+#[cfg(doc)]
+use super::Token;
+use core::{error::Error, fmt};
+
+/// Error returned by [`Token::renew`] if the lease expired before it was
+/// renewed, so it may be held by somebody else now
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ExpiredError;
+
+impl fmt::Display for ExpiredError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "renewing an expired lease")
+    }
+}
+
+impl Error for ExpiredError {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::string::ToString;
+
+    #[test]
+    fn test_display() {
+        assert_eq!(ExpiredError.to_string(), "renewing an expired lease");
+    }
+}
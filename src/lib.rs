@@ -4,13 +4,446 @@
 //! different functionalities. They're all grouped under the `laika` namespace,
 //! providing some kind of scoped crates (avoiding naming conflicts).
 //!
+//! Only the `std` feature is enabled by default. Without it, the crate is
+//! `no_std` (but requires `alloc`), see the module documentation of the
+//! submodules for what they support.
+//!
+//! Errors that return a value which couldn't be sent (like the `SendError` of
+//! [`shotgun`]) implement [`Debug`](core::fmt::Debug) without requiring the
+//! value to do so, omitting it instead. That way they can be unwrapped even if
+//! they contain e.g. closures.
+//!
 //! ## Submodules / Features
 //!
 //! ### [`shotgun`]
 //!
 //! Shotgun is a simple one-shot single producer, multiple consumer (SPMC)
-//! channel. It internally uses `std::sync::Mutex` and `std::sync::Arc` and does
-//! not contain any unsafe code.  
+//! channel. It internally uses `std::sync::OnceLock`, atomics and
+//! `std::sync::Arc` and does not contain any unsafe code. Also works in
+//! `no_std` builds with the `spin` feature.  
 //! See module documentation for more information.
-#[cfg(feature = "shotgun")]
+//!
+//! ### [`ack`]
+//!
+//! A work queue where every value has to be acknowledged once it has been
+//! processed. Rejected or timed out values are delivered again (at-least-once
+//! processing), optionally up to a maximum before they become dead letters.
+//! Requires `std`, doesn't contain any unsafe code.
+//!
+//! ### [`broadcast`]
+//!
+//! A multi-value, multi-producer multi-consumer broadcast channel with a
+//! bounded ring buffer. Receivers that fall behind get a `Lagged` error instead
+//! of blocking the producers. Requires `std`, doesn't contain any unsafe code.
+//!
+//! ### [`bus`]
+//!
+//! An in-process bus routing messages by their type: components subscribe to
+//! the types they're interested in, with a bounded queue per subscriber.
+//! Enables the `mpsc` feature, doesn't contain any unsafe code.
+//!
+//! ### [`chunks`]
+//!
+//! Yields the values of a receiver in chunks of a fixed size, optionally
+//! emitting partial chunks after a timeout, for batch-oriented downstream
+//! APIs. Enables the `merge` feature and requires `std`, doesn't contain any
+//! unsafe code.
+//!
+//! ### [`conflate`]
+//!
+//! A single-slot channel where newer values overwrite the ones that haven't
+//! been received yet, so the receiver always gets the latest state. Requires
+//! `std`, doesn't contain any unsafe code.
+//!
+//! ### [`counter`]
+//!
+//! Counters for concurrent code: a sharded counter spreading increments over
+//! multiple cache lines for hot paths and a counter tasks can wait on until it
+//! reaches a threshold. Requires `std`, doesn't contain any unsafe code.
+//!
+//! ### [`debounce`]
+//!
+//! Holds back the values of a receiver until it has been quiet for a while,
+//! yielding only the final value of each burst, e.g. for file watchers and UI
+//! input. Enables the `merge` feature and requires `std`, doesn't contain any
+//! unsafe code.
+//!
+//! ### [`event`]
+//!
+//! An async event that either stays set until cleared (manual reset) or lets
+//! one waiter proceed per set (auto reset), and can notify all current
+//! waiters. Requires `std`, doesn't contain any unsafe code.
+//!
+//! ### [`exchange`]
+//!
+//! A slot where two parties each offer a value and get the other one's in
+//! return, for double-buffering and ping-pong transfers of ownership. Requires
+//! `std`, doesn't contain any unsafe code.
+//!
+//! ### [`fanout`]
+//!
+//! A hub delivering every published message to all subscribers, each with an
+//! optional filter or transformation. Subscribers can come and go at any
+//! time. Enables the `mpsc` feature, doesn't contain any unsafe code.
+//!
+//! ### [`gate`]
+//!
+//! An open or closed gate that workers pass at their checkpoints, so a
+//! controller can pause a whole fleet of them and resume them all at once.
+//! Requires `std`, doesn't contain any unsafe code.
+//!
+//! ### [`generator`]
+//!
+//! A bridge from push-style callback APIs to a receiver, queueing the values
+//! emitted by callbacks with backpressure and unregistering them on drop.
+//! Enables the `mpsc` feature, doesn't contain any unsafe code.
+//!
+//! ### [`keyed`]
+//!
+//! A work queue for multiple workers that keeps the order of values with the
+//! same key, while values of different keys are processed concurrently.
+//! Requires `std`, doesn't contain any unsafe code.
+//!
+//! ### [`lease`]
+//!
+//! An exclusive token with a time to live that expires unless its holder
+//! renews it, so a hanging holder doesn't block the waiters forever. Requires
+//! `std`, doesn't contain any unsafe code.
+//!
+//! ### [`limit`]
+//!
+//! Runs futures with at most `n` of them executing at the same time, queueing
+//! the rest, as a wrapper around the semaphore of [`sync`] that can't be
+//! misused. Enables the `sync` feature, doesn't contain any unsafe code.
+//!
+//! ### [`mailbox`]
+//!
+//! An actor mailbox combining a bounded queue of messages with an unbounded
+//! control lane (e.g. to stop or pause the actor) that is received first.
+//! Enables the `mpsc` feature, doesn't contain any unsafe code.
+//!
+//! ### [`merge`]
+//!
+//! Combines the receivers of several channels (like [`shotgun`], [`mpsc`] or
+//! [`broadcast`]) into one that yields values from whichever channel produces
+//! next. Doesn't require `std` and doesn't contain any unsafe code.
+//!
+//! ### [`mpmc`]
+//!
+//! A multi-producer, multi-consumer work queue where every value is received
+//! by exactly one of the (cloneable) receivers. Requires `std`, doesn't contain
+//! any unsafe code.
+//!
+//! ### [`mpsc`]
+//!
+//! Multi-producer, single-consumer queues that work with any async runtime
+//! (or blocking threads). Requires `std`, doesn't contain any unsafe code.
+//!
+//! ### [`once`]
+//!
+//! An async once cell whose initializer runs only once, even if multiple tasks
+//! ask for the value at the same time, and a lazy value for async global
+//! singletons in a `static`. Requires `std`, doesn't contain any unsafe code.
+//!
+//! ### [`oneshot`]
+//!
+//! A oneshot single producer, single consumer channel that moves its value to
+//! the receiver, so unlike [`shotgun`] it doesn't require `T: Clone`. Requires
+//! `std`, doesn't contain any unsafe code.
+//!
+//! ### [`pipeline`]
+//!
+//! A builder that connects stages (closures or async functions) with bounded
+//! channels, runs each stage on its own thread and drains them in order on
+//! shutdown. Enables the `mpsc` feature, doesn't contain any unsafe code.
+//!
+//! ### [`poller`]
+//!
+//! A bridge from completion callbacks to futures: the callback completes a
+//! [`shotgun`] channel and the future resolves, with completing twice and
+//! dropped callbacks handled. Enables the `shotgun` feature, doesn't require
+//! `std` and doesn't contain any unsafe code.
+//!
+//! ### [`progress`]
+//!
+//! A channel where a producer reports the progress of an operation and
+//! finally its result. Receivers read the progress, wait until it changed or
+//! wait for the result. Requires `std`, doesn't contain any unsafe code.
+//!
+//! ### [`pubsub`]
+//!
+//! An MQTT-flavored hub delivering messages published on topics to the
+//! subscriptions whose pattern matches, with `*` and `#` wildcards and
+//! retained messages. Enables the `mpsc` feature, doesn't contain any unsafe
+//! code.
+//!
+//! ### [`race`]
+//!
+//! A channel where many producers race for delivering a single value to the
+//! receiver. The first one wins, the others learn that they lost, e.g. for
+//! hedged lookups. Requires `std`, doesn't contain any unsafe code.
+//!
+//! ### [`rendezvous`]
+//!
+//! A zero-capacity channel where a send only completes once a receiver took
+//! the value, for strict handoffs between tasks or threads. Requires `std`,
+//! doesn't contain any unsafe code.
+//!
+//! ### [`replay`]
+//!
+//! A broadcast channel that retains the last `capacity` (or all) values and
+//! replays them to receivers subscribing later. Requires `std`, doesn't
+//! contain any unsafe code.
+//!
+//! ### [`router`]
+//!
+//! Routes the messages of an [`mpsc`] channel to one of several outputs,
+//! chosen by predicates or a hash, with a fallback route. Enables the `mpsc`
+//! feature, doesn't contain any unsafe code.
+//!
+//! ### [`sampling`]
+//!
+//! Yields only the latest value of a receiver per tick of an interval,
+//! discarding the ones in between, to decimate high-frequency streams.
+//! Enables the `merge` feature and requires `std`, doesn't contain any unsafe
+//! code.
+//!
+//! ### [`singleflight`]
+//!
+//! Coalesces concurrent calls for the same key into a single execution whose
+//! result all of them get a clone of, suppressing thundering herds. Enables
+//! the `once` feature, doesn't contain any unsafe code.
+//!
+//! ### [`spsc`]
+//!
+//! A fixed-capacity single producer, single consumer ring buffer for low
+//! latency hot paths, with blocking and async push and pop. Requires `std`,
+//! doesn't contain any unsafe code.
+//!
+//! ### [`swapcell`]
+//!
+//! A cell holding an `Arc` that readers load without contending with each
+//! other and writers replace atomically, with an awaitable change
+//! notification, for hot configuration and routing tables. Requires `std`,
+//! doesn't contain any unsafe code.
+//!
+//! ### [`sync`]
+//!
+//! Runtime-agnostic async synchronization primitives: a semaphore handing out
+//! RAII permits in FIFO order and a reusable barrier. Requires `std`, doesn't
+//! contain any unsafe code.
+//!
+//! ### [`tee`]
+//!
+//! Duplicates every message of one channel into several others, with a
+//! backpressure strategy per branch. Enables the `mpsc` feature, doesn't
+//! contain any unsafe code.
+//!
+//! ### [`throttle`]
+//!
+//! Limits the rate of values received from a channel, optionally allowing
+//! bursts. No values are lost, they wait in the channel (suspending producers
+//! of bounded ones) instead. Enables the `merge` feature and requires `std`,
+//! doesn't contain any unsafe code.
+//!
+//! ### [`watch`]
+//!
+//! A channel that only keeps the latest value, for config and state
+//! propagation. Receivers borrow the current value or wait until it changed.
+//! Requires `std`, doesn't contain any unsafe code.
+//!
+//! ### [`window`]
+//!
+//! Keeps a sliding window (by count or time) over the values of a receiver
+//! and yields an aggregate folded from it for every value, for rolling
+//! metrics and rates. Enables the `merge` feature and requires `std`, doesn't
+//! contain any unsafe code.
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+// Tests use threads even without `std`
+#[cfg(all(test, not(feature = "std")))]
+extern crate std;
+
+/// Implements [`fmt::Debug`](core::fmt::Debug) for an error containing a
+/// value, without requiring the value to implement it (see the crate docs).
+/// Takes either a tuple struct or an enum along with its variants.
+#[allow(unused_macros)]
+macro_rules! debug_without_value {
+    ($error:ident) => {
+        impl<T> core::fmt::Debug for $error<T> {
+            fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                write!(f, concat!(stringify!($error), "(..)"))
+            }
+        }
+    };
+    ($error:ident { $($variant:ident),+ $(,)? }) => {
+        impl<T> core::fmt::Debug for $error<T> {
+            fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                match self {
+                    $($error::$variant(_) => write!(f, concat!(stringify!($variant), "(..)")),)+
+                }
+            }
+        }
+    };
+}
+
+/// Locks `mutex`, ignoring poisoning.
+///
+/// The modules only hold their locks to update their own bookkeeping and
+/// finish doing so before anything that could panic, so a panic while locked
+/// can't leave the state half updated. Modules that run user code while locked
+/// document how they stay consistent.
+#[cfg(any(
+    feature = "ack",
+    feature = "broadcast",
+    feature = "chunks",
+    feature = "conflate",
+    feature = "counter",
+    feature = "debounce",
+    feature = "event",
+    feature = "exchange",
+    feature = "gate",
+    feature = "keyed",
+    feature = "lease",
+    feature = "mpmc",
+    feature = "mpsc",
+    feature = "once",
+    feature = "oneshot",
+    feature = "progress",
+    feature = "race",
+    feature = "rendezvous",
+    feature = "replay",
+    feature = "sampling",
+    feature = "spsc",
+    feature = "swapcell",
+    feature = "sync",
+    feature = "throttle",
+    feature = "watch",
+    all(feature = "shotgun", feature = "std", not(laika_loom))
+))]
+fn lock<T>(mutex: &std::sync::Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+#[cfg(feature = "ack")]
+pub mod ack;
+#[cfg(feature = "broadcast")]
+pub mod broadcast;
+#[cfg(feature = "bus")]
+pub mod bus;
+#[cfg(feature = "chunks")]
+pub mod chunks;
+#[cfg(feature = "conflate")]
+pub mod conflate;
+#[cfg(feature = "counter")]
+pub mod counter;
+#[cfg(feature = "debounce")]
+pub mod debounce;
+#[cfg(feature = "event")]
+pub mod event;
+#[cfg(feature = "exchange")]
+pub mod exchange;
+#[cfg(feature = "fanout")]
+pub mod fanout;
+#[cfg(feature = "gate")]
+pub mod gate;
+#[cfg(feature = "generator")]
+pub mod generator;
+#[cfg(feature = "keyed")]
+pub mod keyed;
+#[cfg(feature = "lease")]
+pub mod lease;
+#[cfg(feature = "limit")]
+pub mod limit;
+#[cfg(feature = "mailbox")]
+pub mod mailbox;
+#[cfg(feature = "merge")]
+pub mod merge;
+#[cfg(feature = "mpmc")]
+pub mod mpmc;
+#[cfg(feature = "mpsc")]
+pub mod mpsc;
+#[cfg(feature = "once")]
+pub mod once;
+#[cfg(feature = "oneshot")]
+pub mod oneshot;
+#[cfg(any(
+    feature = "broadcast",
+    feature = "mpmc",
+    feature = "mpsc",
+    feature = "spsc"
+))]
+mod overflow;
+#[cfg(any(
+    feature = "chunks",
+    feature = "debounce",
+    feature = "mailbox",
+    feature = "pipeline",
+    feature = "rendezvous",
+    feature = "sampling",
+    feature = "spsc",
+    feature = "throttle",
+    feature = "window"
+))]
+mod park;
+#[cfg(feature = "pipeline")]
+pub mod pipeline;
+#[cfg(all(feature = "poller", any(feature = "std", feature = "spin")))]
+pub mod poller;
+#[cfg(feature = "progress")]
+pub mod progress;
+#[cfg(feature = "pubsub")]
+pub mod pubsub;
+#[cfg(feature = "race")]
+pub mod race;
+#[cfg(feature = "rendezvous")]
+pub mod rendezvous;
+#[cfg(feature = "replay")]
+pub mod replay;
+#[cfg(feature = "router")]
+pub mod router;
+#[cfg(feature = "sampling")]
+pub mod sampling;
+// Without a lock backend, only the error below is reported, instead of every
+// use of the missing locks.
+#[cfg(all(feature = "shotgun", not(any(feature = "std", feature = "spin"))))]
+compile_error!("shotgun requires either the `std` or the `spin` feature");
+#[cfg(all(feature = "shotgun", any(feature = "std", feature = "spin")))]
 pub mod shotgun;
+#[cfg(feature = "singleflight")]
+pub mod singleflight;
+#[cfg(feature = "spsc")]
+pub mod spsc;
+#[cfg(feature = "swapcell")]
+pub mod swapcell;
+#[cfg(feature = "sync")]
+pub mod sync;
+#[cfg(feature = "tee")]
+pub mod tee;
+#[cfg(feature = "throttle")]
+pub mod throttle;
+#[cfg(any(
+    feature = "ack",
+    feature = "chunks",
+    feature = "debounce",
+    feature = "lease",
+    feature = "sampling",
+    feature = "throttle",
+    all(feature = "shotgun", feature = "std", not(laika_loom))
+))]
+mod timer;
+#[cfg(feature = "watch")]
+pub mod watch;
+#[cfg(feature = "window")]
+pub mod window;
+
+#[cfg(any(
+    feature = "broadcast",
+    feature = "mpmc",
+    feature = "mpsc",
+    feature = "spsc"
+))]
+pub use overflow::Overflow;
@@ -0,0 +1,258 @@
+#![forbid(unsafe_code)]
+//! # Limiting concurrency
+//!
+//! [`Concurrency`] runs futures with at most `n` of them executing at the same
+//! time: [`Concurrency::run`] waits for a free slot, runs the future and frees
+//! the slot once it's done. Futures beyond the limit queue up and start in the
+//! order they arrived, e.g. to cap the number of requests to a service or of
+//! files open at once.
+//!
+//! It's a thin layer over the [`Semaphore`](crate::sync::Semaphore) of the
+//! `sync` module that can't be misused: there are no permits to forget,
+//! acquire twice or hold longer than needed. A slot is freed when its future
+//! completes, panics or is dropped (e.g. cancelled by a timeout), and a future
+//! that is dropped while it's queued gives up its place.
+//!
+//! The limiter doesn't contain any unsafe code and works with any async
+//! runtime.
+//!
+//! # Examples
+//! ```rust
+//! # tokio::runtime::Builder::new_current_thread()
+//! #     .build()
+//! #     .unwrap()
+//! #     .block_on(async {
+//! use laika::limit::Concurrency;
+//!
+//! // At most 2 downloads at once, shared by all callers
+//! static DOWNLOADS: Concurrency = Concurrency::new(2);
+//!
+//! async fn download(id: u32) -> u32 {
+//!     DOWNLOADS
+//!         .run(async move {
+//!             assert!(DOWNLOADS.running() <= 2);
+//!             tokio::task::yield_now().await;
+//!             id
+//!         })
+//!         .await
+//! }
+//!
+//! let tasks: Vec<_> = (0..5).map(|id| tokio::spawn(download(id))).collect();
+//!
+//! for (id, task) in tasks.into_iter().enumerate() {
+//!     assert_eq!(task.await.unwrap(), id as u32);
+//! }
+//!
+//! assert_eq!(DOWNLOADS.running(), 0);
+//! # });
+//! ```
+
+use crate::sync::{Permit, Semaphore};
+use core::{fmt, future::Future};
+
+/// Limiter running at most `n` futures at the same time
+///
+/// See the [module documentation](self) for details.
+pub struct Concurrency {
+    /// Maximum number of futures running at the same time
+    limit: usize,
+    /// Semaphore with one permit per slot
+    semaphore: Semaphore,
+}
+
+impl Concurrency {
+    /// Creates a limiter running at most `n` futures at the same time. As it's
+    /// a const function, the limiter can be placed in a `static`.
+    ///
+    /// # Panics
+    /// Panics if `n` is 0, as no future would ever run.
+    pub const fn new(n: usize) -> Self {
+        assert!(n > 0, "the concurrency limit must be at least 1");
+
+        Concurrency {
+            limit: n,
+            semaphore: Semaphore::new(n),
+        }
+    }
+
+    /// Acquires a slot. The semaphore is never closed, so it can't fail.
+    async fn slot(&self) -> Permit<'_> {
+        self.semaphore
+            .acquire(1)
+            .await
+            .expect("the semaphore of a limiter is never closed")
+    }
+
+    /// Runs `future` once fewer than `n` futures are running, waiting in line
+    /// otherwise, and returns its output.
+    ///
+    /// The slot is freed once `future` completed or panicked, or once the
+    /// returned future is dropped.
+    pub async fn run<F>(&self, future: F) -> F::Output
+    where
+        F: Future,
+    {
+        let _slot = self.slot().await;
+
+        future.await
+    }
+
+    /// Runs `f` once fewer than `n` futures (or functions) are running,
+    /// blocking the current thread in line otherwise, and returns its result.
+    /// Shares the limit with [`Concurrency::run`].
+    ///
+    /// # Examples
+    /// ```rust
+    /// use laika::limit::Concurrency;
+    /// use std::{sync::Arc, thread};
+    ///
+    /// let disk = Arc::new(Concurrency::new(1));
+    ///
+    /// let writers: Vec<_> = (0..3)
+    ///     .map(|id| {
+    ///         let disk = Arc::clone(&disk);
+    ///         thread::spawn(move || disk.run_blocking(|| id * 2))
+    ///     })
+    ///     .collect();
+    ///
+    /// let written: u32 = writers.into_iter().map(|w| w.join().unwrap()).sum();
+    /// assert_eq!(written, 6);
+    /// ```
+    pub fn run_blocking<F, T>(&self, f: F) -> T
+    where
+        F: FnOnce() -> T,
+    {
+        let _slot = self
+            .semaphore
+            .acquire_blocking(1)
+            .expect("the semaphore of a limiter is never closed");
+
+        f()
+    }
+
+    /// Returns the maximum number of futures running at the same time.
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Returns the number of futures running right now.
+    pub fn running(&self) -> usize {
+        self.limit - self.semaphore.available_permits()
+    }
+}
+
+impl fmt::Debug for Concurrency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Concurrency")
+            .field("limit", &self.limit)
+            .field("running", &self.running())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::{
+        pin::pin,
+        task::{Context, Poll, Waker},
+    };
+    use std::{
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        thread,
+        vec::Vec,
+    };
+
+    #[test]
+    fn test_queue() {
+        let limit = Concurrency::new(1);
+        let mut cx = Context::from_waker(Waker::noop());
+        let done = AtomicBool::new(false);
+
+        let mut first = pin!(limit.run(core::future::poll_fn(|_| {
+            match done.load(Ordering::Relaxed) {
+                true => Poll::Ready(1),
+                false => Poll::Pending,
+            }
+        })));
+        let mut second = pin!(limit.run(async { 2 }));
+
+        assert!(first.as_mut().poll(&mut cx).is_pending());
+        assert!(second.as_mut().poll(&mut cx).is_pending());
+        assert_eq!(limit.running(), 1);
+
+        done.store(true, Ordering::Relaxed);
+        assert_eq!(first.as_mut().poll(&mut cx), Poll::Ready(1));
+        assert_eq!(second.as_mut().poll(&mut cx), Poll::Ready(2));
+        assert_eq!(limit.running(), 0);
+    }
+
+    #[test]
+    fn test_cancel() {
+        let limit = Concurrency::new(1);
+        let mut cx = Context::from_waker(Waker::noop());
+
+        {
+            let mut running = pin!(limit.run(core::future::pending::<()>()));
+            assert!(running.as_mut().poll(&mut cx).is_pending());
+            assert_eq!(limit.running(), 1);
+        }
+
+        // Dropping a running future frees its slot
+        assert_eq!(limit.running(), 0);
+        assert_eq!(
+            pin!(limit.run(async { 'a' })).poll(&mut cx),
+            Poll::Ready('a')
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_zero() {
+        Concurrency::new(0);
+    }
+
+    #[test]
+    fn test_panic() {
+        let limit = Arc::new(Concurrency::new(1));
+
+        let panicking = thread::spawn({
+            let limit = Arc::clone(&limit);
+            move || limit.run_blocking(|| panic!("failed"))
+        });
+
+        assert!(panicking.join().is_err());
+        assert_eq!(limit.run_blocking(|| limit.running()), 1);
+        assert_eq!(limit.running(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_run() {
+        let limit = Arc::new(Concurrency::new(2));
+
+        let tasks: Vec<_> = (0..6)
+            .map(|_| {
+                let limit = Arc::clone(&limit);
+
+                tokio::spawn(async move {
+                    let inner = Arc::clone(&limit);
+
+                    limit
+                        .run(async move {
+                            let running = inner.running();
+                            tokio::task::yield_now().await;
+                            running
+                        })
+                        .await
+                })
+            })
+            .collect();
+
+        for task in tasks {
+            assert!(task.await.unwrap() <= 2);
+        }
+    }
+}
@@ -0,0 +1,405 @@
+#![forbid(unsafe_code)]
+//! # Actor mailboxes with a prioritized control lane
+//!
+//! A [`Mailbox`] is the receiving end of an actor: it combines a bounded queue
+//! of regular messages with a control lane for system messages like stopping
+//! or pausing the actor. Receiving prefers the control lane, so control
+//! messages overtake the regular messages queued before them and an actor
+//! reacts to them even while it's flooded with work.
+//!
+//! [`channel`] creates a mailbox and its (cloneable) [`Address`]. Regular
+//! messages are sent with [`Address::send`] (or [`Address::send_blocking`]),
+//! which waits while the queue is full (backpressure). Control messages are
+//! sent with [`Address::send_control`], which never waits: the control lane
+//! isn't bounded, so a full queue of regular messages can't hold up a stop.
+//!
+//! Both lanes are [`mpsc`](crate::mpsc) channels. The mailbox receives an
+//! [`Envelope`] holding either kind of message, and [`None`] once all
+//! addresses have been dropped and both lanes are empty. It doesn't contain
+//! any unsafe code and works with any async runtime.
+//!
+//! ## Optional features
+//!
+//! - `futures`: Implements `futures_core::Stream` for [`Mailbox`].
+//!
+//! # Examples
+//! ```rust
+//! use laika::mailbox::{self, Envelope};
+//!
+//! #[derive(Debug, PartialEq)]
+//! enum Control {
+//!     Pause,
+//!     Stop,
+//! }
+//!
+//! let (address, mut mailbox) = mailbox::channel(8);
+//!
+//! address.send_blocking("resize image").unwrap();
+//! address.send_blocking("upload image").unwrap();
+//! address.send_control(Control::Stop).unwrap();
+//!
+//! // The control message overtakes the queued work
+//! assert_eq!(mailbox.recv_blocking(), Some(Envelope::Control(Control::Stop)));
+//! assert_eq!(mailbox.recv_blocking(), Some(Envelope::Message("resize image")));
+//! ```
+
+use crate::{
+    mpsc::{
+        self, Receiver, SendError, Sender, TryRecvError, TrySendError, UnboundedReceiver,
+        UnboundedSender,
+    },
+    park,
+};
+#[cfg(feature = "futures")]
+use core::pin::Pin;
+use core::{
+    fmt,
+    task::{Context, Poll},
+};
+
+/// Message received from a [`Mailbox`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Envelope<M, C> {
+    /// A control message, sent with [`Address::send_control`]
+    Control(C),
+    /// A regular message, sent with [`Address::send`]
+    Message(M),
+}
+
+/// Sending side of a [`Mailbox`]
+///
+/// Can be cloned to send from multiple places. The mailbox is closed once all
+/// addresses have been dropped.
+pub struct Address<M, C> {
+    /// Sender of the regular messages
+    messages: Sender<M>,
+    /// Sender of the control messages
+    control: UnboundedSender<C>,
+}
+
+/// Receiving side of an actor, preferring control messages over regular ones
+///
+/// See the [module documentation](self) for details.
+pub struct Mailbox<M, C> {
+    /// Receiver of the regular messages
+    messages: Receiver<M>,
+    /// Receiver of the control messages
+    control: UnboundedReceiver<C>,
+}
+
+impl<M, C> Unpin for Mailbox<M, C> {}
+
+impl<M, C> Address<M, C> {
+    /// Queues a regular message if there's room for it. This function is
+    /// **non-blocking**.
+    ///
+    /// Returns the message in [`TrySendError::Full`] if the queue is full or
+    /// [`TrySendError::Disconnected`] if the [`Mailbox`] has been dropped.
+    pub fn try_send(&self, message: M) -> Result<(), TrySendError<M>> {
+        self.messages.try_send(message)
+    }
+
+    /// Queues a regular message, waiting asynchronously until there's room
+    /// for it.
+    ///
+    /// Resolves to [`SendError::Disconnected`] containing the message if the
+    /// [`Mailbox`] has been dropped (also while waiting). If the future is
+    /// dropped before it resolves, the message isn't sent.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # tokio::runtime::Builder::new_current_thread()
+    /// #     .build()
+    /// #     .unwrap()
+    /// #     .block_on(async {
+    /// use laika::mailbox::{self, Envelope};
+    ///
+    /// let (address, mut mailbox) = mailbox::channel::<u32, ()>(2);
+    ///
+    /// // A counting actor
+    /// let actor = tokio::spawn(async move {
+    ///     let mut sum = 0;
+    ///
+    ///     while let Some(envelope) = mailbox.recv().await {
+    ///         match envelope {
+    ///             Envelope::Message(value) => sum += value,
+    ///             Envelope::Control(()) => break,
+    ///         }
+    ///     }
+    ///
+    ///     sum
+    /// });
+    ///
+    /// for value in 1..=10 {
+    ///     address.send(value).await.unwrap();
+    /// }
+    ///
+    /// drop(address);
+    /// assert_eq!(actor.await.unwrap(), 55);
+    /// # });
+    /// ```
+    pub async fn send(&self, message: M) -> Result<(), SendError<M>> {
+        self.messages.send(message).await
+    }
+
+    /// Queues a regular message, blocking the current thread until there's
+    /// room for it. See [`Address::send`].
+    pub fn send_blocking(&self, message: M) -> Result<(), SendError<M>> {
+        self.messages.send_blocking(message)
+    }
+
+    /// Queues a control message, which is received before any regular
+    /// message. Never waits, as the control lane isn't bounded.
+    ///
+    /// Returns [`SendError`] containing the message if the [`Mailbox`] has
+    /// been dropped.
+    pub fn send_control(&self, control: C) -> Result<(), SendError<C>> {
+        self.control.send(control)
+    }
+
+    /// Returns whether the [`Mailbox`] has been dropped, so sending would
+    /// fail.
+    pub fn is_closed(&self) -> bool {
+        self.messages.is_closed()
+    }
+
+    /// Returns the maximum number of queued regular messages.
+    pub fn capacity(&self) -> usize {
+        self.messages.capacity()
+    }
+}
+
+impl<M, C> Clone for Address<M, C> {
+    fn clone(&self) -> Self {
+        Address {
+            messages: self.messages.clone(),
+            control: self.control.clone(),
+        }
+    }
+}
+
+impl<M, C> fmt::Debug for Address<M, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Address")
+            .field("closed", &self.is_closed())
+            .field("capacity", &self.capacity())
+            .finish()
+    }
+}
+
+impl<M, C> Mailbox<M, C> {
+    /// Receives the oldest control message, or the oldest regular message if
+    /// there is none. This function is **non-blocking**.
+    ///
+    /// Returns [`TryRecvError::Empty`] if both lanes are empty or
+    /// [`TryRecvError::Disconnected`] if they are and all addresses have been
+    /// dropped.
+    pub fn try_recv(&mut self) -> Result<Envelope<M, C>, TryRecvError> {
+        let control = match self.control.try_recv() {
+            Ok(control) => return Ok(Envelope::Control(control)),
+            Err(error) => error,
+        };
+
+        match self.messages.try_recv() {
+            Ok(message) => Ok(Envelope::Message(message)),
+            Err(TryRecvError::Disconnected) if control == TryRecvError::Disconnected => {
+                Err(TryRecvError::Disconnected)
+            }
+            Err(_) => Err(TryRecvError::Empty),
+        }
+    }
+
+    /// Receives the oldest control message if there is one, leaving regular
+    /// messages queued. This function is **non-blocking**, e.g. for actors
+    /// checking for a stop while processing a long running message.
+    ///
+    /// Returns [`TryRecvError::Empty`] if no control message is queued or
+    /// [`TryRecvError::Disconnected`] if none is and all addresses have been
+    /// dropped.
+    pub fn try_recv_control(&mut self) -> Result<C, TryRecvError> {
+        self.control.try_recv()
+    }
+
+    /// Polls for the oldest control message, or the oldest regular message if
+    /// there is none, registering the waker of `cx` with both lanes if both
+    /// are empty. Resolves to [`None`] once both lanes are empty and all
+    /// addresses have been dropped.
+    pub fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<Envelope<M, C>>> {
+        let control = self.control.poll_recv(cx);
+
+        if let Poll::Ready(Some(control)) = control {
+            return Poll::Ready(Some(Envelope::Control(control)));
+        }
+
+        match self.messages.poll_recv(cx) {
+            Poll::Ready(Some(message)) => Poll::Ready(Some(Envelope::Message(message))),
+            Poll::Ready(None) if control.is_ready() => Poll::Ready(None),
+            _ => Poll::Pending,
+        }
+    }
+
+    /// Receives the oldest control message, or the oldest regular message if
+    /// there is none, waiting asynchronously until there is one. Resolves to
+    /// [`None`] once both lanes are empty and all addresses have been dropped.
+    pub async fn recv(&mut self) -> Option<Envelope<M, C>> {
+        core::future::poll_fn(|cx| self.poll_recv(cx)).await
+    }
+
+    /// Receives the oldest control message, or the oldest regular message if
+    /// there is none, blocking the current thread until there is one. See
+    /// [`Mailbox::recv`].
+    pub fn recv_blocking(&mut self) -> Option<Envelope<M, C>> {
+        park::block_on(|cx| self.poll_recv(cx))
+    }
+
+    /// Returns the number of queued regular messages.
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    /// Returns whether no regular message is queued.
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+
+    /// Returns the number of queued control messages.
+    pub fn control_len(&self) -> usize {
+        self.control.len()
+    }
+}
+
+impl<M, C> fmt::Debug for Mailbox<M, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Mailbox")
+            .field("len", &self.len())
+            .field("control_len", &self.control_len())
+            .field("capacity", &self.messages.capacity())
+            .finish()
+    }
+}
+
+/// Implement [`futures_core::Stream`] for [`Mailbox`], yielding envelopes
+/// until all addresses have been dropped and both lanes are empty.
+#[cfg(feature = "futures")]
+impl<M, C> futures_core::Stream for Mailbox<M, C> {
+    type Item = Envelope<M, C>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Envelope<M, C>>> {
+        self.get_mut().poll_recv(cx)
+    }
+}
+
+/// Creates a mailbox queueing at most `capacity` regular messages, and its
+/// [`Address`].
+///
+/// # Panics
+/// Panics if `capacity` is zero.
+pub fn channel<M, C>(capacity: usize) -> (Address<M, C>, Mailbox<M, C>) {
+    let (messages, messages_rx) = mpsc::bounded(capacity);
+    let (control, control_rx) = mpsc::unbounded();
+
+    (
+        Address { messages, control },
+        Mailbox {
+            messages: messages_rx,
+            control: control_rx,
+        },
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::{thread, time::Duration, vec::Vec};
+
+    #[test]
+    fn test_priority() {
+        let (address, mut mailbox) = channel(2);
+
+        address.try_send(1).unwrap();
+        address.try_send(2).unwrap();
+        assert_eq!(address.try_send(3), Err(TrySendError::Full(3)));
+
+        // The control lane isn't bounded
+        address.send_control('a').unwrap();
+        address.send_control('b').unwrap();
+        assert_eq!(mailbox.control_len(), 2);
+
+        assert_eq!(mailbox.try_recv(), Ok(Envelope::Control('a')));
+        assert_eq!(mailbox.try_recv_control(), Ok('b'));
+        assert_eq!(mailbox.try_recv_control(), Err(TryRecvError::Empty));
+        assert_eq!(mailbox.try_recv(), Ok(Envelope::Message(1)));
+
+        address.send_control('c').unwrap();
+        drop(address);
+
+        assert_eq!(mailbox.try_recv(), Ok(Envelope::Control('c')));
+        assert_eq!(mailbox.try_recv(), Ok(Envelope::Message(2)));
+        assert_eq!(mailbox.try_recv(), Err(TryRecvError::Disconnected));
+    }
+
+    #[test]
+    fn test_recv_blocking() {
+        let (address, mut mailbox) = channel(1);
+
+        let producer = thread::spawn(move || {
+            for value in 0..3 {
+                address.send_blocking(value).unwrap();
+            }
+
+            // Not held up by the full queue
+            address.send_control("stop").unwrap();
+        });
+
+        thread::sleep(Duration::from_millis(20));
+
+        let mut received = Vec::new();
+
+        while let Some(envelope) = mailbox.recv_blocking() {
+            received.push(envelope);
+        }
+
+        producer.join().unwrap();
+
+        assert_eq!(received.len(), 4);
+        assert!(received.contains(&Envelope::Control("stop")));
+        assert_eq!(
+            received
+                .iter()
+                .filter_map(|envelope| match envelope {
+                    Envelope::Message(value) => Some(*value),
+                    Envelope::Control(_) => None,
+                })
+                .collect::<Vec<_>>(),
+            [0, 1, 2]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_recv() {
+        let (address, mut mailbox) = channel::<u8, bool>(4);
+
+        let actor = tokio::spawn(async move {
+            let mut paused = false;
+            let mut processed = Vec::new();
+
+            while let Some(envelope) = mailbox.recv().await {
+                match envelope {
+                    Envelope::Control(pause) => paused = pause,
+                    Envelope::Message(value) if !paused => processed.push(value),
+                    Envelope::Message(_) => {}
+                }
+            }
+
+            processed
+        });
+
+        address.send(1).await.unwrap();
+        address.send(2).await.unwrap();
+        // Overtakes both messages
+        address.send_control(true).unwrap();
+
+        drop(address);
+        assert!(actor.await.unwrap().is_empty());
+    }
+}
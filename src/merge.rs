@@ -0,0 +1,383 @@
+#![forbid(unsafe_code)]
+//! # Merging multiple receivers into one
+//!
+//! A [`Merge`] combines the receivers of several channels into a single
+//! receiver that yields values from whichever channel produces next. Inputs
+//! are polled in turns (starting after the one that produced last), so a
+//! busy channel doesn't starve the others. Once an input has been closed it's
+//! removed, and the merge completes once all of its inputs have been closed.
+//!
+//! Inputs are anything implementing [`Source`], which is implemented for the
+//! receivers of the enabled channel modules:
+//!
+//! - [`shotgun::Receiver`](crate::shotgun::Receiver): Yields the value once.
+//! - [`mpsc::Receiver`](crate::mpsc::Receiver) and
+//!   [`mpsc::UnboundedReceiver`](crate::mpsc::UnboundedReceiver)
+//! - [`mpmc::Receiver`](crate::mpmc::Receiver)
+//! - [`broadcast::Receiver`](crate::broadcast::Receiver): Values a lagging
+//!   receiver missed are skipped.
+//! - [`generator::Generator`](crate::generator::Generator)
+//!
+//! Inputs don't have to be of the same channel type, as long as they yield
+//! the same type of values. The merge doesn't contain any unsafe code and
+//! works with any async runtime.
+//!
+//! ## Optional features
+//!
+//! - `futures`: Implements `futures_core::Stream` for [`Merge`].
+//!
+//! # Examples
+#![cfg_attr(all(feature = "mpsc", feature = "shotgun"), doc = "```rust")]
+#![cfg_attr(not(all(feature = "mpsc", feature = "shotgun")), doc = "```ignore")]
+//! # tokio::runtime::Builder::new_current_thread()
+//! #     .build()
+//! #     .unwrap()
+//! #     .block_on(async {
+//! use laika::{merge::Merge, mpsc, shotgun};
+//!
+//! let (events, events_rx) = mpsc::unbounded();
+//! let (shutdown, shutdown_rx) = shotgun::channel();
+//!
+//! let mut merge = Merge::new().with(events_rx).with(shutdown_rx);
+//!
+//! events.send("tick").unwrap();
+//! shutdown.send("shutdown").unwrap();
+//! drop(events);
+//!
+//! let mut received = Vec::new();
+//!
+//! while let Some(message) = merge.recv().await {
+//!     received.push(message);
+//! }
+//!
+//! received.sort();
+//! assert_eq!(received, ["shutdown", "tick"]);
+//! # });
+//! ```
+
+use alloc::{boxed::Box, vec::Vec};
+#[cfg(feature = "futures")]
+use core::pin::Pin;
+use core::{
+    fmt,
+    task::{Context, Poll},
+};
+
+/// Input of a [`Merge`]
+///
+/// Implemented for the receivers of the channel modules. Implement it for
+/// custom inputs, e.g. to wrap other channels.
+pub trait Source<T> {
+    /// Polls for the next value, registering the waker of `cx` if there is
+    /// none yet. Resolves to [`None`] once the input has been closed, after
+    /// which it isn't polled again.
+    fn poll_next(&mut self, cx: &mut Context<'_>) -> Poll<Option<T>>;
+}
+
+#[cfg(all(feature = "shotgun", any(feature = "std", feature = "spin")))]
+impl<T> Source<T> for crate::shotgun::Receiver<T>
+where
+    T: Clone,
+{
+    fn poll_next(&mut self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        self.poll_once(cx)
+    }
+}
+
+#[cfg(feature = "mpsc")]
+impl<T> Source<T> for crate::mpsc::Receiver<T> {
+    fn poll_next(&mut self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        self.poll_recv(cx)
+    }
+}
+
+#[cfg(feature = "mpsc")]
+impl<T> Source<T> for crate::mpsc::UnboundedReceiver<T> {
+    fn poll_next(&mut self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        self.poll_recv(cx)
+    }
+}
+
+#[cfg(feature = "mpmc")]
+impl<T> Source<T> for crate::mpmc::Receiver<T> {
+    fn poll_next(&mut self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        self.poll_recv(cx)
+    }
+}
+
+#[cfg(feature = "broadcast")]
+impl<T> Source<T> for crate::broadcast::Receiver<T>
+where
+    T: Clone,
+{
+    fn poll_next(&mut self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        use crate::broadcast::RecvError;
+
+        loop {
+            match core::task::ready!(self.poll_recv(cx)) {
+                Ok(value) => return Poll::Ready(Some(value)),
+                Err(RecvError::Closed) => return Poll::Ready(None),
+                // The receiver continues at the oldest retained value
+                Err(RecvError::Lagged(_)) => {}
+            }
+        }
+    }
+}
+
+#[cfg(feature = "generator")]
+impl<T> Source<T> for crate::generator::Generator<T> {
+    fn poll_next(&mut self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        self.poll_recv(cx)
+    }
+}
+
+/// Receiver yielding the values of multiple inputs
+///
+/// See the [module documentation](self) for details.
+pub struct Merge<T> {
+    /// Inputs that haven't been closed yet
+    sources: Vec<Box<dyn Source<T> + Send>>,
+    /// Index of the input that is polled first
+    next: usize,
+}
+
+impl<T> Merge<T> {
+    /// Creates a merge without any inputs, which completes right away unless
+    /// inputs are added.
+    pub fn new() -> Self {
+        Merge {
+            sources: Vec::new(),
+            next: 0,
+        }
+    }
+
+    /// Adds an input, see [`Merge::push`].
+    pub fn with<S>(mut self, source: S) -> Self
+    where
+        S: Source<T> + Send + 'static,
+    {
+        self.push(source);
+        self
+    }
+
+    /// Adds an input. It's polled after the inputs that have been added
+    /// before.
+    pub fn push<S>(&mut self, source: S)
+    where
+        S: Source<T> + Send + 'static,
+    {
+        self.sources.push(Box::new(source));
+    }
+
+    /// Returns the number of inputs that haven't been closed yet.
+    pub fn len(&self) -> usize {
+        self.sources.len()
+    }
+
+    /// Returns whether all inputs have been closed (or none have been added),
+    /// so the merge is complete.
+    pub fn is_empty(&self) -> bool {
+        self.sources.is_empty()
+    }
+
+    /// Polls the inputs for the next value, registering the waker of `cx` with
+    /// all of them if none has one. Resolves to [`None`] once all inputs have
+    /// been closed.
+    pub fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        let mut polled = 0;
+
+        while polled < self.sources.len() {
+            let index = (self.next + polled) % self.sources.len();
+
+            match self.sources[index].poll_next(cx) {
+                Poll::Ready(Some(value)) => {
+                    self.next = index + 1;
+                    return Poll::Ready(Some(value));
+                }
+                Poll::Ready(None) => {
+                    // The inputs after the closed one move up, so the same
+                    // number of inputs has been polled from `next` on.
+                    self.sources.remove(index);
+
+                    if index < self.next {
+                        self.next -= 1;
+                    }
+                }
+                Poll::Pending => polled += 1,
+            }
+        }
+
+        if self.sources.is_empty() {
+            Poll::Ready(None)
+        } else {
+            Poll::Pending
+        }
+    }
+
+    /// Receives the next value of any input, waiting asynchronously until one
+    /// produces a value. Resolves to [`None`] once all inputs have been
+    /// closed.
+    ///
+    /// # Examples
+    #[cfg_attr(feature = "mpsc", doc = "```rust")]
+    #[cfg_attr(not(feature = "mpsc"), doc = "```ignore")]
+    /// # tokio::runtime::Builder::new_current_thread()
+    /// #     .build()
+    /// #     .unwrap()
+    /// #     .block_on(async {
+    /// use laika::{merge::Merge, mpsc};
+    ///
+    /// let mut merge = Merge::new();
+    /// let mut senders = Vec::new();
+    ///
+    /// for _ in 0..3 {
+    ///     let (tx, rx) = mpsc::unbounded();
+    ///     senders.push(tx);
+    ///     merge.push(rx);
+    /// }
+    ///
+    /// for (index, tx) in senders.into_iter().enumerate() {
+    ///     tokio::spawn(async move { tx.send(index).unwrap() });
+    /// }
+    ///
+    /// let mut sum = 0;
+    ///
+    /// while let Some(index) = merge.recv().await {
+    ///     sum += index;
+    /// }
+    ///
+    /// assert_eq!(sum, 3);
+    /// # });
+    /// ```
+    pub async fn recv(&mut self) -> Option<T> {
+        core::future::poll_fn(|cx| self.poll_recv(cx)).await
+    }
+}
+
+impl<T> Default for Merge<T> {
+    fn default() -> Self {
+        Merge::new()
+    }
+}
+
+impl<T> fmt::Debug for Merge<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Merge")
+            .field("sources", &self.sources.len())
+            .field("next", &self.next)
+            .finish()
+    }
+}
+
+/// Implement [`futures_core::Stream`] for [`Merge`], yielding the values of
+/// all inputs until all of them have been closed.
+#[cfg(feature = "futures")]
+impl<T> futures_core::Stream for Merge<T> {
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        self.get_mut().poll_recv(cx)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::vec;
+    use core::task::Waker;
+
+    /// Input yielding its values (`None` meaning a pending poll) and closing
+    /// afterwards
+    struct Scripted(vec::IntoIter<Option<u8>>);
+
+    impl Source<u8> for Scripted {
+        fn poll_next(&mut self, _: &mut Context<'_>) -> Poll<Option<u8>> {
+            match self.0.next() {
+                Some(Some(value)) => Poll::Ready(Some(value)),
+                Some(None) => Poll::Pending,
+                None => Poll::Ready(None),
+            }
+        }
+    }
+
+    fn scripted(values: Vec<Option<u8>>) -> Scripted {
+        Scripted(values.into_iter())
+    }
+
+    #[test]
+    fn test_round_robin() {
+        let mut cx = Context::from_waker(Waker::noop());
+
+        let mut merge = Merge::new()
+            .with(scripted(vec![Some(1), Some(2), Some(3)]))
+            .with(scripted(vec![Some(10), Some(20)]));
+
+        let mut values = Vec::new();
+
+        while let Poll::Ready(Some(value)) = merge.poll_recv(&mut cx) {
+            values.push(value);
+        }
+
+        // Inputs take turns and closed ones are skipped
+        assert_eq!(values, [1, 10, 2, 20, 3]);
+        assert!(merge.is_empty());
+        assert_eq!(merge.poll_recv(&mut cx), Poll::Ready(None));
+    }
+
+    #[test]
+    fn test_pending() {
+        let mut cx = Context::from_waker(Waker::noop());
+
+        let mut merge = Merge::new()
+            .with(scripted(vec![None, Some(1)]))
+            .with(scripted(vec![]))
+            .with(scripted(vec![None, None, Some(2)]));
+
+        // The closed input is removed, the others are polled once
+        assert_eq!(merge.poll_recv(&mut cx), Poll::Pending);
+        assert_eq!(merge.len(), 2);
+
+        assert_eq!(merge.poll_recv(&mut cx), Poll::Ready(Some(1)));
+        assert_eq!(merge.poll_recv(&mut cx), Poll::Pending);
+        assert_eq!(merge.poll_recv(&mut cx), Poll::Ready(Some(2)));
+        assert_eq!(merge.poll_recv(&mut cx), Poll::Ready(None));
+
+        assert_eq!(Merge::<u8>::new().poll_recv(&mut cx), Poll::Ready(None));
+    }
+
+    #[cfg(all(feature = "shotgun", feature = "mpsc", feature = "broadcast"))]
+    #[tokio::test]
+    async fn test_channels() {
+        use crate::{broadcast, mpsc, shotgun};
+
+        let (shotgun_tx, shotgun_rx) = shotgun::channel();
+        let (mpsc_tx, mpsc_rx) = mpsc::bounded(1);
+        let (broadcast_tx, broadcast_rx) = broadcast::channel(1);
+
+        let mut merge = Merge::new()
+            .with(shotgun_rx)
+            .with(mpsc_rx)
+            .with(broadcast_rx);
+
+        let producer = tokio::spawn(async move {
+            shotgun_tx.send(1).unwrap();
+            mpsc_tx.send(2).await.unwrap();
+            mpsc_tx.send(3).await.unwrap();
+
+            // Lags the broadcast receiver behind, skipping 4
+            broadcast_tx.send(4).unwrap();
+            broadcast_tx.send(5).unwrap();
+        });
+
+        let mut values = Vec::new();
+
+        while let Some(value) = merge.recv().await {
+            values.push(value);
+        }
+
+        producer.await.unwrap();
+        values.sort();
+
+        assert_eq!(values, [1, 2, 3, 5]);
+    }
+}
@@ -0,0 +1,1097 @@
+#![forbid(unsafe_code)]
+//! # A multi-producer, multi-consumer (MPMC) work queue
+//!
+//! Both [`Sender`]s and [`Receiver`]s can be cloned. Every value is delivered
+//! to exactly one receiver, whichever asks first, so the channel distributes
+//! work among a pool of consumers. Once all senders have been dropped, the
+//! receivers get the remaining queued values and `None` afterwards.
+//!
+//! - [`bounded`]: The queue holds at most `capacity` values and sending waits
+//!   until there's room. [`bounded_with_overflow`] creates one that sheds load
+//!   instead, see [`Overflow`].
+//! - [`unbounded`]: Sending never waits, the queue grows as needed.
+//!
+//! Besides the `async` functions, [`Sender::poll_send`] and
+//! [`Receiver::poll_recv`] can be polled directly, e.g. from hand written
+//! futures or select loops. They register the waker under the handle, so
+//! only the task that polled a handle last is woken up. Tasks sharing a handle
+//! wait with [`Sender::send`] and [`Receiver::recv`] instead, whose futures
+//! each register a waker of their own.
+//!
+//! The channel is backed by a `std::sync::Mutex`, doesn't contain any unsafe
+//! code and works with any async runtime.
+//!
+//! ## Optional features
+//!
+//! - `futures`: Implements `futures_core::Stream` for [`Receiver`].
+//!
+//! # Examples
+//! ```rust
+//! use std::thread;
+//!
+//! let (tx, rx) = laika::mpmc::bounded(8);
+//!
+//! let workers = (0..4)
+//!     .map(|_| {
+//!         let rx = rx.clone();
+//!
+//!         thread::spawn(move || {
+//!             let mut done = 0;
+//!
+//!             while let Some(job) = rx.recv_blocking() {
+//!                 done += job;
+//!             }
+//!
+//!             done
+//!         })
+//!     })
+//!     .collect::<Vec<_>>();
+//!
+//! for job in 1..=100 {
+//!     tx.send_blocking(job).unwrap();
+//! }
+//!
+//! drop(tx);
+//!
+//! let total: u32 = workers.into_iter().map(|w| w.join().unwrap()).sum();
+//! assert_eq!(total, 5050);
+//! ```
+
+mod error;
+
+pub use crate::Overflow;
+pub use error::{SendError, TryRecvError, TrySendError};
+
+use alloc::{collections::VecDeque, sync::Arc};
+use core::{
+    future::Future,
+    mem,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+use std::{
+    collections::BTreeMap,
+    sync::{Condvar, Mutex, MutexGuard, PoisonError},
+};
+
+/// Queue and bookkeeping of a channel, guarded by [`Chan::state`]
+#[derive(Debug)]
+struct State<T> {
+    /// Queued values, oldest first
+    queue: VecDeque<T>,
+    /// Number of existing [`Sender`]s
+    senders: usize,
+    /// Number of existing [`Receiver`]s
+    receivers: usize,
+    /// Wakers of receivers waiting for a value, keyed by receiver or future
+    /// id
+    recv_wakers: BTreeMap<u64, Waker>,
+    /// Wakers of senders waiting for room, keyed by sender or future id
+    send_wakers: BTreeMap<u64, Waker>,
+    /// Id of the next sender, receiver or waiting future
+    next_id: u64,
+}
+
+impl<T> State<T> {
+    /// Returns a new sender, receiver or future id.
+    fn id(&mut self) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    /// Takes the oldest queued value.
+    fn pop(&mut self) -> Result<T, TryRecvError> {
+        match self.queue.pop_front() {
+            Some(value) => Ok(value),
+            None if self.senders == 0 => Err(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+}
+
+/// Registers the waker of `cx` under `id`, reusing `previous` (the waker
+/// registered under `id` before) if it wakes the same task.
+fn register(wakers: &mut BTreeMap<u64, Waker>, id: u64, previous: Option<Waker>, cx: &Context<'_>) {
+    let waker = match previous {
+        Some(waker) if waker.will_wake(cx.waker()) => waker,
+        _ => cx.waker().clone(),
+    };
+
+    wakers.insert(id, waker);
+}
+
+/// State shared by all senders and receivers of a channel
+#[derive(Debug)]
+struct Chan<T> {
+    /// Queue and bookkeeping
+    state: Mutex<State<T>>,
+    /// Maximum number of queued values, [`usize::MAX`] if unbounded
+    capacity: usize,
+    /// What sending on a full queue does
+    overflow: Overflow,
+    /// Notified when a value is sent or the channel is closed, so that
+    /// threads blocking in [`Receiver::recv_blocking`] wake up.
+    values: Condvar,
+    /// Notified when a value is received or all receivers are dropped, so that
+    /// threads blocking in [`Sender::send_blocking`] wake up.
+    space: Condvar,
+}
+
+impl<T> Chan<T> {
+    /// Locks the state.
+    fn lock(&self) -> MutexGuard<'_, State<T>> {
+        crate::lock(&self.state)
+    }
+
+    /// Wakes up one waiting receiver after unlocking `state`, as a single
+    /// value can only be received by one of them.
+    ///
+    /// A woken up receiver that's dropped before receiving passes the wake-up
+    /// on to the next one, see [`Chan::withdraw_receiver`].
+    fn wake_receiver(&self, mut state: MutexGuard<'_, State<T>>) {
+        let waker = state.recv_wakers.pop_first();
+        drop(state);
+
+        if let Some((_, waker)) = waker {
+            waker.wake();
+        }
+
+        self.values.notify_one();
+    }
+
+    /// Wakes up one waiting sender after unlocking `state`, see
+    /// [`Chan::wake_receiver`].
+    fn wake_sender(&self, mut state: MutexGuard<'_, State<T>>) {
+        let waker = state.send_wakers.pop_first();
+        drop(state);
+
+        if let Some((_, waker)) = waker {
+            waker.wake();
+        }
+
+        self.space.notify_one();
+    }
+
+    /// Wakes up all waiting senders and receivers after unlocking `state`, as
+    /// the channel has been closed.
+    fn wake_all(&self, mut state: MutexGuard<'_, State<T>>) {
+        let recv_wakers = mem::take(&mut state.recv_wakers);
+        let send_wakers = mem::take(&mut state.send_wakers);
+        drop(state);
+
+        for waker in recv_wakers.into_values().chain(send_wakers.into_values()) {
+            waker.wake();
+        }
+
+        self.values.notify_all();
+        self.space.notify_all();
+    }
+
+    /// Removes the waker of a receiver that stops waiting under `id`. If it
+    /// has been taken already, the receiver has been woken up for a value it
+    /// won't receive, so the next waiting receiver is woken up instead.
+    fn withdraw_receiver(&self, id: u64) {
+        let mut state = self.lock();
+
+        if state.recv_wakers.remove(&id).is_none() && !state.queue.is_empty() {
+            self.wake_receiver(state);
+        }
+    }
+
+    /// Removes the waker of a sender that stops waiting under `id`, passing
+    /// the wake-up on like [`Chan::withdraw_receiver`].
+    fn withdraw_sender(&self, id: u64) {
+        let mut state = self.lock();
+
+        if state.send_wakers.remove(&id).is_none()
+            && state.receivers > 0
+            && state.queue.len() < self.capacity
+        {
+            self.wake_sender(state);
+        }
+    }
+
+    /// Queues `value` if there's room for it (or the overflow policy makes
+    /// room), returning the locked state otherwise.
+    fn push<'a>(
+        &'a self,
+        mut state: MutexGuard<'a, State<T>>,
+        value: T,
+    ) -> Result<(), (MutexGuard<'a, State<T>>, TrySendError<T>)> {
+        if state.receivers == 0 {
+            return Err((state, TrySendError::Disconnected(value)));
+        }
+
+        let mut oldest = None;
+
+        if state.queue.len() >= self.capacity {
+            match self.overflow {
+                Overflow::Block | Overflow::Error => {
+                    return Err((state, TrySendError::Full(value)));
+                }
+                Overflow::DropNewest => {
+                    // The value is dropped after unlocking, in case its `Drop`
+                    // uses the channel.
+                    drop(state);
+                    drop(value);
+
+                    return Ok(());
+                }
+                Overflow::DropOldest => oldest = state.queue.pop_front(),
+            }
+        }
+
+        state.queue.push_back(value);
+        self.wake_receiver(state);
+        drop(oldest);
+
+        Ok(())
+    }
+
+    /// Takes the oldest queued value, making room for waiting senders.
+    fn pop<'a>(
+        &'a self,
+        mut state: MutexGuard<'a, State<T>>,
+    ) -> Result<T, (MutexGuard<'a, State<T>>, TryRecvError)> {
+        match state.pop() {
+            Ok(value) => {
+                if self.capacity != usize::MAX {
+                    self.wake_sender(state);
+                }
+
+                Ok(value)
+            }
+            Err(error) => Err((state, error)),
+        }
+    }
+
+    /// Queues the value in `value` once there's room for it, registering the
+    /// waker of `cx` under `id` while the queue is full. A new id is assigned
+    /// if `id` is [`None`].
+    fn poll_push(
+        &self,
+        id: &mut Option<u64>,
+        cx: &Context<'_>,
+        value: &mut Option<T>,
+    ) -> Poll<Result<(), SendError<T>>> {
+        let Some(sent) = value.take() else {
+            panic!("`poll_send` called without a value");
+        };
+
+        let mut state = self.lock();
+        let previous = id.and_then(|id| state.send_wakers.remove(&id));
+
+        match self.push(state, sent) {
+            Ok(()) => Poll::Ready(Ok(())),
+            Err((_, TrySendError::Disconnected(sent))) => {
+                Poll::Ready(Err(SendError::Disconnected(sent)))
+            }
+            Err((_, TrySendError::Full(sent))) if self.overflow == Overflow::Error => {
+                Poll::Ready(Err(SendError::Full(sent)))
+            }
+            Err((mut state, TrySendError::Full(sent))) => {
+                let id = *id.get_or_insert_with(|| state.id());
+                register(&mut state.send_wakers, id, previous, cx);
+                *value = Some(sent);
+
+                Poll::Pending
+            }
+        }
+    }
+
+    /// Takes the oldest queued value, registering the waker of `cx` under
+    /// `id` if there is none yet. A new id is assigned if `id` is [`None`].
+    fn poll_pop(&self, id: &mut Option<u64>, cx: &Context<'_>) -> Poll<Option<T>> {
+        let mut state = self.lock();
+        let previous = id.and_then(|id| state.recv_wakers.remove(&id));
+
+        match self.pop(state) {
+            Ok(value) => Poll::Ready(Some(value)),
+            Err((_, TryRecvError::Disconnected)) => Poll::Ready(None),
+            Err((mut state, TryRecvError::Empty)) => {
+                let id = *id.get_or_insert_with(|| state.id());
+                register(&mut state.recv_wakers, id, previous, cx);
+
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// Sending side of an mpmc channel
+///
+/// Can be cloned to send from multiple producers. The channel is closed once
+/// all senders have been dropped.
+#[derive(Debug)]
+pub struct Sender<T> {
+    /// State shared with all other senders and receivers
+    chan: Arc<Chan<T>>,
+    /// Id of this sender, under which [`Sender::poll_send`] registers its
+    /// waker
+    id: u64,
+}
+
+/// Receiving side of an mpmc channel
+///
+/// Can be cloned to receive from multiple consumers. Every value is received
+/// by exactly one of them.
+#[derive(Debug)]
+pub struct Receiver<T> {
+    /// State shared with all senders and other receivers
+    chan: Arc<Chan<T>>,
+    /// Id of this receiver, under which [`Receiver::poll_recv`] registers its
+    /// waker
+    id: u64,
+}
+
+/// Future returned by [`Sender::send`]
+///
+/// Resolves once the value is queued. Dropping it before withdraws the value,
+/// so it's never sent.
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Enqueue<'a, T> {
+    /// Sender the value is sent with
+    sender: &'a Sender<T>,
+    /// Value until it has been queued
+    value: Option<T>,
+    /// Id of the waker once the future waits for room
+    id: Option<u64>,
+}
+
+/// Future returned by [`Receiver::recv`]
+///
+/// Resolves to the oldest queued value, or to [`None`] once the queue is empty
+/// and all senders have been dropped.
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Recv<'a, T> {
+    /// Receiver the value is received with
+    receiver: &'a Receiver<T>,
+    /// Id of the waker once the future waits for a value
+    id: Option<u64>,
+}
+
+impl<T> Sender<T> {
+    /// Queues a value if there's room for it. This function is
+    /// **non-blocking**.
+    ///
+    /// Returns the value in [`TrySendError::Full`] if the queue is full (and
+    /// the [`Overflow`] policy is [`Block`](Overflow::Block) or
+    /// [`Error`](Overflow::Error)) or [`TrySendError::Disconnected`] if all
+    /// [`Receiver`]s have been dropped.
+    pub fn try_send(&self, value: T) -> Result<(), TrySendError<T>> {
+        self.chan
+            .push(self.chan.lock(), value)
+            .map_err(|(_, error)| error)
+    }
+
+    /// Queues the value in `value` once there's room for it, registering the
+    /// waker of `cx` while the queue is full. Only the waker of the last call
+    /// is kept, so tasks sharing a sender use [`Sender::send`] instead.
+    ///
+    /// `value` keeps the value while this returns [`Poll::Pending`], so the
+    /// same slot can be polled again, e.g. in a select loop. It's emptied once
+    /// this returns [`Poll::Ready`], with the value handed back in the
+    /// [`SendError`] if all [`Receiver`]s have been dropped (or the queue is
+    /// full and the [`Overflow`] policy is [`Error`](Overflow::Error)).
+    ///
+    /// # Panics
+    /// Panics if `value` is [`None`].
+    ///
+    /// # Examples
+    /// ```rust
+    /// use std::task::{Context, Poll, Waker};
+    ///
+    /// let (tx, rx) = laika::mpmc::bounded(1);
+    /// let mut cx = Context::from_waker(Waker::noop());
+    ///
+    /// tx.try_send(1).unwrap();
+    ///
+    /// let mut slot = Some(2);
+    /// assert!(tx.poll_send(&mut cx, &mut slot).is_pending());
+    /// assert_eq!(slot, Some(2));
+    ///
+    /// assert_eq!(rx.try_recv(), Ok(1));
+    /// assert_eq!(tx.poll_send(&mut cx, &mut slot), Poll::Ready(Ok(())));
+    /// assert_eq!(slot, None);
+    /// ```
+    pub fn poll_send(
+        &self,
+        cx: &mut Context<'_>,
+        value: &mut Option<T>,
+    ) -> Poll<Result<(), SendError<T>>> {
+        self.chan.poll_push(&mut Some(self.id), cx, value)
+    }
+
+    /// Queues a value, waiting asynchronously until there's room for it. If the
+    /// queue is full, channels with an [`Overflow`] policy other than
+    /// [`Block`](Overflow::Block) don't wait but handle the value by their
+    /// policy.
+    ///
+    /// Resolves to [`SendError::Disconnected`] containing the value if all
+    /// [`Receiver`]s have been dropped, or to [`SendError::Full`] if the queue
+    /// is full and the policy is [`Error`](Overflow::Error). If the future is
+    /// dropped before it resolves, the value isn't sent.
+    pub fn send(&self, value: T) -> Enqueue<'_, T> {
+        Enqueue {
+            sender: self,
+            value: Some(value),
+            id: None,
+        }
+    }
+
+    /// Queues a value, blocking the current thread until there's room for it.
+    /// See [`Sender::send`].
+    pub fn send_blocking(&self, value: T) -> Result<(), SendError<T>> {
+        let mut state = self.chan.lock();
+        let mut value = value;
+
+        loop {
+            match self.chan.push(state, value) {
+                Ok(()) => return Ok(()),
+                Err((_, TrySendError::Disconnected(sent))) => {
+                    return Err(SendError::Disconnected(sent));
+                }
+                Err((_, TrySendError::Full(sent))) if self.chan.overflow == Overflow::Error => {
+                    return Err(SendError::Full(sent));
+                }
+                Err((locked, TrySendError::Full(sent))) => {
+                    value = sent;
+                    state = self
+                        .chan
+                        .space
+                        .wait(locked)
+                        .unwrap_or_else(PoisonError::into_inner);
+                }
+            }
+        }
+    }
+
+    /// Returns whether all [`Receiver`]s have been dropped, so sending would
+    /// fail.
+    pub fn is_closed(&self) -> bool {
+        self.chan.lock().receivers == 0
+    }
+
+    /// Returns the maximum number of queued values, [`usize::MAX`] if the
+    /// channel is unbounded.
+    pub fn capacity(&self) -> usize {
+        self.chan.capacity
+    }
+
+    /// Returns what sending on a full queue does.
+    pub fn overflow(&self) -> Overflow {
+        self.chan.overflow
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        let mut state = self.chan.lock();
+        state.senders += 1;
+
+        Sender {
+            chan: Arc::clone(&self.chan),
+            id: state.id(),
+        }
+    }
+}
+
+/// Closes the channel when the last [`Sender`] is dropped, waking up all
+/// waiting receivers. Otherwise, a wake-up this sender got is passed on.
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut state = self.chan.lock();
+        state.senders -= 1;
+
+        if state.senders == 0 {
+            self.chan.wake_all(state);
+        } else {
+            drop(state);
+            self.chan.withdraw_sender(self.id);
+        }
+    }
+}
+
+impl<T> Receiver<T> {
+    /// Receives the oldest queued value if there is one. This function is
+    /// **non-blocking**.
+    ///
+    /// Returns [`TryRecvError::Empty`] if the queue is empty or
+    /// [`TryRecvError::Disconnected`] if it's empty and all senders have been
+    /// dropped.
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        self.chan.pop(self.chan.lock()).map_err(|(_, error)| error)
+    }
+
+    /// Polls for the oldest queued value, registering the waker of `cx` if
+    /// there is none yet. Resolves to [`None`] once the queue is empty and all
+    /// senders have been dropped.
+    ///
+    /// Only the waker of the last call is kept, so tasks sharing a receiver
+    /// use [`Receiver::recv`] instead.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use std::task::{Context, Poll, Waker};
+    ///
+    /// let (tx, rx) = laika::mpmc::unbounded();
+    /// let mut cx = Context::from_waker(Waker::noop());
+    ///
+    /// assert_eq!(rx.poll_recv(&mut cx), Poll::Pending);
+    ///
+    /// tx.try_send(1).unwrap();
+    /// drop(tx);
+    ///
+    /// assert_eq!(rx.poll_recv(&mut cx), Poll::Ready(Some(1)));
+    /// assert_eq!(rx.poll_recv(&mut cx), Poll::Ready(None));
+    /// ```
+    pub fn poll_recv(&self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        self.chan.poll_pop(&mut Some(self.id), cx)
+    }
+
+    /// Receives the oldest queued value, waiting asynchronously until there is
+    /// one. Resolves to [`None`] once the queue is empty and all senders have
+    /// been dropped.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # tokio::runtime::Builder::new_current_thread()
+    /// #     .build()
+    /// #     .unwrap()
+    /// #     .block_on(async {
+    /// let (tx, rx) = laika::mpmc::unbounded();
+    ///
+    /// let workers = (0..2)
+    ///     .map(|_| {
+    ///         let rx = rx.clone();
+    ///         tokio::spawn(async move {
+    ///             let mut jobs = 0;
+    ///
+    ///             while rx.recv().await.is_some() {
+    ///                 jobs += 1;
+    ///             }
+    ///
+    ///             jobs
+    ///         })
+    ///     })
+    ///     .collect::<Vec<_>>();
+    ///
+    /// for job in 0..10 {
+    ///     tx.send(job).await.unwrap();
+    /// }
+    ///
+    /// drop(tx);
+    ///
+    /// let mut jobs = 0;
+    ///
+    /// for worker in workers {
+    ///     jobs += worker.await.unwrap();
+    /// }
+    ///
+    /// assert_eq!(jobs, 10);
+    /// # });
+    /// ```
+    pub fn recv(&self) -> Recv<'_, T> {
+        Recv {
+            receiver: self,
+            id: None,
+        }
+    }
+
+    /// Receives the oldest queued value, blocking the current thread until
+    /// there is one. See [`Receiver::recv`].
+    pub fn recv_blocking(&self) -> Option<T> {
+        let mut state = self.chan.lock();
+
+        loop {
+            match self.chan.pop(state) {
+                Ok(value) => return Some(value),
+                Err((_, TryRecvError::Disconnected)) => return None,
+                Err((locked, TryRecvError::Empty)) => {
+                    state = self
+                        .chan
+                        .values
+                        .wait(locked)
+                        .unwrap_or_else(PoisonError::into_inner);
+                }
+            }
+        }
+    }
+
+    /// Returns the number of queued values.
+    pub fn len(&self) -> usize {
+        self.chan.lock().queue.len()
+    }
+
+    /// Returns whether the queue is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the maximum number of queued values, [`usize::MAX`] if the
+    /// channel is unbounded.
+    pub fn capacity(&self) -> usize {
+        self.chan.capacity
+    }
+}
+
+impl<T> Clone for Receiver<T> {
+    fn clone(&self) -> Self {
+        let mut state = self.chan.lock();
+        state.receivers += 1;
+
+        Receiver {
+            chan: Arc::clone(&self.chan),
+            id: state.id(),
+        }
+    }
+}
+
+/// Closes the channel for senders when the last [`Receiver`] is dropped,
+/// waking up waiting ones and dropping the queued values. Otherwise, a wake-up
+/// this receiver got is passed on.
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        let mut state = self.chan.lock();
+        state.receivers -= 1;
+
+        if state.receivers == 0 {
+            // Values are dropped after unlocking, in case their `Drop` uses
+            // the channel.
+            let queue = mem::take(&mut state.queue);
+            self.chan.wake_all(state);
+            drop(queue);
+        } else {
+            drop(state);
+            self.chan.withdraw_receiver(self.id);
+        }
+    }
+}
+
+impl<T> Unpin for Enqueue<'_, T> {}
+
+impl<T> Future for Enqueue<'_, T> {
+    type Output = Result<(), SendError<T>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let poll = this
+            .sender
+            .chan
+            .poll_push(&mut this.id, cx, &mut this.value);
+
+        if poll.is_ready() {
+            this.id = None;
+        }
+
+        poll
+    }
+}
+
+/// Withdraws the waker of a pending future, so the channel doesn't keep it.
+/// If the future has been woken up already, the next waiting sender is woken
+/// up instead.
+impl<T> Drop for Enqueue<'_, T> {
+    fn drop(&mut self) {
+        if let Some(id) = self.id {
+            self.sender.chan.withdraw_sender(id);
+        }
+    }
+}
+
+impl<T> Future for Recv<'_, T> {
+    type Output = Option<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let poll = this.receiver.chan.poll_pop(&mut this.id, cx);
+
+        if poll.is_ready() {
+            this.id = None;
+        }
+
+        poll
+    }
+}
+
+/// Withdraws the waker of a pending future, so the channel doesn't keep it.
+/// If the future has been woken up already, the value it was woken up for is
+/// passed on to the next waiting receiver.
+impl<T> Drop for Recv<'_, T> {
+    fn drop(&mut self) {
+        if let Some(id) = self.id {
+            self.receiver.chan.withdraw_receiver(id);
+        }
+    }
+}
+
+/// Implement [`futures_core::Stream`] for [`Receiver`], yielding values until
+/// the queue is empty and all senders have been dropped.
+#[cfg(feature = "futures")]
+impl<T> futures_core::Stream for Receiver<T> {
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        self.poll_recv(cx)
+    }
+}
+
+/// Creates a channel with the given capacity and overflow policy.
+fn with_capacity<T>(capacity: usize, overflow: Overflow) -> (Sender<T>, Receiver<T>) {
+    let chan = Arc::new(Chan {
+        state: Mutex::new(State {
+            queue: VecDeque::new(),
+            senders: 1,
+            receivers: 1,
+            recv_wakers: BTreeMap::new(),
+            send_wakers: BTreeMap::new(),
+            next_id: 2,
+        }),
+        capacity,
+        overflow,
+        values: Condvar::new(),
+        space: Condvar::new(),
+    });
+
+    let sender = Sender {
+        chan: Arc::clone(&chan),
+        id: 0,
+    };
+
+    (sender, Receiver { chan, id: 1 })
+}
+
+/// Creates a multi-producer, multi-consumer channel that queues at most
+/// `capacity` values.
+///
+/// # Panics
+/// Panics if `capacity` is zero.
+pub fn bounded<T>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    bounded_with_overflow(capacity, Overflow::Block)
+}
+
+/// Creates a multi-producer, multi-consumer channel that queues at most
+/// `capacity` values and handles sending on a full queue by `overflow`.
+///
+/// # Panics
+/// Panics if `capacity` is zero.
+///
+/// # Examples
+/// ```rust
+/// use laika::mpmc::{self, Overflow, SendError};
+///
+/// let (tx, rx) = mpmc::bounded_with_overflow(1, Overflow::Error);
+///
+/// tx.send_blocking(1).unwrap();
+/// assert_eq!(tx.send_blocking(2), Err(SendError::Full(2)));
+/// assert_eq!(rx.try_recv(), Ok(1));
+/// ```
+pub fn bounded_with_overflow<T>(capacity: usize, overflow: Overflow) -> (Sender<T>, Receiver<T>) {
+    assert!(capacity > 0, "a bounded channel needs a capacity");
+
+    with_capacity(capacity, overflow)
+}
+
+/// Creates a multi-producer, multi-consumer channel without a capacity limit.
+pub fn unbounded<T>() -> (Sender<T>, Receiver<T>) {
+    with_capacity(usize::MAX, Overflow::Block)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::{future::Future, pin::pin};
+    use std::{thread, vec::Vec};
+
+    #[test]
+    fn test_exactly_once() {
+        let (tx, rx) = unbounded();
+        let rx1 = rx.clone();
+
+        for value in 0..4 {
+            tx.try_send(value).unwrap();
+        }
+
+        assert_eq!(rx.try_recv(), Ok(0));
+        assert_eq!(rx1.try_recv(), Ok(1));
+        assert_eq!(rx1.len(), 2);
+        assert_eq!(tx.capacity(), usize::MAX);
+
+        drop(tx);
+
+        assert_eq!(rx.recv_blocking(), Some(2));
+        assert_eq!(rx1.recv_blocking(), Some(3));
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Disconnected));
+        assert_eq!(rx1.recv_blocking(), None);
+    }
+
+    #[test]
+    fn test_disconnected() {
+        let (tx, rx) = bounded(1);
+        let rx1 = rx.clone();
+        let mut cx = Context::from_waker(Waker::noop());
+
+        tx.try_send(1).unwrap();
+        assert_eq!(tx.try_send(2), Err(TrySendError::Full(2)));
+
+        let mut slot = Some(2);
+        assert!(tx.poll_send(&mut cx, &mut slot).is_pending());
+        assert_eq!(tx.chan.lock().send_wakers.len(), 1);
+
+        drop(rx);
+        assert!(!tx.is_closed());
+        drop(rx1);
+        assert!(tx.is_closed());
+
+        assert!(tx.chan.lock().send_wakers.is_empty());
+        assert_eq!(
+            tx.poll_send(&mut cx, &mut slot),
+            Poll::Ready(Err(SendError::Disconnected(2)))
+        );
+        assert_eq!(tx.send_blocking(3), Err(SendError::Disconnected(3)));
+    }
+
+    #[test]
+    fn test_overflow() {
+        let mut cx = Context::from_waker(Waker::noop());
+
+        let (tx, rx) = bounded_with_overflow(2, Overflow::DropOldest);
+        assert_eq!(tx.overflow(), Overflow::DropOldest);
+
+        for value in 0..4 {
+            assert_eq!(pin!(tx.send(value)).poll(&mut cx), Poll::Ready(Ok(())));
+        }
+
+        assert_eq!(rx.try_recv(), Ok(2));
+        assert_eq!(rx.try_recv(), Ok(3));
+
+        let (tx, rx) = bounded_with_overflow(2, Overflow::DropNewest);
+
+        for value in 0..4 {
+            assert_eq!(tx.try_send(value), Ok(()));
+        }
+
+        assert_eq!(rx.try_recv(), Ok(0));
+        assert_eq!(rx.try_recv(), Ok(1));
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+
+        let (tx, rx) = bounded_with_overflow(1, Overflow::Error);
+
+        tx.try_send(0).unwrap();
+        assert_eq!(
+            pin!(tx.send(1)).poll(&mut cx),
+            Poll::Ready(Err(SendError::Full(1)))
+        );
+
+        let mut slot = Some(2);
+        assert_eq!(
+            tx.poll_send(&mut cx, &mut slot),
+            Poll::Ready(Err(SendError::Full(2)))
+        );
+        assert!(tx.chan.lock().send_wakers.is_empty());
+
+        // A closed channel fails regardless of the policy
+        drop(rx);
+        assert_eq!(tx.send_blocking(3), Err(SendError::Disconnected(3)));
+    }
+
+    #[test]
+    fn test_poll_recv() {
+        let (tx, rx) = bounded(2);
+        let rx1 = rx.clone();
+        let mut cx = Context::from_waker(Waker::noop());
+
+        {
+            let mut recv = pin!(rx.recv());
+            let mut recv1 = pin!(rx1.recv());
+
+            assert_eq!(recv.as_mut().poll(&mut cx), Poll::Pending);
+            assert_eq!(recv.as_mut().poll(&mut cx), Poll::Pending);
+            assert_eq!(recv1.as_mut().poll(&mut cx), Poll::Pending);
+            assert_eq!(tx.chan.lock().recv_wakers.len(), 2);
+
+            // A single value wakes up a single receiver
+            tx.try_send(1).unwrap();
+            assert_eq!(tx.chan.lock().recv_wakers.len(), 1);
+
+            assert_eq!(recv1.as_mut().poll(&mut cx), Poll::Ready(Some(1)));
+            assert_eq!(recv.as_mut().poll(&mut cx), Poll::Pending);
+        }
+
+        drop(rx);
+        assert!(tx.chan.lock().recv_wakers.is_empty());
+    }
+
+    #[test]
+    fn test_shared_handle() {
+        let (tx, rx) = bounded(1);
+        let mut cx = Context::from_waker(Waker::noop());
+
+        {
+            let mut recv = pin!(rx.recv());
+            let mut recv1 = pin!(rx.recv());
+
+            assert_eq!(recv.as_mut().poll(&mut cx), Poll::Pending);
+            assert_eq!(recv1.as_mut().poll(&mut cx), Poll::Pending);
+
+            // Each future waits with a waker of its own
+            assert_eq!(tx.chan.lock().recv_wakers.len(), 2);
+
+            tx.try_send(1).unwrap();
+            assert_eq!(recv1.as_mut().poll(&mut cx), Poll::Ready(Some(1)));
+            assert_eq!(recv.as_mut().poll(&mut cx), Poll::Pending);
+        }
+
+        assert!(tx.chan.lock().recv_wakers.is_empty());
+
+        {
+            tx.try_send(2).unwrap();
+
+            let mut send = pin!(tx.send(3));
+            let mut send1 = pin!(tx.send(4));
+
+            assert!(send.as_mut().poll(&mut cx).is_pending());
+            assert!(send1.as_mut().poll(&mut cx).is_pending());
+            assert_eq!(tx.chan.lock().send_wakers.len(), 2);
+        }
+
+        // Dropped futures withdraw their wakers and values
+        assert!(tx.chan.lock().send_wakers.is_empty());
+        assert_eq!(rx.len(), 1);
+    }
+
+    #[test]
+    fn test_pass_on() {
+        let (tx, rx) = bounded(1);
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut recv1 = pin!(rx.recv());
+
+        {
+            let mut recv = pin!(rx.recv());
+
+            assert_eq!(recv.as_mut().poll(&mut cx), Poll::Pending);
+            assert_eq!(recv1.as_mut().poll(&mut cx), Poll::Pending);
+
+            tx.try_send(1).unwrap();
+            assert_eq!(tx.chan.lock().recv_wakers.len(), 1);
+        }
+
+        // The woken up future has been dropped, so the next one is woken up
+        assert!(tx.chan.lock().recv_wakers.is_empty());
+        assert_eq!(recv1.as_mut().poll(&mut cx), Poll::Ready(Some(1)));
+
+        tx.try_send(2).unwrap();
+        let mut send1 = pin!(tx.send(4));
+
+        {
+            let mut send = pin!(tx.send(3));
+
+            assert!(send.as_mut().poll(&mut cx).is_pending());
+            assert!(send1.as_mut().poll(&mut cx).is_pending());
+
+            assert_eq!(rx.try_recv(), Ok(2));
+            assert_eq!(tx.chan.lock().send_wakers.len(), 1);
+        }
+
+        assert!(tx.chan.lock().send_wakers.is_empty());
+        assert_eq!(send1.as_mut().poll(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(rx.try_recv(), Ok(4));
+
+        // A resolved future doesn't pass anything on
+        let mut recv = pin!(rx.recv());
+        assert_eq!(recv.as_mut().poll(&mut cx), Poll::Pending);
+        tx.try_send(5).unwrap();
+        assert_eq!(recv.as_mut().poll(&mut cx), Poll::Ready(Some(5)));
+        assert_eq!(recv.id, None);
+    }
+
+    #[test]
+    fn test_blocking() {
+        let (tx, rx) = bounded(2);
+
+        let workers = (0..4)
+            .map(|_| {
+                let rx = rx.clone();
+
+                thread::spawn(move || {
+                    let mut values = Vec::new();
+
+                    while let Some(value) = rx.recv_blocking() {
+                        values.push(value);
+                    }
+
+                    values
+                })
+            })
+            .collect::<Vec<_>>();
+
+        drop(rx);
+
+        let producers = (0..2)
+            .map(|id| {
+                let tx = tx.clone();
+
+                thread::spawn(move || {
+                    for value in 0..50 {
+                        tx.send_blocking(id * 50 + value).unwrap();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        drop(tx);
+
+        for producer in producers {
+            producer.join().unwrap();
+        }
+
+        let mut values = workers
+            .into_iter()
+            .flat_map(|worker| worker.join().unwrap())
+            .collect::<Vec<_>>();
+
+        values.sort();
+        assert_eq!(values, (0..100).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn test_async() {
+        let (tx, rx) = bounded(1);
+
+        let workers = (0..3)
+            .map(|_| {
+                let rx = rx.clone();
+
+                tokio::spawn(async move {
+                    let mut values = Vec::new();
+
+                    while let Some(value) = rx.recv().await {
+                        values.push(value);
+                    }
+
+                    values
+                })
+            })
+            .collect::<Vec<_>>();
+
+        drop(rx);
+
+        for value in 0..30 {
+            tx.send(value).await.unwrap();
+        }
+
+        drop(tx);
+
+        let mut values = Vec::new();
+
+        for worker in workers {
+            values.extend(worker.await.unwrap());
+        }
+
+        values.sort();
+        assert_eq!(values, (0..30).collect::<Vec<_>>());
+    }
+}
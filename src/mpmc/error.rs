@@ -0,0 +1,127 @@
+//! Errors of sending on and receiving from mpmc channels.
+
+#[cfg(doc)]
+use super::{Overflow, Receiver, Sender};
+use core::{error::Error, fmt};
+
+/// Error returned by [`Sender::send`], [`Sender::poll_send`] and
+/// [`Sender::send_blocking`]. Contains the value, so it can be reclaimed.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SendError<T> {
+    /// The queue is full and the [`Overflow::Error`] policy rejects new values
+    /// until a receiver catches up. Never returned by unbounded channels.
+    Full(T),
+    /// All [`Receiver`]s have been dropped.
+    Disconnected(T),
+}
+
+impl<T> SendError<T> {
+    /// Returns the value that couldn't be sent.
+    pub fn into_inner(self) -> T {
+        match self {
+            SendError::Full(value) | SendError::Disconnected(value) => value,
+        }
+    }
+}
+
+debug_without_value!(SendError { Full, Disconnected });
+
+impl<T> fmt::Display for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SendError::Full(_) => write!(f, "sending on a full channel"),
+            SendError::Disconnected(_) => write!(f, "sending on a channel without receivers"),
+        }
+    }
+}
+
+impl<T> Error for SendError<T> {}
+
+/// Error returned by [`Sender::try_send`]. Contains the value, so it can be
+/// reclaimed.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum TrySendError<T> {
+    /// The queue is full, sending would have to wait (or fails by the
+    /// [`Overflow::Error`] policy).
+    Full(T),
+    /// All [`Receiver`]s have been dropped.
+    Disconnected(T),
+}
+
+impl<T> TrySendError<T> {
+    /// Returns the value that couldn't be sent.
+    pub fn into_inner(self) -> T {
+        match self {
+            TrySendError::Full(value) | TrySendError::Disconnected(value) => value,
+        }
+    }
+}
+
+debug_without_value!(TrySendError { Full, Disconnected });
+
+impl<T> fmt::Display for TrySendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TrySendError::Full(_) => write!(f, "sending on a full channel"),
+            TrySendError::Disconnected(_) => write!(f, "sending on a channel without receivers"),
+        }
+    }
+}
+
+impl<T> Error for TrySendError<T> {}
+
+impl<T> From<SendError<T>> for TrySendError<T> {
+    fn from(error: SendError<T>) -> Self {
+        match error {
+            SendError::Full(value) => TrySendError::Full(value),
+            SendError::Disconnected(value) => TrySendError::Disconnected(value),
+        }
+    }
+}
+
+/// Error returned by [`Receiver::try_recv`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TryRecvError {
+    /// The queue is empty, but a sender still exists.
+    Empty,
+    /// The queue is empty and all senders have been dropped, so no value will
+    /// ever be received.
+    Disconnected,
+}
+
+impl fmt::Display for TryRecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TryRecvError::Empty => write!(f, "channel is empty"),
+            TryRecvError::Disconnected => write!(f, "channel is empty and closed"),
+        }
+    }
+}
+
+impl Error for TryRecvError {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::string::ToString;
+
+    #[test]
+    fn test_display() {
+        assert_eq!(
+            SendError::Disconnected(1).to_string(),
+            "sending on a channel without receivers"
+        );
+        assert_eq!(
+            TrySendError::Full(1).to_string(),
+            "sending on a full channel"
+        );
+        assert_eq!(
+            TrySendError::from(SendError::Full(1)).to_string(),
+            SendError::Full(1).to_string()
+        );
+        assert_eq!(
+            TryRecvError::Disconnected.to_string(),
+            "channel is empty and closed"
+        );
+    }
+}
@@ -0,0 +1,402 @@
+#![forbid(unsafe_code)]
+//! # Multi-producer, single-consumer (MPSC) queues
+//!
+//! Values sent by any of the (cloneable) senders are queued and received in
+//! order by the single receiver, which can wait for them asynchronously or by
+//! blocking the current thread. Once all senders have been dropped, the
+//! receiver gets the remaining queued values and `None` afterwards.
+//!
+//! - [`unbounded`]: Sending never waits, the queue grows as needed.
+//! - [`bounded`]: The queue holds at most `capacity` values. Sending waits
+//!   until there's room, so fast producers are slowed down to the pace of the
+//!   receiver (backpressure). [`bounded_with_overflow`] creates one that
+//!   sheds load instead, see [`Overflow`].
+//!
+//! The channels are backed by a `std::sync::Mutex`, don't contain any unsafe
+//! code and work with any async runtime, so no runtime has to be pulled in
+//! just for a queue.
+//!
+//! ## Optional features
+//!
+//! - `futures`: Implements `futures_core::Stream` for the receivers.
+//!
+//! # Examples
+//! ```rust
+//! use std::thread;
+//!
+//! let (tx, mut rx) = laika::mpsc::unbounded();
+//!
+//! for id in 0..4 {
+//!     let tx = tx.clone();
+//!     thread::spawn(move || tx.send(id).unwrap());
+//! }
+//!
+//! drop(tx);
+//!
+//! let mut ids = Vec::new();
+//!
+//! while let Some(id) = rx.recv_blocking() {
+//!     ids.push(id);
+//! }
+//!
+//! ids.sort();
+//! assert_eq!(ids, [0, 1, 2, 3]);
+//! ```
+
+mod bounded;
+mod error;
+mod unbounded;
+
+pub use crate::Overflow;
+pub use bounded::{bounded, bounded_with_overflow, Enqueue, Receiver, Sender};
+pub use error::{SendError, TryRecvError, TrySendError};
+pub use unbounded::{unbounded, UnboundedReceiver, UnboundedSender};
+
+use alloc::collections::VecDeque;
+use core::{
+    mem,
+    task::{Context, Poll, Waker},
+};
+use std::{
+    collections::BTreeMap,
+    sync::{Condvar, Mutex, MutexGuard, PoisonError},
+};
+
+/// Queue and bookkeeping of a channel, guarded by [`Chan::state`]
+#[derive(Debug)]
+struct State<T> {
+    /// Queued values, oldest first
+    queue: VecDeque<T>,
+    /// Number of existing senders
+    senders: usize,
+    /// Whether the receiver still exists
+    receiver: bool,
+    /// Waker of the receiver while it's waiting for a value
+    waker: Option<Waker>,
+    /// Wakers of `send` futures waiting for room in a full queue, keyed by
+    /// future id
+    send_wakers: BTreeMap<u64, Waker>,
+    /// Id of the next `send` future
+    next_id: u64,
+}
+
+impl<T> State<T> {
+    /// Returns a new future id.
+    fn id(&mut self) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+}
+
+/// State shared by all senders and the receiver of a channel
+#[derive(Debug)]
+struct Chan<T> {
+    /// Queue and bookkeeping
+    state: Mutex<State<T>>,
+    /// Maximum number of queued values, [`usize::MAX`] if unbounded
+    capacity: usize,
+    /// What sending on a full queue does
+    overflow: Overflow,
+    /// Notified when a value is sent or the channel is closed, so that a thread
+    /// blocking in `recv_blocking` wakes up.
+    condvar: Condvar,
+    /// Notified when a value is received or the receiver is dropped, so that
+    /// threads blocking in `send_blocking` wake up.
+    space: Condvar,
+}
+
+impl<T> Chan<T> {
+    /// Creates a channel with a single sender.
+    fn new(capacity: usize, overflow: Overflow) -> Self {
+        Chan {
+            state: Mutex::new(State {
+                queue: VecDeque::new(),
+                senders: 1,
+                receiver: true,
+                waker: None,
+                send_wakers: BTreeMap::new(),
+                next_id: 0,
+            }),
+            capacity,
+            overflow,
+            condvar: Condvar::new(),
+            space: Condvar::new(),
+        }
+    }
+
+    /// Locks the state.
+    fn lock(&self) -> MutexGuard<'_, State<T>> {
+        crate::lock(&self.state)
+    }
+
+    /// Wakes up the receiver. Takes its waker while `state` is locked but
+    /// wakes it after unlocking it.
+    fn wake(&self, mut state: MutexGuard<'_, State<T>>) {
+        let waker = state.waker.take();
+        drop(state);
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+
+        self.condvar.notify_one();
+    }
+
+    /// Wakes up one sender waiting for room in the queue after unlocking
+    /// `state`, as a single value has been taken from it.
+    ///
+    /// A woken up `send` future that's dropped before sending passes the
+    /// wake-up on to the next one, see [`Chan::withdraw_sender`].
+    fn wake_sender(&self, mut state: MutexGuard<'_, State<T>>) {
+        let waker = state.send_wakers.pop_first();
+        drop(state);
+
+        if let Some((_, waker)) = waker {
+            waker.wake();
+        }
+
+        self.space.notify_one();
+    }
+
+    /// Removes the waker of a `send` future that stops waiting under `id`. If
+    /// it has been taken already, the future has been woken up for room it
+    /// won't use, so the next waiting sender is woken up instead.
+    fn withdraw_sender(&self, id: u64) {
+        let mut state = self.lock();
+
+        if state.send_wakers.remove(&id).is_none()
+            && state.receiver
+            && state.queue.len() < self.capacity
+        {
+            self.wake_sender(state);
+        }
+    }
+
+    /// Wakes up all senders waiting for room in the queue after unlocking
+    /// `state`, as the receiver has been dropped.
+    fn wake_senders(&self, mut state: MutexGuard<'_, State<T>>) {
+        let wakers = mem::take(&mut state.send_wakers);
+        drop(state);
+
+        for waker in wakers.into_values() {
+            waker.wake();
+        }
+
+        self.space.notify_all();
+    }
+
+    /// Queues `value` unless the receiver has been dropped, ignoring the
+    /// capacity.
+    fn send(&self, value: T) -> Result<(), SendError<T>> {
+        let mut state = self.lock();
+
+        if !state.receiver {
+            return Err(SendError::Disconnected(value));
+        }
+
+        state.queue.push_back(value);
+        self.wake(state);
+
+        Ok(())
+    }
+
+    /// Returns whether sending has to wait for room in the queue.
+    fn must_wait(&self, state: &State<T>) -> bool {
+        state.receiver && state.queue.len() >= self.capacity && self.overflow == Overflow::Block
+    }
+
+    /// Queues `value` in locked `state` if there's room for it, or handles it
+    /// by the overflow policy otherwise.
+    fn push(&self, mut state: MutexGuard<'_, State<T>>, value: T) -> Result<(), SendError<T>> {
+        if !state.receiver {
+            return Err(SendError::Disconnected(value));
+        }
+
+        if state.queue.len() < self.capacity {
+            state.queue.push_back(value);
+            self.wake(state);
+
+            return Ok(());
+        }
+
+        match self.overflow {
+            Overflow::Block | Overflow::Error => Err(SendError::Full(value)),
+            Overflow::DropNewest => {
+                // The value is dropped after unlocking, in case its `Drop`
+                // uses the channel.
+                drop(state);
+                drop(value);
+
+                Ok(())
+            }
+            Overflow::DropOldest => {
+                let oldest = state.queue.pop_front();
+                state.queue.push_back(value);
+                self.wake(state);
+                drop(oldest);
+
+                Ok(())
+            }
+        }
+    }
+
+    /// Queues `value` if there's room for it (or the overflow policy makes
+    /// room).
+    fn try_send(&self, value: T) -> Result<(), TrySendError<T>> {
+        self.push(self.lock(), value).map_err(TrySendError::from)
+    }
+
+    /// Queues the value in `value` once there's room for it, registering the
+    /// waker of `cx` under `id` while the queue is full. A new id is assigned
+    /// if `id` is [`None`].
+    fn poll_send(
+        &self,
+        id: &mut Option<u64>,
+        cx: &mut Context<'_>,
+        value: &mut Option<T>,
+    ) -> Poll<Result<(), SendError<T>>> {
+        let mut state = self.lock();
+
+        let Some(sent) = value.take() else {
+            panic!("`Enqueue` polled after completion");
+        };
+
+        let previous = id.and_then(|id| state.send_wakers.remove(&id));
+
+        if self.must_wait(&state) {
+            *value = Some(sent);
+
+            let id = *id.get_or_insert_with(|| state.id());
+            let waker = match previous {
+                Some(waker) if waker.will_wake(cx.waker()) => waker,
+                _ => cx.waker().clone(),
+            };
+
+            state.send_wakers.insert(id, waker);
+
+            return Poll::Pending;
+        }
+
+        Poll::Ready(self.push(state, sent))
+    }
+
+    /// Queues `value`, blocking until there's room for it.
+    fn send_blocking(&self, value: T) -> Result<(), SendError<T>> {
+        let mut state = self.lock();
+
+        loop {
+            if !self.must_wait(&state) {
+                return self.push(state, value);
+            }
+
+            state = self
+                .space
+                .wait(state)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+    }
+
+    /// Takes the oldest queued value from locked `state`.
+    fn pop(state: &mut State<T>) -> Result<T, TryRecvError> {
+        match state.queue.pop_front() {
+            Some(value) => Ok(value),
+            None if state.senders == 0 => Err(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+
+    /// Makes room for waiting senders after a value has been taken from a
+    /// bounded queue.
+    fn made_room(&self, state: MutexGuard<'_, State<T>>) {
+        if self.capacity != usize::MAX {
+            self.wake_sender(state);
+        }
+    }
+
+    /// Takes the oldest queued value without waiting.
+    fn try_recv(&self) -> Result<T, TryRecvError> {
+        let mut state = self.lock();
+        let value = Self::pop(&mut state)?;
+
+        self.made_room(state);
+
+        Ok(value)
+    }
+
+    /// Takes the oldest queued value, registering the waker of `cx` if there
+    /// is none.
+    fn poll_recv(&self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        let mut state = self.lock();
+
+        match Self::pop(&mut state) {
+            Ok(value) => {
+                self.made_room(state);
+                Poll::Ready(Some(value))
+            }
+            Err(TryRecvError::Disconnected) => Poll::Ready(None),
+            Err(TryRecvError::Empty) => {
+                if !state
+                    .waker
+                    .as_ref()
+                    .is_some_and(|waker| waker.will_wake(cx.waker()))
+                {
+                    state.waker = Some(cx.waker().clone());
+                }
+
+                Poll::Pending
+            }
+        }
+    }
+
+    /// Takes the oldest queued value, blocking until there is one.
+    fn recv_blocking(&self) -> Option<T> {
+        let mut state = self.lock();
+
+        loop {
+            match Self::pop(&mut state) {
+                Ok(value) => {
+                    self.made_room(state);
+                    return Some(value);
+                }
+                Err(TryRecvError::Disconnected) => return None,
+                Err(TryRecvError::Empty) => {
+                    state = self
+                        .condvar
+                        .wait(state)
+                        .unwrap_or_else(PoisonError::into_inner);
+                }
+            }
+        }
+    }
+
+    /// Registers a cloned sender.
+    fn add_sender(&self) {
+        self.lock().senders += 1;
+    }
+
+    /// Unregisters a dropped sender, waking up the receiver if it was the last
+    /// one.
+    fn drop_sender(&self) {
+        let mut state = self.lock();
+        state.senders -= 1;
+
+        if state.senders == 0 {
+            self.wake(state);
+        }
+    }
+
+    /// Marks the receiver as dropped, so that sending fails, and drops all
+    /// queued values.
+    fn drop_receiver(&self) {
+        let mut state = self.lock();
+        state.receiver = false;
+        state.waker = None;
+
+        // Values are dropped after unlocking, in case their `Drop` uses the
+        // channel.
+        let queue = mem::take(&mut state.queue);
+        self.wake_senders(state);
+        drop(queue);
+    }
+}
@@ -0,0 +1,524 @@
+//! Queue with a capacity limit, so sending waits for room (backpressure) or
+//! sheds load by an [`Overflow`] policy.
+
+use super::{Chan, Overflow, SendError, TryRecvError, TrySendError};
+use alloc::sync::Arc;
+use core::{
+    future::{self, Future},
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+/// Sending side of a [`bounded`] channel
+///
+/// Can be cloned to send from multiple producers. The channel is closed once
+/// all senders have been dropped.
+#[derive(Debug)]
+pub struct Sender<T> {
+    /// State shared with the receiver
+    chan: Arc<Chan<T>>,
+}
+
+/// Receiving side of a [`bounded`] channel
+///
+/// Receives the values of all senders in the order they've been sent.
+#[derive(Debug)]
+pub struct Receiver<T> {
+    /// State shared with the senders
+    chan: Arc<Chan<T>>,
+}
+
+/// Future returned by [`Sender::send`]
+///
+/// Resolves once the value is queued (or handled by the [`Overflow`] policy).
+/// Dropping it before withdraws the value, so it's never sent.
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Enqueue<'a, T> {
+    /// Sender the value is sent with
+    sender: &'a Sender<T>,
+    /// Value until it has been queued
+    value: Option<T>,
+    /// Id of the waker once the future waits for room
+    id: Option<u64>,
+}
+
+impl<T> Sender<T> {
+    /// Queues a value for the receiver if there's room for it. This function
+    /// is **non-blocking**.
+    ///
+    /// Returns the value in [`TrySendError::Full`] if the queue is full (and
+    /// the [`Overflow`] policy is [`Block`](Overflow::Block) or
+    /// [`Error`](Overflow::Error)) or [`TrySendError::Disconnected`] if the
+    /// [`Receiver`] has been dropped.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use laika::mpsc::TrySendError;
+    ///
+    /// let (tx, mut rx) = laika::mpsc::bounded(1);
+    ///
+    /// tx.try_send(1).unwrap();
+    /// assert_eq!(tx.try_send(2), Err(TrySendError::Full(2)));
+    ///
+    /// assert_eq!(rx.try_recv(), Ok(1));
+    /// tx.try_send(2).unwrap();
+    /// ```
+    pub fn try_send(&self, value: T) -> Result<(), TrySendError<T>> {
+        self.chan.try_send(value)
+    }
+
+    /// Queues a value for the receiver, waiting asynchronously until there's
+    /// room for it. If the queue is full, channels with an [`Overflow`] policy
+    /// other than [`Block`](Overflow::Block) don't wait but handle the value
+    /// by their policy.
+    ///
+    /// Resolves to [`SendError::Disconnected`] containing the value if the
+    /// [`Receiver`] has been dropped (also while waiting), or to
+    /// [`SendError::Full`] if the queue is full and the policy is
+    /// [`Error`](Overflow::Error). If the future is dropped before it
+    /// resolves, the value isn't sent.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # tokio::runtime::Builder::new_current_thread()
+    /// #     .build()
+    /// #     .unwrap()
+    /// #     .block_on(async {
+    /// let (tx, mut rx) = laika::mpsc::bounded(2);
+    ///
+    /// // The producer can't get more than 2 values ahead of the consumer
+    /// let producer = tokio::spawn(async move {
+    ///     for value in 0..10 {
+    ///         tx.send(value).await.unwrap();
+    ///     }
+    /// });
+    ///
+    /// let mut sum = 0;
+    ///
+    /// while let Some(value) = rx.recv().await {
+    ///     sum += value;
+    /// }
+    ///
+    /// producer.await.unwrap();
+    /// assert_eq!(sum, 45);
+    /// # });
+    /// ```
+    pub fn send(&self, value: T) -> Enqueue<'_, T> {
+        Enqueue {
+            sender: self,
+            value: Some(value),
+            id: None,
+        }
+    }
+
+    /// Queues a value for the receiver, blocking the current thread until
+    /// there's room for it. See [`Sender::send`].
+    pub fn send_blocking(&self, value: T) -> Result<(), SendError<T>> {
+        self.chan.send_blocking(value)
+    }
+
+    /// Returns whether the [`Receiver`] has been dropped, so sending would
+    /// fail.
+    pub fn is_closed(&self) -> bool {
+        !self.chan.lock().receiver
+    }
+
+    /// Returns the maximum number of queued values.
+    pub fn capacity(&self) -> usize {
+        self.chan.capacity
+    }
+
+    /// Returns what sending on a full queue does.
+    pub fn overflow(&self) -> Overflow {
+        self.chan.overflow
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.chan.add_sender();
+
+        Sender {
+            chan: Arc::clone(&self.chan),
+        }
+    }
+}
+
+/// Closes the channel when the last [`Sender`] is dropped, waking up the
+/// receiver.
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        self.chan.drop_sender();
+    }
+}
+
+impl<T> Unpin for Enqueue<'_, T> {}
+
+impl<T> Future for Enqueue<'_, T> {
+    type Output = Result<(), SendError<T>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let poll = this
+            .sender
+            .chan
+            .poll_send(&mut this.id, cx, &mut this.value);
+
+        if poll.is_ready() {
+            this.id = None;
+        }
+
+        poll
+    }
+}
+
+/// Withdraws the waker of a pending future, so the channel doesn't keep it.
+/// If the future has been woken up already, the next waiting sender is woken
+/// up instead.
+impl<T> Drop for Enqueue<'_, T> {
+    fn drop(&mut self) {
+        if let Some(id) = self.id {
+            self.sender.chan.withdraw_sender(id);
+        }
+    }
+}
+
+impl<T> Receiver<T> {
+    /// Receives the oldest queued value if there is one, making room for a
+    /// waiting sender. This function is **non-blocking**.
+    ///
+    /// Returns [`TryRecvError::Empty`] if the queue is empty or
+    /// [`TryRecvError::Disconnected`] if it's empty and all senders have been
+    /// dropped.
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        self.chan.try_recv()
+    }
+
+    /// Polls for the oldest queued value, registering the waker of `cx` if
+    /// there is none yet. Resolves to [`None`] once the queue is empty and all
+    /// senders have been dropped.
+    pub fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        self.chan.poll_recv(cx)
+    }
+
+    /// Receives the oldest queued value, waiting asynchronously until there is
+    /// one. Resolves to [`None`] once the queue is empty and all senders have
+    /// been dropped.
+    pub async fn recv(&mut self) -> Option<T> {
+        future::poll_fn(|cx| self.poll_recv(cx)).await
+    }
+
+    /// Receives the oldest queued value, blocking the current thread until
+    /// there is one. See [`Receiver::recv`].
+    pub fn recv_blocking(&mut self) -> Option<T> {
+        self.chan.recv_blocking()
+    }
+
+    /// Returns the number of queued values.
+    pub fn len(&self) -> usize {
+        self.chan.lock().queue.len()
+    }
+
+    /// Returns whether the queue is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the maximum number of queued values.
+    pub fn capacity(&self) -> usize {
+        self.chan.capacity
+    }
+}
+
+/// Closes the channel for senders, waking up waiting ones, and drops the
+/// queued values.
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.chan.drop_receiver();
+    }
+}
+
+/// Implement [`futures_core::Stream`] for [`Receiver`], yielding the values
+/// until the queue is empty and all senders have been dropped.
+#[cfg(feature = "futures")]
+impl<T> futures_core::Stream for Receiver<T> {
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        self.get_mut().poll_recv(cx)
+    }
+}
+
+/// Creates a multi-producer, single-consumer channel that queues at most
+/// `capacity` values.
+///
+/// # Panics
+/// Panics if `capacity` is zero.
+///
+/// # Examples
+/// ```rust
+/// use std::thread;
+///
+/// let (tx, mut rx) = laika::mpsc::bounded(1);
+///
+/// let producer = thread::spawn(move || {
+///     for value in 0..3 {
+///         tx.send_blocking(value).unwrap();
+///     }
+/// });
+///
+/// assert_eq!(rx.recv_blocking(), Some(0));
+/// assert_eq!(rx.recv_blocking(), Some(1));
+/// assert_eq!(rx.recv_blocking(), Some(2));
+/// assert_eq!(rx.recv_blocking(), None);
+///
+/// producer.join().unwrap();
+/// ```
+pub fn bounded<T>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    bounded_with_overflow(capacity, Overflow::Block)
+}
+
+/// Creates a multi-producer, single-consumer channel that queues at most
+/// `capacity` values and handles sending on a full queue by `overflow`.
+///
+/// # Panics
+/// Panics if `capacity` is zero.
+///
+/// # Examples
+/// ```rust
+/// use laika::mpsc::{self, Overflow, SendError};
+///
+/// let (tx, mut rx) = mpsc::bounded_with_overflow(1, Overflow::Error);
+///
+/// tx.send_blocking(1).unwrap();
+/// assert_eq!(tx.send_blocking(2), Err(SendError::Full(2)));
+/// assert_eq!(rx.try_recv(), Ok(1));
+/// ```
+pub fn bounded_with_overflow<T>(capacity: usize, overflow: Overflow) -> (Sender<T>, Receiver<T>) {
+    assert!(capacity > 0, "a bounded channel needs a capacity");
+
+    let chan = Arc::new(Chan::new(capacity, overflow));
+
+    let sender = Sender {
+        chan: Arc::clone(&chan),
+    };
+
+    (sender, Receiver { chan })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::{future::Future, pin::pin, task::Waker};
+    use std::{
+        sync::{Arc, Barrier},
+        thread,
+        vec::Vec,
+    };
+
+    #[test]
+    fn test_try_send() {
+        let (tx, mut rx) = bounded(2);
+
+        assert_eq!(tx.capacity(), 2);
+        assert_eq!(tx.try_send(1), Ok(()));
+        assert_eq!(tx.try_send(2), Ok(()));
+        assert_eq!(tx.try_send(3), Err(TrySendError::Full(3)));
+        assert_eq!(rx.len(), 2);
+
+        assert_eq!(rx.try_recv(), Ok(1));
+        assert_eq!(tx.try_send(3), Ok(()));
+
+        drop(rx);
+        assert!(tx.is_closed());
+        assert_eq!(tx.try_send(4), Err(TrySendError::Disconnected(4)));
+        assert_eq!(tx.send_blocking(4), Err(SendError::Disconnected(4)));
+    }
+
+    #[test]
+    fn test_poll_send() {
+        let (tx, mut rx) = bounded(1);
+        let tx1 = tx.clone();
+        let mut cx = Context::from_waker(Waker::noop());
+
+        tx.try_send(0).unwrap();
+
+        let mut send = pin!(tx.send(1));
+        let mut send1 = pin!(tx1.send(2));
+
+        assert_eq!(send.as_mut().poll(&mut cx), Poll::Pending);
+        assert_eq!(send.as_mut().poll(&mut cx), Poll::Pending);
+        assert_eq!(send1.as_mut().poll(&mut cx), Poll::Pending);
+        assert_eq!(rx.chan.lock().send_wakers.len(), 2);
+
+        // Receiving a single value wakes up a single sender
+        assert_eq!(rx.try_recv(), Ok(0));
+        assert_eq!(rx.chan.lock().send_wakers.len(), 1);
+
+        assert_eq!(send1.as_mut().poll(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(send.as_mut().poll(&mut cx), Poll::Pending);
+
+        // Dropping the receiver fails waiting senders
+        drop(rx);
+        assert_eq!(
+            send.as_mut().poll(&mut cx),
+            Poll::Ready(Err(SendError::Disconnected(1)))
+        );
+    }
+
+    #[test]
+    fn test_shared_sender() {
+        let (tx, mut rx) = bounded(1);
+        let mut cx = Context::from_waker(Waker::noop());
+
+        tx.try_send(0).unwrap();
+
+        {
+            let mut send = pin!(tx.send(1));
+            let mut send1 = pin!(tx.send(2));
+
+            assert_eq!(send.as_mut().poll(&mut cx), Poll::Pending);
+            assert_eq!(send1.as_mut().poll(&mut cx), Poll::Pending);
+
+            // Each future waits with a waker of its own
+            assert_eq!(rx.chan.lock().send_wakers.len(), 2);
+
+            assert_eq!(rx.try_recv(), Ok(0));
+            assert_eq!(send.as_mut().poll(&mut cx), Poll::Ready(Ok(())));
+            assert_eq!(send1.as_mut().poll(&mut cx), Poll::Pending);
+        }
+
+        // The dropped future withdraws its waker and value
+        assert!(rx.chan.lock().send_wakers.is_empty());
+        assert_eq!(rx.try_recv(), Ok(1));
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+    }
+
+    #[test]
+    fn test_pass_on() {
+        let (tx, mut rx) = bounded(1);
+        let mut cx = Context::from_waker(Waker::noop());
+
+        tx.try_send(0).unwrap();
+        let mut send1 = pin!(tx.send(2));
+
+        {
+            let mut send = pin!(tx.send(1));
+
+            assert_eq!(send.as_mut().poll(&mut cx), Poll::Pending);
+            assert_eq!(send1.as_mut().poll(&mut cx), Poll::Pending);
+
+            assert_eq!(rx.try_recv(), Ok(0));
+            assert_eq!(rx.chan.lock().send_wakers.len(), 1);
+        }
+
+        // The woken up future has been dropped, so the next one is woken up
+        assert!(rx.chan.lock().send_wakers.is_empty());
+        assert_eq!(send1.as_mut().poll(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(rx.try_recv(), Ok(2));
+    }
+
+    #[test]
+    fn test_overflow() {
+        let mut cx = Context::from_waker(Waker::noop());
+
+        let (tx, mut rx) = bounded_with_overflow(2, Overflow::DropOldest);
+        assert_eq!(tx.overflow(), Overflow::DropOldest);
+
+        for value in 0..4 {
+            assert_eq!(pin!(tx.send(value)).poll(&mut cx), Poll::Ready(Ok(())));
+        }
+
+        assert_eq!(rx.try_recv(), Ok(2));
+        assert_eq!(rx.try_recv(), Ok(3));
+
+        let (tx, mut rx) = bounded_with_overflow(2, Overflow::DropNewest);
+
+        for value in 0..4 {
+            assert_eq!(tx.try_send(value), Ok(()));
+        }
+
+        assert_eq!(rx.try_recv(), Ok(0));
+        assert_eq!(rx.try_recv(), Ok(1));
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+
+        let (tx, rx) = bounded_with_overflow(1, Overflow::Error);
+
+        tx.try_send(0).unwrap();
+        assert_eq!(
+            pin!(tx.send(1)).poll(&mut cx),
+            Poll::Ready(Err(SendError::Full(1)))
+        );
+
+        // A closed channel fails regardless of the policy
+        drop(rx);
+        assert_eq!(tx.send_blocking(2), Err(SendError::Disconnected(2)));
+    }
+
+    #[test]
+    fn test_send_blocking() {
+        let (tx, mut rx) = bounded(2);
+        let filled = Arc::new(Barrier::new(2));
+
+        let producer = thread::spawn({
+            let filled = Arc::clone(&filled);
+
+            move || {
+                for value in 0..8 {
+                    tx.send_blocking(value).unwrap();
+
+                    if value == 1 {
+                        filled.wait();
+                    }
+                }
+            }
+        });
+
+        // The producer fills the queue, but can't get past its capacity
+        filled.wait();
+        assert_eq!(rx.len(), 2);
+
+        let mut values = Vec::new();
+
+        while let Some(value) = rx.recv_blocking() {
+            values.push(value);
+        }
+
+        producer.join().unwrap();
+        assert_eq!(values, [0, 1, 2, 3, 4, 5, 6, 7]);
+    }
+
+    #[tokio::test]
+    async fn test_send() {
+        let (tx, mut rx) = bounded(1);
+
+        let producers = (0..4)
+            .map(|id| {
+                let tx = tx.clone();
+
+                tokio::spawn(async move {
+                    for value in 0..4 {
+                        tx.send(id * 4 + value).await.unwrap();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        drop(tx);
+
+        let mut values = Vec::new();
+
+        while let Some(value) = rx.recv().await {
+            assert!(rx.len() <= 1);
+            values.push(value);
+        }
+
+        for producer in producers {
+            producer.await.unwrap();
+        }
+
+        values.sort();
+        assert_eq!(values, (0..16).collect::<Vec<_>>());
+    }
+}
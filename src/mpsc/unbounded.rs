@@ -0,0 +1,324 @@
+//! Queue without a capacity limit, so sending never waits.
+
+use super::{Chan, Overflow, SendError, TryRecvError};
+use alloc::sync::Arc;
+#[cfg(feature = "futures")]
+use core::pin::Pin;
+use core::{
+    future,
+    task::{Context, Poll},
+};
+
+/// Sending side of an [`unbounded`] channel
+///
+/// Can be cloned to send from multiple producers. The channel is closed once
+/// all senders have been dropped.
+#[derive(Debug)]
+pub struct UnboundedSender<T> {
+    /// State shared with the receiver
+    chan: Arc<Chan<T>>,
+}
+
+/// Receiving side of an [`unbounded`] channel
+///
+/// Receives the values of all senders in the order they've been sent.
+#[derive(Debug)]
+pub struct UnboundedReceiver<T> {
+    /// State shared with the senders
+    chan: Arc<Chan<T>>,
+}
+
+impl<T> UnboundedSender<T> {
+    /// Queues a value for the receiver. Never waits.
+    ///
+    /// Returns the value in a [`SendError`] if the [`UnboundedReceiver`] has
+    /// been dropped.
+    ///
+    /// # Examples
+    /// ```rust
+    /// let (tx, rx) = laika::mpsc::unbounded();
+    ///
+    /// tx.send(1).unwrap();
+    ///
+    /// drop(rx);
+    /// assert_eq!(tx.send(2).unwrap_err().into_inner(), 2);
+    /// ```
+    pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+        self.chan.send(value)
+    }
+
+    /// Returns whether the [`UnboundedReceiver`] has been dropped, so sending
+    /// would fail.
+    pub fn is_closed(&self) -> bool {
+        !self.chan.lock().receiver
+    }
+}
+
+impl<T> Clone for UnboundedSender<T> {
+    fn clone(&self) -> Self {
+        self.chan.add_sender();
+
+        UnboundedSender {
+            chan: Arc::clone(&self.chan),
+        }
+    }
+}
+
+/// Closes the channel when the last [`UnboundedSender`] is dropped, waking up
+/// the receiver.
+impl<T> Drop for UnboundedSender<T> {
+    fn drop(&mut self) {
+        self.chan.drop_sender();
+    }
+}
+
+impl<T> UnboundedReceiver<T> {
+    /// Receives the oldest queued value if there is one. This function is
+    /// **non-blocking**.
+    ///
+    /// Returns [`TryRecvError::Empty`] if the queue is empty or
+    /// [`TryRecvError::Disconnected`] if it's empty and all senders have been
+    /// dropped.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use laika::mpsc::TryRecvError;
+    ///
+    /// let (tx, mut rx) = laika::mpsc::unbounded();
+    /// assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+    ///
+    /// tx.send(1).unwrap();
+    /// drop(tx);
+    ///
+    /// assert_eq!(rx.try_recv(), Ok(1));
+    /// assert_eq!(rx.try_recv(), Err(TryRecvError::Disconnected));
+    /// ```
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        self.chan.try_recv()
+    }
+
+    /// Polls for the oldest queued value, registering the waker of `cx` if
+    /// there is none yet. Resolves to [`None`] once the queue is empty and all
+    /// senders have been dropped.
+    pub fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        self.chan.poll_recv(cx)
+    }
+
+    /// Receives the oldest queued value, waiting asynchronously until there is
+    /// one. Resolves to [`None`] once the queue is empty and all senders have
+    /// been dropped.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # tokio::runtime::Builder::new_current_thread()
+    /// #     .build()
+    /// #     .unwrap()
+    /// #     .block_on(async {
+    /// let (tx, mut rx) = laika::mpsc::unbounded();
+    ///
+    /// let task = tokio::spawn(async move {
+    ///     let mut sum = 0;
+    ///
+    ///     while let Some(value) = rx.recv().await {
+    ///         sum += value;
+    ///     }
+    ///
+    ///     sum
+    /// });
+    ///
+    /// for value in 1..=3 {
+    ///     tx.send(value).unwrap();
+    /// }
+    ///
+    /// drop(tx);
+    /// assert_eq!(task.await.unwrap(), 6);
+    /// # });
+    /// ```
+    pub async fn recv(&mut self) -> Option<T> {
+        future::poll_fn(|cx| self.poll_recv(cx)).await
+    }
+
+    /// Receives the oldest queued value, blocking the current thread until
+    /// there is one. See [`UnboundedReceiver::recv`].
+    pub fn recv_blocking(&mut self) -> Option<T> {
+        self.chan.recv_blocking()
+    }
+
+    /// Returns the number of queued values.
+    pub fn len(&self) -> usize {
+        self.chan.lock().queue.len()
+    }
+
+    /// Returns whether the queue is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Closes the channel for senders and drops the queued values.
+impl<T> Drop for UnboundedReceiver<T> {
+    fn drop(&mut self) {
+        self.chan.drop_receiver();
+    }
+}
+
+/// Implement [`futures_core::Stream`] for [`UnboundedReceiver`], yielding the
+/// values until the queue is empty and all senders have been dropped.
+#[cfg(feature = "futures")]
+impl<T> futures_core::Stream for UnboundedReceiver<T> {
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        self.get_mut().poll_recv(cx)
+    }
+}
+
+/// Creates an unbounded multi-producer, single-consumer channel.
+///
+/// # Examples
+/// ```rust
+/// let (tx, mut rx) = laika::mpsc::unbounded();
+///
+/// tx.send("hello").unwrap();
+/// tx.clone().send("world").unwrap();
+///
+/// assert_eq!(rx.recv_blocking(), Some("hello"));
+/// assert_eq!(rx.recv_blocking(), Some("world"));
+/// ```
+pub fn unbounded<T>() -> (UnboundedSender<T>, UnboundedReceiver<T>) {
+    let chan = Arc::new(Chan::new(usize::MAX, Overflow::Block));
+
+    let sender = UnboundedSender {
+        chan: Arc::clone(&chan),
+    };
+
+    (sender, UnboundedReceiver { chan })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::{
+        future::Future,
+        pin::pin,
+        sync::atomic::{AtomicUsize, Ordering},
+        task::Waker,
+    };
+    use std::{thread, time::Duration, vec::Vec};
+
+    #[test]
+    fn test_unbounded() {
+        let (tx, mut rx) = unbounded();
+        let tx1 = tx.clone();
+
+        tx.send(1).unwrap();
+        tx1.send(2).unwrap();
+        tx.send(3).unwrap();
+
+        assert_eq!(rx.len(), 3);
+        assert_eq!(rx.try_recv(), Ok(1));
+        assert_eq!(rx.try_recv(), Ok(2));
+
+        drop((tx, tx1));
+
+        assert_eq!(rx.recv_blocking(), Some(3));
+        assert!(rx.is_empty());
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Disconnected));
+        assert_eq!(rx.recv_blocking(), None);
+    }
+
+    #[test]
+    fn test_receiver_dropped() {
+        /// Value that counts how often it has been dropped
+        struct Counted<'a>(&'a AtomicUsize);
+
+        impl Drop for Counted<'_> {
+            fn drop(&mut self) {
+                self.0.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let drops = AtomicUsize::new(0);
+        let (tx, rx) = unbounded();
+
+        assert!(tx.send(Counted(&drops)).is_ok());
+        assert!(!tx.is_closed());
+
+        drop(rx);
+
+        assert!(tx.is_closed());
+        assert_eq!(drops.load(Ordering::Relaxed), 1);
+        assert!(tx.send(Counted(&drops)).is_err());
+    }
+
+    #[test]
+    fn test_poll_recv() {
+        let (tx, mut rx) = unbounded();
+        let mut cx = Context::from_waker(Waker::noop());
+
+        {
+            let mut recv = pin!(rx.recv());
+            assert_eq!(recv.as_mut().poll(&mut cx), Poll::Pending);
+            assert!(tx.chan.lock().waker.is_some());
+
+            tx.send(1).unwrap();
+            assert!(tx.chan.lock().waker.is_none());
+            assert_eq!(recv.as_mut().poll(&mut cx), Poll::Ready(Some(1)));
+        }
+
+        assert_eq!(rx.poll_recv(&mut cx), Poll::Pending);
+        drop(tx);
+        assert_eq!(rx.poll_recv(&mut cx), Poll::Ready(None));
+    }
+
+    #[test]
+    fn test_recv_blocking() {
+        let (tx, mut rx) = unbounded();
+
+        let thread = thread::spawn(move || {
+            let mut values = Vec::new();
+
+            while let Some(value) = rx.recv_blocking() {
+                values.push(value);
+            }
+
+            values
+        });
+
+        thread::sleep(Duration::from_millis(10));
+
+        for value in 0..4 {
+            tx.send(value).unwrap();
+        }
+
+        drop(tx);
+        assert_eq!(thread.join().unwrap(), [0, 1, 2, 3]);
+    }
+
+    #[tokio::test]
+    async fn test_recv() {
+        let (tx, mut rx) = unbounded();
+
+        let tasks = (0..4)
+            .map(|id| {
+                let tx = tx.clone();
+                tokio::spawn(async move { tx.send(id).unwrap() })
+            })
+            .collect::<Vec<_>>();
+
+        drop(tx);
+
+        for task in tasks {
+            task.await.unwrap();
+        }
+
+        let mut values = Vec::new();
+
+        while let Some(value) = rx.recv().await {
+            values.push(value);
+        }
+
+        values.sort();
+        assert_eq!(values, [0, 1, 2, 3]);
+    }
+}
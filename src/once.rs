@@ -0,0 +1,646 @@
+#![forbid(unsafe_code)]
+//! # Async one-time initialization
+//!
+//! A [`OnceCell`] holds a value that is initialized at most once, by an async
+//! function. If multiple tasks call [`OnceCell::get_or_init`] at the same time,
+//! only one of them runs its initializer while the others wait for its result
+//! instead of initializing the value again, e.g. for connecting to a database
+//! or fetching a configuration on first use.
+//!
+//! [`OnceCell::get_or_try_init`] handles fallible initializers: if one fails,
+//! the error is returned to its caller and the next waiting task runs its own
+//! initializer. The same happens if the initializing task is cancelled (its
+//! future is dropped) or panics, so the cell never gets stuck.
+//!
+//! A [`Lazy`] bundles a cell with its initializer, so it can be placed in a
+//! `static` and initializes itself on the first [`Lazy::get`], making async
+//! global singletons painless.
+//!
+//! The value is stored in a [`std::sync::OnceLock`], so getting it once it's
+//! initialized doesn't take a lock. The cell doesn't contain any unsafe code
+//! and works with any async runtime.
+//!
+//! # Examples
+//! ```rust
+//! # tokio::runtime::Builder::new_current_thread()
+//! #     .build()
+//! #     .unwrap()
+//! #     .block_on(async {
+//! use laika::once::OnceCell;
+//! use std::sync::atomic::{AtomicUsize, Ordering};
+//!
+//! static CONFIG: OnceCell<String> = OnceCell::new();
+//! static LOADED: AtomicUsize = AtomicUsize::new(0);
+//!
+//! async fn config() -> &'static str {
+//!     CONFIG
+//!         .get_or_init(|| async {
+//!             LOADED.fetch_add(1, Ordering::Relaxed);
+//!             String::from("verbose = true")
+//!         })
+//!         .await
+//! }
+//!
+//! let tasks: Vec<_> = (0..4).map(|_| tokio::spawn(config())).collect();
+//!
+//! for task in tasks {
+//!     assert_eq!(task.await.unwrap(), "verbose = true");
+//! }
+//!
+//! // Loaded only once, even though 4 tasks asked at the same time
+//! assert_eq!(LOADED.load(Ordering::Relaxed), 1);
+//! # });
+//! ```
+
+use alloc::collections::BTreeMap;
+use core::{
+    convert::Infallible,
+    fmt,
+    future::Future,
+    mem,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+use std::{
+    boxed::Box,
+    sync::{Mutex, MutexGuard, OnceLock},
+};
+
+/// State of the initialization of a [`OnceCell`], guarded by
+/// [`OnceCell::state`]
+struct State {
+    /// Whether a task is running its initializer
+    initializing: bool,
+    /// Wakers of the tasks waiting for the initializer, keyed by id
+    wakers: BTreeMap<u64, Waker>,
+    /// Id of the next waker
+    next_id: u64,
+}
+
+/// Cell that is initialized at most once by an async function
+///
+/// See the [module documentation](self) for details.
+pub struct OnceCell<T> {
+    /// Value, once initialized
+    value: OnceLock<T>,
+    /// Whether the value is being initialized and the waiting tasks
+    state: Mutex<State>,
+}
+
+/// Future waiting for the initializer of a [`OnceCell`]
+struct Waiting<'a, T> {
+    /// Cell to wait for
+    cell: &'a OnceCell<T>,
+    /// Whether to resolve once nobody is initializing, so the waiting task
+    /// may initialize the value itself
+    for_turn: bool,
+    /// Id of the registered waker
+    id: Option<u64>,
+}
+
+/// Clears [`State::initializing`] once the initializer finished, failed, has
+/// been cancelled or panicked, waking up the waiting tasks.
+struct Initializing<'a, T> {
+    /// Cell that is being initialized
+    cell: &'a OnceCell<T>,
+}
+
+impl<T> OnceCell<T> {
+    /// Creates an empty cell. As it's a const function, the cell can be
+    /// placed in a `static`.
+    pub const fn new() -> Self {
+        OnceCell {
+            value: OnceLock::new(),
+            state: Mutex::new(State {
+                initializing: false,
+                wakers: BTreeMap::new(),
+                next_id: 0,
+            }),
+        }
+    }
+
+    /// Locks the state.
+    fn lock(&self) -> MutexGuard<'_, State> {
+        crate::lock(&self.state)
+    }
+
+    /// Returns the value if the cell has been initialized. This function is
+    /// **non-blocking**.
+    pub fn get(&self) -> Option<&T> {
+        self.value.get()
+    }
+
+    /// Returns whether the cell has been initialized.
+    pub fn is_initialized(&self) -> bool {
+        self.value.get().is_some()
+    }
+
+    /// Initializes the cell with `value` if it hasn't been initialized yet.
+    ///
+    /// Returns the value as error if the cell has been initialized already.
+    /// If a task is running its initializer at the same time, its result is
+    /// discarded in favor of `value`.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use laika::once::OnceCell;
+    ///
+    /// let cell = OnceCell::new();
+    ///
+    /// assert_eq!(cell.set(1), Ok(()));
+    /// assert_eq!(cell.set(2), Err(2));
+    /// assert_eq!(cell.get(), Some(&1));
+    /// ```
+    pub fn set(&self, value: T) -> Result<(), T> {
+        self.value.set(value)?;
+        self.wake_all(self.lock());
+
+        Ok(())
+    }
+
+    /// Returns the value, initializing the cell with `init` first if it hasn't
+    /// been initialized yet.
+    ///
+    /// If another task is initializing the cell at the same time, waits for
+    /// its result instead of running `init`. If that task is cancelled or
+    /// panics, `init` runs after all.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # tokio::runtime::Builder::new_current_thread()
+    /// #     .build()
+    /// #     .unwrap()
+    /// #     .block_on(async {
+    /// use laika::once::OnceCell;
+    ///
+    /// let cell = OnceCell::new();
+    ///
+    /// assert_eq!(*cell.get_or_init(|| async { 1 }).await, 1);
+    ///
+    /// // Initialized already, so this initializer doesn't run
+    /// assert_eq!(*cell.get_or_init(|| async { 2 }).await, 1);
+    /// # });
+    /// ```
+    pub async fn get_or_init<F, Fut>(&self, init: F) -> &T
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = T>,
+    {
+        let result = self
+            .get_or_try_init(|| async { Ok::<_, Infallible>(init().await) })
+            .await;
+
+        match result {
+            Ok(value) => value,
+            Err(never) => match never {},
+        }
+    }
+
+    /// Returns the value, initializing the cell with the fallible `init`
+    /// first if it hasn't been initialized yet.
+    ///
+    /// Like [`OnceCell::get_or_init`], but if `init` fails, its error is
+    /// returned, leaving the cell uninitialized. The next waiting task (if
+    /// any) runs its own initializer then.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # tokio::runtime::Builder::new_current_thread()
+    /// #     .build()
+    /// #     .unwrap()
+    /// #     .block_on(async {
+    /// use laika::once::OnceCell;
+    ///
+    /// let cell = OnceCell::new();
+    ///
+    /// let failed = cell
+    ///     .get_or_try_init(|| async { "port".parse::<u16>() })
+    ///     .await;
+    ///
+    /// assert!(failed.is_err());
+    /// assert!(!cell.is_initialized());
+    ///
+    /// let port = cell.get_or_try_init(|| async { "8080".parse() }).await;
+    ///
+    /// assert_eq!(port, Ok(&8080));
+    /// # });
+    /// ```
+    pub async fn get_or_try_init<F, Fut, E>(&self, init: F) -> Result<&T, E>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        loop {
+            if let Some(value) = self.value.get() {
+                return Ok(value);
+            }
+
+            {
+                let mut state = self.lock();
+
+                if !state.initializing {
+                    state.initializing = true;
+                    break;
+                }
+            }
+
+            Waiting {
+                cell: self,
+                for_turn: true,
+                id: None,
+            }
+            .await;
+        }
+
+        let _initializing = Initializing { cell: self };
+
+        // Initialized by `set` in the meantime
+        if let Some(value) = self.value.get() {
+            return Ok(value);
+        }
+
+        let value = init().await?;
+
+        Ok(self.value.get_or_init(|| value))
+    }
+
+    /// Waits until the cell has been initialized by another task and returns
+    /// the value, without initializing it itself.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # tokio::runtime::Builder::new_current_thread()
+    /// #     .build()
+    /// #     .unwrap()
+    /// #     .block_on(async {
+    /// use laika::once::OnceCell;
+    /// use std::sync::Arc;
+    ///
+    /// let cell = Arc::new(OnceCell::new());
+    ///
+    /// let reader = tokio::spawn({
+    ///     let cell = Arc::clone(&cell);
+    ///     async move { *cell.wait().await }
+    /// });
+    ///
+    /// cell.set(5).unwrap();
+    /// assert_eq!(reader.await.unwrap(), 5);
+    /// # });
+    /// ```
+    pub async fn wait(&self) -> &T {
+        loop {
+            if let Some(value) = self.value.get() {
+                return value;
+            }
+
+            Waiting {
+                cell: self,
+                for_turn: false,
+                id: None,
+            }
+            .await;
+        }
+    }
+
+    /// Takes the value out of the cell, leaving it uninitialized.
+    pub fn take(&mut self) -> Option<T> {
+        self.value.take()
+    }
+
+    /// Consumes the cell, returning the value if it has been initialized.
+    pub fn into_inner(self) -> Option<T> {
+        self.value.into_inner()
+    }
+
+    /// Wakes up all waiting tasks after unlocking `state`.
+    fn wake_all(&self, mut state: MutexGuard<'_, State>) {
+        let wakers = mem::take(&mut state.wakers);
+
+        drop(state);
+
+        for waker in wakers.into_values() {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> Default for OnceCell<T> {
+    fn default() -> Self {
+        OnceCell::new()
+    }
+}
+
+impl<T> From<T> for OnceCell<T> {
+    fn from(value: T) -> Self {
+        OnceCell {
+            value: OnceLock::from(value),
+            ..OnceCell::new()
+        }
+    }
+}
+
+impl<T> fmt::Debug for OnceCell<T>
+where
+    T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OnceCell")
+            .field("value", &self.value.get())
+            .field("initializing", &self.lock().initializing)
+            .finish()
+    }
+}
+
+/// Value that is initialized by an async function on first use
+///
+/// Unlike a [`OnceCell`], the initializer is passed on creation, so a lazy
+/// value can be placed in a `static` and initializes itself on the first
+/// [`Lazy::get`]. Tasks getting it at the same time wait for that initializer
+/// instead of running it again.
+///
+/// The initializer is a function returning a future. By default, it's a
+/// function pointer returning a boxed future, so the type of a `static` can be
+/// written down: pass a closure that doesn't capture anything and returns
+/// `Box::pin(async { ... })`. The initializer runs exactly once, unless the
+/// task running it is cancelled or panics, in which case the next task runs
+/// it again.
+///
+/// # Examples
+/// ```rust
+/// # tokio::runtime::Builder::new_current_thread()
+/// #     .build()
+/// #     .unwrap()
+/// #     .block_on(async {
+/// use laika::once::Lazy;
+/// use std::collections::HashMap;
+///
+/// static PRICES: Lazy<HashMap<&str, u32>> = Lazy::new(|| {
+///     Box::pin(async {
+///         // e.g. fetched from a service
+///         HashMap::from([("apple", 30), ("pear", 45)])
+///     })
+/// });
+///
+/// assert!(PRICES.try_get().is_none());
+/// assert_eq!(PRICES.get().await["pear"], 45);
+/// assert!(PRICES.try_get().is_some());
+/// # });
+/// ```
+pub struct Lazy<T, F = fn() -> Pin<Box<dyn Future<Output = T> + Send>>> {
+    /// Cell holding the value
+    cell: OnceCell<T>,
+    /// Function returning the future initializing the value
+    init: F,
+}
+
+impl<T, F> Lazy<T, F> {
+    /// Creates a lazy value that is initialized by the future returned by
+    /// `init` on first use. As it's a const function, the lazy value can be
+    /// placed in a `static`.
+    pub const fn new(init: F) -> Self {
+        Lazy {
+            cell: OnceCell::new(),
+            init,
+        }
+    }
+
+    /// Returns the value if it has been initialized. This function is
+    /// **non-blocking**.
+    pub fn try_get(&self) -> Option<&T> {
+        self.cell.get()
+    }
+
+    /// Returns whether the value has been initialized.
+    pub fn is_initialized(&self) -> bool {
+        self.cell.is_initialized()
+    }
+
+    /// Consumes the lazy value, returning the value if it has been
+    /// initialized.
+    pub fn into_inner(self) -> Option<T> {
+        self.cell.into_inner()
+    }
+}
+
+impl<T, F, Fut> Lazy<T, F>
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = T>,
+{
+    /// Returns the value, running the initializer first if this is the first
+    /// use. If another task is running the initializer at the same time,
+    /// waits for its result instead.
+    pub async fn get(&self) -> &T {
+        self.cell.get_or_init(&self.init).await
+    }
+}
+
+impl<T, F> fmt::Debug for Lazy<T, F>
+where
+    T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Lazy").field("cell", &self.cell).finish()
+    }
+}
+
+impl<T> Future for Waiting<'_, T> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let mut state = this.cell.lock();
+
+        if this.cell.value.get().is_some() || (this.for_turn && !state.initializing) {
+            return Poll::Ready(());
+        }
+
+        match this.id {
+            // Woken up, as the wakers are taken when waking
+            Some(id) if !state.wakers.contains_key(&id) => {
+                this.id = None;
+                return Poll::Ready(());
+            }
+            Some(id) => {
+                if let Some(waker) = state.wakers.get_mut(&id) {
+                    if !waker.will_wake(cx.waker()) {
+                        *waker = cx.waker().clone();
+                    }
+                }
+            }
+            None => {
+                let id = state.next_id;
+                state.next_id += 1;
+                state.wakers.insert(id, cx.waker().clone());
+                this.id = Some(id);
+            }
+        }
+
+        Poll::Pending
+    }
+}
+
+impl<T> Drop for Waiting<'_, T> {
+    fn drop(&mut self) {
+        if let Some(id) = self.id {
+            self.cell.lock().wakers.remove(&id);
+        }
+    }
+}
+
+impl<T> Drop for Initializing<'_, T> {
+    fn drop(&mut self) {
+        let mut state = self.cell.lock();
+        state.initializing = false;
+
+        self.cell.wake_all(state);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::sync::Arc;
+    use core::pin::pin;
+    use std::{
+        sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+        vec::Vec,
+    };
+
+    #[test]
+    fn test_deduplicate() {
+        let cell = OnceCell::new();
+        let mut cx = Context::from_waker(Waker::noop());
+        let ready = AtomicBool::new(false);
+
+        let mut first = pin!(cell.get_or_init(|| {
+            core::future::poll_fn(|_| match ready.load(Ordering::Relaxed) {
+                true => Poll::Ready(3),
+                false => Poll::Pending,
+            })
+        }));
+        let mut second = pin!(cell.get_or_init(|| async { panic!("initialized twice") }));
+
+        assert!(first.as_mut().poll(&mut cx).is_pending());
+        assert!(second.as_mut().poll(&mut cx).is_pending());
+
+        ready.store(true, Ordering::Relaxed);
+        assert!(second.as_mut().poll(&mut cx).is_pending());
+        assert_eq!(first.as_mut().poll(&mut cx), Poll::Ready(&3));
+        assert_eq!(second.as_mut().poll(&mut cx), Poll::Ready(&3));
+    }
+
+    #[test]
+    fn test_cancel() {
+        let cell = OnceCell::new();
+        let mut cx = Context::from_waker(Waker::noop());
+
+        let mut second = pin!(cell.get_or_init(|| async { 2 }));
+
+        {
+            let mut first = pin!(cell.get_or_init(core::future::pending));
+            assert!(first.as_mut().poll(&mut cx).is_pending());
+            assert!(second.as_mut().poll(&mut cx).is_pending());
+        }
+
+        // The cancelled initializer lets the next task initialize
+        assert_eq!(second.as_mut().poll(&mut cx), Poll::Ready(&2));
+        assert_eq!(cell.get(), Some(&2));
+    }
+
+    #[test]
+    fn test_set() {
+        let mut cell = OnceCell::new();
+        let mut cx = Context::from_waker(Waker::noop());
+
+        {
+            let mut waiting = pin!(cell.wait());
+            assert!(waiting.as_mut().poll(&mut cx).is_pending());
+
+            cell.set(String::from("a")).unwrap();
+            assert_eq!(
+                waiting.as_mut().poll(&mut cx),
+                Poll::Ready(&String::from("a"))
+            );
+        }
+
+        assert_eq!(cell.take().as_deref(), Some("a"));
+        assert!(!cell.is_initialized());
+        assert_eq!(OnceCell::from(1).into_inner(), Some(1));
+    }
+
+    #[test]
+    fn test_lazy() {
+        let runs = AtomicUsize::new(0);
+        let lazy = Lazy::new(|| async {
+            runs.fetch_add(1, Ordering::Relaxed);
+            'a'
+        });
+        let mut cx = Context::from_waker(Waker::noop());
+
+        assert_eq!(lazy.try_get(), None);
+        assert_eq!(pin!(lazy.get()).poll(&mut cx), Poll::Ready(&'a'));
+        assert_eq!(pin!(lazy.get()).poll(&mut cx), Poll::Ready(&'a'));
+        assert_eq!(runs.load(Ordering::Relaxed), 1);
+        assert_eq!(lazy.into_inner(), Some('a'));
+    }
+
+    #[tokio::test]
+    async fn test_lazy_static() {
+        static RUNS: AtomicUsize = AtomicUsize::new(0);
+        static VALUE: Lazy<u64> = Lazy::new(|| {
+            Box::pin(async {
+                RUNS.fetch_add(1, Ordering::Relaxed);
+                tokio::task::yield_now().await;
+                17
+            })
+        });
+
+        let tasks: Vec<_> = (0..4)
+            .map(|_| tokio::spawn(async { *VALUE.get().await }))
+            .collect();
+
+        for task in tasks {
+            assert_eq!(task.await.unwrap(), 17);
+        }
+
+        assert_eq!(RUNS.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn test_get_or_try_init() {
+        let cell = Arc::new(OnceCell::new());
+        let attempts = Arc::new(AtomicUsize::new(0));
+
+        let tasks: Vec<_> = (0..4)
+            .map(|_| {
+                let cell = Arc::clone(&cell);
+                let attempts = Arc::clone(&attempts);
+
+                tokio::spawn(async move {
+                    cell.get_or_try_init(|| async {
+                        tokio::task::yield_now().await;
+
+                        // The first attempt fails
+                        match attempts.fetch_add(1, Ordering::Relaxed) {
+                            0 => Err("unavailable"),
+                            attempt => Ok(attempt),
+                        }
+                    })
+                    .await
+                    .copied()
+                })
+            })
+            .collect();
+
+        let mut results = Vec::new();
+
+        for task in tasks {
+            results.push(task.await.unwrap());
+        }
+
+        assert!(results.contains(&Err("unavailable")));
+        assert_eq!(results.iter().filter(|result| **result == Ok(1)).count(), 3);
+        assert_eq!(attempts.load(Ordering::Relaxed), 2);
+    }
+}
@@ -0,0 +1,370 @@
+#![forbid(unsafe_code)]
+//! # A oneshot channel that moves its value to a single receiver
+//!
+//! The [`Sender`] sends exactly one value, which is moved to the single
+//! [`Receiver`]. Unlike [`shotgun`](crate::shotgun) (where every receiver gets
+//! a clone), `T` doesn't have to implement `Clone`, so it can be used for
+//! handing over files, connections or other unique resources, e.g. to reply to
+//! a request.
+//!
+//! The receiver is a future resolving to the value, or waits by blocking the
+//! current thread with [`Receiver::recv_blocking`]. If the sender is dropped
+//! without sending, receiving fails with a [`RecvError`]. If the receiver is
+//! dropped first, sending fails with a [`SendError`] containing the value.
+//!
+//! The channel is backed by a `std::sync::Mutex`, doesn't contain any unsafe
+//! code and works with any async runtime.
+//!
+//! # Examples
+//! ```rust
+//! use std::thread;
+//!
+//! // Doesn't implement `Clone`
+//! struct Token(u8);
+//!
+//! let (tx, rx) = laika::oneshot::channel();
+//!
+//! thread::spawn(move || tx.send(Token(42)).unwrap());
+//!
+//! assert_eq!(rx.recv_blocking().unwrap().0, 42);
+//! ```
+
+mod error;
+
+pub use error::{RecvError, SendError, TryRecvError};
+
+use alloc::sync::Arc;
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+use std::sync::{Condvar, Mutex, MutexGuard, PoisonError};
+
+/// Value and bookkeeping of a channel, guarded by [`Shared::state`]
+#[derive(Debug)]
+struct State<T> {
+    /// Sent value until it's received
+    value: Option<T>,
+    /// Whether the sender still exists (it's dropped by sending)
+    sender: bool,
+    /// Whether the receiver still exists
+    receiver: bool,
+    /// Waker of the receiver while it's waiting for the value
+    waker: Option<Waker>,
+}
+
+/// State shared by the sender and the receiver of a channel
+#[derive(Debug)]
+struct Shared<T> {
+    /// Value and bookkeeping
+    state: Mutex<State<T>>,
+    /// Notified when the sender is dropped (after sending or not), so that a
+    /// thread blocking in [`Receiver::recv_blocking`] wakes up.
+    condvar: Condvar,
+}
+
+impl<T> Shared<T> {
+    /// Locks the state.
+    fn lock(&self) -> MutexGuard<'_, State<T>> {
+        crate::lock(&self.state)
+    }
+}
+
+impl<T> State<T> {
+    /// Takes the value if it has been sent.
+    fn take(&mut self) -> Result<T, TryRecvError> {
+        match self.value.take() {
+            Some(value) => Ok(value),
+            None if !self.sender => Err(TryRecvError::Closed),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+}
+
+/// Sending side of a oneshot channel
+#[derive(Debug)]
+pub struct Sender<T> {
+    /// State shared with the receiver
+    shared: Arc<Shared<T>>,
+}
+
+/// Receiving side of a oneshot channel
+///
+/// Is a future resolving to the sent value, or to a [`RecvError`] if the
+/// [`Sender`] has been dropped without sending one.
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Receiver<T> {
+    /// State shared with the sender
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Sender<T> {
+    /// Sends the value to the receiver, consuming the sender.
+    ///
+    /// Returns a [`SendError`] containing the value if the [`Receiver`] has
+    /// been dropped.
+    ///
+    /// # Examples
+    /// ```rust
+    /// let (tx, rx) = laika::oneshot::channel();
+    /// drop(rx);
+    ///
+    /// assert_eq!(tx.send(1).unwrap_err().into_inner(), 1);
+    /// ```
+    pub fn send(self, value: T) -> Result<(), SendError<T>> {
+        let mut state = self.shared.lock();
+
+        if !state.receiver {
+            return Err(SendError(value));
+        }
+
+        state.value = Some(value);
+
+        // Dropping `self` wakes up the receiver
+        Ok(())
+    }
+
+    /// Returns whether the [`Receiver`] has been dropped, so sending would
+    /// fail.
+    pub fn is_closed(&self) -> bool {
+        !self.shared.lock().receiver
+    }
+}
+
+/// Closes the channel, waking up the receiver. If a value has been sent, the
+/// receiver gets it, otherwise a [`RecvError`].
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.sender = false;
+
+        let waker = state.waker.take();
+        drop(state);
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+
+        self.shared.condvar.notify_one();
+    }
+}
+
+impl<T> Receiver<T> {
+    /// Takes the value if it has been sent. This function is
+    /// **non-blocking**.
+    ///
+    /// Returns [`TryRecvError::Empty`] if no value has been sent yet or
+    /// [`TryRecvError::Closed`] if the [`Sender`] has been dropped without
+    /// sending one (or the value has already been taken).
+    ///
+    /// # Examples
+    /// ```rust
+    /// use laika::oneshot::TryRecvError;
+    ///
+    /// let (tx, mut rx) = laika::oneshot::channel();
+    /// assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+    ///
+    /// tx.send(1).unwrap();
+    /// assert_eq!(rx.try_recv(), Ok(1));
+    /// assert_eq!(rx.try_recv(), Err(TryRecvError::Closed));
+    /// ```
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        self.shared.lock().take()
+    }
+
+    /// Polls for the value, registering the waker of `cx` if it hasn't been
+    /// sent yet.
+    pub fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Result<T, RecvError>> {
+        let mut state = self.shared.lock();
+
+        match state.take() {
+            Ok(value) => Poll::Ready(Ok(value)),
+            Err(TryRecvError::Closed) => Poll::Ready(Err(RecvError)),
+            Err(TryRecvError::Empty) => {
+                if !state
+                    .waker
+                    .as_ref()
+                    .is_some_and(|waker| waker.will_wake(cx.waker()))
+                {
+                    state.waker = Some(cx.waker().clone());
+                }
+
+                Poll::Pending
+            }
+        }
+    }
+
+    /// Receives the value, blocking the current thread until it has been sent.
+    ///
+    /// Returns a [`RecvError`] if the [`Sender`] has been dropped without
+    /// sending a value.
+    pub fn recv_blocking(self) -> Result<T, RecvError> {
+        let mut state = self.shared.lock();
+
+        loop {
+            match state.take() {
+                Ok(value) => return Ok(value),
+                Err(TryRecvError::Closed) => return Err(RecvError),
+                Err(TryRecvError::Empty) => {
+                    state = self
+                        .shared
+                        .condvar
+                        .wait(state)
+                        .unwrap_or_else(PoisonError::into_inner);
+                }
+            }
+        }
+    }
+
+    /// Returns whether the [`Sender`] has been dropped, either after sending
+    /// the value or without sending one.
+    pub fn is_terminated(&self) -> bool {
+        !self.shared.lock().sender
+    }
+}
+
+impl<T> Future for Receiver<T> {
+    type Output = Result<T, RecvError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.get_mut().poll_recv(cx)
+    }
+}
+
+/// Closes the channel for the sender and drops a value that has been sent but
+/// not received.
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.receiver = false;
+        state.waker = None;
+
+        // The value is dropped after unlocking, in case its `Drop` uses the
+        // channel.
+        let value = state.value.take();
+        drop(state);
+        drop(value);
+    }
+}
+
+/// Creates a oneshot channel.
+///
+/// # Examples
+/// ```rust
+/// # tokio::runtime::Builder::new_current_thread()
+/// #     .build()
+/// #     .unwrap()
+/// #     .block_on(async {
+/// let (tx, rx) = laika::oneshot::channel();
+///
+/// tokio::spawn(async move {
+///     tx.send(String::from("done")).unwrap();
+/// });
+///
+/// assert_eq!(rx.await.unwrap(), "done");
+/// # });
+/// ```
+pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            value: None,
+            sender: true,
+            receiver: true,
+            waker: None,
+        }),
+        condvar: Condvar::new(),
+    });
+
+    let receiver = Receiver {
+        shared: Arc::clone(&shared),
+    };
+
+    (Sender { shared }, receiver)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::pin::pin;
+    use std::{sync::atomic::AtomicUsize, sync::atomic::Ordering, thread, time::Duration};
+
+    /// Counts its drops and doesn't implement `Clone`
+    struct Counted<'a>(&'a AtomicUsize);
+
+    impl Drop for Counted<'_> {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn test_poll() {
+        let (tx, rx) = channel();
+        let mut rx = pin!(rx);
+        let mut cx = Context::from_waker(Waker::noop());
+
+        assert_eq!(rx.as_mut().poll(&mut cx), Poll::Pending);
+        assert!(rx.shared.lock().waker.is_some());
+        assert!(!rx.is_terminated());
+
+        tx.send(1).unwrap();
+        assert!(rx.is_terminated());
+        assert_eq!(rx.as_mut().poll(&mut cx), Poll::Ready(Ok(1)));
+        assert_eq!(rx.as_mut().poll(&mut cx), Poll::Ready(Err(RecvError)));
+    }
+
+    #[test]
+    fn test_sender_dropped() {
+        let (tx, mut rx) = channel::<u8>();
+
+        drop(tx);
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Closed));
+        assert_eq!(rx.recv_blocking(), Err(RecvError));
+    }
+
+    #[test]
+    fn test_receiver_dropped() {
+        let drops = AtomicUsize::new(0);
+
+        let (tx, rx) = channel();
+        drop(rx);
+
+        assert!(tx.is_closed());
+        let value = tx.send(Counted(&drops)).unwrap_err().into_inner();
+        assert_eq!(drops.load(Ordering::Relaxed), 0);
+
+        // A sent but not received value is dropped with the receiver
+        let (tx, rx) = channel();
+        tx.send(value).unwrap();
+        drop(rx);
+        assert_eq!(drops.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_recv_blocking() {
+        let (tx, rx) = channel();
+
+        let sender = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(10));
+            tx.send(Box::new(1)).unwrap();
+        });
+
+        assert_eq!(rx.recv_blocking(), Ok(Box::new(1)));
+        sender.join().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_await() {
+        let (tx, rx) = channel();
+
+        tokio::spawn(async move { tx.send(1).unwrap() });
+        assert_eq!(rx.await, Ok(1));
+
+        let (tx, rx) = channel::<u8>();
+
+        tokio::spawn(async move { drop(tx) });
+        assert_eq!(rx.await, Err(RecvError));
+    }
+}
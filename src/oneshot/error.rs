@@ -0,0 +1,84 @@
+//! Errors of sending on and receiving from oneshot channels.
+
+#[cfg(doc)]
+use super::{Receiver, Sender};
+use core::{error::Error, fmt};
+
+/// Error returned by [`Sender::send`] if the [`Receiver`] has been dropped.
+/// Contains the value, so it can be reclaimed.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct SendError<T>(pub T);
+
+impl<T> SendError<T> {
+    /// Returns the value that couldn't be sent.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+debug_without_value!(SendError);
+
+impl<T> fmt::Display for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "sending on a channel without a receiver")
+    }
+}
+
+impl<T> Error for SendError<T> {}
+
+/// Error returned when awaiting the [`Receiver`] (or by
+/// [`Receiver::recv_blocking`]) if the [`Sender`] has been dropped without
+/// sending a value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RecvError;
+
+impl fmt::Display for RecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "channel closed without a value being sent")
+    }
+}
+
+impl Error for RecvError {}
+
+/// Error returned by [`Receiver::try_recv`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TryRecvError {
+    /// No value has been sent yet, but the [`Sender`] still exists.
+    Empty,
+    /// The [`Sender`] has been dropped without sending a value or the value
+    /// has already been received.
+    Closed,
+}
+
+impl fmt::Display for TryRecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TryRecvError::Empty => write!(f, "no value has been sent yet"),
+            TryRecvError::Closed => write!(f, "channel closed without a value being sent"),
+        }
+    }
+}
+
+impl Error for TryRecvError {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::string::ToString;
+
+    #[test]
+    fn test_display() {
+        assert_eq!(
+            SendError(1).to_string(),
+            "sending on a channel without a receiver"
+        );
+        assert_eq!(
+            RecvError.to_string(),
+            "channel closed without a value being sent"
+        );
+        assert_eq!(
+            TryRecvError::Empty.to_string(),
+            "no value has been sent yet"
+        );
+    }
+}
@@ -0,0 +1,46 @@
+//! Policy for sending on a full bounded channel, shared by the channels of
+//! the crate.
+
+/// What sending on a full bounded channel does
+///
+/// Selected when creating a channel, e.g. by `bounded_with_overflow` of
+/// [`mpsc`](crate::mpsc) and [`mpmc`](crate::mpmc) or `channel_with_overflow`
+/// of [`spsc`](crate::spsc) and [`broadcast`](crate::broadcast), so that load
+/// shedding doesn't have to be implemented by every producer. As all of them
+/// take this type, a policy can be configured once and passed to channels of
+/// different kinds. Broadcast channels never wait, so they don't support
+/// [`Overflow::Block`].
+///
+/// # Examples
+#[cfg_attr(all(feature = "mpsc", feature = "spsc"), doc = "```rust")]
+#[cfg_attr(not(all(feature = "mpsc", feature = "spsc")), doc = "```ignore")]
+/// use laika::{mpsc, spsc, Overflow};
+///
+/// let policy = Overflow::DropOldest;
+/// let (tx, mut rx) = mpsc::bounded_with_overflow(2, policy);
+/// let (mut producer, mut consumer) = spsc::channel_with_overflow(2, policy);
+///
+/// for value in 0..4 {
+///     tx.send_blocking(value).unwrap();
+///     producer.push_blocking(value).unwrap();
+/// }
+///
+/// // Only the latest values are kept
+/// assert_eq!((rx.try_recv(), consumer.try_pop()), (Ok(2), Ok(2)));
+/// assert_eq!((rx.try_recv(), consumer.try_pop()), (Ok(3), Ok(3)));
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Overflow {
+    /// Waits until there's room for the value (backpressure). Only
+    /// non-blocking sends (like `try_send`) fail with a `Full` error.
+    #[default]
+    Block,
+    /// Drops the oldest queued value to make room for the new one, so the
+    /// receivers always get the latest values. Sending never waits.
+    DropOldest,
+    /// Drops the new value, keeping the queued ones. Sending never waits and
+    /// succeeds anyway.
+    DropNewest,
+    /// Fails with a `Full` error, returning the value. Sending never waits.
+    Error,
+}
@@ -0,0 +1,34 @@
+#![forbid(unsafe_code)]
+//! Blocking the current thread until a poll function is ready, for the
+//! `*_blocking` variants of async functions.
+
+use alloc::{sync::Arc, task::Wake};
+use core::task::{Context, Poll, Waker};
+use std::thread::{self, Thread};
+
+/// Waker unparking the thread that created it
+struct Unpark(Thread);
+
+impl Wake for Unpark {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// Polls `poll` until it's ready, parking the current thread in between.
+pub(crate) fn block_on<R>(mut poll: impl FnMut(&mut Context<'_>) -> Poll<R>) -> R {
+    let waker = Waker::from(Arc::new(Unpark(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+
+    loop {
+        if let Poll::Ready(output) = poll(&mut cx) {
+            return output;
+        }
+
+        thread::park();
+    }
+}
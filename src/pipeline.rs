@@ -0,0 +1,349 @@
+#![forbid(unsafe_code)]
+//! # Pipelines of stages connected by bounded channels
+//!
+//! A [`Pipeline`] is built from stages, each a closure (or async function)
+//! turning a value into the input of the next stage. [`Pipeline::build`]
+//! connects the stages with bounded [`mpsc`](crate::mpsc) channels, spawns a
+//! worker thread per stage and returns the handles of the pipeline: the
+//! sender of the first stage, the receiver of the last one and a
+//! [`Shutdown`].
+//!
+//! Values keep their order through the pipeline. As the channels are bounded,
+//! a slow stage suspends the stages (and producers) in front of it instead of
+//! buffering indefinitely.
+//!
+//! Shutting down drains the pipeline in order: once all input senders have
+//! been dropped, the first stage processes the values that are still queued
+//! and stops, which closes the input of the second stage, and so on.
+//! [`Shutdown::join`] waits until all stages stopped. If the output receiver
+//! is dropped, stages stop as soon as they can't forward a value anymore.
+//!
+//! The pipeline doesn't contain any unsafe code. Async stages are driven by
+//! their worker thread, so they work with any runtime-agnostic future.
+//!
+//! # Examples
+//! ```rust
+//! use laika::pipeline::Pipeline;
+//!
+//! let (input, mut output, shutdown) = Pipeline::new(16)
+//!     .stage(|line: &str| line.len())
+//!     .stage(|len| len * 2)
+//!     .build();
+//!
+//! for line in ["a", "bb", "ccc"] {
+//!     input.send_blocking(line).unwrap();
+//! }
+//!
+//! // Drains the remaining values, then stops all stages
+//! drop(input);
+//!
+//! assert_eq!(output.recv_blocking(), Some(2));
+//! assert_eq!(output.recv_blocking(), Some(4));
+//! assert_eq!(output.recv_blocking(), Some(6));
+//! assert_eq!(output.recv_blocking(), None);
+//! assert_eq!(shutdown.join(), Ok(()));
+//! ```
+
+mod error;
+
+pub use error::StageError;
+
+use crate::{
+    mpsc::{self, Receiver, Sender},
+    park,
+};
+use alloc::{boxed::Box, format, vec::Vec};
+use core::{fmt, future::Future, pin::pin};
+use std::thread::{self, JoinHandle};
+
+/// Spawns the worker of a stage, given the index of the stage
+type Launcher = Box<dyn FnOnce(usize) -> JoinHandle<()> + Send>;
+
+/// Drives `future` to completion on the current thread.
+fn block_on<F>(future: F) -> F::Output
+where
+    F: Future,
+{
+    let mut future = pin!(future);
+
+    park::block_on(|cx| future.as_mut().poll(cx))
+}
+
+/// Spawns a stage thread, forwarding the values of `input` processed by
+/// `process` to `output` until either of them has been closed.
+fn spawn<T, U, F>(
+    index: usize,
+    mut input: Receiver<T>,
+    output: Sender<U>,
+    mut process: F,
+) -> JoinHandle<()>
+where
+    T: Send + 'static,
+    U: Send + 'static,
+    F: FnMut(T) -> U + Send + 'static,
+{
+    thread::Builder::new()
+        .name(format!("laika-pipeline-{index}"))
+        .spawn(move || {
+            while let Some(value) = input.recv_blocking() {
+                if output.send_blocking(process(value)).is_err() {
+                    break;
+                }
+            }
+        })
+        .expect("failed to spawn a pipeline stage")
+}
+
+/// Builder of a pipeline taking `I` and yielding `O`
+///
+/// See the [module documentation](self) for details.
+pub struct Pipeline<I, O> {
+    /// Sender of the first stage
+    input: Sender<I>,
+    /// Receiver of the last stage
+    output: Receiver<O>,
+    /// Capacity of the channels between the stages
+    capacity: usize,
+    /// Spawns the workers of the stages, in order
+    launchers: Vec<Launcher>,
+}
+
+impl<T> Pipeline<T, T>
+where
+    T: Send + 'static,
+{
+    /// Creates a pipeline without stages, connecting stages with channels of
+    /// `capacity`. Without any stages, the values sent are received as they
+    /// are.
+    ///
+    /// # Panics
+    /// Panics if `capacity` is zero.
+    pub fn new(capacity: usize) -> Self {
+        let (input, output) = mpsc::bounded(capacity);
+
+        Pipeline {
+            input,
+            output,
+            capacity,
+            launchers: Vec::new(),
+        }
+    }
+}
+
+impl<I, O> Pipeline<I, O>
+where
+    O: Send + 'static,
+{
+    /// Adds a stage processing every value with `process` on its own thread.
+    pub fn stage<U, F>(self, process: F) -> Pipeline<I, U>
+    where
+        U: Send + 'static,
+        F: FnMut(O) -> U + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::bounded(self.capacity);
+        let input = self.output;
+
+        let mut launchers = self.launchers;
+        launchers.push(Box::new(move |index| spawn(index, input, sender, process)));
+
+        Pipeline {
+            input: self.input,
+            output: receiver,
+            capacity: self.capacity,
+            launchers,
+        }
+    }
+
+    /// Adds a stage processing every value with the async function `process`,
+    /// awaiting one value at a time on its own thread.
+    ///
+    /// The futures are polled by the thread of the stage, so they must not
+    /// rely on the context of a specific runtime (like timers or sockets of
+    /// tokio). Use [`Pipeline::stage`] with the runtime's `block_on` for those.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use laika::pipeline::Pipeline;
+    ///
+    /// let (input, mut output, shutdown) = Pipeline::new(4)
+    ///     .stage_async(|name: &str| async move { format!("hello, {name}") })
+    ///     .build();
+    ///
+    /// input.send_blocking("laika").unwrap();
+    /// drop(input);
+    ///
+    /// assert_eq!(output.recv_blocking().as_deref(), Some("hello, laika"));
+    /// assert_eq!(output.recv_blocking(), None);
+    /// assert_eq!(shutdown.join(), Ok(()));
+    /// ```
+    pub fn stage_async<U, F, Fut>(self, mut process: F) -> Pipeline<I, U>
+    where
+        U: Send + 'static,
+        F: FnMut(O) -> Fut + Send + 'static,
+        Fut: Future<Output = U>,
+    {
+        self.stage(move |value| block_on(process(value)))
+    }
+
+    /// Spawns the workers of all stages and returns the sender of the first
+    /// stage, the receiver of the last one and the [`Shutdown`] of the
+    /// pipeline.
+    ///
+    /// # Panics
+    /// Panics if the operating system fails to create a thread, like
+    /// [`std::thread::spawn`].
+    pub fn build(self) -> (Sender<I>, Receiver<O>, Shutdown) {
+        let stages = self
+            .launchers
+            .into_iter()
+            .enumerate()
+            .map(|(index, launch)| launch(index))
+            .collect();
+
+        (self.input, self.output, Shutdown { stages })
+    }
+
+    /// Returns the number of stages.
+    pub fn len(&self) -> usize {
+        self.launchers.len()
+    }
+
+    /// Returns whether the pipeline doesn't have any stages.
+    pub fn is_empty(&self) -> bool {
+        self.launchers.is_empty()
+    }
+}
+
+impl<I, O> fmt::Debug for Pipeline<I, O> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Pipeline")
+            .field("stages", &self.launchers.len())
+            .field("capacity", &self.capacity)
+            .finish()
+    }
+}
+
+/// Handle to wait for the stages of a built [`Pipeline`] to stop
+///
+/// Dropping it detaches the stages, which keep running until the pipeline
+/// has been drained.
+#[derive(Debug)]
+pub struct Shutdown {
+    /// Worker threads of the stages, in order
+    stages: Vec<JoinHandle<()>>,
+}
+
+impl Shutdown {
+    /// Blocks the current thread until all stages stopped, i.e. all input
+    /// senders have been dropped and every value has been forwarded (or the
+    /// output receiver has been dropped).
+    ///
+    /// Keep receiving from the output (or drop it) while waiting, as the last
+    /// stage waits for room in its channel otherwise.
+    ///
+    /// Returns a [`StageError`] with the index of the first stage that
+    /// panicked. Values in flight in the stages after it are dropped.
+    pub fn join(self) -> Result<(), StageError> {
+        let mut result = Ok(());
+
+        for (index, stage) in self.stages.into_iter().enumerate() {
+            if stage.join().is_err() && result.is_ok() {
+                result = Err(StageError(index));
+            }
+        }
+
+        result
+    }
+
+    /// Returns whether all stages stopped.
+    pub fn is_finished(&self) -> bool {
+        self.stages.iter().all(JoinHandle::is_finished)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::{string::String, time::Duration};
+
+    #[test]
+    fn test_order() {
+        let (input, mut output, shutdown) = Pipeline::new(2)
+            .stage(|value: u32| value + 1)
+            .stage(|value| {
+                // A slow stage suspends the ones in front of it
+                thread::sleep(Duration::from_millis(1));
+                value * 10
+            })
+            .stage(|value| value.to_string())
+            .build();
+
+        let producer = thread::spawn(move || {
+            for value in 0..50 {
+                input.send_blocking(value).unwrap();
+            }
+        });
+
+        for value in 0..50 {
+            assert_eq!(output.recv_blocking(), Some(((value + 1) * 10).to_string()));
+        }
+
+        assert_eq!(output.recv_blocking(), None);
+        producer.join().unwrap();
+
+        assert_eq!(shutdown.join(), Ok(()));
+    }
+
+    #[test]
+    fn test_without_stages() {
+        let pipeline = Pipeline::new(1);
+        assert!(pipeline.is_empty());
+
+        let (input, mut output, shutdown) = pipeline.build();
+
+        input.send_blocking('a').unwrap();
+        drop(input);
+
+        assert_eq!(output.recv_blocking(), Some('a'));
+        assert_eq!(output.recv_blocking(), None);
+        assert_eq!(shutdown.join(), Ok(()));
+    }
+
+    #[test]
+    fn test_panic() {
+        let (input, mut output, shutdown) = Pipeline::new(1)
+            .stage(|value: u8| value)
+            .stage(|value| match value {
+                0 => panic!("stage failed"),
+                _ => value,
+            })
+            .stage(|value| value)
+            .build();
+
+        input.send_blocking(1).unwrap();
+        assert_eq!(output.recv_blocking(), Some(1));
+
+        input.send_blocking(0).unwrap();
+
+        // The stages after the panicking one stop, and sending fails once
+        // the first stage stopped too
+        assert_eq!(output.recv_blocking(), None);
+        while input.send_blocking(2).is_ok() {}
+
+        assert_eq!(shutdown.join(), Err(StageError(1)));
+    }
+
+    #[test]
+    fn test_dropped_output() {
+        let (input, output, shutdown) = Pipeline::new(1)
+            .stage(|value: u8| value)
+            .stage_async(|value| async move { String::from(char::from(value)) })
+            .build();
+
+        drop(output);
+
+        // Stages stop once they can't forward a value
+        while input.send_blocking(b'a').is_ok() {}
+        assert_eq!(shutdown.join(), Ok(()));
+    }
+}
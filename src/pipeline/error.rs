@@ -0,0 +1,32 @@
+//! Errors of shutting down pipelines.
+
+#[cfg(doc)]
+use super::Shutdown;
+use core::{error::Error, fmt};
+
+/// Error returned by [`Shutdown::join`] if a stage panicked. Contains the
+/// index of the first stage that panicked, starting at `0`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StageError(pub usize);
+
+impl fmt::Display for StageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "stage {} of the pipeline panicked", self.0)
+    }
+}
+
+impl Error for StageError {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::string::ToString;
+
+    #[test]
+    fn test_display() {
+        assert_eq!(
+            StageError(2).to_string(),
+            "stage 2 of the pipeline panicked"
+        );
+    }
+}
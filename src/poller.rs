@@ -0,0 +1,325 @@
+#![forbid(unsafe_code)]
+//! # Bridging completion callbacks to futures
+//!
+//! APIs reporting the result of an operation through a completion callback
+//! (common for C libraries, OS facilities and event loops) are awkward to use
+//! from async code. [`bridge`] creates a [`Completer`] to move into the
+//! callback and a [`Completion`] future: once the callback calls
+//! [`Completer::complete`], the future resolves to the value.
+//!
+//! The states a callback can end up in are handled, so callbacks don't have
+//! to track them themselves:
+//!
+//! - Invoked more than once: Only the first value counts, later calls return
+//!   [`CompleteError::AlreadyCompleted`]. Completers can be cloned for
+//!   callbacks that are registered multiple times (e.g. a success and an
+//!   error callback), the first one completing wins as well.
+//! - Never invoked: Once all completers have been dropped (like when the
+//!   library drops the callback), the future resolves to [`DroppedError`]
+//!   instead of hanging forever.
+//! - Nobody waits: If the future has been dropped, e.g. because the operation
+//!   has been cancelled, completing returns [`CompleteError::Abandoned`], so
+//!   the value can be cleaned up. [`Completer::is_abandoned`] allows skipping
+//!   expensive work before.
+//!
+//! The bridge is a thin layer over a [`shotgun`](crate::shotgun) channel, so
+//! the value has to be `Clone` and completions can be cloned to be awaited by
+//! multiple tasks. Like shotgun, it works in `no_std` builds with the `spin`
+//! feature, doesn't contain any unsafe code and works with any async runtime.
+//!
+//! # Examples
+//! ```rust
+//! # tokio::runtime::Builder::new_current_thread()
+//! #     .build()
+//! #     .unwrap()
+//! #     .block_on(async {
+//! use laika::poller::{self, DroppedError};
+//! use std::thread;
+//!
+//! // A callback-based API, invoking `done` on a thread of its own
+//! fn read_sensor(done: impl FnOnce(Result<u16, i32>) + Send + 'static) {
+//!     thread::spawn(move || done(Ok(451)));
+//! }
+//!
+//! let (completer, completion) = poller::bridge();
+//!
+//! read_sensor(move |result| {
+//!     let _ = completer.complete(result);
+//! });
+//!
+//! assert_eq!(completion.await, Ok(Ok(451)));
+//!
+//! // A callback that is dropped without being invoked
+//! let (completer, completion) = poller::bridge::<u16>();
+//! drop(completer);
+//!
+//! assert_eq!(completion.await, Err(DroppedError));
+//! # });
+//! ```
+
+mod error;
+
+pub use error::{CompleteError, DroppedError};
+
+use crate::shotgun::{self, Receiver, SendError, Sender, TryRecvError};
+use core::{
+    fmt,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+/// Completing side of a [`bridge`], moved into the callback
+///
+/// Can be cloned for multiple callbacks, the first one completing wins. If
+/// all completers are dropped without completing, the [`Completion`] resolves
+/// to [`DroppedError`].
+pub struct Completer<T>
+where
+    T: Clone,
+{
+    /// Sender of the underlying channel
+    sender: Sender<T>,
+}
+
+impl<T> Completer<T>
+where
+    T: Clone,
+{
+    /// Completes the bridge with `value`, waking up the tasks awaiting the
+    /// [`Completion`]. Takes `&self`, so it can be called from callbacks that
+    /// may be invoked more than once.
+    ///
+    /// Returns [`CompleteError::AlreadyCompleted`] containing the value if
+    /// the bridge has been completed already (by this completer or a clone of
+    /// it), or [`CompleteError::Abandoned`] if all completions have been
+    /// dropped.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use laika::poller::{self, CompleteError};
+    ///
+    /// let (completer, completion) = poller::bridge();
+    ///
+    /// completer.complete("first").unwrap();
+    ///
+    /// // Invoked again, e.g. by a misbehaving library
+    /// assert_eq!(
+    ///     completer.complete("second"),
+    ///     Err(CompleteError::AlreadyCompleted("second"))
+    /// );
+    /// assert_eq!(completion.try_completion(), Some(Ok("first")));
+    /// ```
+    pub fn complete(&self, value: T) -> Result<(), CompleteError<T>> {
+        self.sender
+            .clone()
+            .send(value)
+            .map_err(|error| match error {
+                SendError::AlreadySent(value) => CompleteError::AlreadyCompleted(value),
+                SendError::Disconnected(value) => CompleteError::Abandoned(value),
+            })
+    }
+
+    /// Returns whether all [`Completion`]s have been dropped, so completing
+    /// would fail and the callback may skip producing the value.
+    pub fn is_abandoned(&self) -> bool {
+        self.sender.is_disconnected()
+    }
+}
+
+impl<T> Clone for Completer<T>
+where
+    T: Clone,
+{
+    fn clone(&self) -> Self {
+        Completer {
+            sender: self.sender.clone(),
+        }
+    }
+}
+
+impl<T> fmt::Debug for Completer<T>
+where
+    T: Clone,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Completer")
+            .field("abandoned", &self.is_abandoned())
+            .finish()
+    }
+}
+
+/// Future of a [`bridge`], resolving once the callback completed
+///
+/// Resolves to the value of the first [`Completer::complete`], or to
+/// [`DroppedError`] if all completers have been dropped without completing.
+/// Can be cloned to be awaited by multiple tasks, each getting a clone of the
+/// value.
+pub struct Completion<T>
+where
+    T: Clone,
+{
+    /// Receiver of the underlying channel
+    receiver: Receiver<T>,
+}
+
+impl<T> Completion<T>
+where
+    T: Clone,
+{
+    /// Returns (a clone of) the value if the bridge has been completed. This
+    /// function is **non-blocking**.
+    ///
+    /// Returns [`None`] if the callback hasn't completed yet or
+    /// [`Some`]`(Err(DroppedError))` if all completers have been dropped
+    /// without completing.
+    pub fn try_completion(&self) -> Option<Result<T, DroppedError>> {
+        match self.receiver.try_recv() {
+            Ok(value) => Some(Ok(value)),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Closed) => Some(Err(DroppedError)),
+        }
+    }
+
+    /// Returns whether the bridge has been completed, or all completers have
+    /// been dropped, so awaiting resolves right away.
+    pub fn is_finished(&self) -> bool {
+        self.try_completion().is_some()
+    }
+
+    /// Blocks the current thread until the callback completed. See
+    /// [`Completion`].
+    ///
+    /// # Examples
+    /// ```rust
+    /// use laika::poller;
+    /// use std::thread;
+    ///
+    /// let (completer, completion) = poller::bridge();
+    ///
+    /// thread::spawn(move || completer.complete(String::from("done")));
+    ///
+    /// assert_eq!(completion.wait().as_deref(), Ok("done"));
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn wait(&self) -> Result<T, DroppedError> {
+        self.receiver.recv_blocking().map_err(|_| DroppedError)
+    }
+}
+
+impl<T> Future for Completion<T>
+where
+    T: Clone,
+{
+    type Output = Result<T, DroppedError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let receiver = Pin::new(&mut self.get_mut().receiver);
+
+        receiver.poll(cx).map_err(|_| DroppedError)
+    }
+}
+
+impl<T> Clone for Completion<T>
+where
+    T: Clone,
+{
+    fn clone(&self) -> Self {
+        Completion {
+            receiver: self.receiver.clone(),
+        }
+    }
+}
+
+impl<T> fmt::Debug for Completion<T>
+where
+    T: Clone,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Completion")
+            .field("finished", &self.is_finished())
+            .finish()
+    }
+}
+
+/// Creates a bridge from a completion callback to a future: move the
+/// [`Completer`] into the callback and await the [`Completion`].
+///
+/// See the [module documentation](self) for details.
+pub fn bridge<T>() -> (Completer<T>, Completion<T>)
+where
+    T: Clone,
+{
+    let (sender, receiver) = shotgun::channel();
+
+    (Completer { sender }, Completion { receiver })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::task::Waker;
+    use std::thread;
+
+    #[test]
+    fn test_complete() {
+        let (completer, completion) = bridge();
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut future = completion.clone();
+
+        assert_eq!(Pin::new(&mut future).poll(&mut cx), Poll::Pending);
+        assert!(!completion.is_finished());
+
+        // The first clone completing wins
+        let error_callback = completer.clone();
+        completer.complete(Ok(1)).unwrap();
+        assert_eq!(
+            error_callback.complete(Err(())),
+            Err(CompleteError::AlreadyCompleted(Err(())))
+        );
+
+        assert_eq!(Pin::new(&mut future).poll(&mut cx), Poll::Ready(Ok(Ok(1))));
+        assert_eq!(completion.try_completion(), Some(Ok(Ok(1))));
+    }
+
+    #[test]
+    fn test_dropped() {
+        let (completer, completion) = bridge::<u8>();
+        let callback = completer.clone();
+
+        drop(completer);
+        assert_eq!(completion.try_completion(), None);
+
+        // Dropped by another thread, like a library discarding the callback
+        thread::spawn(move || drop(callback)).join().unwrap();
+        assert_eq!(completion.try_completion(), Some(Err(DroppedError)));
+    }
+
+    #[test]
+    fn test_abandoned() {
+        let (completer, completion) = bridge();
+
+        assert!(!completer.is_abandoned());
+
+        drop(completion);
+        assert!(completer.is_abandoned());
+        assert_eq!(completer.complete('a'), Err(CompleteError::Abandoned('a')));
+    }
+
+    #[tokio::test]
+    async fn test_await() {
+        let (completer, completion) = bridge();
+
+        let waiters: [_; 2] = core::array::from_fn(|_| tokio::spawn(completion.clone()));
+        drop(completion);
+
+        thread::spawn(move || completer.complete(7))
+            .join()
+            .unwrap()
+            .unwrap();
+
+        for waiter in waiters {
+            assert_eq!(waiter.await.unwrap(), Ok(7));
+        }
+    }
+}
@@ -0,0 +1,76 @@
+//! Errors of completing bridged callbacks.
+
+#[cfg(doc)]
+use super::{Completer, Completion};
+use core::{error::Error, fmt};
+
+/// Error returned by [`Completer::complete`]. Contains the value, so it can
+/// be reclaimed.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum CompleteError<T> {
+    /// A clone of the [`Completer`] has completed already, e.g. because the
+    /// callback has been invoked more than once.
+    AlreadyCompleted(T),
+    /// All [`Completion`]s have been dropped, so nobody waits for the value.
+    Abandoned(T),
+}
+
+impl<T> CompleteError<T> {
+    /// Returns the value that couldn't be completed with.
+    pub fn into_inner(self) -> T {
+        match self {
+            CompleteError::AlreadyCompleted(value) | CompleteError::Abandoned(value) => value,
+        }
+    }
+}
+
+debug_without_value!(CompleteError {
+    AlreadyCompleted,
+    Abandoned
+});
+
+impl<T> fmt::Display for CompleteError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompleteError::AlreadyCompleted(_) => write!(f, "callback completed already"),
+            CompleteError::Abandoned(_) => write!(f, "completing a callback nobody waits for"),
+        }
+    }
+}
+
+impl<T> Error for CompleteError<T> {}
+
+/// Error a [`Completion`] resolves to if all [`Completer`]s have been dropped
+/// without completing, e.g. because the callback has never been invoked.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DroppedError;
+
+impl fmt::Display for DroppedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "callback dropped without completing")
+    }
+}
+
+impl Error for DroppedError {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::string::ToString;
+
+    #[test]
+    fn test_display() {
+        assert_eq!(
+            CompleteError::AlreadyCompleted(1).to_string(),
+            "callback completed already"
+        );
+        assert_eq!(
+            CompleteError::Abandoned(1).to_string(),
+            "completing a callback nobody waits for"
+        );
+        assert_eq!(
+            DroppedError.to_string(),
+            "callback dropped without completing"
+        );
+    }
+}
@@ -0,0 +1,590 @@
+#![forbid(unsafe_code)]
+//! # A channel reporting the progress and result of an operation
+//!
+//! A progress channel holds the current progress of an operation (like a
+//! percentage or the number of processed items) and, once it's done, its
+//! result. The [`Sender`] updates the progress any number of times and
+//! finally [`complete`](Sender::complete)s the operation with its result.
+//!
+//! Every [`Receiver`] can read the current [`progress`](Receiver::progress) at
+//! any time, wait until it [`changed`](Receiver::changed) or wait for the
+//! [`completion`](Receiver::completion). Like with
+//! [`watch`](crate::watch), receivers that don't keep up skip intermediate
+//! updates, while the result is received by all of them. If the sender is
+//! dropped without completing, receivers get a [`CompletionError`] instead.
+//!
+//! The channel is backed by a `std::sync::Mutex` and doesn't contain any
+//! unsafe code. The async functions work with any runtime.
+//!
+//! # Examples
+//! ```rust
+//! use std::thread;
+//!
+//! let (tx, mut rx) = laika::progress::channel(0);
+//!
+//! let worker = thread::spawn(move || {
+//!     for _ in 0..10 {
+//!         tx.update_with(|done| *done += 1);
+//!     }
+//!
+//!     tx.complete("all done");
+//! });
+//!
+//! while let Some(done) = rx.changed_blocking() {
+//!     assert!(done <= 10);
+//! }
+//!
+//! assert_eq!(rx.progress(), 10);
+//! assert_eq!(rx.completion_blocking(), Ok("all done"));
+//! worker.join().unwrap();
+//! ```
+
+mod error;
+
+pub use error::CompletionError;
+
+use alloc::sync::Arc;
+use core::{
+    future, mem,
+    task::{Context, Poll, Waker},
+};
+use std::{
+    collections::BTreeMap,
+    sync::{Condvar, Mutex, MutexGuard, PoisonError},
+};
+
+/// How an operation ended (if it did)
+#[derive(Debug)]
+enum Outcome<R> {
+    /// The [`Sender`] still exists
+    Running,
+    /// The [`Sender`] completed the operation with a result
+    Completed(R),
+    /// The [`Sender`] has been dropped without completing
+    Abandoned,
+}
+
+/// Progress and bookkeeping of a channel, guarded by [`Shared::state`]
+#[derive(Debug)]
+struct State<P, R> {
+    /// Current progress
+    progress: P,
+    /// Number of times the progress has been updated
+    version: u64,
+    /// How the operation ended
+    outcome: Outcome<R>,
+    /// Number of existing [`Receiver`]s
+    receivers: usize,
+    /// Wakers of pending [`Receiver`]s, keyed by [`Receiver::id`]
+    wakers: BTreeMap<u64, Waker>,
+    /// Id of the next [`Receiver`]
+    next_id: u64,
+}
+
+impl<P, R> State<P, R> {
+    /// Returns a new receiver id.
+    fn id(&mut self) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    /// Returns the result of the operation if it ended.
+    fn completion(&self) -> Option<Result<R, CompletionError>>
+    where
+        R: Clone,
+    {
+        match &self.outcome {
+            Outcome::Running => None,
+            Outcome::Completed(result) => Some(Ok(result.clone())),
+            Outcome::Abandoned => Some(Err(CompletionError)),
+        }
+    }
+
+    /// Returns the progress if it has been updated since `seen` and marks it
+    /// as seen. Returns `Some(None)` if the operation ended and every update
+    /// has been seen.
+    fn changed(&self, seen: &mut u64) -> Option<Option<P>>
+    where
+        P: Clone,
+    {
+        if self.version != *seen {
+            *seen = self.version;
+            return Some(Some(self.progress.clone()));
+        }
+
+        match self.outcome {
+            Outcome::Running => None,
+            _ => Some(None),
+        }
+    }
+
+    /// Registers `waker` under `id`, unless it would wake the registered one.
+    fn register(&mut self, id: u64, waker: &Waker) {
+        if !self.wakers.get(&id).is_some_and(|w| w.will_wake(waker)) {
+            self.wakers.insert(id, waker.clone());
+        }
+    }
+}
+
+/// State shared by the [`Sender`] and all [`Receiver`]s of a channel
+#[derive(Debug)]
+struct Shared<P, R> {
+    /// Progress and bookkeeping
+    state: Mutex<State<P, R>>,
+    /// Notified when the progress is updated or the operation ends, so that
+    /// threads blocking in [`Receiver`] functions wake up.
+    condvar: Condvar,
+}
+
+impl<P, R> Shared<P, R> {
+    /// Locks the state.
+    fn lock(&self) -> MutexGuard<'_, State<P, R>> {
+        crate::lock(&self.state)
+    }
+
+    /// Waits on the condvar, ignoring poisoning like [`Shared::lock`].
+    fn wait<'a>(&self, state: MutexGuard<'a, State<P, R>>) -> MutexGuard<'a, State<P, R>> {
+        self.condvar
+            .wait(state)
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Wakes up all pending receivers. Takes the wakers while `state` is
+    /// locked but wakes them after unlocking it.
+    fn wake_all(&self, mut state: MutexGuard<'_, State<P, R>>) {
+        let wakers = mem::take(&mut state.wakers);
+        drop(state);
+
+        for waker in wakers.into_values() {
+            waker.wake();
+        }
+
+        self.condvar.notify_all();
+    }
+}
+
+/// Reporting side of a progress channel
+///
+/// Updates the progress and completes the operation. Dropping it without
+/// calling [`Sender::complete`] abandons the operation.
+#[derive(Debug)]
+pub struct Sender<P, R> {
+    /// State shared with all receivers
+    shared: Arc<Shared<P, R>>,
+}
+
+/// Observing side of a progress channel
+///
+/// Reads the progress and tracks which update of it has been seen, see
+/// [`Receiver::changed`]. A cloned receiver has seen the same update as the
+/// original one.
+#[derive(Debug)]
+pub struct Receiver<P, R> {
+    /// State shared with the sender and all other receivers
+    shared: Arc<Shared<P, R>>,
+    /// Version of the progress that has been seen last
+    seen: u64,
+    /// Key of the waker this receiver registered while being polled
+    id: u64,
+}
+
+impl<P, R> Sender<P, R> {
+    /// Replaces the progress and wakes up all waiting receivers. Succeeds even
+    /// if there are no receivers.
+    pub fn update(&self, progress: P) {
+        let mut state = self.shared.lock();
+        let previous = mem::replace(&mut state.progress, progress);
+        state.version += 1;
+
+        self.shared.wake_all(state);
+
+        // The previous progress is dropped after unlocking, in case its `Drop`
+        // uses the channel.
+        drop(previous);
+    }
+
+    /// Modifies the progress in place and wakes up all waiting receivers.
+    ///
+    /// `update` is called while the channel is locked, so it must not use the
+    /// channel itself.
+    ///
+    /// # Examples
+    /// ```rust
+    /// let (tx, rx) = laika::progress::channel::<usize, ()>(0);
+    ///
+    /// tx.update_with(|done| *done += 2);
+    /// tx.update_with(|done| *done += 3);
+    ///
+    /// assert_eq!(rx.progress(), 5);
+    /// ```
+    pub fn update_with<F>(&self, update: F)
+    where
+        F: FnOnce(&mut P),
+    {
+        let mut state = self.shared.lock();
+        update(&mut state.progress);
+        state.version += 1;
+
+        self.shared.wake_all(state);
+    }
+
+    /// Completes the operation with `result`, which every receiver gets from
+    /// [`Receiver::completion`]. The progress keeps its last value.
+    pub fn complete(self, result: R) {
+        self.shared.lock().outcome = Outcome::Completed(result);
+        // Dropping the sender wakes up all waiting receivers
+    }
+
+    /// Creates a new [`Receiver`] that has seen the current progress already.
+    pub fn subscribe(&self) -> Receiver<P, R> {
+        let mut state = self.shared.lock();
+        state.receivers += 1;
+
+        Receiver {
+            shared: Arc::clone(&self.shared),
+            seen: state.version,
+            id: state.id(),
+        }
+    }
+
+    /// Returns the number of [`Receiver`]s that currently exist.
+    pub fn receiver_count(&self) -> usize {
+        self.shared.lock().receivers
+    }
+}
+
+/// Ends the operation when the [`Sender`] is dropped (abandoning it unless it
+/// has been completed), waking up all waiting receivers.
+impl<P, R> Drop for Sender<P, R> {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+
+        if let Outcome::Running = state.outcome {
+            state.outcome = Outcome::Abandoned;
+        }
+
+        self.shared.wake_all(state);
+    }
+}
+
+impl<P, R> Receiver<P, R> {
+    /// Returns the current progress without marking it as seen.
+    pub fn progress(&self) -> P
+    where
+        P: Clone,
+    {
+        self.shared.lock().progress.clone()
+    }
+
+    /// Returns whether the progress has been updated since it has been seen
+    /// last.
+    pub fn has_changed(&self) -> bool {
+        self.shared.lock().version != self.seen
+    }
+
+    /// Returns whether the operation ended, i.e. the [`Sender`] completed it
+    /// or has been dropped.
+    pub fn is_finished(&self) -> bool {
+        !matches!(self.shared.lock().outcome, Outcome::Running)
+    }
+
+    /// Polls for an update of the progress, registering the waker of `cx` if
+    /// there is none yet. Marks the progress as seen once it has been updated.
+    pub fn poll_changed(&mut self, cx: &mut Context<'_>) -> Poll<Option<P>>
+    where
+        P: Clone,
+    {
+        let mut state = self.shared.lock();
+
+        if let Some(changed) = state.changed(&mut self.seen) {
+            return Poll::Ready(changed);
+        }
+
+        state.register(self.id, cx.waker());
+
+        Poll::Pending
+    }
+
+    /// Waits asynchronously until the progress has been updated since it has
+    /// been seen last, marks it as seen and returns it. Resolves right away if
+    /// it has been updated already.
+    ///
+    /// Resolves to [`None`] once the operation ended and every update has been
+    /// seen, so the progress won't change anymore.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # tokio::runtime::Builder::new_current_thread()
+    /// #     .build()
+    /// #     .unwrap()
+    /// #     .block_on(async {
+    /// let (tx, mut rx) = laika::progress::channel(0);
+    ///
+    /// let task = tokio::spawn(async move {
+    ///     let mut last = 0;
+    ///
+    ///     while let Some(percent) = rx.changed().await {
+    ///         last = percent;
+    ///     }
+    ///
+    ///     (last, rx.completion().await)
+    /// });
+    ///
+    /// tx.update(50);
+    /// tx.update(100);
+    /// tx.complete("done");
+    ///
+    /// assert_eq!(task.await.unwrap(), (100, Ok("done")));
+    /// # });
+    /// ```
+    pub async fn changed(&mut self) -> Option<P>
+    where
+        P: Clone,
+    {
+        future::poll_fn(|cx| self.poll_changed(cx)).await
+    }
+
+    /// Blocks the current thread until the progress has been updated since it
+    /// has been seen last. See [`Receiver::changed`].
+    pub fn changed_blocking(&mut self) -> Option<P>
+    where
+        P: Clone,
+    {
+        let mut state = self.shared.lock();
+
+        loop {
+            if let Some(changed) = state.changed(&mut self.seen) {
+                return changed;
+            }
+
+            state = self.shared.wait(state);
+        }
+    }
+
+    /// Returns the result if the operation ended. This function is
+    /// **non-blocking**.
+    ///
+    /// Returns [`CompletionError`] if the [`Sender`] has been dropped without
+    /// completing.
+    pub fn try_completion(&self) -> Option<Result<R, CompletionError>>
+    where
+        R: Clone,
+    {
+        self.shared.lock().completion()
+    }
+
+    /// Polls for the result, registering the waker of `cx` if the operation
+    /// hasn't ended yet.
+    pub fn poll_completion(&mut self, cx: &mut Context<'_>) -> Poll<Result<R, CompletionError>>
+    where
+        R: Clone,
+    {
+        let mut state = self.shared.lock();
+
+        if let Some(completion) = state.completion() {
+            return Poll::Ready(completion);
+        }
+
+        state.register(self.id, cx.waker());
+
+        Poll::Pending
+    }
+
+    /// Waits asynchronously until the operation ended and returns its result,
+    /// regardless of the updates that have been seen.
+    ///
+    /// Resolves to [`CompletionError`] if the [`Sender`] has been dropped
+    /// without completing.
+    pub async fn completion(&mut self) -> Result<R, CompletionError>
+    where
+        R: Clone,
+    {
+        future::poll_fn(|cx| self.poll_completion(cx)).await
+    }
+
+    /// Blocks the current thread until the operation ended. See
+    /// [`Receiver::completion`].
+    pub fn completion_blocking(&self) -> Result<R, CompletionError>
+    where
+        R: Clone,
+    {
+        let mut state = self.shared.lock();
+
+        loop {
+            if let Some(completion) = state.completion() {
+                return completion;
+            }
+
+            state = self.shared.wait(state);
+        }
+    }
+}
+
+/// Cloned receivers have seen the same update of the progress as the original
+/// one.
+impl<P, R> Clone for Receiver<P, R> {
+    fn clone(&self) -> Self {
+        let mut state = self.shared.lock();
+        state.receivers += 1;
+
+        Receiver {
+            shared: Arc::clone(&self.shared),
+            seen: self.seen,
+            id: state.id(),
+        }
+    }
+}
+
+/// Removes the registered waker (if any) when a [`Receiver`] is dropped.
+impl<P, R> Drop for Receiver<P, R> {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.receivers -= 1;
+        state.wakers.remove(&self.id);
+    }
+}
+
+/// Creates a progress channel starting at `initial`, which the receiver has
+/// seen already.
+///
+/// # Examples
+/// ```rust
+/// let (tx, rx) = laika::progress::channel(0.0);
+///
+/// tx.update(0.5);
+/// assert_eq!(rx.progress(), 0.5);
+///
+/// tx.complete(Ok::<_, String>(42));
+/// assert_eq!(rx.try_completion(), Some(Ok(Ok(42))));
+/// ```
+pub fn channel<P, R>(initial: P) -> (Sender<P, R>, Receiver<P, R>) {
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            progress: initial,
+            version: 0,
+            outcome: Outcome::Running,
+            receivers: 1,
+            wakers: BTreeMap::new(),
+            next_id: 1,
+        }),
+        condvar: Condvar::new(),
+    });
+
+    let receiver = Receiver {
+        shared: Arc::clone(&shared),
+        seen: 0,
+        id: 0,
+    };
+
+    (Sender { shared }, receiver)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::{future::Future, pin::pin};
+    use std::{thread, time::Duration};
+
+    #[test]
+    fn test_changed() {
+        let (tx, mut rx) = channel::<u8, ()>(0);
+        let mut cx = Context::from_waker(Waker::noop());
+
+        assert!(!rx.has_changed());
+        assert_eq!(rx.poll_changed(&mut cx), Poll::Pending);
+
+        // Intermediate updates are skipped
+        tx.update(1);
+        tx.update(2);
+        assert!(rx.has_changed());
+        assert_eq!(rx.poll_changed(&mut cx), Poll::Ready(Some(2)));
+        assert_eq!(rx.poll_changed(&mut cx), Poll::Pending);
+
+        // Updates before the end are still received
+        tx.update(3);
+        drop(tx);
+        assert_eq!(rx.poll_changed(&mut cx), Poll::Ready(Some(3)));
+        assert_eq!(rx.poll_changed(&mut cx), Poll::Ready(None));
+    }
+
+    #[test]
+    fn test_completion() {
+        let (tx, mut rx) = channel(0);
+        let rx1 = tx.subscribe();
+        let mut cx = Context::from_waker(Waker::noop());
+
+        let mut completion = pin!(rx.completion());
+        assert_eq!(completion.as_mut().poll(&mut cx), Poll::Pending);
+        assert_eq!(rx1.try_completion(), None);
+
+        tx.update(1);
+        tx.complete('x');
+
+        // Every receiver gets the result
+        assert_eq!(completion.poll(&mut cx), Poll::Ready(Ok('x')));
+        assert_eq!(rx1.try_completion(), Some(Ok('x')));
+        assert!(rx1.is_finished());
+        assert_eq!(rx1.progress(), 1);
+    }
+
+    #[test]
+    fn test_abandoned() {
+        let (tx, rx) = channel::<u8, u8>(0);
+
+        let thread = thread::spawn(move || rx.completion_blocking());
+
+        thread::sleep(Duration::from_millis(50));
+        drop(tx);
+
+        assert_eq!(thread.join().unwrap(), Err(CompletionError));
+    }
+
+    #[test]
+    fn test_changed_blocking() {
+        let (tx, mut rx) = channel(0);
+
+        let thread = thread::spawn(move || {
+            let mut updates = 0;
+
+            while let Some(progress) = rx.changed_blocking() {
+                assert!(progress <= 100);
+                updates += 1;
+            }
+
+            (updates, rx.completion_blocking())
+        });
+
+        for _ in 0..100 {
+            tx.update_with(|progress| *progress += 1);
+        }
+
+        tx.complete(());
+
+        let (updates, completion) = thread.join().unwrap();
+        assert!((1..=100).contains(&updates));
+        assert_eq!(completion, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn test_async() {
+        let (tx, rx) = channel(0);
+
+        let tasks = (0..3)
+            .map(|_| {
+                let mut rx = rx.clone();
+                tokio::spawn(async move { rx.completion().await })
+            })
+            .collect::<std::vec::Vec<_>>();
+
+        tokio::task::yield_now().await;
+        tx.update(50);
+        tx.complete(true);
+
+        for task in tasks {
+            assert_eq!(task.await.unwrap(), Ok(true));
+        }
+
+        assert_eq!(rx.progress(), 50);
+    }
+}
@@ -0,0 +1,33 @@
+//! Errors of awaiting the completion of a progress channel.
+
+#[cfg(doc)]
+use super::{Receiver, Sender};
+use core::{error::Error, fmt};
+
+/// Error returned by [`Receiver::completion`] and its variants if the
+/// [`Sender`] has been dropped without calling [`Sender::complete`], so there
+/// will never be a result.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CompletionError;
+
+impl fmt::Display for CompletionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "progress abandoned without completion")
+    }
+}
+
+impl Error for CompletionError {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::string::ToString;
+
+    #[test]
+    fn test_display() {
+        assert_eq!(
+            CompletionError.to_string(),
+            "progress abandoned without completion"
+        );
+    }
+}
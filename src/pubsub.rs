@@ -0,0 +1,612 @@
+#![forbid(unsafe_code)]
+//! # Topic-based publish/subscribe
+//!
+//! A [`PubSub`] hub is an in-process bus in the spirit of MQTT: messages are
+//! published on topics like `metrics.cpu.load`, whose levels are separated by
+//! dots, and delivered to the subscriptions whose pattern matches the topic.
+//! Patterns may contain wildcards as levels of their own:
+//!
+//! - `*` matches exactly one level, so `metrics.*.load` matches
+//!   `metrics.cpu.load` but not `metrics.load`.
+//! - `#` matches any number of levels (including none) and has to be the last
+//!   level, so `metrics.#` matches `metrics`, `metrics.cpu` and
+//!   `metrics.cpu.load`.
+//!
+//! Messages published with [`PubSub::publish_retained`] are retained: the
+//! hub keeps the last one per topic and delivers it to every subscription
+//! created later whose pattern matches, e.g. so a subscriber gets the current
+//! configuration right away. Publishing with [`PubSub::publish`] doesn't
+//! affect the retained message of the topic.
+//!
+//! A [`Subscription`] gets the messages published while it exists (after the
+//! retained ones) and unsubscribes when it's dropped. Every subscription has
+//! its own unbounded queue, so publishing never waits for slow subscribers.
+//! The hub can be cloned to publish from multiple places. Once all clones
+//! have been dropped, subscriptions get the remaining queued messages and
+//! [`None`] afterwards.
+//!
+//! The hub doesn't contain any unsafe code and works with any async runtime.
+//!
+//! ## Optional features
+//!
+//! - `futures`: Implements `futures_core::Stream` for [`Subscription`].
+//!
+//! # Examples
+//! ```rust
+//! use laika::pubsub::PubSub;
+//!
+//! let hub = PubSub::new();
+//!
+//! hub.publish_retained("config.log_level", "info").unwrap();
+//!
+//! let mut config = hub.subscribe("config.#").unwrap();
+//! let mut loads = hub.subscribe("metrics.*.load").unwrap();
+//!
+//! hub.publish("metrics.cpu.load", "0.7").unwrap();
+//! hub.publish("metrics.cpu.temperature", "61").unwrap();
+//!
+//! // Retained before subscribing
+//! let message = config.try_recv().unwrap();
+//! assert_eq!((message.topic(), *message.payload()), ("config.log_level", "info"));
+//!
+//! let message = loads.try_recv().unwrap();
+//! assert_eq!((message.topic(), *message.payload()), ("metrics.cpu.load", "0.7"));
+//! assert!(loads.try_recv().is_err());
+//! ```
+
+mod error;
+
+pub use error::TopicError;
+
+use crate::mpsc::{self, TryRecvError, UnboundedReceiver, UnboundedSender};
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+#[cfg(feature = "futures")]
+use core::pin::Pin;
+use core::{
+    fmt,
+    task::{Context, Poll},
+};
+use std::sync::{Mutex, MutexGuard};
+
+/// Separator of the levels of topics
+const SEPARATOR: char = '.';
+
+/// Level of a [`Pattern`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Level {
+    /// Matches this level only
+    Exact(String),
+    /// Matches any single level (`*`)
+    Any,
+    /// Matches all remaining levels (`#`)
+    Rest,
+}
+
+/// Parsed pattern of a [`Subscription`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Pattern(Vec<Level>);
+
+impl Pattern {
+    /// Parses `pattern`, which may contain wildcards.
+    fn parse(pattern: &str) -> Result<Self, TopicError> {
+        if pattern.is_empty() {
+            return Err(TopicError::Empty);
+        }
+
+        let count = pattern.split(SEPARATOR).count();
+        let mut levels = Vec::with_capacity(count);
+
+        for (index, level) in pattern.split(SEPARATOR).enumerate() {
+            levels.push(match level {
+                "" => return Err(TopicError::EmptyLevel),
+                "*" => Level::Any,
+                "#" if index + 1 == count => Level::Rest,
+                _ if level.contains(['*', '#']) => {
+                    return Err(TopicError::MisplacedWildcard);
+                }
+                _ => Level::Exact(level.to_string()),
+            });
+        }
+
+        Ok(Pattern(levels))
+    }
+
+    /// Returns whether the pattern matches `topic`.
+    fn matches(&self, topic: &str) -> bool {
+        let mut levels = topic.split(SEPARATOR);
+
+        for level in &self.0 {
+            match level {
+                Level::Rest => return true,
+                Level::Any if levels.next().is_some() => {}
+                Level::Exact(exact) if levels.next() == Some(exact.as_str()) => {}
+                _ => return false,
+            }
+        }
+
+        levels.next().is_none()
+    }
+}
+
+/// Checks that `topic` is valid to be published on.
+fn validate(topic: &str) -> Result<(), TopicError> {
+    if topic.is_empty() {
+        return Err(TopicError::Empty);
+    }
+
+    for level in topic.split(SEPARATOR) {
+        if level.is_empty() {
+            return Err(TopicError::EmptyLevel);
+        }
+
+        if level.contains(['*', '#']) {
+            return Err(TopicError::Wildcard);
+        }
+    }
+
+    Ok(())
+}
+
+/// Message delivered to a [`Subscription`]: the payload and the topic it has
+/// been published on
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Message<T> {
+    /// Topic the message has been published on
+    topic: Arc<str>,
+    /// Published value
+    payload: T,
+}
+
+impl<T> Message<T> {
+    /// Returns the topic the message has been published on.
+    pub fn topic(&self) -> &str {
+        &self.topic
+    }
+
+    /// Returns the published value.
+    pub fn payload(&self) -> &T {
+        &self.payload
+    }
+
+    /// Returns the published value, consuming the message.
+    pub fn into_payload(self) -> T {
+        self.payload
+    }
+}
+
+/// Subscription of a hub, as stored in [`State::subscribers`]
+struct Subscriber<T> {
+    /// Pattern of topics the subscription gets messages of
+    pattern: Pattern,
+    /// Queue of the subscription
+    sender: UnboundedSender<Message<T>>,
+}
+
+/// Subscribers and retained messages of a hub, guarded by [`Shared::state`]
+struct State<T> {
+    /// Subscribers, keyed by subscription id
+    subscribers: BTreeMap<u64, Subscriber<T>>,
+    /// Last retained message per topic
+    retained: BTreeMap<Arc<str>, T>,
+    /// Id of the next subscription
+    next_id: u64,
+}
+
+/// State shared by all clones of a [`PubSub`]
+struct Shared<T> {
+    /// Subscribers and retained messages
+    state: Mutex<State<T>>,
+}
+
+impl<T> Shared<T> {
+    /// Locks the state.
+    fn lock(&self) -> MutexGuard<'_, State<T>> {
+        crate::lock(&self.state)
+    }
+}
+
+/// Hub delivering messages published on topics to matching subscriptions
+///
+/// See the [module documentation](self) for details.
+pub struct PubSub<T> {
+    /// State shared with all clones
+    shared: Arc<Shared<T>>,
+}
+
+/// Subscription to a [`PubSub`], receiving the messages published on topics
+/// matching its pattern
+///
+/// Dropping it unsubscribes.
+pub struct Subscription<T> {
+    /// Queue of delivered messages
+    receiver: UnboundedReceiver<Message<T>>,
+    /// Hub to unsubscribe from
+    hub: Weak<Shared<T>>,
+    /// Id of this subscription
+    id: u64,
+}
+
+impl<T> PubSub<T>
+where
+    T: Clone,
+{
+    /// Creates a hub without subscribers or retained messages.
+    pub fn new() -> Self {
+        PubSub {
+            shared: Arc::new(Shared {
+                state: Mutex::new(State {
+                    subscribers: BTreeMap::new(),
+                    retained: BTreeMap::new(),
+                    next_id: 0,
+                }),
+            }),
+        }
+    }
+
+    /// Delivers `payload` to all subscriptions whose pattern matches `topic`.
+    /// Never waits. Returns the number of subscriptions it has been delivered
+    /// to.
+    ///
+    /// Returns [`TopicError`] if `topic` isn't valid, e.g. because it contains
+    /// a wildcard.
+    pub fn publish(&self, topic: &str, payload: T) -> Result<usize, TopicError> {
+        validate(topic)?;
+
+        let mut state = self.shared.lock();
+        Ok(Self::deliver(&mut state, Arc::from(topic), payload))
+    }
+
+    /// Retains `payload` as the last message of `topic`, replacing the one
+    /// retained before, and delivers it like [`PubSub::publish`]. Subscriptions
+    /// created later get it as their first message if their pattern matches.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use laika::pubsub::PubSub;
+    ///
+    /// let hub = PubSub::new();
+    ///
+    /// hub.publish_retained("devices.lamp.state", "off").unwrap();
+    /// hub.publish_retained("devices.lamp.state", "on").unwrap();
+    ///
+    /// // Only the last retained message is delivered
+    /// let mut lamp = hub.subscribe("devices.*.state").unwrap();
+    /// assert_eq!(lamp.try_recv().unwrap().into_payload(), "on");
+    /// assert!(lamp.try_recv().is_err());
+    ///
+    /// assert_eq!(hub.retained("devices.lamp.state"), Some("on"));
+    /// ```
+    pub fn publish_retained(&self, topic: &str, payload: T) -> Result<usize, TopicError> {
+        validate(topic)?;
+
+        let topic = Arc::<str>::from(topic);
+        let mut state = self.shared.lock();
+        let replaced = state.retained.insert(Arc::clone(&topic), payload.clone());
+        let delivered = Self::deliver(&mut state, topic, payload);
+
+        // The replaced message is dropped after unlocking, in case its `Drop`
+        // uses the hub.
+        drop(state);
+        drop(replaced);
+
+        Ok(delivered)
+    }
+
+    /// Delivers `payload` on `topic` to the matching subscriptions, removing
+    /// the closed ones.
+    fn deliver(state: &mut State<T>, topic: Arc<str>, payload: T) -> usize {
+        let mut delivered = 0;
+
+        state.subscribers.retain(|_, subscriber| {
+            if !subscriber.pattern.matches(&topic) {
+                return true;
+            }
+
+            let message = Message {
+                topic: Arc::clone(&topic),
+                payload: payload.clone(),
+            };
+
+            let sent = subscriber.sender.send(message).is_ok();
+            delivered += usize::from(sent);
+
+            sent
+        });
+
+        delivered
+    }
+
+    /// Removes the retained message of `topic` and returns it, if there is
+    /// one.
+    pub fn clear_retained(&self, topic: &str) -> Option<T> {
+        self.shared.lock().retained.remove(topic)
+    }
+
+    /// Returns (a clone of) the retained message of `topic`, if there is one.
+    pub fn retained(&self, topic: &str) -> Option<T> {
+        self.shared.lock().retained.get(topic).cloned()
+    }
+
+    /// Subscribes to the messages published on topics matching `pattern`.
+    /// The retained messages of matching topics are queued right away.
+    ///
+    /// Returns [`TopicError`] if `pattern` isn't valid, e.g. because `#`
+    /// isn't its last level.
+    pub fn subscribe(&self, pattern: &str) -> Result<Subscription<T>, TopicError> {
+        let pattern = Pattern::parse(pattern)?;
+        let (sender, receiver) = mpsc::unbounded();
+
+        let mut state = self.shared.lock();
+
+        for (topic, payload) in &state.retained {
+            if pattern.matches(topic) {
+                let message = Message {
+                    topic: Arc::clone(topic),
+                    payload: payload.clone(),
+                };
+
+                // The receiver can't have been dropped yet
+                let _ = sender.send(message);
+            }
+        }
+
+        let id = state.next_id;
+        state.next_id += 1;
+        state.subscribers.insert(id, Subscriber { pattern, sender });
+
+        Ok(Subscription {
+            receiver,
+            hub: Arc::downgrade(&self.shared),
+            id,
+        })
+    }
+
+    /// Returns the number of current subscribers.
+    pub fn subscriber_count(&self) -> usize {
+        self.shared.lock().subscribers.len()
+    }
+}
+
+impl<T> Default for PubSub<T>
+where
+    T: Clone,
+{
+    fn default() -> Self {
+        PubSub::new()
+    }
+}
+
+impl<T> Clone for PubSub<T> {
+    fn clone(&self) -> Self {
+        PubSub {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+impl<T> fmt::Debug for PubSub<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.shared.lock();
+
+        f.debug_struct("PubSub")
+            .field("subscribers", &state.subscribers.len())
+            .field("retained", &state.retained.len())
+            .finish()
+    }
+}
+
+impl<T> Subscription<T> {
+    /// Receives the oldest delivered message if there is one. This function
+    /// is **non-blocking**.
+    ///
+    /// Returns [`TryRecvError::Empty`] if no message is queued or
+    /// [`TryRecvError::Disconnected`] if none is and the [`PubSub`] has been
+    /// dropped.
+    pub fn try_recv(&mut self) -> Result<Message<T>, TryRecvError> {
+        self.receiver.try_recv()
+    }
+
+    /// Polls for the oldest delivered message, registering the waker of `cx`
+    /// if there is none yet. Resolves to [`None`] once no message is queued
+    /// and the [`PubSub`] has been dropped.
+    pub fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<Message<T>>> {
+        self.receiver.poll_recv(cx)
+    }
+
+    /// Receives the oldest delivered message, waiting asynchronously until
+    /// there is one. Resolves to [`None`] once no message is queued and the
+    /// [`PubSub`] has been dropped.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # tokio::runtime::Builder::new_current_thread()
+    /// #     .build()
+    /// #     .unwrap()
+    /// #     .block_on(async {
+    /// use laika::pubsub::PubSub;
+    ///
+    /// let hub = PubSub::new();
+    /// let mut orders = hub.subscribe("orders.#").unwrap();
+    ///
+    /// let task = tokio::spawn(async move {
+    ///     let mut topics = Vec::new();
+    ///
+    ///     while let Some(message) = orders.recv().await {
+    ///         topics.push(message.topic().to_owned());
+    ///     }
+    ///
+    ///     topics
+    /// });
+    ///
+    /// hub.publish("orders.created", 1).unwrap();
+    /// hub.publish("invoices.created", 1).unwrap();
+    /// hub.publish("orders.shipped.express", 1).unwrap();
+    ///
+    /// drop(hub);
+    /// assert_eq!(task.await.unwrap(), ["orders.created", "orders.shipped.express"]);
+    /// # });
+    /// ```
+    pub async fn recv(&mut self) -> Option<Message<T>> {
+        self.receiver.recv().await
+    }
+
+    /// Receives the oldest delivered message, blocking the current thread
+    /// until there is one. See [`Subscription::recv`].
+    pub fn recv_blocking(&mut self) -> Option<Message<T>> {
+        self.receiver.recv_blocking()
+    }
+
+    /// Unsubscribes, dropping the queued messages. Same as dropping the
+    /// subscription.
+    pub fn unsubscribe(self) {}
+}
+
+/// Unsubscribes from the [`PubSub`], so no more messages are delivered.
+impl<T> Drop for Subscription<T> {
+    fn drop(&mut self) {
+        if let Some(hub) = self.hub.upgrade() {
+            let subscriber = hub.lock().subscribers.remove(&self.id);
+
+            // The subscriber is dropped after unlocking, in case it's the
+            // last one and the `Drop` of queued messages uses the hub.
+            drop(subscriber);
+        }
+    }
+}
+
+impl<T> fmt::Debug for Subscription<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Subscription")
+            .field("id", &self.id)
+            .field("len", &self.receiver.len())
+            .finish()
+    }
+}
+
+/// Implement [`futures_core::Stream`] for [`Subscription`], yielding the
+/// delivered messages until the [`PubSub`] has been dropped.
+#[cfg(feature = "futures")]
+impl<T> futures_core::Stream for Subscription<T> {
+    type Item = Message<T>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Message<T>>> {
+        self.get_mut().poll_recv(cx)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::thread;
+
+    /// Returns the topics of the queued messages of `subscription`.
+    fn topics<T>(subscription: &mut Subscription<T>) -> Vec<String> {
+        core::iter::from_fn(|| subscription.try_recv().ok())
+            .map(|message| message.topic().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_patterns() {
+        let pattern = Pattern::parse("metrics.*.load").unwrap();
+        assert!(pattern.matches("metrics.cpu.load"));
+        assert!(!pattern.matches("metrics.load"));
+        assert!(!pattern.matches("metrics.cpu.load.avg"));
+
+        let pattern = Pattern::parse("metrics.#").unwrap();
+        assert!(pattern.matches("metrics"));
+        assert!(pattern.matches("metrics.cpu.load"));
+        assert!(!pattern.matches("metric"));
+
+        assert_eq!(Pattern::parse(""), Err(TopicError::Empty));
+        assert_eq!(Pattern::parse("a..b"), Err(TopicError::EmptyLevel));
+        assert_eq!(Pattern::parse("a.#.b"), Err(TopicError::MisplacedWildcard));
+        assert_eq!(Pattern::parse("a.b*"), Err(TopicError::MisplacedWildcard));
+
+        assert_eq!(validate("a.*"), Err(TopicError::Wildcard));
+        assert_eq!(validate("a."), Err(TopicError::EmptyLevel));
+        assert_eq!(validate("a.b"), Ok(()));
+    }
+
+    #[test]
+    fn test_publish() {
+        let hub = PubSub::new();
+
+        let mut all = hub.subscribe("#").unwrap();
+        let mut cpu = hub.subscribe("metrics.cpu").unwrap();
+        let mut levels = hub.subscribe("*.*").unwrap();
+
+        assert_eq!(hub.publish("metrics.cpu", 1), Ok(3));
+        assert_eq!(hub.publish("metrics.cpu.load", 2), Ok(1));
+        assert_eq!(hub.publish("metrics", 3), Ok(1));
+        assert_eq!(hub.publish("metrics.#", 4), Err(TopicError::Wildcard));
+
+        assert_eq!(
+            topics(&mut all),
+            ["metrics.cpu", "metrics.cpu.load", "metrics"]
+        );
+        assert_eq!(topics(&mut cpu), ["metrics.cpu"]);
+        assert_eq!(topics(&mut levels), ["metrics.cpu"]);
+    }
+
+    #[test]
+    fn test_retained() {
+        let hub = PubSub::new();
+
+        hub.publish_retained("a.x", 1).unwrap();
+        hub.publish_retained("b.x", 2).unwrap();
+        hub.publish("a.y", 3).unwrap();
+
+        // Retained messages are delivered first
+        let mut a = hub.subscribe("a.*").unwrap();
+        hub.publish("a.z", 4).unwrap();
+        assert_eq!(topics(&mut a), ["a.x", "a.z"]);
+
+        // Publishing without retaining keeps the retained message
+        hub.publish("a.x", 5).unwrap();
+        assert_eq!(hub.retained("a.x"), Some(1));
+
+        assert_eq!(hub.clear_retained("a.x"), Some(1));
+        assert_eq!(hub.retained("a.x"), None);
+
+        let mut all = hub.subscribe("#").unwrap();
+        assert_eq!(all.try_recv().unwrap().into_payload(), 2);
+        assert_eq!(all.try_recv(), Err(TryRecvError::Empty));
+    }
+
+    #[test]
+    fn test_unsubscribe() {
+        let hub = PubSub::new();
+        let publisher = hub.clone();
+
+        let mut first = hub.subscribe("t").unwrap();
+        let second = hub.subscribe("t").unwrap();
+        assert_eq!(hub.subscriber_count(), 2);
+
+        second.unsubscribe();
+        assert_eq!(hub.subscriber_count(), 1);
+        assert_eq!(publisher.publish("t", 'a'), Ok(1));
+
+        let consumer = thread::spawn(move || {
+            let mut payloads = Vec::new();
+
+            while let Some(message) = first.recv_blocking() {
+                payloads.push(message.into_payload());
+            }
+
+            payloads
+        });
+
+        publisher.publish("t", 'b').unwrap();
+        drop(hub);
+        drop(publisher);
+
+        // Queued messages are still received after the hub is gone
+        assert_eq!(consumer.join().unwrap(), ['a', 'b']);
+    }
+}
@@ -0,0 +1,56 @@
+//! Errors of topics and patterns of pub/sub hubs.
+
+#[cfg(doc)]
+use super::PubSub;
+use core::{error::Error, fmt};
+
+/// Error returned by [`PubSub::publish`] and [`PubSub::subscribe`] if a topic
+/// (or pattern) isn't valid
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TopicError {
+    /// The topic is empty.
+    Empty,
+    /// A level of the topic is empty, e.g. in `metrics..cpu`.
+    EmptyLevel,
+    /// A topic to publish on contains a wildcard. Only patterns to subscribe
+    /// to may contain `*` and `#`.
+    Wildcard,
+    /// A wildcard isn't a level of its own, or `#` isn't the last level.
+    MisplacedWildcard,
+}
+
+impl fmt::Display for TopicError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TopicError::Empty => write!(f, "topic is empty"),
+            TopicError::EmptyLevel => write!(f, "topic contains an empty level"),
+            TopicError::Wildcard => write!(f, "publishing on a topic with a wildcard"),
+            TopicError::MisplacedWildcard => write!(f, "wildcard is misplaced in pattern"),
+        }
+    }
+}
+
+impl Error for TopicError {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::string::ToString;
+
+    #[test]
+    fn test_display() {
+        assert_eq!(TopicError::Empty.to_string(), "topic is empty");
+        assert_eq!(
+            TopicError::EmptyLevel.to_string(),
+            "topic contains an empty level"
+        );
+        assert_eq!(
+            TopicError::Wildcard.to_string(),
+            "publishing on a topic with a wildcard"
+        );
+        assert_eq!(
+            TopicError::MisplacedWildcard.to_string(),
+            "wildcard is misplaced in pattern"
+        );
+    }
+}
@@ -0,0 +1,553 @@
+#![forbid(unsafe_code)]
+//! # A channel where the first of many producers wins
+//!
+//! Every producer holds a (cloned) [`Sender`], the single [`Receiver`] gets
+//! the value of whichever delivers first. All later sends fail with
+//! [`SendError::Lost`], returning the value, so producers can tell that they
+//! lost the race. Waiting on [`Sender::closed`] lets a producer stop working
+//! as soon as another one won (or the receiver is gone).
+//!
+//! This fits hedged or parallel lookups, e.g. querying multiple replicas and
+//! taking the fastest answer.
+//!
+//! The receiver is a future resolving to the winning value, or to a
+//! [`RecvError`] if all senders are dropped without delivering one. The
+//! channel is backed by a `std::sync::Mutex`, doesn't contain any unsafe code
+//! and works with any async runtime.
+//!
+//! # Examples
+//! ```rust
+//! use std::{thread, time::Duration};
+//!
+//! let (tx, rx) = laika::race::channel();
+//!
+//! for (replica, latency) in [("a", 30), ("b", 0), ("c", 60)] {
+//!     let tx = tx.clone();
+//!
+//!     thread::spawn(move || {
+//!         thread::sleep(Duration::from_millis(latency));
+//!
+//!         if let Err(e) = tx.send(replica) {
+//!             assert!(e.is_lost());
+//!         }
+//!     });
+//! }
+//!
+//! drop(tx);
+//!
+//! assert_eq!(rx.recv_blocking(), Ok("b"));
+//! ```
+
+mod error;
+
+pub use error::{RecvError, SendError, TryRecvError};
+
+use alloc::sync::Arc;
+use core::{
+    future::Future,
+    mem,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+use std::{
+    collections::BTreeMap,
+    sync::{Condvar, Mutex, MutexGuard, PoisonError},
+};
+
+/// Value and bookkeeping of a channel, guarded by [`Shared::state`]
+#[derive(Debug)]
+struct State<T> {
+    /// Winning value until it's received
+    value: Option<T>,
+    /// Whether a sender won the race
+    decided: bool,
+    /// Number of existing [`Sender`]s
+    senders: usize,
+    /// Whether the receiver still exists
+    receiver: bool,
+    /// Waker of the receiver while it's waiting for a value
+    waker: Option<Waker>,
+    /// Wakers of senders waiting for the channel to close, keyed by sender or
+    /// future id
+    closed_wakers: BTreeMap<u64, Waker>,
+    /// Id of the next sender or `closed` future
+    next_id: u64,
+}
+
+impl<T> State<T> {
+    /// Returns a new sender or future id.
+    fn id(&mut self) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    /// Takes the winning value if it has been delivered.
+    fn take(&mut self) -> Result<T, TryRecvError> {
+        match self.value.take() {
+            Some(value) => Ok(value),
+            None if self.decided || self.senders == 0 => Err(TryRecvError::Closed),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+
+    /// Returns whether sending would fail.
+    fn is_closed(&self) -> bool {
+        self.decided || !self.receiver
+    }
+}
+
+/// State shared by all senders and the receiver of a channel
+#[derive(Debug)]
+struct Shared<T> {
+    /// Value and bookkeeping
+    state: Mutex<State<T>>,
+    /// Notified when a value is delivered or all senders are dropped, so that
+    /// a thread blocking in [`Receiver::recv_blocking`] wakes up.
+    condvar: Condvar,
+}
+
+impl<T> Shared<T> {
+    /// Locks the state.
+    fn lock(&self) -> MutexGuard<'_, State<T>> {
+        crate::lock(&self.state)
+    }
+
+    /// Wakes up the receiver and all senders waiting for the channel to close,
+    /// after unlocking `state`.
+    fn wake_all(&self, mut state: MutexGuard<'_, State<T>>) {
+        let waker = state.waker.take();
+        let wakers = mem::take(&mut state.closed_wakers);
+        drop(state);
+
+        for waker in waker.into_iter().chain(wakers.into_values()) {
+            waker.wake();
+        }
+
+        self.condvar.notify_one();
+    }
+}
+
+/// Sending side of a race channel
+///
+/// Can be cloned, each clone may take part in the race.
+#[derive(Debug)]
+pub struct Sender<T> {
+    /// State shared with all other senders and the receiver
+    shared: Arc<Shared<T>>,
+    /// Id of this sender, under which [`Sender::poll_closed`] registers its
+    /// waker
+    id: u64,
+}
+
+/// Future returned by [`Sender::closed`]
+///
+/// Resolves once another sender won the race or the [`Receiver`] has been
+/// dropped.
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Closed<'a, T> {
+    /// Sender waiting for the channel to close
+    sender: &'a Sender<T>,
+    /// Id of the waker once the future waits
+    id: Option<u64>,
+}
+
+/// Receiving side of a race channel
+///
+/// Is a future resolving to the winning value, or to a [`RecvError`] if all
+/// [`Sender`]s have been dropped without delivering one.
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Receiver<T> {
+    /// State shared with the senders
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Sender<T> {
+    /// Delivers the value to the receiver if no other sender did before,
+    /// consuming the sender.
+    ///
+    /// Returns the value in [`SendError::Lost`] if another sender won the race
+    /// or in [`SendError::Disconnected`] if the [`Receiver`] has been dropped.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use laika::race::SendError;
+    ///
+    /// let (tx, rx) = laika::race::channel();
+    /// let tx1 = tx.clone();
+    ///
+    /// tx.send(1).unwrap();
+    /// assert_eq!(tx1.send(2), Err(SendError::Lost(2)));
+    ///
+    /// assert_eq!(rx.recv_blocking(), Ok(1));
+    /// ```
+    pub fn send(self, value: T) -> Result<(), SendError<T>> {
+        let mut state = self.shared.lock();
+
+        if state.decided {
+            return Err(SendError::Lost(value));
+        }
+
+        if !state.receiver {
+            return Err(SendError::Disconnected(value));
+        }
+
+        state.value = Some(value);
+        state.decided = true;
+        self.shared.wake_all(state);
+
+        Ok(())
+    }
+
+    /// Returns whether another sender won the race.
+    pub fn has_lost(&self) -> bool {
+        self.shared.lock().decided
+    }
+
+    /// Returns whether sending would fail, as another sender won the race or
+    /// the [`Receiver`] has been dropped.
+    pub fn is_closed(&self) -> bool {
+        self.shared.lock().is_closed()
+    }
+
+    /// Polls whether sending would fail, registering the waker of `cx` if it
+    /// wouldn't yet. See [`Sender::closed`].
+    ///
+    /// Only the waker of the last call is kept, so tasks sharing a sender use
+    /// [`Sender::closed`] instead.
+    pub fn poll_closed(&self, cx: &mut Context<'_>) -> Poll<()> {
+        self.poll_closed_as(&mut Some(self.id), cx)
+    }
+
+    /// Polls whether sending would fail, registering the waker of `cx` under
+    /// `id` if it wouldn't yet. A new id is assigned if `id` is [`None`].
+    fn poll_closed_as(&self, id: &mut Option<u64>, cx: &Context<'_>) -> Poll<()> {
+        let mut state = self.shared.lock();
+
+        if state.is_closed() {
+            return Poll::Ready(());
+        }
+
+        let id = *id.get_or_insert_with(|| state.id());
+
+        if !state
+            .closed_wakers
+            .get(&id)
+            .is_some_and(|waker| waker.will_wake(cx.waker()))
+        {
+            state.closed_wakers.insert(id, cx.waker().clone());
+        }
+
+        Poll::Pending
+    }
+
+    /// Waits asynchronously until another sender won the race or the
+    /// [`Receiver`] has been dropped, so a producer can abort its work.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # tokio::runtime::Builder::new_current_thread()
+    /// #     .build()
+    /// #     .unwrap()
+    /// #     .block_on(async {
+    /// let (tx, rx) = laika::race::channel();
+    /// let tx1 = tx.clone();
+    ///
+    /// let loser = tokio::spawn(async move {
+    ///     tx1.closed().await;
+    ///     tx1.has_lost()
+    /// });
+    ///
+    /// tx.send(1).unwrap();
+    ///
+    /// assert!(loser.await.unwrap());
+    /// assert_eq!(rx.await, Ok(1));
+    /// # });
+    /// ```
+    pub fn closed(&self) -> Closed<'_, T> {
+        Closed {
+            sender: self,
+            id: None,
+        }
+    }
+}
+
+impl<T> Future for Closed<'_, T> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let this = self.get_mut();
+
+        this.sender.poll_closed_as(&mut this.id, cx)
+    }
+}
+
+/// Withdraws the waker, so the channel doesn't keep it.
+impl<T> Drop for Closed<'_, T> {
+    fn drop(&mut self) {
+        if let Some(id) = self.id {
+            self.sender.shared.lock().closed_wakers.remove(&id);
+        }
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        let mut state = self.shared.lock();
+        state.senders += 1;
+
+        Sender {
+            shared: Arc::clone(&self.shared),
+            id: state.id(),
+        }
+    }
+}
+
+/// Wakes up the receiver when the last [`Sender`] is dropped without a value
+/// having been delivered.
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.senders -= 1;
+        state.closed_wakers.remove(&self.id);
+
+        if state.senders == 0 && !state.decided {
+            self.shared.wake_all(state);
+        }
+    }
+}
+
+impl<T> Receiver<T> {
+    /// Takes the winning value if it has been delivered. This function is
+    /// **non-blocking**.
+    ///
+    /// Returns [`TryRecvError::Empty`] if no value has been delivered yet or
+    /// [`TryRecvError::Closed`] if all [`Sender`]s have been dropped without
+    /// delivering one (or the value has already been taken).
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        self.shared.lock().take()
+    }
+
+    /// Polls for the winning value, registering the waker of `cx` if it hasn't
+    /// been delivered yet.
+    pub fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Result<T, RecvError>> {
+        let mut state = self.shared.lock();
+
+        match state.take() {
+            Ok(value) => Poll::Ready(Ok(value)),
+            Err(TryRecvError::Closed) => Poll::Ready(Err(RecvError)),
+            Err(TryRecvError::Empty) => {
+                if !state
+                    .waker
+                    .as_ref()
+                    .is_some_and(|waker| waker.will_wake(cx.waker()))
+                {
+                    state.waker = Some(cx.waker().clone());
+                }
+
+                Poll::Pending
+            }
+        }
+    }
+
+    /// Receives the winning value, blocking the current thread until it has
+    /// been delivered.
+    ///
+    /// Returns a [`RecvError`] if all [`Sender`]s have been dropped without
+    /// delivering a value.
+    pub fn recv_blocking(self) -> Result<T, RecvError> {
+        let mut state = self.shared.lock();
+
+        loop {
+            match state.take() {
+                Ok(value) => return Ok(value),
+                Err(TryRecvError::Closed) => return Err(RecvError),
+                Err(TryRecvError::Empty) => {
+                    state = self
+                        .shared
+                        .condvar
+                        .wait(state)
+                        .unwrap_or_else(PoisonError::into_inner);
+                }
+            }
+        }
+    }
+}
+
+impl<T> Future for Receiver<T> {
+    type Output = Result<T, RecvError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.get_mut().poll_recv(cx)
+    }
+}
+
+/// Closes the channel for the senders, waking up the ones waiting on
+/// [`Sender::closed`], and drops a value that has been delivered but not
+/// received.
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.receiver = false;
+
+        // The value is dropped after unlocking, in case its `Drop` uses the
+        // channel.
+        let value = state.value.take();
+        self.shared.wake_all(state);
+        drop(value);
+    }
+}
+
+/// Creates a race channel. Clone the [`Sender`] for every producer taking
+/// part in the race.
+pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            value: None,
+            decided: false,
+            senders: 1,
+            receiver: true,
+            waker: None,
+            closed_wakers: BTreeMap::new(),
+            next_id: 1,
+        }),
+        condvar: Condvar::new(),
+    });
+
+    let receiver = Receiver {
+        shared: Arc::clone(&shared),
+    };
+
+    (Sender { shared, id: 0 }, receiver)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::pin::pin;
+    use std::{thread, time::Duration, vec::Vec};
+
+    #[test]
+    fn test_first_wins() {
+        let (tx, mut rx) = channel();
+        let tx1 = tx.clone();
+        let tx2 = tx.clone();
+        let mut cx = Context::from_waker(Waker::noop());
+
+        assert_eq!(Pin::new(&mut rx).poll(&mut cx), Poll::Pending);
+        assert!(!tx1.has_lost());
+
+        tx.send(1).unwrap();
+        assert!(tx1.has_lost());
+        assert!(tx2.is_closed());
+        assert_eq!(tx1.send(2), Err(SendError::Lost(2)));
+
+        assert_eq!(Pin::new(&mut rx).poll(&mut cx), Poll::Ready(Ok(1)));
+        assert_eq!(Pin::new(&mut rx).poll(&mut cx), Poll::Ready(Err(RecvError)));
+
+        // Losing is still reported after the receiver is gone
+        drop(rx);
+        assert_eq!(tx2.send(3), Err(SendError::Lost(3)));
+    }
+
+    #[test]
+    fn test_closed() {
+        let (tx, rx) = channel::<u8>();
+        let tx1 = tx.clone();
+        let mut cx = Context::from_waker(Waker::noop());
+
+        let mut closed = pin!(tx1.closed());
+        assert_eq!(closed.as_mut().poll(&mut cx), Poll::Pending);
+        assert_eq!(tx.shared.lock().closed_wakers.len(), 1);
+
+        drop(rx);
+        assert!(tx.shared.lock().closed_wakers.is_empty());
+        assert_eq!(closed.as_mut().poll(&mut cx), Poll::Ready(()));
+        assert!(!tx1.has_lost());
+        assert_eq!(tx.send(1), Err(SendError::Disconnected(1)));
+    }
+
+    #[test]
+    fn test_shared_sender() {
+        let (tx, _rx) = channel();
+        let tx1 = tx.clone();
+        let mut cx = Context::from_waker(Waker::noop());
+
+        {
+            let mut closed = pin!(tx1.closed());
+            let mut closed1 = pin!(tx1.closed());
+
+            assert_eq!(closed.as_mut().poll(&mut cx), Poll::Pending);
+            assert_eq!(closed1.as_mut().poll(&mut cx), Poll::Pending);
+
+            // Each future waits with a waker of its own
+            assert_eq!(tx.shared.lock().closed_wakers.len(), 2);
+
+            tx.send(1).unwrap();
+            assert_eq!(closed.as_mut().poll(&mut cx), Poll::Ready(()));
+            assert_eq!(closed1.as_mut().poll(&mut cx), Poll::Ready(()));
+        }
+
+        let mut closed = pin!(tx1.closed());
+        assert_eq!(closed.as_mut().poll(&mut cx), Poll::Ready(()));
+    }
+
+    #[test]
+    fn test_all_senders_dropped() {
+        let (tx, mut rx) = channel::<u8>();
+        let tx1 = tx.clone();
+
+        drop(tx);
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+
+        drop(tx1);
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Closed));
+        assert_eq!(rx.recv_blocking(), Err(RecvError));
+    }
+
+    #[test]
+    fn test_recv_blocking() {
+        let (tx, rx) = channel();
+
+        let producers = (0..4)
+            .map(|id| {
+                let tx = tx.clone();
+
+                thread::spawn(move || {
+                    thread::sleep(Duration::from_millis(id * 20));
+                    tx.send(id)
+                })
+            })
+            .collect::<Vec<_>>();
+
+        drop(tx);
+
+        let winner = rx.recv_blocking().unwrap();
+        let lost = producers
+            .into_iter()
+            .map(|producer| producer.join().unwrap())
+            .filter(|result| result.is_err_and(|e| e.is_lost()))
+            .count();
+
+        assert_eq!(lost, 3);
+        assert!(winner < 4);
+    }
+
+    #[tokio::test]
+    async fn test_await() {
+        let (tx, rx) = channel();
+        let tx1 = tx.clone();
+
+        let loser = tokio::spawn(async move {
+            tx1.closed().await;
+            tx1.send(2)
+        });
+
+        tokio::spawn(async move { tx.send(1).unwrap() });
+
+        assert_eq!(rx.await, Ok(1));
+        assert_eq!(loser.await.unwrap(), Err(SendError::Lost(2)));
+    }
+}
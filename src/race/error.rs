@@ -0,0 +1,100 @@
+//! Errors of sending on and receiving from race channels.
+
+#[cfg(doc)]
+use super::{Receiver, Sender};
+use core::{error::Error, fmt};
+
+/// Error returned by [`Sender::send`] if the value couldn't be delivered.
+/// Contains the value, so it can be reclaimed.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SendError<T> {
+    /// Another [`Sender`] delivered its value first, so this one lost the
+    /// race.
+    Lost(T),
+    /// The [`Receiver`] has been dropped before any value was delivered.
+    Disconnected(T),
+}
+
+impl<T> SendError<T> {
+    /// Returns the value that couldn't be delivered.
+    pub fn into_inner(self) -> T {
+        match self {
+            SendError::Lost(value) | SendError::Disconnected(value) => value,
+        }
+    }
+
+    /// Returns whether another [`Sender`] won the race.
+    pub fn is_lost(&self) -> bool {
+        matches!(self, SendError::Lost(_))
+    }
+}
+
+debug_without_value!(SendError { Lost, Disconnected });
+
+impl<T> fmt::Display for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SendError::Lost(_) => write!(f, "another sender delivered a value first"),
+            SendError::Disconnected(_) => write!(f, "sending on a channel without a receiver"),
+        }
+    }
+}
+
+impl<T> Error for SendError<T> {}
+
+/// Error returned when awaiting the [`Receiver`] (or by
+/// [`Receiver::recv_blocking`]) if all [`Sender`]s have been dropped without
+/// delivering a value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RecvError;
+
+impl fmt::Display for RecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "channel closed without a value being sent")
+    }
+}
+
+impl Error for RecvError {}
+
+/// Error returned by [`Receiver::try_recv`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TryRecvError {
+    /// No value has been delivered yet, but a [`Sender`] still exists.
+    Empty,
+    /// All [`Sender`]s have been dropped without delivering a value or the
+    /// value has already been received.
+    Closed,
+}
+
+impl fmt::Display for TryRecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TryRecvError::Empty => write!(f, "no value has been sent yet"),
+            TryRecvError::Closed => write!(f, "channel closed without a value being sent"),
+        }
+    }
+}
+
+impl Error for TryRecvError {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::string::ToString;
+
+    #[test]
+    fn test_display() {
+        assert_eq!(
+            SendError::Lost(1).to_string(),
+            "another sender delivered a value first"
+        );
+        assert_eq!(
+            SendError::Disconnected(1).to_string(),
+            "sending on a channel without a receiver"
+        );
+        assert_eq!(
+            TryRecvError::Empty.to_string(),
+            "no value has been sent yet"
+        );
+    }
+}
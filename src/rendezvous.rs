@@ -0,0 +1,721 @@
+#![forbid(unsafe_code)]
+//! # A zero-capacity channel where senders and receivers meet
+//!
+//! A rendezvous channel has no buffer: [`Sender::send`] waits until a
+//! [`Receiver`] took the value, and [`Receiver::recv`] waits until a sender
+//! hands one over. Completing a send therefore means the value has been
+//! received, which makes the channel a strict handoff and a synchronization
+//! point between tasks or threads.
+//!
+//! Both senders and receivers can be cloned. Waiting senders are served in
+//! the order they started sending, each value is received exactly once.
+//!
+//! The channel is backed by a `std::sync::Mutex`, doesn't contain any unsafe
+//! code and works with any async runtime.
+//!
+//! # Examples
+//! ```rust
+//! use std::thread;
+//!
+//! let (tx, rx) = laika::rendezvous::channel();
+//!
+//! let thread = thread::spawn(move || {
+//!     // Returns once the main thread received the value
+//!     tx.send_blocking("ping").unwrap();
+//! });
+//!
+//! assert_eq!(rx.recv_blocking(), Some("ping"));
+//! thread.join().unwrap();
+//! ```
+
+mod error;
+
+pub use error::{SendError, TryRecvError};
+
+use crate::park;
+use alloc::{collections::VecDeque, sync::Arc};
+use core::{
+    future::Future,
+    mem,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+use std::{
+    collections::BTreeMap,
+    sync::{Condvar, Mutex, MutexGuard, PoisonError},
+};
+
+/// Value a sender is waiting to hand over
+#[derive(Debug)]
+struct Offer<T> {
+    /// Ticket of the offer, identifying it for its sender
+    ticket: u64,
+    /// Offered value
+    value: T,
+    /// Waker of the sender (unparking it if it's blocking), woken up once the
+    /// offer is taken
+    waker: Waker,
+}
+
+/// Offers and bookkeeping of a channel, guarded by [`Shared::state`]
+#[derive(Debug)]
+struct State<T> {
+    /// Offers of waiting senders, oldest first
+    offers: VecDeque<Offer<T>>,
+    /// Number of existing [`Sender`]s
+    senders: usize,
+    /// Number of existing [`Receiver`]s
+    receivers: usize,
+    /// Wakers of receivers waiting for an offer, keyed by receiver id or
+    /// ticket of the `recv` future
+    wakers: BTreeMap<u64, Waker>,
+    /// Next receiver id or ticket of an offer or `recv` future
+    next_id: u64,
+}
+
+impl<T> State<T> {
+    /// Returns a new receiver id or ticket.
+    fn id(&mut self) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    /// Returns the position of the offer with `ticket`, if it hasn't been
+    /// taken yet.
+    fn position(&self, ticket: u64) -> Option<usize> {
+        self.offers.iter().position(|offer| offer.ticket == ticket)
+    }
+}
+
+/// State shared by all senders and receivers of a channel
+#[derive(Debug)]
+struct Shared<T> {
+    /// Offers and bookkeeping
+    state: Mutex<State<T>>,
+    /// Notified when a value is offered or all senders are dropped, so that
+    /// threads blocking in [`Receiver::recv_blocking`] wake up.
+    offered: Condvar,
+}
+
+impl<T> Shared<T> {
+    /// Locks the state.
+    fn lock(&self) -> MutexGuard<'_, State<T>> {
+        crate::lock(&self.state)
+    }
+
+    /// Queues an offer of `value` and wakes up a waiting receiver after
+    /// unlocking `state`. Returns the ticket of the offer.
+    fn offer(&self, mut state: MutexGuard<'_, State<T>>, value: T, waker: Waker) -> u64 {
+        let ticket = state.id();
+        state.offers.push_back(Offer {
+            ticket,
+            value,
+            waker,
+        });
+
+        self.wake_receiver(state);
+
+        ticket
+    }
+
+    /// Wakes up one waiting receiver after unlocking `state`, as an offer can
+    /// only be taken by one of them.
+    ///
+    /// A woken up receiver that's dropped before taking the offer passes the
+    /// wake-up on to the next one, see [`Shared::withdraw_receiver`].
+    fn wake_receiver(&self, mut state: MutexGuard<'_, State<T>>) {
+        let waker = state.wakers.pop_first();
+        drop(state);
+
+        if let Some((_, waker)) = waker {
+            waker.wake();
+        }
+
+        self.offered.notify_one();
+    }
+
+    /// Removes the waker of a receiver that stops waiting under `ticket`. If
+    /// it has been taken already, the receiver has been woken up for an offer
+    /// it won't take, so the next waiting receiver is woken up instead.
+    fn withdraw_receiver(&self, ticket: u64) {
+        let mut state = self.lock();
+
+        if state.wakers.remove(&ticket).is_none() && !state.offers.is_empty() {
+            self.wake_receiver(state);
+        }
+    }
+
+    /// Takes the oldest offer, waking up its sender after unlocking `state`.
+    fn take<'a>(&self, mut state: MutexGuard<'a, State<T>>) -> Result<T, MutexGuard<'a, State<T>>> {
+        let Some(offer) = state.offers.pop_front() else {
+            return Err(state);
+        };
+
+        drop(state);
+        offer.waker.wake();
+
+        Ok(offer.value)
+    }
+
+    /// Withdraws the offer with `ticket`, returning its value if it hasn't
+    /// been taken yet.
+    fn withdraw(&self, ticket: u64) -> Option<T> {
+        let mut state = self.lock();
+        let position = state.position(ticket)?;
+
+        state.offers.remove(position).map(|offer| offer.value)
+    }
+}
+
+/// Sending side of a rendezvous channel
+///
+/// Can be cloned to send from multiple producers. The channel is closed once
+/// all senders have been dropped.
+#[derive(Debug)]
+pub struct Sender<T> {
+    /// State shared with all other senders and receivers
+    shared: Arc<Shared<T>>,
+}
+
+/// Receiving side of a rendezvous channel
+///
+/// Can be cloned to receive from multiple consumers. Every value is received
+/// by exactly one of them.
+#[derive(Debug)]
+pub struct Receiver<T> {
+    /// State shared with all senders and other receivers
+    shared: Arc<Shared<T>>,
+    /// Id of this receiver, under which [`Receiver::poll_recv`] registers its
+    /// waker
+    id: u64,
+}
+
+/// Future returned by [`Receiver::recv`]
+///
+/// Resolves to the value of a sender, or to [`None`] once all senders have
+/// been dropped.
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Recv<'a, T> {
+    /// Receiver the value is received with
+    receiver: &'a Receiver<T>,
+    /// Ticket of the waker once the future waits for an offer
+    ticket: Option<u64>,
+}
+
+/// Future returned by [`Sender::send`]
+///
+/// Resolves once a [`Receiver`] took the value. Dropping it before withdraws
+/// the value, so it's never received.
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Handoff<'a, T> {
+    /// Sender the value is sent with
+    sender: &'a Sender<T>,
+    /// Value until it has been offered
+    value: Option<T>,
+    /// Ticket of the offer once the value has been offered
+    ticket: Option<u64>,
+}
+
+impl<T> Unpin for Handoff<'_, T> {}
+
+impl<T> Future for Handoff<'_, T> {
+    type Output = Result<(), SendError<T>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let mut state = this.sender.shared.lock();
+
+        let Some(ticket) = this.ticket else {
+            let Some(value) = this.value.take() else {
+                panic!("`Handoff` polled after completion");
+            };
+
+            if state.receivers == 0 {
+                return Poll::Ready(Err(SendError(value)));
+            }
+
+            let ticket = this.sender.shared.offer(state, value, cx.waker().clone());
+            this.ticket = Some(ticket);
+
+            return Poll::Pending;
+        };
+
+        let Some(position) = state.position(ticket) else {
+            this.ticket = None;
+            return Poll::Ready(Ok(()));
+        };
+
+        if state.receivers == 0 {
+            this.ticket = None;
+
+            return match state.offers.remove(position) {
+                Some(offer) => Poll::Ready(Err(SendError(offer.value))),
+                None => unreachable!("The position has just been looked up"),
+            };
+        }
+
+        let offer = &mut state.offers[position];
+
+        if !offer.waker.will_wake(cx.waker()) {
+            offer.waker = cx.waker().clone();
+        }
+
+        Poll::Pending
+    }
+}
+
+/// Withdraws the value if it hasn't been received yet.
+impl<T> Drop for Handoff<'_, T> {
+    fn drop(&mut self) {
+        if let Some(ticket) = self.ticket {
+            self.sender.shared.withdraw(ticket);
+        }
+    }
+}
+
+impl<T> Sender<T> {
+    /// Hands a value over to a receiver, waiting asynchronously until one took
+    /// it.
+    ///
+    /// Resolves to a [`SendError`] containing the value if all [`Receiver`]s
+    /// have been dropped before one took it.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # tokio::runtime::Builder::new_current_thread()
+    /// #     .build()
+    /// #     .unwrap()
+    /// #     .block_on(async {
+    /// let (tx, rx) = laika::rendezvous::channel();
+    ///
+    /// let receiver = tokio::spawn(async move { rx.recv().await });
+    ///
+    /// // Resolves once the spawned task received the value
+    /// tx.send(12).await.unwrap();
+    ///
+    /// assert_eq!(receiver.await.unwrap(), Some(12));
+    /// # });
+    /// ```
+    pub fn send(&self, value: T) -> Handoff<'_, T> {
+        Handoff {
+            sender: self,
+            value: Some(value),
+            ticket: None,
+        }
+    }
+
+    /// Hands a value over to a receiver, blocking the current thread until
+    /// one took it. See [`Sender::send`].
+    pub fn send_blocking(&self, value: T) -> Result<(), SendError<T>> {
+        let mut handoff = self.send(value);
+
+        park::block_on(|cx| Pin::new(&mut handoff).poll(cx))
+    }
+
+    /// Returns whether all [`Receiver`]s have been dropped, so sending would
+    /// fail.
+    pub fn is_closed(&self) -> bool {
+        self.shared.lock().receivers == 0
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.shared.lock().senders += 1;
+
+        Sender {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+/// Closes the channel when the last [`Sender`] is dropped, waking up all
+/// waiting receivers.
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.senders -= 1;
+
+        if state.senders > 0 {
+            return;
+        }
+
+        let wakers = mem::take(&mut state.wakers);
+        drop(state);
+
+        for waker in wakers.into_values() {
+            waker.wake();
+        }
+
+        self.shared.offered.notify_all();
+    }
+}
+
+impl<T> Receiver<T> {
+    /// Takes the value of a waiting sender if there is one. This function is
+    /// **non-blocking**.
+    ///
+    /// Returns [`TryRecvError::Empty`] if no sender is waiting or
+    /// [`TryRecvError::Disconnected`] if all senders have been dropped.
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        match self.shared.take(self.shared.lock()) {
+            Ok(value) => Ok(value),
+            Err(state) if state.senders == 0 => Err(TryRecvError::Disconnected),
+            Err(_) => Err(TryRecvError::Empty),
+        }
+    }
+
+    /// Polls for the value of a waiting sender, registering the waker of `cx`
+    /// if there is none yet. Resolves to [`None`] once all senders have been
+    /// dropped.
+    ///
+    /// Only the waker of the last call is kept, so tasks sharing a receiver
+    /// use [`Receiver::recv`] instead.
+    pub fn poll_recv(&self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        self.poll_take(&mut Some(self.id), cx)
+    }
+
+    /// Polls for the value of a waiting sender, registering the waker of `cx`
+    /// under `ticket` if there is none yet. A new ticket is assigned if
+    /// `ticket` is [`None`].
+    fn poll_take(&self, ticket: &mut Option<u64>, cx: &Context<'_>) -> Poll<Option<T>> {
+        let mut state = self.shared.lock();
+        let previous = ticket.and_then(|ticket| state.wakers.remove(&ticket));
+
+        let mut state = match self.shared.take(state) {
+            Ok(value) => return Poll::Ready(Some(value)),
+            Err(state) if state.senders == 0 => return Poll::Ready(None),
+            Err(state) => state,
+        };
+
+        let ticket = *ticket.get_or_insert_with(|| state.id());
+        let waker = match previous {
+            Some(waker) if waker.will_wake(cx.waker()) => waker,
+            _ => cx.waker().clone(),
+        };
+
+        state.wakers.insert(ticket, waker);
+
+        Poll::Pending
+    }
+
+    /// Takes the value of a sender, waiting asynchronously until one hands
+    /// one over. Resolves to [`None`] once all senders have been dropped.
+    pub fn recv(&self) -> Recv<'_, T> {
+        Recv {
+            receiver: self,
+            ticket: None,
+        }
+    }
+
+    /// Takes the value of a sender, blocking the current thread until one
+    /// hands one over. See [`Receiver::recv`].
+    pub fn recv_blocking(&self) -> Option<T> {
+        let mut state = self.shared.lock();
+
+        loop {
+            state = match self.shared.take(state) {
+                Ok(value) => return Some(value),
+                Err(state) if state.senders == 0 => return None,
+                Err(state) => self
+                    .shared
+                    .offered
+                    .wait(state)
+                    .unwrap_or_else(PoisonError::into_inner),
+            };
+        }
+    }
+}
+
+impl<T> Future for Recv<'_, T> {
+    type Output = Option<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let poll = this.receiver.poll_take(&mut this.ticket, cx);
+
+        if poll.is_ready() {
+            this.ticket = None;
+        }
+
+        poll
+    }
+}
+
+/// Withdraws the waker of a pending future, so the channel doesn't keep it.
+/// If the future has been woken up already, the next waiting receiver is
+/// woken up instead.
+impl<T> Drop for Recv<'_, T> {
+    fn drop(&mut self) {
+        if let Some(ticket) = self.ticket {
+            self.receiver.shared.withdraw_receiver(ticket);
+        }
+    }
+}
+
+impl<T> Clone for Receiver<T> {
+    fn clone(&self) -> Self {
+        let mut state = self.shared.lock();
+        state.receivers += 1;
+
+        Receiver {
+            shared: Arc::clone(&self.shared),
+            id: state.id(),
+        }
+    }
+}
+
+/// Closes the channel for senders when the last [`Receiver`] is dropped,
+/// waking up waiting ones so they get their values back. Otherwise, a wake-up
+/// this receiver got is passed on.
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.receivers -= 1;
+
+        if state.receivers > 0 {
+            drop(state);
+            self.shared.withdraw_receiver(self.id);
+
+            return;
+        }
+
+        let wakers = state
+            .offers
+            .iter()
+            .map(|offer| offer.waker.clone())
+            .collect::<alloc::vec::Vec<_>>();
+        drop(state);
+
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+}
+
+/// Creates a rendezvous channel.
+///
+/// # Examples
+/// ```rust
+/// use laika::rendezvous::TryRecvError;
+///
+/// let (tx, rx) = laika::rendezvous::channel::<u8>();
+///
+/// // Nobody is sending, so there's nothing to take
+/// assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+///
+/// drop(tx);
+/// assert_eq!(rx.try_recv(), Err(TryRecvError::Disconnected));
+/// ```
+pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            offers: VecDeque::new(),
+            senders: 1,
+            receivers: 1,
+            wakers: BTreeMap::new(),
+            next_id: 1,
+        }),
+        offered: Condvar::new(),
+    });
+
+    let receiver = Receiver {
+        shared: Arc::clone(&shared),
+        id: 0,
+    };
+
+    (Sender { shared }, receiver)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::pin::pin;
+    use std::{thread, time::Duration, vec::Vec};
+
+    #[test]
+    fn test_handoff() {
+        let (tx, rx) = channel();
+        let mut cx = Context::from_waker(Waker::noop());
+
+        let mut send = pin!(tx.send(1));
+        let mut send1 = pin!(tx.send(2));
+
+        assert_eq!(send.as_mut().poll(&mut cx), Poll::Pending);
+        assert_eq!(send1.as_mut().poll(&mut cx), Poll::Pending);
+        assert_eq!(send.as_mut().poll(&mut cx), Poll::Pending);
+
+        // Senders are served in order and only complete once received
+        assert_eq!(rx.try_recv(), Ok(1));
+        assert_eq!(send1.as_mut().poll(&mut cx), Poll::Pending);
+        assert_eq!(send.as_mut().poll(&mut cx), Poll::Ready(Ok(())));
+
+        assert_eq!(rx.poll_recv(&mut cx), Poll::Ready(Some(2)));
+        assert_eq!(send1.as_mut().poll(&mut cx), Poll::Ready(Ok(())));
+
+        assert_eq!(rx.poll_recv(&mut cx), Poll::Pending);
+        assert_eq!(tx.shared.lock().wakers.len(), 1);
+    }
+
+    #[test]
+    fn test_shared_receiver() {
+        let (tx, rx) = channel();
+        let mut cx = Context::from_waker(Waker::noop());
+
+        {
+            let mut recv = pin!(rx.recv());
+            let mut recv1 = pin!(rx.recv());
+
+            assert_eq!(recv.as_mut().poll(&mut cx), Poll::Pending);
+            assert_eq!(recv1.as_mut().poll(&mut cx), Poll::Pending);
+
+            // Each future waits with a waker of its own
+            assert_eq!(tx.shared.lock().wakers.len(), 2);
+
+            let mut send = pin!(tx.send(1));
+            assert_eq!(send.as_mut().poll(&mut cx), Poll::Pending);
+            assert_eq!(recv1.as_mut().poll(&mut cx), Poll::Ready(Some(1)));
+            assert_eq!(recv.as_mut().poll(&mut cx), Poll::Pending);
+            assert_eq!(send.as_mut().poll(&mut cx), Poll::Ready(Ok(())));
+        }
+
+        assert!(tx.shared.lock().wakers.is_empty());
+    }
+
+    #[test]
+    fn test_pass_on() {
+        let (tx, rx) = channel();
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut recv1 = pin!(rx.recv());
+        let mut send = pin!(tx.send(1));
+
+        {
+            let mut recv = pin!(rx.recv());
+
+            assert_eq!(recv.as_mut().poll(&mut cx), Poll::Pending);
+            assert_eq!(recv1.as_mut().poll(&mut cx), Poll::Pending);
+
+            // A single offer wakes up a single receiver
+            assert_eq!(send.as_mut().poll(&mut cx), Poll::Pending);
+            assert_eq!(tx.shared.lock().wakers.len(), 1);
+        }
+
+        // The woken up future has been dropped, so the next one is woken up
+        assert!(tx.shared.lock().wakers.is_empty());
+        assert_eq!(recv1.as_mut().poll(&mut cx), Poll::Ready(Some(1)));
+        assert_eq!(send.as_mut().poll(&mut cx), Poll::Ready(Ok(())));
+    }
+
+    #[test]
+    fn test_withdraw() {
+        let (tx, rx) = channel();
+        let mut cx = Context::from_waker(Waker::noop());
+
+        {
+            let mut send = pin!(tx.send(1));
+            assert_eq!(send.as_mut().poll(&mut cx), Poll::Pending);
+        }
+
+        // The dropped send withdrew its value
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+
+        let mut send = pin!(tx.send(2));
+        assert_eq!(send.as_mut().poll(&mut cx), Poll::Pending);
+
+        drop(rx);
+        assert!(tx.is_closed());
+        assert_eq!(send.as_mut().poll(&mut cx), Poll::Ready(Err(SendError(2))));
+        assert_eq!(tx.send_blocking(3), Err(SendError(3)));
+    }
+
+    #[test]
+    fn test_blocking() {
+        let (tx, rx) = channel();
+
+        let receiver = thread::spawn(move || {
+            let mut values = Vec::new();
+
+            while let Some(value) = rx.recv_blocking() {
+                values.push(value);
+            }
+
+            values
+        });
+
+        let senders = (0..2)
+            .map(|id| {
+                let tx = tx.clone();
+
+                thread::spawn(move || {
+                    for value in 0..10 {
+                        tx.send_blocking(id * 10 + value).unwrap();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        drop(tx);
+
+        for sender in senders {
+            sender.join().unwrap();
+        }
+
+        let mut values = receiver.join().unwrap();
+        values.sort();
+        assert_eq!(values, (0..20).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_sender_waits() {
+        let (tx, rx) = channel();
+
+        let sender = thread::spawn(move || tx.send_blocking(1));
+
+        thread::sleep(Duration::from_millis(10));
+        assert!(!sender.is_finished());
+
+        assert_eq!(rx.recv_blocking(), Some(1));
+        assert_eq!(sender.join().unwrap(), Ok(()));
+    }
+
+    #[tokio::test]
+    async fn test_async() {
+        let (tx, rx) = channel();
+
+        let receivers = (0..2)
+            .map(|_| {
+                let rx = rx.clone();
+
+                tokio::spawn(async move {
+                    let mut values = Vec::new();
+
+                    while let Some(value) = rx.recv().await {
+                        values.push(value);
+                    }
+
+                    values
+                })
+            })
+            .collect::<Vec<_>>();
+
+        drop(rx);
+
+        for value in 0..10 {
+            tx.send(value).await.unwrap();
+        }
+
+        drop(tx);
+
+        let mut values = Vec::new();
+
+        for receiver in receivers {
+            values.extend(receiver.await.unwrap());
+        }
+
+        values.sort();
+        assert_eq!(values, (0..10).collect::<Vec<_>>());
+    }
+}
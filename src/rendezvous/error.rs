@@ -0,0 +1,63 @@
+//! Errors of sending on and receiving from rendezvous channels.
+
+#[cfg(doc)]
+use super::{Receiver, Sender};
+use core::{error::Error, fmt};
+
+/// Error returned by [`Sender::send`] and [`Sender::send_blocking`] if all
+/// [`Receiver`]s have been dropped before one took the value. Contains the
+/// value, so it can be reclaimed.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct SendError<T>(pub T);
+
+impl<T> SendError<T> {
+    /// Returns the value that couldn't be sent.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+debug_without_value!(SendError);
+
+impl<T> fmt::Display for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "sending on a channel without receivers")
+    }
+}
+
+impl<T> Error for SendError<T> {}
+
+/// Error returned by [`Receiver::try_recv`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TryRecvError {
+    /// No sender is waiting to hand over a value, but a sender still exists.
+    Empty,
+    /// All senders have been dropped, so no value will ever be received.
+    Disconnected,
+}
+
+impl fmt::Display for TryRecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TryRecvError::Empty => write!(f, "no sender is waiting"),
+            TryRecvError::Disconnected => write!(f, "channel closed"),
+        }
+    }
+}
+
+impl Error for TryRecvError {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::string::ToString;
+
+    #[test]
+    fn test_display() {
+        assert_eq!(
+            SendError(1).to_string(),
+            "sending on a channel without receivers"
+        );
+        assert_eq!(TryRecvError::Disconnected.to_string(), "channel closed");
+    }
+}
@@ -0,0 +1,599 @@
+#![forbid(unsafe_code)]
+//! # A broadcast channel that replays its history to late subscribers
+//!
+//! Like [`broadcast`](crate::broadcast), every value sent by a [`Sender`] is
+//! received by every [`Receiver`]. Additionally, the channel retains the last
+//! `capacity` values (or all of them, see [`unbounded`]) and replays them to
+//! receivers created later with [`Sender::subscribe`], so components that
+//! start late still see the history they missed.
+//!
+//! Sending never blocks and succeeds even if there are no receivers, as the
+//! value is kept for future ones. Once the history is full, the oldest value
+//! is dropped from it; receivers that haven't received it yet get
+//! [`RecvError::Lagged`] with the number of values they missed and continue
+//! with the oldest retained value.
+//!
+//! Once all senders have been dropped, receivers still get the retained
+//! values and [`RecvError::Closed`] afterwards. The channel is backed by a
+//! `std::sync::Mutex` and doesn't contain any unsafe code. The async functions
+//! work with any runtime.
+//!
+//! # Examples
+//! ```rust
+//! use laika::replay::RecvError;
+//!
+//! let (tx, rx) = laika::replay::channel(2);
+//! drop(rx);
+//!
+//! tx.send("connecting");
+//! tx.send("connected");
+//! tx.send("ready");
+//!
+//! // A late subscriber gets the last 2 values
+//! let mut rx = tx.subscribe();
+//! assert_eq!(rx.recv_blocking(), Ok("connected"));
+//! assert_eq!(rx.recv_blocking(), Ok("ready"));
+//!
+//! drop(tx);
+//! assert_eq!(rx.recv_blocking(), Err(RecvError::Closed));
+//! ```
+
+mod error;
+
+pub use error::{RecvError, TryRecvError};
+
+use alloc::{collections::VecDeque, sync::Arc};
+use core::{
+    future, mem,
+    task::{Context, Poll, Waker},
+};
+use std::{
+    collections::BTreeMap,
+    sync::{Condvar, Mutex, MutexGuard, PoisonError},
+};
+
+/// History and bookkeeping of a channel, guarded by [`Shared::state`]
+#[derive(Debug)]
+struct State<T> {
+    /// Retained values, oldest first
+    history: VecDeque<T>,
+    /// Maximum number of retained values, [`usize::MAX`] if unbounded
+    capacity: usize,
+    /// Sequence number of the oldest retained value
+    head: u64,
+    /// Number of existing [`Sender`]s
+    senders: usize,
+    /// Number of existing [`Receiver`]s
+    receivers: usize,
+    /// Wakers of pending [`Receiver`]s, keyed by [`Receiver::id`]
+    wakers: BTreeMap<u64, Waker>,
+    /// Id of the next [`Receiver`]
+    next_id: u64,
+}
+
+impl<T> State<T> {
+    /// Returns the sequence number the next sent value will get.
+    fn tail(&self) -> u64 {
+        self.head + self.history.len() as u64
+    }
+
+    /// Returns a new receiver id.
+    fn id(&mut self) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    /// Takes the value with sequence number `next` and advances it.
+    fn take(&self, next: &mut u64) -> Result<T, TryRecvError>
+    where
+        T: Clone,
+    {
+        if *next < self.head {
+            let missed = self.head - *next;
+            *next = self.head;
+
+            return Err(TryRecvError::Lagged(missed));
+        }
+
+        match self.history.get((*next - self.head) as usize) {
+            Some(value) => {
+                *next += 1;
+                Ok(value.clone())
+            }
+            None if self.senders == 0 => Err(TryRecvError::Closed),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+}
+
+/// State shared by all [`Sender`]s and [`Receiver`]s of a channel
+#[derive(Debug)]
+struct Shared<T> {
+    /// History and bookkeeping
+    state: Mutex<State<T>>,
+    /// Notified when a value is sent or the channel is closed, so that threads
+    /// blocking in [`Receiver::recv_blocking`] wake up.
+    condvar: Condvar,
+}
+
+impl<T> Shared<T> {
+    /// Creates the state of a channel retaining `capacity` values, with a
+    /// single sender and receiver.
+    fn new(history: VecDeque<T>, capacity: usize) -> Self {
+        Shared {
+            state: Mutex::new(State {
+                history,
+                capacity,
+                head: 0,
+                senders: 1,
+                receivers: 1,
+                wakers: BTreeMap::new(),
+                next_id: 1,
+            }),
+            condvar: Condvar::new(),
+        }
+    }
+
+    /// Locks the state.
+    fn lock(&self) -> MutexGuard<'_, State<T>> {
+        crate::lock(&self.state)
+    }
+
+    /// Wakes up all pending receivers. Takes the wakers while `state` is
+    /// locked but wakes them after unlocking it.
+    fn wake_all(&self, mut state: MutexGuard<'_, State<T>>) {
+        let wakers = mem::take(&mut state.wakers);
+        drop(state);
+
+        for waker in wakers.into_values() {
+            waker.wake();
+        }
+
+        self.condvar.notify_all();
+    }
+}
+
+/// Sending side of a replay channel
+///
+/// Can be cloned to send from multiple producers. The channel is closed once
+/// all senders have been dropped.
+#[derive(Debug)]
+pub struct Sender<T> {
+    /// State shared with all receivers
+    shared: Arc<Shared<T>>,
+}
+
+/// Receiving side of a replay channel
+///
+/// Receives the retained history at the time it has been created and every
+/// value sent afterwards. A cloned receiver continues where the original one
+/// is.
+#[derive(Debug)]
+pub struct Receiver<T> {
+    /// State shared with all senders
+    shared: Arc<Shared<T>>,
+    /// Sequence number of the value that's received next
+    next: u64,
+    /// Key of the waker this receiver registered while being polled
+    id: u64,
+}
+
+impl<T> Sender<T> {
+    /// Sends a value to all receivers and retains it for future ones. Returns
+    /// how many receivers there currently are.
+    ///
+    /// Never blocks: if the history is full, its oldest value is dropped and
+    /// receivers that haven't received it yet get [`RecvError::Lagged`].
+    ///
+    /// # Examples
+    /// ```rust
+    /// let (tx, rx) = laika::replay::channel(8);
+    /// assert_eq!(tx.send(1), 1);
+    ///
+    /// // Sending succeeds without receivers as well
+    /// drop(rx);
+    /// assert_eq!(tx.send(2), 0);
+    /// ```
+    pub fn send(&self, value: T) -> usize {
+        let mut state = self.shared.lock();
+
+        let dropped = if state.history.len() == state.capacity {
+            state.head += 1;
+            state.history.pop_front()
+        } else {
+            None
+        };
+
+        state.history.push_back(value);
+
+        let receivers = state.receivers;
+        self.shared.wake_all(state);
+
+        // The dropped value is dropped after unlocking, in case its `Drop`
+        // uses the channel.
+        drop(dropped);
+
+        receivers
+    }
+
+    /// Creates a new [`Receiver`] that first receives the retained history,
+    /// oldest value first, followed by all values sent from now on.
+    ///
+    /// # Examples
+    /// ```rust
+    /// let (tx, _rx) = laika::replay::unbounded();
+    /// tx.send(1);
+    /// tx.send(2);
+    ///
+    /// let mut rx1 = tx.subscribe();
+    /// tx.send(3);
+    ///
+    /// assert_eq!(rx1.try_recv(), Ok(1));
+    /// assert_eq!(rx1.try_recv(), Ok(2));
+    /// assert_eq!(rx1.try_recv(), Ok(3));
+    /// ```
+    pub fn subscribe(&self) -> Receiver<T> {
+        let mut state = self.shared.lock();
+        state.receivers += 1;
+
+        Receiver {
+            shared: Arc::clone(&self.shared),
+            next: state.head,
+            id: state.id(),
+        }
+    }
+
+    /// Returns the number of retained values.
+    pub fn history_len(&self) -> usize {
+        self.shared.lock().history.len()
+    }
+
+    /// Returns the number of [`Receiver`]s that currently exist.
+    pub fn receiver_count(&self) -> usize {
+        self.shared.lock().receivers
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.shared.lock().senders += 1;
+
+        Sender {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+/// Closes the channel when the last [`Sender`] is dropped, waking up all
+/// pending receivers.
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.senders -= 1;
+
+        if state.senders == 0 {
+            self.shared.wake_all(state);
+        }
+    }
+}
+
+impl<T> Receiver<T>
+where
+    T: Clone,
+{
+    /// Receives the next value if there is one. This function is
+    /// **non-blocking**.
+    ///
+    /// Returns [`TryRecvError::Empty`] if no new value has been sent,
+    /// [`TryRecvError::Lagged`] if values have been dropped from the history
+    /// before this receiver got them or [`TryRecvError::Closed`] if all
+    /// [`Sender`]s have been dropped and every value has been received.
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        self.shared.lock().take(&mut self.next)
+    }
+
+    /// Polls for the next value, registering the waker of `cx` if there is
+    /// none yet.
+    pub fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Result<T, RecvError>> {
+        let mut state = self.shared.lock();
+
+        match state.take(&mut self.next) {
+            Ok(value) => Poll::Ready(Ok(value)),
+            Err(TryRecvError::Closed) => Poll::Ready(Err(RecvError::Closed)),
+            Err(TryRecvError::Lagged(missed)) => Poll::Ready(Err(RecvError::Lagged(missed))),
+            Err(TryRecvError::Empty) => {
+                let registered = state.wakers.get(&self.id);
+
+                if !registered.is_some_and(|waker| waker.will_wake(cx.waker())) {
+                    state.wakers.insert(self.id, cx.waker().clone());
+                }
+
+                Poll::Pending
+            }
+        }
+    }
+
+    /// Receives the next value, waiting asynchronously until one is sent.
+    ///
+    /// Resolves to [`RecvError::Lagged`] if values have been dropped from the
+    /// history before this receiver got them, after which receiving continues
+    /// with the oldest retained value, or [`RecvError::Closed`] if all
+    /// [`Sender`]s have been dropped and every value has been received.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # tokio::runtime::Builder::new_current_thread()
+    /// #     .build()
+    /// #     .unwrap()
+    /// #     .block_on(async {
+    /// let (tx, _rx) = laika::replay::channel(8);
+    ///
+    /// for value in 1..=3 {
+    ///     tx.send(value);
+    /// }
+    ///
+    /// // Started after the values have been sent, but still sees them
+    /// let mut rx = tx.subscribe();
+    /// let task = tokio::spawn(async move {
+    ///     let mut sum = 0;
+    ///
+    ///     while let Ok(value) = rx.recv().await {
+    ///         sum += value;
+    ///     }
+    ///
+    ///     sum
+    /// });
+    ///
+    /// drop(tx);
+    /// assert_eq!(task.await.unwrap(), 6);
+    /// # });
+    /// ```
+    pub async fn recv(&mut self) -> Result<T, RecvError> {
+        future::poll_fn(|cx| self.poll_recv(cx)).await
+    }
+
+    /// Receives the next value, blocking the current thread until one is
+    /// sent. See [`Receiver::recv`].
+    pub fn recv_blocking(&mut self) -> Result<T, RecvError> {
+        let mut state = self.shared.lock();
+
+        loop {
+            match state.take(&mut self.next) {
+                Ok(value) => return Ok(value),
+                Err(TryRecvError::Closed) => return Err(RecvError::Closed),
+                Err(TryRecvError::Lagged(missed)) => return Err(RecvError::Lagged(missed)),
+                Err(TryRecvError::Empty) => {
+                    state = self
+                        .shared
+                        .condvar
+                        .wait(state)
+                        .unwrap_or_else(PoisonError::into_inner);
+                }
+            }
+        }
+    }
+}
+
+impl<T> Receiver<T> {
+    /// Returns the number of values that have been sent (or replayed) but not
+    /// received by this receiver yet, including ones dropped from the history.
+    pub fn len(&self) -> usize {
+        (self.shared.lock().tail() - self.next) as usize
+    }
+
+    /// Returns whether this receiver has received every value sent so far.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Cloned receivers continue where the original one is, i.e. receive the same
+/// values.
+impl<T> Clone for Receiver<T> {
+    fn clone(&self) -> Self {
+        let mut state = self.shared.lock();
+        state.receivers += 1;
+
+        Receiver {
+            shared: Arc::clone(&self.shared),
+            next: self.next,
+            id: state.id(),
+        }
+    }
+}
+
+/// Removes the registered waker (if any) when a [`Receiver`] is dropped.
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.receivers -= 1;
+        state.wakers.remove(&self.id);
+    }
+}
+
+/// Creates a replay channel that retains the last `capacity` values.
+///
+/// # Panics
+/// Panics if `capacity` is zero.
+///
+/// # Examples
+/// ```rust
+/// use laika::replay::TryRecvError;
+///
+/// let (tx, _rx) = laika::replay::channel(1);
+/// tx.send(1);
+/// tx.send(2);
+///
+/// let mut rx1 = tx.subscribe();
+/// assert_eq!(rx1.try_recv(), Ok(2));
+/// assert_eq!(rx1.try_recv(), Err(TryRecvError::Empty));
+/// ```
+pub fn channel<T>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    assert!(capacity > 0, "a replay channel needs a capacity");
+
+    with_shared(Shared::new(VecDeque::with_capacity(capacity), capacity))
+}
+
+/// Creates a replay channel that retains every value sent, so late
+/// subscribers receive the complete history.
+///
+/// Note that the history grows for as long as the channel exists.
+pub fn unbounded<T>() -> (Sender<T>, Receiver<T>) {
+    with_shared(Shared::new(VecDeque::new(), usize::MAX))
+}
+
+/// Creates the sender and the initial receiver of a channel.
+fn with_shared<T>(shared: Shared<T>) -> (Sender<T>, Receiver<T>) {
+    let shared = Arc::new(shared);
+
+    let receiver = Receiver {
+        shared: Arc::clone(&shared),
+        next: 0,
+        id: 0,
+    };
+
+    (Sender { shared }, receiver)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::{future::Future, pin::pin};
+    use std::{thread, time::Duration, vec::Vec};
+
+    #[test]
+    fn test_replay() {
+        let (tx, rx) = channel(3);
+        drop(rx);
+
+        for value in 0..5 {
+            assert_eq!(tx.send(value), 0);
+        }
+
+        assert_eq!(tx.history_len(), 3);
+
+        let mut rx = tx.subscribe();
+        assert_eq!(rx.len(), 3);
+        assert_eq!(rx.try_recv(), Ok(2));
+
+        // Clones continue where the original is, new subscribers get the
+        // history
+        let mut rx1 = rx.clone();
+        let mut rx2 = tx.subscribe();
+        assert_eq!(rx1.try_recv(), Ok(3));
+        assert_eq!(rx2.try_recv(), Ok(2));
+        assert_eq!(tx.receiver_count(), 3);
+    }
+
+    #[test]
+    fn test_lagged() {
+        let (tx, mut rx) = channel(2);
+
+        for value in 0..4 {
+            assert_eq!(tx.send(value), 1);
+        }
+
+        assert_eq!(rx.len(), 4);
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Lagged(2)));
+        assert_eq!(rx.try_recv(), Ok(2));
+        assert_eq!(rx.recv_blocking(), Ok(3));
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+        assert!(rx.is_empty());
+    }
+
+    #[test]
+    fn test_unbounded() {
+        let (tx, _rx) = unbounded();
+
+        for value in 0..100 {
+            tx.send(value);
+        }
+
+        drop(tx.clone());
+
+        let mut rx = tx.subscribe();
+        drop(tx);
+
+        let mut values = Vec::new();
+
+        while let Ok(value) = rx.recv_blocking() {
+            values.push(value);
+        }
+
+        assert_eq!(values, (0..100).collect::<Vec<_>>());
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Closed));
+    }
+
+    #[test]
+    fn test_poll_recv() {
+        let (tx, mut rx) = channel(2);
+        let mut cx = Context::from_waker(Waker::noop());
+
+        {
+            let mut recv = pin!(rx.recv());
+            assert_eq!(recv.as_mut().poll(&mut cx), Poll::Pending);
+            assert_eq!(tx.shared.lock().wakers.len(), 1);
+
+            tx.send(1);
+            assert!(tx.shared.lock().wakers.is_empty());
+            assert_eq!(recv.as_mut().poll(&mut cx), Poll::Ready(Ok(1)));
+        }
+
+        assert_eq!(rx.poll_recv(&mut cx), Poll::Pending);
+        drop(rx);
+        assert!(tx.shared.lock().wakers.is_empty());
+    }
+
+    #[test]
+    fn test_recv_blocking() {
+        let (tx, mut rx) = channel(8);
+
+        let thread = thread::spawn(move || {
+            let mut values = Vec::new();
+
+            while let Ok(value) = rx.recv_blocking() {
+                values.push(value);
+            }
+
+            values
+        });
+
+        thread::sleep(Duration::from_millis(10));
+
+        for value in 0..4 {
+            tx.send(value);
+        }
+
+        drop(tx);
+        assert_eq!(thread.join().unwrap(), [0, 1, 2, 3]);
+    }
+
+    #[tokio::test]
+    async fn test_late_subscribers() {
+        let (tx, _rx) = unbounded();
+
+        let tasks = (0..4)
+            .map(|value| {
+                tx.send(value);
+                let mut rx = tx.subscribe();
+
+                tokio::spawn(async move {
+                    let mut values = Vec::new();
+
+                    while let Ok(value) = rx.recv().await {
+                        values.push(value);
+                    }
+
+                    values
+                })
+            })
+            .collect::<Vec<_>>();
+
+        drop(tx);
+
+        for task in tasks {
+            assert_eq!(task.await.unwrap(), [0, 1, 2, 3]);
+        }
+    }
+}
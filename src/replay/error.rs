@@ -0,0 +1,79 @@
+//! Errors of receiving from replay channels.
+
+#[cfg(doc)]
+use super::{Receiver, Sender};
+use core::{error::Error, fmt};
+
+/// Error returned by [`Receiver::recv`] and [`Receiver::recv_blocking`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RecvError {
+    /// All [`Sender`]s have been dropped and every retained value has been
+    /// received, so no value will ever be received.
+    Closed,
+    /// The receiver fell behind and the contained number of values has been
+    /// dropped from the history before it received them. The next receive
+    /// returns the oldest value that's still retained.
+    Lagged(u64),
+}
+
+impl fmt::Display for RecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecvError::Closed => write!(f, "channel closed"),
+            RecvError::Lagged(count) => write!(f, "receiver lagged behind by {count} values"),
+        }
+    }
+}
+
+impl Error for RecvError {}
+
+/// Error returned by [`Receiver::try_recv`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TryRecvError {
+    /// No new value has been sent, but a [`Sender`] still exists.
+    Empty,
+    /// All [`Sender`]s have been dropped and every retained value has been
+    /// received, so no value will ever be received.
+    Closed,
+    /// The receiver fell behind, see [`RecvError::Lagged`].
+    Lagged(u64),
+}
+
+impl fmt::Display for TryRecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TryRecvError::Empty => write!(f, "no new value has been sent"),
+            TryRecvError::Closed => RecvError::Closed.fmt(f),
+            TryRecvError::Lagged(count) => RecvError::Lagged(*count).fmt(f),
+        }
+    }
+}
+
+impl Error for TryRecvError {}
+
+impl From<RecvError> for TryRecvError {
+    fn from(error: RecvError) -> Self {
+        match error {
+            RecvError::Closed => TryRecvError::Closed,
+            RecvError::Lagged(count) => TryRecvError::Lagged(count),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::string::ToString;
+
+    #[test]
+    fn test_display() {
+        assert_eq!(
+            TryRecvError::from(RecvError::Lagged(2)).to_string(),
+            "receiver lagged behind by 2 values"
+        );
+        assert_eq!(
+            TryRecvError::from(RecvError::Closed).to_string(),
+            RecvError::Closed.to_string()
+        );
+    }
+}
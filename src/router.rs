@@ -0,0 +1,376 @@
+#![forbid(unsafe_code)]
+//! # Routing messages from one input to several outputs
+//!
+//! A [`Router`] forwards every message of an input channel to one of several
+//! outputs. Routes are checked in the order they've been added:
+//!
+//! - [`Router::route`]: Messages matching a predicate go to the output.
+//! - [`Router::hashed`]: Messages are spread across a set of outputs by a hash
+//!   of them, so messages with the same hash always end up at the same output.
+//! - [`Router::fallback`]: Messages that no route matched go to the fallback
+//!   output.
+//!
+//! [`Router::run`] (or [`Router::run_blocking`]) takes an
+//! [`mpsc`](crate::mpsc) receiver as input and routes its messages until all
+//! of its senders have been dropped, so fan-out topologies don't need
+//! hand-written dispatcher tasks. Single messages can be routed with
+//! [`Router::dispatch`].
+//!
+//! Outputs are anything implementing [`Output`], like the
+//! [`UnboundedSender`] of an mpsc channel.
+//!
+//! # Examples
+//! ```rust
+//! use laika::{mpsc, router::Router};
+//!
+//! let (tx, rx) = mpsc::unbounded();
+//! let (errors_tx, mut errors) = mpsc::unbounded();
+//! let (rest_tx, mut rest) = mpsc::unbounded();
+//!
+//! let router = Router::new()
+//!     .route(|line: &&str| line.starts_with("error"), errors_tx)
+//!     .fallback(rest_tx);
+//!
+//! tx.send("error: disk full").unwrap();
+//! tx.send("info: started").unwrap();
+//! drop(tx);
+//!
+//! router.run_blocking(rx).unwrap();
+//!
+//! assert_eq!(errors.try_recv(), Ok("error: disk full"));
+//! assert_eq!(rest.try_recv(), Ok("info: started"));
+//! ```
+
+mod error;
+
+pub use error::RouteError;
+
+use crate::mpsc::{UnboundedReceiver, UnboundedSender};
+use alloc::{boxed::Box, vec::Vec};
+use core::fmt;
+
+/// Destination of routed messages
+///
+/// Implemented for the [`UnboundedSender`] of mpsc channels. Implement it for
+/// custom destinations, e.g. to wrap other channels.
+pub trait Output<T> {
+    /// Forwards a message, returning it if the output has been closed.
+    fn send(&self, value: T) -> Result<(), T>;
+}
+
+impl<T> Output<T> for UnboundedSender<T> {
+    fn send(&self, value: T) -> Result<(), T> {
+        UnboundedSender::send(self, value).map_err(|e| e.into_inner())
+    }
+}
+
+/// Boxed [`Output`] of a route
+type BoxedOutput<T> = Box<dyn Output<T> + Send>;
+
+/// Route of a [`Router`]
+enum Route<T> {
+    /// Messages matching the predicate go to the output
+    Predicate {
+        /// Checks whether a message takes this route
+        predicate: Box<dyn Fn(&T) -> bool + Send>,
+        /// Output of matching messages
+        output: BoxedOutput<T>,
+    },
+    /// Every message goes to the output at its hash modulo the number of
+    /// outputs
+    Hashed {
+        /// Hashes a message
+        hash: Box<dyn Fn(&T) -> u64 + Send>,
+        /// Outputs to spread messages across, never empty
+        outputs: Vec<BoxedOutput<T>>,
+    },
+}
+
+/// Routes messages to outputs chosen by predicates or hashes
+///
+/// See the [module documentation](self) for details.
+pub struct Router<T> {
+    /// Routes, checked in order
+    routes: Vec<Route<T>>,
+    /// Output of messages that no route matched
+    fallback: Option<BoxedOutput<T>>,
+}
+
+impl<T> Router<T> {
+    /// Creates a router without any routes.
+    pub fn new() -> Self {
+        Router {
+            routes: Vec::new(),
+            fallback: None,
+        }
+    }
+
+    /// Adds a route sending every message that matches `predicate` (and no
+    /// route added before) to `output`.
+    pub fn route<P, O>(mut self, predicate: P, output: O) -> Self
+    where
+        P: Fn(&T) -> bool + Send + 'static,
+        O: Output<T> + Send + 'static,
+    {
+        self.routes.push(Route::Predicate {
+            predicate: Box::new(predicate),
+            output: Box::new(output),
+        });
+
+        self
+    }
+
+    /// Adds a route spreading messages across `outputs` by `hash`: a message
+    /// goes to the output at its hash modulo the number of outputs, so
+    /// messages with the same hash always take the same output.
+    ///
+    /// As every message takes this route, routes added afterwards (including
+    /// the fallback) are never used.
+    ///
+    /// # Panics
+    /// Panics if `outputs` is empty.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use laika::{mpsc, router::Router};
+    ///
+    /// let (outputs, mut receivers): (Vec<_>, Vec<_>) = (0..2).map(|_| mpsc::unbounded()).unzip();
+    /// let router = Router::new().hashed(|id: &u64| *id, outputs);
+    ///
+    /// for id in 0..4 {
+    ///     router.dispatch(id).unwrap();
+    /// }
+    ///
+    /// assert_eq!(receivers[0].try_recv(), Ok(0));
+    /// assert_eq!(receivers[0].try_recv(), Ok(2));
+    /// assert_eq!(receivers[1].try_recv(), Ok(1));
+    /// assert_eq!(receivers[1].try_recv(), Ok(3));
+    /// ```
+    pub fn hashed<H, O>(mut self, hash: H, outputs: impl IntoIterator<Item = O>) -> Self
+    where
+        H: Fn(&T) -> u64 + Send + 'static,
+        O: Output<T> + Send + 'static,
+    {
+        let outputs = outputs
+            .into_iter()
+            .map(|output| Box::new(output) as BoxedOutput<T>)
+            .collect::<Vec<_>>();
+
+        assert!(!outputs.is_empty(), "a hashed route needs outputs");
+
+        self.routes.push(Route::Hashed {
+            hash: Box::new(hash),
+            outputs,
+        });
+
+        self
+    }
+
+    /// Sets the output of messages that no route matched, replacing a
+    /// previously set one. Without one, [`Router::dispatch`] returns
+    /// [`RouteError::Unrouted`] for such messages.
+    pub fn fallback<O>(mut self, output: O) -> Self
+    where
+        O: Output<T> + Send + 'static,
+    {
+        self.fallback = Some(Box::new(output));
+        self
+    }
+
+    /// Routes a single message to the output of the first matching route (or
+    /// the fallback).
+    ///
+    /// Returns the message in [`RouteError::Unrouted`] if no route matched and
+    /// there's no fallback, or in [`RouteError::Disconnected`] if the chosen
+    /// output has been closed.
+    pub fn dispatch(&self, value: T) -> Result<(), RouteError<T>> {
+        let output = self
+            .routes
+            .iter()
+            .find_map(|route| match route {
+                Route::Predicate { predicate, output } => predicate(&value).then_some(output),
+                Route::Hashed { hash, outputs } => {
+                    let index = hash(&value) % outputs.len() as u64;
+                    Some(&outputs[index as usize])
+                }
+            })
+            .or(self.fallback.as_ref());
+
+        match output {
+            Some(output) => output.send(value).map_err(RouteError::Disconnected),
+            None => Err(RouteError::Unrouted(value)),
+        }
+    }
+
+    /// Routes the messages of `input` asynchronously until all of its senders
+    /// have been dropped and every message has been routed.
+    ///
+    /// Stops at the first message that couldn't be routed, returning it in
+    /// the [`RouteError`]. Messages still queued in `input` are dropped with
+    /// it.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # tokio::runtime::Builder::new_current_thread()
+    /// #     .build()
+    /// #     .unwrap()
+    /// #     .block_on(async {
+    /// use laika::{mpsc, router::Router};
+    ///
+    /// let (tx, rx) = mpsc::unbounded();
+    /// let (even_tx, mut even) = mpsc::unbounded();
+    /// let (odd_tx, mut odd) = mpsc::unbounded();
+    ///
+    /// let router = Router::new()
+    ///     .route(|value: &u8| value.is_multiple_of(2), even_tx)
+    ///     .fallback(odd_tx);
+    ///
+    /// let dispatcher = tokio::spawn(router.run(rx));
+    ///
+    /// for value in 0..4 {
+    ///     tx.send(value).unwrap();
+    /// }
+    ///
+    /// drop(tx);
+    /// dispatcher.await.unwrap().unwrap();
+    ///
+    /// assert_eq!(even.recv().await, Some(0));
+    /// assert_eq!(odd.recv().await, Some(1));
+    /// # });
+    /// ```
+    pub async fn run(self, mut input: UnboundedReceiver<T>) -> Result<(), RouteError<T>> {
+        while let Some(value) = input.recv().await {
+            self.dispatch(value)?;
+        }
+
+        Ok(())
+    }
+
+    /// Routes the messages of `input`, blocking the current thread until all
+    /// of its senders have been dropped. See [`Router::run`].
+    pub fn run_blocking(self, mut input: UnboundedReceiver<T>) -> Result<(), RouteError<T>> {
+        while let Some(value) = input.recv_blocking() {
+            self.dispatch(value)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<T> Default for Router<T> {
+    fn default() -> Self {
+        Router::new()
+    }
+}
+
+impl<T> fmt::Debug for Router<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Router")
+            .field("routes", &self.routes.len())
+            .field("fallback", &self.fallback.is_some())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mpsc::{self, TryRecvError};
+    use std::{sync::Mutex, thread};
+
+    /// Output collecting messages, closed if the flag is `false`
+    struct Collect(&'static Mutex<Vec<u8>>, bool);
+
+    impl Output<u8> for Collect {
+        fn send(&self, value: u8) -> Result<(), u8> {
+            if !self.1 {
+                return Err(value);
+            }
+
+            self.0.lock().unwrap().push(value);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_dispatch_order() {
+        let (small_tx, mut small) = mpsc::unbounded();
+        let (even_tx, mut even) = mpsc::unbounded();
+
+        let router = Router::new()
+            .route(|value: &u8| *value < 2, small_tx)
+            .route(|value: &u8| value.is_multiple_of(2), even_tx);
+
+        for value in 0..4 {
+            let result = router.dispatch(value);
+
+            // Odd values above 1 don't match any route
+            match value {
+                3 => assert_eq!(result, Err(RouteError::Unrouted(3))),
+                _ => assert_eq!(result, Ok(())),
+            }
+        }
+
+        // The first matching route wins
+        assert_eq!(small.try_recv(), Ok(0));
+        assert_eq!(small.try_recv(), Ok(1));
+        assert_eq!(even.try_recv(), Ok(2));
+        assert_eq!(even.try_recv(), Err(TryRecvError::Empty));
+    }
+
+    #[test]
+    fn test_custom_output() {
+        static COLLECTED: Mutex<Vec<u8>> = Mutex::new(Vec::new());
+
+        let router = Router::new()
+            .route(|value: &u8| *value == 0, Collect(&COLLECTED, false))
+            .fallback(Collect(&COLLECTED, true));
+
+        assert_eq!(router.dispatch(0), Err(RouteError::Disconnected(0)));
+        assert_eq!(router.dispatch(1), Ok(()));
+        assert_eq!(*COLLECTED.lock().unwrap(), [1]);
+    }
+
+    #[test]
+    fn test_run_blocking() {
+        let (tx, rx) = mpsc::unbounded();
+        let (outputs, receivers): (Vec<_>, Vec<_>) = (0..3).map(|_| mpsc::unbounded()).unzip();
+
+        let router = Router::new().hashed(|value: &u64| *value, outputs);
+        let dispatcher = thread::spawn(move || router.run_blocking(rx));
+
+        for value in 0..9 {
+            tx.send(value).unwrap();
+        }
+
+        drop(tx);
+        assert_eq!(dispatcher.join().unwrap(), Ok(()));
+
+        for (index, mut receiver) in receivers.into_iter().enumerate() {
+            let mut values = Vec::new();
+
+            while let Some(value) = receiver.recv_blocking() {
+                values.push(value);
+            }
+
+            assert!(values.iter().all(|value| *value % 3 == index as u64));
+            assert_eq!(values.len(), 3);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run() {
+        let (tx, rx) = mpsc::unbounded();
+        let (output, output_rx) = mpsc::unbounded();
+
+        let router = Router::new().fallback(output);
+        let dispatcher = tokio::spawn(router.run(rx));
+
+        tx.send(1).unwrap();
+        tx.send(2).unwrap();
+        drop(output_rx);
+
+        // Stops at the first message that couldn't be routed
+        assert_eq!(dispatcher.await.unwrap(), Err(RouteError::Disconnected(1)));
+    }
+}
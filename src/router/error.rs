@@ -0,0 +1,58 @@
+//! Errors of routing messages.
+
+#[cfg(doc)]
+use super::{Output, Router};
+use core::{error::Error, fmt};
+
+/// Error returned by [`Router::dispatch`] if a message couldn't be routed.
+/// Contains the message, so it can be reclaimed.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum RouteError<T> {
+    /// No route matched the message and the [`Router`] has no fallback.
+    Unrouted(T),
+    /// The [`Output`] chosen for the message has been closed.
+    Disconnected(T),
+}
+
+impl<T> RouteError<T> {
+    /// Returns the message that couldn't be routed.
+    pub fn into_inner(self) -> T {
+        match self {
+            RouteError::Unrouted(value) | RouteError::Disconnected(value) => value,
+        }
+    }
+}
+
+debug_without_value!(RouteError {
+    Unrouted,
+    Disconnected
+});
+
+impl<T> fmt::Display for RouteError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RouteError::Unrouted(_) => write!(f, "no route matched the message"),
+            RouteError::Disconnected(_) => write!(f, "routing to a closed output"),
+        }
+    }
+}
+
+impl<T> Error for RouteError<T> {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::string::ToString;
+
+    #[test]
+    fn test_display() {
+        assert_eq!(
+            RouteError::Unrouted(1).to_string(),
+            "no route matched the message"
+        );
+        assert_eq!(
+            RouteError::Disconnected(1).to_string(),
+            "routing to a closed output"
+        );
+    }
+}
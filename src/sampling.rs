@@ -0,0 +1,325 @@
+#![forbid(unsafe_code)]
+//! # Sampling the latest value of a receiver per interval
+//!
+//! [`Sampler`] wraps a receiver and yields at most one value per tick of an
+//! interval: the most recent one received since the previous tick. The values
+//! received in between are discarded, which decimates high-frequency streams
+//! (like sensor readings or market data) to a rate consumers can handle.
+//! Ticks without a new value are skipped, and the value held when the
+//! receiver has been closed is yielded right away.
+//!
+//! Any [`Source`] can be wrapped, i.e. the receivers of the channel modules
+//! (see [`merge`](crate::merge) for the list). A sampler is a [`Source`]
+//! itself. Values are received with [`Sampler::next`] or blocking with
+//! [`Sampler::next_blocking`].
+//!
+//! Ticks of samplers received asynchronously are tracked by a single
+//! background thread, so they work with any async runtime. The sampler
+//! doesn't contain any unsafe code.
+//!
+//! ## Optional features
+//!
+//! - `futures`: Implements `futures_core::Stream` for [`Sampler`].
+//!
+//! # Examples
+#![cfg_attr(feature = "mpsc", doc = "```rust")]
+#![cfg_attr(not(feature = "mpsc"), doc = "```ignore")]
+//! use laika::{mpsc, sampling::Sampler};
+//! use std::time::Duration;
+//!
+//! let (tx, rx) = mpsc::unbounded();
+//! let mut sampler = Sampler::new(rx, Duration::from_millis(20));
+//!
+//! for reading in 0..1000 {
+//!     tx.send(reading).unwrap();
+//! }
+//!
+//! // Only the latest reading of the interval is yielded
+//! assert_eq!(sampler.next_blocking(), Some(999));
+//!
+//! drop(tx);
+//! assert_eq!(sampler.next_blocking(), None);
+//! ```
+
+use crate::{
+    merge::Source,
+    park,
+    timer::{self, alarm::Alarm},
+};
+use alloc::{boxed::Box, sync::Arc};
+#[cfg(feature = "futures")]
+use core::pin::Pin;
+use core::{
+    fmt,
+    task::{Context, Poll},
+};
+use std::time::{Duration, Instant};
+
+/// Adapter yielding the latest value of a receiver per interval
+///
+/// See the [module documentation](self) for details.
+pub struct Sampler<T> {
+    /// Wrapped receiver, [`None`] once it has been closed
+    source: Option<Box<dyn Source<T> + Send>>,
+    /// Time between two ticks
+    interval: Duration,
+    /// When the next tick is, [`None`] if it's too far in the future to be
+    /// represented, so there's no tick anymore
+    tick: Option<Instant>,
+    /// Latest value received since the last tick
+    latest: Option<T>,
+    /// Number of values discarded in favor of a later one
+    discarded: u64,
+    /// Alarm of the next tick, if it has been scheduled
+    alarm: Option<Arc<Alarm>>,
+}
+
+impl<T> Unpin for Sampler<T> {}
+
+impl<T> Sampler<T> {
+    /// Wraps `source`, yielding its latest value once per `interval`. The
+    /// first tick is one interval after creating the sampler.
+    ///
+    /// # Panics
+    /// Panics if `interval` is zero.
+    pub fn new<S>(source: S, interval: Duration) -> Self
+    where
+        S: Source<T> + Send + 'static,
+    {
+        assert!(!interval.is_zero(), "a sampler needs an interval");
+
+        Sampler {
+            source: Some(Box::new(source)),
+            interval,
+            tick: Instant::now().checked_add(interval),
+            latest: None,
+            discarded: 0,
+            alarm: None,
+        }
+    }
+
+    /// Returns the number of values that have been discarded since the
+    /// sampler has been created, as a later one arrived within their
+    /// interval.
+    pub fn discarded(&self) -> u64 {
+        self.discarded
+    }
+
+    /// Moves the next tick from `tick` past `now`, skipping ticks that have
+    /// been missed.
+    fn advance(&mut self, tick: Instant, now: Instant) {
+        let missed = now.duration_since(tick).as_nanos() / self.interval.as_nanos();
+        let ticks = u32::try_from(missed + 1).unwrap_or(u32::MAX);
+
+        self.tick = tick.checked_add(self.interval.saturating_mul(ticks));
+        self.alarm = None;
+    }
+
+    /// Receives all values that are ready, keeping the latest one. Afterwards,
+    /// yields it if the tick passed (or the receiver has been closed).
+    fn poll_sample(&mut self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        while let Some(source) = &mut self.source {
+            match source.poll_next(cx) {
+                Poll::Ready(Some(value)) => {
+                    if self.latest.replace(value).is_some() {
+                        self.discarded += 1;
+                    }
+                }
+                Poll::Ready(None) => self.source = None,
+                Poll::Pending => break,
+            }
+        }
+
+        if self.source.is_none() {
+            self.alarm = None;
+            return Poll::Ready(self.latest.take());
+        }
+
+        let now = Instant::now();
+
+        match self.tick {
+            Some(tick) if tick <= now => self.advance(tick, now),
+            _ => return Poll::Pending,
+        }
+
+        match self.latest.take() {
+            Some(value) => Poll::Ready(Some(value)),
+            None => Poll::Pending,
+        }
+    }
+
+    /// Polls for the next sample, registering the waker of `cx` with the
+    /// receiver (and the timer, if a value is waiting for the tick) if there
+    /// is none yet. Resolves to [`None`] once the receiver has been closed and
+    /// the latest value has been yielded.
+    pub fn poll_next(&mut self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        let poll = self.poll_sample(cx);
+
+        if let (Poll::Pending, Some(_), Some(tick)) = (&poll, &self.latest, self.tick) {
+            let alarm = self.alarm.get_or_insert_with(|| {
+                let alarm = Arc::new(Alarm::default());
+                timer::schedule(tick, &alarm);
+
+                alarm
+            });
+
+            alarm.register(cx.waker());
+        }
+
+        poll
+    }
+
+    /// Receives the next sample, waiting asynchronously until a tick passed
+    /// with a new value. Resolves to [`None`] once the receiver has been
+    /// closed and the latest value has been yielded.
+    ///
+    /// # Examples
+    #[cfg_attr(feature = "mpsc", doc = "```rust")]
+    #[cfg_attr(not(feature = "mpsc"), doc = "```ignore")]
+    /// # tokio::runtime::Builder::new_current_thread()
+    /// #     .build()
+    /// #     .unwrap()
+    /// #     .block_on(async {
+    /// use laika::{mpsc, sampling::Sampler};
+    /// use std::time::Duration;
+    ///
+    /// let (tx, rx) = mpsc::unbounded();
+    /// let mut prices = Sampler::new(rx, Duration::from_millis(10));
+    ///
+    /// tx.send(101.5).unwrap();
+    /// tx.send(101.7).unwrap();
+    ///
+    /// assert_eq!(prices.next().await, Some(101.7));
+    /// assert_eq!(prices.discarded(), 1);
+    /// # });
+    /// ```
+    pub async fn next(&mut self) -> Option<T> {
+        core::future::poll_fn(|cx| self.poll_next(cx)).await
+    }
+
+    /// Receives the next sample, blocking the current thread until a tick
+    /// passed with a new value. See [`Sampler::next`].
+    pub fn next_blocking(&mut self) -> Option<T> {
+        park::block_on(|cx| self.poll_next(cx))
+    }
+}
+
+impl<T> Source<T> for Sampler<T> {
+    fn poll_next(&mut self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        Sampler::poll_next(self, cx)
+    }
+}
+
+impl<T> fmt::Debug for Sampler<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sampler")
+            .field("closed", &self.source.is_none())
+            .field("interval", &self.interval)
+            .field("tick", &self.tick)
+            .field("pending", &self.latest.is_some())
+            .field("discarded", &self.discarded)
+            .finish()
+    }
+}
+
+/// Implement [`futures_core::Stream`] for [`Sampler`], yielding samples until
+/// the receiver has been closed.
+#[cfg(feature = "futures")]
+impl<T> futures_core::Stream for Sampler<T> {
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        self.get_mut().poll_next(cx)
+    }
+}
+
+#[cfg(all(test, feature = "mpsc"))]
+mod test {
+    use super::*;
+    use crate::mpsc;
+    use core::task::Waker;
+    use std::{thread, vec::Vec};
+
+    #[test]
+    fn test_tick() {
+        let (tx, rx) = mpsc::unbounded();
+        let mut sampler = Sampler::new(rx, Duration::from_millis(50));
+        let mut cx = Context::from_waker(Waker::noop());
+
+        tx.send(1).unwrap();
+        tx.send(2).unwrap();
+
+        // Held back until the tick
+        assert_eq!(sampler.poll_next(&mut cx), Poll::Pending);
+        thread::sleep(Duration::from_millis(60));
+        assert_eq!(sampler.poll_next(&mut cx), Poll::Ready(Some(2)));
+        assert_eq!(sampler.discarded(), 1);
+
+        // Ticks without a value are skipped
+        thread::sleep(Duration::from_millis(60));
+        assert_eq!(sampler.poll_next(&mut cx), Poll::Pending);
+
+        // The latest value is yielded right away once the receiver is closed
+        tx.send(3).unwrap();
+        drop(tx);
+        assert_eq!(sampler.poll_next(&mut cx), Poll::Ready(Some(3)));
+        assert_eq!(sampler.poll_next(&mut cx), Poll::Ready(None));
+    }
+
+    #[test]
+    fn test_endless_interval() {
+        let (tx, rx) = mpsc::unbounded();
+        let mut sampler = Sampler::new(rx, Duration::MAX);
+        let mut cx = Context::from_waker(Waker::noop());
+
+        // There's never a tick, so the value is only yielded once closed
+        tx.send(1).unwrap();
+        assert_eq!(sampler.tick, None);
+        assert_eq!(sampler.poll_next(&mut cx), Poll::Pending);
+        assert!(sampler.alarm.is_none());
+
+        drop(tx);
+        assert_eq!(sampler.poll_next(&mut cx), Poll::Ready(Some(1)));
+    }
+
+    #[test]
+    fn test_next_blocking() {
+        let (tx, rx) = mpsc::unbounded();
+        let mut sampler = Sampler::new(rx, Duration::from_millis(30));
+
+        let producer = thread::spawn(move || {
+            for value in 0..20 {
+                tx.send(value).unwrap();
+                thread::sleep(Duration::from_millis(5));
+            }
+        });
+
+        let mut samples = Vec::new();
+
+        while let Some(value) = sampler.next_blocking() {
+            samples.push(value);
+        }
+
+        producer.join().unwrap();
+
+        // Later samples are later values, and the last value isn't lost
+        assert!(samples.len() < 20);
+        assert!(samples.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(samples.last(), Some(&19));
+        assert_eq!(sampler.discarded() as usize, 20 - samples.len());
+    }
+
+    #[tokio::test]
+    async fn test_next() {
+        let (tx, rx) = mpsc::unbounded();
+        let mut sampler = Sampler::new(rx, Duration::from_millis(20));
+
+        tx.send('a').unwrap();
+
+        // Woken up by the timer thread at the tick
+        assert_eq!(sampler.next().await, Some('a'));
+
+        drop(tx);
+        assert_eq!(sampler.next().await, None);
+    }
+}
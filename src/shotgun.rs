@@ -2,38 +2,162 @@
 //! # A dead simple one-shot single producer, multiple consumer (SPMC) channel
 //!
 //! Shotgun is a simple oneshot single producer, multiple consumer (SPMC)
-//! channel. Internally using [`std::sync::Mutex`] and [`std::sync::Arc`], not
-//! containing any unsafe code.
+//! channel. Internally using `std::sync::OnceLock`, atomics and [`Arc`], not
+//! containing any unsafe code. Reading the value once it has been sent is
+//! lock-free; a `std::sync::Mutex` is only taken to register wakers of pending
+//! receivers.
+//!
+//! The [`Sender`] can be cloned for multiple racing producers, in which case
+//! the first sent value wins.
+//!
+//! ## Optional features
+//!
+//! - `std` (default): Backs the channel by [`std::sync`] and enables the
+//!   blocking functions like [`Receiver::recv_blocking`].
+//! - `spin`: Backs the channel by spin locks of the `spin` crate instead, for
+//!   `no_std` builds. Ignored if `std` is enabled.
+//! - `parking_lot`: Backs the channel by the locks of the `parking_lot` crate
+//!   instead of [`std::sync`]. Implies `std`.
+//! - `futures`: Implements `futures_core::Stream` for [`Receiver`], yielding
+//!   the value once and ending afterwards, and `FusedFuture`.
+//! - `tokio`: Enables [`Receiver::recv_timeout_async`], using tokio's timer as
+//!   time source, and conversions from and to `tokio::sync` channels (like
+//!   [`from_oneshot`]). Implies `std`.
+//! - `serde`: Enables [`Snapshot`]s of channels that can be serialized (as can
+//!   [`Receiver`]s) and restored, e.g. to checkpoint workflows.
+//!
+//! ## `no_std`
+//!
+//! Shotgun only needs `alloc`. Without the (default) `std` feature, the `spin`
+//! feature has to be enabled instead, which backs the channel by spin locks.
+//! The async API is fully functional then, while the blocking functions that
+//! rely on threads and clocks are left out. Use [`Receiver::recv_blocking_with`]
+//! with a [`Park`] implementation of your platform to block instead.
+//!
+//! ## Model checking with `loom`
+//!
+//! If built with `RUSTFLAGS="--cfg laika_loom"`, shotgun uses the atomics,
+//! locks and condition variables of [`loom`](https://docs.rs/loom), so that
+//! the crate itself as well as downstream primitives embedding a channel can be
+//! model-checked. Channels have to be created within `loom::model` then. The
+//! cfg is specific to this crate, as a plain `--cfg loom` would switch
+//! dependencies like tokio to loom as well.
+//!
+//! ```sh
+//! RUSTFLAGS="--cfg laika_loom" cargo test --release --features shotgun --lib loom
+//! ```
 
-use std::{
+#[cfg(all(feature = "std", not(laika_loom)))]
+use crate::timer::alarm::Alarm;
+use alloc::{
+    boxed::Box,
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use core::{
     clone::Clone,
-    future::Future,
-    pin::Pin,
-    sync::{Arc, Mutex},
+    cmp::Reverse,
+    fmt,
+    future::{self, Future, IntoFuture},
+    hash::{Hash, Hasher},
+    iter, mem,
+    pin::{self, Pin},
+    ptr,
+    sync::atomic::Ordering,
     task::{Context, Poll, Waker},
 };
+#[cfg(all(feature = "std", not(laika_loom)))]
+use std::sync::OnceLock;
+#[cfg(feature = "std")]
+use std::time::{Duration, Instant};
+#[cfg(feature = "std")]
+use sync::Condvar;
+use sync::{AtomicBool, AtomicU8, AtomicUsize, Mutex, MutexGuard, OnceCell};
+
+mod arc;
+mod boxed;
+mod builder;
+mod complete;
+#[cfg(all(feature = "std", not(laika_loom)))]
+mod deadline;
+mod error;
+mod guard;
+#[cfg(feature = "tokio")]
+mod interop;
+mod join;
+mod local;
+#[cfg(feature = "std")]
+mod observer;
+mod park;
+mod reusable;
+mod select;
+#[cfg(feature = "std")]
+mod set;
+mod signal;
+#[cfg(feature = "serde")]
+mod snapshot;
+#[cfg(feature = "std")]
+mod spawn;
+#[cfg(not(laika_loom))]
+mod static_shotgun;
+mod sync;
+mod take;
+
+pub use arc::{arc_channel, ArcSender};
+pub use boxed::BoxedReceiver;
+pub use builder::{Builder, PoisonPolicy};
+pub use complete::Complete;
+#[cfg(all(feature = "std", not(laika_loom)))]
+pub use deadline::{channel_with_deadline, DeadlineReceiver};
+pub use error::{
+    DeadlineRecvError, ReceiverLimitError, RecvError, RecvTimeoutError, RecvUntilError, SendError,
+    TryRecvError,
+};
+pub use guard::CompletionGuard;
+#[cfg(feature = "tokio")]
+pub use interop::{forward_to_broadcast, from_broadcast, from_oneshot, into_oneshot};
+pub use join::{join, Join};
+pub use local::{local_channel, LocalReceiver, LocalSender};
+#[cfg(feature = "std")]
+pub use observer::{set_observer, ChannelId, ChannelObserver};
+pub use park::Park;
+pub use reusable::ReusableSender;
+pub use select::{select_any, SelectAny};
+#[cfg(feature = "std")]
+pub use set::ShotgunSet;
+pub use signal::{signal, SignalReceiver, SignalSender};
+#[cfg(feature = "serde")]
+pub use snapshot::Snapshot;
+#[cfg(feature = "std")]
+pub use spawn::spawn;
+#[cfg(not(laika_loom))]
+pub use static_shotgun::{StaticRecv, StaticShotgun};
+pub use take::{take_channel, TakeReceiver, TakeSender};
 
 /// Oneshot receiver of a [`channel`]
 ///
 /// Use [`Receiver::try_recv`] or [`Receiver::recv`] to (try to) receive a value
 /// from the channel, if it has been sent. As this is a oneshot receiver, only
-/// one value can be received.
+/// one value can be received. If the [`Sender`] is dropped without sending a
+/// value, the channel is closed and receiving fails with an error.
 ///
 /// # Examples
 ///
 /// ## Synchronous
 ///
 /// ```rust
+/// use laika::shotgun::TryRecvError;
+///
 /// let (mut tx, rx) = laika::shotgun::channel();
 ///
 /// // Initialy, oneshot receiver has no value
-/// assert_eq!(rx.try_recv(), None);
+/// assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
 ///
 /// // Send a value
-/// tx.send(12);
+/// tx.send(12).unwrap();
 ///
 /// // Now, oneshot receiver has the value
-/// assert_eq!(rx.try_recv(), Some(12));
+/// assert_eq!(rx.try_recv(), Ok(12));
 /// ```
 ///
 /// ## Asynchronous
@@ -49,15 +173,21 @@ use std::{
 /// };
 ///
 /// // Send a value
-/// tx.send(12);
+/// tx.send(12).unwrap();
 /// ```
-#[derive(Clone, Debug)]
+#[derive(Debug)]
 pub struct Receiver<T>
 where
     T: Clone,
 {
-    /// Inner receiver that holds the sent value and possible wakers
-    inner: Arc<Mutex<_Receiver<T>>>,
+    /// Shared state that holds the sent value and possible wakers
+    inner: Arc<_Channel<T>>,
+    /// Key of the waker this receiver registered while being polled, if any
+    key: _Key,
+    /// Whether this receiver has completed as a future or yielded its item as
+    /// a stream already
+    #[cfg_attr(not(any(feature = "futures", feature = "merge")), allow(dead_code))]
+    done: bool,
 }
 
 /// Oneshot sender of a [`channel`]
@@ -65,6 +195,11 @@ where
 /// Use [`Sender::send`] to send a value to all receivers of the channel.
 /// As this is a oneshot sender, only one value can be sent.
 ///
+/// Senders can be cloned so that multiple racing producers (e.g. hedged
+/// requests) can try to complete the same channel. The first sent value wins,
+/// later sends fail with [`SendError::AlreadySent`]. The channel is closed
+/// once all senders have been dropped without sending a value.
+///
 /// # Examples
 /// ## Send a value
 ///
@@ -72,7 +207,7 @@ where
 /// let (mut tx, rx) = laika::shotgun::channel();
 ///
 /// // Send a value
-/// tx.send(12);
+/// tx.send(12).unwrap();
 /// ```
 ///
 /// ## Sender is dropped after sending
@@ -81,8 +216,22 @@ where
 /// let (mut tx, rx) = laika::shotgun::channel();
 ///
 /// // Send a value
-/// tx.send(12);
-/// tx.send(13); // This won't compile
+/// tx.send(12).unwrap();
+/// tx.send(13).unwrap(); // This won't compile
+/// ```
+///
+/// ## Racing senders
+///
+/// ```rust
+/// use laika::shotgun::SendError;
+///
+/// let (tx, rx) = laika::shotgun::channel();
+/// let tx1 = tx.clone();
+///
+/// tx1.send(1).unwrap();
+/// assert_eq!(tx.send(2), Err(SendError::AlreadySent(2)));
+///
+/// assert_eq!(rx.try_recv(), Ok(1));
 /// ```
 #[derive(Debug)]
 pub struct Sender<T>
@@ -92,52 +241,536 @@ where
     inner: _Sender<T>,
 }
 
+/// Weak reference to the receiving side of a [`channel`]
+///
+/// Created by [`Receiver::downgrade`]. A weak receiver doesn't keep the channel
+/// state alive: once all [`Receiver`]s are dropped, [`WeakReceiver::upgrade`]
+/// returns [`None`]. This makes it possible to keep caches of pending
+/// channels that don't prevent cleanup.
+///
+/// # Examples
+/// ```rust
+/// let (tx, rx) = laika::shotgun::channel::<u8>();
+///
+/// let weak = rx.downgrade();
+/// assert!(weak.upgrade().is_some());
+///
+/// drop(rx);
+/// assert!(weak.upgrade().is_none());
+/// ```
+#[derive(Clone, Debug)]
+pub struct WeakReceiver<T>
+where
+    T: Clone,
+{
+    inner: Weak<_Channel<T>>,
+}
+
+impl<T> WeakReceiver<T>
+where
+    T: Clone,
+{
+    /// Tries to upgrade to a [`Receiver`]. Returns [`None`] if all
+    /// [`Receiver`]s of the channel have been dropped already, or if the
+    /// channel has reached its maximum number of receivers (see
+    /// [`channel_with_max_receivers`]).
+    ///
+    /// # Examples
+    /// ```rust
+    /// let (tx, rx) = laika::shotgun::channel();
+    /// let weak = rx.downgrade();
+    ///
+    /// tx.send(12).unwrap();
+    ///
+    /// let rx1 = weak.upgrade().unwrap();
+    /// assert_eq!(rx1.try_recv(), Ok(12));
+    /// ```
+    pub fn upgrade(&self) -> Option<Receiver<T>> {
+        self.inner
+            .upgrade()
+            .and_then(|inner| Receiver::attach(inner).ok())
+    }
+}
+
 impl<T> Receiver<T>
 where
     T: Clone,
 {
+    /// Creates a receiver of the given channel without a registered waker,
+    /// regardless of [`channel_with_max_receivers`].
+    fn new(inner: Arc<_Channel<T>>) -> Self {
+        inner.receivers.fetch_add(1, Ordering::Relaxed);
+
+        Receiver {
+            inner,
+            key: _Key::new(),
+            done: false,
+        }
+    }
+
+    /// Creates a receiver of the given channel without a registered waker,
+    /// unless the channel has reached its maximum number of receivers.
+    fn attach(inner: Arc<_Channel<T>>) -> Result<Self, ReceiverLimitError> {
+        let max = inner.max_receivers;
+
+        inner
+            .receivers
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |count| {
+                (count < max).then_some(count + 1)
+            })
+            .map_err(|_| ReceiverLimitError)?;
+
+        Ok(Receiver {
+            inner,
+            key: _Key::new(),
+            done: false,
+        })
+    }
+
+    /// Clones the receiver, unless the channel has reached its maximum number
+    /// of receivers (see [`channel_with_max_receivers`]). Returns
+    /// [`ReceiverLimitError`] then.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use laika::shotgun::ReceiverLimitError;
+    ///
+    /// let (tx, rx) = laika::shotgun::channel_with_max_receivers::<u8>(2);
+    ///
+    /// let rx1 = rx.try_clone().unwrap();
+    /// assert_eq!(rx.try_clone(), Err(ReceiverLimitError));
+    ///
+    /// drop(rx1);
+    /// assert!(rx.try_clone().is_ok());
+    /// ```
+    pub fn try_clone(&self) -> Result<Self, ReceiverLimitError> {
+        Receiver::attach(Arc::clone(&self.inner))
+    }
+
+    /// Creates a receiver of an already completed channel that holds `value`.
+    /// Useful for caches and test doubles where the value is known up front,
+    /// but a [`Receiver`] is expected.
+    ///
+    /// As there is no [`Sender`], [`Receiver::is_sender_alive`] returns
+    /// `false`.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use laika::shotgun::Receiver;
+    ///
+    /// let rx = Receiver::ready(12);
+    ///
+    /// assert_eq!(rx.try_recv(), Ok(12));
+    /// assert!(!rx.is_sender_alive());
+    /// ```
+    pub fn ready(value: T) -> Self {
+        let channel = Arc::new(_Channel {
+            value: OnceCell::from(value),
+            state: AtomicU8::new(SENT),
+            senders: AtomicUsize::new(0),
+            receivers: AtomicUsize::new(0),
+            max_receivers: usize::MAX,
+            #[cfg(feature = "std")]
+            poison_policy: PoisonPolicy::Recover,
+            wakers: Mutex::new(_Wakers::default()),
+            callbacks: Mutex::new(_Callbacks::default()),
+            observed: AtomicBool::new(false),
+            ack: Mutex::new(None),
+            #[cfg(feature = "std")]
+            condvar: Condvar::new(),
+            #[cfg(feature = "std")]
+            metrics: observer::Metrics::new(None),
+            #[cfg(all(feature = "std", not(laika_loom)))]
+            alarm: OnceLock::new(),
+        });
+
+        // The value counts as sent right away
+        #[cfg(feature = "std")]
+        if let Some(metrics) = &channel.metrics {
+            metrics.sent();
+        }
+
+        Receiver::new(channel)
+    }
+
     /// Try to receive a value from the channel, if it has been sent.
     /// As this is a oneshot receiver, only one value can be received.
-    /// This function is **non-blocking** and just returns [`None`] if no value
-    /// has been sent.
-    ///
-    /// # Panics
+    /// This function is **non-blocking** and returns
+    /// [`TryRecvError::Empty`] if no value has been sent yet or
+    /// [`TryRecvError::Closed`] if the [`Sender`] has been dropped without
+    /// sending a value.
     ///
-    /// Panics if mutex is poisened due to another thread panicking while using
-    /// inner receiver too.
+    /// Only atomics are checked, no lock is taken (except once per channel to
+    /// acknowledge the value, see [`Sender::send_acknowledged`]). So polling
+    /// this in a tight loop from many threads doesn't serialize the readers.
     ///
     /// # Examples
     /// ```rust
+    /// use laika::shotgun::TryRecvError;
+    ///
     /// let (mut tx, rx) = laika::shotgun::channel();
     ///
     /// // Initialy, oneshot receiver has no value
-    /// assert_eq!(rx.try_recv(), None);
+    /// assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
     ///
     /// // Send a value
-    /// tx.send(12);
+    /// tx.send(12).unwrap();
     ///
     /// // Now, oneshot receiver has the value
-    /// assert_eq!(rx.try_recv(), Some(12));
+    /// assert_eq!(rx.try_recv(), Ok(12));
     /// // Value is kept after being received
-    /// assert_eq!(rx.try_recv(), Some(12));
+    /// assert_eq!(rx.try_recv(), Ok(12));
+    /// ```
+    ///
+    /// ## Sender is dropped without sending
+    /// ```rust
+    /// use laika::shotgun::TryRecvError;
+    ///
+    /// let (tx, rx) = laika::shotgun::channel::<u8>();
+    ///
+    /// drop(tx);
+    ///
+    /// // No value will ever be received
+    /// assert_eq!(rx.try_recv(), Err(TryRecvError::Closed));
     /// ```
-    pub fn try_recv(&self) -> Option<T>
+    pub fn try_recv(&self) -> Result<T, TryRecvError>
     where
         T: Clone,
     {
-        self.inner
-            .as_ref()
-            .lock()
-            .expect("Mutex is poisoned")
-            .try_recv()
+        self.inner.try_recv()
+    }
+
+    /// Try to receive a value from the channel, falling back to
+    /// [`T::default()`](Default::default) if the [`Sender`] has been dropped
+    /// without sending a value. Returns [`None`] if no value has been sent
+    /// yet. This function is **non-blocking**.
+    ///
+    /// # Examples
+    /// ```rust
+    /// let (tx, rx) = laika::shotgun::channel::<Vec<u8>>();
+    ///
+    /// assert_eq!(rx.try_recv_or_default(), None);
+    ///
+    /// drop(tx);
+    /// assert_eq!(rx.try_recv_or_default(), Some(Vec::new()));
+    /// ```
+    pub fn try_recv_or_default(&self) -> Option<T>
+    where
+        T: Default,
+    {
+        match self.try_recv() {
+            Ok(value) => Some(value),
+            Err(TryRecvError::Closed) => Some(T::default()),
+            Err(TryRecvError::Empty) => None,
+        }
+    }
+
+    /// Runs `f` with a reference to the value, if it has been sent, and
+    /// returns its result. Unlike [`Receiver::try_recv`], the value isn't
+    /// cloned, which helps with large payloads. Doesn't take any lock, so `f`
+    /// may take its time.
+    ///
+    /// Returns [`None`] if no value has been sent (yet).
+    ///
+    /// # Examples
+    /// ```rust
+    /// let (tx, rx) = laika::shotgun::channel::<Vec<u8>>();
+    ///
+    /// assert_eq!(rx.with_value(|snapshot| snapshot.len()), None);
+    ///
+    /// tx.send(vec![0; 1024 * 1024]).unwrap();
+    ///
+    /// assert_eq!(rx.with_value(|snapshot| snapshot.len()), Some(1024 * 1024));
+    /// ```
+    pub fn with_value<R>(&self, f: impl FnOnce(&T) -> R) -> Option<R> {
+        self.inner.observe().map(f)
+    }
+
+    /// Polls for the value without consuming the receiver, to embed the
+    /// channel into hand-written [`Future`]s or streams.
+    ///
+    /// Returns [`Poll::Ready`] with the value once it has been sent (or with
+    /// [`RecvError`] if the [`Sender`] has been dropped without sending a
+    /// value). Otherwise, returns [`Poll::Pending`] and registers the waker of
+    /// `cx` to be woken up once the value is sent.
+    ///
+    /// # Note
+    /// Every receiver registers a single waker only, so only the waker passed
+    /// to the most recent call is woken up. Use a cloned receiver per task
+    /// that waits for the value.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use laika::shotgun::Receiver;
+    /// use std::{
+    ///     future::Future,
+    ///     pin::Pin,
+    ///     task::{Context, Poll},
+    /// };
+    ///
+    /// /// Future that resolves to the doubled value of a channel
+    /// struct Doubled(Receiver<u8>);
+    ///
+    /// impl Future for Doubled {
+    ///     type Output = Option<u8>;
+    ///
+    ///     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+    ///         self.0.poll_recv(cx).map(|result| result.ok().map(|v| v * 2))
+    ///     }
+    /// }
+    ///
+    /// let (tx, rx) = laika::shotgun::channel();
+    /// let doubled = Doubled(rx);
+    ///
+    /// tx.send(21).unwrap();
+    /// # let waker = std::task::Waker::noop();
+    /// # let mut cx = Context::from_waker(&waker);
+    /// # let mut doubled = std::pin::pin!(doubled);
+    /// # assert_eq!(doubled.as_mut().poll(&mut cx), Poll::Ready(Some(42)));
+    /// ```
+    pub fn poll_recv(&self, cx: &mut Context<'_>) -> Poll<Result<T, RecvError>> {
+        self.inner.poll_recv(&self.key, cx)
+    }
+
+    /// Registers a callback that is called with a reference to the value once
+    /// it has been sent, for purely callback-driven code (e.g. GUI or event
+    /// loop glue) that has no task to await the value in.
+    ///
+    /// If the value has been sent already, `f` is called right away on the
+    /// current thread. Otherwise, it's called by the thread sending the value,
+    /// after all waiting receivers have been woken up. If the channel is closed
+    /// without a value, `f` is dropped without being called.
+    ///
+    /// A registered callback keeps the channel alive (so it isn't
+    /// [disconnected](Sender::is_disconnected)) until it has been called or
+    /// dropped, so this receiver may be dropped afterwards.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use std::sync::mpsc;
+    ///
+    /// let (tx, rx) = laika::shotgun::channel::<u8>();
+    /// let (events, inbox) = mpsc::channel();
+    ///
+    /// rx.on_receive(move |value| events.send(*value).unwrap());
+    /// drop(rx);
+    ///
+    /// tx.send(12).unwrap();
+    /// assert_eq!(inbox.recv(), Ok(12));
+    /// ```
+    pub fn on_receive<F>(&self, f: F)
+    where
+        F: FnOnce(&T) + Send + 'static,
+        T: Send + Sync + 'static,
+    {
+        // The callback holds its own reference to the channel, so it still
+        // fires if all receivers are gone. This cycle is broken as soon as the
+        // channel completes or closes, as callbacks are taken out of it then.
+        let channel = Arc::clone(&self.inner);
+
+        self.inner.on_receive(Box::new(move |value| {
+            f(value);
+            drop(channel);
+        }));
+    }
+
+    /// Returns whether `self` and `other` are receivers of the same channel.
+    ///
+    /// This is also what [`PartialEq`] and [`Hash`] of [`Receiver`] are based
+    /// on, so receivers can be deduplicated in e.g. a `HashSet`.
+    ///
+    /// # Examples
+    /// ```rust
+    /// let (_tx, rx) = laika::shotgun::channel::<u8>();
+    /// let (_tx1, rx1) = laika::shotgun::channel::<u8>();
+    ///
+    /// assert!(rx.same_channel(&rx.clone()));
+    /// assert!(!rx.same_channel(&rx1));
+    /// ```
+    pub fn same_channel(&self, other: &Receiver<T>) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+
+    /// Creates a [`WeakReceiver`] that doesn't keep the channel state alive.
+    ///
+    /// # Examples
+    /// ```rust
+    /// let (tx, rx) = laika::shotgun::channel::<u8>();
+    ///
+    /// let weak = rx.downgrade();
+    /// assert_eq!(tx.receiver_count(), 1);
+    /// ```
+    pub fn downgrade(&self) -> WeakReceiver<T> {
+        WeakReceiver {
+            inner: Arc::downgrade(&self.inner),
+        }
+    }
+
+    /// Returns whether a [`Sender`] may still send a value on the channel.
+    /// This is `false` once one of the (cloneable) senders has sent a value,
+    /// or once the channel has been closed without one, e.g. because every
+    /// sender has been dropped.
+    ///
+    /// Long-lived consumers can use this to stop waiting early, e.g. when the
+    /// producer has gone away without sending.
+    ///
+    /// # Examples
+    /// ```rust
+    /// let (tx, rx) = laika::shotgun::channel::<u8>();
+    ///
+    /// assert!(rx.is_sender_alive());
+    ///
+    /// drop(tx);
+    /// assert!(!rx.is_sender_alive());
+    /// ```
+    pub fn is_sender_alive(&self) -> bool {
+        matches!(self.inner.state.load(Ordering::Acquire), EMPTY | SENDING)
+    }
+
+    /// Returns the state of the channel, so supervisory code can report
+    /// precisely why a consumer is (not) blocked. Doesn't take any lock.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use laika::shotgun::ChannelState;
+    ///
+    /// let (tx, rx) = laika::shotgun::channel::<u8>();
+    /// assert_eq!(rx.state(), ChannelState::Empty);
+    ///
+    /// drop(tx);
+    /// assert_eq!(rx.state(), ChannelState::Closed);
+    ///
+    /// let rx = laika::shotgun::Receiver::ready(12);
+    /// assert_eq!(rx.state(), ChannelState::Sent);
+    /// ```
+    pub fn state(&self) -> ChannelState {
+        self.inner.state()
+    }
+
+    /// Receive a value from the channel, blocking the current thread until
+    /// the value has been sent. If the [`Sender`] is dropped without sending a
+    /// value, [`RecvError`] is returned instead.
+    ///
+    /// This function parks the thread (using a condition variable) instead of
+    /// spinning, so it's meant to be used from plain threads. **Don't** call it
+    /// from within an async runtime; await the receiver there instead.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use std::thread;
+    ///
+    /// let (tx, rx) = laika::shotgun::channel();
+    ///
+    /// let thread = thread::spawn(move || rx.recv_blocking());
+    ///
+    /// tx.send(12).unwrap();
+    ///
+    /// assert_eq!(thread.join().unwrap(), Ok(12));
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn recv_blocking(&self) -> Result<T, RecvError> {
+        let mut wakers = self.inner.lock();
+
+        loop {
+            match self.inner.try_recv() {
+                Ok(value) => return Ok(value),
+                Err(TryRecvError::Closed) => return Err(RecvError),
+                Err(TryRecvError::Empty) => {
+                    wakers = self.inner.condvar.wait(wakers);
+                }
+            }
+        }
+    }
+
+    /// Receive a value from the channel, blocking the current thread for at
+    /// most `timeout`. Returns [`RecvTimeoutError::Timeout`] if no value has
+    /// been sent in time or [`RecvTimeoutError::Closed`] if the [`Sender`] has
+    /// been dropped without sending a value.
+    ///
+    /// See [`Receiver::recv_blocking`] for notes on blocking.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use laika::shotgun::RecvTimeoutError;
+    /// use std::time::Duration;
+    ///
+    /// let (tx, rx) = laika::shotgun::channel();
+    ///
+    /// // Nothing sent yet, so this times out
+    /// assert_eq!(
+    ///     rx.recv_timeout(Duration::from_millis(10)),
+    ///     Err(RecvTimeoutError::Timeout)
+    /// );
+    ///
+    /// tx.send(12).unwrap();
+    ///
+    /// assert_eq!(rx.recv_timeout(Duration::from_millis(10)), Ok(12));
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<T, RecvTimeoutError> {
+        match Instant::now().checked_add(timeout) {
+            Some(deadline) => self.recv_deadline(deadline),
+            // Deadline is too far in the future to be represented, so it's
+            // effectively no deadline at all.
+            None => self.recv_blocking().map_err(RecvTimeoutError::from),
+        }
+    }
+
+    /// Receive a value from the channel, blocking the current thread until
+    /// `deadline` is reached at most. Returns [`RecvTimeoutError::Timeout`] if
+    /// no value has been sent in time or [`RecvTimeoutError::Closed`] if the
+    /// [`Sender`] has been dropped without sending a value.
+    ///
+    /// See [`Receiver::recv_blocking`] for notes on blocking.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use laika::shotgun::RecvTimeoutError;
+    /// use std::time::{Duration, Instant};
+    ///
+    /// let (tx, rx) = laika::shotgun::channel::<u8>();
+    ///
+    /// let deadline = Instant::now() + Duration::from_millis(10);
+    /// assert_eq!(rx.recv_deadline(deadline), Err(RecvTimeoutError::Timeout));
+    ///
+    /// drop(tx);
+    ///
+    /// let deadline = Instant::now() + Duration::from_millis(10);
+    /// assert_eq!(rx.recv_deadline(deadline), Err(RecvTimeoutError::Closed));
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn recv_deadline(&self, deadline: Instant) -> Result<T, RecvTimeoutError> {
+        let mut wakers = self.inner.lock();
+
+        loop {
+            match self.inner.try_recv() {
+                Ok(value) => return Ok(value),
+                Err(TryRecvError::Closed) => return Err(RecvTimeoutError::Closed),
+                Err(TryRecvError::Empty) => {
+                    let now = Instant::now();
+
+                    if now >= deadline {
+                        return Err(RecvTimeoutError::Timeout);
+                    }
+
+                    wakers = self.inner.condvar.wait_timeout(wakers, deadline - now);
+                }
+            }
+        }
     }
 
     /// Receive a value from the channel.
-    /// Waits until value has been sent and then returns it.
+    /// Waits until value has been sent and then returns it. If the [`Sender`]
+    /// is dropped without sending a value, [`RecvError`] is returned instead.
     /// This function is blocking asynchronously.
     ///
+    /// Borrows the receiver, so one that's stored somewhere (e.g. in a struct
+    /// field) can be awaited from a method without cloning it first. Like
+    /// [`Recv`], every call registers its own waker.
+    ///
     /// # Note
-    /// You can directly [`Future`]'s `.await` on the receiver too.
+    /// You can directly [`Future`]'s `.await` on the receiver too, which
+    /// consumes it.
     ///
     /// # Examples
     /// (*Note that this won't compile because no async runtime exists here.*)
@@ -152,14 +785,123 @@ where
     /// std::thread::sleep(std::time::Duration::from_secs(1));
     ///
     /// // Send a value
-    /// tx.send(());
+    /// tx.send(()).unwrap();
     ///
     /// // Now, oneshot receiver has the value
     /// assert_eq!(fun1.await, 1);
     /// ```
-    pub async fn recv(self) -> T {
+    pub async fn recv(&self) -> Result<T, RecvError> {
         self.await
     }
+
+    /// Receive a value from the channel asynchronously, falling back to the
+    /// value returned by `f` if the [`Sender`] is dropped without sending a
+    /// value. `f` is only called in that case.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # tokio::runtime::Builder::new_current_thread()
+    /// #     .build()
+    /// #     .unwrap()
+    /// #     .block_on(async {
+    /// let (tx, rx) = laika::shotgun::channel();
+    ///
+    /// drop(tx);
+    ///
+    /// let config = rx.recv_or_else(|| String::from("defaults")).await;
+    /// assert_eq!(config, "defaults");
+    /// # });
+    /// ```
+    pub async fn recv_or_else<F>(self, f: F) -> T
+    where
+        F: FnOnce() -> T,
+    {
+        self.await.unwrap_or_else(|RecvError| f())
+    }
+
+    /// Receive a value from the channel, waiting asynchronously for at most
+    /// `timeout`. Resolves to [`RecvTimeoutError::Timeout`] if no value has
+    /// been sent in time or [`RecvTimeoutError::Closed`] if the [`Sender`] has
+    /// been dropped without sending a value.
+    ///
+    /// Uses tokio's timer, so this must be awaited within a tokio runtime that
+    /// has the time driver enabled. Only available with the `tokio` feature.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use laika::shotgun::RecvTimeoutError;
+    /// use std::time::Duration;
+    ///
+    /// # tokio::runtime::Builder::new_current_thread()
+    /// #     .enable_time()
+    /// #     .build()
+    /// #     .unwrap()
+    /// #     .block_on(async {
+    /// let (tx, rx) = laika::shotgun::channel::<u8>();
+    ///
+    /// let result = rx.clone().recv_timeout_async(Duration::from_millis(10)).await;
+    /// assert_eq!(result, Err(RecvTimeoutError::Timeout));
+    ///
+    /// tx.send(12).unwrap();
+    ///
+    /// let result = rx.recv_timeout_async(Duration::from_millis(10)).await;
+    /// assert_eq!(result, Ok(12));
+    /// # });
+    /// ```
+    #[cfg(feature = "tokio")]
+    pub async fn recv_timeout_async(self, timeout: Duration) -> Result<T, RecvTimeoutError> {
+        match tokio::time::timeout(timeout, self).await {
+            Ok(Ok(value)) => Ok(value),
+            Ok(Err(RecvError)) => Err(RecvTimeoutError::Closed),
+            Err(_) => Err(RecvTimeoutError::Timeout),
+        }
+    }
+
+    /// Receive a value from the channel asynchronously, unless `cancel`
+    /// completes first, e.g. to gate a oneshot on a shutdown signal without
+    /// `select!` boilerplate. Resolves to [`RecvUntilError::Cancelled`] if
+    /// `cancel` completes before a value has been sent or
+    /// [`RecvUntilError::Closed`] if the [`Sender`] has been dropped without
+    /// sending a value.
+    ///
+    /// If the value is available already, it's returned without polling
+    /// `cancel`. Works with any runtime.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use laika::shotgun::RecvUntilError;
+    ///
+    /// # tokio::runtime::Builder::new_current_thread()
+    /// #     .build()
+    /// #     .unwrap()
+    /// #     .block_on(async {
+    /// let (shutdown_tx, shutdown_rx) = laika::shotgun::channel::<()>();
+    /// let (tx, rx) = laika::shotgun::channel::<u8>();
+    ///
+    /// shutdown_tx.send(()).unwrap();
+    ///
+    /// let result = rx.recv_until(shutdown_rx).await;
+    /// assert_eq!(result, Err(RecvUntilError::Cancelled));
+    /// # });
+    /// ```
+    pub async fn recv_until<F>(self, cancel: F) -> Result<T, RecvUntilError>
+    where
+        F: Future,
+    {
+        let mut cancel = pin::pin!(cancel);
+
+        future::poll_fn(|cx| {
+            if let Poll::Ready(result) = self.poll_recv(cx) {
+                return Poll::Ready(result.map_err(RecvUntilError::from));
+            }
+
+            cancel
+                .as_mut()
+                .poll(cx)
+                .map(|_| Err(RecvUntilError::Cancelled))
+        })
+        .await
+    }
 }
 
 impl<T> Sender<T>
@@ -169,6 +911,11 @@ where
     /// Send a value to all receivers of the channel.
     /// As this is a oneshot sender, only one value can be sent.
     ///
+    /// Returns [`SendError::Disconnected`] containing the value if all
+    /// [`Receiver`]s have been dropped already, so it can be reclaimed, or
+    /// [`SendError::AlreadySent`] if a clone of this sender has sent a value
+    /// already.
+    ///
     /// # Examples
     /// ## Send a value
     ///
@@ -176,251 +923,2489 @@ where
     /// let (mut tx, rx) = laika::shotgun::channel();
     ///
     /// // Send a value
-    /// tx.send(12);
+    /// tx.send(12).unwrap();
     /// ```
-    pub fn send(self, value: T) {
-        self.inner.send(value);
-    }
-}
-
-/// Inner receiver of a [`channel`]
-#[derive(Clone, Debug)]
-struct _Receiver<T>
+    ///
+    /// ## All receivers are dropped
+    ///
+    /// ```rust
+    /// use laika::shotgun::SendError;
+    ///
+    /// let (mut tx, rx) = laika::shotgun::channel();
+    ///
+    /// drop(rx);
+    ///
+    /// // Value is given back
+    /// assert_eq!(tx.send(12), Err(SendError::Disconnected(12)));
+    /// ```
+    pub fn send(self, value: T) -> Result<(), SendError<T>> {
+        self.inner.send(value)
+    }
+
+    /// Send the value returned by `f` to all receivers of the channel. `f` is
+    /// only called if at least one [`Receiver`] still exists, avoiding
+    /// expensive work when nobody is listening.
+    ///
+    /// Returns [`SendError`] containing `f` (without having called it) if all
+    /// [`Receiver`]s have been dropped already or a clone of this sender has
+    /// sent a value already.
+    ///
+    /// # Examples
+    /// ```rust
+    /// let (tx, rx) = laika::shotgun::channel();
+    ///
+    /// drop(rx);
+    ///
+    /// // Closure isn't called as there are no receivers
+    /// assert!(tx.send_with(|| unreachable!("nobody is listening")).is_err());
+    ///
+    /// let (tx, rx) = laika::shotgun::channel();
+    ///
+    /// tx.send_with(|| 12).unwrap();
+    /// assert_eq!(rx.try_recv(), Ok(12));
+    /// ```
+    pub fn send_with<F>(self, f: F) -> Result<(), SendError<F>>
+    where
+        F: FnOnce() -> T,
+    {
+        self.inner.send_with(f)
+    }
+
+    /// Send a value to all receivers of the channel and return an
+    /// [`AckReceiver`] that resolves once the value has been observed by the
+    /// first receiver, i.e. it has been returned by [`Receiver::try_recv`],
+    /// [`Receiver::with_value`] or by awaiting (or blocking on) a receiver.
+    ///
+    /// Meant for handoff protocols where the producer must not tear down
+    /// resources before a consumer has picked up the value. If all receivers
+    /// are dropped without observing the value, the [`AckReceiver`] is closed.
+    ///
+    /// Returns [`SendError`] containing the value if it couldn't be sent, see
+    /// [`Sender::send`].
+    ///
+    /// # Examples
+    /// ```rust
+    /// use laika::shotgun::TryRecvError;
+    ///
+    /// let (tx, rx) = laika::shotgun::channel();
+    ///
+    /// let ack = tx.send_acknowledged(12).unwrap();
+    /// assert_eq!(ack.try_recv(), Err(TryRecvError::Empty));
+    ///
+    /// assert_eq!(rx.try_recv(), Ok(12));
+    /// assert_eq!(ack.try_recv(), Ok(()));
+    /// ```
+    pub fn send_acknowledged(self, value: T) -> Result<AckReceiver, SendError<T>> {
+        self.inner.send_acknowledged(value)
+    }
+
+    /// Drive `future` to completion and send its output to all receivers of
+    /// the channel, so resolving a channel from an async computation doesn't
+    /// need a hand-written wrapper task. The returned future can be awaited or
+    /// spawned directly.
+    ///
+    /// Resolves to [`SendError`] containing the output if it couldn't be sent,
+    /// see [`Sender::send`].
+    ///
+    /// # Examples
+    /// ```rust
+    /// # tokio::runtime::Builder::new_current_thread()
+    /// #     .build()
+    /// #     .unwrap()
+    /// #     .block_on(async {
+    /// async fn fetch_config() -> &'static str {
+    ///     "config"
+    /// }
+    ///
+    /// let (tx, rx) = laika::shotgun::channel();
+    ///
+    /// tokio::spawn(tx.complete_with(fetch_config()));
+    ///
+    /// assert_eq!(rx.await, Ok("config"));
+    /// # });
+    /// ```
+    pub async fn complete_with<F>(self, future: F) -> Result<(), SendError<T>>
+    where
+        F: Future<Output = T>,
+    {
+        let value = future.await;
+        self.send(value)
+    }
+
+    /// Returns the number of [`Receiver`]s of the channel that currently
+    /// exist. Receivers that are being awaited count as well.
+    ///
+    /// # Examples
+    /// ```rust
+    /// let (tx, rx) = laika::shotgun::channel::<u8>();
+    ///
+    /// assert_eq!(tx.receiver_count(), 1);
+    ///
+    /// let rx1 = rx.clone();
+    /// assert_eq!(tx.receiver_count(), 2);
+    ///
+    /// drop(rx);
+    /// drop(rx1);
+    /// assert_eq!(tx.receiver_count(), 0);
+    /// ```
+    pub fn receiver_count(&self) -> usize {
+        self.inner
+            .receiver
+            .upgrade()
+            .map_or(0, |channel| channel.receivers.load(Ordering::Relaxed))
+    }
+
+    /// Returns whether all [`Receiver`]s (and [`Receiver::on_receive`]
+    /// callbacks) of the channel have been dropped. If so, a sent value would
+    /// never be received, so producers can skip computing it.
+    ///
+    /// # Examples
+    /// ```rust
+    /// let (tx, rx) = laika::shotgun::channel::<u8>();
+    ///
+    /// assert!(!tx.is_disconnected());
+    ///
+    /// drop(rx);
+    /// assert!(tx.is_disconnected());
+    /// ```
+    pub fn is_disconnected(&self) -> bool {
+        self.inner.receiver.strong_count() == 0
+    }
+}
+
+impl<T, E> Sender<Result<T, E>>
+where
+    T: Clone,
+    E: Clone,
+{
+    /// Closes a channel of [`Result`]s with a typed `reason` (e.g. a timeout
+    /// or an upstream failure), by sending it as error. Unlike dropping the
+    /// sender, receivers get to know why no value will be sent: they receive
+    /// `Ok(Err(reason))` instead of [`RecvError`].
+    ///
+    /// Returns [`SendError`] containing the reason if it couldn't be sent, see
+    /// [`Sender::send`].
+    ///
+    /// # Examples
+    /// ```rust
+    /// #[derive(Clone, Debug, PartialEq)]
+    /// enum Shed {
+    ///     Overloaded,
+    /// }
+    ///
+    /// let (tx, rx) = laika::shotgun::channel::<Result<u8, Shed>>();
+    ///
+    /// tx.close_with(Shed::Overloaded).unwrap();
+    ///
+    /// assert_eq!(rx.try_recv(), Ok(Err(Shed::Overloaded)));
+    /// ```
+    pub fn close_with(self, reason: E) -> Result<(), SendError<E>> {
+        self.send(Err(reason)).map_err(|error| {
+            error.map(|result| match result {
+                Err(reason) => reason,
+                Ok(_) => unreachable!("Only the reason has been sent"),
+            })
+        })
+    }
+}
+
+/// Receiver of the acknowledgement returned by [`Sender::send_acknowledged`]
+///
+/// Receives `()` once the sent value has been observed by a [`Receiver`] and is
+/// closed if all receivers are dropped without observing it.
+pub type AckReceiver = Receiver<()>;
+
+/// State of a [`channel`], returned by [`Receiver::state`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ChannelState {
+    /// No value has been sent yet, but a [`Sender`] still exists.
+    Empty,
+    /// The value has been sent.
+    Sent,
+    /// All [`Sender`]s have been dropped without sending a value.
+    Closed,
+}
+
+/// Order in which pending receivers are woken up once the value is sent or
+/// the channel is closed, see [`channel_with_wake_order`]
+///
+/// Only applies to async receivers. Threads blocking in e.g.
+/// [`Receiver::recv_blocking`] are woken up by the operating system in no
+/// particular order.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum WakeOrder {
+    /// Receivers that started waiting first are woken up first, for fairness
+    /// in latency-sensitive pipelines.
+    #[default]
+    Fifo,
+    /// Receivers that started waiting last are woken up first, whose data is
+    /// the most likely to still be in the cache.
+    Lifo,
+}
+
+/// No value has been sent yet and the [`_Sender`] still exists.
+const EMPTY: u8 = 0;
+/// The value has been sent.
+const SENT: u8 = 1;
+/// The [`_Sender`] has been dropped without sending a value.
+const CLOSED: u8 = 2;
+/// A [`_Sender`] claimed the channel and is about to set the value. Until it's
+/// set, the channel can neither be closed nor expire.
+const SENDING: u8 = 3;
+/// The deadline of a [`channel_with_deadline`] passed without a value having
+/// been sent.
+const EXPIRED: u8 = 4;
+
+/// Shared state of a [`channel`], referenced by all [`Receiver`]s and the
+/// [`_Sender`].
+///
+/// Everything a channel needs is stored inline, so creating a channel performs
+/// a single allocation (the [`Arc`] holding this struct). The value, locks,
+/// callbacks and the first [`INLINE_WAKERS`] wakers don't allocate
+/// themselves; only registering further wakers or callbacks does.
+#[derive(Debug)]
+struct _Channel<T>
 where
     T: Clone,
 {
-    /// Value that was sent by [`_Sender`]
-    value: Option<T>,
+    /// Value that was sent by [`_Sender`]. Once set, it can be read without
+    /// taking any lock.
+    value: OnceCell<T>,
+    /// State of the channel, one of [`EMPTY`], [`SENDING`], [`SENT`],
+    /// [`CLOSED`] or [`EXPIRED`]
+    state: AtomicU8,
+    /// Number of [`_Sender`]s that exist, the channel is closed once it drops
+    /// to zero without a value having been sent
+    senders: AtomicUsize,
+    /// Number of [`Receiver`]s that exist
+    receivers: AtomicUsize,
+    /// Maximum number of [`Receiver`]s, see [`channel_with_max_receivers`]
+    max_receivers: usize,
+    /// How poisoned locks are handled. Only [`std::sync`] locks can be
+    /// poisoned.
+    #[cfg(feature = "std")]
+    poison_policy: PoisonPolicy,
     /// Wakers that will be woken up when value is sent by [`_Sender`]
-    wakers: Vec<Waker>,
+    wakers: Mutex<_Wakers>,
+    /// Callbacks registered by [`Receiver::on_receive`] that will be called
+    /// when value is sent by [`_Sender`]
+    callbacks: Mutex<_Callbacks<T>>,
+    /// Whether a [`Receiver`] has observed the value already
+    observed: AtomicBool,
+    /// Acknowledgement requested by [`Sender::send_acknowledged`], sent once
+    /// the value has been observed
+    ack: Mutex<Option<Sender<()>>>,
+    /// Notified when the value is sent or the channel is closed, so that
+    /// threads blocking in [`Receiver::recv_blocking`] wake up.
+    #[cfg(feature = "std")]
+    condvar: Condvar,
+    /// Lifecycle reported to the [`ChannelObserver`], if one has been
+    /// installed when the channel was created
+    #[cfg(feature = "std")]
+    metrics: Option<observer::Metrics>,
+    /// Alarm expiring the channel at its deadline, see
+    /// [`channel_with_deadline`]. The timer only refers to the alarm, so it
+    /// doesn't keep the channel allocated until the deadline.
+    #[cfg(all(feature = "std", not(laika_loom)))]
+    alarm: OnceLock<Arc<Alarm>>,
+}
+
+/// Number of wakers that are stored inline in [`_Wakers`]
+const INLINE_WAKERS: usize = 4;
+
+/// Slot of a registered waker together with its sequence number, which
+/// tells the order of registration. [`None`] marks a free slot.
+type _Slot = Option<(usize, Waker)>;
+
+/// Wakers of pending [`Receiver`]s, stored as a slab so that every receiver
+/// can update or remove its own waker by key.
+///
+/// The first [`INLINE_WAKERS`] slots are stored inline, so the common case of a
+/// few receivers never allocates. Only further wakers spill to the heap.
+#[derive(Debug, Default)]
+struct _Wakers {
+    /// Inline slots of registered wakers
+    inline: [_Slot; INLINE_WAKERS],
+    /// Slots of registered wakers beyond the inline ones
+    spilled: Vec<_Slot>,
+    /// Indices of free slots in `spilled` that can be reused
+    free: Vec<usize>,
+    /// Sequence number of the next registered waker
+    next: usize,
+    /// Order in which the wakers are woken up
+    order: WakeOrder,
+}
+
+impl _Wakers {
+    /// Creates an empty slab whose wakers are woken up in `order`, with room
+    /// for `capacity` wakers before allocating.
+    fn with_capacity(order: WakeOrder, capacity: usize) -> Self {
+        _Wakers {
+            spilled: Vec::with_capacity(capacity.saturating_sub(INLINE_WAKERS)),
+            order,
+            ..Default::default()
+        }
+    }
+
+    /// Returns the slot at `index`, which covers the inline slots first and
+    /// the spilled ones afterwards.
+    fn slot_mut(&mut self, index: usize) -> Option<&mut _Slot> {
+        match index.checked_sub(INLINE_WAKERS) {
+            None => self.inline.get_mut(index),
+            Some(index) => self.spilled.get_mut(index),
+        }
+    }
+
+    /// Registers `waker` under `key`. If `key` is [`None`], a new slot is
+    /// taken and `key` is set to it, otherwise the waker in the existing slot
+    /// is replaced (if it wouldn't wake the same task anyway). A replaced
+    /// waker keeps its place in the wake order.
+    fn register(&mut self, key: &_Key, waker: &Waker) {
+        if let Some(Some((_, registered))) = key.get().and_then(|key| self.slot_mut(key)) {
+            if !registered.will_wake(waker) {
+                *registered = waker.clone();
+            }
+
+            return;
+        }
+
+        let slot = Some((self.next, waker.clone()));
+        self.next = self.next.wrapping_add(1);
+
+        let index = if let Some(index) = self.inline.iter().position(Option::is_none) {
+            self.inline[index] = slot;
+            index
+        } else if let Some(index) = self.free.pop() {
+            self.spilled[index] = slot;
+            INLINE_WAKERS + index
+        } else {
+            self.spilled.push(slot);
+            INLINE_WAKERS + self.spilled.len() - 1
+        };
+
+        key.set(Some(index));
+    }
+
+    /// Removes the waker registered under `key` and resets `key`.
+    fn deregister(&mut self, key: &_Key) {
+        let Some(index) = key.get() else {
+            return;
+        };
+
+        key.set(None);
+
+        let freed = self
+            .slot_mut(index)
+            .is_some_and(|slot| slot.take().is_some());
+
+        // Free inline slots are found by scanning them, so only spilled slots
+        // are tracked.
+        if let Some(index) = index.checked_sub(INLINE_WAKERS).filter(|_| freed) {
+            self.free.push(index);
+        }
+    }
+
+    /// Removes and returns all registered wakers in the configured
+    /// [`WakeOrder`].
+    ///
+    /// This is only called once the channel has reached its final state, so
+    /// no receiver registers again afterwards and remaining keys are stale.
+    fn take_all(&mut self) -> impl Iterator<Item = Waker> {
+        self.free.clear();
+
+        let order = self.order;
+        let mut inline = mem::take(&mut self.inline);
+        let mut spilled = mem::take(&mut self.spilled);
+
+        // Both parts are sorted in place and merged afterwards, so waking in
+        // order doesn't allocate. Free slots are sorted to either end and
+        // skipped.
+        let sequence = |slot: &_Slot| slot.as_ref().map(|(sequence, _)| *sequence);
+
+        match order {
+            WakeOrder::Fifo => {
+                inline.sort_unstable_by_key(sequence);
+                spilled.sort_unstable_by_key(sequence);
+            }
+            WakeOrder::Lifo => {
+                inline.sort_unstable_by_key(|slot| Reverse(sequence(slot)));
+                spilled.sort_unstable_by_key(|slot| Reverse(sequence(slot)));
+            }
+        }
+
+        let mut inline = inline.into_iter().flatten().peekable();
+        let mut spilled = spilled.into_iter().flatten().peekable();
+
+        iter::from_fn(move || {
+            let inline_first = match (inline.peek(), spilled.peek()) {
+                (Some((a, _)), Some((b, _))) => match order {
+                    WakeOrder::Fifo => a < b,
+                    WakeOrder::Lifo => a > b,
+                },
+                (Some(_), None) => true,
+                (None, _) => false,
+            };
+
+            if inline_first {
+                inline.next()
+            } else {
+                spilled.next()
+            }
+        })
+        .map(|(_, waker)| waker)
+    }
+
+    /// Returns the number of registered wakers.
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.inline.iter().chain(&self.spilled).flatten().count()
+    }
+}
+
+/// Callback registered by [`Receiver::on_receive`]
+type Callback<T> = Box<dyn FnOnce(&T) + Send>;
+
+/// Callbacks of a channel, waiting for the value to be sent.
+struct _Callbacks<T>(Vec<Callback<T>>);
+
+/// Callbacks can't be printed, so only their number is shown.
+impl<T> fmt::Debug for _Callbacks<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("_Callbacks").field(&self.0.len()).finish()
+    }
+}
+
+impl<T> Default for _Callbacks<T> {
+    fn default() -> Self {
+        _Callbacks(Vec::new())
+    }
+}
+
+/// Marks a [`_Key`] that has no waker registered
+const NO_KEY: usize = usize::MAX;
+
+/// Key of a waker registered in [`_Wakers`].
+///
+/// Only accessed while holding the lock of the wakers, being atomic just
+/// allows updating it through a shared reference.
+#[derive(Debug)]
+struct _Key(AtomicUsize);
+
+impl _Key {
+    /// Creates a key without a registered waker.
+    fn new() -> Self {
+        _Key(AtomicUsize::new(NO_KEY))
+    }
+
+    /// Returns the index of the registered waker, if any.
+    fn get(&self) -> Option<usize> {
+        match self.0.load(Ordering::Relaxed) {
+            NO_KEY => None,
+            index => Some(index),
+        }
+    }
+
+    /// Sets the index of the registered waker.
+    fn set(&self, index: Option<usize>) {
+        self.0.store(index.unwrap_or(NO_KEY), Ordering::Relaxed);
+    }
+}
+
+/// Claim of a [`_Sender`] that is about to set the value, see
+/// [`_Channel::claim`]. Releases the claim when dropped, so it's forgotten
+/// once the value has been set.
+struct _Claim<'a>(&'a AtomicU8);
+
+impl Drop for _Claim<'_> {
+    fn drop(&mut self) {
+        self.0.store(EMPTY, Ordering::Release);
+    }
+}
+
+/// Inner sender of a [`channel`]
+#[derive(Debug)]
+struct _Sender<T>
+where
+    T: Clone,
+{
+    /// [`_Channel`] instance that will receive the value and is referecend by
+    /// all [`Receiver`]s. Only held weakly, so the channel state is dropped as
+    /// soon as the last [`Receiver`] is gone.
+    receiver: Weak<_Channel<T>>,
+}
+
+impl<T> _Channel<T>
+where
+    T: Clone,
+{
+    /// Locks the wakers. See [`_Channel::guard`] for how poisoning is handled.
+    fn lock(&self) -> MutexGuard<'_, _Wakers> {
+        self.guard(&self.wakers)
+    }
+
+    /// Locks `mutex` of the channel. Recovers from poisoning (see
+    /// [`Mutex::lock`]), unless the [`PoisonPolicy`] of the channel says
+    /// otherwise.
+    fn guard<'a, U>(&self, mutex: &'a Mutex<U>) -> MutexGuard<'a, U> {
+        let guard = mutex.lock();
+
+        #[cfg(feature = "std")]
+        if self.poison_policy == PoisonPolicy::Propagate
+            && mutex.is_poisoned()
+            && !std::thread::panicking()
+        {
+            drop(guard);
+            panic!("lock of the channel has been poisoned by a panicking thread");
+        }
+
+        guard
+    }
+
+    /// Clones the value (if it has been given by [`_Sender`]) and returns clone
+    /// of it. Doesn't take any lock.
+    fn try_recv(&self) -> Result<T, TryRecvError> {
+        if let Some(value) = self.observe() {
+            return Ok(value.clone());
+        }
+
+        match self.state.load(Ordering::Acquire) {
+            CLOSED | EXPIRED => Err(TryRecvError::Closed),
+            _ => Err(TryRecvError::Empty),
+        }
+    }
+
+    /// Sets the value after the channel has been claimed by
+    /// [`_Channel::claim`].
+    fn set(&self, value: T) {
+        if self.value.set(value).is_err() {
+            unreachable!("Only the claiming sender sets the value");
+        }
+    }
+
+    /// Marks the value as sent after it has been set by a [`_Sender`], waking
+    /// up all waiting [`Receiver`]s.
+    fn complete(&self) {
+        self.state.store(SENT, Ordering::Release);
+
+        #[cfg(feature = "std")]
+        if let Some(metrics) = &self.metrics {
+            metrics.sent();
+        }
+
+        self.wake_all();
+
+        let callbacks = self.take_callbacks();
+
+        if callbacks.is_empty() {
+            return;
+        }
+
+        if let Some(value) = self.observe() {
+            for callback in callbacks {
+                callback(value);
+            }
+        }
+    }
+
+    /// Returns the value (if it has been given by [`_Sender`]) and marks it as
+    /// observed, acknowledging it for [`Sender::send_acknowledged`]. Only
+    /// takes a lock the first time the value is observed.
+    fn observe(&self) -> Option<&T> {
+        let value = self.value.get()?;
+
+        if !self.observed.load(Ordering::Acquire) && !self.observed.swap(true, Ordering::AcqRel) {
+            #[cfg(feature = "std")]
+            if let Some(metrics) = &self.metrics {
+                metrics.first_recv();
+            }
+
+            self.acknowledge();
+        }
+
+        Some(value)
+    }
+
+    /// Sends the acknowledgement of [`Sender::send_acknowledged`], if it has
+    /// been requested and not been sent yet.
+    fn acknowledge(&self) {
+        // Sending wakes up the receivers of the acknowledgement, so it's done
+        // after releasing the lock.
+        let ack = self.guard(&self.ack).take();
+
+        if let Some(ack) = ack {
+            let _ = ack.send(());
+        }
+    }
+
+    /// Returns the current state of the channel.
+    fn state(&self) -> ChannelState {
+        // The value is set before the state is updated, so check it first to
+        // report a sent value as soon as it can be received.
+        if self.value.get().is_some() {
+            return ChannelState::Sent;
+        }
+
+        match self.state.load(Ordering::Acquire) {
+            CLOSED | EXPIRED => ChannelState::Closed,
+            _ => ChannelState::Empty,
+        }
+    }
+
+    /// Claims the channel for sending the value, so that no other sender can
+    /// send one and the channel can't expire in the meantime.
+    fn claim(&self) -> Result<(), SendError<()>> {
+        match self
+            .state
+            .compare_exchange(EMPTY, SENDING, Ordering::AcqRel, Ordering::Acquire)
+        {
+            Ok(_) => Ok(()),
+            Err(CLOSED | EXPIRED) => Err(SendError::Disconnected(())),
+            Err(_) => Err(SendError::AlreadySent(())),
+        }
+    }
+
+    /// Marks the channel as closed if no value has been set, waking up all
+    /// waiting [`Receiver`]s.
+    fn close(&self) {
+        self.close_as(CLOSED);
+    }
+
+    /// Marks the channel as expired if no value has been set, waking up all
+    /// waiting [`Receiver`]s. See [`channel_with_deadline`].
+    #[cfg(all(feature = "std", not(laika_loom)))]
+    fn expire(&self) {
+        self.close_as(EXPIRED);
+    }
+
+    /// Returns whether the channel has expired. See [`channel_with_deadline`].
+    #[cfg(all(feature = "std", not(laika_loom)))]
+    fn is_expired(&self) -> bool {
+        self.state.load(Ordering::Acquire) == EXPIRED
+    }
+
+    /// Moves the channel into the final `state` if no value has been set (or
+    /// is about to be), waking up all waiting [`Receiver`]s.
+    fn close_as(&self, state: u8) {
+        if self
+            .state
+            .compare_exchange(EMPTY, state, Ordering::AcqRel, Ordering::Acquire)
+            .is_ok()
+        {
+            self.wake_all();
+
+            // Dropped outside of the lock, as dropping a callback may run
+            // arbitrary code.
+            drop(self.take_callbacks());
+        }
+    }
+
+    /// Registers a callback to be called with the value once it has been
+    /// sent. Calls it right away if that's the case already and drops it if
+    /// the channel has been closed.
+    fn on_receive(&self, callback: Callback<T>) {
+        if let Some(value) = self.observe() {
+            callback(value);
+            return;
+        }
+
+        let mut callbacks = self.guard(&self.callbacks);
+
+        // Check again while holding the lock, as the callbacks might have been
+        // taken out in the meantime. The state is updated before that, so it
+        // tells whether the callback would still be called.
+        if matches!(self.state.load(Ordering::Acquire), EMPTY | SENDING) {
+            callbacks.0.push(callback);
+            return;
+        }
+
+        drop(callbacks);
+
+        if let Some(value) = self.observe() {
+            callback(value);
+        }
+    }
+
+    /// Removes and returns all registered callbacks. The lock is released
+    /// before returning, so callbacks may register further callbacks.
+    fn take_callbacks(&self) -> Vec<Callback<T>> {
+        mem::take(&mut self.guard(&self.callbacks).0)
+    }
+
+    /// Polls for the value, registering the waker of `cx` under `key` if it
+    /// hasn't been sent yet.
+    fn poll_recv(&self, key: &_Key, cx: &mut Context<'_>) -> Poll<Result<T, RecvError>> {
+        match self.try_recv() {
+            Ok(value) => return Poll::Ready(Ok(value)),
+            Err(TryRecvError::Closed) => return Poll::Ready(Err(RecvError)),
+            Err(TryRecvError::Empty) => {}
+        }
+
+        let mut wakers = self.lock();
+
+        // Check again while holding the lock as the value might have been sent
+        // in the meantime.
+        match self.try_recv() {
+            Ok(value) => Poll::Ready(Ok(value)),
+            Err(TryRecvError::Closed) => Poll::Ready(Err(RecvError)),
+            Err(TryRecvError::Empty) => {
+                wakers.register(key, cx.waker());
+                Poll::Pending
+            }
+        }
+    }
+
+    /// Wakes up all pending [`Receiver`]s, both async and blocking ones.
+    ///
+    /// Takes the lock after the state has been updated, so registering
+    /// receivers either see the updated state or have registered in time to be
+    /// woken up. The wakers are only swapped out while holding the lock and
+    /// woken up after releasing it, so woken up tasks that immediately poll
+    /// again don't contend for the lock with the remaining wake-ups.
+    fn wake_all(&self) {
+        let wakers = self.lock().take_all();
+
+        for waker in wakers {
+            waker.wake();
+        }
+
+        #[cfg(feature = "std")]
+        self.condvar.notify_all();
+
+        // The channel is completed, so it can't expire anymore
+        #[cfg(all(feature = "std", not(laika_loom)))]
+        if let Some(alarm) = self.alarm.get() {
+            alarm.cancel();
+        }
+    }
 }
 
-/// Inner sender of a [`channel`]
-#[derive(Clone, Debug)]
-struct _Sender<T>
-where
-    T: Clone,
-{
-    /// [`_Receiver`] instance that will receive the value and is referecend by
-    /// all [`Receiver`]s.
-    receiver: Option<Arc<Mutex<_Receiver<T>>>>,
-}
+/// Reports the end of the channel's lifecycle to the [`ChannelObserver`].
+#[cfg(feature = "std")]
+impl<T> Drop for _Channel<T>
+where
+    T: Clone,
+{
+    fn drop(&mut self) {
+        if let Some(metrics) = &self.metrics {
+            metrics.dropped(self.state());
+        }
+    }
+}
+
+/// Implement [`Future`] for [`Receiver`] to be able to use it in async
+/// functions.
+///
+/// Polling a receiver again after it has completed doesn't panic, but returns
+/// (a clone of) the value or [`RecvError`] again.
+impl<T> Future for Receiver<T>
+where
+    T: Clone,
+{
+    type Output = Result<T, RecvError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        let result = core::task::ready!(this.poll_recv(cx));
+        this.done = true;
+
+        Poll::Ready(result)
+    }
+}
+
+impl<T> Receiver<T>
+where
+    T: Clone,
+{
+    /// Polls for the value like a stream: yields it once it has been sent and
+    /// [`None`] afterwards (or if the channel is closed without a value).
+    #[cfg(any(feature = "futures", feature = "merge"))]
+    pub(crate) fn poll_once(&mut self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        if self.done {
+            return Poll::Ready(None);
+        }
+
+        let result = core::task::ready!(Pin::new(&mut *self).poll(cx));
+        Poll::Ready(result.ok())
+    }
+}
+
+/// Implement [`futures_core::future::FusedFuture`] for [`Receiver`], so it can
+/// be used in `futures::select!` loops without manual fusing. The receiver is
+/// terminated once it has completed (as a future or stream).
+#[cfg(feature = "futures")]
+impl<T> futures_core::future::FusedFuture for Receiver<T>
+where
+    T: Clone,
+{
+    fn is_terminated(&self) -> bool {
+        self.done
+    }
+}
+
+/// Future that waits for the value of a borrowed [`Receiver`]
+///
+/// Created by awaiting a `&Receiver`, so a receiver that's stored somewhere
+/// (e.g. in a struct field) can be awaited repeatedly without cloning or
+/// consuming it. Every [`Recv`] registers its own waker, so multiple of them
+/// can wait for the same receiver at once.
+///
+/// # Examples
+/// ```rust
+/// use laika::shotgun::Receiver;
+///
+/// struct Worker {
+///     shutdown: Receiver<()>,
+/// }
+///
+/// impl Worker {
+///     async fn wait_for_shutdown(&self) {
+///         let _ = (&self.shutdown).await;
+///     }
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Recv<'a, T>
+where
+    T: Clone,
+{
+    /// Receiver that is waited for
+    receiver: &'a Receiver<T>,
+    /// Key of the waker this future registered while being polled, if any
+    key: _Key,
+}
+
+impl<'a, T> IntoFuture for &'a Receiver<T>
+where
+    T: Clone,
+{
+    type Output = Result<T, RecvError>;
+    type IntoFuture = Recv<'a, T>;
+
+    fn into_future(self) -> Self::IntoFuture {
+        Recv {
+            receiver: self,
+            key: _Key::new(),
+        }
+    }
+}
+
+impl<T> Future for Recv<'_, T>
+where
+    T: Clone,
+{
+    type Output = Result<T, RecvError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.receiver.inner.poll_recv(&self.key, cx)
+    }
+}
+
+/// Removes the registered waker (if any) when a [`Recv`] is dropped.
+impl<T> Drop for Recv<'_, T>
+where
+    T: Clone,
+{
+    fn drop(&mut self) {
+        if self.key.get().is_some() {
+            self.receiver.inner.lock().deregister(&self.key);
+        }
+    }
+}
+
+/// Implement [`futures_core::Stream`] for [`Receiver`], so it can be used with
+/// stream combinators. The stream yields the value once it has been sent and
+/// ends afterwards. If the [`Sender`] is dropped without sending a value, the
+/// stream ends without yielding anything.
+#[cfg(feature = "futures")]
+impl<T> futures_core::Stream for Receiver<T>
+where
+    T: Clone,
+{
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.get_mut().poll_once(cx)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        if self.done {
+            (0, Some(0))
+        } else {
+            (0, Some(1))
+        }
+    }
+}
+
+#[cfg(feature = "futures")]
+impl<T> futures_core::stream::FusedStream for Receiver<T>
+where
+    T: Clone,
+{
+    fn is_terminated(&self) -> bool {
+        self.done
+    }
+}
+
+/// Receivers are equal if they belong to the same channel, see
+/// [`Receiver::same_channel`].
+impl<T> PartialEq for Receiver<T>
+where
+    T: Clone,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.same_channel(other)
+    }
+}
+
+impl<T> Eq for Receiver<T> where T: Clone {}
+
+/// Hashes the identity of the channel, consistent with [`PartialEq`].
+impl<T> Hash for Receiver<T>
+where
+    T: Clone,
+{
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        ptr::hash(Arc::as_ptr(&self.inner), state);
+    }
+}
+
+/// Cloned [`Receiver`]s start without a registered waker.
+///
+/// # Panics
+/// In debug builds, panics if the channel has reached its maximum number of
+/// receivers (see [`channel_with_max_receivers`]). Release builds don't
+/// enforce the limit here; use [`Receiver::try_clone`] for that.
+impl<T> Clone for Receiver<T>
+where
+    T: Clone,
+{
+    fn clone(&self) -> Self {
+        match self.try_clone() {
+            Ok(receiver) => receiver,
+            Err(error) if cfg!(debug_assertions) => panic!("{error}"),
+            Err(ReceiverLimitError) => Receiver::new(Arc::clone(&self.inner)),
+        }
+    }
+}
+
+/// Cloned [`Sender`]s race for sending the value of the same channel.
+impl<T> Clone for Sender<T>
+where
+    T: Clone,
+{
+    fn clone(&self) -> Self {
+        Sender {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+/// Removes the registered waker (if any) when a [`Receiver`] is dropped, e.g.
+/// because it lost a `select!`, so it isn't woken up pointlessly and doesn't
+/// leak.
+impl<T> Drop for Receiver<T>
+where
+    T: Clone,
+{
+    fn drop(&mut self) {
+        self.inner.receivers.fetch_sub(1, Ordering::Relaxed);
+
+        if self.key.get().is_some() {
+            self.inner.lock().deregister(&self.key);
+        }
+    }
+}
+
+impl<T> _Sender<T>
+where
+    T: Clone,
+{
+    /// Returns whether any sender has sent a value on the channel. `false` if
+    /// all [`Receiver`]s have been dropped, as the channel is gone then.
+    fn is_sent(&self) -> bool {
+        self.receiver
+            .upgrade()
+            .is_some_and(|channel| channel.state() == ChannelState::Sent)
+    }
+
+    /// Send a value to all [`Receiver`]s. Returns the value if there are no
+    /// [`Receiver`]s left or another sender has sent a value already.
+    fn send(self, value: T) -> Result<(), SendError<T>> {
+        let Some(channel) = self.receiver.upgrade() else {
+            return Err(SendError::Disconnected(value));
+        };
+
+        if let Err(error) = channel.claim() {
+            return Err(error.map(|()| value));
+        }
+
+        channel.set(value);
+        channel.complete();
+
+        Ok(())
+    }
+
+    /// Send a value to all [`Receiver`]s and return a receiver of the
+    /// acknowledgement that is sent once the value has been observed. Returns
+    /// the value if there are no [`Receiver`]s left or another sender has sent
+    /// a value already.
+    fn send_acknowledged(self, value: T) -> Result<AckReceiver, SendError<T>> {
+        let Some(shared) = self.receiver.upgrade() else {
+            return Err(SendError::Disconnected(value));
+        };
+
+        if let Err(error) = shared.claim() {
+            return Err(error.map(|()| value));
+        }
+
+        shared.set(value);
+
+        // The acknowledgement is only requested after winning the race for
+        // sending, so receivers can't acknowledge the value of another sender.
+        // If the value has been observed in the meantime, the observer might
+        // have missed the request, so it's sent right away.
+        let (ack_tx, ack_rx) = channel();
+        *shared.guard(&shared.ack) = Some(ack_tx);
+
+        if shared.observed.load(Ordering::Acquire) {
+            shared.acknowledge();
+        }
+
+        shared.complete();
+
+        Ok(ack_rx)
+    }
+
+    /// Send the value returned by `f` to all [`Receiver`]s. Returns `f` without
+    /// calling it if there are no [`Receiver`]s left or another sender has sent
+    /// a value already.
+    fn send_with<F>(self, f: F) -> Result<(), SendError<F>>
+    where
+        F: FnOnce() -> T,
+    {
+        let Some(channel) = self.receiver.upgrade() else {
+            return Err(SendError::Disconnected(f));
+        };
+
+        // Only the initializer of the winning sender is called, so `f` is left
+        // untouched if another sender was faster.
+        if let Err(error) = channel.claim() {
+            return Err(error.map(|()| f));
+        }
+
+        // If `f` panics, the claim is released, so the channel is closed once
+        // the sender is dropped while unwinding.
+        let claim = _Claim(&channel.state);
+        let value = f();
+        mem::forget(claim);
+
+        channel.set(value);
+        channel.complete();
+
+        Ok(())
+    }
+}
+
+/// Registers another sender of the channel, if it still exists.
+impl<T> Clone for _Sender<T>
+where
+    T: Clone,
+{
+    fn clone(&self) -> Self {
+        if let Some(channel) = self.receiver.upgrade() {
+            channel.senders.fetch_add(1, Ordering::Relaxed);
+        }
+
+        _Sender {
+            receiver: self.receiver.clone(),
+        }
+    }
+}
+
+/// Closes the channel when the last [`_Sender`] is dropped without a value
+/// having been sent, so that waiting [`Receiver`]s don't wait forever.
+impl<T> Drop for _Sender<T>
+where
+    T: Clone,
+{
+    fn drop(&mut self) {
+        if let Some(channel) = self.receiver.upgrade() {
+            if channel.senders.fetch_sub(1, Ordering::AcqRel) == 1 {
+                channel.close();
+            }
+        }
+    }
+}
+
+/// Creates a one-shot, single producer multiple consumer channel that can be
+/// used to send one value to multiple receivers.
+///
+/// # Examples
+///
+/// ```rust
+/// let (mut tx, rx) = laika::shotgun::channel::<u8>();
+/// // do something with tx and rx
+/// ```
+pub fn channel<T>() -> (Sender<T>, Receiver<T>)
+where
+    T: Clone,
+{
+    Builder::new().build()
+}
+
+/// Creates a [`channel`] whose pending receivers are woken up in the given
+/// [`WakeOrder`] once the value is sent or the channel is closed, instead of
+/// the default first in, first out order.
+///
+/// # Examples
+/// ```rust
+/// use laika::shotgun::WakeOrder;
+///
+/// let (tx, rx) = laika::shotgun::channel_with_wake_order(WakeOrder::Lifo);
+///
+/// tx.send(12).unwrap();
+/// assert_eq!(rx.try_recv(), Ok(12));
+/// ```
+pub fn channel_with_wake_order<T>(order: WakeOrder) -> (Sender<T>, Receiver<T>)
+where
+    T: Clone,
+{
+    Builder::new().wake_order(order).build()
+}
+
+/// Creates a [`channel`] that allows at most `max` [`Receiver`]s to exist at
+/// the same time, to catch accidental unbounded fan-out of large payloads.
+///
+/// [`Receiver::try_clone`] (and [`WeakReceiver::upgrade`]) fail once the
+/// limit has been reached. [`Receiver::clone`] panics then in debug builds.
+///
+/// # Panics
+/// Panics if `max` is zero.
+///
+/// # Examples
+/// ```rust
+/// let (tx, rx) = laika::shotgun::channel_with_max_receivers(2);
+/// let rx1 = rx.try_clone().unwrap();
+///
+/// assert!(rx.try_clone().is_err());
+///
+/// tx.send(12).unwrap();
+/// assert_eq!(rx1.try_recv(), Ok(12));
+/// ```
+pub fn channel_with_max_receivers<T>(max: usize) -> (Sender<T>, Receiver<T>)
+where
+    T: Clone,
+{
+    Builder::new().max_receivers(max).build()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::{string::String, vec, vec::Vec};
+    use tokio::task::JoinSet;
+
+    #[test]
+    fn test_basic() {
+        let (tx, rx) = channel();
+
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+
+        tx.send(()).unwrap();
+
+        assert_eq!(rx.try_recv(), Ok(()));
+        assert_eq!(rx.try_recv(), Ok(()));
+    }
+
+    #[test]
+    fn test_work_without_receiver() {
+        let (tx, rx) = channel();
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+
+        drop(rx);
+
+        assert_eq!(tx.send(()), Err(SendError::Disconnected(())));
+    }
+
+    #[test]
+    fn test_work_without_sender() {
+        let (tx, rx) = channel::<()>();
+
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+
+        drop(tx);
+
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Closed));
+    }
+
+    #[test]
+    fn test_work_with_multiple_receivers() {
+        let (tx, rx) = channel();
+
+        let rx1 = rx.clone();
+        let rx2 = rx.clone();
+
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+        assert_eq!(rx1.try_recv(), Err(TryRecvError::Empty));
+        assert_eq!(rx2.try_recv(), Err(TryRecvError::Empty));
+
+        tx.send(1337).unwrap();
+
+        assert_eq!(rx.try_recv(), Ok(1337));
+        assert_eq!(rx1.try_recv(), Ok(1337));
+        assert_eq!(rx2.try_recv(), Ok(1337));
+    }
+
+    #[test]
+    fn test_works_in_threads() {
+        use std::thread;
+        use std::time;
+
+        let (tx, rx) = channel();
+
+        let rx1 = rx.clone();
+
+        let thread1 = thread::spawn(move || loop {
+            if rx1.try_recv().is_ok() {
+                return 1;
+            }
+
+            thread::sleep(time::Duration::from_secs(1));
+        });
+
+        let rx2 = rx.clone();
+        let thread2 = thread::spawn(move || loop {
+            if rx2.try_recv().is_ok() {
+                return 2;
+            }
+
+            thread::sleep(time::Duration::from_secs(1));
+        });
+
+        thread::sleep(time::Duration::from_secs(2));
+
+        tx.send(()).unwrap();
+
+        assert!(thread1.join().is_ok_and(|v| v == 1));
+        assert!(thread2.join().is_ok_and(|v| v == 2));
+    }
+
+    #[tokio::test]
+    async fn test_recv() {
+        use std::thread;
+        use std::time;
+
+        let (tx, rx) = channel();
+
+        let mut join_set = JoinSet::new();
+        let rx1 = rx.clone();
+        join_set.spawn(async move {
+            rx1.await.unwrap();
+            1
+        });
+
+        let rx2 = rx.clone();
+        join_set.spawn(async move {
+            rx2.recv().await.unwrap(); // Explicit call to recv
+            2
+        });
+
+        thread::sleep(time::Duration::from_secs(2));
+
+        tx.send(()).unwrap();
+
+        let rx3 = rx.clone();
+        let fun3 = async move {
+            rx3.await.unwrap();
+            3
+        };
+
+        let result = join_set.join_all().await;
+
+        assert_eq!(result[0], 1);
+        assert_eq!(result[1], 2);
+        assert_eq!(fun3.await, 3);
+    }
+
+    #[tokio::test]
+    async fn test_recv_borrowed() {
+        /// Owner of a receiver that's awaited through `&self`
+        struct Worker {
+            shutdown: Receiver<u8>,
+        }
+
+        impl Worker {
+            async fn wait(&self) -> Result<u8, RecvError> {
+                self.shutdown.recv().await
+            }
+        }
+
+        let (tx, rx) = channel();
+        let worker = Worker { shutdown: rx };
+        let mut cx = Context::from_waker(Waker::noop());
+
+        {
+            let mut first = pin::pin!(worker.wait());
+            let mut second = pin::pin!(worker.wait());
+
+            assert_eq!(first.as_mut().poll(&mut cx), Poll::Pending);
+            assert_eq!(second.as_mut().poll(&mut cx), Poll::Pending);
+            assert_eq!(worker.shutdown.inner.lock().len(), 2);
+
+            tx.send(1).unwrap();
+
+            assert_eq!(first.as_mut().poll(&mut cx), Poll::Ready(Ok(1)));
+            assert_eq!(second.as_mut().poll(&mut cx), Poll::Ready(Ok(1)));
+        }
+
+        assert_eq!(worker.wait().await, Ok(1));
+        assert_eq!(worker.shutdown.inner.receivers.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn test_recv_without_sender() {
+        let (tx, rx) = channel::<()>();
+
+        let mut join_set = JoinSet::new();
+        let rx1 = rx.clone();
+        join_set.spawn(rx1);
+
+        let rx2 = rx.clone();
+        join_set.spawn(async move { rx2.recv().await });
+
+        tokio::task::yield_now().await;
+
+        drop(tx);
+
+        let result = join_set.join_all().await;
+
+        assert_eq!(result, vec![Err(RecvError), Err(RecvError)]);
+        assert_eq!(rx.await, Err(RecvError));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_recv_blocking() {
+        use std::thread;
+        use std::time;
+
+        let (tx, rx) = channel();
+
+        let rx1 = rx.clone();
+        let thread1 = thread::spawn(move || rx1.recv_blocking());
+
+        let rx2 = rx.clone();
+        let thread2 = thread::spawn(move || rx2.recv_blocking());
+
+        thread::sleep(time::Duration::from_millis(100));
+
+        tx.send(42).unwrap();
+
+        assert_eq!(thread1.join().unwrap(), Ok(42));
+        assert_eq!(thread2.join().unwrap(), Ok(42));
+        assert_eq!(rx.recv_blocking(), Ok(42));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_recv_blocking_without_sender() {
+        use std::thread;
+        use std::time;
+
+        let (tx, rx) = channel::<()>();
+
+        let thread = thread::spawn(move || rx.recv_blocking());
+
+        thread::sleep(time::Duration::from_millis(100));
+
+        drop(tx);
+
+        assert_eq!(thread.join().unwrap(), Err(RecvError));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_recv_timeout() {
+        use std::thread;
+
+        let (tx, rx) = channel();
+
+        assert_eq!(
+            rx.recv_timeout(Duration::from_millis(50)),
+            Err(RecvTimeoutError::Timeout)
+        );
+
+        let rx1 = rx.clone();
+        let thread = thread::spawn(move || rx1.recv_timeout(Duration::from_secs(10)));
+
+        thread::sleep(Duration::from_millis(100));
+
+        tx.send(7).unwrap();
+
+        assert_eq!(thread.join().unwrap(), Ok(7));
+        assert_eq!(rx.recv_timeout(Duration::ZERO), Ok(7));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_recv_deadline() {
+        use std::thread;
+
+        let (tx, rx) = channel::<()>();
+
+        let start = Instant::now();
+        assert_eq!(
+            rx.recv_deadline(start + Duration::from_millis(50)),
+            Err(RecvTimeoutError::Timeout)
+        );
+        assert!(start.elapsed() >= Duration::from_millis(50));
+
+        let rx1 = rx.clone();
+        let thread =
+            thread::spawn(move || rx1.recv_deadline(Instant::now() + Duration::from_secs(10)));
+
+        thread::sleep(Duration::from_millis(100));
+
+        drop(tx);
+
+        assert_eq!(thread.join().unwrap(), Err(RecvTimeoutError::Closed));
+        assert_eq!(
+            rx.recv_deadline(Instant::now()),
+            Err(RecvTimeoutError::Closed)
+        );
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_recv_timeout_async() {
+        let (tx, rx) = channel();
+
+        assert_eq!(
+            rx.clone()
+                .recv_timeout_async(Duration::from_millis(50))
+                .await,
+            Err(RecvTimeoutError::Timeout)
+        );
+
+        let rx1 = rx.clone();
+        let handle =
+            tokio::spawn(async move { rx1.recv_timeout_async(Duration::from_secs(10)).await });
+
+        tokio::task::yield_now().await;
+
+        tx.send(3).unwrap();
+
+        assert_eq!(handle.await.unwrap(), Ok(3));
+        assert_eq!(
+            rx.recv_timeout_async(Duration::from_millis(50)).await,
+            Ok(3)
+        );
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_recv_timeout_async_without_sender() {
+        let (tx, rx) = channel::<()>();
+
+        drop(tx);
+
+        assert_eq!(
+            rx.recv_timeout_async(Duration::from_secs(10)).await,
+            Err(RecvTimeoutError::Closed)
+        );
+    }
+
+    #[test]
+    // Neither loom's nor parking_lot's locks can be poisoned by a panicking
+    // thread
+    #[cfg(all(feature = "std", not(any(laika_loom, feature = "parking_lot"))))]
+    fn test_recover_from_poisoned_mutex() {
+        use std::task::Wake;
+        use std::thread;
+
+        /// Waker that panics when being woken up
+        struct PanicWaker;
+
+        impl Wake for PanicWaker {
+            fn wake(self: Arc<Self>) {
+                panic!("waking panics");
+            }
+        }
+
+        let (tx, rx) = channel();
+
+        let waker = Waker::from(Arc::new(PanicWaker));
+        let mut cx = Context::from_waker(&waker);
+        let mut rx1 = rx.clone();
+        assert!(Pin::new(&mut rx1).poll(&mut cx).is_pending());
+
+        let thread = thread::spawn(move || tx.send(5));
+        assert!(thread.join().is_err());
+
+        // Wakers are woken up outside of the lock, so it isn't poisoned.
+        assert!(!rx.inner.wakers.is_poisoned());
+        assert_eq!(rx.try_recv(), Ok(5));
+
+        let (tx, rx) = channel();
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut rx1 = rx.clone();
+        assert!(Pin::new(&mut rx1).poll(&mut cx).is_pending());
+
+        let inner = Arc::clone(&rx.inner);
+        let thread = thread::spawn(move || {
+            let _wakers = inner.lock();
+            panic!("panics while holding the lock");
+        });
+        assert!(thread.join().is_err());
+        assert!(rx.inner.wakers.is_poisoned());
+
+        // Mutex is poisoned now, but sender and receivers keep working.
+        tx.send(6).unwrap();
+        assert_eq!(rx.try_recv(), Ok(6));
+        assert_eq!(rx.recv_blocking(), Ok(6));
+        assert_eq!(Pin::new(&mut rx1).poll(&mut cx), Poll::Ready(Ok(6)));
+    }
+
+    #[tokio::test]
+    async fn test_receiver_count() {
+        let (tx, rx) = channel::<()>();
+
+        assert_eq!(tx.receiver_count(), 1);
+        assert!(!tx.is_disconnected());
+
+        let rx1 = rx.clone();
+        let handle = tokio::spawn(rx.clone());
+
+        assert_eq!(tx.receiver_count(), 3);
+
+        drop(rx);
+        drop(rx1);
+
+        assert_eq!(tx.receiver_count(), 1);
+        assert!(!tx.is_disconnected());
+
+        handle.abort();
+        assert!(handle.await.unwrap_err().is_cancelled());
+
+        assert_eq!(tx.receiver_count(), 0);
+        assert!(tx.is_disconnected());
+    }
+
+    #[test]
+    fn test_receiver_count_without_holders() {
+        let (tx, rx) = channel::<u8>();
+        let weak = rx.downgrade();
+
+        // Neither callbacks nor other references to the channel are receivers
+        rx.on_receive(|_| {});
+        let inner = Arc::clone(&rx.inner);
+        assert_eq!(tx.receiver_count(), 1);
+
+        drop(rx);
+        assert_eq!(tx.receiver_count(), 0);
+        assert!(!tx.is_disconnected());
+
+        let rx = weak.upgrade().unwrap();
+        assert_eq!(tx.receiver_count(), 1);
+
+        drop((rx, inner));
+        tx.send(1).unwrap();
+    }
+
+    #[test]
+    fn test_is_sender_alive() {
+        let (tx, rx) = channel();
+        assert!(rx.is_sender_alive());
+
+        tx.send(()).unwrap();
+        assert!(!rx.is_sender_alive());
+
+        let (tx, rx) = channel::<()>();
+        let rx1 = rx.clone();
+        assert!(rx1.is_sender_alive());
+
+        drop(tx);
+        assert!(!rx.is_sender_alive());
+        assert!(!rx1.is_sender_alive());
+    }
+
+    #[test]
+    fn test_weak_receiver() {
+        let (tx, rx) = channel();
+
+        let weak = rx.downgrade();
+        let weak1 = weak.clone();
+        assert_eq!(tx.receiver_count(), 1);
+
+        let rx1 = weak.upgrade().unwrap();
+        assert_eq!(tx.receiver_count(), 2);
+
+        drop(rx);
+        assert_eq!(rx1.try_recv(), Err(TryRecvError::Empty));
+
+        drop(rx1);
+        assert!(weak.upgrade().is_none());
+        assert!(weak1.upgrade().is_none());
+        assert!(tx.is_disconnected());
+
+        assert_eq!(tx.send(1), Err(SendError::Disconnected(1)));
+        assert!(weak.upgrade().is_none());
+    }
+
+    #[test]
+    fn test_deregister_waker_on_drop() {
+        use alloc::task::Wake;
+
+        /// Waker that does nothing
+        struct NoopWaker;
+
+        impl Wake for NoopWaker {
+            fn wake(self: Arc<Self>) {}
+        }
+
+        let (tx, rx) = channel::<()>();
+
+        let waker = Waker::from(Arc::new(NoopWaker));
+        let mut cx = Context::from_waker(&waker);
+
+        let mut rx1 = rx.clone();
+        let mut rx2 = rx.clone();
+        assert!(Pin::new(&mut rx1).poll(&mut cx).is_pending());
+        assert!(Pin::new(&mut rx2).poll(&mut cx).is_pending());
+        assert_eq!(rx.inner.lock().len(), 2);
+
+        // Polling again reuses the slot of the receiver
+        let other_waker = Waker::from(Arc::new(NoopWaker));
+        let mut other_cx = Context::from_waker(&other_waker);
+        assert!(Pin::new(&mut rx1).poll(&mut other_cx).is_pending());
+        assert_eq!(rx.inner.lock().len(), 2);
+
+        drop(rx1);
+        assert_eq!(rx.inner.lock().len(), 1);
+
+        // Freed slot is reused
+        let mut rx3 = rx.clone();
+        assert!(Pin::new(&mut rx3).poll(&mut cx).is_pending());
+        assert_eq!(rx3.key.get(), Some(0));
+        assert_eq!(rx.inner.lock().len(), 2);
+
+        // Cloning a polled receiver doesn't share its slot
+        let rx4 = rx3.clone();
+        assert_eq!(rx4.key.get(), None);
+
+        tx.send(()).unwrap();
+        assert_eq!(rx.inner.lock().len(), 0);
+
+        assert_eq!(Pin::new(&mut rx2).poll(&mut cx), Poll::Ready(Ok(())));
+        drop(rx2);
+        drop(rx3);
+    }
+
+    #[cfg(feature = "futures")]
+    #[tokio::test]
+    async fn test_stream() {
+        use futures_core::{stream::FusedStream, Stream};
+        use std::future::poll_fn;
+
+        let (tx, mut rx) = channel();
+        let mut rx1 = rx.clone();
+
+        assert_eq!(rx.size_hint(), (0, Some(1)));
+
+        let handle = tokio::spawn(async move {
+            let first = poll_fn(|cx| Pin::new(&mut rx1).poll_next(cx)).await;
+            let second = poll_fn(|cx| Pin::new(&mut rx1).poll_next(cx)).await;
+            (first, second)
+        });
+
+        tokio::task::yield_now().await;
+
+        tx.send(8).unwrap();
+
+        assert_eq!(handle.await.unwrap(), (Some(8), None));
+
+        assert!(!rx.is_terminated());
+        assert_eq!(poll_fn(|cx| Pin::new(&mut rx).poll_next(cx)).await, Some(8));
+        assert!(rx.is_terminated());
+        assert_eq!(rx.size_hint(), (0, Some(0)));
+    }
+
+    #[cfg(feature = "futures")]
+    #[tokio::test]
+    async fn test_stream_without_sender() {
+        use futures_core::Stream;
+        use std::future::poll_fn;
+
+        let (tx, mut rx) = channel::<()>();
+
+        drop(tx);
+
+        assert_eq!(poll_fn(|cx| Pin::new(&mut rx).poll_next(cx)).await, None);
+    }
+
+    #[cfg(feature = "futures")]
+    #[test]
+    fn test_fused_future() {
+        use futures_core::future::FusedFuture;
+
+        let (tx, mut rx) = channel();
+        let mut cx = Context::from_waker(Waker::noop());
+
+        assert!(Pin::new(&mut rx).poll(&mut cx).is_pending());
+        assert!(!rx.is_terminated());
+
+        tx.send(3).unwrap();
+
+        assert_eq!(Pin::new(&mut rx).poll(&mut cx), Poll::Ready(Ok(3)));
+        assert!(rx.is_terminated());
+
+        // Polling after completion returns the value again
+        assert_eq!(Pin::new(&mut rx).poll(&mut cx), Poll::Ready(Ok(3)));
+        assert!(!rx.clone().is_terminated());
+    }
+
+    #[test]
+    fn test_poll_recv() {
+        let (tx, rx) = channel();
+
+        let waker = Waker::noop();
+        let mut cx = Context::from_waker(waker);
+
+        assert!(rx.poll_recv(&mut cx).is_pending());
+        assert!(rx.poll_recv(&mut cx).is_pending());
+        assert_eq!(rx.inner.lock().len(), 1);
+
+        tx.send(5).unwrap();
+
+        assert_eq!(rx.poll_recv(&mut cx), Poll::Ready(Ok(5)));
+        assert_eq!(rx.poll_recv(&mut cx), Poll::Ready(Ok(5)));
+
+        let (tx, rx) = channel::<()>();
+        assert!(rx.poll_recv(&mut cx).is_pending());
+
+        drop(tx);
+
+        assert_eq!(rx.poll_recv(&mut cx), Poll::Ready(Err(RecvError)));
+    }
+
+    #[tokio::test]
+    async fn test_await_reference() {
+        /// Struct holding a receiver that is awaited repeatedly
+        struct Holder {
+            rx: Receiver<u8>,
+        }
+
+        impl Holder {
+            async fn wait(&self) -> Result<u8, RecvError> {
+                (&self.rx).await
+            }
+        }
+
+        let (tx, rx) = channel();
+        let holder = Arc::new(Holder { rx });
+
+        let holder1 = holder.clone();
+        let handle = tokio::spawn(async move {
+            let (first, second) = tokio::join!(holder1.wait(), holder1.wait());
+            (first, second, holder1.wait().await)
+        });
+
+        tokio::task::yield_now().await;
+        assert_eq!(holder.rx.inner.lock().len(), 2);
+
+        tx.send(9).unwrap();
+
+        assert_eq!(handle.await.unwrap(), (Ok(9), Ok(9), Ok(9)));
+        assert_eq!(holder.wait().await, Ok(9));
+    }
+
+    #[test]
+    fn test_deregister_reference_waker_on_drop() {
+        let (_tx, rx) = channel::<()>();
+
+        let waker = Waker::noop();
+        let mut cx = Context::from_waker(waker);
+
+        let mut fut = (&rx).into_future();
+        assert!(Pin::new(&mut fut).poll(&mut cx).is_pending());
+        assert_eq!(rx.inner.lock().len(), 1);
+
+        drop(fut);
+        assert_eq!(rx.inner.lock().len(), 0);
+    }
+
+    #[test]
+    fn test_with_value() {
+        use core::sync::atomic::AtomicUsize;
+
+        static CLONES: AtomicUsize = AtomicUsize::new(0);
+
+        /// Value that counts how often it's cloned
+        #[derive(Debug)]
+        struct CountClones(String);
+
+        impl Clone for CountClones {
+            fn clone(&self) -> Self {
+                CLONES.fetch_add(1, Ordering::SeqCst);
+                CountClones(self.0.clone())
+            }
+        }
+
+        let (tx, rx) = channel();
+        let rx1 = rx.clone();
+
+        assert_eq!(rx.with_value(|value: &CountClones| value.0.len()), None);
+
+        tx.send(CountClones(String::from("snapshot"))).unwrap();
+
+        assert_eq!(rx.with_value(|value| value.0.len()), Some(8));
+        assert_eq!(rx1.with_value(|value| value.0.clone()).unwrap(), "snapshot");
+        assert_eq!(CLONES.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn test_ready() {
+        let rx = Receiver::ready(String::from("cached"));
+        let rx1 = rx.clone();
+
+        assert_eq!(rx.try_recv(), Ok(String::from("cached")));
+        #[cfg(feature = "std")]
+        assert_eq!(rx.recv_blocking(), Ok(String::from("cached")));
+        assert_eq!(rx.with_value(String::len), Some(6));
+        assert!(!rx.is_sender_alive());
+        assert_eq!(rx1.await, Ok(String::from("cached")));
+    }
+
+    #[test]
+    fn test_send_with() {
+        let (tx, rx) = channel();
+
+        tx.send_with(|| String::from("computed")).unwrap();
+        assert_eq!(rx.try_recv(), Ok(String::from("computed")));
+
+        let (tx, rx) = channel::<String>();
+        let mut called = false;
+
+        drop(rx);
+
+        let result = tx.send_with(|| {
+            called = true;
+            String::from("computed")
+        });
 
-impl<T> _Receiver<T>
-where
-    T: Clone,
-{
-    /// Clones the value (if it has been given by [`_Sender`]) and returns clone
-    /// of it.
-    fn try_recv(&self) -> Option<T> {
-        self.value.clone()
+        assert!(result.is_err());
+        assert!(!called);
     }
 
-    /// Sets the value to be received by all [`Receiver`]s from [`_Sender`].
-    fn set(&mut self, value: T) {
-        self.value = Some(value);
+    #[tokio::test]
+    async fn test_close_with() {
+        let (tx, rx) = channel::<Result<u8, &str>>();
+        let pending = tokio::spawn(rx.clone());
+        tokio::task::yield_now().await;
 
-        for waker in self.wakers.clone() {
-            waker.wake();
-        }
+        tx.close_with("upstream failed").unwrap();
+        assert_eq!(pending.await.unwrap(), Ok(Err("upstream failed")));
+        assert_eq!(rx.state(), ChannelState::Sent);
+
+        let (tx, rx) = channel::<Result<u8, &str>>();
+        drop(rx);
+        assert_eq!(
+            tx.close_with("upstream failed"),
+            Err(SendError::Disconnected("upstream failed"))
+        );
+
+        let (tx, _rx) = channel::<Result<u8, &str>>();
+        tx.clone().send(Ok(1)).unwrap();
+        assert_eq!(
+            tx.close_with("too late"),
+            Err(SendError::AlreadySent("too late"))
+        );
     }
-}
 
-/// Implement [`Future`] for [`Receiver`] to be able to use it in async
-/// functions.
-impl<T> Future for Receiver<T>
-where
-    T: Clone,
-{
-    type Output = T;
+    #[test]
+    fn test_send_with_panic() {
+        let (tx, rx) = channel::<u8>();
+        let tx1 = tx.clone();
 
-    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        let mut inner = self.inner.lock().expect("Mutex is poisoned");
+        let result = std::thread::spawn(move || tx.send_with(|| panic!("Expected panic"))).join();
+        assert!(result.is_err());
 
-        if let Some(value) = &inner.value {
-            Poll::Ready(value.clone())
-        } else {
-            if inner.wakers.iter().all(|w| !w.will_wake(cx.waker())) {
-                inner.wakers.push(cx.waker().clone());
-            }
-            Poll::Pending
-        }
-    }
-}
+        // The claim of the panicking sender has been released
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+        tx1.send(1).unwrap();
+        assert_eq!(rx.try_recv(), Ok(1));
 
-impl<T> _Sender<T>
-where
-    T: Clone,
-{
-    /// Send a value to all [`Receiver`]s.
-    ///
-    /// # Panics
-    ///
-    /// Panics if mutex is poisened due to another thread panicking while using
-    /// referenced receiver too.
-    fn send(self, value: T) {
-        if let Some(recv) = self.receiver.as_ref() {
-            recv.lock().expect("Mutex is poisoned").set(value);
-        }
+        let (tx, rx) = channel::<u8>();
+        let result = std::thread::spawn(move || tx.send_with(|| panic!("Expected panic"))).join();
+
+        assert!(result.is_err());
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Closed));
     }
-}
 
-/// Creates a one-shot, single producer multiple consumer channel that can be
-/// used to send one value to multiple receivers.
-///
-/// # Examples
-///
-/// ```rust
-/// let (mut tx, rx) = laika::shotgun::channel::<u8>();
-/// // do something with tx and rx
-/// ```
-pub fn channel<T>() -> (Sender<T>, Receiver<T>)
-where
-    T: Clone,
-{
-    let mut sender = Sender {
-        inner: _Sender { receiver: None },
-    };
+    #[test]
+    fn test_racing_senders() {
+        let (tx, rx) = channel();
+        let senders = (0..8).map(|_| tx.clone()).collect::<Vec<_>>();
 
-    let receiver_ref = Arc::new(Mutex::new(_Receiver {
-        value: None,
-        wakers: Vec::new(),
-    }));
+        drop(tx);
 
-    let receiver = Receiver {
-        inner: receiver_ref.clone(),
-    };
+        let handles = senders
+            .into_iter()
+            .enumerate()
+            .map(|(i, tx)| std::thread::spawn(move || tx.send(i).is_ok()))
+            .collect::<Vec<_>>();
 
-    sender.inner.receiver = Some(receiver_ref);
+        let successes = handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .filter(|&sent| sent)
+            .count();
 
-    (sender, receiver)
-}
+        assert_eq!(successes, 1);
+        assert!(rx.try_recv().is_ok_and(|value| value < 8));
 
-#[cfg(test)]
-mod test {
-    use super::*;
-    use tokio::task::JoinSet;
+        let (tx, rx) = channel::<u8>();
+        let tx1 = tx.clone();
+        let tx2 = tx.clone();
+
+        tx.send(1).unwrap();
+        assert_eq!(tx1.send(2), Err(SendError::AlreadySent(2)));
+
+        let result = tx2.send_with(|| 3);
+        assert!(matches!(result, Err(SendError::AlreadySent(_))));
+        assert_eq!(rx.try_recv(), Ok(1));
+    }
 
     #[test]
-    fn test_basic() {
-        let (tx, rx) = channel();
+    fn test_close_after_last_sender() {
+        let (tx, rx) = channel::<u8>();
+        let tx1 = tx.clone();
+
+        drop(tx);
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+
+        drop(tx1);
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Closed));
+    }
+
+    #[test]
+    fn test_on_receive() {
+        let (tx, rx) = channel::<u8>();
+        let (events, inbox) = std::sync::mpsc::channel();
+
+        let events1 = events.clone();
+        rx.on_receive(move |value| events1.send(*value).unwrap());
+        assert_eq!(tx.receiver_count(), 1);
 
-        assert_eq!(rx.try_recv(), None);
-        assert_eq!(rx.try_recv(), None);
+        // The callback keeps the channel connected, but isn't a receiver
+        drop(rx);
+        assert_eq!(tx.receiver_count(), 0);
+        assert!(!tx.is_disconnected());
+        assert!(inbox.try_recv().is_err());
 
-        tx.send(());
+        tx.send(3).unwrap();
+        assert_eq!(inbox.try_recv(), Ok(3));
 
-        assert_eq!(rx.try_recv(), Some(()));
-        assert_eq!(rx.try_recv(), Some(()));
+        // Value has been sent already, so the callback is called right away
+        let rx = Receiver::ready(4);
+        rx.on_receive(move |value| events.send(*value).unwrap());
+        assert_eq!(inbox.try_recv(), Ok(4));
     }
 
     #[test]
-    fn test_work_without_receiver() {
+    fn test_on_receive_closed() {
+        let (tx, rx) = channel::<u8>();
+        let called = Arc::new(Mutex::new(false));
+
+        let called1 = Arc::clone(&called);
+        rx.on_receive(move |_| *called1.lock() = true);
+
+        drop(tx);
+        assert!(!*called.lock());
+
+        // Callback has been dropped, so it doesn't keep the channel alive
+        assert_eq!(Arc::strong_count(&called), 1);
+        assert_eq!(Arc::strong_count(&rx.inner), 1);
+
+        let called1 = Arc::clone(&called);
+        rx.on_receive(move |_| *called1.lock() = true);
+        assert_eq!(Arc::strong_count(&called), 1);
+    }
+
+    #[tokio::test]
+    async fn test_complete_with() {
         let (tx, rx) = channel();
-        assert_eq!(rx.try_recv(), None);
 
+        let handle = tokio::spawn(tx.complete_with(async {
+            tokio::task::yield_now().await;
+            String::from("computed")
+        }));
+
+        assert_eq!(rx.clone().await, Ok(String::from("computed")));
+        assert_eq!(handle.await.unwrap(), Ok(()));
+
+        let (tx, rx) = channel();
         drop(rx);
 
-        tx.send(());
+        let result = tx.complete_with(async { 1 }).await;
+        assert_eq!(result, Err(SendError::Disconnected(1)));
     }
 
     #[test]
-    fn test_work_without_sender() {
-        let (tx, rx) = channel::<()>();
+    fn test_state() {
+        let (tx, rx) = channel::<u8>();
+        let tx1 = tx.clone();
+        assert_eq!(rx.state(), ChannelState::Empty);
+
+        drop(tx);
+        assert_eq!(rx.state(), ChannelState::Empty);
 
-        assert_eq!(rx.try_recv(), None);
+        tx1.send(1).unwrap();
+        assert_eq!(rx.state(), ChannelState::Sent);
 
+        let (tx, rx) = channel::<u8>();
         drop(tx);
+        assert_eq!(rx.state(), ChannelState::Closed);
+    }
+
+    #[test]
+    // Hashing is based on the identity of the channel only, so its interior
+    // mutability doesn't matter.
+    #[allow(clippy::mutable_key_type)]
+    fn test_same_channel() {
+        use std::collections::HashSet;
+
+        let (_tx, rx) = channel::<u8>();
+        let (_tx1, rx1) = channel::<u8>();
+
+        assert_eq!(rx, rx.clone());
+        assert_eq!(rx, rx.downgrade().upgrade().unwrap());
+        assert_ne!(rx, rx1);
 
-        assert_eq!(rx.try_recv(), None);
+        let receivers = [rx.clone(), rx1.clone(), rx.clone(), rx1]
+            .into_iter()
+            .collect::<HashSet<_>>();
+
+        assert_eq!(receivers.len(), 2);
+        assert!(receivers.contains(&rx));
+    }
+
+    #[tokio::test]
+    async fn test_send_acknowledged() {
+        let (tx, rx) = channel();
+        let rx1 = rx.clone();
+
+        let ack = tx.send_acknowledged(String::from("handoff")).unwrap();
+        assert_eq!(ack.try_recv(), Err(TryRecvError::Empty));
+        assert_eq!(rx.state(), ChannelState::Sent);
+
+        let handle = tokio::spawn(ack);
+        tokio::task::yield_now().await;
+        assert!(!handle.is_finished());
+
+        assert_eq!(rx1.await, Ok(String::from("handoff")));
+        assert_eq!(handle.await.unwrap(), Ok(()));
+
+        // Observing again doesn't send anything or take the lock again
+        assert_eq!(rx.try_recv(), Ok(String::from("handoff")));
+        assert!(rx.inner.ack.lock().is_none());
     }
 
     #[test]
-    fn test_work_with_multiple_receivers() {
+    fn test_send_acknowledged_without_observer() {
         let (tx, rx) = channel();
 
-        let rx1 = rx.clone();
-        let rx2 = rx.clone();
+        let ack = tx.send_acknowledged(1).unwrap();
+        drop(rx);
+        assert_eq!(ack.try_recv(), Err(TryRecvError::Closed));
+
+        let (tx, rx) = channel::<u8>();
+        let tx1 = tx.clone();
+
+        tx.send(1).unwrap();
+        assert_eq!(tx1.send_acknowledged(2), Err(SendError::AlreadySent(2)));
+        assert_eq!(rx.try_recv(), Ok(1));
 
-        assert_eq!(rx.try_recv(), None);
-        assert_eq!(rx1.try_recv(), None);
-        assert_eq!(rx2.try_recv(), None);
+        let (tx, rx) = channel();
+        drop(rx);
+        assert_eq!(tx.send_acknowledged(3), Err(SendError::Disconnected(3)));
+    }
 
-        tx.send(1337);
+    #[test]
+    fn test_send_acknowledged_callback() {
+        let (tx, rx) = channel();
+        rx.on_receive(|_| {});
 
-        assert_eq!(rx.try_recv(), Some(1337));
-        assert_eq!(rx1.try_recv(), Some(1337));
-        assert_eq!(rx2.try_recv(), Some(1337));
+        // Callback observes the value as soon as it's sent
+        let ack = tx.send_acknowledged(1).unwrap();
+        assert_eq!(ack.try_recv(), Ok(()));
     }
 
     #[test]
-    fn test_works_in_threads() {
+    fn test_try_recv_lock_free() {
         use std::thread;
-        use std::time;
 
-        let (tx, rx) = channel();
+        let (tx, rx) = channel::<u8>();
+
+        // All locks of the channel are held, so any reader taking one of them
+        // would block forever.
+        let wakers = rx.inner.lock();
+        let callbacks = rx.inner.callbacks.lock();
 
         let rx1 = rx.clone();
+        let empty = thread::spawn(move || rx1.try_recv()).join().unwrap();
+        assert_eq!(empty, Err(TryRecvError::Empty));
 
-        let thread1 = thread::spawn(move || loop {
-            if rx1.try_recv().is_some() {
-                return 1;
+        drop((wakers, callbacks));
+        tx.send(1).unwrap();
+        assert_eq!(rx.try_recv(), Ok(1));
+
+        let wakers = rx.inner.lock();
+        let callbacks = rx.inner.callbacks.lock();
+        let ack = rx.inner.ack.lock();
+
+        let readers = (0..4)
+            .map(|_| {
+                let rx = rx.clone();
+                thread::spawn(move || (0..1000).all(|_| rx.try_recv() == Ok(1)))
+            })
+            .collect::<Vec<_>>();
+
+        for reader in readers {
+            assert!(reader.join().unwrap());
+        }
+
+        drop((wakers, callbacks, ack));
+    }
+
+    #[test]
+    fn test_wake_outside_lock() {
+        use alloc::task::Wake;
+        use core::sync::atomic::AtomicBool;
+
+        /// Waker that records whether the wakers were locked while waking
+        struct LockCheck {
+            channel: Arc<_Channel<u8>>,
+            locked: AtomicBool,
+        }
+
+        impl Wake for LockCheck {
+            fn wake(self: Arc<Self>) {
+                self.locked
+                    .store(self.channel.wakers.is_locked(), Ordering::Relaxed);
             }
+        }
 
-            thread::sleep(time::Duration::from_secs(1));
+        let (tx, rx) = channel();
+        let check = Arc::new(LockCheck {
+            channel: Arc::clone(&rx.inner),
+            locked: AtomicBool::new(true),
         });
 
+        let waker = Waker::from(Arc::clone(&check));
+        assert!(rx.poll_recv(&mut Context::from_waker(&waker)).is_pending());
+
+        tx.send(1).unwrap();
+        assert!(!check.locked.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn test_spill_wakers() {
+        let (tx, rx) = channel::<()>();
+        let mut cx = Context::from_waker(Waker::noop());
+
+        let mut receivers = (0..INLINE_WAKERS + 2)
+            .map(|_| rx.clone())
+            .collect::<Vec<_>>();
+
+        for receiver in &receivers[..INLINE_WAKERS] {
+            assert!(receiver.poll_recv(&mut cx).is_pending());
+        }
+
+        // Inline slots don't allocate
+        assert_eq!(rx.inner.lock().spilled.capacity(), 0);
+
+        for receiver in &receivers[INLINE_WAKERS..] {
+            assert!(receiver.poll_recv(&mut cx).is_pending());
+        }
+
+        assert_eq!(rx.inner.lock().len(), INLINE_WAKERS + 2);
+        assert_eq!(
+            receivers[INLINE_WAKERS + 1].key.get(),
+            Some(INLINE_WAKERS + 1)
+        );
+
+        // Freed slots are reused, inline ones first
+        drop(receivers.remove(INLINE_WAKERS));
+        drop(receivers.remove(1));
+
+        let rx1 = rx.clone();
         let rx2 = rx.clone();
-        let thread2 = thread::spawn(move || loop {
-            if rx2.try_recv().is_some() {
-                return 2;
-            }
+        assert!(rx1.poll_recv(&mut cx).is_pending());
+        assert!(rx2.poll_recv(&mut cx).is_pending());
+        assert_eq!(rx1.key.get(), Some(1));
+        assert_eq!(rx2.key.get(), Some(INLINE_WAKERS));
+        assert_eq!(rx.inner.lock().spilled.len(), 2);
 
-            thread::sleep(time::Duration::from_secs(1));
-        });
+        tx.send(()).unwrap();
+        assert_eq!(rx.inner.lock().len(), 0);
+    }
 
-        thread::sleep(time::Duration::from_secs(2));
+    #[test]
+    fn test_single_allocation() {
+        let mut cx = Context::from_waker(Waker::noop());
 
-        tx.send(());
+        let (tx, rx) = channel::<u64>();
+        let receivers: [_; INLINE_WAKERS] = core::array::from_fn(|_| rx.clone());
 
-        assert!(thread1.join().is_ok_and(|v| v == 1));
-        assert!(thread2.join().is_ok_and(|v| v == 2));
+        for receiver in &receivers {
+            assert!(receiver.poll_recv(&mut cx).is_pending());
+        }
+
+        // Apart from the `Arc` itself, nothing has been allocated
+        {
+            let wakers = rx.inner.lock();
+            assert_eq!(wakers.spilled.capacity(), 0);
+            assert_eq!(wakers.free.capacity(), 0);
+            assert_eq!(rx.inner.callbacks.lock().0.capacity(), 0);
+        }
+
+        tx.send(7).unwrap();
+
+        for receiver in &receivers {
+            assert_eq!(receiver.try_recv(), Ok(7));
+        }
     }
 
     #[tokio::test]
-    async fn test_recv() {
-        use std::thread;
-        use std::time;
+    async fn test_recv_until() {
+        let (tx, rx) = channel::<u8>();
+        let (cancel_tx, cancel_rx) = channel::<()>();
 
-        let (tx, rx) = channel();
+        let received = tokio::spawn(rx.clone().recv_until(cancel_rx.clone()));
+        tokio::task::yield_now().await;
 
-        let mut join_set = JoinSet::new();
+        tx.send(1).unwrap();
+        assert_eq!(received.await.unwrap(), Ok(1));
+
+        // Available value wins over completed cancellation
+        assert_eq!(rx.clone().recv_until(async {}).await, Ok(1));
+
+        let (tx, rx) = channel::<u8>();
+        let cancelled = tokio::spawn(rx.clone().recv_until(cancel_rx.clone()));
+        tokio::task::yield_now().await;
+
+        cancel_tx.send(()).unwrap();
+        assert_eq!(cancelled.await.unwrap(), Err(RecvUntilError::Cancelled));
+
+        drop(tx);
+        let closed = rx.recv_until(core::future::pending::<()>()).await;
+        assert_eq!(closed, Err(RecvUntilError::Closed));
+    }
+
+    #[test]
+    fn test_max_receivers() {
+        let (tx, rx) = channel_with_max_receivers::<u8>(2);
+        let weak = rx.downgrade();
+
+        let rx1 = rx.try_clone().unwrap();
+        assert_eq!(rx.try_clone(), Err(ReceiverLimitError));
+        assert!(weak.upgrade().is_none());
+
+        // Cloning beyond the limit is caught in debug builds
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| rx.clone()));
+        assert_eq!(result.is_err(), cfg!(debug_assertions));
+
+        drop(rx1);
+        let rx2 = weak.upgrade().unwrap();
+
+        tx.send(1).unwrap();
+        assert_eq!(rx2.try_recv(), Ok(1));
+        assert_eq!(rx.inner.receivers.load(Ordering::Relaxed), 2);
+
+        // Unbounded channels can be cloned freely
+        let (_tx, rx) = channel::<u8>();
+        let receivers = (0..16).map(|_| rx.try_clone().unwrap()).collect::<Vec<_>>();
+        assert_eq!(rx.inner.receivers.load(Ordering::Relaxed), 17);
+
+        drop(receivers);
+        assert_eq!(rx.inner.receivers.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn test_recv_or_else() {
+        let (tx, rx) = channel::<u8>();
         let rx1 = rx.clone();
-        join_set.spawn(async move {
-            rx1.await;
-            1
+
+        assert_eq!(rx.try_recv_or_default(), None);
+
+        tx.send(1).unwrap();
+        assert_eq!(rx.try_recv_or_default(), Some(1));
+        assert_eq!(rx.recv_or_else(|| unreachable!()).await, 1);
+
+        drop(rx1);
+
+        let (tx, rx) = channel::<u8>();
+        let fallback = tokio::spawn(rx.clone().recv_or_else(|| 2));
+        tokio::task::yield_now().await;
+
+        drop(tx);
+        assert_eq!(fallback.await.unwrap(), 2);
+        assert_eq!(rx.try_recv_or_default(), Some(0));
+    }
+
+    #[test]
+    fn test_wake_order() {
+        use std::{sync::Mutex, task::Wake};
+
+        /// Waker that logs its id when woken
+        struct Logger(usize, Arc<Mutex<Vec<usize>>>);
+
+        impl Wake for Logger {
+            fn wake(self: Arc<Self>) {
+                self.1.lock().unwrap().push(self.0);
+            }
+        }
+
+        for (order, expected) in [
+            (WakeOrder::Fifo, [0, 2, 3, 4, 5, 6, 1]),
+            (WakeOrder::Lifo, [1, 6, 5, 4, 3, 2, 0]),
+        ] {
+            let (tx, rx) = channel_with_wake_order::<()>(order);
+            let log = Arc::new(Mutex::new(Vec::new()));
+
+            let mut receivers = (0..INLINE_WAKERS + 3)
+                .map(|id| {
+                    let waker = Waker::from(Arc::new(Logger(id, Arc::clone(&log))));
+                    let receiver = rx.clone();
+                    assert!(receiver
+                        .poll_recv(&mut Context::from_waker(&waker))
+                        .is_pending());
+                    (id, receiver)
+                })
+                .collect::<Vec<_>>();
+
+            // Re-registering moves receiver 1 to the end of the queue, while
+            // replacing the waker of receiver 2 keeps its place.
+            let (_, receiver) = receivers.remove(1);
+            drop(receiver);
+
+            let waker = Waker::from(Arc::new(Logger(1, Arc::clone(&log))));
+            let receiver = rx.clone();
+            assert!(receiver
+                .poll_recv(&mut Context::from_waker(&waker))
+                .is_pending());
+            receivers.push((1, receiver));
+
+            let waker = Waker::from(Arc::new(Logger(2, Arc::clone(&log))));
+            let (_, receiver) = &receivers[1];
+            assert!(receiver
+                .poll_recv(&mut Context::from_waker(&waker))
+                .is_pending());
+
+            tx.send(()).unwrap();
+            assert_eq!(*log.lock().unwrap(), expected);
+        }
+    }
+}
+
+#[cfg(all(test, laika_loom))]
+mod loom_test {
+    use super::*;
+    use alloc::task::Wake;
+    use loom::thread;
+
+    /// Waker that records whether it has been woken
+    #[derive(Default)]
+    struct Flag(AtomicBool);
+
+    impl Wake for Flag {
+        fn wake(self: Arc<Self>) {
+            self.0.store(true, Ordering::Release);
+        }
+    }
+
+    #[test]
+    fn loom_send_poll() {
+        loom::model(|| {
+            let (tx, rx) = channel();
+            let handle = thread::spawn(move || tx.send(1).unwrap());
+
+            let flag = Arc::new(Flag::default());
+            let waker = Waker::from(Arc::clone(&flag));
+            let mut cx = Context::from_waker(&waker);
+
+            let polled = rx.poll_recv(&mut cx);
+            handle.join().unwrap();
+
+            // Either the value was there already, or the waker is woken
+            match polled {
+                Poll::Ready(value) => assert_eq!(value, Ok(1)),
+                Poll::Pending => assert!(flag.0.load(Ordering::Acquire)),
+            }
+
+            assert_eq!(rx.try_recv(), Ok(1));
         });
+    }
 
-        let rx2 = rx.clone();
-        join_set.spawn(async move {
-            rx2.recv().await; // Explicit call to recv
-            2
+    #[test]
+    fn loom_racing_senders() {
+        loom::model(|| {
+            let (tx, rx) = channel();
+            let tx1 = tx.clone();
+
+            let handle = thread::spawn(move || tx1.send(1).is_ok());
+            let sent = tx.send(2).is_ok();
+            let sent1 = handle.join().unwrap();
+
+            assert!(sent ^ sent1);
+            assert_eq!(rx.try_recv(), Ok(if sent { 2 } else { 1 }));
         });
+    }
 
-        thread::sleep(time::Duration::from_secs(2));
+    #[test]
+    fn loom_drop_senders() {
+        loom::model(|| {
+            let (tx, rx) = channel::<u8>();
+            let tx1 = tx.clone();
 
-        tx.send(());
+            let handle = thread::spawn(move || drop(tx1));
+            drop(tx);
+            handle.join().unwrap();
 
-        let rx3 = rx.clone();
-        let fun3 = async move {
-            rx3.await;
-            3
-        };
+            assert_eq!(rx.try_recv(), Err(TryRecvError::Closed));
+        });
+    }
 
-        let result = join_set.join_all().await;
+    #[test]
+    fn loom_recv_blocking() {
+        loom::model(|| {
+            let (tx, rx) = channel();
+            let handle = thread::spawn(move || tx.send(1).unwrap());
 
-        assert_eq!(result[0], 1);
-        assert_eq!(result[1], 2);
-        assert_eq!(fun3.await, 3);
+            assert_eq!(rx.recv_blocking(), Ok(1));
+            handle.join().unwrap();
+        });
+    }
+
+    #[test]
+    fn loom_send_acknowledged() {
+        loom::model(|| {
+            let (tx, rx) = channel();
+            let rx1 = rx.clone();
+
+            let handle = thread::spawn(move || tx.send_acknowledged(1).unwrap());
+            let handle1 = thread::spawn(move || rx1.try_recv());
+            let received = rx.try_recv();
+
+            let ack = handle.join().unwrap();
+            let received1 = handle1.join().unwrap();
+
+            // Acknowledged exactly once if any receiver observed the value
+            let observed = received.is_ok() || received1.is_ok();
+            assert_eq!(ack.try_recv().is_ok(), observed);
+        });
     }
 }
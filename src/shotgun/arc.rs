@@ -0,0 +1,142 @@
+//! Variant of [`channel`] that broadcasts values wrapped in an [`Arc`], so the
+//! value itself doesn't need to implement [`Clone`].
+
+use super::{channel, Receiver, SendError, Sender};
+use alloc::sync::Arc;
+
+/// Oneshot sender of an [`arc_channel`]
+///
+/// Works like [`Sender`], but takes the plain value and wraps it in an [`Arc`]
+/// before sending it to all receivers.
+///
+/// # Examples
+/// ```rust
+/// let (tx, rx) = laika::shotgun::arc_channel();
+///
+/// tx.send(String::from("hello")).unwrap();
+///
+/// assert_eq!(*rx.try_recv().unwrap(), "hello");
+/// ```
+#[derive(Debug)]
+pub struct ArcSender<T> {
+    inner: Sender<Arc<T>>,
+}
+
+impl<T> ArcSender<T> {
+    /// Send a value to all receivers of the channel.
+    /// As this is a oneshot sender, only one value can be sent.
+    ///
+    /// Returns [`SendError`] containing the (unwrapped) value if all
+    /// [`Receiver`]s have been dropped already.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use laika::shotgun::SendError;
+    ///
+    /// let (tx, rx) = laika::shotgun::arc_channel();
+    ///
+    /// drop(rx);
+    ///
+    /// assert_eq!(tx.send(12), Err(SendError::Disconnected(12)));
+    /// ```
+    pub fn send(self, value: T) -> Result<(), SendError<T>> {
+        self.inner.send(Arc::new(value)).map_err(|error| {
+            error.map(|value| Arc::into_inner(value).expect("Unsent value is not shared"))
+        })
+    }
+
+    /// Returns the number of [`Receiver`]s of the channel that currently
+    /// exist. See [`Sender::receiver_count`].
+    pub fn receiver_count(&self) -> usize {
+        self.inner.receiver_count()
+    }
+
+    /// Returns whether all [`Receiver`]s of the channel have been dropped.
+    /// See [`Sender::is_disconnected`].
+    pub fn is_disconnected(&self) -> bool {
+        self.inner.is_disconnected()
+    }
+
+    /// Returns the underlying [`Sender`] that sends [`Arc`]s directly.
+    pub fn into_inner(self) -> Sender<Arc<T>> {
+        self.inner
+    }
+}
+
+/// Creates a one-shot, single producer multiple consumer channel whose
+/// receivers yield [`Arc<T>`]. This way, values that don't implement [`Clone`]
+/// (or are expensive to clone) can be broadcast without wrapping them in an
+/// [`Arc`] at every call site.
+///
+/// # Examples
+///
+/// ```rust
+/// // Doesn't implement `Clone`
+/// #[derive(Debug, PartialEq)]
+/// struct Snapshot(Vec<u8>);
+///
+/// let (tx, rx) = laika::shotgun::arc_channel();
+/// let rx1 = rx.clone();
+///
+/// tx.send(Snapshot(vec![1, 2, 3])).unwrap();
+///
+/// assert_eq!(*rx.try_recv().unwrap(), Snapshot(vec![1, 2, 3]));
+/// assert_eq!(*rx1.try_recv().unwrap(), Snapshot(vec![1, 2, 3]));
+/// ```
+pub fn arc_channel<T>() -> (ArcSender<T>, Receiver<Arc<T>>) {
+    let (tx, rx) = channel();
+
+    (ArcSender { inner: tx }, rx)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::shotgun::TryRecvError;
+
+    /// Value that doesn't implement [`Clone`]
+    #[derive(Debug, PartialEq)]
+    struct NotClone(u32);
+
+    #[test]
+    fn test_basic() {
+        let (tx, rx) = arc_channel();
+        let rx1 = rx.clone();
+
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+        assert_eq!(tx.receiver_count(), 2);
+
+        tx.send(NotClone(3)).unwrap();
+
+        let value = rx.try_recv().unwrap();
+        let value1 = rx1.try_recv().unwrap();
+
+        assert_eq!(*value, NotClone(3));
+        assert!(Arc::ptr_eq(&value, &value1));
+    }
+
+    #[test]
+    fn test_work_without_receiver() {
+        let (tx, rx) = arc_channel();
+
+        drop(rx);
+
+        assert!(tx.is_disconnected());
+        assert_eq!(
+            tx.send(NotClone(3)),
+            Err(SendError::Disconnected(NotClone(3)))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_recv() {
+        let (tx, rx) = arc_channel();
+
+        let handle = tokio::spawn(rx.clone());
+
+        tx.into_inner().send(Arc::new(NotClone(4))).unwrap();
+
+        assert_eq!(*handle.await.unwrap().unwrap(), NotClone(4));
+        assert_eq!(*rx.await.unwrap(), NotClone(4));
+    }
+}
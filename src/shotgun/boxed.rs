@@ -0,0 +1,130 @@
+//! Type-erased receiver, so pending results of different origins can be
+//! stored behind one type.
+
+use super::{Receiver, RecvError};
+use alloc::boxed::Box;
+use core::{
+    fmt,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+/// Type-erased, awaitable pending result
+///
+/// Wraps any `Send` future that resolves like a [`Receiver`], e.g. a plain
+/// receiver, a receiver whose value is mapped by an `async` block or a
+/// [`Receiver::ready`] one, so they can be stored in the same collection.
+/// Created by [`BoxedReceiver::new`] or from a [`Receiver`] via [`From`].
+///
+/// # Examples
+/// ```rust
+/// use laika::shotgun::{BoxedReceiver, Receiver};
+///
+/// # tokio::runtime::Builder::new_current_thread()
+/// #     .build()
+/// #     .unwrap()
+/// #     .block_on(async {
+/// let (tx, rx) = laika::shotgun::channel::<u8>();
+///
+/// let pending = [
+///     BoxedReceiver::from(rx.clone()),
+///     BoxedReceiver::new(async move { rx.await.map(|value| value * 2) }),
+///     BoxedReceiver::from(Receiver::ready(1)),
+/// ];
+///
+/// tx.send(21).unwrap();
+///
+/// let mut results = Vec::new();
+///
+/// for receiver in pending {
+///     results.push(receiver.await);
+/// }
+///
+/// assert_eq!(results, [Ok(21), Ok(42), Ok(1)]);
+/// # });
+/// ```
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct BoxedReceiver<T> {
+    /// Future that resolves to the value
+    inner: Pin<Box<dyn Future<Output = Result<T, RecvError>> + Send>>,
+}
+
+impl<T> BoxedReceiver<T> {
+    /// Boxes `future`, which resolves to the value or [`RecvError`] like a
+    /// [`Receiver`].
+    pub fn new<F>(future: F) -> Self
+    where
+        F: Future<Output = Result<T, RecvError>> + Send + 'static,
+    {
+        BoxedReceiver {
+            inner: Box::pin(future),
+        }
+    }
+}
+
+impl<T> From<Receiver<T>> for BoxedReceiver<T>
+where
+    T: Clone + Send + Sync + 'static,
+{
+    fn from(receiver: Receiver<T>) -> Self {
+        BoxedReceiver::new(receiver)
+    }
+}
+
+impl<T> Future for BoxedReceiver<T> {
+    type Output = Result<T, RecvError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.inner.as_mut().poll(cx)
+    }
+}
+
+/// The boxed future can't be printed, so it's left out.
+impl<T> fmt::Debug for BoxedReceiver<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BoxedReceiver").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::shotgun::channel;
+    use alloc::vec::Vec;
+    use core::task::Waker;
+
+    #[test]
+    fn test_boxed_receiver() {
+        let (tx, rx) = channel::<u8>();
+        let (closed_tx, closed_rx) = channel::<u8>();
+        let mut cx = Context::from_waker(Waker::noop());
+
+        let mut pending = Vec::from([
+            BoxedReceiver::from(rx.clone()),
+            BoxedReceiver::new(async move { rx.await.map(|value| value + 1) }),
+            BoxedReceiver::from(closed_rx),
+        ]);
+
+        for receiver in &mut pending {
+            assert_eq!(Pin::new(receiver).poll(&mut cx), Poll::Pending);
+        }
+
+        tx.send(1).unwrap();
+        drop(closed_tx);
+
+        let results = pending
+            .iter_mut()
+            .map(|receiver| Pin::new(receiver).poll(&mut cx))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            results,
+            [
+                Poll::Ready(Ok(1)),
+                Poll::Ready(Ok(2)),
+                Poll::Ready(Err(RecvError))
+            ]
+        );
+    }
+}
@@ -0,0 +1,262 @@
+//! Builder for channels with non-default options, so that options don't need a
+//! `channel_with_*` constructor for every combination of them.
+
+#[cfg(feature = "std")]
+use super::sync::Condvar;
+use super::{
+    _Callbacks, _Channel, _Sender, _Wakers,
+    sync::{AtomicBool, AtomicU8, AtomicUsize, Mutex, OnceCell},
+    Receiver, Sender, WakeOrder, EMPTY,
+};
+#[cfg(feature = "std")]
+use super::{observer, ChannelObserver};
+use alloc::sync::Arc;
+use core::fmt;
+#[cfg(all(feature = "std", not(laika_loom)))]
+use std::sync::OnceLock;
+
+/// How a channel handles locks that have been poisoned, i.e. another thread
+/// panicked while holding them
+///
+/// The channel never leaves its state half updated, so recovering is safe.
+/// Only [`std::sync`] locks can be poisoned, with the `spin` or `parking_lot`
+/// feature the policy has no effect.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum PoisonPolicy {
+    /// Ignore poisoning, so a panic in one consumer doesn't cascade into every
+    /// other consumer of the channel.
+    #[default]
+    Recover,
+    /// Panic when locking a poisoned lock, for code that treats any panic
+    /// while a channel is in use as fatal. Doesn't panic while the thread is
+    /// unwinding already.
+    Propagate,
+}
+
+/// Builder of a [`channel`](super::channel) with custom options
+///
+/// Every option defaults to the behavior of [`channel`](super::channel).
+///
+/// # Examples
+/// ```rust
+/// use laika::shotgun::{Builder, WakeOrder};
+///
+/// let (tx, rx) = Builder::new()
+///     .wake_order(WakeOrder::Lifo)
+///     .waker_capacity(16)
+///     .max_receivers(32)
+///     .build();
+///
+/// tx.send(12).unwrap();
+/// assert_eq!(rx.try_recv(), Ok(12));
+/// ```
+#[derive(Clone, Copy)]
+#[must_use = "a builder does nothing until `build` is called"]
+pub struct Builder {
+    /// Order in which pending receivers are woken up
+    wake_order: WakeOrder,
+    /// Number of wakers that can be registered without allocating
+    waker_capacity: usize,
+    /// How poisoned locks are handled
+    poison_policy: PoisonPolicy,
+    /// Maximum number of receivers
+    max_receivers: usize,
+    /// Observer of the channel instead of the global one, if any
+    #[cfg(feature = "std")]
+    observer: Option<&'static dyn ChannelObserver>,
+}
+
+impl Builder {
+    /// Creates a builder with the default options.
+    pub fn new() -> Self {
+        Builder {
+            wake_order: WakeOrder::default(),
+            waker_capacity: 0,
+            poison_policy: PoisonPolicy::default(),
+            max_receivers: usize::MAX,
+            #[cfg(feature = "std")]
+            observer: None,
+        }
+    }
+
+    /// Sets the order in which pending receivers are woken up, see
+    /// [`channel_with_wake_order`](super::channel_with_wake_order).
+    pub fn wake_order(mut self, order: WakeOrder) -> Self {
+        self.wake_order = order;
+        self
+    }
+
+    /// Reserves space for the wakers of `capacity` pending receivers up front,
+    /// so registering them doesn't allocate. A few wakers are stored inline
+    /// anyway.
+    pub fn waker_capacity(mut self, capacity: usize) -> Self {
+        self.waker_capacity = capacity;
+        self
+    }
+
+    /// Sets how poisoned locks are handled, see [`PoisonPolicy`].
+    pub fn poison_policy(mut self, policy: PoisonPolicy) -> Self {
+        self.poison_policy = policy;
+        self
+    }
+
+    /// Limits the number of receivers that may exist at the same time, see
+    /// [`channel_with_max_receivers`](super::channel_with_max_receivers).
+    ///
+    /// # Panics
+    /// Panics if `max` is zero.
+    pub fn max_receivers(mut self, max: usize) -> Self {
+        assert!(max > 0, "a channel needs at least one receiver");
+
+        self.max_receivers = max;
+        self
+    }
+
+    /// Reports the lifecycle of the channel to `observer` instead of the one
+    /// installed by [`set_observer`](super::set_observer). Only available with
+    /// the `std` feature.
+    #[cfg(feature = "std")]
+    pub fn observer(mut self, observer: &'static dyn ChannelObserver) -> Self {
+        self.observer = Some(observer);
+        self
+    }
+
+    /// Creates the channel.
+    pub fn build<T>(self) -> (Sender<T>, Receiver<T>)
+    where
+        T: Clone,
+    {
+        let receiver_ref = Arc::new(_Channel {
+            value: OnceCell::new(),
+            state: AtomicU8::new(EMPTY),
+            senders: AtomicUsize::new(1),
+            receivers: AtomicUsize::new(0),
+            max_receivers: self.max_receivers,
+            #[cfg(feature = "std")]
+            poison_policy: self.poison_policy,
+            wakers: Mutex::new(_Wakers::with_capacity(self.wake_order, self.waker_capacity)),
+            callbacks: Mutex::new(_Callbacks::default()),
+            observed: AtomicBool::new(false),
+            ack: Mutex::new(None),
+            #[cfg(feature = "std")]
+            condvar: Condvar::new(),
+            #[cfg(feature = "std")]
+            metrics: observer::Metrics::new(self.observer),
+            #[cfg(all(feature = "std", not(laika_loom)))]
+            alarm: OnceLock::new(),
+        });
+
+        let sender = Sender {
+            inner: _Sender {
+                receiver: Arc::downgrade(&receiver_ref),
+            },
+        };
+
+        (sender, Receiver::new(receiver_ref))
+    }
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Builder::new()
+    }
+}
+
+/// Observers can't be printed, so only whether one is set is shown.
+impl fmt::Debug for Builder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("Builder");
+
+        debug
+            .field("wake_order", &self.wake_order)
+            .field("waker_capacity", &self.waker_capacity)
+            .field("poison_policy", &self.poison_policy)
+            .field("max_receivers", &self.max_receivers);
+
+        #[cfg(feature = "std")]
+        debug.field("observer", &self.observer.is_some());
+
+        debug.finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::shotgun::{ReceiverLimitError, INLINE_WAKERS};
+
+    #[test]
+    fn test_builder() {
+        let (tx, rx) = Builder::new()
+            .wake_order(WakeOrder::Lifo)
+            .waker_capacity(INLINE_WAKERS + 8)
+            .max_receivers(2)
+            .build();
+
+        assert_eq!(rx.inner.lock().order, WakeOrder::Lifo);
+        assert_eq!(rx.inner.lock().spilled.capacity(), 8);
+        #[cfg(feature = "std")]
+        assert_eq!(rx.inner.poison_policy, PoisonPolicy::Recover);
+
+        let rx1 = rx.try_clone().unwrap();
+        assert_eq!(rx1.try_clone(), Err(ReceiverLimitError));
+
+        tx.send(1).unwrap();
+        assert_eq!(rx.try_recv(), Ok(1));
+    }
+
+    #[test]
+    fn test_default_builder() {
+        let (_tx, rx) = Builder::default().build::<u8>();
+
+        assert_eq!(rx.inner.lock().order, WakeOrder::Fifo);
+        assert_eq!(rx.inner.lock().spilled.capacity(), 0);
+        assert_eq!(rx.inner.max_receivers, usize::MAX);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_observer() {
+        use crate::shotgun::{ChannelId, ChannelState};
+        use core::sync::atomic::{AtomicUsize, Ordering};
+        use std::time::Duration;
+
+        /// Observer that counts dropped channels
+        struct Counter(AtomicUsize);
+
+        impl ChannelObserver for Counter {
+            fn on_drop(&self, _id: ChannelId, _elapsed: Duration, _state: ChannelState) {
+                self.0.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        static COUNTER: Counter = Counter(AtomicUsize::new(0));
+
+        let (tx, rx) = Builder::new().observer(&COUNTER).build::<u8>();
+        drop((tx, rx));
+
+        assert_eq!(COUNTER.0.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    #[cfg(all(feature = "std", not(any(laika_loom, feature = "parking_lot"))))]
+    fn test_propagate_poison() {
+        use std::{panic, thread};
+
+        let (tx, rx) = Builder::new()
+            .poison_policy(PoisonPolicy::Propagate)
+            .build::<u8>();
+
+        let inner = Arc::clone(&rx.inner);
+        let thread = thread::spawn(move || {
+            let _wakers = inner.lock();
+            panic!("panics while holding the lock");
+        });
+        assert!(thread.join().is_err());
+
+        // Taking the lock panics now, while lock-free reads keep working
+        let result = panic::catch_unwind(panic::AssertUnwindSafe(|| tx.send(1)));
+        assert!(result.is_err());
+        assert_eq!(rx.try_recv(), Ok(1));
+    }
+}
@@ -0,0 +1,95 @@
+//! Object-safe abstraction over senders, so libraries can accept any
+//! completion handle (e.g. a recording fake in tests) instead of a concrete
+//! [`Sender`].
+
+use super::{CompletionGuard, SendError, Sender};
+use alloc::boxed::Box;
+
+/// Handle that completes a pending operation with a value, like [`Sender`]
+///
+/// Object-safe, so functions can take a `Box<dyn Complete<T>>` and tests can
+/// pass a fake that records the value instead of a real channel. Implemented
+/// by [`Sender`] and [`CompletionGuard`].
+///
+/// # Examples
+/// ```rust
+/// use laika::shotgun::{Complete, SendError};
+/// use std::sync::{Arc, Mutex};
+///
+/// fn fetch(done: Box<dyn Complete<u8>>) {
+///     let _ = done.complete(12);
+/// }
+///
+/// // Production code passes a sender...
+/// let (tx, rx) = laika::shotgun::channel();
+/// fetch(Box::new(tx));
+/// assert_eq!(rx.try_recv(), Ok(12));
+///
+/// // ...while tests pass a recording fake.
+/// #[derive(Clone, Default)]
+/// struct Recorder(Arc<Mutex<Vec<u8>>>);
+///
+/// impl Complete<u8> for Recorder {
+///     fn complete(self: Box<Self>, value: u8) -> Result<(), SendError<u8>> {
+///         self.0.lock().unwrap().push(value);
+///         Ok(())
+///     }
+/// }
+///
+/// let recorder = Recorder::default();
+/// fetch(Box::new(recorder.clone()));
+/// assert_eq!(*recorder.0.lock().unwrap(), [12]);
+/// ```
+pub trait Complete<T> {
+    /// Completes the operation with `value`. Returns the value in a
+    /// [`SendError`] if nobody is waiting for it (anymore) or it has been
+    /// completed already. See [`Sender::send`].
+    fn complete(self: Box<Self>, value: T) -> Result<(), SendError<T>>;
+}
+
+impl<T> Complete<T> for Sender<T>
+where
+    T: Clone,
+{
+    fn complete(self: Box<Self>, value: T) -> Result<(), SendError<T>> {
+        self.send(value)
+    }
+}
+
+impl<T> Complete<T> for CompletionGuard<T>
+where
+    T: Clone,
+{
+    fn complete(self: Box<Self>, value: T) -> Result<(), SendError<T>> {
+        CompletionGuard::complete(*self, value)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::shotgun::channel;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn test_dyn_complete() {
+        let (tx, rx) = channel::<u8>();
+        let (guarded_tx, guarded_rx) = channel::<u8>();
+        let (disconnected_tx, _) = channel::<u8>();
+
+        let handles: [Box<dyn Complete<u8> + Send>; 3] = [
+            Box::new(tx),
+            Box::new(guarded_tx.send_on_drop(0)),
+            Box::new(disconnected_tx),
+        ];
+
+        let results = handles
+            .into_iter()
+            .map(|handle| handle.complete(1))
+            .collect::<Vec<_>>();
+
+        assert_eq!(results, [Ok(()), Ok(()), Err(SendError::Disconnected(1))]);
+        assert_eq!(rx.try_recv(), Ok(1));
+        assert_eq!(guarded_rx.try_recv(), Ok(1));
+    }
+}
@@ -0,0 +1,298 @@
+//! Channels that expire at a deadline given at construction, so consumers
+//! don't have to attach their own timeouts.
+
+use super::{_Channel, Builder, DeadlineRecvError, Receiver, RecvError, Sender, TryRecvError};
+use crate::timer::{self, alarm::Alarm};
+use alloc::{
+    sync::{Arc, Weak},
+    task::Wake,
+};
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+use std::time::Instant;
+
+/// Waker the alarm of a channel rings, expiring the channel if no value has
+/// been sent yet. Weak, so the alarm doesn't keep a dropped channel alive.
+struct Expire<T>(Weak<_Channel<T>>)
+where
+    T: Clone;
+
+impl<T> Wake for Expire<T>
+where
+    T: Clone + Send + Sync,
+{
+    fn wake(self: Arc<Self>) {
+        if let Some(channel) = self.0.upgrade() {
+            channel.expire();
+        }
+    }
+}
+
+/// Receiver of a [`channel_with_deadline`]
+///
+/// Like [`Receiver`], but resolves to [`DeadlineRecvError::Expired`] if no
+/// value has been sent by the deadline of the channel. Clones share the
+/// deadline.
+#[derive(Debug)]
+pub struct DeadlineReceiver<T>
+where
+    T: Clone,
+{
+    /// Receiver of the channel that expires
+    inner: Receiver<T>,
+    /// When the channel expires
+    deadline: Instant,
+}
+
+impl<T> DeadlineReceiver<T>
+where
+    T: Clone,
+{
+    /// Returns the deadline of the channel.
+    pub fn deadline(&self) -> Instant {
+        self.deadline
+    }
+
+    /// Returns whether the deadline of the channel passed without a value
+    /// having been sent.
+    ///
+    /// An expired channel counts as closed for everything that doesn't tell
+    /// expiry apart, like [`DeadlineReceiver::try_recv`] or
+    /// [`Receiver::state`].
+    pub fn is_expired(&self) -> bool {
+        self.inner.inner.is_expired()
+    }
+
+    /// Try to receive a value from the channel, if it has been sent. This
+    /// function is **non-blocking**. See [`Receiver::try_recv`].
+    ///
+    /// Returns [`TryRecvError::Closed`] if the channel expired, see
+    /// [`DeadlineReceiver::is_expired`].
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        self.inner.try_recv()
+    }
+
+    /// Polls for the value, registering the waker of `cx` if it hasn't been
+    /// sent yet. See [`Receiver::poll_recv`].
+    pub fn poll_recv(&self, cx: &mut Context<'_>) -> Poll<Result<T, DeadlineRecvError>> {
+        self.inner
+            .poll_recv(cx)
+            .map(|result| result.map_err(|RecvError| self.error()))
+    }
+
+    /// Receive a value from the channel asynchronously. See
+    /// [`Receiver::recv`].
+    pub async fn recv(self) -> Result<T, DeadlineRecvError> {
+        self.await
+    }
+
+    /// Receive a value from the channel, blocking the current thread until
+    /// the value has been sent or the channel expired. See
+    /// [`Receiver::recv_blocking`].
+    pub fn recv_blocking(&self) -> Result<T, DeadlineRecvError> {
+        self.inner.recv_blocking().map_err(|RecvError| self.error())
+    }
+
+    /// Returns the underlying [`Receiver`], which reports an expired channel
+    /// as closed.
+    pub fn into_inner(self) -> Receiver<T> {
+        self.inner
+    }
+
+    /// Returns why no value can be received.
+    fn error(&self) -> DeadlineRecvError {
+        if self.is_expired() {
+            DeadlineRecvError::Expired
+        } else {
+            DeadlineRecvError::Closed
+        }
+    }
+}
+
+/// Implement [`Future`] for [`DeadlineReceiver`] to be able to use it in async
+/// functions.
+impl<T> Future for DeadlineReceiver<T>
+where
+    T: Clone,
+{
+    type Output = Result<T, DeadlineRecvError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.poll_recv(cx)
+    }
+}
+
+/// Cloned [`DeadlineReceiver`]s start without a registered waker.
+impl<T> Clone for DeadlineReceiver<T>
+where
+    T: Clone,
+{
+    fn clone(&self) -> Self {
+        DeadlineReceiver {
+            inner: self.inner.clone(),
+            deadline: self.deadline,
+        }
+    }
+}
+
+/// Creates a [`channel`](super::channel) that expires at `deadline` if no value has been sent
+/// by then.
+///
+/// At the deadline, all receivers resolve to [`DeadlineRecvError::Expired`]
+/// (both pending and future ones) and sending fails with
+/// [`SendError::Disconnected`](super::SendError::Disconnected), without any
+/// consumer attaching its own timeout. A value sent before the deadline is
+/// received as usual, even after the deadline.
+///
+/// The deadlines of all channels are tracked by the timer thread of the crate,
+/// which is started on first use. Only available with the `std` feature.
+///
+/// # Panics
+/// Panics if the operating system fails to create the background thread.
+///
+/// # Examples
+/// ```rust
+/// use laika::shotgun::{DeadlineRecvError, SendError};
+/// use std::time::{Duration, Instant};
+///
+/// # tokio::runtime::Builder::new_current_thread()
+/// #     .build()
+/// #     .unwrap()
+/// #     .block_on(async {
+/// let deadline = Instant::now() + Duration::from_millis(10);
+/// let (tx, rx) = laika::shotgun::channel_with_deadline(deadline);
+///
+/// assert_eq!(rx.await, Err(DeadlineRecvError::Expired));
+/// assert_eq!(tx.send(12), Err(SendError::Disconnected(12)));
+/// # });
+/// ```
+pub fn channel_with_deadline<T>(deadline: Instant) -> (Sender<T>, DeadlineReceiver<T>)
+where
+    T: Clone + Send + Sync + 'static,
+{
+    Builder::new().build_with_deadline(deadline)
+}
+
+impl Builder {
+    /// Creates the channel, expiring at `deadline` if no value has been sent
+    /// by then. See [`channel_with_deadline`].
+    ///
+    /// # Examples
+    /// ```rust
+    /// use laika::shotgun::Builder;
+    /// use std::time::{Duration, Instant};
+    ///
+    /// let (tx, rx) = Builder::new()
+    ///     .max_receivers(4)
+    ///     .build_with_deadline(Instant::now() + Duration::from_secs(1));
+    ///
+    /// tx.send(12).unwrap();
+    /// assert_eq!(rx.recv_blocking(), Ok(12));
+    /// ```
+    pub fn build_with_deadline<T>(self, deadline: Instant) -> (Sender<T>, DeadlineReceiver<T>)
+    where
+        T: Clone + Send + Sync + 'static,
+    {
+        let (sender, receiver) = self.build();
+
+        if deadline <= Instant::now() {
+            receiver.inner.expire();
+        } else {
+            let expire = Waker::from(Arc::new(Expire(Arc::downgrade(&receiver.inner))));
+            let alarm = Arc::new(Alarm::default());
+            alarm.register(&expire);
+            timer::schedule(deadline, &alarm);
+
+            // Kept by the channel, as the timer only refers to the alarm
+            let _ = receiver.inner.alarm.set(alarm);
+        }
+
+        let receiver = DeadlineReceiver {
+            inner: receiver,
+            deadline,
+        };
+
+        (sender, receiver)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::shotgun::{ChannelState, SendError};
+    use std::{thread, time::Duration};
+
+    #[tokio::test]
+    async fn test_expired() {
+        let (tx, rx) = channel_with_deadline::<u8>(Instant::now() + Duration::from_millis(50));
+        let rx1 = rx.clone();
+
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+        assert!(!rx.is_expired());
+
+        let blocking = thread::spawn(move || rx1.recv_blocking());
+
+        assert_eq!(rx.clone().await, Err(DeadlineRecvError::Expired));
+        assert_eq!(blocking.join().unwrap(), Err(DeadlineRecvError::Expired));
+
+        assert!(rx.is_expired());
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Closed));
+        assert_eq!(rx.clone().into_inner().state(), ChannelState::Closed);
+        assert_eq!(tx.send(1), Err(SendError::Disconnected(1)));
+    }
+
+    #[test]
+    fn test_sent_before_deadline() {
+        let (tx, rx) = channel_with_deadline(Instant::now() + Duration::from_millis(20));
+
+        tx.send(1).unwrap();
+        thread::sleep(Duration::from_millis(50));
+
+        assert!(!rx.is_expired());
+        assert_eq!(rx.recv_blocking(), Ok(1));
+    }
+
+    #[test]
+    fn test_alarm_released() {
+        let (tx, rx) = channel_with_deadline(Instant::now() + Duration::from_secs(60));
+
+        // Referred to by the sender and the alarm
+        assert_eq!(Arc::weak_count(&rx.inner.inner), 2);
+
+        // Completing the channel cancels the alarm, so it doesn't keep the
+        // channel allocated until the deadline
+        tx.send(1).unwrap();
+        assert_eq!(Arc::weak_count(&rx.inner.inner), 0);
+    }
+
+    #[test]
+    fn test_closed_before_deadline() {
+        let (tx, rx) = channel_with_deadline::<u8>(Instant::now() + Duration::from_secs(60));
+
+        drop(tx);
+
+        assert_eq!(rx.recv_blocking(), Err(DeadlineRecvError::Closed));
+    }
+
+    #[test]
+    fn test_past_deadline() {
+        let (tx, rx) = channel_with_deadline(Instant::now());
+
+        assert!(rx.is_expired());
+        assert_eq!(tx.send(1), Err(SendError::Disconnected(1)));
+        assert_eq!(rx.recv_blocking(), Err(DeadlineRecvError::Expired));
+    }
+
+    #[test]
+    fn test_earlier_deadline_queued_later() {
+        let now = Instant::now();
+        let (_late_tx, late) = channel_with_deadline::<u8>(now + Duration::from_secs(60));
+        let (_early_tx, early) = channel_with_deadline::<u8>(now + Duration::from_millis(20));
+
+        assert_eq!(early.recv_blocking(), Err(DeadlineRecvError::Expired));
+        assert!(!late.is_expired());
+    }
+}
@@ -0,0 +1,242 @@
+//! Errors of sending on and receiving from channels.
+
+#[cfg(doc)]
+use super::{Receiver, Sender};
+use core::{error::Error, fmt};
+
+/// Error returned by [`Sender::send`] if the value couldn't be sent.
+/// Contains the value, so it can be reclaimed.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SendError<T> {
+    /// All [`Receiver`]s have been dropped, or the deadline of a channel
+    /// created by `channel_with_deadline` passed.
+    Disconnected(T),
+    /// Another clone of the [`Sender`] has sent a value already.
+    AlreadySent(T),
+}
+
+impl<T> SendError<T> {
+    /// Returns the value that couldn't be sent.
+    ///
+    /// # Examples
+    /// ```rust
+    /// let (tx, rx) = laika::shotgun::channel();
+    ///
+    /// drop(rx);
+    ///
+    /// assert_eq!(tx.send(12).unwrap_err().into_inner(), 12);
+    /// ```
+    pub fn into_inner(self) -> T {
+        match self {
+            SendError::Disconnected(value) | SendError::AlreadySent(value) => value,
+        }
+    }
+
+    /// Maps the contained value, keeping the reason why it couldn't be sent.
+    pub(super) fn map<U>(self, f: impl FnOnce(T) -> U) -> SendError<U> {
+        match self {
+            SendError::Disconnected(value) => SendError::Disconnected(f(value)),
+            SendError::AlreadySent(value) => SendError::AlreadySent(f(value)),
+        }
+    }
+}
+
+debug_without_value!(SendError {
+    Disconnected,
+    AlreadySent
+});
+
+impl<T> fmt::Display for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SendError::Disconnected(_) => write!(f, "sending on a channel without receivers"),
+            SendError::AlreadySent(_) => write!(f, "a value has been sent already"),
+        }
+    }
+}
+
+impl<T> Error for SendError<T> {}
+
+/// Error returned by [`Receiver::recv`] (or awaiting a [`Receiver`]) if the
+/// [`Sender`] has been dropped without sending a value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RecvError;
+
+impl fmt::Display for RecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "channel closed without a value being sent")
+    }
+}
+
+impl Error for RecvError {}
+
+/// Error returned by [`Receiver::try_recv`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TryRecvError {
+    /// No value has been sent yet, but the [`Sender`] still exists.
+    Empty,
+    /// The [`Sender`] has been dropped without sending a value, so no value
+    /// will ever be received.
+    Closed,
+}
+
+impl fmt::Display for TryRecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TryRecvError::Empty => write!(f, "no value has been sent yet"),
+            TryRecvError::Closed => write!(f, "channel closed without a value being sent"),
+        }
+    }
+}
+
+impl Error for TryRecvError {}
+
+/// A closed channel is the only reason for [`RecvError`].
+impl From<RecvError> for TryRecvError {
+    fn from(_: RecvError) -> Self {
+        TryRecvError::Closed
+    }
+}
+
+/// Error returned by [`Receiver::recv_timeout`] and [`Receiver::recv_deadline`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RecvTimeoutError {
+    /// No value has been sent before the timeout or deadline was reached.
+    Timeout,
+    /// The [`Sender`] has been dropped without sending a value, so no value
+    /// will ever be received.
+    Closed,
+}
+
+impl fmt::Display for RecvTimeoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecvTimeoutError::Timeout => write!(f, "timed out waiting for a value"),
+            RecvTimeoutError::Closed => write!(f, "channel closed without a value being sent"),
+        }
+    }
+}
+
+impl Error for RecvTimeoutError {}
+
+/// A closed channel is the only reason for [`RecvError`].
+impl From<RecvError> for RecvTimeoutError {
+    fn from(_: RecvError) -> Self {
+        RecvTimeoutError::Closed
+    }
+}
+
+/// Error returned by [`Receiver::recv_until`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RecvUntilError {
+    /// The cancellation future completed before a value has been sent.
+    Cancelled,
+    /// The [`Sender`] has been dropped without sending a value, so no value
+    /// will ever be received.
+    Closed,
+}
+
+impl fmt::Display for RecvUntilError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecvUntilError::Cancelled => write!(f, "cancelled waiting for a value"),
+            RecvUntilError::Closed => write!(f, "channel closed without a value being sent"),
+        }
+    }
+}
+
+impl Error for RecvUntilError {}
+
+/// A closed channel is the only reason for [`RecvError`].
+impl From<RecvError> for RecvUntilError {
+    fn from(_: RecvError) -> Self {
+        RecvUntilError::Closed
+    }
+}
+
+/// Error returned by receiving from a [`DeadlineReceiver`]
+///
+/// [`DeadlineReceiver`]: super::DeadlineReceiver
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeadlineRecvError {
+    /// The deadline of the channel passed before a value has been sent.
+    Expired,
+    /// The [`Sender`] has been dropped without sending a value, so no value
+    /// will ever be received.
+    Closed,
+}
+
+impl fmt::Display for DeadlineRecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeadlineRecvError::Expired => write!(f, "channel expired without a value being sent"),
+            DeadlineRecvError::Closed => write!(f, "channel closed without a value being sent"),
+        }
+    }
+}
+
+impl Error for DeadlineRecvError {}
+
+/// Error returned by [`Receiver::try_clone`] if the channel has reached its
+/// maximum number of receivers
+///
+/// [`Receiver::try_clone`]: super::Receiver::try_clone
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ReceiverLimitError;
+
+impl fmt::Display for ReceiverLimitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "channel has reached its maximum number of receivers")
+    }
+}
+
+impl Error for ReceiverLimitError {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::shotgun::channel;
+    use alloc::{boxed::Box, string::ToString};
+
+    #[test]
+    fn test_display() {
+        assert_eq!(
+            SendError::Disconnected(1).to_string(),
+            "sending on a channel without receivers"
+        );
+        assert_eq!(
+            SendError::AlreadySent(1).to_string(),
+            "a value has been sent already"
+        );
+        assert_eq!(
+            TryRecvError::from(RecvError).to_string(),
+            RecvError.to_string()
+        );
+    }
+
+    #[test]
+    fn test_question_mark() {
+        fn receive() -> Result<u8, Box<dyn Error>> {
+            let (tx, rx) = channel();
+
+            tx.send(1)?;
+            let value = rx.try_recv()?;
+
+            let (tx, rx) = channel();
+            drop(rx);
+            tx.send(value)?;
+
+            Ok(value)
+        }
+
+        let error = receive().unwrap_err();
+        assert!(error.is::<SendError<u8>>());
+    }
+
+    #[test]
+    fn test_from_recv_error() {
+        assert_eq!(TryRecvError::from(RecvError), TryRecvError::Closed);
+        assert_eq!(RecvTimeoutError::from(RecvError), RecvTimeoutError::Closed);
+        assert_eq!(RecvUntilError::from(RecvError), RecvUntilError::Closed);
+    }
+}
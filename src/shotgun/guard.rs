@@ -0,0 +1,157 @@
+//! Guard around [`Sender`] that sends a fallback value if it's dropped without
+//! completing, so waiters are never stranded.
+
+use super::{SendError, Sender};
+
+/// Sender that is guaranteed to send a value
+///
+/// Created by [`Sender::send_on_drop`]. Sends the value given to
+/// [`CompletionGuard::complete`], or the fallback value if the guard is dropped
+/// without completing, e.g. because the producing code returned early or
+/// panicked. Receivers then get the fallback (which may well be an error, if
+/// `T` is a [`Result`]) instead of [`RecvError`](super::RecvError).
+///
+/// # Examples
+/// ```rust
+/// use std::thread;
+///
+/// let (tx, rx) = laika::shotgun::channel::<Result<u8, &str>>();
+/// let guard = tx.send_on_drop(Err("worker panicked"));
+///
+/// let worker = thread::spawn(move || {
+///     let _guard = guard;
+///     panic!("Expected panic");
+/// });
+///
+/// assert!(worker.join().is_err());
+/// assert_eq!(rx.try_recv(), Ok(Err("worker panicked")));
+/// ```
+#[derive(Debug)]
+#[must_use = "dropping the guard sends the fallback value right away"]
+pub struct CompletionGuard<T>
+where
+    T: Clone,
+{
+    /// Sender of the channel, [`None`] once it has been used
+    sender: Option<Sender<T>>,
+    /// Value that is sent if the guard is dropped without completing
+    fallback: Option<T>,
+}
+
+impl<T> Sender<T>
+where
+    T: Clone,
+{
+    /// Turns the sender into a [`CompletionGuard`] that sends `fallback` if
+    /// it's dropped without [`CompletionGuard::complete`] being called.
+    ///
+    /// # Examples
+    /// ```rust
+    /// let (tx, rx) = laika::shotgun::channel();
+    ///
+    /// let guard = tx.send_on_drop(0);
+    /// guard.complete(12).unwrap();
+    ///
+    /// assert_eq!(rx.try_recv(), Ok(12));
+    /// ```
+    pub fn send_on_drop(self, fallback: T) -> CompletionGuard<T> {
+        CompletionGuard {
+            sender: Some(self),
+            fallback: Some(fallback),
+        }
+    }
+}
+
+impl<T> CompletionGuard<T>
+where
+    T: Clone,
+{
+    /// Send a value to all receivers of the channel instead of the fallback
+    /// value. See [`Sender::send`].
+    pub fn complete(mut self, value: T) -> Result<(), SendError<T>> {
+        self.fallback = None;
+
+        match self.sender.take() {
+            Some(sender) => sender.send(value),
+            None => unreachable!("Sender is only taken when consuming the guard"),
+        }
+    }
+
+    /// Returns the wrapped [`Sender`] without sending the fallback value.
+    pub fn disarm(mut self) -> Sender<T> {
+        self.fallback = None;
+
+        match self.sender.take() {
+            Some(sender) => sender,
+            None => unreachable!("Sender is only taken when consuming the guard"),
+        }
+    }
+}
+
+/// Sends the fallback value if the guard hasn't been completed. Errors are
+/// ignored, as nobody is waiting for the value then.
+impl<T> Drop for CompletionGuard<T>
+where
+    T: Clone,
+{
+    fn drop(&mut self) {
+        if let (Some(sender), Some(fallback)) = (self.sender.take(), self.fallback.take()) {
+            let _ = sender.send(fallback);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::shotgun::{channel, TryRecvError};
+
+    #[test]
+    fn test_complete() {
+        let (tx, rx) = channel();
+
+        tx.send_on_drop(0).complete(1).unwrap();
+        assert_eq!(rx.try_recv(), Ok(1));
+
+        let (tx, rx) = channel();
+        drop(rx);
+
+        assert_eq!(
+            tx.send_on_drop(0).complete(1),
+            Err(SendError::Disconnected(1))
+        );
+    }
+
+    #[test]
+    fn test_fallback() {
+        fn produce(guard: CompletionGuard<Result<u8, &'static str>>, fail: bool) -> Option<()> {
+            if fail {
+                return None;
+            }
+
+            guard.complete(Ok(1)).ok()
+        }
+
+        let (tx, rx) = channel();
+        assert_eq!(produce(tx.send_on_drop(Err("early return")), true), None);
+        assert_eq!(rx.try_recv(), Ok(Err("early return")));
+
+        let (tx, rx) = channel();
+        assert_eq!(
+            produce(tx.send_on_drop(Err("early return")), false),
+            Some(())
+        );
+        assert_eq!(rx.try_recv(), Ok(Ok(1)));
+    }
+
+    #[test]
+    fn test_disarm() {
+        let (tx, rx) = channel::<u8>();
+
+        let tx = tx.send_on_drop(0).disarm();
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+
+        drop(tx);
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Closed));
+    }
+}
@@ -0,0 +1,222 @@
+//! Conversions between shotgun channels and the channels of `tokio::sync`, so
+//! mixed codebases don't need hand-rolled bridge tasks.
+
+use super::{channel, Receiver};
+use tokio::sync::{broadcast, oneshot};
+
+/// Wraps a `tokio::sync::oneshot::Receiver` as a [`Receiver`], so its value
+/// can be shared with multiple consumers. If the tokio sender is dropped
+/// without sending a value, the returned channel is closed.
+///
+/// # Panics
+/// Spawns a task that waits for the value, so this panics if it's not called
+/// within a tokio runtime.
+///
+/// # Examples
+/// ```rust
+/// # tokio::runtime::Builder::new_current_thread()
+/// #     .build()
+/// #     .unwrap()
+/// #     .block_on(async {
+/// let (tx, rx) = tokio::sync::oneshot::channel();
+/// let rx = laika::shotgun::from_oneshot(rx);
+/// let rx1 = rx.clone();
+///
+/// tx.send(12).unwrap();
+///
+/// assert_eq!(rx.await, Ok(12));
+/// assert_eq!(rx1.await, Ok(12));
+/// # });
+/// ```
+pub fn from_oneshot<T>(receiver: oneshot::Receiver<T>) -> Receiver<T>
+where
+    T: Clone + Send + Sync + 'static,
+{
+    let (tx, rx) = channel();
+
+    tokio::spawn(async move {
+        if let Ok(value) = receiver.await {
+            let _ = tx.send(value);
+        }
+    });
+
+    rx
+}
+
+/// Wraps a `tokio::sync::broadcast::Receiver` as a [`Receiver`] that receives
+/// the next value of the broadcast channel. If the receiver lagged behind,
+/// the oldest value still available is taken. If the broadcast channel is
+/// closed before a value arrives, the returned channel is closed.
+///
+/// # Panics
+/// Spawns a task that waits for the value, so this panics if it's not called
+/// within a tokio runtime.
+///
+/// # Examples
+/// ```rust
+/// # tokio::runtime::Builder::new_current_thread()
+/// #     .build()
+/// #     .unwrap()
+/// #     .block_on(async {
+/// let (tx, rx) = tokio::sync::broadcast::channel(4);
+/// let rx = laika::shotgun::from_broadcast(rx);
+///
+/// tx.send(12).unwrap();
+/// tx.send(13).unwrap();
+///
+/// assert_eq!(rx.await, Ok(12));
+/// # });
+/// ```
+pub fn from_broadcast<T>(mut receiver: broadcast::Receiver<T>) -> Receiver<T>
+where
+    T: Clone + Send + Sync + 'static,
+{
+    let (tx, rx) = channel();
+
+    tokio::spawn(async move {
+        loop {
+            match receiver.recv().await {
+                Ok(value) => {
+                    let _ = tx.send(value);
+                    return;
+                }
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return,
+            }
+        }
+    });
+
+    rx
+}
+
+/// Converts a [`Receiver`] into a `tokio::sync::oneshot::Receiver` that
+/// receives a clone of the value. If the shotgun channel is closed without a
+/// value, the tokio channel is closed as well.
+///
+/// Uses [`Receiver::on_receive`], so no task is spawned and no runtime is
+/// required.
+///
+/// # Examples
+/// ```rust
+/// # tokio::runtime::Builder::new_current_thread()
+/// #     .build()
+/// #     .unwrap()
+/// #     .block_on(async {
+/// let (tx, rx) = laika::shotgun::channel();
+/// let rx = laika::shotgun::into_oneshot(rx);
+///
+/// tx.send(12).unwrap();
+///
+/// assert_eq!(rx.await, Ok(12));
+/// # });
+/// ```
+pub fn into_oneshot<T>(receiver: Receiver<T>) -> oneshot::Receiver<T>
+where
+    T: Clone + Send + Sync + 'static,
+{
+    let (tx, rx) = oneshot::channel();
+
+    receiver.on_receive(move |value| {
+        let _ = tx.send(value.clone());
+    });
+
+    rx
+}
+
+/// Forwards the value of a [`Receiver`] to a `tokio::sync::broadcast::Sender`
+/// once it has been sent. Nothing is forwarded if the shotgun channel is
+/// closed without a value.
+///
+/// Uses [`Receiver::on_receive`], so no task is spawned and no runtime is
+/// required.
+///
+/// # Examples
+/// ```rust
+/// # tokio::runtime::Builder::new_current_thread()
+/// #     .build()
+/// #     .unwrap()
+/// #     .block_on(async {
+/// let (shutdown_tx, shutdown_rx) = laika::shotgun::channel();
+/// let (events_tx, mut events_rx) = tokio::sync::broadcast::channel(4);
+///
+/// laika::shotgun::forward_to_broadcast(shutdown_rx, events_tx);
+/// shutdown_tx.send("shutdown").unwrap();
+///
+/// assert_eq!(events_rx.recv().await, Ok("shutdown"));
+/// # });
+/// ```
+pub fn forward_to_broadcast<T>(receiver: Receiver<T>, sender: broadcast::Sender<T>)
+where
+    T: Clone + Send + Sync + 'static,
+{
+    receiver.on_receive(move |value| {
+        let _ = sender.send(value.clone());
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::shotgun::{RecvError, TryRecvError};
+
+    #[tokio::test]
+    async fn test_from_oneshot() {
+        let (tx, rx) = oneshot::channel();
+        let rx = from_oneshot(rx);
+
+        tokio::task::yield_now().await;
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+
+        tx.send(1).unwrap();
+        assert_eq!(rx.await, Ok(1));
+
+        let (tx, rx) = oneshot::channel::<u8>();
+        let rx = from_oneshot(rx);
+
+        drop(tx);
+        assert_eq!(rx.await, Err(RecvError));
+    }
+
+    #[tokio::test]
+    async fn test_from_broadcast() {
+        let (tx, rx) = broadcast::channel(1);
+        let rx = from_broadcast(rx);
+
+        // Lags behind, so only the most recent value is left
+        tx.send(1).unwrap();
+        tx.send(2).unwrap();
+        assert_eq!(rx.await, Ok(2));
+
+        let (tx, rx) = broadcast::channel::<u8>(1);
+        let rx = from_broadcast(rx);
+
+        drop(tx);
+        assert_eq!(rx.await, Err(RecvError));
+    }
+
+    #[tokio::test]
+    async fn test_into_oneshot() {
+        let (tx, rx) = channel::<u8>();
+        let rx = into_oneshot(rx);
+
+        drop(tx);
+        assert!(rx.await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_forward_to_broadcast() {
+        let (tx, rx) = channel();
+        let (events_tx, mut events_rx) = broadcast::channel(2);
+
+        forward_to_broadcast(rx.clone(), events_tx.clone());
+        forward_to_broadcast(rx, events_tx);
+
+        tx.send(1).unwrap();
+        assert_eq!(events_rx.recv().await, Ok(1));
+        assert_eq!(events_rx.recv().await, Ok(1));
+        assert_eq!(
+            events_rx.recv().await,
+            Err(broadcast::error::RecvError::Closed)
+        );
+    }
+}
@@ -0,0 +1,160 @@
+//! Combinator that waits for the values of two channels at once.
+
+use super::{Receiver, RecvError};
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+/// Future that waits for the values of two [`Receiver`]s
+///
+/// Created by [`join`]. Resolves to both values once they have been sent, or to
+/// [`RecvError`] as soon as one of the channels is closed without a value.
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Join<A, B>
+where
+    A: Clone,
+    B: Clone,
+{
+    /// Receiver of the first value
+    a: Receiver<A>,
+    /// Receiver of the second value
+    b: Receiver<B>,
+    /// First value, once it has been received
+    value_a: Option<A>,
+    /// Second value, once it has been received
+    value_b: Option<B>,
+}
+
+/// [`Join`] is never pinned structurally, the received values are just moved
+/// out once both are available.
+impl<A, B> Unpin for Join<A, B>
+where
+    A: Clone,
+    B: Clone,
+{
+}
+
+impl<A, B> Future for Join<A, B>
+where
+    A: Clone,
+    B: Clone,
+{
+    type Output = Result<(A, B), RecvError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        if this.value_a.is_none() {
+            if let Poll::Ready(result) = this.a.poll_recv(cx) {
+                this.value_a = Some(result?);
+            }
+        }
+
+        if this.value_b.is_none() {
+            if let Poll::Ready(result) = this.b.poll_recv(cx) {
+                this.value_b = Some(result?);
+            }
+        }
+
+        match (this.value_a.take(), this.value_b.take()) {
+            (Some(a), Some(b)) => Poll::Ready(Ok((a, b))),
+            (a, b) => {
+                this.value_a = a;
+                this.value_b = b;
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// Waits for the values of two channels, so fan-in of several oneshot results
+/// doesn't require hand-written poll logic. Resolves to both values once they
+/// have been sent, or to [`RecvError`] as soon as one of the channels is
+/// closed without a value.
+///
+/// # Examples
+/// ```rust
+/// # tokio::runtime::Builder::new_current_thread()
+/// #     .build()
+/// #     .unwrap()
+/// #     .block_on(async {
+/// let (tx_user, rx_user) = laika::shotgun::channel();
+/// let (tx_quota, rx_quota) = laika::shotgun::channel();
+///
+/// tx_quota.send(42).unwrap();
+/// tx_user.send("laika").unwrap();
+///
+/// let result = laika::shotgun::join(rx_user, rx_quota).await;
+/// assert_eq!(result, Ok(("laika", 42)));
+/// # });
+/// ```
+pub fn join<A, B>(a: Receiver<A>, b: Receiver<B>) -> Join<A, B>
+where
+    A: Clone,
+    B: Clone,
+{
+    Join {
+        a,
+        b,
+        value_a: None,
+        value_b: None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::shotgun::channel;
+    use core::{pin::pin, task::Waker};
+
+    #[test]
+    fn test_join() {
+        let (tx_a, rx_a) = channel::<u8>();
+        let (tx_b, rx_b) = channel::<&str>();
+
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut joined = pin!(join(rx_a, rx_b));
+
+        assert_eq!(joined.as_mut().poll(&mut cx), Poll::Pending);
+
+        tx_b.send("b").unwrap();
+        assert_eq!(joined.as_mut().poll(&mut cx), Poll::Pending);
+
+        tx_a.send(1).unwrap();
+        assert_eq!(joined.as_mut().poll(&mut cx), Poll::Ready(Ok((1, "b"))));
+    }
+
+    #[test]
+    fn test_join_closed() {
+        let (tx_a, rx_a) = channel::<u8>();
+        let (tx_b, rx_b) = channel::<u8>();
+
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut joined = pin!(join(rx_a, rx_b));
+
+        tx_a.send(1).unwrap();
+        assert_eq!(joined.as_mut().poll(&mut cx), Poll::Pending);
+
+        drop(tx_b);
+        assert_eq!(joined.as_mut().poll(&mut cx), Poll::Ready(Err(RecvError)));
+    }
+
+    #[tokio::test]
+    async fn test_join_spawned() {
+        let (tx_a, rx_a) = channel();
+        let (tx_b, rx_b) = channel();
+
+        let joined = tokio::spawn(join(rx_a, rx_b));
+
+        tokio::task::yield_now().await;
+
+        tx_b.send('b').unwrap();
+        tokio::task::yield_now().await;
+        tx_a.send(1).unwrap();
+
+        assert_eq!(joined.await.unwrap(), Ok((1, 'b')));
+    }
+}
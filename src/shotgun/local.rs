@@ -0,0 +1,420 @@
+//! Single-threaded variant of [`channel`](super::channel) that doesn't need any
+//! locks or atomics.
+
+use super::{RecvError, SendError, TryRecvError};
+use alloc::{
+    rc::{Rc, Weak},
+    vec::Vec,
+};
+use core::{
+    cell::{Cell, RefCell},
+    future::Future,
+    mem,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+
+/// Shared state of a [`local_channel`], referenced by all [`LocalReceiver`]s
+/// and the [`LocalSender`]
+#[derive(Debug)]
+struct Channel<T> {
+    /// Value that was sent by [`LocalSender`]
+    value: Option<T>,
+    /// Whether the [`LocalSender`] has been dropped without sending a value
+    closed: bool,
+    /// Wakers of pending [`LocalReceiver`]s, [`None`] marks a free slot
+    wakers: Vec<Option<Waker>>,
+}
+
+impl<T> Channel<T>
+where
+    T: Clone,
+{
+    /// Returns (a clone of) the value, if it has been sent.
+    fn try_recv(&self) -> Result<T, TryRecvError> {
+        match &self.value {
+            Some(value) => Ok(value.clone()),
+            None if self.closed => Err(TryRecvError::Closed),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+}
+
+/// Oneshot sender of a [`local_channel`]
+///
+/// Like [`Sender`](super::Sender), but neither `Send` nor `Sync`.
+///
+/// # Examples
+/// ```rust
+/// let (tx, rx) = laika::shotgun::local_channel();
+///
+/// tx.send(12).unwrap();
+///
+/// assert_eq!(rx.try_recv(), Ok(12));
+/// ```
+#[derive(Debug)]
+pub struct LocalSender<T>
+where
+    T: Clone,
+{
+    /// Shared state, weak so that dropped receivers disconnect the sender
+    inner: Weak<RefCell<Channel<T>>>,
+}
+
+/// Oneshot receiver of a [`local_channel`]
+///
+/// Like [`Receiver`](super::Receiver), but neither `Send` nor `Sync`, so it
+/// can only be used on the thread (or single-threaded executor) that created
+/// it.
+///
+/// # Examples
+/// ```rust
+/// use std::rc::Rc;
+///
+/// # tokio::runtime::Builder::new_current_thread()
+/// #     .build()
+/// #     .unwrap()
+/// #     .block_on(async {
+/// // `Rc` isn't `Send`, so it couldn't be sent through a `channel`
+/// let (tx, rx) = laika::shotgun::local_channel::<Rc<str>>();
+/// let rx1 = rx.clone();
+///
+/// tx.send(Rc::from("local")).unwrap();
+///
+/// assert_eq!(rx.await.as_deref(), Ok("local"));
+/// assert_eq!(rx1.await.as_deref(), Ok("local"));
+/// # });
+/// ```
+#[derive(Debug)]
+pub struct LocalReceiver<T>
+where
+    T: Clone,
+{
+    /// Shared state that holds the sent value and possible wakers
+    inner: Rc<RefCell<Channel<T>>>,
+    /// Index of the waker this receiver registered while being polled, if any
+    key: Cell<Option<usize>>,
+}
+
+impl<T> LocalSender<T>
+where
+    T: Clone,
+{
+    /// Send a value to all receivers of the channel. As this is a oneshot
+    /// sender, only one value can be sent.
+    ///
+    /// Returns [`SendError::Disconnected`] containing the value if all
+    /// [`LocalReceiver`]s have been dropped already.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use laika::shotgun::SendError;
+    ///
+    /// let (tx, rx) = laika::shotgun::local_channel();
+    ///
+    /// drop(rx);
+    ///
+    /// assert_eq!(tx.send(12), Err(SendError::Disconnected(12)));
+    /// ```
+    pub fn send(self, value: T) -> Result<(), SendError<T>> {
+        let Some(channel) = self.inner.upgrade() else {
+            return Err(SendError::Disconnected(value));
+        };
+
+        // Woken up after releasing the borrow, as waking may poll receivers
+        // right away.
+        let wakers = {
+            let mut channel = channel.borrow_mut();
+            channel.value = Some(value);
+            mem::take(&mut channel.wakers)
+        };
+
+        wakers.into_iter().flatten().for_each(Waker::wake);
+
+        Ok(())
+    }
+
+    /// Returns the number of [`LocalReceiver`]s of the channel that currently
+    /// exist.
+    pub fn receiver_count(&self) -> usize {
+        self.inner.strong_count()
+    }
+
+    /// Returns whether all [`LocalReceiver`]s of the channel have been
+    /// dropped.
+    pub fn is_disconnected(&self) -> bool {
+        self.receiver_count() == 0
+    }
+}
+
+/// Closes the channel when the [`LocalSender`] is dropped without a value
+/// having been sent, so that waiting [`LocalReceiver`]s don't wait forever.
+impl<T> Drop for LocalSender<T>
+where
+    T: Clone,
+{
+    fn drop(&mut self) {
+        let Some(channel) = self.inner.upgrade() else {
+            return;
+        };
+
+        let wakers = {
+            let mut channel = channel.borrow_mut();
+
+            if channel.value.is_some() {
+                return;
+            }
+
+            channel.closed = true;
+            mem::take(&mut channel.wakers)
+        };
+
+        wakers.into_iter().flatten().for_each(Waker::wake);
+    }
+}
+
+impl<T> LocalReceiver<T>
+where
+    T: Clone,
+{
+    /// Try to receive a value from the channel, if it has been sent. This
+    /// function is **non-blocking**. See [`Receiver::try_recv`].
+    ///
+    /// [`Receiver::try_recv`]: super::Receiver::try_recv
+    ///
+    /// # Examples
+    /// ```rust
+    /// use laika::shotgun::TryRecvError;
+    ///
+    /// let (tx, rx) = laika::shotgun::local_channel::<u8>();
+    ///
+    /// assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+    ///
+    /// drop(tx);
+    /// assert_eq!(rx.try_recv(), Err(TryRecvError::Closed));
+    /// ```
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        self.inner.borrow().try_recv()
+    }
+
+    /// Polls for the value, registering the waker of `cx` if it hasn't been
+    /// sent yet. See [`Receiver::poll_recv`].
+    ///
+    /// [`Receiver::poll_recv`]: super::Receiver::poll_recv
+    pub fn poll_recv(&self, cx: &mut Context<'_>) -> Poll<Result<T, RecvError>> {
+        let mut channel = self.inner.borrow_mut();
+
+        match channel.try_recv() {
+            Ok(value) => return Poll::Ready(Ok(value)),
+            Err(TryRecvError::Closed) => return Poll::Ready(Err(RecvError)),
+            Err(TryRecvError::Empty) => {}
+        }
+
+        if let Some(Some(registered)) = self.key.get().and_then(|key| channel.wakers.get_mut(key)) {
+            if !registered.will_wake(cx.waker()) {
+                *registered = cx.waker().clone();
+            }
+
+            return Poll::Pending;
+        }
+
+        let waker = Some(cx.waker().clone());
+
+        let key = match channel.wakers.iter().position(Option::is_none) {
+            Some(key) => {
+                channel.wakers[key] = waker;
+                key
+            }
+            None => {
+                channel.wakers.push(waker);
+                channel.wakers.len() - 1
+            }
+        };
+
+        self.key.set(Some(key));
+        Poll::Pending
+    }
+
+    /// Receive a value from the channel asynchronously. See
+    /// [`Receiver::recv`].
+    ///
+    /// [`Receiver::recv`]: super::Receiver::recv
+    pub async fn recv(self) -> Result<T, RecvError> {
+        self.await
+    }
+}
+
+/// Implement [`Future`] for [`LocalReceiver`] to be able to use it in async
+/// functions.
+impl<T> Future for LocalReceiver<T>
+where
+    T: Clone,
+{
+    type Output = Result<T, RecvError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.poll_recv(cx)
+    }
+}
+
+impl<T> Clone for LocalReceiver<T>
+where
+    T: Clone,
+{
+    fn clone(&self) -> Self {
+        LocalReceiver {
+            inner: Rc::clone(&self.inner),
+            key: Cell::new(None),
+        }
+    }
+}
+
+/// Removes the registered waker (if any) when a [`LocalReceiver`] is dropped,
+/// so it isn't woken up pointlessly.
+impl<T> Drop for LocalReceiver<T>
+where
+    T: Clone,
+{
+    fn drop(&mut self) {
+        let Some(key) = self.key.take() else {
+            return;
+        };
+
+        if let Some(slot) = self.inner.borrow_mut().wakers.get_mut(key) {
+            *slot = None;
+        }
+    }
+}
+
+/// Creates a one-shot, single producer multiple consumer channel for a single
+/// thread, e.g. for `tokio::task::LocalSet` or GUI event loops.
+///
+/// Uses [`Rc`] and [`RefCell`] instead of atomics and locks, so neither the
+/// channel nor `T` have to be `Send`. Otherwise, it behaves like
+/// [`channel`](super::channel).
+///
+/// # Examples
+/// ```rust
+/// # tokio::runtime::Builder::new_current_thread()
+/// #     .build()
+/// #     .unwrap()
+/// #     .block_on(async {
+/// let local = tokio::task::LocalSet::new();
+///
+/// local
+///     .run_until(async {
+///         let (tx, rx) = laika::shotgun::local_channel();
+///         let task = tokio::task::spawn_local(rx.recv());
+///
+///         tx.send(12).unwrap();
+///         assert_eq!(task.await.unwrap(), Ok(12));
+///     })
+///     .await;
+/// # });
+/// ```
+pub fn local_channel<T>() -> (LocalSender<T>, LocalReceiver<T>)
+where
+    T: Clone,
+{
+    let inner = Rc::new(RefCell::new(Channel {
+        value: None,
+        closed: false,
+        wakers: Vec::new(),
+    }));
+
+    let sender = LocalSender {
+        inner: Rc::downgrade(&inner),
+    };
+
+    let receiver = LocalReceiver {
+        inner,
+        key: Cell::new(None),
+    };
+
+    (sender, receiver)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::rc::Rc;
+    use core::pin::pin;
+
+    #[test]
+    fn test_local_channel() {
+        let (tx, rx) = local_channel();
+        let rx1 = rx.clone();
+
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+        assert_eq!(tx.receiver_count(), 2);
+
+        tx.send(Rc::new(1)).unwrap();
+
+        assert_eq!(rx.try_recv(), Ok(Rc::new(1)));
+        assert_eq!(rx1.try_recv(), Ok(Rc::new(1)));
+    }
+
+    #[test]
+    fn test_local_close() {
+        let (tx, rx) = local_channel::<u8>();
+        let mut cx = Context::from_waker(Waker::noop());
+
+        let mut pending = pin!(rx.clone());
+        assert_eq!(pending.as_mut().poll(&mut cx), Poll::Pending);
+
+        drop(tx);
+        assert_eq!(pending.as_mut().poll(&mut cx), Poll::Ready(Err(RecvError)));
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Closed));
+
+        let (tx, rx) = local_channel();
+        drop(rx);
+
+        assert!(tx.is_disconnected());
+        assert_eq!(tx.send(1), Err(SendError::Disconnected(1)));
+    }
+
+    #[test]
+    fn test_local_wakers() {
+        let (tx, rx) = local_channel::<u8>();
+        let mut cx = Context::from_waker(Waker::noop());
+
+        let rx1 = rx.clone();
+        let rx2 = rx.clone();
+        assert!(rx.poll_recv(&mut cx).is_pending());
+        assert!(rx.poll_recv(&mut cx).is_pending());
+        assert!(rx1.poll_recv(&mut cx).is_pending());
+        assert_eq!(rx.inner.borrow().wakers.len(), 2);
+
+        // Freed slot is reused
+        drop(rx1);
+        assert!(rx2.poll_recv(&mut cx).is_pending());
+        assert_eq!(rx2.key.get(), Some(1));
+        assert_eq!(rx.inner.borrow().wakers.len(), 2);
+
+        tx.send(1).unwrap();
+        assert!(rx.inner.borrow().wakers.is_empty());
+        assert_eq!(rx2.poll_recv(&mut cx), Poll::Ready(Ok(1)));
+    }
+
+    #[tokio::test]
+    async fn test_local_spawned() {
+        let local = tokio::task::LocalSet::new();
+
+        local
+            .run_until(async {
+                let (tx, rx) = local_channel();
+                let tasks = [
+                    tokio::task::spawn_local(rx.clone().recv()),
+                    tokio::task::spawn_local(rx.recv()),
+                ];
+
+                tokio::task::yield_now().await;
+                tx.send(Rc::from("local")).unwrap();
+
+                for task in tasks {
+                    assert_eq!(task.await.unwrap().as_deref(), Ok("local"));
+                }
+            })
+            .await;
+    }
+}
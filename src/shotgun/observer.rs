@@ -0,0 +1,230 @@
+//! Global hooks into the lifecycle of channels, e.g. to export metrics.
+
+use super::ChannelState;
+use core::{
+    fmt,
+    sync::atomic::{AtomicU64, Ordering},
+};
+use std::{
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
+
+/// Observer that is installed by [`set_observer`]
+static OBSERVER: OnceLock<&'static dyn ChannelObserver> = OnceLock::new();
+
+/// Id of the next observed channel
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Id of an observed channel, unique within the process
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ChannelId(u64);
+
+impl fmt::Display for ChannelId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Hooks that are called during the lifecycle of every channel, so services
+/// can export metrics (like "oneshot wait latency" histograms) without
+/// wrapping the channel types
+///
+/// Installed globally by [`set_observer`]. All hooks do nothing by default,
+/// so only the relevant ones have to be implemented. Every hook gets the id
+/// of the channel and the time that elapsed since the channel has been
+/// created. Hooks are called synchronously by whichever thread triggers the
+/// event, so they should be cheap. Hooks of different events may run
+/// concurrently on different threads, e.g. a receiver may observe the value
+/// while [`ChannelObserver::on_send`] is still running.
+///
+/// This applies to all channels that are backed by [`channel`](super::channel)
+/// and have been created after the observer has been installed, including the
+/// ones used internally (like by [`arc_channel`](super::arc_channel)). Single
+/// channels can be given their own observer by [`Builder::observer`].
+/// Only available with the `std` feature.
+///
+/// [`Builder::observer`]: super::Builder::observer
+pub trait ChannelObserver: Send + Sync {
+    /// Called when a channel is created.
+    fn on_create(&self, _id: ChannelId) {}
+
+    /// Called when a value is sent on the channel.
+    fn on_send(&self, _id: ChannelId, _elapsed: Duration) {}
+
+    /// Called when a receiver observes the value of the channel for the
+    /// first time, e.g. by receiving it or running a callback with it.
+    fn on_first_recv(&self, _id: ChannelId, _elapsed: Duration) {}
+
+    /// Called when the last receiver of the channel is dropped, with the
+    /// final state of the channel. [`ChannelState::Empty`] means that all
+    /// receivers gave up before a value has been sent.
+    fn on_drop(&self, _id: ChannelId, _elapsed: Duration, _state: ChannelState) {}
+}
+
+/// Installs `observer` for all channels that are created from now on.
+///
+/// The observer can only be installed once per process. Returns the given
+/// observer as error if one has been installed already.
+///
+/// # Examples
+/// ```rust
+/// use laika::shotgun::{ChannelId, ChannelObserver};
+/// use std::time::Duration;
+///
+/// struct Latency;
+///
+/// impl ChannelObserver for Latency {
+///     fn on_first_recv(&self, id: ChannelId, elapsed: Duration) {
+///         println!("channel {id} was received after {elapsed:?}");
+///     }
+/// }
+///
+/// assert!(laika::shotgun::set_observer(&Latency).is_ok());
+/// assert!(laika::shotgun::set_observer(&Latency).is_err());
+///
+/// let (tx, rx) = laika::shotgun::channel();
+/// tx.send(12).unwrap();
+/// assert_eq!(rx.try_recv(), Ok(12));
+/// ```
+pub fn set_observer(
+    observer: &'static dyn ChannelObserver,
+) -> Result<(), &'static dyn ChannelObserver> {
+    OBSERVER.set(observer)
+}
+
+/// Lifecycle of a channel that is reported to the installed
+/// [`ChannelObserver`]
+pub(super) struct Metrics {
+    /// Observer that is notified
+    observer: &'static dyn ChannelObserver,
+    /// Id of the channel
+    id: ChannelId,
+    /// When the channel has been created
+    created: Instant,
+}
+
+impl Metrics {
+    /// Starts observing a new channel by `observer`, or by the installed
+    /// observer if there is none.
+    pub(super) fn new(observer: Option<&'static dyn ChannelObserver>) -> Option<Self> {
+        let observer = observer.or_else(|| OBSERVER.get().copied())?;
+        let id = ChannelId(NEXT_ID.fetch_add(1, Ordering::Relaxed));
+
+        observer.on_create(id);
+
+        Some(Metrics {
+            observer,
+            id,
+            created: Instant::now(),
+        })
+    }
+
+    /// Reports that the value has been sent.
+    pub(super) fn sent(&self) {
+        self.observer.on_send(self.id, self.created.elapsed());
+    }
+
+    /// Reports that the value has been observed for the first time.
+    pub(super) fn first_recv(&self) {
+        self.observer.on_first_recv(self.id, self.created.elapsed());
+    }
+
+    /// Reports that the channel has been dropped in `state`.
+    pub(super) fn dropped(&self, state: ChannelState) {
+        self.observer
+            .on_drop(self.id, self.created.elapsed(), state);
+    }
+}
+
+impl fmt::Debug for Metrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Metrics")
+            .field("id", &self.id)
+            .field("created", &self.created)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::shotgun::Builder;
+    use std::sync::Mutex;
+
+    /// Event reported to [`Recorder`]
+    #[derive(Debug, PartialEq, Eq)]
+    enum Event {
+        Create,
+        Send,
+        FirstRecv,
+        Drop(ChannelState),
+    }
+
+    /// Observer that records all events
+    struct Recorder(Mutex<Vec<(ChannelId, Event)>>);
+
+    impl Recorder {
+        /// Takes the recorded events, which all have to be of the channel `id`.
+        fn events(&self, id: ChannelId) -> Vec<Event> {
+            let events = core::mem::take(&mut *self.0.lock().unwrap());
+            assert!(events.iter().all(|(event_id, _)| *event_id == id));
+            events.into_iter().map(|(_, event)| event).collect()
+        }
+    }
+
+    impl ChannelObserver for Recorder {
+        fn on_create(&self, id: ChannelId) {
+            self.0.lock().unwrap().push((id, Event::Create));
+        }
+
+        fn on_send(&self, id: ChannelId, _elapsed: Duration) {
+            self.0.lock().unwrap().push((id, Event::Send));
+        }
+
+        fn on_first_recv(&self, id: ChannelId, _elapsed: Duration) {
+            self.0.lock().unwrap().push((id, Event::FirstRecv));
+        }
+
+        fn on_drop(&self, id: ChannelId, _elapsed: Duration, state: ChannelState) {
+            self.0.lock().unwrap().push((id, Event::Drop(state)));
+        }
+    }
+
+    #[test]
+    fn test_observer() {
+        // Observes only the channels built with it, as installing it globally
+        // would observe the channels of all other tests as well
+        static RECORDER: Recorder = Recorder(Mutex::new(Vec::new()));
+
+        let (tx, rx) = Builder::new().observer(&RECORDER).build();
+        let id = rx.inner.metrics.as_ref().unwrap().id;
+        let rx1 = rx.clone();
+
+        tx.send(1).unwrap();
+        assert_eq!(rx.try_recv(), Ok(1));
+        assert_eq!(rx1.try_recv(), Ok(1));
+        drop((rx, rx1));
+
+        assert_eq!(
+            RECORDER.events(id),
+            [
+                Event::Create,
+                Event::Send,
+                Event::FirstRecv,
+                Event::Drop(ChannelState::Sent)
+            ]
+        );
+
+        let (tx, rx) = Builder::new().observer(&RECORDER).build::<u8>();
+        let id = rx.inner.metrics.as_ref().unwrap().id;
+
+        drop(rx);
+        drop(tx);
+
+        assert_eq!(
+            RECORDER.events(id),
+            [Event::Create, Event::Drop(ChannelState::Empty)]
+        );
+    }
+}
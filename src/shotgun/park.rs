@@ -0,0 +1,134 @@
+//! Blocking on a channel with a custom blocking primitive, e.g. in `no_std`
+//! builds.
+
+use super::{Receiver, RecvError};
+use alloc::{sync::Arc, task::Wake};
+use core::task::{Context, Poll, Waker};
+
+/// Blocking primitive used by [`Receiver::recv_blocking_with`]
+///
+/// Allows blocking on a channel without `std`, e.g. by waiting for an
+/// interrupt on embedded targets. The parker is woken up via [`Wake`] once the
+/// value has been sent or the channel has been closed.
+///
+/// Implementations must not lose wake-ups: If the parker is woken up before
+/// [`Park::park`] is called, `park` has to return right away (like
+/// [`std::thread::park`] does with its token). Returning spuriously is fine.
+///
+/// # Examples
+/// ```rust
+/// use laika::shotgun::Park;
+/// use std::{
+///     sync::Arc,
+///     task::Wake,
+///     thread::{self, Thread},
+/// };
+///
+/// struct ThreadParker(Thread);
+///
+/// impl Wake for ThreadParker {
+///     fn wake(self: Arc<Self>) {
+///         self.0.unpark();
+///     }
+/// }
+///
+/// impl Park for ThreadParker {
+///     fn park(&self) {
+///         thread::park();
+///     }
+/// }
+///
+/// let (tx, rx) = laika::shotgun::channel();
+/// let parker = Arc::new(ThreadParker(thread::current()));
+///
+/// thread::spawn(move || tx.send(12).unwrap());
+///
+/// assert_eq!(rx.recv_blocking_with(&parker), Ok(12));
+/// ```
+pub trait Park: Wake + Send + Sync + 'static {
+    /// Blocks the current thread until the parker is woken up.
+    fn park(&self);
+}
+
+impl<T> Receiver<T>
+where
+    T: Clone,
+{
+    /// Receive a value from the channel, blocking on `parker` until the value
+    /// has been sent. If the [`super::Sender`] is dropped without sending a
+    /// value, [`RecvError`] is returned instead.
+    ///
+    /// Unlike [`Receiver::recv_blocking`], this doesn't require `std`, so it's
+    /// meant for `no_std` targets that bring their own blocking primitive. See
+    /// [`Park`] for an example.
+    pub fn recv_blocking_with<P>(&self, parker: &Arc<P>) -> Result<T, RecvError>
+    where
+        P: Park,
+    {
+        let waker = Waker::from(Arc::clone(parker));
+        let mut cx = Context::from_waker(&waker);
+
+        loop {
+            match self.poll_recv(&mut cx) {
+                Poll::Ready(result) => return result,
+                Poll::Pending => parker.park(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::shotgun::channel;
+    use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::thread;
+
+    /// Parker that spins on a flag, like a `no_std` target without threads
+    /// might do
+    #[derive(Default)]
+    struct SpinParker {
+        woken: AtomicBool,
+        parked: AtomicUsize,
+    }
+
+    impl Wake for SpinParker {
+        fn wake(self: Arc<Self>) {
+            self.woken.store(true, Ordering::Release);
+        }
+    }
+
+    impl Park for SpinParker {
+        fn park(&self) {
+            self.parked.fetch_add(1, Ordering::Relaxed);
+
+            while !self.woken.swap(false, Ordering::Acquire) {
+                core::hint::spin_loop();
+            }
+        }
+    }
+
+    #[test]
+    fn test_recv_blocking_with() {
+        let parker = Arc::new(SpinParker::default());
+
+        let (tx, rx) = channel();
+        tx.send(1).unwrap();
+        assert_eq!(rx.recv_blocking_with(&parker), Ok(1));
+        assert_eq!(parker.parked.load(Ordering::Relaxed), 0);
+
+        let (tx, rx) = channel();
+        let thread = thread::spawn(move || {
+            thread::sleep(std::time::Duration::from_millis(50));
+            tx.send(2).unwrap();
+        });
+
+        assert_eq!(rx.recv_blocking_with(&parker), Ok(2));
+        assert!(parker.parked.load(Ordering::Relaxed) >= 1);
+        thread.join().unwrap();
+
+        let (tx, rx) = channel::<u8>();
+        thread::spawn(move || drop(tx));
+        assert_eq!(rx.recv_blocking_with(&parker), Err(RecvError));
+    }
+}
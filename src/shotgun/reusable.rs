@@ -0,0 +1,155 @@
+//! Wrapper around [`Sender`] that sends through a mutable reference, for
+//! contexts like `FnMut` callbacks that can't give up ownership.
+
+use super::{SendError, Sender};
+
+/// Sender that can be called repeatedly, but sends only once
+///
+/// [`Sender::send`] consumes the sender, so sending from within an `FnMut`
+/// callback requires an `Option::take` dance. [`ReusableSender::send`] takes
+/// `&mut self` instead and fails with [`SendError::AlreadySent`] on every call
+/// after the first one. Created from a [`Sender`] via [`From`].
+///
+/// # Examples
+/// ```rust
+/// use laika::shotgun::{ReusableSender, SendError};
+///
+/// let (tx, rx) = laika::shotgun::channel();
+/// let mut tx = ReusableSender::from(tx);
+///
+/// let mut on_event = |id| tx.send(id);
+///
+/// assert_eq!(on_event(1), Ok(()));
+/// assert_eq!(on_event(2), Err(SendError::AlreadySent(2)));
+///
+/// assert_eq!(rx.try_recv(), Ok(1));
+/// ```
+#[derive(Debug)]
+pub struct ReusableSender<T>
+where
+    T: Clone,
+{
+    /// Sender that hasn't been used yet, if any
+    inner: Option<Sender<T>>,
+    /// Whether a value has been sent on the channel, telling apart why
+    /// `inner` is gone
+    sent: bool,
+}
+
+impl<T> ReusableSender<T>
+where
+    T: Clone,
+{
+    /// Send a value to all receivers of the channel, if no value has been sent
+    /// yet. See [`Sender::send`].
+    ///
+    /// Returns [`SendError::AlreadySent`] containing the value if this sender
+    /// (or a clone of the wrapped [`Sender`]) has sent a value already, or
+    /// [`SendError::Disconnected`] if all receivers have been dropped.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use laika::shotgun::{ReusableSender, SendError};
+    ///
+    /// let (tx, rx) = laika::shotgun::channel();
+    /// let mut tx = ReusableSender::from(tx);
+    ///
+    /// drop(rx);
+    ///
+    /// assert_eq!(tx.send(1), Err(SendError::Disconnected(1)));
+    /// assert_eq!(tx.send(2), Err(SendError::Disconnected(2)));
+    /// ```
+    pub fn send(&mut self, value: T) -> Result<(), SendError<T>> {
+        let Some(sender) = self.inner.take() else {
+            return Err(if self.sent {
+                SendError::AlreadySent(value)
+            } else {
+                SendError::Disconnected(value)
+            });
+        };
+
+        let result = sender.send(value);
+        self.sent = !matches!(result, Err(SendError::Disconnected(_)));
+
+        result
+    }
+
+    /// Returns whether a value has been sent on the channel, either by this
+    /// sender or by a clone of the wrapped [`Sender`]. Values sent by clones
+    /// are only seen while a receiver of the channel exists.
+    pub fn is_sent(&self) -> bool {
+        self.sent
+            || self
+                .inner
+                .as_ref()
+                .is_some_and(|sender| sender.inner.is_sent())
+    }
+}
+
+impl<T> From<Sender<T>> for ReusableSender<T>
+where
+    T: Clone,
+{
+    fn from(sender: Sender<T>) -> Self {
+        ReusableSender {
+            inner: Some(sender),
+            sent: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::shotgun::channel;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn test_reusable_sender() {
+        let (tx, rx) = channel();
+        let mut tx = ReusableSender::from(tx);
+
+        assert!(!tx.is_sent());
+        assert_eq!(tx.send(1), Ok(()));
+        assert!(tx.is_sent());
+        assert_eq!(tx.send(2), Err(SendError::AlreadySent(2)));
+
+        assert_eq!(rx.try_recv(), Ok(1));
+    }
+
+    #[test]
+    fn test_reusable_sender_racing() {
+        let (tx, rx) = channel();
+        let tx1 = tx.clone();
+        let mut tx = ReusableSender::from(tx);
+
+        assert!(!tx.is_sent());
+        tx1.send(1).unwrap();
+
+        // Seen before this sender tries to send itself
+        assert!(tx.is_sent());
+        assert_eq!(tx.send(2), Err(SendError::AlreadySent(2)));
+        assert!(tx.is_sent());
+        assert_eq!(tx.send(3), Err(SendError::AlreadySent(3)));
+
+        assert_eq!(rx.try_recv(), Ok(1));
+    }
+
+    #[test]
+    fn test_reusable_sender_in_callback() {
+        let (tx, rx) = channel();
+        let mut tx = ReusableSender::from(tx);
+
+        let results = (1..=3).map(|i| tx.send(i)).collect::<Vec<_>>();
+        assert_eq!(
+            results,
+            [
+                Ok(()),
+                Err(SendError::AlreadySent(2)),
+                Err(SendError::AlreadySent(3))
+            ]
+        );
+
+        assert_eq!(rx.try_recv(), Ok(1));
+    }
+}
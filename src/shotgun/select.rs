@@ -0,0 +1,149 @@
+//! Combinator that waits for the first value of a set of channels.
+
+use super::{Receiver, RecvError};
+use alloc::vec::Vec;
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+/// Future that waits for the first value of a set of [`Receiver`]s
+///
+/// Created by [`select_any`]. Resolves to the first value that is sent
+/// together with the index of its receiver, or to [`RecvError`] if all
+/// channels are closed without a value.
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct SelectAny<T>
+where
+    T: Clone,
+{
+    /// Receivers that are waited for, [`None`] marks closed channels
+    receivers: Vec<Option<Receiver<T>>>,
+}
+
+impl<T> Future for SelectAny<T>
+where
+    T: Clone,
+{
+    type Output = Result<(usize, T), RecvError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        for (index, slot) in this.receivers.iter_mut().enumerate() {
+            let Some(receiver) = slot else {
+                continue;
+            };
+
+            match receiver.poll_recv(cx) {
+                Poll::Ready(Ok(value)) => return Poll::Ready(Ok((index, value))),
+                // Closed channels can't deliver anything, so they're dropped
+                // (and their wakers with them) and skipped from now on.
+                Poll::Ready(Err(RecvError)) => *slot = None,
+                Poll::Pending => {}
+            }
+        }
+
+        if this.receivers.iter().all(Option::is_none) {
+            Poll::Ready(Err(RecvError))
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+/// Waits for the first value of a set of channels, e.g. to race multiple
+/// backends. Resolves to the first value that is sent together with the index
+/// of its receiver (in iteration order). Closed channels are skipped, so
+/// [`RecvError`] is only returned once all channels have been closed without a
+/// value (or if no receivers are given at all).
+///
+/// If multiple values are available already, the one with the lowest index
+/// wins.
+///
+/// # Examples
+/// ```rust
+/// # tokio::runtime::Builder::new_current_thread()
+/// #     .build()
+/// #     .unwrap()
+/// #     .block_on(async {
+/// let (tx_primary, rx_primary) = laika::shotgun::channel::<&str>();
+/// let (tx_fallback, rx_fallback) = laika::shotgun::channel();
+///
+/// drop(tx_primary);
+/// tx_fallback.send("fallback").unwrap();
+///
+/// let result = laika::shotgun::select_any([rx_primary, rx_fallback]).await;
+/// assert_eq!(result, Ok((1, "fallback")));
+/// # });
+/// ```
+pub fn select_any<T, I>(receivers: I) -> SelectAny<T>
+where
+    T: Clone,
+    I: IntoIterator<Item = Receiver<T>>,
+{
+    SelectAny {
+        receivers: receivers.into_iter().map(Some).collect(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::shotgun::channel;
+    use core::{pin::pin, task::Waker};
+
+    #[test]
+    fn test_select_any() {
+        let (tx_a, rx_a) = channel::<u8>();
+        let (tx_b, rx_b) = channel::<u8>();
+        let (tx_c, rx_c) = channel::<u8>();
+
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut selected = pin!(select_any([rx_a, rx_b, rx_c]));
+
+        assert_eq!(selected.as_mut().poll(&mut cx), Poll::Pending);
+
+        drop(tx_a);
+        assert_eq!(selected.as_mut().poll(&mut cx), Poll::Pending);
+
+        tx_c.send(3).unwrap();
+        tx_b.send(2).unwrap();
+        assert_eq!(selected.as_mut().poll(&mut cx), Poll::Ready(Ok((1, 2))));
+    }
+
+    #[test]
+    fn test_select_any_closed() {
+        let (tx_a, rx_a) = channel::<u8>();
+        let (tx_b, rx_b) = channel::<u8>();
+
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut selected = pin!(select_any([rx_a, rx_b]));
+
+        drop(tx_a);
+        assert_eq!(selected.as_mut().poll(&mut cx), Poll::Pending);
+
+        drop(tx_b);
+        assert_eq!(selected.as_mut().poll(&mut cx), Poll::Ready(Err(RecvError)));
+
+        let mut selected = pin!(select_any(Vec::<Receiver<u8>>::new()));
+        assert_eq!(selected.as_mut().poll(&mut cx), Poll::Ready(Err(RecvError)));
+    }
+
+    #[tokio::test]
+    async fn test_select_any_spawned() {
+        let (senders, receivers): (Vec<_>, Vec<_>) = (0..4).map(|_| channel()).unzip();
+
+        let selected = tokio::spawn(select_any(receivers));
+
+        tokio::task::yield_now().await;
+
+        let mut senders = senders.into_iter();
+        drop(senders.next());
+        senders.nth(1).unwrap().send("third").unwrap();
+
+        assert_eq!(selected.await.unwrap(), Ok((2, "third")));
+    }
+}
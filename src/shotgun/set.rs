@@ -0,0 +1,313 @@
+//! Keyed collection of pending channels, e.g. to correlate requests with their
+//! responses.
+
+use super::{channel, sync::Mutex, Receiver, SendError, Sender, WeakReceiver};
+use std::{
+    borrow::Borrow,
+    collections::HashMap,
+    hash::Hash,
+    mem,
+    time::{Duration, Instant},
+};
+
+/// Pending channel of a [`ShotgunSet`]
+#[derive(Debug)]
+struct Entry<T>
+where
+    T: Clone,
+{
+    /// Sender that completes the channel
+    sender: Sender<T>,
+    /// Receiver to hand out further receivers for the same key
+    receiver: WeakReceiver<T>,
+    /// When the entry has been inserted, used for expiry
+    inserted: Instant,
+}
+
+/// Keyed collection of pending channels
+///
+/// The classic request/response correlation map: [`ShotgunSet::insert`] a
+/// channel for a request id and wait on the returned [`Receiver`], then
+/// [`ShotgunSet::complete`] it once the response arrives. Entries that never
+/// get completed (e.g. because the response got lost) can be removed with
+/// [`ShotgunSet::expire`], closing their channels.
+///
+/// All methods take `&self`, so the set can be shared (e.g. in an
+/// [`std::sync::Arc`]) between the code sending requests and the code
+/// receiving responses. Only available with the `std` feature.
+///
+/// # Examples
+/// ```rust
+/// use laika::shotgun::ShotgunSet;
+///
+/// let pending = ShotgunSet::new();
+///
+/// let response = pending.insert(17);
+/// assert!(pending.contains_key(&17));
+///
+/// // Response to request 17 arrives
+/// pending.complete(&17, "pong").unwrap();
+///
+/// assert_eq!(response.try_recv(), Ok("pong"));
+/// assert!(pending.is_empty());
+/// ```
+#[derive(Debug)]
+pub struct ShotgunSet<K, T>
+where
+    T: Clone,
+{
+    /// Pending channels by their key
+    entries: Mutex<HashMap<K, Entry<T>>>,
+}
+
+impl<K, T> ShotgunSet<K, T>
+where
+    K: Eq + Hash,
+    T: Clone,
+{
+    /// Creates an empty set.
+    pub fn new() -> Self {
+        ShotgunSet {
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Inserts a pending channel for `key` and returns its [`Receiver`].
+    ///
+    /// If a channel is pending for `key` already, a receiver of that channel
+    /// is returned instead, so multiple consumers can wait for the same key.
+    ///
+    /// # Examples
+    /// ```rust
+    /// let pending = laika::shotgun::ShotgunSet::new();
+    ///
+    /// let rx = pending.insert("request");
+    /// let rx1 = pending.insert("request");
+    /// assert_eq!(pending.len(), 1);
+    ///
+    /// pending.complete("request", 12).unwrap();
+    ///
+    /// assert_eq!(rx.try_recv(), Ok(12));
+    /// assert_eq!(rx1.try_recv(), Ok(12));
+    /// ```
+    pub fn insert(&self, key: K) -> Receiver<T> {
+        let mut entries = self.entries.lock();
+
+        if let Some(receiver) = entries.get(&key).and_then(|entry| entry.receiver.upgrade()) {
+            return receiver;
+        }
+
+        let (sender, receiver) = channel();
+
+        entries.insert(
+            key,
+            Entry {
+                sender,
+                receiver: receiver.downgrade(),
+                inserted: Instant::now(),
+            },
+        );
+
+        receiver
+    }
+
+    /// Completes the pending channel of `key` by sending `value` to all of
+    /// its receivers and removes it from the set.
+    ///
+    /// Returns [`SendError::Disconnected`] containing the value if no channel
+    /// is pending for `key` or all of its receivers have been dropped.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use laika::shotgun::SendError;
+    ///
+    /// let pending = laika::shotgun::ShotgunSet::<u32, _>::new();
+    ///
+    /// assert_eq!(pending.complete(&3, 12), Err(SendError::Disconnected(12)));
+    /// ```
+    pub fn complete<Q>(&self, key: &Q, value: T) -> Result<(), SendError<T>>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        // Sending wakes up the receivers, so it's done after releasing the
+        // lock.
+        let entry = self.entries.lock().remove(key);
+
+        match entry {
+            Some(entry) => entry.sender.send(value),
+            None => Err(SendError::Disconnected(value)),
+        }
+    }
+
+    /// Removes the pending channel of `key`, closing it. Returns whether a
+    /// channel was pending for `key`.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use laika::shotgun::TryRecvError;
+    ///
+    /// let pending = laika::shotgun::ShotgunSet::<_, u8>::new();
+    /// let rx = pending.insert(3);
+    ///
+    /// assert!(pending.cancel(&3));
+    /// assert_eq!(rx.try_recv(), Err(TryRecvError::Closed));
+    /// ```
+    pub fn cancel<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let entry = self.entries.lock().remove(key);
+        entry.is_some()
+    }
+
+    /// Removes all channels that have been pending for longer than `max_age`,
+    /// closing them, as well as all channels whose receivers have been
+    /// dropped. Returns the number of removed channels.
+    ///
+    /// Call this periodically to clean up requests whose responses never
+    /// arrived.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use laika::shotgun::TryRecvError;
+    /// use std::time::Duration;
+    ///
+    /// let pending = laika::shotgun::ShotgunSet::<_, u8>::new();
+    /// let rx = pending.insert(3);
+    ///
+    /// assert_eq!(pending.expire(Duration::from_secs(60)), 0);
+    /// assert_eq!(pending.expire(Duration::ZERO), 1);
+    ///
+    /// assert_eq!(rx.try_recv(), Err(TryRecvError::Closed));
+    /// ```
+    pub fn expire(&self, max_age: Duration) -> usize {
+        let now = Instant::now();
+
+        let expired = {
+            let mut entries = self.entries.lock();
+
+            let (pending, expired): (HashMap<_, _>, HashMap<_, _>) = mem::take(&mut *entries)
+                .into_iter()
+                .partition(|(_, entry)| {
+                    now.duration_since(entry.inserted) < max_age && !entry.sender.is_disconnected()
+                });
+
+            *entries = pending;
+            expired
+        };
+
+        // Closing wakes up the receivers, so the senders of expired entries
+        // are only dropped after releasing the lock.
+        expired.len()
+    }
+
+    /// Returns the number of pending channels.
+    pub fn len(&self) -> usize {
+        self.entries.lock().len()
+    }
+
+    /// Returns whether no channels are pending.
+    pub fn is_empty(&self) -> bool {
+        self.entries.lock().is_empty()
+    }
+
+    /// Returns whether a channel is pending for `key`.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.entries.lock().contains_key(key)
+    }
+}
+
+impl<K, T> Default for ShotgunSet<K, T>
+where
+    K: Eq + Hash,
+    T: Clone,
+{
+    fn default() -> Self {
+        ShotgunSet::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::shotgun::TryRecvError;
+    use std::{sync::Arc, thread};
+
+    #[test]
+    fn test_insert_complete() {
+        let pending = ShotgunSet::new();
+
+        let rx = pending.insert(String::from("a"));
+        let rx1 = pending.insert(String::from("a"));
+        let rx2 = pending.insert(String::from("b"));
+        assert_eq!(pending.len(), 2);
+
+        pending.complete("a", 1).unwrap();
+        assert_eq!(rx.try_recv(), Ok(1));
+        assert_eq!(rx1.try_recv(), Ok(1));
+        assert_eq!(rx2.try_recv(), Err(TryRecvError::Empty));
+        assert!(!pending.contains_key("a"));
+
+        assert_eq!(pending.complete("a", 2), Err(SendError::Disconnected(2)));
+
+        drop(rx2);
+        assert_eq!(pending.complete("b", 3), Err(SendError::Disconnected(3)));
+        assert!(pending.is_empty());
+    }
+
+    #[test]
+    fn test_reinsert_without_receivers() {
+        let pending = ShotgunSet::new();
+
+        drop(pending.insert(1));
+        let rx = pending.insert(1);
+
+        pending.complete(&1, 'a').unwrap();
+        assert_eq!(rx.try_recv(), Ok('a'));
+    }
+
+    #[test]
+    fn test_expire() {
+        let pending = ShotgunSet::<_, u8>::new();
+
+        let old = pending.insert(1);
+        drop(pending.insert(2));
+
+        thread::sleep(Duration::from_millis(50));
+        let new = pending.insert(3);
+
+        assert_eq!(pending.expire(Duration::from_millis(40)), 2);
+        assert_eq!(old.try_recv(), Err(TryRecvError::Closed));
+        assert_eq!(new.try_recv(), Err(TryRecvError::Empty));
+        assert_eq!(pending.len(), 1);
+    }
+
+    #[test]
+    fn test_shared() {
+        let pending = Arc::new(ShotgunSet::new());
+
+        let receivers = (0..4).map(|i| pending.insert(i)).collect::<Vec<_>>();
+
+        let responder = {
+            let pending = Arc::clone(&pending);
+            thread::spawn(move || {
+                for i in (0..4).rev() {
+                    pending.complete(&i, i * 10).unwrap();
+                }
+            })
+        };
+
+        for (i, rx) in receivers.into_iter().enumerate() {
+            assert_eq!(rx.recv_blocking(), Ok(i * 10));
+        }
+
+        responder.join().unwrap();
+        assert!(pending.is_empty());
+    }
+}
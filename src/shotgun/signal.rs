@@ -0,0 +1,371 @@
+//! Variant of [`channel`](super::channel) without a value, for cheap "event
+//! happened once" notifications.
+
+#[cfg(feature = "std")]
+use super::sync::Condvar;
+use super::{
+    _Key, _Wakers,
+    sync::{AtomicU8, Mutex, MutexGuard},
+    RecvError, SendError, TryRecvError, CLOSED, EMPTY, SENT,
+};
+use alloc::sync::{Arc, Weak};
+use core::{
+    future::Future,
+    pin::Pin,
+    sync::atomic::Ordering,
+    task::{Context, Poll},
+};
+
+/// Shared state of a [`signal`], referenced by all [`SignalReceiver`]s and the
+/// [`SignalSender`]
+#[derive(Debug)]
+struct Signal {
+    /// State of the signal, one of [`EMPTY`], [`SENT`] or [`CLOSED`]
+    state: AtomicU8,
+    /// Wakers of pending [`SignalReceiver`]s
+    wakers: Mutex<_Wakers>,
+    /// Notified when the signal is sent or closed, so that threads blocking in
+    /// [`SignalReceiver::recv_blocking`] wake up.
+    #[cfg(feature = "std")]
+    condvar: Condvar,
+}
+
+impl Signal {
+    /// Returns whether the signal has been sent.
+    fn try_recv(&self) -> Result<(), TryRecvError> {
+        match self.state.load(Ordering::Acquire) {
+            SENT => Ok(()),
+            CLOSED => Err(TryRecvError::Closed),
+            _ => Err(TryRecvError::Empty),
+        }
+    }
+
+    /// Moves the signal from [`EMPTY`] into `state`, waking up all waiting
+    /// [`SignalReceiver`]s. Returns whether it was still empty.
+    fn finish(&self, state: u8) -> bool {
+        if self
+            .state
+            .compare_exchange(EMPTY, state, Ordering::AcqRel, Ordering::Acquire)
+            .is_err()
+        {
+            return false;
+        }
+
+        // Takes the lock after the state has been updated, see
+        // `_Channel::wake_all`.
+        let wakers = self.wakers.lock().take_all();
+
+        for waker in wakers {
+            waker.wake();
+        }
+
+        #[cfg(feature = "std")]
+        self.condvar.notify_all();
+
+        true
+    }
+}
+
+/// Sender of a [`signal`]
+///
+/// Sends the signal once by [`SignalSender::send`]. Dropping it without
+/// sending closes the signal.
+#[derive(Debug)]
+pub struct SignalSender {
+    /// Shared state, weak so that dropped receivers disconnect the sender
+    inner: Weak<Signal>,
+}
+
+/// Receiver of a [`signal`]
+///
+/// Resolves to `Ok(())` once the signal has been sent, or [`RecvError`] if the
+/// [`SignalSender`] has been dropped without sending it. Can be cloned to
+/// notify multiple consumers.
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct SignalReceiver {
+    /// Shared state that holds the state and possible wakers
+    inner: Arc<Signal>,
+    /// Key of the waker this receiver registered while being polled, if any
+    key: _Key,
+}
+
+impl SignalSender {
+    /// Sends the signal to all receivers.
+    ///
+    /// Returns [`SendError::Disconnected`] if all [`SignalReceiver`]s have
+    /// been dropped already.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use laika::shotgun::SendError;
+    ///
+    /// let (tx, rx) = laika::shotgun::signal();
+    ///
+    /// drop(rx);
+    ///
+    /// assert_eq!(tx.send(), Err(SendError::Disconnected(())));
+    /// ```
+    pub fn send(self) -> Result<(), SendError<()>> {
+        let Some(signal) = self.inner.upgrade() else {
+            return Err(SendError::Disconnected(()));
+        };
+
+        signal.finish(SENT);
+
+        Ok(())
+    }
+
+    /// Returns the number of [`SignalReceiver`]s that currently exist.
+    pub fn receiver_count(&self) -> usize {
+        self.inner.strong_count()
+    }
+
+    /// Returns whether all [`SignalReceiver`]s have been dropped.
+    pub fn is_disconnected(&self) -> bool {
+        self.receiver_count() == 0
+    }
+}
+
+/// Closes the signal when the [`SignalSender`] is dropped without sending, so
+/// that waiting [`SignalReceiver`]s don't wait forever.
+impl Drop for SignalSender {
+    fn drop(&mut self) {
+        if let Some(signal) = self.inner.upgrade() {
+            signal.finish(CLOSED);
+        }
+    }
+}
+
+impl SignalReceiver {
+    /// Locks the wakers. See [`Mutex::lock`] for how poisoning is handled.
+    fn lock(&self) -> MutexGuard<'_, _Wakers> {
+        self.inner.wakers.lock()
+    }
+
+    /// Returns whether the signal has been sent. This function is
+    /// **non-blocking** and lock-free.
+    ///
+    /// Returns [`TryRecvError::Empty`] if the signal hasn't been sent yet or
+    /// [`TryRecvError::Closed`] if the [`SignalSender`] has been dropped
+    /// without sending it.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use laika::shotgun::TryRecvError;
+    ///
+    /// let (tx, rx) = laika::shotgun::signal();
+    /// assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+    ///
+    /// tx.send().unwrap();
+    /// assert_eq!(rx.try_recv(), Ok(()));
+    /// ```
+    pub fn try_recv(&self) -> Result<(), TryRecvError> {
+        self.inner.try_recv()
+    }
+
+    /// Polls for the signal, registering the waker of `cx` if it hasn't been
+    /// sent yet. See [`Receiver::poll_recv`](super::Receiver::poll_recv).
+    pub fn poll_recv(&self, cx: &mut Context<'_>) -> Poll<Result<(), RecvError>> {
+        match self.try_recv() {
+            Ok(()) => return Poll::Ready(Ok(())),
+            Err(TryRecvError::Closed) => return Poll::Ready(Err(RecvError)),
+            Err(TryRecvError::Empty) => {}
+        }
+
+        let mut wakers = self.lock();
+
+        // Check again while holding the lock as the signal might have been
+        // sent in the meantime.
+        match self.try_recv() {
+            Ok(()) => Poll::Ready(Ok(())),
+            Err(TryRecvError::Closed) => Poll::Ready(Err(RecvError)),
+            Err(TryRecvError::Empty) => {
+                wakers.register(&self.key, cx.waker());
+                Poll::Pending
+            }
+        }
+    }
+
+    /// Waits asynchronously until the signal has been sent. See
+    /// [`Receiver::recv`](super::Receiver::recv).
+    pub async fn recv(self) -> Result<(), RecvError> {
+        self.await
+    }
+
+    /// Blocks the current thread until the signal has been sent. See
+    /// [`Receiver::recv_blocking`](super::Receiver::recv_blocking). Only
+    /// available with the `std` feature.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use std::thread;
+    ///
+    /// let (tx, rx) = laika::shotgun::signal();
+    ///
+    /// let thread = thread::spawn(move || rx.recv_blocking());
+    ///
+    /// tx.send().unwrap();
+    ///
+    /// assert_eq!(thread.join().unwrap(), Ok(()));
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn recv_blocking(&self) -> Result<(), RecvError> {
+        let mut wakers = self.lock();
+
+        loop {
+            match self.try_recv() {
+                Ok(()) => return Ok(()),
+                Err(TryRecvError::Closed) => return Err(RecvError),
+                Err(TryRecvError::Empty) => wakers = self.inner.condvar.wait(wakers),
+            }
+        }
+    }
+}
+
+/// Implement [`Future`] for [`SignalReceiver`] to be able to use it in async
+/// functions.
+impl Future for SignalReceiver {
+    type Output = Result<(), RecvError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.poll_recv(cx)
+    }
+}
+
+/// Cloned [`SignalReceiver`]s start without a registered waker.
+impl Clone for SignalReceiver {
+    fn clone(&self) -> Self {
+        SignalReceiver {
+            inner: Arc::clone(&self.inner),
+            key: _Key::new(),
+        }
+    }
+}
+
+/// Removes the registered waker (if any) when a [`SignalReceiver`] is
+/// dropped, so it isn't woken up pointlessly.
+impl Drop for SignalReceiver {
+    fn drop(&mut self) {
+        if self.key.get().is_some() {
+            self.lock().deregister(&self.key);
+        }
+    }
+}
+
+/// Creates a oneshot signal, i.e. a [`channel`](super::channel) of `()`.
+///
+/// As there is no value, the signal is just an atomic state plus the wakers of
+/// pending receivers: there's no value slot to fill, nothing to clone and no
+/// callbacks or acknowledgements to handle. Use it as a cheap "event happened
+/// once" notification, e.g. for shutdown or readiness.
+///
+/// # Examples
+/// ```rust
+/// # tokio::runtime::Builder::new_current_thread()
+/// #     .build()
+/// #     .unwrap()
+/// #     .block_on(async {
+/// let (ready_tx, ready) = laika::shotgun::signal();
+///
+/// let task = tokio::spawn(ready.clone());
+///
+/// ready_tx.send().unwrap();
+///
+/// assert_eq!(task.await.unwrap(), Ok(()));
+/// assert_eq!(ready.await, Ok(()));
+/// # });
+/// ```
+pub fn signal() -> (SignalSender, SignalReceiver) {
+    let inner = Arc::new(Signal {
+        state: AtomicU8::new(EMPTY),
+        wakers: Mutex::new(_Wakers::default()),
+        #[cfg(feature = "std")]
+        condvar: Condvar::new(),
+    });
+
+    let sender = SignalSender {
+        inner: Arc::downgrade(&inner),
+    };
+
+    let receiver = SignalReceiver {
+        inner,
+        key: _Key::new(),
+    };
+
+    (sender, receiver)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::{pin::pin, task::Waker};
+
+    #[test]
+    fn test_signal() {
+        let (tx, rx) = signal();
+        let mut cx = Context::from_waker(Waker::noop());
+
+        let mut pending = pin!(rx.clone());
+        assert_eq!(pending.as_mut().poll(&mut cx), Poll::Pending);
+        assert_eq!(rx.inner.wakers.lock().len(), 1);
+        assert_eq!(tx.receiver_count(), 2);
+
+        tx.send().unwrap();
+
+        assert_eq!(rx.inner.wakers.lock().len(), 0);
+        assert_eq!(pending.as_mut().poll(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(rx.try_recv(), Ok(()));
+    }
+
+    #[test]
+    fn test_signal_closed() {
+        let (tx, rx) = signal();
+        let mut cx = Context::from_waker(Waker::noop());
+
+        let mut pending = pin!(rx.clone());
+        assert_eq!(pending.as_mut().poll(&mut cx), Poll::Pending);
+
+        drop(tx);
+
+        assert_eq!(pending.as_mut().poll(&mut cx), Poll::Ready(Err(RecvError)));
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Closed));
+        #[cfg(feature = "std")]
+        assert_eq!(rx.recv_blocking(), Err(RecvError));
+
+        let (tx, rx) = signal();
+        drop(rx);
+
+        assert!(tx.is_disconnected());
+        assert_eq!(tx.send(), Err(SendError::Disconnected(())));
+    }
+
+    #[test]
+    fn test_signal_deregister() {
+        let (_tx, rx) = signal();
+        let mut cx = Context::from_waker(Waker::noop());
+
+        let rx1 = rx.clone();
+        assert!(rx1.poll_recv(&mut cx).is_pending());
+        assert_eq!(rx.inner.wakers.lock().len(), 1);
+
+        drop(rx1);
+        assert_eq!(rx.inner.wakers.lock().len(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_signal_spawned() {
+        let (tx, rx) = signal();
+
+        let tasks = (0..4)
+            .map(|_| tokio::spawn(rx.clone().recv()))
+            .collect::<alloc::vec::Vec<_>>();
+
+        tokio::task::yield_now().await;
+        tx.send().unwrap();
+
+        for task in tasks {
+            assert_eq!(task.await.unwrap(), Ok(()));
+        }
+    }
+}
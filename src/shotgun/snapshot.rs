@@ -0,0 +1,176 @@
+//! Serializable snapshots of channels, so long-running workflows can
+//! checkpoint their handoffs across restarts.
+
+use super::{channel, ChannelState, Receiver, Sender};
+use serde::{Deserialize, Serialize};
+
+/// Serializable snapshot of the state and value of a channel
+///
+/// Taken by [`Receiver::snapshot`] and turned back into a channel by
+/// [`Snapshot::restore`]. Receivers serialize as their snapshot as well. Only
+/// available with the `serde` feature.
+///
+/// # Examples
+/// ```rust
+/// use laika::shotgun::Snapshot;
+///
+/// let (tx, rx) = laika::shotgun::channel();
+/// tx.send(12).unwrap();
+///
+/// let checkpoint = serde_json::to_string(&rx).unwrap();
+/// assert_eq!(checkpoint, r#"{"Sent":12}"#);
+///
+/// let snapshot: Snapshot<u8> = serde_json::from_str(&checkpoint).unwrap();
+/// let (tx, rx) = snapshot.restore();
+///
+/// assert!(tx.is_none());
+/// assert_eq!(rx.try_recv(), Ok(12));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Snapshot<T> {
+    /// No value has been sent yet, but a [`Sender`] still existed.
+    Pending,
+    /// The value has been sent.
+    Sent(T),
+    /// All [`Sender`]s have been dropped without sending a value.
+    Closed,
+}
+
+impl<T> Snapshot<T>
+where
+    T: Clone,
+{
+    /// Returns the [`ChannelState`] the snapshot has been taken in.
+    pub fn state(&self) -> ChannelState {
+        match self {
+            Snapshot::Pending => ChannelState::Empty,
+            Snapshot::Sent(_) => ChannelState::Sent,
+            Snapshot::Closed => ChannelState::Closed,
+        }
+    }
+
+    /// Reconstructs a channel in the state of the snapshot.
+    ///
+    /// A pending channel is restored as a new channel, returning its
+    /// [`Sender`] so that the value can still be sent after the restart.
+    /// Sent and closed channels are restored without a sender, receiving the
+    /// value or [`RecvError`](super::RecvError) respectively.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use laika::shotgun::{Snapshot, TryRecvError};
+    ///
+    /// let (tx, rx) = Snapshot::<u8>::Pending.restore();
+    /// assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+    ///
+    /// tx.unwrap().send(12).unwrap();
+    /// assert_eq!(rx.try_recv(), Ok(12));
+    ///
+    /// let (tx, rx) = Snapshot::<u8>::Closed.restore();
+    /// assert!(tx.is_none());
+    /// assert_eq!(rx.try_recv(), Err(TryRecvError::Closed));
+    /// ```
+    pub fn restore(self) -> (Option<Sender<T>>, Receiver<T>) {
+        match self {
+            Snapshot::Pending => {
+                let (sender, receiver) = channel();
+                (Some(sender), receiver)
+            }
+            Snapshot::Sent(value) => (None, Receiver::ready(value)),
+            Snapshot::Closed => (None, channel().1),
+        }
+    }
+}
+
+impl<T> Receiver<T>
+where
+    T: Clone,
+{
+    /// Takes a serializable [`Snapshot`] of the channel, containing (a clone
+    /// of) the value if it has been sent. Doesn't count as receiving the
+    /// value for [`Sender::send_acknowledged`]. Only available with the
+    /// `serde` feature.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use laika::shotgun::Snapshot;
+    ///
+    /// let (tx, rx) = laika::shotgun::channel();
+    /// assert_eq!(rx.snapshot(), Snapshot::Pending);
+    ///
+    /// tx.send(12).unwrap();
+    /// assert_eq!(rx.snapshot(), Snapshot::Sent(12));
+    /// ```
+    pub fn snapshot(&self) -> Snapshot<T> {
+        match self.snapshot_ref() {
+            Snapshot::Pending => Snapshot::Pending,
+            Snapshot::Sent(value) => Snapshot::Sent(value.clone()),
+            Snapshot::Closed => Snapshot::Closed,
+        }
+    }
+
+    /// Takes a [`Snapshot`] that references the value instead of cloning it.
+    fn snapshot_ref(&self) -> Snapshot<&T> {
+        match (self.inner.value.get(), self.state()) {
+            (Some(value), _) => Snapshot::Sent(value),
+            (None, ChannelState::Closed) => Snapshot::Closed,
+            (None, _) => Snapshot::Pending,
+        }
+    }
+}
+
+/// Serializes a [`Receiver`] as its [`Snapshot`]. Deserialize a [`Snapshot`] and
+/// [`Snapshot::restore`] it to get the channel back.
+impl<T> Serialize for Receiver<T>
+where
+    T: Clone + Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        self.snapshot_ref().serialize(serializer)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::shotgun::TryRecvError;
+    use alloc::{string::String, vec::Vec};
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        let (tx, rx) = channel::<String>();
+        let (tx_closed, rx_closed) = channel::<String>();
+        drop(tx_closed);
+
+        let rx_sent = Receiver::ready(String::from("done"));
+
+        let checkpoint = serde_json::to_string(&[&rx, &rx_sent, &rx_closed]).unwrap();
+        assert_eq!(checkpoint, r#"["Pending",{"Sent":"done"},"Closed"]"#);
+
+        let snapshots: Vec<Snapshot<String>> = serde_json::from_str(&checkpoint).unwrap();
+        assert_eq!(
+            snapshots.iter().map(Snapshot::state).collect::<Vec<_>>(),
+            [
+                ChannelState::Empty,
+                ChannelState::Sent,
+                ChannelState::Closed
+            ]
+        );
+
+        let [pending, sent, closed] = snapshots.try_into().unwrap();
+
+        let (restored_tx, restored_rx) = pending.restore();
+        restored_tx.unwrap().send(String::from("later")).unwrap();
+        assert_eq!(restored_rx.try_recv(), Ok(String::from("later")));
+
+        assert_eq!(sent.restore().1.try_recv(), Ok(String::from("done")));
+        assert_eq!(closed.restore().1.try_recv(), Err(TryRecvError::Closed));
+
+        // Original channel is unaffected
+        tx.send(String::from("original")).unwrap();
+        assert_eq!(rx.snapshot(), Snapshot::Sent(String::from("original")));
+    }
+}
@@ -0,0 +1,77 @@
+//! Promise-like helper that runs a closure on a new thread.
+
+use super::{channel, Receiver};
+use std::thread;
+
+/// Runs `f` on a new thread and returns a [`Receiver`] of its result, giving a
+/// promise-like API without any extra dependencies.
+///
+/// If `f` panics, the channel is closed without a value, so receiving fails
+/// with [`super::RecvError`] (or [`super::TryRecvError::Closed`]) instead of
+/// waiting forever. The panic itself is reported by the spawned thread as
+/// usual. Only available with the `std` feature.
+///
+/// # Panics
+/// Panics if the operating system fails to create a thread, like
+/// [`std::thread::spawn`].
+///
+/// # Examples
+/// ```rust
+/// use laika::shotgun::RecvError;
+///
+/// let answer = laika::shotgun::spawn(|| 6 * 7);
+/// let rx = answer.clone();
+///
+/// assert_eq!(answer.recv_blocking(), Ok(42));
+/// assert_eq!(rx.recv_blocking(), Ok(42));
+///
+/// let failed = laika::shotgun::spawn(|| -> u8 { panic!("no answer") });
+/// assert_eq!(failed.recv_blocking(), Err(RecvError));
+/// ```
+pub fn spawn<F, T>(f: F) -> Receiver<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Clone + Send + Sync + 'static,
+{
+    let (tx, rx) = channel();
+
+    // If `f` panics, the sender is dropped while unwinding, closing the
+    // channel.
+    thread::spawn(move || {
+        let _ = tx.send(f());
+    });
+
+    rx
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::shotgun::{RecvError, TryRecvError};
+    use std::time::Duration;
+
+    #[test]
+    fn test_spawn() {
+        let rx = spawn(|| {
+            thread::sleep(Duration::from_millis(50));
+            String::from("done")
+        });
+
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+        assert_eq!(rx.recv_blocking(), Ok(String::from("done")));
+    }
+
+    #[test]
+    fn test_spawn_panic() {
+        let rx = spawn(|| -> u8 { panic!("Expected panic") });
+
+        assert_eq!(rx.recv_blocking(), Err(RecvError));
+    }
+
+    #[tokio::test]
+    async fn test_spawn_await() {
+        let rx = spawn(|| 1);
+
+        assert_eq!(rx.await, Ok(1));
+    }
+}
@@ -0,0 +1,289 @@
+//! Allocation-free variant of [`channel`](super::channel) that can live in a
+//! `static`, e.g. in bare-metal firmware without a heap.
+
+use super::{
+    _Key,
+    sync::{Mutex, OnceCell},
+    SendError, TryRecvError,
+};
+use core::{
+    future::Future,
+    mem,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+
+/// Oneshot channel that can be stored in a `static`
+///
+/// Combines sender and receiver in one value that doesn't need any heap: the
+/// value and up to `N` wakers of pending [`StaticRecv`] futures are stored
+/// inline. Created in constant contexts by [`StaticShotgun::new`].
+///
+/// As there is no sender that could be dropped, the channel is never closed.
+/// If more than `N` futures wait at the same time, the excess ones can't
+/// register their waker and are woken up right away instead, so they
+/// effectively poll until the value arrives. Choose `N` according to the
+/// number of tasks that wait for the value.
+///
+/// Not available with `--cfg laika_loom`, as `loom`'s locks can't be created
+/// in constant contexts.
+///
+/// # Examples
+/// ```rust
+/// use laika::shotgun::StaticShotgun;
+///
+/// static SHUTDOWN: StaticShotgun<u8, 4> = StaticShotgun::new();
+///
+/// # tokio::runtime::Builder::new_current_thread()
+/// #     .build()
+/// #     .unwrap()
+/// #     .block_on(async {
+/// let task = async { SHUTDOWN.recv().await };
+///
+/// SHUTDOWN.send(3).unwrap();
+///
+/// assert_eq!(task.await, 3);
+/// assert_eq!(SHUTDOWN.try_recv(), Ok(3));
+/// # });
+/// ```
+#[derive(Debug)]
+pub struct StaticShotgun<T, const N: usize>
+where
+    T: Clone,
+{
+    /// Value that was sent. Once set, it can be read without taking any lock.
+    value: OnceCell<T>,
+    /// Wakers of pending [`StaticRecv`]s, [`None`] marks a free slot
+    wakers: Mutex<[Option<Waker>; N]>,
+}
+
+impl<T, const N: usize> StaticShotgun<T, N>
+where
+    T: Clone,
+{
+    /// Creates an empty channel. Usable in constant contexts, so the channel
+    /// can be put into a `static`.
+    pub const fn new() -> Self {
+        StaticShotgun {
+            value: OnceCell::new(),
+            wakers: Mutex::new([const { None }; N]),
+        }
+    }
+
+    /// Send a value to everyone waiting on the channel. As this is a oneshot
+    /// channel, only one value can be sent.
+    ///
+    /// Returns [`SendError::AlreadySent`] containing the value if a value has
+    /// been sent already.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use laika::shotgun::{SendError, StaticShotgun};
+    ///
+    /// static READY: StaticShotgun<u8, 1> = StaticShotgun::new();
+    ///
+    /// READY.send(1).unwrap();
+    /// assert_eq!(READY.send(2), Err(SendError::AlreadySent(2)));
+    /// ```
+    pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+        self.value.set(value).map_err(SendError::AlreadySent)?;
+
+        // Takes the lock after the value has been set, so futures either see
+        // the value or have registered in time to be woken up. They're woken
+        // up after releasing the lock.
+        let wakers = mem::replace(&mut *self.wakers.lock(), [const { None }; N]);
+
+        for waker in wakers.into_iter().flatten() {
+            waker.wake();
+        }
+
+        Ok(())
+    }
+
+    /// Try to receive (a clone of) the value, if it has been sent. This
+    /// function is **non-blocking** and lock-free.
+    ///
+    /// Returns [`TryRecvError::Empty`] if no value has been sent yet. As the
+    /// channel is never closed, [`TryRecvError::Closed`] isn't returned.
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        self.value.get().cloned().ok_or(TryRecvError::Empty)
+    }
+
+    /// Returns a future that resolves to (a clone of) the value once it has
+    /// been sent. Every future registers its own waker, so multiple tasks can
+    /// wait at once.
+    pub fn recv(&self) -> StaticRecv<'_, T, N> {
+        StaticRecv {
+            channel: self,
+            key: _Key::new(),
+        }
+    }
+
+    /// Polls for the value, registering the waker of `cx` under `key` if it
+    /// hasn't been sent yet.
+    fn poll_recv(&self, key: &_Key, cx: &mut Context<'_>) -> Poll<T> {
+        if let Some(value) = self.value.get() {
+            return Poll::Ready(value.clone());
+        }
+
+        let mut wakers = self.wakers.lock();
+
+        // Check again while holding the lock as the value might have been sent
+        // in the meantime.
+        if let Some(value) = self.value.get() {
+            return Poll::Ready(value.clone());
+        }
+
+        let index = key
+            .get()
+            .or_else(|| wakers.iter().position(Option::is_none));
+
+        match index {
+            Some(index) => {
+                match &mut wakers[index] {
+                    Some(registered) if registered.will_wake(cx.waker()) => {}
+                    slot => *slot = Some(cx.waker().clone()),
+                }
+
+                key.set(Some(index));
+            }
+            // All slots are taken, so poll again instead of waiting for a
+            // wake-up
+            None => cx.waker().wake_by_ref(),
+        }
+
+        Poll::Pending
+    }
+}
+
+impl<T, const N: usize> Default for StaticShotgun<T, N>
+where
+    T: Clone,
+{
+    fn default() -> Self {
+        StaticShotgun::new()
+    }
+}
+
+/// Future that waits for the value of a [`StaticShotgun`]
+///
+/// Created by [`StaticShotgun::recv`].
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct StaticRecv<'a, T, const N: usize>
+where
+    T: Clone,
+{
+    /// Channel that is waited for
+    channel: &'a StaticShotgun<T, N>,
+    /// Key of the waker this future registered while being polled, if any
+    key: _Key,
+}
+
+impl<T, const N: usize> Future for StaticRecv<'_, T, N>
+where
+    T: Clone,
+{
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.channel.poll_recv(&self.key, cx)
+    }
+}
+
+/// Frees the slot of the registered waker (if any) when a [`StaticRecv`] is
+/// dropped, so it can be used by other futures.
+impl<T, const N: usize> Drop for StaticRecv<'_, T, N>
+where
+    T: Clone,
+{
+    fn drop(&mut self) {
+        if let Some(index) = self.key.get() {
+            self.channel.wakers.lock()[index] = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::{boxed::Box, sync::Arc, task::Wake};
+    use core::{
+        pin::pin,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    /// Waker that counts how often it has been woken
+    #[derive(Default)]
+    struct Counter(AtomicUsize);
+
+    impl Wake for Counter {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn test_static_shotgun() {
+        static CHANNEL: StaticShotgun<u8, 2> = StaticShotgun::new();
+
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut recv = pin!(CHANNEL.recv());
+
+        assert_eq!(CHANNEL.try_recv(), Err(TryRecvError::Empty));
+        assert_eq!(recv.as_mut().poll(&mut cx), Poll::Pending);
+
+        CHANNEL.send(1).unwrap();
+        assert_eq!(CHANNEL.send(2), Err(SendError::AlreadySent(2)));
+
+        assert_eq!(recv.as_mut().poll(&mut cx), Poll::Ready(1));
+        assert_eq!(CHANNEL.try_recv(), Ok(1));
+    }
+
+    #[test]
+    fn test_static_shotgun_wakers() {
+        let channel = StaticShotgun::<u8, 2>::new();
+
+        let counter = Arc::new(Counter::default());
+        let waker = Waker::from(Arc::clone(&counter));
+        let mut cx = Context::from_waker(&waker);
+
+        let mut recv = pin!(channel.recv());
+        let mut recv1 = Box::pin(channel.recv());
+        assert_eq!(recv.as_mut().poll(&mut cx), Poll::Pending);
+        assert_eq!(recv.as_mut().poll(&mut cx), Poll::Pending);
+        assert_eq!(recv1.as_mut().poll(&mut cx), Poll::Pending);
+
+        // All slots are taken, so the third future is woken up right away
+        {
+            let mut recv2 = pin!(channel.recv());
+            assert_eq!(recv2.as_mut().poll(&mut cx), Poll::Pending);
+            assert_eq!(counter.0.load(Ordering::Relaxed), 1);
+        }
+
+        // Freed slot is reused
+        drop(recv1);
+        let mut recv3 = pin!(channel.recv());
+        assert_eq!(recv3.as_mut().poll(&mut cx), Poll::Pending);
+        assert_eq!(recv3.key.get(), Some(1));
+        assert_eq!(counter.0.load(Ordering::Relaxed), 1);
+
+        channel.send(1).unwrap();
+        assert_eq!(counter.0.load(Ordering::Relaxed), 3);
+        assert!(channel.wakers.lock().iter().all(Option::is_none));
+    }
+
+    #[tokio::test]
+    async fn test_static_shotgun_spawned() {
+        static CHANNEL: StaticShotgun<&str, 4> = StaticShotgun::new();
+
+        let tasks = [tokio::spawn(CHANNEL.recv()), tokio::spawn(CHANNEL.recv())];
+
+        tokio::task::yield_now().await;
+        CHANNEL.send("static").unwrap();
+
+        for task in tasks {
+            assert_eq!(task.await.unwrap(), "static");
+        }
+    }
+}
@@ -0,0 +1,324 @@
+//! Synchronization primitives of the channels.
+//!
+//! Backed by [`std::sync`] if the `std` feature is enabled, otherwise by the
+//! `spin` crate, so that the channels work in `no_std` builds as well. With
+//! the `parking_lot` feature, the locks of the `parking_lot` crate are used
+//! instead of the ones of [`std::sync`], see [`RawLock`]. If built with
+//! `--cfg laika_loom`, the atomics, locks and condition variables of the `loom`
+//! crate are used instead, so that interleavings can be model-checked.
+//! `loom` neither provides a `OnceLock` nor `Weak` references, so the value
+//! cell and the [`alloc::sync::Arc`] of a channel stay as they are.
+
+#[cfg(all(laika_loom, not(feature = "std")))]
+compile_error!("loom requires the `std` feature");
+
+#[cfg(not(laika_loom))]
+pub(super) use core::sync::atomic::{AtomicBool, AtomicU8, AtomicUsize};
+#[cfg(laika_loom)]
+pub(super) use loom::sync::atomic::{AtomicBool, AtomicU8, AtomicUsize};
+
+use core::ops::DerefMut;
+#[cfg(feature = "std")]
+use std::time::Duration;
+
+/// Cell that is written once and can be read without taking any lock
+/// afterwards
+#[derive(Debug)]
+pub(super) struct OnceCell<T> {
+    #[cfg(feature = "std")]
+    inner: std::sync::OnceLock<T>,
+    #[cfg(not(feature = "std"))]
+    inner: spin::Once<T>,
+}
+
+impl<T> OnceCell<T> {
+    /// Creates an empty cell.
+    pub(super) const fn new() -> Self {
+        OnceCell {
+            #[cfg(feature = "std")]
+            inner: std::sync::OnceLock::new(),
+            #[cfg(not(feature = "std"))]
+            inner: spin::Once::new(),
+        }
+    }
+
+    /// Returns the value, if it has been set.
+    pub(super) fn get(&self) -> Option<&T> {
+        self.inner.get()
+    }
+
+    /// Sets the value, if it hasn't been set before. Returns `value` otherwise.
+    pub(super) fn set(&self, value: T) -> Result<(), T> {
+        let mut value = Some(value);
+        self.get_or_init(|| value.take().expect("Initializer is called at most once"));
+
+        match value {
+            Some(value) => Err(value),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the value, setting it to the result of `f` if it hasn't been
+    /// set before. `f` isn't called if the value has been set already.
+    #[cfg(feature = "std")]
+    pub(super) fn get_or_init(&self, f: impl FnOnce() -> T) -> &T {
+        self.inner.get_or_init(f)
+    }
+
+    /// Returns the value, setting it to the result of `f` if it hasn't been
+    /// set before. `f` isn't called if the value has been set already.
+    #[cfg(not(feature = "std"))]
+    pub(super) fn get_or_init(&self, f: impl FnOnce() -> T) -> &T {
+        self.inner.call_once(f)
+    }
+}
+
+impl<T> From<T> for OnceCell<T> {
+    fn from(value: T) -> Self {
+        OnceCell {
+            #[cfg(feature = "std")]
+            inner: std::sync::OnceLock::from(value),
+            #[cfg(not(feature = "std"))]
+            inner: spin::Once::initialized(value),
+        }
+    }
+}
+
+/// Lock backend of [`Mutex`]
+///
+/// Implemented for the mutexes of all supported backends, one of which is
+/// selected depending on the enabled features: `loom` (with
+/// `--cfg laika_loom`), `parking_lot`, [`std::sync`] or `spin`, in that order of
+/// precedence.
+pub(super) trait RawLock<T> {
+    /// Guard that releases the lock when dropped
+    type Guard<'a>: DerefMut<Target = T>
+    where
+        Self: 'a;
+
+    /// Locks the lock, blocking (or spinning) until it's available. Never
+    /// fails, a panic in one consumer must not cascade into every other
+    /// consumer of the channel.
+    fn lock(&self) -> Self::Guard<'_>;
+
+    /// Returns whether another thread panicked while holding the lock. Only
+    /// [`std::sync`] locks can be poisoned.
+    #[cfg(feature = "std")]
+    fn is_poisoned(&self) -> bool {
+        false
+    }
+
+    /// Returns whether the lock is currently locked.
+    #[cfg(test)]
+    fn is_locked(&self) -> bool;
+}
+
+/// Recovers from poisoning (another thread panicked while holding the lock),
+/// as the channels never leave their state half updated.
+#[cfg(feature = "std")]
+impl<T> RawLock<T> for std::sync::Mutex<T> {
+    type Guard<'a>
+        = std::sync::MutexGuard<'a, T>
+    where
+        Self: 'a;
+
+    fn lock(&self) -> Self::Guard<'_> {
+        std::sync::Mutex::lock(self).unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    fn is_poisoned(&self) -> bool {
+        std::sync::Mutex::is_poisoned(self)
+    }
+
+    #[cfg(test)]
+    fn is_locked(&self) -> bool {
+        matches!(self.try_lock(), Err(std::sync::TryLockError::WouldBlock))
+    }
+}
+
+/// Spin locks can't be poisoned.
+#[cfg(feature = "spin")]
+impl<T> RawLock<T> for spin::Mutex<T> {
+    type Guard<'a>
+        = spin::MutexGuard<'a, T>
+    where
+        Self: 'a;
+
+    fn lock(&self) -> Self::Guard<'_> {
+        spin::Mutex::lock(self)
+    }
+
+    #[cfg(test)]
+    fn is_locked(&self) -> bool {
+        spin::Mutex::is_locked(self)
+    }
+}
+
+/// `parking_lot`'s locks can't be poisoned.
+#[cfg(feature = "parking_lot")]
+impl<T> RawLock<T> for parking_lot::Mutex<T> {
+    type Guard<'a>
+        = parking_lot::MutexGuard<'a, T>
+    where
+        Self: 'a;
+
+    fn lock(&self) -> Self::Guard<'_> {
+        parking_lot::Mutex::lock(self)
+    }
+
+    #[cfg(test)]
+    fn is_locked(&self) -> bool {
+        parking_lot::Mutex::is_locked(self)
+    }
+}
+
+/// Recovers from poisoning like [`std::sync::Mutex`].
+#[cfg(laika_loom)]
+impl<T> RawLock<T> for loom::sync::Mutex<T> {
+    type Guard<'a>
+        = loom::sync::MutexGuard<'a, T>
+    where
+        Self: 'a;
+
+    fn lock(&self) -> Self::Guard<'_> {
+        loom::sync::Mutex::lock(self).unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    #[cfg(test)]
+    fn is_locked(&self) -> bool {
+        matches!(self.try_lock(), Err(std::sync::TryLockError::WouldBlock))
+    }
+}
+
+/// Lock backend selected by the enabled features
+#[cfg(laika_loom)]
+type Backend<T> = loom::sync::Mutex<T>;
+/// Lock backend selected by the enabled features
+#[cfg(all(feature = "parking_lot", not(laika_loom)))]
+type Backend<T> = parking_lot::Mutex<T>;
+/// Lock backend selected by the enabled features
+#[cfg(all(feature = "std", not(feature = "parking_lot"), not(laika_loom)))]
+type Backend<T> = std::sync::Mutex<T>;
+/// Lock backend selected by the enabled features
+#[cfg(not(feature = "std"))]
+type Backend<T> = spin::Mutex<T>;
+
+/// Guard of a locked [`Mutex`]
+pub(super) type MutexGuard<'a, T> = <Backend<T> as RawLock<T>>::Guard<'a>;
+
+/// Mutual exclusion lock that doesn't propagate panics of other threads
+#[derive(Debug, Default)]
+pub(super) struct Mutex<T> {
+    inner: Backend<T>,
+}
+
+impl<T> Mutex<T> {
+    /// Creates an unlocked mutex containing `value`.
+    #[cfg(not(laika_loom))]
+    pub(super) const fn new(value: T) -> Self {
+        Mutex {
+            inner: Backend::new(value),
+        }
+    }
+
+    /// Creates an unlocked mutex containing `value`. Unlike the other
+    /// backends, `loom` can't do so in constant contexts.
+    #[cfg(laika_loom)]
+    pub(super) fn new(value: T) -> Self {
+        Mutex {
+            inner: Backend::new(value),
+        }
+    }
+
+    /// Locks the mutex, see [`RawLock::lock`].
+    pub(super) fn lock(&self) -> MutexGuard<'_, T> {
+        RawLock::lock(&self.inner)
+    }
+
+    /// Returns whether another thread panicked while holding the lock, see
+    /// [`RawLock::is_poisoned`].
+    #[cfg(feature = "std")]
+    pub(super) fn is_poisoned(&self) -> bool {
+        RawLock::is_poisoned(&self.inner)
+    }
+
+    /// Returns whether the mutex is currently locked.
+    #[cfg(test)]
+    pub(super) fn is_locked(&self) -> bool {
+        RawLock::is_locked(&self.inner)
+    }
+}
+
+/// Condition variable of the lock backend
+#[cfg(laika_loom)]
+type CondvarBackend = loom::sync::Condvar;
+/// Condition variable of the lock backend
+#[cfg(all(feature = "parking_lot", not(laika_loom)))]
+type CondvarBackend = parking_lot::Condvar;
+/// Condition variable of the lock backend
+#[cfg(all(feature = "std", not(feature = "parking_lot"), not(laika_loom)))]
+type CondvarBackend = std::sync::Condvar;
+
+/// Condition variable to block threads on a [`Mutex`], recovering from
+/// poisoning like [`Mutex::lock`]
+#[cfg(feature = "std")]
+#[derive(Debug, Default)]
+pub(super) struct Condvar {
+    inner: CondvarBackend,
+}
+
+#[cfg(feature = "std")]
+impl Condvar {
+    /// Creates a condition variable without waiting threads.
+    pub(super) fn new() -> Self {
+        Condvar {
+            inner: CondvarBackend::new(),
+        }
+    }
+
+    /// Blocks the current thread until notified, releasing the lock of
+    /// `guard` in the meantime. May wake up spuriously.
+    #[cfg(any(laika_loom, not(feature = "parking_lot")))]
+    pub(super) fn wait<'a, T>(&self, guard: MutexGuard<'a, T>) -> MutexGuard<'a, T> {
+        self.inner
+            .wait(guard)
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    /// Blocks the current thread until notified, releasing the lock of
+    /// `guard` in the meantime. May wake up spuriously.
+    #[cfg(all(feature = "parking_lot", not(laika_loom)))]
+    pub(super) fn wait<'a, T>(&self, mut guard: MutexGuard<'a, T>) -> MutexGuard<'a, T> {
+        self.inner.wait(&mut guard);
+        guard
+    }
+
+    /// Like [`Condvar::wait`], but waits for at most `timeout`.
+    #[cfg(any(laika_loom, not(feature = "parking_lot")))]
+    pub(super) fn wait_timeout<'a, T>(
+        &self,
+        guard: MutexGuard<'a, T>,
+        timeout: Duration,
+    ) -> MutexGuard<'a, T> {
+        self.inner
+            .wait_timeout(guard, timeout)
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .0
+    }
+
+    /// Like [`Condvar::wait`], but waits for at most `timeout`.
+    #[cfg(all(feature = "parking_lot", not(laika_loom)))]
+    pub(super) fn wait_timeout<'a, T>(
+        &self,
+        mut guard: MutexGuard<'a, T>,
+        timeout: Duration,
+    ) -> MutexGuard<'a, T> {
+        self.inner.wait_for(&mut guard, timeout);
+        guard
+    }
+
+    /// Wakes up all waiting threads.
+    pub(super) fn notify_all(&self) {
+        self.inner.notify_all();
+    }
+}
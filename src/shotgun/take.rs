@@ -0,0 +1,285 @@
+//! Variant of [`channel`] where exactly one receiver takes the sent value by
+//! move, so the value doesn't need to implement [`Clone`].
+
+use super::{channel, sync::Mutex, Receiver, SendError, Sender};
+use alloc::sync::Arc;
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+/// Slot holding the sent value until a receiver takes it
+type Slot<T> = Arc<Mutex<Option<T>>>;
+
+/// Oneshot sender of a [`take_channel`]
+///
+/// Use [`TakeSender::send`] to hand a value to whichever [`TakeReceiver`]
+/// takes it first.
+///
+/// # Examples
+/// ```rust
+/// let (tx, rx) = laika::shotgun::take_channel();
+///
+/// tx.send(12).unwrap();
+///
+/// assert_eq!(rx.take(), Some(12));
+/// ```
+#[derive(Debug)]
+pub struct TakeSender<T> {
+    inner: Sender<Slot<T>>,
+}
+
+/// Receiver of a [`take_channel`]
+///
+/// Receivers can be cloned, but only a single one of them gets the value:
+/// [`TakeReceiver::take`] (or awaiting the receiver) returns it exactly once
+/// and [`None`] for everyone else. This makes it a work-claiming primitive for
+/// racing workers.
+///
+/// # Examples
+/// ```rust
+/// let (tx, rx) = laika::shotgun::take_channel();
+/// let rx1 = rx.clone();
+///
+/// tx.send(String::from("work")).unwrap();
+///
+/// assert_eq!(rx1.take(), Some(String::from("work")));
+/// // Value has been taken already
+/// assert_eq!(rx.take(), None);
+/// ```
+#[derive(Debug)]
+pub struct TakeReceiver<T> {
+    inner: Receiver<Slot<T>>,
+}
+
+impl<T> TakeSender<T> {
+    /// Send a value to be taken by one of the receivers of the channel.
+    /// As this is a oneshot sender, only one value can be sent.
+    ///
+    /// Returns [`SendError`] containing the value if all [`TakeReceiver`]s
+    /// have been dropped already.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use laika::shotgun::SendError;
+    ///
+    /// let (tx, rx) = laika::shotgun::take_channel();
+    ///
+    /// drop(rx);
+    ///
+    /// assert_eq!(tx.send(12), Err(SendError::Disconnected(12)));
+    /// ```
+    pub fn send(self, value: T) -> Result<(), SendError<T>> {
+        self.inner
+            .send(Arc::new(Mutex::new(Some(value))))
+            .map_err(|error| {
+                error.map(|slot| take_slot(&slot).expect("Unsent value is still in its slot"))
+            })
+    }
+
+    /// Returns the number of [`TakeReceiver`]s of the channel that currently
+    /// exist. See [`Sender::receiver_count`].
+    pub fn receiver_count(&self) -> usize {
+        self.inner.receiver_count()
+    }
+
+    /// Returns whether all [`TakeReceiver`]s of the channel have been dropped.
+    /// See [`Sender::is_disconnected`].
+    pub fn is_disconnected(&self) -> bool {
+        self.inner.is_disconnected()
+    }
+}
+
+impl<T> TakeReceiver<T> {
+    /// Try to take the value from the channel. This function is
+    /// **non-blocking**.
+    ///
+    /// Returns [`Some`] for exactly one receiver of the channel. Returns
+    /// [`None`] if no value has been sent yet, the value has been taken by
+    /// another receiver already or the [`TakeSender`] has been dropped without
+    /// sending a value.
+    ///
+    /// # Examples
+    /// ```rust
+    /// let (tx, rx) = laika::shotgun::take_channel();
+    ///
+    /// assert_eq!(rx.take(), None);
+    ///
+    /// tx.send(12).unwrap();
+    ///
+    /// assert_eq!(rx.take(), Some(12));
+    /// assert_eq!(rx.take(), None);
+    /// ```
+    pub fn take(&self) -> Option<T> {
+        self.inner.try_recv().ok().and_then(|slot| take_slot(&slot))
+    }
+
+    /// Returns whether the value has been sent and taken by any receiver.
+    ///
+    /// # Examples
+    /// ```rust
+    /// let (tx, rx) = laika::shotgun::take_channel();
+    /// let rx1 = rx.clone();
+    ///
+    /// tx.send(12).unwrap();
+    /// assert!(!rx.is_taken());
+    ///
+    /// rx1.take();
+    /// assert!(rx.is_taken());
+    /// ```
+    pub fn is_taken(&self) -> bool {
+        self.inner
+            .try_recv()
+            .is_ok_and(|slot| slot.lock().is_none())
+    }
+
+    /// Wait for the value and try to take it. Resolves to [`Some`] for exactly
+    /// one receiver of the channel and to [`None`] for everyone else, or if
+    /// the [`TakeSender`] has been dropped without sending a value.
+    ///
+    /// # Note
+    /// You can directly [`Future`]'s `.await` on the receiver too.
+    pub async fn recv(self) -> Option<T> {
+        self.await
+    }
+}
+
+impl<T> Clone for TakeReceiver<T> {
+    fn clone(&self) -> Self {
+        TakeReceiver {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+/// Implement [`Future`] for [`TakeReceiver`] to be able to use it in async
+/// functions.
+impl<T> Future for TakeReceiver<T> {
+    type Output = Option<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.get_mut().inner)
+            .poll(cx)
+            .map(|result| result.ok().and_then(|slot| take_slot(&slot)))
+    }
+}
+
+/// Takes the value out of `slot`, if it's still there.
+fn take_slot<T>(slot: &Slot<T>) -> Option<T> {
+    slot.lock().take()
+}
+
+/// Creates a one-shot, single producer multiple consumer channel where exactly
+/// one receiver takes the sent value by move. All other receivers get
+/// [`None`], so racing workers can use it to claim a piece of work.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::thread;
+///
+/// let (tx, rx) = laika::shotgun::take_channel();
+///
+/// let workers: Vec<_> = (0..4)
+///     .map(|_| {
+///         let rx = rx.clone();
+///         thread::spawn(move || loop {
+///             if let Some(work) = rx.take() {
+///                 return Some(work);
+///             }
+///
+///             if rx.is_taken() {
+///                 return None;
+///             }
+///
+///             thread::yield_now();
+///         })
+///     })
+///     .collect();
+///
+/// tx.send(String::from("work")).unwrap();
+///
+/// let claimed: Vec<_> = workers
+///     .into_iter()
+///     .filter_map(|worker| worker.join().unwrap())
+///     .collect();
+///
+/// assert_eq!(claimed, vec![String::from("work")]);
+/// ```
+pub fn take_channel<T>() -> (TakeSender<T>, TakeReceiver<T>) {
+    let (tx, rx) = channel();
+
+    (TakeSender { inner: tx }, TakeReceiver { inner: rx })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tokio::task::JoinSet;
+
+    /// Value that doesn't implement [`Clone`]
+    #[derive(Debug, PartialEq)]
+    struct NotClone(u32);
+
+    #[test]
+    fn test_basic() {
+        let (tx, rx) = take_channel();
+        let rx1 = rx.clone();
+
+        assert_eq!(rx.take(), None);
+        assert!(!rx.is_taken());
+
+        tx.send(NotClone(1)).unwrap();
+
+        assert_eq!(rx1.take(), Some(NotClone(1)));
+        assert_eq!(rx1.take(), None);
+        assert_eq!(rx.take(), None);
+        assert!(rx.is_taken());
+    }
+
+    #[test]
+    fn test_work_without_receiver() {
+        let (tx, rx) = take_channel();
+
+        drop(rx);
+
+        assert!(tx.is_disconnected());
+        assert_eq!(
+            tx.send(NotClone(2)),
+            Err(SendError::Disconnected(NotClone(2)))
+        );
+    }
+
+    #[test]
+    fn test_work_without_sender() {
+        let (tx, rx) = take_channel::<NotClone>();
+
+        drop(tx);
+
+        assert_eq!(rx.take(), None);
+        assert!(!rx.is_taken());
+    }
+
+    #[tokio::test]
+    async fn test_recv() {
+        let (tx, rx) = take_channel();
+
+        let mut join_set = JoinSet::new();
+        for _ in 0..4 {
+            join_set.spawn(rx.clone());
+        }
+        join_set.spawn(rx.clone().recv());
+
+        tokio::task::yield_now().await;
+
+        tx.send(NotClone(3)).unwrap();
+
+        let mut result = join_set.join_all().await;
+        result.sort_by_key(Option::is_none);
+
+        assert_eq!(result[0], Some(NotClone(3)));
+        assert!(result[1..].iter().all(Option::is_none));
+        assert_eq!(rx.await, None);
+    }
+}
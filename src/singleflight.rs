@@ -0,0 +1,359 @@
+#![forbid(unsafe_code)]
+//! # Coalescing concurrent requests
+//!
+//! When many tasks ask for the same thing at the same time, e.g. for a cache
+//! entry that just expired, they'd all run the same expensive request (the
+//! "thundering herd"). A [`Group`] suppresses this: calls to [`Group::work`]
+//! for a key that is already in flight don't run their own function, but wait
+//! for the running one and get a clone of its result.
+//!
+//! Once a flight landed, the key is free again: the next call runs the
+//! function anew, so a group coalesces concurrent calls, but doesn't cache
+//! results. To share a result that is expensive to clone, return an
+//! [`Arc`](alloc::sync::Arc). To share errors as well, return a [`Result`].
+//!
+//! If the task running the function is cancelled (its future is dropped) or
+//! panics, one of the waiting calls runs its own function instead, so waiters
+//! never hang. The flights are tracked by [`OnceCell`]s of the `once` module.
+//! The group doesn't contain any unsafe code and works with any async
+//! runtime.
+//!
+//! # Examples
+//! ```rust
+//! # tokio::runtime::Builder::new_current_thread()
+//! #     .build()
+//! #     .unwrap()
+//! #     .block_on(async {
+//! use laika::singleflight::Group;
+//! use std::sync::{
+//!     Arc,
+//!     atomic::{AtomicUsize, Ordering},
+//! };
+//!
+//! let profiles = Arc::new(Group::new());
+//! let fetched = Arc::new(AtomicUsize::new(0));
+//!
+//! let requests: Vec<_> = (0..4)
+//!     .map(|_| {
+//!         let profiles = Arc::clone(&profiles);
+//!         let fetched = Arc::clone(&fetched);
+//!
+//!         tokio::spawn(async move {
+//!             profiles
+//!                 .work("laika", || async move {
+//!                     fetched.fetch_add(1, Ordering::Relaxed);
+//!                     tokio::task::yield_now().await;
+//!                     String::from("first dog in orbit")
+//!                 })
+//!                 .await
+//!         })
+//!     })
+//!     .collect();
+//!
+//! for request in requests {
+//!     assert_eq!(request.await.unwrap(), "first dog in orbit");
+//! }
+//!
+//! // The 4 concurrent requests shared a single fetch
+//! assert_eq!(fetched.load(Ordering::Relaxed), 1);
+//! # });
+//! ```
+
+use crate::once::OnceCell;
+use alloc::{collections::BTreeMap, sync::Arc};
+use core::{fmt, future::Future};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+/// Group of calls sharing a single execution per key
+///
+/// See the [module documentation](self) for details.
+pub struct Group<K, V> {
+    /// Flights in progress, by key
+    flights: Mutex<BTreeMap<K, Arc<OnceCell<V>>>>,
+}
+
+/// Lands a flight once a call is done, even if it has been cancelled or
+/// panicked
+struct Landing<'a, K, V>
+where
+    K: Ord,
+{
+    /// Group of the flight
+    group: &'a Group<K, V>,
+    /// Key of the flight
+    key: K,
+    /// Flight the call took part in, taken when landing
+    flight: Option<Arc<OnceCell<V>>>,
+}
+
+impl<K, V> Group<K, V>
+where
+    K: Ord + Clone,
+    V: Clone,
+{
+    /// Creates a group without flights. As it's a const function, the group
+    /// can be placed in a `static`.
+    pub const fn new() -> Self {
+        Group {
+            flights: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Locks the flights.
+    fn lock(&self) -> MutexGuard<'_, BTreeMap<K, Arc<OnceCell<V>>>> {
+        crate::lock(&self.flights)
+    }
+
+    /// Runs the future returned by `f` and returns its output, unless a call
+    /// for `key` is in flight already: then waits for that one and returns a
+    /// clone of its output instead, without calling `f`.
+    ///
+    /// If the call in flight is cancelled or panics, one of the waiting calls
+    /// runs its own `f` instead.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # tokio::runtime::Builder::new_current_thread()
+    /// #     .build()
+    /// #     .unwrap()
+    /// #     .block_on(async {
+    /// use laika::singleflight::Group;
+    ///
+    /// let group = Group::new();
+    ///
+    /// let (first, second) = tokio::join!(
+    ///     group.work(1, || async {
+    ///         tokio::task::yield_now().await;
+    ///         "fetched"
+    ///     }),
+    ///     group.work(1, || async { "fetched again" }),
+    /// );
+    ///
+    /// // The second call joined the flight of the first one
+    /// assert_eq!((first, second), ("fetched", "fetched"));
+    ///
+    /// // Landed, so the next call runs its own function
+    /// assert_eq!(group.work(1, || async { "later" }).await, "later");
+    /// # });
+    /// ```
+    pub async fn work<F, Fut>(&self, key: K, f: F) -> V
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = V>,
+    {
+        let flight = Arc::clone(self.lock().entry(key.clone()).or_default());
+        let landing = Landing {
+            group: self,
+            key,
+            flight: Some(flight),
+        };
+
+        match &landing.flight {
+            Some(flight) => flight.get_or_init(f).await.clone(),
+            None => unreachable!("the flight is only taken when landing"),
+        }
+    }
+
+    /// Returns whether a call for `key` is in flight right now.
+    pub fn is_in_flight(&self, key: &K) -> bool {
+        self.lock().contains_key(key)
+    }
+
+    /// Returns the number of keys with a call in flight right now.
+    pub fn in_flight(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Forgets the flight for `key`, so the next call runs its own function
+    /// instead of joining it, e.g. because the data changed since the call in
+    /// flight started. Calls that joined the flight already still get its
+    /// output.
+    pub fn forget(&self, key: &K) {
+        self.lock().remove(key);
+    }
+}
+
+/// Removes the flight from the group once the first call with a result is
+/// done, or once the last call is cancelled, unless it has been forgotten and
+/// replaced in the meantime. The references to a flight are only dropped
+/// while the flights are locked, so their count is exact.
+impl<K, V> Drop for Landing<'_, K, V>
+where
+    K: Ord,
+{
+    fn drop(&mut self) {
+        let Some(flight) = self.flight.take() else {
+            return;
+        };
+
+        let mut flights = self
+            .group
+            .flights
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+
+        let lands = flights.get(&self.key).is_some_and(|current| {
+            Arc::ptr_eq(current, &flight)
+                && (flight.is_initialized() || Arc::strong_count(&flight) == 2)
+        });
+
+        if !lands {
+            drop(flight);
+            return;
+        }
+
+        let landed = flights.remove(&self.key);
+
+        // Dropped after unlocking, in case the `Drop` of the value uses the
+        // group
+        drop(flights);
+        drop(landed);
+        drop(flight);
+    }
+}
+
+impl<K, V> Default for Group<K, V>
+where
+    K: Ord + Clone,
+    V: Clone,
+{
+    fn default() -> Self {
+        Group::new()
+    }
+}
+
+impl<K, V> fmt::Debug for Group<K, V>
+where
+    K: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let flights = crate::lock(&self.flights);
+
+        f.debug_struct("Group")
+            .field("in_flight", &flights.keys())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::{
+        pin::pin,
+        task::{Context, Poll, Waker},
+    };
+    use std::{
+        sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+        vec::Vec,
+    };
+
+    #[test]
+    fn test_coalesce() {
+        let group = Group::new();
+        let mut cx = Context::from_waker(Waker::noop());
+        let landed = AtomicBool::new(false);
+
+        let mut first = pin!(group.work('a', || {
+            core::future::poll_fn(|_| match landed.load(Ordering::Relaxed) {
+                true => Poll::Ready(1),
+                false => Poll::Pending,
+            })
+        }));
+        let mut second = pin!(group.work('a', || async { panic!("ran twice") }));
+        let mut other = pin!(group.work('b', || async { 2 }));
+
+        assert!(first.as_mut().poll(&mut cx).is_pending());
+        assert!(second.as_mut().poll(&mut cx).is_pending());
+        assert_eq!(other.as_mut().poll(&mut cx), Poll::Ready(2));
+        assert!(group.is_in_flight(&'a'));
+        assert_eq!(group.in_flight(), 1);
+
+        landed.store(true, Ordering::Relaxed);
+        assert_eq!(first.as_mut().poll(&mut cx), Poll::Ready(1));
+        assert_eq!(second.as_mut().poll(&mut cx), Poll::Ready(1));
+        assert_eq!(group.in_flight(), 0);
+    }
+
+    #[test]
+    fn test_cancel() {
+        let group = Group::new();
+        let mut cx = Context::from_waker(Waker::noop());
+
+        let mut waiting = pin!(group.work(0, || async { "waiter" }));
+
+        {
+            let mut cancelled = pin!(group.work(0, core::future::pending));
+            assert!(cancelled.as_mut().poll(&mut cx).is_pending());
+            assert!(waiting.as_mut().poll(&mut cx).is_pending());
+        }
+
+        // The waiting call takes over
+        assert_eq!(group.in_flight(), 1);
+        assert_eq!(waiting.as_mut().poll(&mut cx), Poll::Ready("waiter"));
+        assert_eq!(group.in_flight(), 0);
+
+        {
+            let mut cancelled = pin!(group.work(1, core::future::pending));
+            assert!(cancelled.as_mut().poll(&mut cx).is_pending());
+        }
+
+        // Nobody is waiting for the cancelled flight
+        assert!(!group.is_in_flight(&1));
+    }
+
+    #[test]
+    fn test_forget() {
+        let group = Group::new();
+        let mut cx = Context::from_waker(Waker::noop());
+        let landed = AtomicBool::new(false);
+
+        let mut stale = pin!(group.work(0, || {
+            core::future::poll_fn(|_| match landed.load(Ordering::Relaxed) {
+                true => Poll::Ready("stale"),
+                false => Poll::Pending,
+            })
+        }));
+        assert!(stale.as_mut().poll(&mut cx).is_pending());
+
+        group.forget(&0);
+        assert!(!group.is_in_flight(&0));
+
+        // Runs its own function instead of joining the forgotten flight
+        assert_eq!(
+            pin!(group.work(0, || async { "fresh" })).poll(&mut cx),
+            Poll::Ready("fresh")
+        );
+
+        landed.store(true, Ordering::Relaxed);
+        assert_eq!(stale.as_mut().poll(&mut cx), Poll::Ready("stale"));
+    }
+
+    #[tokio::test]
+    async fn test_work() {
+        let group = Arc::new(Group::new());
+        let runs = Arc::new(AtomicUsize::new(0));
+
+        let calls: Vec<_> = (0..8)
+            .map(|_| {
+                let group = Arc::clone(&group);
+                let runs = Arc::clone(&runs);
+
+                tokio::spawn(async move {
+                    group
+                        .work("key", || async move {
+                            runs.fetch_add(1, Ordering::Relaxed);
+                            tokio::task::yield_now().await;
+                            Arc::new([1, 2, 3])
+                        })
+                        .await
+                })
+            })
+            .collect();
+
+        for call in calls {
+            assert_eq!(*call.await.unwrap(), [1, 2, 3]);
+        }
+
+        assert_eq!(runs.load(Ordering::Relaxed), 1);
+    }
+}
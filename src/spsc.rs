@@ -0,0 +1,683 @@
+#![forbid(unsafe_code)]
+//! # A fixed-capacity single producer, single consumer (SPSC) queue
+//!
+//! A ring buffer of `capacity` slots with one [`Producer`] that pushes values
+//! and one [`Consumer`] that pops them in order. Both sides can wait for room
+//! or values asynchronously or by blocking the current thread.
+//!
+//! The queue is built for hot paths like audio or network processing: the
+//! state is just two indices. Each side only writes its own index, so pushing
+//! and popping take no shared lock. Every slot is guarded by its own
+//! `std::sync::Mutex`, which keeps the queue free of unsafe code but is never
+//! contended, as the indices hand each slot to one side at a time. Wakers are
+//! only locked if the other side is actually waiting.
+//!
+//! Queues created with [`channel_with_overflow`] shed load by an [`Overflow`]
+//! policy instead of making the producer wait for room. With
+//! [`Overflow::DropOldest`], the producer advances the consumer's index of a
+//! full queue as well. It does so while holding the lock of the oldest slot,
+//! which the consumer checks its index under, so that slot is only contended
+//! while the queue is full.
+//!
+//! # Examples
+//! ```rust
+//! use std::thread;
+//!
+//! let (mut producer, mut consumer) = laika::spsc::channel(64);
+//!
+//! let thread = thread::spawn(move || {
+//!     for sample in 0..1024 {
+//!         producer.push_blocking(sample).unwrap();
+//!     }
+//! });
+//!
+//! let mut sum = 0;
+//!
+//! while let Some(sample) = consumer.pop_blocking() {
+//!     sum += sample;
+//! }
+//!
+//! thread.join().unwrap();
+//! assert_eq!(sum, 1023 * 1024 / 2);
+//! ```
+
+mod error;
+
+pub use crate::Overflow;
+pub use error::{PushError, TryPopError, TryPushError};
+
+use crate::park;
+use alloc::{boxed::Box, sync::Arc};
+use core::{
+    future,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    task::{Context, Poll, Waker},
+};
+use std::sync::Mutex;
+
+/// Waker of one side of the queue while it's waiting for the other one
+#[derive(Debug, Default)]
+struct Side {
+    /// Whether a waker has been registered, so the other side only locks
+    /// [`Side::waker`] if there's someone to wake up
+    waiting: AtomicBool,
+    /// Registered waker
+    waker: Mutex<Option<Waker>>,
+}
+
+impl Side {
+    /// Registers the waker of `cx`. The caller has to check the queue again
+    /// afterwards, in case the other side made progress in the meantime.
+    fn register(&self, cx: &Context<'_>) {
+        let mut waker = crate::lock(&self.waker);
+
+        if !waker.as_ref().is_some_and(|w| w.will_wake(cx.waker())) {
+            *waker = Some(cx.waker().clone());
+        }
+
+        self.waiting.store(true, Ordering::SeqCst);
+    }
+
+    /// Wakes up the registered waker, if any.
+    fn wake(&self) {
+        // Only the load is paid on the hot path if nobody is waiting
+        if !self.waiting.load(Ordering::SeqCst) || !self.waiting.swap(false, Ordering::SeqCst) {
+            return;
+        }
+
+        let waker = crate::lock(&self.waker).take();
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+/// Ring buffer shared by the [`Producer`] and the [`Consumer`]
+#[derive(Debug)]
+struct Ring<T> {
+    /// Slots of the values. Slot `i % capacity` holds the `i`th value.
+    slots: Box<[Mutex<Option<T>>]>,
+    /// Number of popped (or dropped) values. Written by the consumer, and by
+    /// the producer if it drops the oldest value, both only while holding the
+    /// lock of the slot of the `head`th value.
+    head: AtomicUsize,
+    /// Number of pushed values, only written by the producer
+    tail: AtomicUsize,
+    /// What pushing to a full queue does
+    overflow: Overflow,
+    /// Whether the producer has been dropped
+    producer_dropped: AtomicBool,
+    /// Whether the consumer has been dropped
+    consumer_dropped: AtomicBool,
+    /// Producer waiting for room
+    producer: Side,
+    /// Consumer waiting for a value
+    consumer: Side,
+}
+
+impl<T> Ring<T> {
+    /// Returns the slot of the `index`th value.
+    fn slot(&self, index: usize) -> &Mutex<Option<T>> {
+        &self.slots[index % self.slots.len()]
+    }
+
+    /// Returns the number of values in the queue.
+    fn len(&self) -> usize {
+        // Loads the head first, so the tail can't be behind it
+        let head = self.head.load(Ordering::Acquire);
+        self.tail.load(Ordering::Acquire).wrapping_sub(head)
+    }
+
+    /// Pushes `value` if there's room (or the overflow policy makes room).
+    /// Only called by the producer.
+    ///
+    /// Loads of the other side's state are sequentially consistent, pairing
+    /// with [`Side::register`] so that either the waiting side sees the
+    /// progress or this side sees the waker.
+    fn try_push(&self, value: T) -> Result<(), TryPushError<T>> {
+        if self.consumer_dropped.load(Ordering::SeqCst) {
+            return Err(TryPushError::Disconnected(value));
+        }
+
+        let tail = self.tail.load(Ordering::Relaxed);
+
+        if tail.wrapping_sub(self.head.load(Ordering::SeqCst)) == self.slots.len() {
+            match self.overflow {
+                Overflow::Block | Overflow::Error => return Err(TryPushError::Full(value)),
+                Overflow::DropNewest => {
+                    drop(value);
+                    return Ok(());
+                }
+                Overflow::DropOldest => self.drop_oldest(tail),
+            }
+        }
+
+        *crate::lock(self.slot(tail)) = Some(value);
+        self.tail.store(tail.wrapping_add(1), Ordering::SeqCst);
+        self.consumer.wake();
+
+        Ok(())
+    }
+
+    /// Drops the oldest value of the queue, which is full with `tail` pushed
+    /// values, to make room for the next one. Only called by the producer.
+    fn drop_oldest(&self, tail: usize) {
+        let head = tail.wrapping_sub(self.slots.len());
+        let mut slot = crate::lock(self.slot(head));
+
+        // The consumer might have popped the value in the meantime, making
+        // room anyway.
+        if self.head.load(Ordering::SeqCst) != head {
+            return;
+        }
+
+        let oldest = slot.take();
+        self.head.store(head.wrapping_add(1), Ordering::SeqCst);
+
+        drop(slot);
+        drop(oldest);
+    }
+
+    /// Pops the oldest value if there is one. Only called by the consumer.
+    fn try_pop(&self) -> Result<T, TryPopError> {
+        loop {
+            let head = self.head.load(Ordering::SeqCst);
+
+            if head == self.tail.load(Ordering::SeqCst) {
+                // The producer sets the flag after its last push, so check for
+                // a value once more after seeing it.
+                if !self.producer_dropped.load(Ordering::SeqCst) {
+                    return Err(TryPopError::Empty);
+                }
+
+                if head == self.tail.load(Ordering::SeqCst) {
+                    return Err(TryPopError::Disconnected);
+                }
+            }
+
+            let mut slot = crate::lock(self.slot(head));
+
+            // The producer might have dropped the value in the meantime, so
+            // start over with the next one.
+            if self.head.load(Ordering::SeqCst) != head {
+                continue;
+            }
+
+            let value = slot.take();
+            self.head.store(head.wrapping_add(1), Ordering::SeqCst);
+
+            drop(slot);
+            self.producer.wake();
+
+            return match value {
+                Some(value) => Ok(value),
+                None => unreachable!("Slots before the tail are always filled"),
+            };
+        }
+    }
+
+    /// Pushes the value in `value` once there's room, registering the waker
+    /// of `cx` while the queue is full.
+    fn poll_push(&self, cx: &Context<'_>, value: &mut Option<T>) -> Poll<Result<(), PushError<T>>> {
+        let Some(pushed) = value.take() else {
+            panic!("`poll_push` called after the value has been pushed");
+        };
+
+        let pushed = match self.try_push(pushed) {
+            Ok(()) => return Poll::Ready(Ok(())),
+            Err(TryPushError::Disconnected(pushed)) => {
+                return Poll::Ready(Err(PushError::Disconnected(pushed)));
+            }
+            Err(TryPushError::Full(pushed)) if self.overflow == Overflow::Error => {
+                return Poll::Ready(Err(PushError::Full(pushed)));
+            }
+            Err(TryPushError::Full(pushed)) => pushed,
+        };
+
+        self.producer.register(cx);
+
+        // Check again as the consumer might have made room in the meantime.
+        match self.try_push(pushed) {
+            Ok(()) => Poll::Ready(Ok(())),
+            Err(TryPushError::Disconnected(pushed)) => {
+                Poll::Ready(Err(PushError::Disconnected(pushed)))
+            }
+            Err(TryPushError::Full(pushed)) => {
+                *value = Some(pushed);
+                Poll::Pending
+            }
+        }
+    }
+
+    /// Pops the oldest value, registering the waker of `cx` while the queue
+    /// is empty.
+    fn poll_pop(&self, cx: &Context<'_>) -> Poll<Option<T>> {
+        match self.try_pop() {
+            Ok(value) => return Poll::Ready(Some(value)),
+            Err(TryPopError::Disconnected) => return Poll::Ready(None),
+            Err(TryPopError::Empty) => {}
+        }
+
+        self.consumer.register(cx);
+
+        // Check again as the producer might have pushed in the meantime.
+        match self.try_pop() {
+            Ok(value) => Poll::Ready(Some(value)),
+            Err(TryPopError::Disconnected) => Poll::Ready(None),
+            Err(TryPopError::Empty) => Poll::Pending,
+        }
+    }
+}
+
+/// Pushing side of an spsc queue
+#[derive(Debug)]
+pub struct Producer<T> {
+    /// Ring buffer shared with the consumer
+    ring: Arc<Ring<T>>,
+}
+
+/// Popping side of an spsc queue
+#[derive(Debug)]
+pub struct Consumer<T> {
+    /// Ring buffer shared with the producer
+    ring: Arc<Ring<T>>,
+}
+
+impl<T> Producer<T> {
+    /// Pushes a value if there's room for it. This function is
+    /// **non-blocking**.
+    ///
+    /// Returns the value in [`TryPushError::Full`] if the queue is full (and
+    /// the [`Overflow`] policy is [`Block`](Overflow::Block) or
+    /// [`Error`](Overflow::Error)) or [`TryPushError::Disconnected`] if the
+    /// [`Consumer`] has been dropped.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use laika::spsc::TryPushError;
+    ///
+    /// let (mut producer, mut consumer) = laika::spsc::channel(1);
+    ///
+    /// producer.try_push(1).unwrap();
+    /// assert_eq!(producer.try_push(2), Err(TryPushError::Full(2)));
+    ///
+    /// assert_eq!(consumer.try_pop(), Ok(1));
+    /// producer.try_push(2).unwrap();
+    /// ```
+    pub fn try_push(&mut self, value: T) -> Result<(), TryPushError<T>> {
+        self.ring.try_push(value)
+    }
+
+    /// Pushes a value, waiting asynchronously until there's room for it. If the
+    /// queue is full, queues with an [`Overflow`] policy other than
+    /// [`Block`](Overflow::Block) don't wait but handle the value by their
+    /// policy.
+    ///
+    /// Resolves to [`PushError::Disconnected`] containing the value if the
+    /// [`Consumer`] has been dropped, or to [`PushError::Full`] if the queue is
+    /// full and the policy is [`Error`](Overflow::Error). If the future is
+    /// dropped before it resolves, the value isn't pushed.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # tokio::runtime::Builder::new_current_thread()
+    /// #     .build()
+    /// #     .unwrap()
+    /// #     .block_on(async {
+    /// let (mut producer, mut consumer) = laika::spsc::channel(4);
+    ///
+    /// let task = tokio::spawn(async move {
+    ///     for packet in 0..16 {
+    ///         producer.push(packet).await.unwrap();
+    ///     }
+    /// });
+    ///
+    /// let mut packets = Vec::new();
+    ///
+    /// while let Some(packet) = consumer.pop().await {
+    ///     packets.push(packet);
+    /// }
+    ///
+    /// task.await.unwrap();
+    /// assert_eq!(packets, (0..16).collect::<Vec<_>>());
+    /// # });
+    /// ```
+    pub async fn push(&mut self, value: T) -> Result<(), PushError<T>> {
+        let mut value = Some(value);
+
+        future::poll_fn(|cx| self.ring.poll_push(cx, &mut value)).await
+    }
+
+    /// Pushes a value, blocking the current thread until there's room for it.
+    /// See [`Producer::push`].
+    pub fn push_blocking(&mut self, value: T) -> Result<(), PushError<T>> {
+        let mut value = Some(value);
+
+        park::block_on(|cx| self.ring.poll_push(cx, &mut value))
+    }
+
+    /// Returns the number of values in the queue.
+    pub fn len(&self) -> usize {
+        self.ring.len()
+    }
+
+    /// Returns whether the queue is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of slots of the queue.
+    pub fn capacity(&self) -> usize {
+        self.ring.slots.len()
+    }
+
+    /// Returns what pushing to a full queue does.
+    pub fn overflow(&self) -> Overflow {
+        self.ring.overflow
+    }
+
+    /// Returns whether the [`Consumer`] has been dropped, so pushing would
+    /// fail.
+    pub fn is_closed(&self) -> bool {
+        self.ring.consumer_dropped.load(Ordering::Acquire)
+    }
+}
+
+/// Closes the queue when the [`Producer`] is dropped, waking up the consumer.
+impl<T> Drop for Producer<T> {
+    fn drop(&mut self) {
+        self.ring.producer_dropped.store(true, Ordering::SeqCst);
+        self.ring.consumer.wake();
+    }
+}
+
+impl<T> Consumer<T> {
+    /// Pops the oldest value if there is one. This function is
+    /// **non-blocking**.
+    ///
+    /// Returns [`TryPopError::Empty`] if the queue is empty or
+    /// [`TryPopError::Disconnected`] if it's empty and the [`Producer`] has
+    /// been dropped.
+    pub fn try_pop(&mut self) -> Result<T, TryPopError> {
+        self.ring.try_pop()
+    }
+
+    /// Polls for the oldest value, registering the waker of `cx` if there is
+    /// none yet. Resolves to [`None`] once the queue is empty and the
+    /// [`Producer`] has been dropped.
+    pub fn poll_pop(&mut self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        self.ring.poll_pop(cx)
+    }
+
+    /// Pops the oldest value, waiting asynchronously until there is one.
+    /// Resolves to [`None`] once the queue is empty and the [`Producer`] has
+    /// been dropped.
+    pub async fn pop(&mut self) -> Option<T> {
+        future::poll_fn(|cx| self.ring.poll_pop(cx)).await
+    }
+
+    /// Pops the oldest value, blocking the current thread until there is one.
+    /// See [`Consumer::pop`].
+    pub fn pop_blocking(&mut self) -> Option<T> {
+        park::block_on(|cx| self.ring.poll_pop(cx))
+    }
+
+    /// Returns the number of values in the queue.
+    pub fn len(&self) -> usize {
+        self.ring.len()
+    }
+
+    /// Returns whether the queue is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of slots of the queue.
+    pub fn capacity(&self) -> usize {
+        self.ring.slots.len()
+    }
+}
+
+/// Closes the queue when the [`Consumer`] is dropped, waking up the producer.
+/// Values left in the queue are dropped along with it.
+impl<T> Drop for Consumer<T> {
+    fn drop(&mut self) {
+        self.ring.consumer_dropped.store(true, Ordering::SeqCst);
+        self.ring.producer.wake();
+    }
+}
+
+/// Creates an spsc queue with `capacity` slots.
+///
+/// # Panics
+/// Panics if `capacity` is zero.
+///
+/// # Examples
+/// ```rust
+/// let (mut producer, mut consumer) = laika::spsc::channel(2);
+///
+/// producer.try_push("a").unwrap();
+/// producer.try_push("b").unwrap();
+///
+/// assert_eq!(consumer.try_pop(), Ok("a"));
+/// assert_eq!(consumer.try_pop(), Ok("b"));
+/// ```
+pub fn channel<T>(capacity: usize) -> (Producer<T>, Consumer<T>) {
+    channel_with_overflow(capacity, Overflow::Block)
+}
+
+/// Creates an spsc queue with `capacity` slots that handles pushing to a full
+/// queue by `overflow`.
+///
+/// # Panics
+/// Panics if `capacity` is zero.
+///
+/// # Examples
+/// ```rust
+/// use laika::spsc::{self, Overflow, PushError};
+///
+/// let (mut producer, mut consumer) = spsc::channel_with_overflow(1, Overflow::Error);
+///
+/// producer.push_blocking(1).unwrap();
+/// assert_eq!(producer.push_blocking(2), Err(PushError::Full(2)));
+/// assert_eq!(consumer.try_pop(), Ok(1));
+/// ```
+pub fn channel_with_overflow<T>(capacity: usize, overflow: Overflow) -> (Producer<T>, Consumer<T>) {
+    assert!(capacity > 0, "an spsc queue needs a capacity");
+
+    let ring = Arc::new(Ring {
+        slots: (0..capacity).map(|_| Mutex::new(None)).collect(),
+        head: AtomicUsize::new(0),
+        tail: AtomicUsize::new(0),
+        overflow,
+        producer_dropped: AtomicBool::new(false),
+        consumer_dropped: AtomicBool::new(false),
+        producer: Side::default(),
+        consumer: Side::default(),
+    });
+
+    let producer = Producer {
+        ring: Arc::clone(&ring),
+    };
+
+    (producer, Consumer { ring })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::{future::Future, pin::pin};
+    use std::{thread, time::Duration, vec::Vec};
+
+    #[test]
+    fn test_wrap_around() {
+        let (mut producer, mut consumer) = channel(3);
+
+        for round in 0..10 {
+            producer.try_push(round * 2).unwrap();
+            producer.try_push(round * 2 + 1).unwrap();
+
+            assert_eq!(consumer.len(), 2);
+            assert_eq!(consumer.try_pop(), Ok(round * 2));
+            assert_eq!(consumer.try_pop(), Ok(round * 2 + 1));
+            assert_eq!(consumer.try_pop(), Err(TryPopError::Empty));
+        }
+
+        assert_eq!(producer.capacity(), 3);
+        assert!(producer.is_empty());
+    }
+
+    #[test]
+    fn test_disconnected() {
+        let (mut producer, mut consumer) = channel(2);
+
+        producer.try_push(1).unwrap();
+        drop(producer);
+
+        assert_eq!(consumer.try_pop(), Ok(1));
+        assert_eq!(consumer.try_pop(), Err(TryPopError::Disconnected));
+        assert_eq!(consumer.pop_blocking(), None);
+
+        let (mut producer, consumer) = channel(2);
+        drop(consumer);
+
+        assert!(producer.is_closed());
+        assert_eq!(producer.try_push(1), Err(TryPushError::Disconnected(1)));
+        assert_eq!(producer.push_blocking(1), Err(PushError::Disconnected(1)));
+    }
+
+    #[test]
+    fn test_overflow() {
+        let mut cx = Context::from_waker(Waker::noop());
+
+        let (mut producer, mut consumer) = channel_with_overflow(2, Overflow::DropOldest);
+        assert_eq!(producer.overflow(), Overflow::DropOldest);
+
+        for value in 0..5 {
+            assert_eq!(
+                pin!(producer.push(value)).poll(&mut cx),
+                Poll::Ready(Ok(()))
+            );
+        }
+
+        assert_eq!(consumer.len(), 2);
+        assert_eq!(consumer.try_pop(), Ok(3));
+        assert_eq!(consumer.try_pop(), Ok(4));
+
+        let (mut producer, mut consumer) = channel_with_overflow(2, Overflow::DropNewest);
+
+        for value in 0..4 {
+            assert_eq!(producer.try_push(value), Ok(()));
+        }
+
+        assert_eq!(consumer.try_pop(), Ok(0));
+        assert_eq!(consumer.try_pop(), Ok(1));
+        assert_eq!(consumer.try_pop(), Err(TryPopError::Empty));
+
+        let (mut producer, consumer) = channel_with_overflow(1, Overflow::Error);
+
+        producer.try_push(0).unwrap();
+        assert_eq!(
+            pin!(producer.push(1)).poll(&mut cx),
+            Poll::Ready(Err(PushError::Full(1)))
+        );
+        assert!(!producer.ring.producer.waiting.load(Ordering::SeqCst));
+
+        // A closed queue fails regardless of the policy
+        drop(consumer);
+        assert_eq!(producer.push_blocking(2), Err(PushError::Disconnected(2)));
+    }
+
+    #[test]
+    fn test_drop_oldest_concurrently() {
+        let (mut producer, mut consumer) = channel_with_overflow(4, Overflow::DropOldest);
+
+        let thread = thread::spawn(move || {
+            for value in 0..10_000 {
+                producer.push_blocking(value).unwrap();
+            }
+        });
+
+        let mut values = Vec::new();
+
+        while let Some(value) = consumer.pop_blocking() {
+            values.push(value);
+        }
+
+        thread.join().unwrap();
+
+        // Values are dropped, but never reordered or popped twice
+        assert!(values.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(values.last(), Some(&9_999));
+    }
+
+    #[test]
+    fn test_poll() {
+        let (mut producer, mut consumer) = channel(1);
+        let mut cx = Context::from_waker(Waker::noop());
+
+        assert_eq!(consumer.poll_pop(&mut cx), Poll::Pending);
+        assert!(producer.ring.consumer.waiting.load(Ordering::SeqCst));
+
+        producer.try_push(1).unwrap();
+        assert!(!producer.ring.consumer.waiting.load(Ordering::SeqCst));
+
+        {
+            let mut push = pin!(producer.push(2));
+            assert_eq!(push.as_mut().poll(&mut cx), Poll::Pending);
+            assert!(consumer.ring.producer.waiting.load(Ordering::SeqCst));
+
+            assert_eq!(consumer.try_pop(), Ok(1));
+            assert_eq!(push.as_mut().poll(&mut cx), Poll::Ready(Ok(())));
+        }
+
+        assert_eq!(consumer.poll_pop(&mut cx), Poll::Ready(Some(2)));
+    }
+
+    #[test]
+    fn test_blocking() {
+        let (mut producer, mut consumer) = channel(2);
+
+        let thread = thread::spawn(move || {
+            let mut values = Vec::new();
+
+            while let Some(value) = consumer.pop_blocking() {
+                values.push(value);
+            }
+
+            values
+        });
+
+        thread::sleep(Duration::from_millis(10));
+
+        for value in 0..100 {
+            producer.push_blocking(value).unwrap();
+        }
+
+        drop(producer);
+        assert_eq!(thread.join().unwrap(), (0..100).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn test_async() {
+        let (mut producer, mut consumer) = channel(3);
+
+        let task = tokio::spawn(async move {
+            let mut values = Vec::new();
+
+            while let Some(value) = consumer.pop().await {
+                values.push(value);
+            }
+
+            values
+        });
+
+        for value in 0..100 {
+            producer.push(value).await.unwrap();
+        }
+
+        drop(producer);
+        assert_eq!(task.await.unwrap(), (0..100).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,115 @@
+//! Errors of pushing to and popping from spsc queues.
+
+#[cfg(doc)]
+use super::{Consumer, Overflow, Producer};
+use core::{error::Error, fmt};
+
+/// Error returned by [`Producer::push`] and [`Producer::push_blocking`].
+/// Contains the value, so it can be reclaimed.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum PushError<T> {
+    /// The queue is full and the [`Overflow::Error`] policy rejects new values
+    /// until the consumer catches up.
+    Full(T),
+    /// The [`Consumer`] has been dropped.
+    Disconnected(T),
+}
+
+impl<T> PushError<T> {
+    /// Returns the value that couldn't be pushed.
+    pub fn into_inner(self) -> T {
+        match self {
+            PushError::Full(value) | PushError::Disconnected(value) => value,
+        }
+    }
+}
+
+debug_without_value!(PushError { Full, Disconnected });
+
+impl<T> fmt::Display for PushError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PushError::Full(_) => write!(f, "pushing to a full queue"),
+            PushError::Disconnected(_) => write!(f, "pushing to a queue without consumer"),
+        }
+    }
+}
+
+impl<T> Error for PushError<T> {}
+
+/// Error returned by [`Producer::try_push`]. Contains the value, so it can be
+/// reclaimed.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum TryPushError<T> {
+    /// The queue is full, pushing would have to wait (or fails by the
+    /// [`Overflow::Error`] policy).
+    Full(T),
+    /// The [`Consumer`] has been dropped.
+    Disconnected(T),
+}
+
+impl<T> TryPushError<T> {
+    /// Returns the value that couldn't be pushed.
+    pub fn into_inner(self) -> T {
+        match self {
+            TryPushError::Full(value) | TryPushError::Disconnected(value) => value,
+        }
+    }
+}
+
+debug_without_value!(TryPushError { Full, Disconnected });
+
+impl<T> fmt::Display for TryPushError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TryPushError::Full(_) => write!(f, "pushing to a full queue"),
+            TryPushError::Disconnected(_) => write!(f, "pushing to a queue without consumer"),
+        }
+    }
+}
+
+impl<T> Error for TryPushError<T> {}
+
+/// Error returned by [`Consumer::try_pop`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TryPopError {
+    /// The queue is empty, but the [`Producer`] still exists.
+    Empty,
+    /// The queue is empty and the [`Producer`] has been dropped, so no value
+    /// will ever be popped.
+    Disconnected,
+}
+
+impl fmt::Display for TryPopError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TryPopError::Empty => write!(f, "queue is empty"),
+            TryPopError::Disconnected => write!(f, "queue is empty and closed"),
+        }
+    }
+}
+
+impl Error for TryPopError {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::string::ToString;
+
+    #[test]
+    fn test_display() {
+        assert_eq!(
+            TryPushError::Disconnected(1).to_string(),
+            PushError::Disconnected(1).to_string()
+        );
+        assert_eq!(
+            TryPushError::Full(1).to_string(),
+            PushError::Full(1).to_string()
+        );
+        assert_eq!(TryPushError::Full(1).to_string(), "pushing to a full queue");
+        assert_eq!(
+            TryPopError::Disconnected.to_string(),
+            "queue is empty and closed"
+        );
+    }
+}
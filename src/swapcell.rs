@@ -0,0 +1,556 @@
+#![forbid(unsafe_code)]
+//! # Read-mostly swap cell
+//!
+//! A [`SwapCell`] holds an [`Arc`] that many readers load and a few writers
+//! replace, like a configuration that is reloaded at runtime or a routing
+//! table. Readers get a snapshot ([`SwapCell::load`]) they can keep as long
+//! as they need, while writers publish a new value ([`SwapCell::store`])
+//! atomically: once it's published, every reader gets the new value.
+//!
+//! The value is copied into multiple stripes, each on a cache line of its own
+//! and guarded by its own lock, and every thread reads from the stripe it's
+//! assigned to. So readers don't contend with each other (as long as there are
+//! enough stripes), only with a writer that is publishing right now.
+//!
+//! Every published value increases the [version](SwapCell::version) of the
+//! cell. [`SwapCell::changed`] waits until a value newer than a known version
+//! has been published, so tasks can react to changes without polling.
+//!
+//! The cell doesn't contain any unsafe code and works with any async runtime.
+//!
+//! # Examples
+//! ```rust
+//! # tokio::runtime::Builder::new_current_thread()
+//! #     .build()
+//! #     .unwrap()
+//! #     .block_on(async {
+//! use laika::swapcell::SwapCell;
+//! use std::{collections::HashMap, sync::Arc};
+//!
+//! let routes = Arc::new(SwapCell::new(HashMap::from([("/", "index")])));
+//!
+//! let router = tokio::spawn({
+//!     let routes = Arc::clone(&routes);
+//!
+//!     async move {
+//!         let (snapshot, version) = routes.snapshot();
+//!         assert_eq!(snapshot.get("/about"), None);
+//!
+//!         // Wait for the next routing table
+//!         let (snapshot, _) = routes.changed(version).await;
+//!         snapshot.get("/about").copied()
+//!     }
+//! });
+//!
+//! tokio::task::yield_now().await;
+//! routes.update(|routes| {
+//!     let mut routes = routes.clone();
+//!     routes.insert("/about", "about");
+//!     routes
+//! });
+//!
+//! assert_eq!(router.await.unwrap(), Some("about"));
+//! # });
+//! ```
+
+use alloc::{boxed::Box, collections::BTreeMap, sync::Arc, vec::Vec};
+use core::{
+    fmt,
+    future::Future,
+    mem,
+    pin::Pin,
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+    task::{Context, Poll, Waker},
+};
+use std::{
+    sync::{Condvar, Mutex, MutexGuard, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard},
+    thread,
+};
+
+/// Id of the next thread reading a swap cell, to spread threads over the
+/// stripes
+static NEXT_THREAD: AtomicUsize = AtomicUsize::new(0);
+
+std::thread_local! {
+    /// Id of the current thread, picking its stripe
+    static THREAD: usize = NEXT_THREAD.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Copy of the value of a [`SwapCell`], aligned to its own cache line (or pair
+/// of them, as some CPUs prefetch adjacent lines), so readers of different
+/// stripes don't contend
+#[repr(align(128))]
+struct Stripe<T>(RwLock<Arc<T>>);
+
+/// Tasks waiting for a change, guarded by [`SwapCell::waiters`]
+struct Waiters {
+    /// Wakers keyed by id
+    wakers: BTreeMap<u64, Waker>,
+    /// Id of the next waker
+    next_id: u64,
+}
+
+/// Cell holding an [`Arc`] that is read often and replaced rarely
+///
+/// See the [module documentation](self) for details.
+pub struct SwapCell<T> {
+    /// Copies of the value, a power of two
+    stripes: Box<[Stripe<T>]>,
+    /// Number of values published since the cell was created, updated while
+    /// all stripes are locked
+    version: AtomicU64,
+    /// Serializes writers, so [`SwapCell::update`] doesn't lose updates
+    writer: Mutex<()>,
+    /// Tasks waiting for a change
+    waiters: Mutex<Waiters>,
+    /// Wakes up threads blocking in [`SwapCell::changed_blocking`]
+    condvar: Condvar,
+}
+
+/// Future returned by [`SwapCell::changed`]
+///
+/// Resolves to the new value and its version once a value newer than the
+/// given version has been published.
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Changed<'a, T> {
+    /// Cell to wait for
+    cell: &'a SwapCell<T>,
+    /// Version to wait for a newer value than
+    version: u64,
+    /// Id of the registered waker
+    id: Option<u64>,
+}
+
+/// Locks a stripe for reading, ignoring poisoning. Writers only replace the
+/// [`Arc`] of a stripe, so it always holds a complete value.
+fn read<T>(stripe: &Stripe<T>) -> RwLockReadGuard<'_, Arc<T>> {
+    stripe.0.read().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Locks a stripe for writing. See [`read`].
+fn write<T>(stripe: &Stripe<T>) -> RwLockWriteGuard<'_, Arc<T>> {
+    stripe.0.write().unwrap_or_else(PoisonError::into_inner)
+}
+
+impl<T> SwapCell<T> {
+    /// Creates a cell holding `value`, with one stripe per CPU core (rounded
+    /// up to a power of two).
+    pub fn new(value: T) -> Self {
+        SwapCell::from_arc(Arc::new(value))
+    }
+
+    /// Creates a cell holding `value` that is shared already. See
+    /// [`SwapCell::new`].
+    pub fn from_arc(value: Arc<T>) -> Self {
+        let cores = thread::available_parallelism().map_or(1, |cores| cores.get());
+
+        SwapCell::with_stripes(value, cores)
+    }
+
+    /// Creates a cell holding `value` with `stripes` stripes (rounded up to a
+    /// power of two). More stripes make contention between readers less
+    /// likely, but publishing slower.
+    ///
+    /// # Panics
+    /// Panics if `stripes` is 0.
+    pub fn with_stripes(value: Arc<T>, stripes: usize) -> Self {
+        assert!(stripes > 0, "a swap cell needs at least one stripe");
+
+        let stripes: Vec<_> = (0..stripes.next_power_of_two())
+            .map(|_| Stripe(RwLock::new(Arc::clone(&value))))
+            .collect();
+
+        SwapCell {
+            stripes: stripes.into_boxed_slice(),
+            version: AtomicU64::new(0),
+            writer: Mutex::new(()),
+            waiters: Mutex::new(Waiters {
+                wakers: BTreeMap::new(),
+                next_id: 0,
+            }),
+            condvar: Condvar::new(),
+        }
+    }
+
+    /// Returns the stripe of the current thread.
+    fn stripe(&self) -> &Stripe<T> {
+        let thread = THREAD.with(|thread| *thread);
+
+        &self.stripes[thread & (self.stripes.len() - 1)]
+    }
+
+    /// Locks the waiters.
+    fn lock_waiters(&self) -> MutexGuard<'_, Waiters> {
+        crate::lock(&self.waiters)
+    }
+
+    /// Returns a snapshot of the current value. It stays valid (and
+    /// unchanged) even if a new value is published afterwards.
+    pub fn load(&self) -> Arc<T> {
+        Arc::clone(&read(self.stripe()))
+    }
+
+    /// Returns a snapshot of the current value together with its version,
+    /// e.g. to wait for the next value with [`SwapCell::changed`].
+    pub fn snapshot(&self) -> (Arc<T>, u64) {
+        let value = read(self.stripe());
+
+        // The version is only updated while all stripes are locked
+        (Arc::clone(&value), self.version.load(Ordering::SeqCst))
+    }
+
+    /// Returns the version of the current value: the number of values that
+    /// have been published since the cell was created.
+    pub fn version(&self) -> u64 {
+        self.version.load(Ordering::SeqCst)
+    }
+
+    /// Publishes `value`, so every reader gets it from now on. Snapshots
+    /// loaded before keep the previous value.
+    pub fn store(&self, value: T) {
+        self.swap_arc(Arc::new(value));
+    }
+
+    /// Publishes `value` that is shared already. See [`SwapCell::store`].
+    pub fn store_arc(&self, value: Arc<T>) {
+        self.swap_arc(value);
+    }
+
+    /// Publishes `value` and returns the previous value.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use laika::swapcell::SwapCell;
+    ///
+    /// let config = SwapCell::new("v1");
+    ///
+    /// assert_eq!(*config.swap("v2"), "v1");
+    /// assert_eq!(*config.load(), "v2");
+    /// assert_eq!(config.version(), 1);
+    /// ```
+    pub fn swap(&self, value: T) -> Arc<T> {
+        self.swap_arc(Arc::new(value))
+    }
+
+    /// Publishes `value` that is shared already and returns the previous
+    /// value. See [`SwapCell::swap`].
+    pub fn swap_arc(&self, value: Arc<T>) -> Arc<T> {
+        let _writer = crate::lock(&self.writer);
+
+        self.publish(value)
+    }
+
+    /// Publishes the value returned by `f`, which gets the current value.
+    /// Writers are serialized, so concurrent updates aren't lost. Returns the
+    /// new value.
+    pub fn update<F>(&self, f: F) -> Arc<T>
+    where
+        F: FnOnce(&T) -> T,
+    {
+        let _writer = crate::lock(&self.writer);
+        let value = Arc::new(f(&self.load()));
+
+        self.publish(Arc::clone(&value));
+
+        value
+    }
+
+    /// Publishes `value` while holding [`SwapCell::writer`] and returns the
+    /// previous value.
+    fn publish(&self, value: Arc<T>) -> Arc<T> {
+        // Lock all stripes before replacing any, so no reader gets the new
+        // value from one stripe and then the previous one from another
+        let mut stripes: Vec<_> = self.stripes.iter().map(write).collect();
+        let mut previous = Arc::clone(&value);
+
+        for stripe in &mut stripes {
+            previous = mem::replace(&mut **stripe, Arc::clone(&value));
+        }
+
+        self.version.fetch_add(1, Ordering::SeqCst);
+        drop(stripes);
+
+        let wakers = mem::take(&mut self.lock_waiters().wakers);
+
+        for waker in wakers.into_values() {
+            waker.wake();
+        }
+
+        self.condvar.notify_all();
+
+        previous
+    }
+
+    /// Waits asynchronously until a value newer than `version` has been
+    /// published and returns a snapshot of it with its version. Completes
+    /// right away if that happened already, so no change is missed between
+    /// two calls.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # tokio::runtime::Builder::new_current_thread()
+    /// #     .build()
+    /// #     .unwrap()
+    /// #     .block_on(async {
+    /// use laika::swapcell::SwapCell;
+    ///
+    /// let level = SwapCell::new("info");
+    /// let version = level.version();
+    ///
+    /// level.store("debug");
+    ///
+    /// // Published after `version`, so this doesn't wait
+    /// let (current, version) = level.changed(version).await;
+    ///
+    /// assert_eq!(*current, "debug");
+    /// assert_eq!(version, 1);
+    /// # });
+    /// ```
+    pub fn changed(&self, version: u64) -> Changed<'_, T> {
+        Changed {
+            cell: self,
+            version,
+            id: None,
+        }
+    }
+
+    /// Blocks the current thread until a value newer than `version` has been
+    /// published. See [`SwapCell::changed`].
+    pub fn changed_blocking(&self, version: u64) -> (Arc<T>, u64) {
+        let mut waiters = self.lock_waiters();
+
+        while self.version.load(Ordering::SeqCst) == version {
+            waiters = self
+                .condvar
+                .wait(waiters)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+
+        drop(waiters);
+
+        self.snapshot()
+    }
+
+    /// Returns the number of stripes.
+    pub fn stripes(&self) -> usize {
+        self.stripes.len()
+    }
+}
+
+impl<T> Default for SwapCell<T>
+where
+    T: Default,
+{
+    fn default() -> Self {
+        SwapCell::new(T::default())
+    }
+}
+
+impl<T> From<T> for SwapCell<T> {
+    fn from(value: T) -> Self {
+        SwapCell::new(value)
+    }
+}
+
+impl<T> fmt::Debug for SwapCell<T>
+where
+    T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (value, version) = self.snapshot();
+
+        f.debug_struct("SwapCell")
+            .field("value", &value)
+            .field("version", &version)
+            .finish()
+    }
+}
+
+impl<T> Future for Changed<'_, T> {
+    type Output = (Arc<T>, u64);
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let mut waiters = this.cell.lock_waiters();
+
+        // Publishing wakes up the waiters after updating the version, so
+        // checking it while the waiters are locked doesn't miss a change
+        if this.cell.version.load(Ordering::SeqCst) != this.version {
+            if let Some(id) = this.id.take() {
+                waiters.wakers.remove(&id);
+            }
+
+            drop(waiters);
+
+            return Poll::Ready(this.cell.snapshot());
+        }
+
+        match this.id.and_then(|id| waiters.wakers.get_mut(&id)) {
+            Some(waker) => {
+                if !waker.will_wake(cx.waker()) {
+                    *waker = cx.waker().clone();
+                }
+            }
+            None => {
+                let id = waiters.next_id;
+                waiters.next_id += 1;
+                waiters.wakers.insert(id, cx.waker().clone());
+                this.id = Some(id);
+            }
+        }
+
+        Poll::Pending
+    }
+}
+
+impl<T> Drop for Changed<'_, T> {
+    fn drop(&mut self) {
+        if let Some(id) = self.id {
+            self.cell.lock_waiters().wakers.remove(&id);
+        }
+    }
+}
+
+impl<T> fmt::Debug for Changed<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Changed")
+            .field("version", &self.version)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::pin::pin;
+    use std::{string::String, time::Duration};
+
+    #[test]
+    fn test_store() {
+        let cell = SwapCell::with_stripes(Arc::new(1), 3);
+        assert_eq!(cell.stripes(), 4);
+
+        let snapshot = cell.load();
+        cell.store(2);
+
+        // Snapshots keep the value they were loaded with
+        assert_eq!(*snapshot, 1);
+        assert_eq!(cell.snapshot(), (Arc::new(2), 1));
+
+        // Every stripe has the new value
+        for stripe in cell.stripes.iter() {
+            assert_eq!(**read(stripe), 2);
+        }
+
+        assert_eq!(*cell.update(|value| value * 10), 20);
+        assert_eq!(*cell.swap(3), 20);
+        assert_eq!(cell.version(), 3);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_no_stripes() {
+        SwapCell::with_stripes(Arc::new(()), 0);
+    }
+
+    #[test]
+    fn test_changed() {
+        let cell = SwapCell::new(String::from("a"));
+        let mut cx = Context::from_waker(Waker::noop());
+
+        let mut changed = pin!(cell.changed(0));
+        assert!(changed.as_mut().poll(&mut cx).is_pending());
+        assert!(changed.as_mut().poll(&mut cx).is_pending());
+
+        cell.store(String::from("b"));
+        cell.store(String::from("c"));
+
+        let Poll::Ready((value, version)) = changed.as_mut().poll(&mut cx) else {
+            panic!("the value changed");
+        };
+
+        assert_eq!(*value, "c");
+        assert_eq!(version, 2);
+        assert!(cell.lock_waiters().wakers.is_empty());
+    }
+
+    #[test]
+    fn test_concurrent_update() {
+        let cell = Arc::new(SwapCell::new(0));
+
+        let writers: Vec<_> = (0..4)
+            .map(|_| {
+                let cell = Arc::clone(&cell);
+                thread::spawn(move || {
+                    for _ in 0..100 {
+                        cell.update(|value| value + 1);
+                    }
+                })
+            })
+            .collect();
+
+        let reader = thread::spawn({
+            let cell = Arc::clone(&cell);
+            move || {
+                let mut last = 0;
+
+                // Values never go back
+                while last < 400 {
+                    let value = *cell.load();
+                    assert!(value >= last);
+                    last = value;
+                }
+            }
+        });
+
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        reader.join().unwrap();
+        assert_eq!(cell.snapshot(), (Arc::new(400), 400));
+    }
+
+    #[test]
+    fn test_changed_blocking() {
+        let cell = Arc::new(SwapCell::new('a'));
+
+        let waiter = thread::spawn({
+            let cell = Arc::clone(&cell);
+            move || cell.changed_blocking(0)
+        });
+
+        thread::sleep(Duration::from_millis(20));
+        cell.store('b');
+
+        assert_eq!(waiter.join().unwrap(), (Arc::new('b'), 1));
+    }
+
+    #[tokio::test]
+    async fn test_await_changed() {
+        let cell = Arc::new(SwapCell::new(0_u8));
+
+        let watcher = tokio::spawn({
+            let cell = Arc::clone(&cell);
+            async move {
+                let mut seen = Vec::new();
+                let mut version = 0;
+
+                while version < 3 {
+                    let (value, new_version) = cell.changed(version).await;
+                    seen.push(*value);
+                    version = new_version;
+                }
+
+                seen
+            }
+        });
+
+        for value in 1..=3 {
+            tokio::task::yield_now().await;
+            cell.store(value);
+        }
+
+        assert_eq!(watcher.await.unwrap().last(), Some(&3));
+    }
+}
@@ -0,0 +1,44 @@
+#![forbid(unsafe_code)]
+//! # Runtime-agnostic async synchronization primitives
+//!
+//! Primitives to coordinate tasks (and threads) without depending on the
+//! synchronization types of an async runtime, so library code can use them
+//! regardless of the runtime of the application:
+//!
+//! - [`Barrier`]: Lets a group of tasks proceed together once all of them
+//!   arrived, e.g. between the phases of a parallel algorithm.
+//! - [`Semaphore`]: Caps the number of concurrent operations by handing out
+//!   permits, which are returned once dropped.
+//!
+//! The primitives are backed by a `std::sync::Mutex`, don't contain any unsafe
+//! code and work with any async runtime. Every async function has a blocking
+//! variant for threads.
+//!
+//! # Examples
+//! ```rust
+//! # tokio::runtime::Builder::new_current_thread()
+//! #     .build()
+//! #     .unwrap()
+//! #     .block_on(async {
+//! use laika::sync::Semaphore;
+//!
+//! // At most 2 concurrent requests, shared by all callers
+//! static REQUESTS: Semaphore = Semaphore::new(2);
+//!
+//! async fn fetch(id: u32) -> u32 {
+//!     let _permit = REQUESTS.acquire(1).await.unwrap();
+//!     id * 10
+//! }
+//!
+//! assert_eq!(fetch(4).await, 40);
+//! assert_eq!(REQUESTS.available_permits(), 2);
+//! # });
+//! ```
+
+mod barrier;
+mod error;
+mod semaphore;
+
+pub use barrier::{Barrier, BarrierWaitResult, Wait};
+pub use error::{AcquireError, TryAcquireError};
+pub use semaphore::{Acquire, Permit, Semaphore};
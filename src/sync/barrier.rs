@@ -0,0 +1,355 @@
+//! Barrier letting a group of tasks proceed together.
+
+use alloc::{collections::BTreeMap, vec::Vec};
+use core::{
+    fmt,
+    future::Future,
+    mem,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+use std::sync::{Condvar, Mutex, MutexGuard, PoisonError};
+
+/// State of a [`Barrier`], guarded by [`Barrier::state`]
+struct State {
+    /// Number of tasks that have arrived in the current generation
+    arrived: usize,
+    /// Generation, increased every time the barrier is released
+    generation: u64,
+    /// Wakers of the tasks waiting in the current generation, keyed by id
+    wakers: BTreeMap<u64, Waker>,
+    /// Id of the next waker
+    next_id: u64,
+}
+
+/// Barrier releasing tasks once `n` of them are waiting
+///
+/// Every task calling [`Barrier::wait`] waits until `n` tasks (including
+/// itself) are waiting, then all of them proceed together. One of them, the
+/// last one arriving, is marked as the [leader](BarrierWaitResult::is_leader),
+/// e.g. to combine the results of a phase. The barrier is reset right away, so
+/// it can be reused for the next phase.
+///
+/// See the [module documentation](super) for details.
+pub struct Barrier {
+    /// Number of tasks to wait for
+    n: usize,
+    /// Arrived tasks and their wakers
+    state: Mutex<State>,
+    /// Wakes up threads blocking in [`Barrier::wait_blocking`]
+    condvar: Condvar,
+}
+
+/// Result of waiting at a [`Barrier`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BarrierWaitResult {
+    /// Whether this task released the barrier
+    leader: bool,
+}
+
+/// Future returned by [`Barrier::wait`]
+///
+/// Resolves once `n` tasks are waiting. Dropping it before withdraws the
+/// arrival, so the barrier keeps waiting for another task instead.
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Wait<'a> {
+    /// Barrier to wait at
+    barrier: &'a Barrier,
+    /// Generation and waker id once arrived
+    arrival: Option<(u64, u64)>,
+}
+
+impl Barrier {
+    /// Creates a barrier releasing the tasks once `n` of them are waiting. As
+    /// it's a const function, the barrier can be placed in a `static`.
+    ///
+    /// A barrier for 0 tasks behaves like one for a single task: waiting
+    /// returns right away.
+    pub const fn new(n: usize) -> Self {
+        Barrier {
+            n: if n == 0 { 1 } else { n },
+            state: Mutex::new(State {
+                arrived: 0,
+                generation: 0,
+                wakers: BTreeMap::new(),
+                next_id: 0,
+            }),
+            condvar: Condvar::new(),
+        }
+    }
+
+    /// Locks the state.
+    fn lock(&self) -> MutexGuard<'_, State> {
+        crate::lock(&self.state)
+    }
+
+    /// Registers the arrival of a task. If it's the last one, releases the
+    /// barrier and returns [`None`], otherwise returns the state to wait on.
+    fn arrive<'a>(&self, mut state: MutexGuard<'a, State>) -> Option<MutexGuard<'a, State>> {
+        state.arrived += 1;
+
+        if state.arrived < self.n {
+            return Some(state);
+        }
+
+        state.arrived = 0;
+        state.generation = state.generation.wrapping_add(1);
+
+        let wakers: Vec<_> = mem::take(&mut state.wakers).into_values().collect();
+
+        drop(state);
+
+        for waker in wakers {
+            waker.wake();
+        }
+
+        self.condvar.notify_all();
+
+        None
+    }
+
+    /// Waits asynchronously until `n` tasks are waiting, then proceeds
+    /// together with them.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # tokio::runtime::Builder::new_current_thread()
+    /// #     .build()
+    /// #     .unwrap()
+    /// #     .block_on(async {
+    /// use laika::sync::Barrier;
+    /// use std::sync::Arc;
+    ///
+    /// let barrier = Arc::new(Barrier::new(3));
+    ///
+    /// let workers: Vec<_> = (0..3)
+    ///     .map(|_| {
+    ///         let barrier = Arc::clone(&barrier);
+    ///
+    ///         tokio::spawn(async move {
+    ///             let mut leaders = 0;
+    ///
+    ///             for _phase in 0..2 {
+    ///                 // Work on the phase, then wait for the others
+    ///                 if barrier.wait().await.is_leader() {
+    ///                     leaders += 1;
+    ///                 }
+    ///             }
+    ///
+    ///             leaders
+    ///         })
+    ///     })
+    ///     .collect();
+    ///
+    /// let mut leaders = 0;
+    ///
+    /// for worker in workers {
+    ///     leaders += worker.await.unwrap();
+    /// }
+    ///
+    /// // One leader per phase
+    /// assert_eq!(leaders, 2);
+    /// # });
+    /// ```
+    pub fn wait(&self) -> Wait<'_> {
+        Wait {
+            barrier: self,
+            arrival: None,
+        }
+    }
+
+    /// Blocks the current thread until `n` tasks are waiting. See
+    /// [`Barrier::wait`].
+    pub fn wait_blocking(&self) -> BarrierWaitResult {
+        let Some(mut state) = self.arrive(self.lock()) else {
+            return BarrierWaitResult { leader: true };
+        };
+
+        let generation = state.generation;
+
+        while state.generation == generation {
+            state = self
+                .condvar
+                .wait(state)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+
+        BarrierWaitResult { leader: false }
+    }
+
+    /// Returns the number of tasks the barrier waits for.
+    pub fn n(&self) -> usize {
+        self.n
+    }
+
+    /// Returns the number of tasks waiting right now.
+    pub fn waiting(&self) -> usize {
+        self.lock().arrived
+    }
+}
+
+impl fmt::Debug for Barrier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.lock();
+
+        f.debug_struct("Barrier")
+            .field("n", &self.n)
+            .field("waiting", &state.arrived)
+            .field("generation", &state.generation)
+            .finish()
+    }
+}
+
+impl BarrierWaitResult {
+    /// Returns whether this task released the barrier. Exactly one task per
+    /// generation is the leader.
+    pub fn is_leader(&self) -> bool {
+        self.leader
+    }
+}
+
+impl Future for Wait<'_> {
+    type Output = BarrierWaitResult;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let mut state = this.barrier.lock();
+
+        let Some((generation, id)) = this.arrival else {
+            let Some(mut state) = this.barrier.arrive(state) else {
+                return Poll::Ready(BarrierWaitResult { leader: true });
+            };
+
+            let id = state.next_id;
+            state.next_id += 1;
+            state.wakers.insert(id, cx.waker().clone());
+            this.arrival = Some((state.generation, id));
+
+            return Poll::Pending;
+        };
+
+        if state.generation != generation {
+            this.arrival = None;
+            return Poll::Ready(BarrierWaitResult { leader: false });
+        }
+
+        if let Some(waker) = state.wakers.get_mut(&id) {
+            if !waker.will_wake(cx.waker()) {
+                *waker = cx.waker().clone();
+            }
+        }
+
+        Poll::Pending
+    }
+}
+
+/// Withdraws the arrival if the barrier hasn't been released yet.
+impl Drop for Wait<'_> {
+    fn drop(&mut self) {
+        let Some((generation, id)) = self.arrival else {
+            return;
+        };
+
+        let mut state = self.barrier.lock();
+
+        if state.generation == generation {
+            state.arrived -= 1;
+            state.wakers.remove(&id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::sync::Arc;
+    use core::pin::pin;
+    use std::thread;
+
+    #[test]
+    fn test_wait() {
+        let barrier = Barrier::new(2);
+        let mut cx = Context::from_waker(Waker::noop());
+
+        for _generation in 0..3 {
+            let mut first = pin!(barrier.wait());
+            assert!(first.as_mut().poll(&mut cx).is_pending());
+            assert!(first.as_mut().poll(&mut cx).is_pending());
+            assert_eq!(barrier.waiting(), 1);
+
+            let mut second = pin!(barrier.wait());
+            assert_eq!(
+                second.as_mut().poll(&mut cx),
+                Poll::Ready(BarrierWaitResult { leader: true })
+            );
+            assert_eq!(
+                first.as_mut().poll(&mut cx),
+                Poll::Ready(BarrierWaitResult { leader: false })
+            );
+            assert_eq!(barrier.waiting(), 0);
+        }
+    }
+
+    #[test]
+    fn test_withdraw() {
+        let barrier = Barrier::new(2);
+        let mut cx = Context::from_waker(Waker::noop());
+
+        {
+            let mut cancelled = pin!(barrier.wait());
+            assert!(cancelled.as_mut().poll(&mut cx).is_pending());
+        }
+
+        assert_eq!(barrier.waiting(), 0);
+
+        let mut first = pin!(barrier.wait());
+        assert!(first.as_mut().poll(&mut cx).is_pending());
+    }
+
+    #[test]
+    fn test_zero() {
+        let barrier = Barrier::new(0);
+
+        assert_eq!(barrier.n(), 1);
+        assert!(barrier.wait_blocking().is_leader());
+    }
+
+    #[test]
+    fn test_wait_blocking() {
+        let barrier = Arc::new(Barrier::new(4));
+
+        let workers: Vec<_> = (0..4)
+            .map(|_| {
+                let barrier = Arc::clone(&barrier);
+
+                thread::spawn(move || {
+                    (0..10)
+                        .filter(|_| barrier.wait_blocking().is_leader())
+                        .count()
+                })
+            })
+            .collect();
+
+        let leaders: usize = workers
+            .into_iter()
+            .map(|worker| worker.join().unwrap())
+            .sum();
+
+        assert_eq!(leaders, 10);
+    }
+
+    #[tokio::test]
+    async fn test_mixed() {
+        let barrier = Arc::new(Barrier::new(2));
+
+        let thread = thread::spawn({
+            let barrier = Arc::clone(&barrier);
+            move || barrier.wait_blocking().is_leader()
+        });
+
+        let leader = barrier.wait().await.is_leader();
+
+        assert_ne!(leader, thread.join().unwrap());
+    }
+}
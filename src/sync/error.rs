@@ -0,0 +1,60 @@
+//! Errors of the synchronization primitives.
+
+#[cfg(doc)]
+use super::Semaphore;
+use core::{error::Error, fmt};
+
+/// Error returned by [`Semaphore::acquire`] if the semaphore has been closed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AcquireError;
+
+impl fmt::Display for AcquireError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "acquiring from a closed semaphore")
+    }
+}
+
+impl Error for AcquireError {}
+
+/// Error returned by [`Semaphore::try_acquire`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TryAcquireError {
+    /// Not enough permits are available right now (or others are waiting for
+    /// them already).
+    NoPermits,
+    /// The semaphore has been closed.
+    Closed,
+}
+
+impl fmt::Display for TryAcquireError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TryAcquireError::NoPermits => write!(f, "not enough permits available"),
+            TryAcquireError::Closed => write!(f, "acquiring from a closed semaphore"),
+        }
+    }
+}
+
+impl Error for TryAcquireError {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::string::ToString;
+
+    #[test]
+    fn test_display() {
+        assert_eq!(
+            AcquireError.to_string(),
+            "acquiring from a closed semaphore"
+        );
+        assert_eq!(
+            TryAcquireError::NoPermits.to_string(),
+            "not enough permits available"
+        );
+        assert_eq!(
+            TryAcquireError::Closed.to_string(),
+            "acquiring from a closed semaphore"
+        );
+    }
+}
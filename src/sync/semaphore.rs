@@ -0,0 +1,525 @@
+//! Semaphore capping the number of concurrent operations.
+
+use super::{AcquireError, TryAcquireError};
+use alloc::{collections::BTreeMap, vec::Vec};
+use core::{
+    fmt,
+    future::Future,
+    mem,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+use std::sync::{Condvar, Mutex, MutexGuard, PoisonError};
+
+/// Task or thread waiting for permits
+struct Waiter {
+    /// Number of permits it waits for
+    needed: usize,
+    /// Waker of the task, [`None`] for threads waiting on the condition
+    /// variable
+    waker: Option<Waker>,
+}
+
+/// State of a [`Semaphore`], guarded by [`Semaphore::state`]
+struct State {
+    /// Number of permits that haven't been handed out
+    permits: usize,
+    /// Whether the semaphore has been closed
+    closed: bool,
+    /// Waiters in the order they started waiting, keyed by id
+    waiters: BTreeMap<u64, Waiter>,
+    /// Id of the next waiter
+    next_id: u64,
+}
+
+impl State {
+    /// Takes `needed` permits for the waiter with `id` (or a new one, if
+    /// [`None`]) if they are available and nobody waited before it.
+    fn take(&mut self, id: Option<u64>, needed: usize) -> bool {
+        let first = match self.waiters.first_key_value() {
+            Some((first, _)) => Some(*first) == id,
+            None => true,
+        };
+
+        if !first || self.permits < needed {
+            return false;
+        }
+
+        self.permits -= needed;
+
+        if let Some(id) = id {
+            self.waiters.remove(&id);
+        }
+
+        true
+    }
+
+    /// Adds a waiter for `needed` permits and returns its id.
+    fn enqueue(&mut self, needed: usize, waker: Option<Waker>) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.waiters.insert(id, Waiter { needed, waker });
+
+        id
+    }
+}
+
+/// Semaphore handing out a limited number of permits
+///
+/// Acquiring waits until enough permits are available. Waiters are served in
+/// the order they started waiting, so a waiter for many permits isn't starved
+/// by waiters for few ones (but holds up the waiters behind it in turn).
+///
+/// See the [module documentation](super) for details.
+pub struct Semaphore {
+    /// Permits and waiters
+    state: Mutex<State>,
+    /// Wakes up threads blocking in [`Semaphore::acquire_blocking`]
+    condvar: Condvar,
+}
+
+/// Permits acquired from a [`Semaphore`], returned when dropped
+#[derive(Debug)]
+#[must_use = "permits are returned right away if they're not used"]
+pub struct Permit<'a> {
+    /// Semaphore to return the permits to
+    semaphore: &'a Semaphore,
+    /// Number of permits
+    count: usize,
+}
+
+/// Future returned by [`Semaphore::acquire`]
+///
+/// Resolves to the permits once they are available. Dropping it before gives
+/// up the place in the queue of waiters.
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Acquire<'a> {
+    /// Semaphore to acquire the permits from
+    semaphore: &'a Semaphore,
+    /// Number of permits to acquire
+    needed: usize,
+    /// Id while waiting in the queue
+    id: Option<u64>,
+}
+
+impl Semaphore {
+    /// Creates a semaphore with `permits` available permits. As it's a const
+    /// function, the semaphore can be placed in a `static`.
+    pub const fn new(permits: usize) -> Self {
+        Semaphore {
+            state: Mutex::new(State {
+                permits,
+                closed: false,
+                waiters: BTreeMap::new(),
+                next_id: 0,
+            }),
+            condvar: Condvar::new(),
+        }
+    }
+
+    /// Locks the state.
+    fn lock(&self) -> MutexGuard<'_, State> {
+        crate::lock(&self.state)
+    }
+
+    /// Wakes up the first waiter if there are enough permits for it.
+    fn wake_next(&self, state: MutexGuard<'_, State>) {
+        let waker = match state.waiters.first_key_value() {
+            Some((_, waiter)) if waiter.needed <= state.permits => waiter.waker.clone(),
+            _ => return,
+        };
+
+        drop(state);
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+
+        self.condvar.notify_all();
+    }
+
+    /// Acquires `n` permits if they are available and nobody is waiting for
+    /// permits already. This function is **non-blocking**.
+    ///
+    /// Returns [`TryAcquireError::NoPermits`] if not or
+    /// [`TryAcquireError::Closed`] if the semaphore has been closed.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use laika::sync::{Semaphore, TryAcquireError};
+    ///
+    /// let semaphore = Semaphore::new(3);
+    ///
+    /// let permit = semaphore.try_acquire(2).unwrap();
+    /// assert_eq!(semaphore.try_acquire(2).unwrap_err(), TryAcquireError::NoPermits);
+    ///
+    /// drop(permit);
+    /// assert_eq!(semaphore.try_acquire(2).unwrap().count(), 2);
+    /// ```
+    pub fn try_acquire(&self, n: usize) -> Result<Permit<'_>, TryAcquireError> {
+        let mut state = self.lock();
+
+        if state.closed {
+            return Err(TryAcquireError::Closed);
+        }
+
+        if !state.take(None, n) {
+            return Err(TryAcquireError::NoPermits);
+        }
+
+        Ok(Permit {
+            semaphore: self,
+            count: n,
+        })
+    }
+
+    /// Acquires `n` permits, waiting asynchronously until they are available.
+    ///
+    /// Resolves to [`AcquireError`] if the semaphore has been closed (also
+    /// while waiting).
+    ///
+    /// # Examples
+    /// ```rust
+    /// # tokio::runtime::Builder::new_current_thread()
+    /// #     .build()
+    /// #     .unwrap()
+    /// #     .block_on(async {
+    /// use laika::sync::Semaphore;
+    /// use std::sync::Arc;
+    ///
+    /// let uploads = Arc::new(Semaphore::new(2));
+    ///
+    /// let tasks: Vec<_> = (0..5)
+    ///     .map(|id| {
+    ///         let uploads = Arc::clone(&uploads);
+    ///
+    ///         tokio::spawn(async move {
+    ///             let _permit = uploads.acquire(1).await.unwrap();
+    ///             // At most 2 uploads run at once
+    ///             tokio::task::yield_now().await;
+    ///             id
+    ///         })
+    ///     })
+    ///     .collect();
+    ///
+    /// for task in tasks {
+    ///     task.await.unwrap();
+    /// }
+    ///
+    /// assert_eq!(uploads.available_permits(), 2);
+    /// # });
+    /// ```
+    pub fn acquire(&self, n: usize) -> Acquire<'_> {
+        Acquire {
+            semaphore: self,
+            needed: n,
+            id: None,
+        }
+    }
+
+    /// Acquires `n` permits, blocking the current thread until they are
+    /// available. See [`Semaphore::acquire`].
+    pub fn acquire_blocking(&self, n: usize) -> Result<Permit<'_>, AcquireError> {
+        let mut state = self.lock();
+        let mut id = None;
+
+        loop {
+            if state.closed {
+                if let Some(id) = id {
+                    state.waiters.remove(&id);
+                }
+
+                return Err(AcquireError);
+            }
+
+            if state.take(id, n) {
+                self.wake_next(state);
+
+                return Ok(Permit {
+                    semaphore: self,
+                    count: n,
+                });
+            }
+
+            if id.is_none() {
+                id = Some(state.enqueue(n, None));
+            }
+
+            state = self
+                .condvar
+                .wait(state)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+    }
+
+    /// Adds `n` permits, e.g. to raise the limit at runtime.
+    ///
+    /// # Panics
+    /// Panics if the number of available permits overflows.
+    pub fn add_permits(&self, n: usize) {
+        let mut state = self.lock();
+        state.permits = state
+            .permits
+            .checked_add(n)
+            .expect("too many permits in the semaphore");
+
+        self.wake_next(state);
+    }
+
+    /// Returns the number of permits that are available right now.
+    pub fn available_permits(&self) -> usize {
+        self.lock().permits
+    }
+
+    /// Closes the semaphore: waiting and later acquisitions fail with
+    /// [`AcquireError`]. Permits that have been acquired before stay valid.
+    pub fn close(&self) {
+        let mut state = self.lock();
+        state.closed = true;
+
+        let wakers: Vec<_> = mem::take(&mut state.waiters)
+            .into_values()
+            .filter_map(|waiter| waiter.waker)
+            .collect();
+
+        drop(state);
+
+        for waker in wakers {
+            waker.wake();
+        }
+
+        self.condvar.notify_all();
+    }
+
+    /// Returns whether the semaphore has been closed.
+    pub fn is_closed(&self) -> bool {
+        self.lock().closed
+    }
+}
+
+impl fmt::Debug for Semaphore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.lock();
+
+        f.debug_struct("Semaphore")
+            .field("permits", &state.permits)
+            .field("waiters", &state.waiters.len())
+            .field("closed", &state.closed)
+            .finish()
+    }
+}
+
+impl Permit<'_> {
+    /// Returns the number of permits.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Drops the permits without returning them to the semaphore, lowering
+    /// its limit permanently.
+    pub fn forget(mut self) {
+        self.count = 0;
+    }
+}
+
+/// Returns the permits to the semaphore, waking up the next waiter.
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        if self.count == 0 {
+            return;
+        }
+
+        let mut state = self.semaphore.lock();
+        state.permits += self.count;
+
+        self.semaphore.wake_next(state);
+    }
+}
+
+impl<'a> Future for Acquire<'a> {
+    type Output = Result<Permit<'a>, AcquireError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let semaphore = this.semaphore;
+        let mut state = semaphore.lock();
+
+        if state.closed {
+            this.id = None;
+            return Poll::Ready(Err(AcquireError));
+        }
+
+        if state.take(this.id, this.needed) {
+            this.id = None;
+            semaphore.wake_next(state);
+
+            return Poll::Ready(Ok(Permit {
+                semaphore,
+                count: this.needed,
+            }));
+        }
+
+        match this.id.and_then(|id| state.waiters.get_mut(&id)) {
+            Some(waiter) => {
+                if !waiter
+                    .waker
+                    .as_ref()
+                    .is_some_and(|w| w.will_wake(cx.waker()))
+                {
+                    waiter.waker = Some(cx.waker().clone());
+                }
+            }
+            None => this.id = Some(state.enqueue(this.needed, Some(cx.waker().clone()))),
+        }
+
+        Poll::Pending
+    }
+}
+
+/// Gives up the place in the queue, waking up the next waiter if it can
+/// proceed now.
+impl Drop for Acquire<'_> {
+    fn drop(&mut self) {
+        let Some(id) = self.id else {
+            return;
+        };
+
+        let mut state = self.semaphore.lock();
+        state.waiters.remove(&id);
+
+        self.semaphore.wake_next(state);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::sync::Arc;
+    use core::pin::pin;
+    use std::{thread, time::Duration};
+
+    #[test]
+    fn test_fifo() {
+        let semaphore = Semaphore::new(2);
+        let mut cx = Context::from_waker(Waker::noop());
+
+        let first = semaphore.try_acquire(1).unwrap();
+
+        let mut large = pin!(semaphore.acquire(2));
+        assert!(large.as_mut().poll(&mut cx).is_pending());
+
+        // A permit is available, but the large waiter came first
+        assert_eq!(
+            semaphore.try_acquire(1).unwrap_err(),
+            TryAcquireError::NoPermits
+        );
+        let mut small = pin!(semaphore.acquire(1));
+        assert!(small.as_mut().poll(&mut cx).is_pending());
+
+        drop(first);
+
+        let Poll::Ready(Ok(permit)) = large.as_mut().poll(&mut cx) else {
+            panic!("the large waiter should have been served");
+        };
+
+        assert_eq!(permit.count(), 2);
+        assert!(small.as_mut().poll(&mut cx).is_pending());
+
+        drop(permit);
+        assert!(small.as_mut().poll(&mut cx).is_ready());
+    }
+
+    #[test]
+    fn test_cancel() {
+        let semaphore = Semaphore::new(1);
+        let mut cx = Context::from_waker(Waker::noop());
+
+        let permit = semaphore.try_acquire(1).unwrap();
+
+        {
+            let mut acquire = pin!(semaphore.acquire(1));
+            assert!(acquire.as_mut().poll(&mut cx).is_pending());
+        }
+
+        // The dropped waiter doesn't hold up others
+        drop(permit);
+        assert!(semaphore.try_acquire(1).is_ok());
+
+        semaphore.try_acquire(1).unwrap().forget();
+        assert_eq!(semaphore.available_permits(), 0);
+
+        semaphore.add_permits(3);
+        assert_eq!(semaphore.available_permits(), 3);
+    }
+
+    #[test]
+    fn test_close() {
+        let semaphore = Arc::new(Semaphore::new(0));
+
+        let waiter = thread::spawn({
+            let semaphore = Arc::clone(&semaphore);
+            move || semaphore.acquire_blocking(1).map(|permit| permit.count())
+        });
+
+        thread::sleep(Duration::from_millis(20));
+        semaphore.close();
+
+        assert_eq!(waiter.join().unwrap(), Err(AcquireError));
+        assert!(semaphore.is_closed());
+        assert_eq!(
+            semaphore.try_acquire(0).unwrap_err(),
+            TryAcquireError::Closed
+        );
+    }
+
+    #[test]
+    fn test_acquire_blocking() {
+        let semaphore = Arc::new(Semaphore::new(2));
+        let running = Arc::new(Mutex::new((0, 0)));
+
+        let workers: Vec<_> = (0..6)
+            .map(|_| {
+                let semaphore = Arc::clone(&semaphore);
+                let running = Arc::clone(&running);
+
+                thread::spawn(move || {
+                    let _permit = semaphore.acquire_blocking(1).unwrap();
+
+                    {
+                        let mut running = running.lock().unwrap();
+                        running.0 += 1;
+                        running.1 = running.1.max(running.0);
+                    }
+
+                    thread::sleep(Duration::from_millis(5));
+                    running.lock().unwrap().0 -= 1;
+                })
+            })
+            .collect();
+
+        for worker in workers {
+            worker.join().unwrap();
+        }
+
+        // Never more than 2 at once
+        assert_eq!(running.lock().unwrap().1, 2);
+        assert_eq!(semaphore.available_permits(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_acquire() {
+        let semaphore = Arc::new(Semaphore::new(1));
+        let permit = semaphore.acquire(1).await.unwrap();
+
+        let waiter = tokio::spawn({
+            let semaphore = Arc::clone(&semaphore);
+            async move { semaphore.acquire(1).await.map(|permit| permit.count()) }
+        });
+
+        tokio::task::yield_now().await;
+        drop(permit);
+
+        assert_eq!(waiter.await.unwrap(), Ok(1));
+    }
+}
@@ -0,0 +1,502 @@
+#![forbid(unsafe_code)]
+//! # Duplicating messages into multiple channels
+//!
+//! A [`Tee`] sends a clone of every message to each of its branches, so one
+//! producer can feed e.g. logging, metrics and processing at once. Branches
+//! are the senders of [`mpsc`](crate::mpsc) channels:
+//!
+//! - [`Tee::branch`]: A bounded channel, with a [`Backpressure`] strategy for
+//!   when it's full. Waiting slows the tee (and the branches after it) down
+//!   to the pace of the branch, while skipping or detaching lets less
+//!   important branches fall behind without holding up the others.
+//! - [`Tee::unbounded_branch`]: An unbounded channel, which is never full.
+//!
+//! Messages are sent to the branches in the order they've been added. Once
+//! the receiver of a branch has been dropped (or a branch is detached), the
+//! branch is removed.
+//!
+//! [`Tee::run`] (or [`Tee::run_blocking`]) takes an [`mpsc`](crate::mpsc)
+//! receiver as input and duplicates its messages until all of its senders
+//! have been dropped. Single messages can be sent with [`Tee::send`].
+//!
+//! Messages have to be `Clone`. To share messages that are expensive to clone
+//! instead, wrap them in an `Arc` (so a `Tee<Arc<T>>` is used).
+//!
+//! # Examples
+//! ```rust
+//! use laika::{
+//!     mpsc,
+//!     tee::{Backpressure, Tee},
+//! };
+//!
+//! let (tx, rx) = mpsc::unbounded();
+//! let (processing_tx, mut processing) = mpsc::unbounded();
+//! let (metrics_tx, mut metrics) = mpsc::bounded(1);
+//!
+//! let tee = Tee::new()
+//!     .unbounded_branch(processing_tx)
+//!     .branch(metrics_tx, Backpressure::Skip);
+//!
+//! tx.send("first").unwrap();
+//! tx.send("second").unwrap();
+//! drop(tx);
+//!
+//! tee.run_blocking(rx);
+//!
+//! assert_eq!(processing.try_recv(), Ok("first"));
+//! assert_eq!(processing.try_recv(), Ok("second"));
+//!
+//! // The metrics branch was full, so it skipped the second message
+//! assert_eq!(metrics.try_recv(), Ok("first"));
+//! assert_eq!(metrics.try_recv(), Err(mpsc::TryRecvError::Disconnected));
+//! ```
+
+use crate::mpsc::{Receiver, SendError, Sender, TrySendError, UnboundedReceiver, UnboundedSender};
+use alloc::vec::Vec;
+use core::{
+    fmt,
+    task::{Context, Poll},
+};
+
+/// What a [`Tee`] does with a message for a bounded branch that is full
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Backpressure {
+    /// Waits until the branch has room for the message.
+    #[default]
+    Wait,
+    /// Doesn't send the message to the branch.
+    Skip,
+    /// Removes the branch, dropping its sender. Its receiver gets the queued
+    /// messages and `None` afterwards.
+    Detach,
+}
+
+/// Outcome of sending a message to a branch without waiting
+enum Delivery<T> {
+    /// The branch got the message
+    Delivered,
+    /// The branch is full and skips the message
+    Skipped,
+    /// The branch has been closed or detached and is removed
+    Closed,
+    /// The branch is full and waits for room, returning the message
+    Full(T),
+}
+
+/// Branch of a [`Tee`]
+enum Branch<T> {
+    /// Bounded channel, handling a full queue by its strategy
+    Bounded {
+        /// Sender of the channel
+        sender: Sender<T>,
+        /// Strategy for when the channel is full
+        backpressure: Backpressure,
+    },
+    /// Unbounded channel
+    Unbounded(UnboundedSender<T>),
+}
+
+impl<T> Branch<T> {
+    /// Sends a message to the branch if it doesn't have to wait for room.
+    fn try_send(&self, value: T) -> Delivery<T> {
+        let (sender, backpressure) = match self {
+            Branch::Unbounded(sender) => {
+                return match sender.send(value) {
+                    Ok(()) => Delivery::Delivered,
+                    Err(_) => Delivery::Closed,
+                };
+            }
+            Branch::Bounded {
+                sender,
+                backpressure,
+            } => (sender, backpressure),
+        };
+
+        match (sender.try_send(value), backpressure) {
+            (Ok(()), _) => Delivery::Delivered,
+            (Err(TrySendError::Full(value)), Backpressure::Wait) => Delivery::Full(value),
+            (Err(TrySendError::Full(_)), Backpressure::Skip) => Delivery::Skipped,
+            (Err(TrySendError::Full(_)), Backpressure::Detach) => Delivery::Closed,
+            (Err(TrySendError::Disconnected(_)), _) => Delivery::Closed,
+        }
+    }
+
+    /// Returns the sender of a bounded branch, which a [`Delivery::Full`]
+    /// message waits for.
+    fn bounded(&self) -> &Sender<T> {
+        match self {
+            Branch::Bounded { sender, .. } => sender,
+            Branch::Unbounded(_) => unreachable!("unbounded branches are never full"),
+        }
+    }
+}
+
+/// Input of [`Tee::run`] and [`Tee::run_blocking`]
+///
+/// Implemented for the receivers of mpsc channels.
+pub trait Input<T> {
+    /// Polls for the next message, resolving to [`None`] once the input has
+    /// been closed.
+    fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<T>>;
+
+    /// Receives the next message, blocking the current thread until there is
+    /// one. Returns [`None`] once the input has been closed.
+    fn recv_blocking(&mut self) -> Option<T>;
+}
+
+impl<T> Input<T> for Receiver<T> {
+    fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        Receiver::poll_recv(self, cx)
+    }
+
+    fn recv_blocking(&mut self) -> Option<T> {
+        Receiver::recv_blocking(self)
+    }
+}
+
+impl<T> Input<T> for UnboundedReceiver<T> {
+    fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        UnboundedReceiver::poll_recv(self, cx)
+    }
+
+    fn recv_blocking(&mut self) -> Option<T> {
+        UnboundedReceiver::recv_blocking(self)
+    }
+}
+
+/// Duplicates messages into multiple branches
+///
+/// See the [module documentation](self) for details.
+pub struct Tee<T> {
+    /// Branches that haven't been closed or detached, in the order they've
+    /// been added
+    branches: Vec<Branch<T>>,
+}
+
+impl<T> Tee<T>
+where
+    T: Clone,
+{
+    /// Creates a tee without any branches.
+    pub fn new() -> Self {
+        Tee {
+            branches: Vec::new(),
+        }
+    }
+
+    /// Adds a bounded branch, handling a full channel by `backpressure`.
+    pub fn branch(mut self, sender: Sender<T>, backpressure: Backpressure) -> Self {
+        self.branches.push(Branch::Bounded {
+            sender,
+            backpressure,
+        });
+
+        self
+    }
+
+    /// Adds an unbounded branch.
+    pub fn unbounded_branch(mut self, sender: UnboundedSender<T>) -> Self {
+        self.branches.push(Branch::Unbounded(sender));
+        self
+    }
+
+    /// Returns the number of branches that haven't been closed or detached.
+    pub fn len(&self) -> usize {
+        self.branches.len()
+    }
+
+    /// Returns whether all branches have been closed or detached (or none
+    /// have been added).
+    pub fn is_empty(&self) -> bool {
+        self.branches.is_empty()
+    }
+
+    /// Sends a clone of `value` to every branch, waiting asynchronously for
+    /// full branches with [`Backpressure::Wait`]. Closed and detached branches are removed.
+    ///
+    /// Returns the number of branches that got the message.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # tokio::runtime::Builder::new_current_thread()
+    /// #     .build()
+    /// #     .unwrap()
+    /// #     .block_on(async {
+    /// use laika::{
+    ///     mpsc,
+    ///     tee::{Backpressure, Tee},
+    /// };
+    ///
+    /// let (log_tx, _log) = mpsc::bounded(1);
+    /// let (work_tx, mut work) = mpsc::bounded(1);
+    ///
+    /// let mut tee = Tee::new()
+    ///     .branch(log_tx, Backpressure::Detach)
+    ///     .branch(work_tx, Backpressure::Wait);
+    ///
+    /// assert_eq!(tee.send(1).await, 2);
+    ///
+    /// let consumer = tokio::spawn(async move { (work.recv().await, work.recv().await) });
+    ///
+    /// // The full log branch is detached, the work branch is waited for
+    /// assert_eq!(tee.send(2).await, 1);
+    /// assert_eq!(tee.len(), 1);
+    ///
+    /// drop(tee);
+    /// assert_eq!(consumer.await.unwrap(), (Some(1), Some(2)));
+    /// # });
+    /// ```
+    pub async fn send(&mut self, value: T) -> usize {
+        let mut delivered = 0;
+        let mut index = 0;
+
+        while index < self.branches.len() {
+            let branch = &self.branches[index];
+
+            let delivery = match branch.try_send(value.clone()) {
+                Delivery::Full(value) => match branch.bounded().send(value).await {
+                    Ok(()) => Delivery::Delivered,
+                    // The channel has its own overflow policy
+                    Err(SendError::Full(_)) => Delivery::Skipped,
+                    Err(SendError::Disconnected(_)) => Delivery::Closed,
+                },
+                delivery => delivery,
+            };
+
+            self.settle(&mut index, &mut delivered, delivery);
+        }
+
+        delivered
+    }
+
+    /// Sends a clone of `value` to every branch, blocking the current thread
+    /// for full branches with [`Backpressure::Wait`]. See [`Tee::send`].
+    pub fn send_blocking(&mut self, value: T) -> usize {
+        let mut delivered = 0;
+        let mut index = 0;
+
+        while index < self.branches.len() {
+            let branch = &self.branches[index];
+
+            let delivery = match branch.try_send(value.clone()) {
+                Delivery::Full(value) => match branch.bounded().send_blocking(value) {
+                    Ok(()) => Delivery::Delivered,
+                    // The channel has its own overflow policy
+                    Err(SendError::Full(_)) => Delivery::Skipped,
+                    Err(SendError::Disconnected(_)) => Delivery::Closed,
+                },
+                delivery => delivery,
+            };
+
+            self.settle(&mut index, &mut delivered, delivery);
+        }
+
+        delivered
+    }
+
+    /// Duplicates the messages of `input` asynchronously until all of its
+    /// senders have been dropped (or all branches have been closed).
+    ///
+    /// # Examples
+    /// ```rust
+    /// # tokio::runtime::Builder::new_current_thread()
+    /// #     .build()
+    /// #     .unwrap()
+    /// #     .block_on(async {
+    /// use laika::{mpsc, tee::Tee};
+    ///
+    /// let (tx, rx) = mpsc::bounded(4);
+    /// let (log_tx, mut log) = mpsc::unbounded();
+    /// let (work_tx, mut work) = mpsc::unbounded();
+    ///
+    /// let tee = Tee::new().unbounded_branch(log_tx).unbounded_branch(work_tx);
+    /// let splitter = tokio::spawn(tee.run(rx));
+    ///
+    /// tx.send("job").await.unwrap();
+    /// drop(tx);
+    /// splitter.await.unwrap();
+    ///
+    /// assert_eq!(log.recv().await, Some("job"));
+    /// assert_eq!(work.recv().await, Some("job"));
+    /// # });
+    /// ```
+    pub async fn run<I>(mut self, mut input: I)
+    where
+        I: Input<T>,
+    {
+        while !self.is_empty() {
+            match core::future::poll_fn(|cx| input.poll_recv(cx)).await {
+                Some(value) => self.send(value).await,
+                None => return,
+            };
+        }
+    }
+
+    /// Duplicates the messages of `input`, blocking the current thread until
+    /// all of its senders have been dropped. See [`Tee::run`].
+    pub fn run_blocking<I>(mut self, mut input: I)
+    where
+        I: Input<T>,
+    {
+        while !self.is_empty() {
+            match input.recv_blocking() {
+                Some(value) => self.send_blocking(value),
+                None => return,
+            };
+        }
+    }
+
+    /// Handles the delivery to the branch at `index`, removing the branch if
+    /// it has been closed or moving on to the next one.
+    fn settle(&mut self, index: &mut usize, delivered: &mut usize, delivery: Delivery<T>) {
+        match delivery {
+            Delivery::Delivered => {
+                *delivered += 1;
+                *index += 1;
+            }
+            // A full branch has been waited for already
+            Delivery::Skipped | Delivery::Full(_) => *index += 1,
+            Delivery::Closed => {
+                self.branches.remove(*index);
+            }
+        }
+    }
+}
+
+impl<T> Default for Tee<T>
+where
+    T: Clone,
+{
+    fn default() -> Self {
+        Tee::new()
+    }
+}
+
+impl<T> fmt::Debug for Tee<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Tee")
+            .field("branches", &self.branches.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mpsc::{self, TryRecvError};
+    use std::{thread, vec::Vec};
+
+    #[test]
+    fn test_backpressure() {
+        let (skip_tx, mut skip) = mpsc::bounded(1);
+        let (detach_tx, mut detach) = mpsc::bounded(1);
+        let (unbounded_tx, mut unbounded) = mpsc::unbounded();
+
+        let mut tee = Tee::new()
+            .branch(skip_tx, Backpressure::Skip)
+            .branch(detach_tx, Backpressure::Detach)
+            .unbounded_branch(unbounded_tx);
+
+        assert_eq!(tee.send_blocking(1), 3);
+        assert_eq!(tee.send_blocking(2), 1);
+        assert_eq!(tee.len(), 2);
+
+        assert_eq!(skip.try_recv(), Ok(1));
+        assert_eq!(tee.send_blocking(3), 2);
+
+        // Skipped messages are lost, detached branches are closed
+        assert_eq!(skip.try_recv(), Ok(3));
+        assert_eq!(detach.try_recv(), Ok(1));
+        assert_eq!(detach.try_recv(), Err(TryRecvError::Disconnected));
+        assert_eq!(unbounded.try_recv(), Ok(1));
+        assert_eq!(unbounded.try_recv(), Ok(2));
+        assert_eq!(unbounded.try_recv(), Ok(3));
+    }
+
+    #[test]
+    fn test_closed_branches() {
+        let (bounded_tx, bounded) = mpsc::bounded(1);
+        let (unbounded_tx, unbounded) = mpsc::unbounded();
+
+        let mut tee = Tee::new()
+            .branch(bounded_tx, Backpressure::Wait)
+            .unbounded_branch(unbounded_tx);
+
+        drop(bounded);
+        assert_eq!(tee.send_blocking(1), 1);
+
+        drop(unbounded);
+        assert_eq!(tee.send_blocking(2), 0);
+        assert!(tee.is_empty());
+
+        // Without branches, running stops right away
+        let (tx, rx) = mpsc::unbounded();
+        tx.send(3).unwrap();
+        tee.run_blocking(rx);
+    }
+
+    #[test]
+    fn test_run_blocking() {
+        let (tx, rx) = mpsc::bounded(1);
+        let (branches, receivers): (Vec<_>, Vec<_>) = (0..3).map(|_| mpsc::bounded(1)).unzip();
+
+        let tee = branches.into_iter().fold(Tee::new(), |tee, branch| {
+            tee.branch(branch, Backpressure::Wait)
+        });
+
+        let splitter = thread::spawn(move || tee.run_blocking(rx));
+
+        let consumers = receivers
+            .into_iter()
+            .map(|mut receiver| {
+                thread::spawn(move || {
+                    let mut values = Vec::new();
+
+                    while let Some(value) = receiver.recv_blocking() {
+                        values.push(value);
+                    }
+
+                    values
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for value in 0..100 {
+            tx.send_blocking(value).unwrap();
+        }
+
+        drop(tx);
+        splitter.join().unwrap();
+
+        // Waiting branches get every message in order
+        for consumer in consumers {
+            assert_eq!(consumer.join().unwrap(), (0..100).collect::<Vec<_>>());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run() {
+        let (tx, rx) = mpsc::unbounded();
+        let (branch_tx, mut branch) = mpsc::bounded(2);
+
+        let tee = Tee::new().branch(branch_tx, Backpressure::Wait);
+        let splitter = tokio::spawn(tee.run(rx));
+
+        for value in 0..10 {
+            tx.send(value).unwrap();
+        }
+
+        let mut sum = 0;
+
+        for _ in 0..10 {
+            sum += branch.recv().await.unwrap();
+        }
+
+        // Running stops once the only branch has been closed
+        drop(branch);
+        tx.send(10).unwrap();
+        splitter.await.unwrap();
+
+        assert_eq!(sum, 45);
+    }
+}
@@ -0,0 +1,375 @@
+#![forbid(unsafe_code)]
+//! # Limiting the rate of values received from a channel
+//!
+//! [`Throttle`] wraps a receiver and yields its values no faster than one per
+//! interval, optionally allowing short bursts. Unlike
+//! [`sampling`](crate::sampling), no value is lost: while the rate is
+//! exceeded, the throttle simply stops receiving. The values pile up in the
+//! wrapped channel instead, so a bounded channel buffers up to its capacity
+//! and then suspends the producers, while an unbounded one buffers all of
+//! them.
+//!
+//! Any [`Source`] can be wrapped, i.e. the receivers of the channel modules
+//! (see [`merge`](crate::merge) for the list). A throttle is a [`Source`]
+//! itself. Values are received with [`Throttle::next`] or blocking with
+//! [`Throttle::next_blocking`].
+//!
+//! The rate of throttles received asynchronously is tracked by a single
+//! background thread, so they work with any async runtime. The throttle
+//! doesn't contain any unsafe code.
+//!
+//! ## Optional features
+//!
+//! - `futures`: Implements `futures_core::Stream` for [`Throttle`].
+//!
+//! # Examples
+#![cfg_attr(feature = "mpsc", doc = "```rust")]
+#![cfg_attr(not(feature = "mpsc"), doc = "```ignore")]
+//! use laika::{mpsc, throttle::Throttle};
+//! use std::time::{Duration, Instant};
+//!
+//! let (tx, rx) = mpsc::bounded(4);
+//! let mut throttle = Throttle::new(rx, Duration::from_millis(10));
+//!
+//! for request in 0..4 {
+//!     tx.send_blocking(request).unwrap();
+//! }
+//!
+//! drop(tx);
+//!
+//! let start = Instant::now();
+//! let mut forwarded = 0;
+//!
+//! while let Some(_) = throttle.next_blocking() {
+//!     forwarded += 1;
+//! }
+//!
+//! // Nothing is lost, but the last request waited for 3 intervals
+//! assert_eq!(forwarded, 4);
+//! assert!(start.elapsed() >= Duration::from_millis(30));
+//! ```
+
+use crate::{
+    merge::Source,
+    park,
+    timer::{self, alarm::Alarm},
+};
+use alloc::{boxed::Box, sync::Arc};
+#[cfg(feature = "futures")]
+use core::pin::Pin;
+use core::{
+    fmt,
+    task::{Context, Poll},
+};
+use std::time::{Duration, Instant};
+
+/// Adapter yielding the values of a receiver at a limited rate
+///
+/// See the [module documentation](self) for details.
+pub struct Throttle<T> {
+    /// Wrapped receiver
+    source: Box<dyn Source<T> + Send>,
+    /// Minimum time between two values, on average
+    interval: Duration,
+    /// Number of values that may be yielded back to back after being idle
+    burst: u32,
+    /// When the next value would be due if values were yielded exactly once
+    /// per interval. Values may be yielded up to `burst - 1` intervals
+    /// earlier. [`None`] if it's too far in the future to be represented, so
+    /// no value is yielded anymore.
+    due: Option<Instant>,
+    /// Alarm of the time the next value may be yielded, if it has been
+    /// scheduled
+    alarm: Option<Arc<Alarm>>,
+}
+
+impl<T> Unpin for Throttle<T> {}
+
+impl<T> Throttle<T> {
+    /// Wraps `source`, yielding at most one value per `interval`.
+    pub fn new<S>(source: S, interval: Duration) -> Self
+    where
+        S: Source<T> + Send + 'static,
+    {
+        Throttle {
+            source: Box::new(source),
+            interval,
+            burst: 1,
+            due: Some(Instant::now()),
+            alarm: None,
+        }
+    }
+
+    /// Allows yielding up to `burst` values back to back once the throttle
+    /// has been idle for long enough, while keeping the average rate at one
+    /// value per interval.
+    ///
+    /// # Panics
+    /// Panics if `burst` is zero.
+    ///
+    /// # Examples
+    #[cfg_attr(feature = "mpsc", doc = "```rust")]
+    #[cfg_attr(not(feature = "mpsc"), doc = "```ignore")]
+    /// use laika::{mpsc, throttle::Throttle};
+    /// use std::{task::{Context, Poll, Waker}, time::Duration};
+    ///
+    /// let (tx, rx) = mpsc::unbounded();
+    /// let mut throttle = Throttle::new(rx, Duration::from_secs(1)).with_burst(2);
+    /// let mut cx = Context::from_waker(Waker::noop());
+    ///
+    /// for value in 0..3 {
+    ///     tx.send(value).unwrap();
+    /// }
+    ///
+    /// assert_eq!(throttle.poll_next(&mut cx), Poll::Ready(Some(0)));
+    /// assert_eq!(throttle.poll_next(&mut cx), Poll::Ready(Some(1)));
+    /// // The third value has to wait for a second
+    /// assert_eq!(throttle.poll_next(&mut cx), Poll::Pending);
+    /// ```
+    pub fn with_burst(mut self, burst: u32) -> Self {
+        assert!(burst > 0, "a throttle needs a burst of at least 1");
+
+        self.burst = burst;
+        self
+    }
+
+    /// Returns when the next value may be yielded ([`None`] if never), if
+    /// that's after `now`.
+    fn throttled(&self, now: Instant) -> Option<Option<Instant>> {
+        let Some(due) = self.due else {
+            return Some(None);
+        };
+
+        let tolerance = self.interval.saturating_mul(self.burst - 1);
+        let allowed = due.checked_sub(tolerance)?;
+
+        (allowed > now).then_some(Some(allowed))
+    }
+
+    /// Receives the next value if the rate allows it. Returns when it does
+    /// otherwise ([`None`] if never).
+    fn poll_throttled(&mut self, cx: &mut Context<'_>) -> Result<Poll<Option<T>>, Option<Instant>> {
+        let now = Instant::now();
+
+        if let Some(allowed) = self.throttled(now) {
+            return Err(allowed);
+        }
+
+        self.alarm = None;
+        let poll = self.source.poll_next(cx);
+
+        if let Poll::Ready(Some(_)) = poll {
+            self.due = self
+                .due
+                .and_then(|due| due.max(now).checked_add(self.interval));
+        }
+
+        Ok(poll)
+    }
+
+    /// Polls for the next value, registering the waker of `cx` with the
+    /// receiver (or the timer, if the rate is exceeded) if there is none yet.
+    /// Resolves to [`None`] once the receiver has been closed.
+    pub fn poll_next(&mut self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        match self.poll_throttled(cx) {
+            Ok(poll) => poll,
+            Err(None) => Poll::Pending,
+            Err(Some(allowed)) => {
+                let alarm = self.alarm.get_or_insert_with(|| {
+                    let alarm = Arc::new(Alarm::default());
+                    timer::schedule(allowed, &alarm);
+
+                    alarm
+                });
+
+                alarm.register(cx.waker());
+
+                Poll::Pending
+            }
+        }
+    }
+
+    /// Receives the next value, waiting asynchronously until there is one and
+    /// the rate allows yielding it. Resolves to [`None`] once the receiver has
+    /// been closed.
+    ///
+    /// # Examples
+    #[cfg_attr(feature = "mpsc", doc = "```rust")]
+    #[cfg_attr(not(feature = "mpsc"), doc = "```ignore")]
+    /// # tokio::runtime::Builder::new_current_thread()
+    /// #     .build()
+    /// #     .unwrap()
+    /// #     .block_on(async {
+    /// use laika::{mpsc, throttle::Throttle};
+    /// use std::time::Duration;
+    ///
+    /// let (tx, rx) = mpsc::bounded(1);
+    /// let mut throttle = Throttle::new(rx, Duration::from_millis(5));
+    ///
+    /// // Suspended by the bounded channel while the throttle holds back
+    /// let producer = tokio::spawn(async move {
+    ///     for value in 0..5 {
+    ///         tx.send(value).await.unwrap();
+    ///     }
+    /// });
+    ///
+    /// let mut received = Vec::new();
+    ///
+    /// while let Some(value) = throttle.next().await {
+    ///     received.push(value);
+    /// }
+    ///
+    /// producer.await.unwrap();
+    /// assert_eq!(received, [0, 1, 2, 3, 4]);
+    /// # });
+    /// ```
+    pub async fn next(&mut self) -> Option<T> {
+        core::future::poll_fn(|cx| self.poll_next(cx)).await
+    }
+
+    /// Receives the next value, blocking the current thread until there is
+    /// one and the rate allows yielding it. See [`Throttle::next`].
+    pub fn next_blocking(&mut self) -> Option<T> {
+        park::block_on(|cx| self.poll_next(cx))
+    }
+}
+
+impl<T> Source<T> for Throttle<T> {
+    fn poll_next(&mut self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        Throttle::poll_next(self, cx)
+    }
+}
+
+impl<T> fmt::Debug for Throttle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Throttle")
+            .field("interval", &self.interval)
+            .field("burst", &self.burst)
+            .field("due", &self.due)
+            .finish()
+    }
+}
+
+/// Implement [`futures_core::Stream`] for [`Throttle`], yielding values until
+/// the receiver has been closed.
+#[cfg(feature = "futures")]
+impl<T> futures_core::Stream for Throttle<T> {
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        self.get_mut().poll_next(cx)
+    }
+}
+
+#[cfg(all(test, feature = "mpsc"))]
+mod test {
+    use super::*;
+    use crate::mpsc;
+    use core::task::Waker;
+    use std::thread;
+
+    #[test]
+    fn test_rate() {
+        let (tx, rx) = mpsc::unbounded();
+        let mut throttle = Throttle::new(rx, Duration::from_millis(50));
+        let mut cx = Context::from_waker(Waker::noop());
+
+        assert_eq!(throttle.poll_next(&mut cx), Poll::Pending);
+
+        tx.send(1).unwrap();
+        tx.send(2).unwrap();
+        assert_eq!(throttle.poll_next(&mut cx), Poll::Ready(Some(1)));
+
+        // Held back in the channel, not dropped
+        assert_eq!(throttle.poll_next(&mut cx), Poll::Pending);
+        thread::sleep(Duration::from_millis(60));
+        assert_eq!(throttle.poll_next(&mut cx), Poll::Ready(Some(2)));
+
+        drop(tx);
+        thread::sleep(Duration::from_millis(60));
+        assert_eq!(throttle.poll_next(&mut cx), Poll::Ready(None));
+    }
+
+    #[test]
+    fn test_burst() {
+        let (tx, rx) = mpsc::unbounded();
+        let mut throttle = Throttle::new(rx, Duration::from_millis(50)).with_burst(3);
+        let mut cx = Context::from_waker(Waker::noop());
+
+        for value in 0..5 {
+            tx.send(value).unwrap();
+        }
+
+        for value in 0..3 {
+            assert_eq!(throttle.poll_next(&mut cx), Poll::Ready(Some(value)));
+        }
+
+        // The burst is used up, refilling at one value per interval
+        assert_eq!(throttle.poll_next(&mut cx), Poll::Pending);
+        thread::sleep(Duration::from_millis(60));
+        assert_eq!(throttle.poll_next(&mut cx), Poll::Ready(Some(3)));
+        assert_eq!(throttle.poll_next(&mut cx), Poll::Pending);
+    }
+
+    #[test]
+    fn test_endless_interval() {
+        let (tx, rx) = mpsc::unbounded();
+        let mut throttle = Throttle::new(rx, Duration::MAX);
+        let mut cx = Context::from_waker(Waker::noop());
+
+        tx.send(1).unwrap();
+        tx.send(2).unwrap();
+        assert_eq!(throttle.poll_next(&mut cx), Poll::Ready(Some(1)));
+
+        // The next value is never allowed, so no alarm is scheduled for it
+        assert_eq!(throttle.due, None);
+        assert_eq!(throttle.poll_next(&mut cx), Poll::Pending);
+        assert!(throttle.alarm.is_none());
+    }
+
+    #[test]
+    fn test_next_blocking() {
+        let (tx, rx) = mpsc::bounded(1);
+        let mut throttle = Throttle::new(rx, Duration::from_millis(20));
+
+        let producer = thread::spawn(move || {
+            let start = Instant::now();
+
+            for value in 0..5 {
+                tx.send_blocking(value).unwrap();
+            }
+
+            start.elapsed()
+        });
+
+        let start = Instant::now();
+
+        for value in 0..5 {
+            assert_eq!(throttle.next_blocking(), Some(value));
+        }
+
+        assert_eq!(throttle.next_blocking(), None);
+        assert!(start.elapsed() >= Duration::from_millis(80));
+
+        // The producer has been suspended by the full channel
+        assert!(producer.join().unwrap() >= Duration::from_millis(40));
+    }
+
+    #[tokio::test]
+    async fn test_next() {
+        let (tx, rx) = mpsc::unbounded();
+        let mut throttle = Throttle::new(rx, Duration::from_millis(20));
+
+        tx.send(1).unwrap();
+        tx.send(2).unwrap();
+        drop(tx);
+
+        let start = Instant::now();
+
+        // Woken up by the timer thread once the second value may be yielded
+        assert_eq!(throttle.next().await, Some(1));
+        assert_eq!(throttle.next().await, Some(2));
+        assert_eq!(throttle.next().await, None);
+        assert!(start.elapsed() >= Duration::from_millis(20));
+    }
+}
@@ -0,0 +1,224 @@
+#![forbid(unsafe_code)]
+//! Timer waking up adapters (like [`Chunks`](crate::chunks::Chunks)) and
+//! expiring channels (like the ones of `shotgun::channel_with_deadline`) whose
+//! deadline passed, so they don't depend on a runtime. All of them share a
+//! single background thread.
+
+use alloc::sync::{Arc, Weak};
+use core::{
+    cmp::{Ordering, Reverse},
+    panic::AssertUnwindSafe,
+};
+use std::{
+    collections::BinaryHeap,
+    panic,
+    sync::{Condvar, Mutex, Once, PoisonError},
+    thread,
+    time::Instant,
+};
+
+#[cfg(any(
+    feature = "ack",
+    feature = "chunks",
+    feature = "debounce",
+    feature = "lease",
+    feature = "sampling",
+    feature = "throttle",
+    all(feature = "shotgun", not(laika_loom))
+))]
+pub(crate) mod alarm;
+
+/// Alarms waiting for their deadline, ordered by the earliest deadline
+static QUEUE: Mutex<BinaryHeap<Reverse<Entry>>> = Mutex::new(BinaryHeap::new());
+
+/// Notified when an alarm with an earlier deadline is queued
+static QUEUED: Condvar = Condvar::new();
+
+/// Starts the timer thread on first use
+static TIMER: Once = Once::new();
+
+/// Rings once its deadline passes. Type-erased, so that alarms of all kinds
+/// share one queue.
+pub(crate) trait Ring: Send + Sync {
+    /// Rings, e.g. by waking up a task or expiring a channel.
+    fn ring(&self);
+}
+
+/// Alarm in [`QUEUE`]. Weak, so that alarms that aren't needed anymore (e.g.
+/// of dropped adapters or channels) are skipped.
+struct Entry {
+    /// When the alarm rings
+    deadline: Instant,
+    /// Alarm that rings
+    alarm: Weak<dyn Ring>,
+}
+
+impl PartialEq for Entry {
+    fn eq(&self, other: &Self) -> bool {
+        self.deadline == other.deadline
+    }
+}
+
+impl Eq for Entry {}
+
+impl PartialOrd for Entry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Entry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.deadline.cmp(&other.deadline)
+    }
+}
+
+/// Queues `alarm` to ring at `deadline`, starting the timer thread if it isn't
+/// running yet.
+pub(crate) fn schedule<A>(deadline: Instant, alarm: &Arc<A>)
+where
+    A: Ring + 'static,
+{
+    let alarm: Weak<dyn Ring> = Arc::downgrade(alarm) as Weak<A>;
+
+    TIMER.call_once(|| {
+        thread::Builder::new()
+            .name(String::from("laika-timer"))
+            .spawn(run)
+            .expect("failed to spawn the timer thread");
+    });
+
+    let mut queue = crate::lock(&QUEUE);
+    let earliest = queue
+        .peek()
+        .is_none_or(|Reverse(entry)| deadline < entry.deadline);
+
+    queue.push(Reverse(Entry { deadline, alarm }));
+
+    if earliest {
+        QUEUED.notify_one();
+    }
+}
+
+/// Runs the timer thread, ringing the queued alarms once their deadline
+/// passes.
+fn run() {
+    let mut queue = crate::lock(&QUEUE);
+
+    loop {
+        let now = Instant::now();
+
+        match queue.peek() {
+            Some(Reverse(entry)) if entry.deadline <= now => {
+                let Some(Reverse(entry)) = queue.pop() else {
+                    unreachable!("Queue has been peeked");
+                };
+
+                // Ringing wakes up tasks, so it's done without holding the
+                // lock.
+                drop(queue);
+
+                // A panicking waker (or `Drop` of the last reference) must not
+                // take down the thread, as every other alarm would never ring.
+                let _ = panic::catch_unwind(AssertUnwindSafe(|| {
+                    if let Some(alarm) = entry.alarm.upgrade() {
+                        alarm.ring();
+                    }
+                }));
+
+                queue = crate::lock(&QUEUE);
+            }
+            Some(Reverse(entry)) => {
+                let timeout = entry.deadline - now;
+
+                queue = QUEUED
+                    .wait_timeout(queue, timeout)
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .0;
+            }
+            None => {
+                queue = QUEUED.wait(queue).unwrap_or_else(PoisonError::into_inner);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::time::Duration;
+    use std::{
+        sync::mpsc::{self, Sender},
+        vec::Vec,
+    };
+
+    /// Alarm sending its index once it rings.
+    struct Report(usize, Mutex<Sender<usize>>);
+
+    impl Ring for Report {
+        fn ring(&self) {
+            let _ = self
+                .1
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .send(self.0);
+        }
+    }
+
+    /// Alarm panicking once it rings.
+    struct Panic;
+
+    impl Ring for Panic {
+        fn ring(&self) {
+            panic!("ringing");
+        }
+    }
+
+    #[test]
+    fn test_order() {
+        let (tx, rx) = mpsc::channel();
+        let now = Instant::now();
+
+        let _alarms = [3, 1, 2].map(|i| {
+            let alarm = Arc::new(Report(i, Mutex::new(tx.clone())));
+            schedule(now + Duration::from_millis(20 * i as u64), &alarm);
+            alarm
+        });
+
+        assert_eq!(
+            rx.iter().take(3).collect::<Vec<_>>(),
+            [1, 2, 3],
+            "alarms must ring in the order of their deadlines"
+        );
+        assert!(now.elapsed() >= Duration::from_millis(60));
+    }
+
+    #[test]
+    fn test_dropped() {
+        let (tx, rx) = mpsc::channel();
+        let now = Instant::now();
+
+        let dropped = Arc::new(Report(0, Mutex::new(tx.clone())));
+        schedule(now + Duration::from_millis(10), &dropped);
+        drop(dropped);
+
+        let alarm = Arc::new(Report(1, Mutex::new(tx)));
+        schedule(now + Duration::from_millis(20), &alarm);
+
+        assert_eq!(rx.recv_timeout(Duration::from_secs(10)), Ok(1));
+    }
+
+    #[test]
+    fn test_panic() {
+        let (tx, rx) = mpsc::channel();
+        let now = Instant::now();
+
+        let panic = Arc::new(Panic);
+        schedule(now, &panic);
+
+        let alarm = Arc::new(Report(0, Mutex::new(tx)));
+        schedule(now + Duration::from_millis(10), &alarm);
+
+        assert_eq!(rx.recv_timeout(Duration::from_secs(10)), Ok(0));
+    }
+}
@@ -0,0 +1,42 @@
+//! Alarm waking up the task that polled last once its deadline passes.
+
+use super::Ring;
+use core::task::Waker;
+use std::sync::Mutex;
+
+/// Waker of a task waiting for a deadline. Replaced on every poll, so the
+/// timer wakes up the task that polled last.
+#[derive(Debug, Default)]
+pub(crate) struct Alarm {
+    /// Waker of the waiting task
+    waker: Mutex<Option<Waker>>,
+}
+
+impl Alarm {
+    /// Replaces the waker, unless it would wake the registered one.
+    pub(crate) fn register(&self, waker: &Waker) {
+        let mut registered = crate::lock(&self.waker);
+
+        if !registered.as_ref().is_some_and(|w| w.will_wake(waker)) {
+            *registered = Some(waker.clone());
+        }
+    }
+
+    /// Drops the waker, so the alarm doesn't wake up anyone once it rings.
+    #[cfg(all(feature = "shotgun", not(laika_loom)))]
+    pub(crate) fn cancel(&self) {
+        let waker = crate::lock(&self.waker).take();
+        drop(waker);
+    }
+}
+
+impl Ring for Alarm {
+    /// Wakes up the waiting task, if there is one.
+    fn ring(&self) {
+        let waker = crate::lock(&self.waker).take();
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
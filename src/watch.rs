@@ -0,0 +1,524 @@
+#![forbid(unsafe_code)]
+//! # A single-value channel that only keeps the latest value
+//!
+//! A watch channel holds one value. The [`Sender`] overwrites it, and every
+//! [`Receiver`] can [`borrow`](Receiver::borrow) the current value at any time
+//! or wait until it [`changed`](Receiver::changed). Receivers that don't keep
+//! up simply skip the intermediate values, so this fits config or state
+//! propagation where only the freshest value matters.
+//!
+//! The channel is backed by a `std::sync::RwLock` (for the value) and a
+//! `std::sync::Mutex` (for pending waiters) and doesn't contain any unsafe
+//! code. The async functions work with any runtime.
+//!
+//! # Examples
+//! ```rust
+//! let (tx, mut rx) = laika::watch::channel("initial");
+//! assert_eq!(*rx.borrow(), "initial");
+//!
+//! tx.send("first").unwrap();
+//! tx.send("second").unwrap();
+//!
+//! // Only the latest value is kept
+//! assert_eq!(rx.has_changed(), Ok(true));
+//! assert_eq!(*rx.borrow_and_update(), "second");
+//! assert_eq!(rx.has_changed(), Ok(false));
+//! ```
+
+mod error;
+
+pub use error::{RecvError, SendError};
+
+use alloc::sync::Arc;
+use core::{
+    future, mem,
+    ops::Deref,
+    task::{Context, Poll, Waker},
+};
+use std::{
+    collections::BTreeMap,
+    sync::{Condvar, Mutex, MutexGuard, PoisonError, RwLock, RwLockReadGuard},
+};
+
+/// Bookkeeping of a channel, guarded by [`Shared::state`]
+#[derive(Debug)]
+struct State {
+    /// Number of times the value has been replaced
+    version: u64,
+    /// Whether the [`Sender`] has been dropped
+    closed: bool,
+    /// Number of existing [`Receiver`]s
+    receivers: usize,
+    /// Wakers of pending [`Receiver`]s, keyed by [`Receiver::id`]
+    wakers: BTreeMap<u64, Waker>,
+    /// Id of the next [`Receiver`]
+    next_id: u64,
+}
+
+impl State {
+    /// Returns a new receiver id.
+    fn id(&mut self) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+}
+
+/// State shared by the [`Sender`] and all [`Receiver`]s of a channel
+#[derive(Debug)]
+struct Shared<T> {
+    /// Current value. Always locked before [`Shared::state`], so that the
+    /// version is updated together with the value.
+    value: RwLock<T>,
+    /// Version and pending waiters
+    state: Mutex<State>,
+    /// Notified when the value changes or the channel is closed, so that
+    /// threads blocking in [`Receiver::changed_blocking`] wake up.
+    condvar: Condvar,
+}
+
+impl<T> Shared<T> {
+    /// Locks the state.
+    fn lock(&self) -> MutexGuard<'_, State> {
+        crate::lock(&self.state)
+    }
+
+    /// Locks the value for reading, ignoring poisoning like [`Shared::lock`].
+    fn read(&self) -> RwLockReadGuard<'_, T> {
+        self.value.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Replaces the value, bumps the version and wakes up all pending
+    /// receivers. Returns the previous value.
+    fn replace(&self, value: T) -> T {
+        let mut current = self.value.write().unwrap_or_else(PoisonError::into_inner);
+        let previous = mem::replace(&mut *current, value);
+
+        let mut state = self.lock();
+        state.version += 1;
+        drop(current);
+
+        self.wake_all(state);
+
+        previous
+    }
+
+    /// Wakes up all pending receivers. Takes the wakers while `state` is
+    /// locked but wakes them after unlocking it.
+    fn wake_all(&self, mut state: MutexGuard<'_, State>) {
+        let wakers = mem::take(&mut state.wakers);
+        drop(state);
+
+        for waker in wakers.into_values() {
+            waker.wake();
+        }
+
+        self.condvar.notify_all();
+    }
+}
+
+/// Sending side of a watch channel
+///
+/// Replaces the value of the channel. The channel is closed once the sender
+/// is dropped, while receivers can still borrow the last value.
+#[derive(Debug)]
+pub struct Sender<T> {
+    /// State shared with all receivers
+    shared: Arc<Shared<T>>,
+}
+
+/// Receiving side of a watch channel
+///
+/// Borrows the current value and tracks which version of it has been seen,
+/// see [`Receiver::changed`]. A cloned receiver has seen the same version as
+/// the original one.
+#[derive(Debug)]
+pub struct Receiver<T> {
+    /// State shared with the sender
+    shared: Arc<Shared<T>>,
+    /// Version of the value that has been seen last
+    seen: u64,
+    /// Key of the waker this receiver registered while being polled
+    id: u64,
+}
+
+/// Borrowed value of a watch channel
+///
+/// Holds a read lock, so the [`Sender`] can't replace the value while it's
+/// borrowed. Don't keep it around any longer than necessary, especially not
+/// across `.await` points.
+#[derive(Debug)]
+pub struct Ref<'a, T> {
+    /// Read lock of the value
+    guard: RwLockReadGuard<'a, T>,
+}
+
+impl<T> Deref for Ref<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+impl<T> Sender<T> {
+    /// Replaces the value and notifies all receivers.
+    ///
+    /// Returns the value in a [`SendError`] without replacing the current one
+    /// if all [`Receiver`]s have been dropped. Use [`Sender::send_replace`] to
+    /// replace it regardless.
+    ///
+    /// # Examples
+    /// ```rust
+    /// let (tx, rx) = laika::watch::channel(1);
+    ///
+    /// tx.send(2).unwrap();
+    /// assert_eq!(*rx.borrow(), 2);
+    ///
+    /// drop(rx);
+    /// assert_eq!(tx.send(3).unwrap_err().into_inner(), 3);
+    /// assert_eq!(*tx.borrow(), 2);
+    /// ```
+    pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+        if self.receiver_count() == 0 {
+            return Err(SendError(value));
+        }
+
+        self.shared.replace(value);
+
+        Ok(())
+    }
+
+    /// Replaces the value even if there are no receivers and returns the
+    /// previous one.
+    pub fn send_replace(&self, value: T) -> T {
+        self.shared.replace(value)
+    }
+
+    /// Borrows the current value.
+    pub fn borrow(&self) -> Ref<'_, T> {
+        Ref {
+            guard: self.shared.read(),
+        }
+    }
+
+    /// Creates a new [`Receiver`] that has seen the current value already.
+    ///
+    /// # Examples
+    /// ```rust
+    /// let (tx, _rx) = laika::watch::channel(1);
+    ///
+    /// let rx1 = tx.subscribe();
+    /// assert_eq!(rx1.has_changed(), Ok(false));
+    ///
+    /// tx.send(2).unwrap();
+    /// assert_eq!(rx1.has_changed(), Ok(true));
+    /// ```
+    pub fn subscribe(&self) -> Receiver<T> {
+        let mut state = self.shared.lock();
+        state.receivers += 1;
+
+        Receiver {
+            shared: Arc::clone(&self.shared),
+            seen: state.version,
+            id: state.id(),
+        }
+    }
+
+    /// Returns the number of [`Receiver`]s that currently exist.
+    pub fn receiver_count(&self) -> usize {
+        self.shared.lock().receivers
+    }
+}
+
+/// Closes the channel when the [`Sender`] is dropped, waking up all pending
+/// receivers.
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.closed = true;
+
+        self.shared.wake_all(state);
+    }
+}
+
+impl<T> Receiver<T> {
+    /// Borrows the current value without marking it as seen.
+    pub fn borrow(&self) -> Ref<'_, T> {
+        Ref {
+            guard: self.shared.read(),
+        }
+    }
+
+    /// Borrows the current value and marks it as seen, so that
+    /// [`Receiver::changed`] waits for the next one.
+    pub fn borrow_and_update(&mut self) -> Ref<'_, T> {
+        let guard = self.shared.read();
+        self.seen = self.shared.lock().version;
+
+        Ref { guard }
+    }
+
+    /// Returns whether the value has changed since it has been seen last.
+    ///
+    /// Returns [`RecvError`] if the [`Sender`] has been dropped.
+    pub fn has_changed(&self) -> Result<bool, RecvError> {
+        let state = self.shared.lock();
+
+        if state.closed {
+            return Err(RecvError);
+        }
+
+        Ok(state.version != self.seen)
+    }
+
+    /// Polls for a change of the value, registering the waker of `cx` if it
+    /// hasn't changed yet. Marks the value as seen once it has changed.
+    pub fn poll_changed(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), RecvError>> {
+        let mut state = self.shared.lock();
+
+        if state.version != self.seen {
+            self.seen = state.version;
+            return Poll::Ready(Ok(()));
+        }
+
+        if state.closed {
+            return Poll::Ready(Err(RecvError));
+        }
+
+        let registered = state.wakers.get(&self.id);
+
+        if !registered.is_some_and(|waker| waker.will_wake(cx.waker())) {
+            state.wakers.insert(self.id, cx.waker().clone());
+        }
+
+        Poll::Pending
+    }
+
+    /// Waits asynchronously until the value changed since it has been seen
+    /// last and marks it as seen. Resolves right away if it has changed
+    /// already.
+    ///
+    /// Resolves to [`RecvError`] if the [`Sender`] has been dropped and the
+    /// value hasn't changed since.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # tokio::runtime::Builder::new_current_thread()
+    /// #     .build()
+    /// #     .unwrap()
+    /// #     .block_on(async {
+    /// let (tx, mut rx) = laika::watch::channel(0);
+    ///
+    /// let task = tokio::spawn(async move {
+    ///     while rx.changed().await.is_ok() {
+    ///         if *rx.borrow() == 3 {
+    ///             return true;
+    ///         }
+    ///     }
+    ///
+    ///     false
+    /// });
+    ///
+    /// tx.send(3).unwrap();
+    ///
+    /// assert!(task.await.unwrap());
+    /// # });
+    /// ```
+    pub async fn changed(&mut self) -> Result<(), RecvError> {
+        future::poll_fn(|cx| self.poll_changed(cx)).await
+    }
+
+    /// Blocks the current thread until the value changed since it has been
+    /// seen last. See [`Receiver::changed`].
+    pub fn changed_blocking(&mut self) -> Result<(), RecvError> {
+        let mut state = self.shared.lock();
+
+        loop {
+            if state.version != self.seen {
+                self.seen = state.version;
+                return Ok(());
+            }
+
+            if state.closed {
+                return Err(RecvError);
+            }
+
+            state = self
+                .shared
+                .condvar
+                .wait(state)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+    }
+}
+
+/// Cloned receivers have seen the same version of the value as the original
+/// one.
+impl<T> Clone for Receiver<T> {
+    fn clone(&self) -> Self {
+        let mut state = self.shared.lock();
+        state.receivers += 1;
+
+        Receiver {
+            shared: Arc::clone(&self.shared),
+            seen: self.seen,
+            id: state.id(),
+        }
+    }
+}
+
+/// Removes the registered waker (if any) when a [`Receiver`] is dropped.
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.receivers -= 1;
+        state.wakers.remove(&self.id);
+    }
+}
+
+/// Creates a watch channel holding `initial`, which the receiver has seen
+/// already.
+///
+/// # Examples
+/// ```rust
+/// let (tx, rx) = laika::watch::channel(String::from("config"));
+///
+/// tx.send(String::from("updated config")).unwrap();
+/// assert_eq!(*rx.borrow(), "updated config");
+/// ```
+pub fn channel<T>(initial: T) -> (Sender<T>, Receiver<T>) {
+    let shared = Arc::new(Shared {
+        value: RwLock::new(initial),
+        state: Mutex::new(State {
+            version: 0,
+            closed: false,
+            receivers: 1,
+            wakers: BTreeMap::new(),
+            next_id: 1,
+        }),
+        condvar: Condvar::new(),
+    });
+
+    let receiver = Receiver {
+        shared: Arc::clone(&shared),
+        seen: 0,
+        id: 0,
+    };
+
+    (Sender { shared }, receiver)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::{future::Future, pin::pin};
+    use std::{thread, time::Duration, vec::Vec};
+
+    #[test]
+    fn test_send() {
+        let (tx, mut rx) = channel(0);
+        let rx1 = rx.clone();
+
+        assert_eq!(rx.has_changed(), Ok(false));
+
+        tx.send(1).unwrap();
+        tx.send(2).unwrap();
+
+        assert_eq!(rx.has_changed(), Ok(true));
+        assert_eq!(*rx.borrow(), 2);
+        assert_eq!(rx.has_changed(), Ok(true));
+        assert_eq!(*rx.borrow_and_update(), 2);
+        assert_eq!(rx.has_changed(), Ok(false));
+
+        // Clones track their own version
+        assert_eq!(rx1.has_changed(), Ok(true));
+        assert_eq!(tx.receiver_count(), 2);
+
+        drop((rx, rx1));
+        assert_eq!(tx.send(3), Err(SendError(3)));
+        assert_eq!(tx.send_replace(4), 2);
+        assert_eq!(*tx.borrow(), 4);
+    }
+
+    #[test]
+    fn test_closed() {
+        let (tx, mut rx) = channel(0);
+
+        tx.send(1).unwrap();
+        drop(tx);
+
+        assert_eq!(rx.has_changed(), Err(RecvError));
+        assert_eq!(*rx.borrow(), 1);
+
+        // The change before closing is still reported
+        assert_eq!(rx.changed_blocking(), Ok(()));
+        assert_eq!(rx.changed_blocking(), Err(RecvError));
+    }
+
+    #[test]
+    fn test_poll_changed() {
+        let (tx, mut rx) = channel(0);
+        let mut cx = Context::from_waker(Waker::noop());
+
+        {
+            let mut changed = pin!(rx.changed());
+            assert_eq!(changed.as_mut().poll(&mut cx), Poll::Pending);
+            assert_eq!(changed.as_mut().poll(&mut cx), Poll::Pending);
+            assert_eq!(tx.shared.lock().wakers.len(), 1);
+
+            tx.send(1).unwrap();
+            assert!(tx.shared.lock().wakers.is_empty());
+            assert_eq!(changed.as_mut().poll(&mut cx), Poll::Ready(Ok(())));
+        }
+
+        assert_eq!(rx.poll_changed(&mut cx), Poll::Pending);
+        drop(rx);
+        assert!(tx.shared.lock().wakers.is_empty());
+    }
+
+    #[test]
+    fn test_changed_blocking() {
+        let (tx, mut rx) = channel(0);
+
+        let thread = thread::spawn(move || {
+            let mut values = Vec::new();
+
+            while rx.changed_blocking().is_ok() {
+                values.push(*rx.borrow());
+            }
+
+            values
+        });
+
+        thread::sleep(Duration::from_millis(10));
+        tx.send(1).unwrap();
+        drop(tx);
+
+        // Intermediate values may be skipped, but the last one is always seen
+        assert_eq!(thread.join().unwrap().last(), Some(&1));
+    }
+
+    #[tokio::test]
+    async fn test_changed() {
+        let (tx, rx) = channel(0);
+
+        let tasks = (0..4)
+            .map(|_| {
+                let mut rx = rx.clone();
+
+                tokio::spawn(async move {
+                    rx.changed().await.unwrap();
+                    *rx.borrow_and_update()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        tokio::task::yield_now().await;
+        tx.send(1).unwrap();
+
+        for task in tasks {
+            assert_eq!(task.await.unwrap(), 1);
+        }
+    }
+}
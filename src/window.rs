@@ -0,0 +1,363 @@
+#![forbid(unsafe_code)]
+//! # Sliding-window aggregates over a receiver
+//!
+//! [`Window`] wraps a receiver and keeps a sliding window of its latest
+//! values: either the last `len` of them ([`Window::counted`]) or the ones
+//! received within a time span ([`Window::timed`]). For every value received,
+//! it folds the values in the window into an aggregate and yields it, e.g. a
+//! rolling average or the number of messages in the last second.
+//!
+//! Any [`Source`] can be wrapped, i.e. the receivers of the channel modules
+//! (see [`merge`](crate::merge) for the list). A window is a [`Source`] of its
+//! aggregates itself, so it can be merged or chunked in turn. Aggregates are
+//! received with [`Window::next`] or blocking with [`Window::next_blocking`].
+//!
+//! The window doesn't contain any unsafe code and works with any async
+//! runtime.
+//!
+//! ## Optional features
+//!
+//! - `futures`: Implements `futures_core::Stream` for [`Window`].
+//!
+//! # Examples
+#![cfg_attr(feature = "mpsc", doc = "```rust")]
+#![cfg_attr(not(feature = "mpsc"), doc = "```ignore")]
+//! use laika::{mpsc, window::Window};
+//!
+//! let (tx, rx) = mpsc::unbounded();
+//! let mut sums = Window::counted(rx, 3, 0, |sum, value| sum + value);
+//!
+//! for value in [1, 2, 3, 4] {
+//!     tx.send(value).unwrap();
+//! }
+//!
+//! drop(tx);
+//!
+//! assert_eq!(sums.next_blocking(), Some(1));
+//! assert_eq!(sums.next_blocking(), Some(3));
+//! assert_eq!(sums.next_blocking(), Some(6));
+//! // 1 dropped out of the window
+//! assert_eq!(sums.next_blocking(), Some(9));
+//! assert_eq!(sums.next_blocking(), None);
+//! ```
+
+use crate::{merge::Source, park};
+use alloc::{boxed::Box, collections::VecDeque};
+#[cfg(feature = "futures")]
+use core::pin::Pin;
+use core::{
+    fmt,
+    task::{Context, Poll},
+};
+use std::time::{Duration, Instant};
+
+/// Boxed fold function of a [`Window`]
+type Fold<T, A> = Box<dyn FnMut(A, &T) -> A + Send>;
+
+/// Which values a [`Window`] holds
+#[derive(Clone, Copy, Debug)]
+enum Span {
+    /// The last values, at most this many
+    Count(usize),
+    /// The values received within this duration
+    Time(Duration),
+}
+
+/// Sliding window yielding an aggregate of its values for every value received
+///
+/// See the [module documentation](self) for details.
+pub struct Window<T, A> {
+    /// Wrapped receiver, [`None`] once it has been closed
+    source: Option<Box<dyn Source<T> + Send>>,
+    /// Which values the window holds
+    span: Span,
+    /// Values in the window with the time they have been received, oldest
+    /// first
+    values: VecDeque<(Instant, T)>,
+    /// Aggregate of an empty window
+    init: A,
+    /// Folds a value into the aggregate
+    fold: Fold<T, A>,
+}
+
+impl<T, A> Unpin for Window<T, A> {}
+
+impl<T, A> Window<T, A>
+where
+    A: Clone,
+{
+    /// Wraps `source` in a window holding its last `len` values. Every
+    /// aggregate is the result of folding them (oldest first) into `init`.
+    ///
+    /// # Panics
+    /// Panics if `len` is zero.
+    pub fn counted<S, F>(source: S, len: usize, init: A, fold: F) -> Self
+    where
+        S: Source<T> + Send + 'static,
+        F: FnMut(A, &T) -> A + Send + 'static,
+    {
+        assert!(len > 0, "a window needs a length");
+
+        Window::new(source, Span::Count(len), init, fold)
+    }
+
+    /// Wraps `source` in a window holding the values received within `span`,
+    /// measured when a value is received. Every aggregate is the result of
+    /// folding them (oldest first) into `init`.
+    ///
+    /// # Examples
+    #[cfg_attr(feature = "mpsc", doc = "```rust")]
+    #[cfg_attr(not(feature = "mpsc"), doc = "```ignore")]
+    /// use laika::{mpsc, window::Window};
+    /// use std::{thread, time::Duration};
+    ///
+    /// let (tx, rx) = mpsc::unbounded();
+    /// let mut rate = Window::timed(rx, Duration::from_millis(50), 0, |count, _| count + 1);
+    ///
+    /// tx.send(()).unwrap();
+    /// tx.send(()).unwrap();
+    /// assert_eq!(rate.next_blocking(), Some(1));
+    /// assert_eq!(rate.next_blocking(), Some(2));
+    ///
+    /// thread::sleep(Duration::from_millis(100));
+    ///
+    /// // The earlier values are outside of the window by now
+    /// tx.send(()).unwrap();
+    /// assert_eq!(rate.next_blocking(), Some(1));
+    /// ```
+    pub fn timed<S, F>(source: S, span: Duration, init: A, fold: F) -> Self
+    where
+        S: Source<T> + Send + 'static,
+        F: FnMut(A, &T) -> A + Send + 'static,
+    {
+        Window::new(source, Span::Time(span), init, fold)
+    }
+
+    /// Creates a window holding the values within `span`.
+    fn new<S, F>(source: S, span: Span, init: A, fold: F) -> Self
+    where
+        S: Source<T> + Send + 'static,
+        F: FnMut(A, &T) -> A + Send + 'static,
+    {
+        Window {
+            source: Some(Box::new(source)),
+            span,
+            values: VecDeque::new(),
+            init,
+            fold: Box::new(fold),
+        }
+    }
+
+    /// Returns the number of values in the window.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Returns whether the window doesn't hold any values.
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Adds `value` to the window, drops the values outside of it and returns
+    /// the aggregate.
+    fn slide(&mut self, value: T) -> A {
+        let now = Instant::now();
+        self.values.push_back((now, value));
+
+        match self.span {
+            Span::Count(len) => {
+                while self.values.len() > len {
+                    self.values.pop_front();
+                }
+            }
+            Span::Time(span) => {
+                while self
+                    .values
+                    .front()
+                    .is_some_and(|(received, _)| now.duration_since(*received) > span)
+                {
+                    self.values.pop_front();
+                }
+            }
+        }
+
+        self.values
+            .iter()
+            .fold(self.init.clone(), |aggregate, (_, value)| {
+                (self.fold)(aggregate, value)
+            })
+    }
+
+    /// Polls the receiver for the next value, registering the waker of `cx`
+    /// if there is none yet. Resolves to the aggregate of the window including
+    /// it, or to [`None`] once the receiver has been closed.
+    pub fn poll_next(&mut self, cx: &mut Context<'_>) -> Poll<Option<A>> {
+        let Some(source) = &mut self.source else {
+            return Poll::Ready(None);
+        };
+
+        match source.poll_next(cx) {
+            Poll::Ready(Some(value)) => Poll::Ready(Some(self.slide(value))),
+            Poll::Ready(None) => {
+                self.source = None;
+                Poll::Ready(None)
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
+    /// Receives the next value, waiting asynchronously until there is one, and
+    /// returns the aggregate of the window including it. Resolves to [`None`]
+    /// once the receiver has been closed.
+    ///
+    /// # Examples
+    #[cfg_attr(feature = "mpsc", doc = "```rust")]
+    #[cfg_attr(not(feature = "mpsc"), doc = "```ignore")]
+    /// # tokio::runtime::Builder::new_current_thread()
+    /// #     .build()
+    /// #     .unwrap()
+    /// #     .block_on(async {
+    /// use laika::{mpsc, window::Window};
+    ///
+    /// let (tx, rx) = mpsc::unbounded();
+    /// let mut peaks = Window::counted(rx, 2, 0, |peak: u32, value| peak.max(*value));
+    ///
+    /// tokio::spawn(async move {
+    ///     for latency in [12, 40, 7, 9] {
+    ///         tx.send(latency).unwrap();
+    ///     }
+    /// });
+    ///
+    /// let mut received = Vec::new();
+    ///
+    /// while let Some(peak) = peaks.next().await {
+    ///     received.push(peak);
+    /// }
+    ///
+    /// assert_eq!(received, [12, 40, 40, 9]);
+    /// # });
+    /// ```
+    pub async fn next(&mut self) -> Option<A> {
+        core::future::poll_fn(|cx| self.poll_next(cx)).await
+    }
+
+    /// Receives the next value, blocking the current thread until there is
+    /// one. See [`Window::next`].
+    pub fn next_blocking(&mut self) -> Option<A> {
+        park::block_on(|cx| self.poll_next(cx))
+    }
+}
+
+impl<T, A> Source<A> for Window<T, A>
+where
+    A: Clone,
+{
+    fn poll_next(&mut self, cx: &mut Context<'_>) -> Poll<Option<A>> {
+        Window::poll_next(self, cx)
+    }
+}
+
+impl<T, A> fmt::Debug for Window<T, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Window")
+            .field("closed", &self.source.is_none())
+            .field("span", &self.span)
+            .field("len", &self.values.len())
+            .finish()
+    }
+}
+
+/// Implement [`futures_core::Stream`] for [`Window`], yielding an aggregate
+/// for every value until the receiver has been closed.
+#[cfg(feature = "futures")]
+impl<T, A> futures_core::Stream for Window<T, A>
+where
+    A: Clone,
+{
+    type Item = A;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<A>> {
+        self.get_mut().poll_next(cx)
+    }
+}
+
+#[cfg(all(test, feature = "mpsc"))]
+mod test {
+    use super::*;
+    use crate::mpsc;
+    use core::task::Waker;
+    use std::{thread, vec::Vec};
+
+    #[test]
+    fn test_counted() {
+        let (tx, rx) = mpsc::unbounded();
+        let mut window = Window::counted(rx, 2, Vec::new(), |mut values, value| {
+            values.push(*value);
+            values
+        });
+        let mut cx = Context::from_waker(Waker::noop());
+
+        assert_eq!(window.poll_next(&mut cx), Poll::Pending);
+
+        for value in 0..3 {
+            tx.send(value).unwrap();
+        }
+
+        // Folded oldest first
+        assert_eq!(window.poll_next(&mut cx), Poll::Ready(Some(Vec::from([0]))));
+        assert_eq!(
+            window.poll_next(&mut cx),
+            Poll::Ready(Some(Vec::from([0, 1])))
+        );
+        assert_eq!(
+            window.poll_next(&mut cx),
+            Poll::Ready(Some(Vec::from([1, 2])))
+        );
+        assert_eq!(window.len(), 2);
+
+        drop(tx);
+        assert_eq!(window.poll_next(&mut cx), Poll::Ready(None));
+        assert_eq!(window.poll_next(&mut cx), Poll::Ready(None));
+    }
+
+    #[test]
+    fn test_timed() {
+        let (tx, rx) = mpsc::unbounded();
+        let mut window = Window::timed(rx, Duration::from_millis(100), 0, |sum, value| sum + value);
+
+        let producer = thread::spawn(move || {
+            tx.send(1).unwrap();
+            tx.send(2).unwrap();
+            thread::sleep(Duration::from_millis(200));
+            tx.send(3).unwrap();
+        });
+
+        assert_eq!(window.next_blocking(), Some(1));
+        assert_eq!(window.next_blocking(), Some(3));
+        assert_eq!(window.next_blocking(), Some(3));
+        assert_eq!(window.len(), 1);
+        assert_eq!(window.next_blocking(), None);
+
+        producer.join().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_next() {
+        let (tx, rx) = mpsc::bounded(1);
+        let mut window = Window::counted(rx, 4, 0.0, |sum, value: &f64| sum + value / 4.0);
+
+        tokio::spawn(async move {
+            for value in [4.0, 4.0, 4.0, 4.0, 8.0] {
+                tx.send(value).await.unwrap();
+            }
+        });
+
+        let mut averages = Vec::new();
+
+        while let Some(average) = window.next().await {
+            averages.push(average);
+        }
+
+        assert_eq!(averages, [1.0, 2.0, 3.0, 4.0, 5.0]);
+    }
+}